@@ -9,6 +9,10 @@
 # $6 -> if we are cross building, detected via meson .is_cross_build()
 # $7 -> Whether we should enable gtk3_22 support, enabled automatically
 # if we have gtk+-3.0 >= 3.22
+# $11 -> Whether we should build the terminal panel, enabled automatically
+# if we have vte-2.91
+# $12 -> Whether we should build spell checking, enabled automatically
+# if we have enchant-2
 
 # These variables are used by gxi itself in src/globals.rs
 # to decide where to look for certain system components
@@ -25,6 +29,8 @@ echo "\tGXI Plugindir: ${GREEN}${GXI_PLUGIN_DIR}${NO_COLOR}
 \tGXI Version:         ${GREEN}${GXI_VERSION}${NO_COLOR}
 \tCrossbuild:          ${GREEN}${6}${NO_COLOR}
 \tDetected GTK+3.22:   ${GREEN}${7}${NO_COLOR}
+\tDetected VTE:         ${GREEN}${11}${NO_COLOR}
+\tDetected Enchant:     ${GREEN}${12}${NO_COLOR}
 "
 
 cd "$1"
@@ -34,8 +40,15 @@ else
     path="${4}/release/${5}"
 fi
 
+features=""
 if [ "$7" = "true" ]; then
-    features="--features gtk_v3_22"
+    features="gtk_v3_22"
+fi
+if [ "${11}" = "true" ]; then
+    features="${features} terminal"
+fi
+if [ "${12}" = "true" ]; then
+    features="${features} spell_check"
 fi
 
-cargo build --target-dir "${4}" --release ${features} && cp "${path}" "${2}/${3}"
\ No newline at end of file
+cargo build --target-dir "${4}" --release --features "${features}" && cp "${path}" "${2}/${3}"
\ No newline at end of file
@@ -2,7 +2,7 @@
 
 write_index() {
 	cat > target/doc/index.html <<EOF
-	<a href="editview/index.html">EditView docs</a>
+	<a href="gxi_editview/index.html">gxi-editview docs</a>
 	<br>
 	<a href="gxi/index.html">gxi docs</a>
 	<br>
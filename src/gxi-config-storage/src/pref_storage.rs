@@ -1,6 +1,8 @@
 use crate::errors::Error;
-use gio::{Settings, SettingsExt, SettingsSchemaSource};
+use gio::{Settings, SettingsBackend, SettingsExt, SettingsSchemaSource};
+use glib::translate::{from_glib_full, ToGlibPtr};
 use glib::Variant;
+use std::path::Path;
 
 /// Trait to use `GSchema::get_key` and `GSchema::set_key` for any supported type.
 pub trait GSchemaExt<RHS = Self> {
@@ -46,6 +48,34 @@ impl GSchema {
         }
     }
 
+    /// Get a new GSchema object whose keys are stored in the keyfile at `path` instead of
+    /// dconf, e.g. to keep separate sets of settings. The keys are kept in a group named after
+    /// the schema, keys which aren't in the keyfile have their default value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it can't find the GSchema, just like `GSchema::new`.
+    pub fn new_with_keyfile(schema_name: &str, path: &Path) -> Self {
+        let root_path = SettingsSchemaSource::get_default()
+            .and_then(|settings_source| settings_source.lookup(schema_name, true))
+            .and_then(|schema| schema.get_path())
+            .map_or_else(|| "/".to_string(), |path| path.to_string());
+        // SAFETY: The function isn't bound by gio. The strings we pass are NUL-terminated copies
+        // which live until the end of this statement, and it copies them before returning. It
+        // returns a new, non-null backend we own (transfer full), so `from_glib_full` takes over
+        // that reference instead of adding one.
+        let backend: SettingsBackend = unsafe {
+            from_glib_full(gio_sys::g_keyfile_settings_backend_new(
+                path.to_glib_none().0,
+                root_path.to_glib_none().0,
+                schema_name.to_glib_none().0,
+            ))
+        };
+        Self {
+            settings: Settings::new_with_backend(schema_name, &backend),
+        }
+    }
+
     /// The panic-safe version of `GSchema::new`. Instead of simply requiring the
     /// requested GSchema to be present it first looks up if it exists and then
     /// creates it. Do note that you have to use `Gschema::try_get_key` and
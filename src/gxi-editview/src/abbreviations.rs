@@ -0,0 +1,82 @@
+//! Abbreviations the user has defined, like `teh` for `the`, which are expanded when typing a
+//! space or pressing Enter after them. Unlike snippets they aren't triggered by Tab, so they also
+//! fix typos without the user noticing.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Abbreviations {
+    /// Expansions of abbreviations which apply to all languages
+    global: HashMap<String, String>,
+    /// Expansions of abbreviations which only apply to one language, by language
+    languages: HashMap<String, HashMap<String, String>>,
+}
+
+impl Abbreviations {
+    /// Parses the `[language:]abbreviation=expansion` entries of the 'abbreviations' key. `\n`
+    /// and `\t` in the expansion are replaced by a newline and a tab.
+    pub fn parse<'a, I>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut abbreviations = Self::default();
+        for entry in entries {
+            let mut split = entry.splitn(2, '=');
+            let (key, expansion) = match (split.next(), split.next()) {
+                (Some(key), Some(expansion)) => (key, expansion),
+                _ => continue,
+            };
+            let (language, abbreviation) = match key.rfind(':') {
+                Some(ix) => (Some(key[..ix].trim()), key[ix + 1..].trim()),
+                None => (None, key.trim()),
+            };
+            if abbreviation.is_empty() {
+                continue;
+            }
+            let expansion = expansion.replace("\\n", "\n").replace("\\t", "\t");
+            let map = match language {
+                Some(language) => abbreviations
+                    .languages
+                    .entry(language.to_string())
+                    .or_insert_with(HashMap::new),
+                None => &mut abbreviations.global,
+            };
+            map.insert(abbreviation.to_string(), expansion);
+        }
+        abbreviations
+    }
+
+    /// Returns what `word` expands to in documents of the language `language`. Abbreviations of
+    /// the language take precedence over the ones of all languages.
+    pub fn expansion(&self, language: &str, word: &str) -> Option<&str> {
+        self.languages
+            .get(language)
+            .and_then(|map| map.get(word))
+            .or_else(|| self.global.get(word))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expand() {
+        let abbreviations = Abbreviations::parse(vec![
+            "teh=the",
+            "Rust:fnres=fn name() -> Result<()> {\\n}",
+            "Rust:teh=teh",
+            "invalid",
+            "=nothing",
+        ]);
+        assert_eq!(abbreviations.expansion("Markdown", "teh"), Some("the"));
+        assert_eq!(abbreviations.expansion("Rust", "teh"), Some("teh"));
+        assert_eq!(
+            abbreviations.expansion("Rust", "fnres"),
+            Some("fn name() -> Result<()> {\n}")
+        );
+        assert_eq!(abbreviations.expansion("Markdown", "fnres"), None);
+        assert_eq!(abbreviations.expansion("Rust", ""), None);
+    }
+}
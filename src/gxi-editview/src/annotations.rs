@@ -0,0 +1,119 @@
+use crate::line_items::LineItems;
+use serde_json::Value;
+use syntect::highlighting::Color;
+
+/// What an annotation shows
+#[derive(Clone)]
+pub enum AnnotationContent {
+    /// Text drawn after the end of the line ("phantom" text), which isn't part of the document.
+    /// Without a color it's drawn like invisible characters.
+    Text { text: String, color: Option<Color> },
+    /// A widget shown over the text, with its top left corner at the anchor
+    Widget(gtk::Widget),
+}
+
+/// Something shown in the text area that isn't part of the text, e.g. a blame annotation, the
+/// result of an evaluation or the hint of a diagnostic. It's anchored to a position in the
+/// document and follows it when lines are inserted or removed above it.
+#[derive(Clone)]
+pub struct Annotation {
+    /// What added the annotation, e.g. `"blame"`, so it can remove all of its annotations at once
+    pub source: String,
+    /// Byte index in the line the annotation is anchored to. Text annotations are always shown
+    /// at the end of the line.
+    pub index: usize,
+    pub content: AnnotationContent,
+}
+
+/// Identifies an annotation, to remove it again
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AnnotationId(u64);
+
+/// The annotations of an EditView
+#[derive(Default)]
+pub struct Annotations {
+    annotations: LineItems<Annotation>,
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, line: u64, annotation: Annotation) -> AnnotationId {
+        AnnotationId(self.annotations.add(line, annotation))
+    }
+
+    /// Removes the annotation `id` and returns it, unless it's gone already
+    pub fn remove(&mut self, id: AnnotationId) -> Option<Annotation> {
+        self.annotations.remove(id.0)
+    }
+
+    /// Removes all annotations added by `source` and returns them
+    pub fn clear(&mut self, source: &str) -> Vec<Annotation> {
+        self.annotations
+            .remove_where(|annotation| annotation.source == source)
+    }
+
+    /// The line annotation `id` is on now
+    pub fn line(&self, id: AnnotationId) -> Option<u64> {
+        self.annotations.line(id.0)
+    }
+
+    /// The texts to show after the end of `line` and their colors
+    pub fn texts_at(&self, line: u64) -> Vec<(&str, Option<Color>)> {
+        self.annotations
+            .at(line)
+            .filter_map(|annotation| match &annotation.content {
+                AnnotationContent::Text { text, color } => Some((text.as_str(), *color)),
+                AnnotationContent::Widget(_) => None,
+            })
+            .collect()
+    }
+
+    /// The widgets and the lines and indices they're anchored to
+    pub fn widgets(&self) -> impl Iterator<Item = (u64, usize, &gtk::Widget)> {
+        self.annotations
+            .iter()
+            .filter_map(|(line, annotation)| match &annotation.content {
+                AnnotationContent::Widget(widget) => Some((line, annotation.index, widget)),
+                AnnotationContent::Text { .. } => None,
+            })
+    }
+
+    /// Moves the annotations along with their lines according to an `update` xi-editor sent us.
+    /// Annotations of removed lines are removed too and returned, so their widgets can be
+    /// destroyed.
+    pub fn apply_update(&mut self, update: &Value, height: u64) -> Vec<Annotation> {
+        self.annotations.apply_update(update, height)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(source: &str, text: &str) -> Annotation {
+        Annotation {
+            source: source.to_string(),
+            index: 0,
+            content: AnnotationContent::Text {
+                text: text.to_string(),
+                color: None,
+            },
+        }
+    }
+
+    #[test]
+    fn texts_by_line() {
+        let mut annotations = Annotations::new();
+        annotations.add(2, text("blame", "me, yesterday"));
+        annotations.add(2, text("eval", "= 42"));
+        annotations.add(3, text("eval", "= 43"));
+        let texts: Vec<&str> = annotations.texts_at(2).into_iter().map(|t| t.0).collect();
+        assert_eq!(texts, vec!["me, yesterday", "= 42"]);
+        assert_eq!(annotations.clear("eval").len(), 2);
+        assert_eq!(annotations.texts_at(2).len(), 1);
+        assert!(annotations.texts_at(3).is_empty());
+    }
+}
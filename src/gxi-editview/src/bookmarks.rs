@@ -0,0 +1,107 @@
+use crate::line_map::LineMap;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// The lines the user has bookmarked in an EditView
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    lines: BTreeSet<u64>,
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn contains(&self, line: u64) -> bool {
+        self.lines.contains(&line)
+    }
+
+    /// Bookmarks `line` or removes its bookmark. Returns whether the line is bookmarked now.
+    pub fn toggle(&mut self, line: u64) -> bool {
+        if self.lines.remove(&line) {
+            false
+        } else {
+            self.lines.insert(line);
+            true
+        }
+    }
+
+    /// Returns the first bookmark after `line`, wrapping around at the end of the document
+    pub fn next(&self, line: u64) -> Option<u64> {
+        self.lines
+            .range(line + 1..)
+            .next()
+            .or_else(|| self.lines.iter().next())
+            .cloned()
+    }
+
+    /// Returns the last bookmark before `line`, wrapping around at the start of the document
+    pub fn previous(&self, line: u64) -> Option<u64> {
+        self.lines
+            .range(..line)
+            .next_back()
+            .or_else(|| self.lines.iter().next_back())
+            .cloned()
+    }
+
+    /// Moves the bookmarks along with their lines according to an `update` xi-editor sent us.
+    /// Bookmarks of removed lines are removed too.
+    pub fn apply_update(&mut self, update: &Value, height: u64) {
+        if self.lines.is_empty() {
+            return;
+        }
+        if let Some(map) = LineMap::from_update(update) {
+            self.lines = self
+                .lines
+                .iter()
+                .filter_map(|line| map.map(*line))
+                .filter(|line| *line < height)
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn navigate() {
+        let mut bookmarks = Bookmarks::new();
+        assert_eq!(bookmarks.next(0), None);
+        assert!(bookmarks.toggle(3));
+        assert!(bookmarks.toggle(7));
+        assert_eq!(bookmarks.next(3), Some(7));
+        assert_eq!(bookmarks.next(7), Some(3));
+        assert_eq!(bookmarks.previous(5), Some(3));
+        assert_eq!(bookmarks.previous(2), Some(7));
+        assert!(!bookmarks.toggle(3));
+        assert_eq!(bookmarks.next(0), Some(7));
+    }
+
+    #[test]
+    fn update_moves_bookmarks() {
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.toggle(1);
+        bookmarks.toggle(4);
+        // A line is inserted at the start and line 1 is removed
+        bookmarks.apply_update(
+            &json!({"ops": [
+                {"op": "ins", "n": 1},
+                {"op": "copy", "n": 1},
+                {"op": "skip", "n": 1},
+                {"op": "copy", "n": 5},
+            ]}),
+            7,
+        );
+        assert!(!bookmarks.contains(1));
+        assert!(bookmarks.contains(4));
+        assert_eq!(bookmarks.next(0), Some(4));
+    }
+}
@@ -0,0 +1,149 @@
+//! Finds the bracket matching the one at the cursor. Only the text is looked at, so brackets in
+//! strings and comments are matched, too.
+
+/// The result of looking for a matching bracket
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BracketMatch {
+    /// The matching bracket is at this line and (byte) column
+    Found(u64, usize),
+    /// The search went through this line, but we don't have its text
+    Missing(u64),
+    /// There's no bracket at the cursor or it isn't matched
+    NotFound,
+}
+
+/// The bracket matching `c` and whether it comes after `c`
+fn counterpart(c: char) -> Option<(char, bool)> {
+    match c {
+        '(' => Some((')', true)),
+        '[' => Some((']', true)),
+        '{' => Some(('}', true)),
+        ')' => Some(('(', false)),
+        ']' => Some(('[', false)),
+        '}' => Some(('{', false)),
+        _ => None,
+    }
+}
+
+/// Looks for the bracket matching the one right after `col` of `line` or, if that isn't a
+/// bracket, the one right before it. `get_line` returns the text of a line or None if it isn't
+/// known (yet).
+pub fn matching_bracket<'a, F>(get_line: F, line: u64, col: usize, height: u64) -> BracketMatch
+where
+    F: Fn(u64) -> Option<&'a str>,
+{
+    let text = match get_line(line) {
+        Some(text) => text,
+        None => return BracketMatch::Missing(line),
+    };
+    if col > text.len() || !text.is_char_boundary(col) {
+        return BracketMatch::NotFound;
+    }
+
+    let after = text[col..].chars().next().map(|c| (col, c));
+    let before = text[..col]
+        .chars()
+        .next_back()
+        .map(|c| (col - c.len_utf8(), c));
+    let bracket = after
+        .and_then(|(col, c)| counterpart(c).map(|other| (col, c, other)))
+        .or_else(|| before.and_then(|(col, c)| counterpart(c).map(|other| (col, c, other))));
+    let (start_col, bracket, (other, forward)) = match bracket {
+        Some(bracket) => bracket,
+        None => return BracketMatch::NotFound,
+    };
+
+    let mut depth = 0;
+    let mut visit = |c: char| {
+        if c == bracket {
+            depth += 1;
+        } else if c == other {
+            depth -= 1;
+        }
+        depth == 0
+    };
+
+    if forward {
+        for (ix, c) in text[start_col..].char_indices() {
+            if visit(c) {
+                return BracketMatch::Found(line, start_col + ix);
+            }
+        }
+        for line in line + 1..height {
+            let text = match get_line(line) {
+                Some(text) => text,
+                None => return BracketMatch::Missing(line),
+            };
+            for (ix, c) in text.char_indices() {
+                if visit(c) {
+                    return BracketMatch::Found(line, ix);
+                }
+            }
+        }
+    } else {
+        for (ix, c) in text[..=start_col].char_indices().rev() {
+            if visit(c) {
+                return BracketMatch::Found(line, ix);
+            }
+        }
+        for line in (0..line).rev() {
+            let text = match get_line(line) {
+                Some(text) => text,
+                None => return BracketMatch::Missing(line),
+            };
+            for (ix, c) in text.char_indices().rev() {
+                if visit(c) {
+                    return BracketMatch::Found(line, ix);
+                }
+            }
+        }
+    }
+
+    BracketMatch::NotFound
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches() {
+        let lines = ["fn a() {\n", "    b[(1)]\n", "}\n"];
+        let get_line = |n: u64| lines.get(n as usize).cloned();
+
+        assert_eq!(
+            matching_bracket(get_line, 0, 7, 3),
+            BracketMatch::Found(2, 0)
+        );
+        assert_eq!(
+            matching_bracket(get_line, 2, 0, 3),
+            BracketMatch::Found(0, 7)
+        );
+        // The bracket before the cursor is used if there's none after it
+        assert_eq!(
+            matching_bracket(get_line, 2, 1, 3),
+            BracketMatch::Found(0, 7)
+        );
+        assert_eq!(
+            matching_bracket(get_line, 1, 5, 3),
+            BracketMatch::Found(1, 9)
+        );
+        assert_eq!(
+            matching_bracket(get_line, 1, 8, 3),
+            BracketMatch::Found(1, 6)
+        );
+        assert_eq!(matching_bracket(get_line, 0, 1, 3), BracketMatch::NotFound);
+
+        let get_line = |n: u64| {
+            if n == 1 {
+                None
+            } else {
+                lines.get(n as usize).cloned()
+            }
+        };
+        assert_eq!(
+            matching_bracket(get_line, 0, 7, 3),
+            BracketMatch::Missing(1)
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Remembers where the caret jumped from within a document, e.g. when going to a line or the next
+//! match, so the user can walk back (Alt+-) and forth (Alt+=) between those positions.
+
+/// Line and (byte) column
+type Position = (u64, usize);
+
+/// The caret has to move by more than this many lines for its old position to be remembered
+const MIN_JUMP_LINES: u64 = 10;
+/// How many positions are remembered
+const MAX_POSITIONS: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct CaretHistory {
+    back: Vec<Position>,
+    forward: Vec<Position>,
+    current: Option<Position>,
+    /// Whether the caret is being moved by `back` or `forward`, which mustn't be recorded
+    navigating: bool,
+}
+
+impl CaretHistory {
+    /// Tells the history that the caret has moved to `pos`
+    pub fn moved(&mut self, pos: Position) {
+        if self.navigating {
+            self.navigating = false;
+        } else if let Some(current) = self.current {
+            let distance = if current.0 > pos.0 {
+                current.0 - pos.0
+            } else {
+                pos.0 - current.0
+            };
+            if distance > MIN_JUMP_LINES {
+                self.back.push(current);
+                if self.back.len() > MAX_POSITIONS {
+                    self.back.remove(0);
+                }
+                self.forward.clear();
+            }
+        }
+        self.current = Some(pos);
+    }
+
+    /// Returns the position the caret jumped from last, to move it back there
+    pub fn back(&mut self) -> Option<Position> {
+        let pos = self.back.pop()?;
+        if let Some(current) = self.current {
+            self.forward.push(current);
+        }
+        self.go_to(pos)
+    }
+
+    /// Returns the position `back` has been called from last, to move the caret there again
+    pub fn forward(&mut self) -> Option<Position> {
+        let pos = self.forward.pop()?;
+        if let Some(current) = self.current {
+            self.back.push(current);
+        }
+        self.go_to(pos)
+    }
+
+    fn go_to(&mut self, pos: Position) -> Option<Position> {
+        // xi-editor doesn't tell us about the caret moving if it's there already
+        self.navigating = self.current != Some(pos);
+        self.current = Some(pos);
+        Some(pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn history() {
+        let mut history = CaretHistory::default();
+        history.moved((0, 0));
+        history.moved((5, 0));
+        history.moved((50, 3));
+        history.moved((51, 0));
+        history.moved((200, 0));
+        assert_eq!(history.back(), Some((51, 0)));
+        history.moved((51, 0));
+        assert_eq!(history.back(), Some((5, 0)));
+        history.moved((5, 0));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), Some((51, 0)));
+        history.moved((51, 0));
+
+        // Jumping somewhere else drops the positions we could go forward to
+        history.moved((120, 0));
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back(), Some((51, 0)));
+    }
+}
@@ -0,0 +1,234 @@
+//! Finds color literals like `#fa0`, `#ffaa00` or `rgba(255, 170, 0, 0.5)` in lines, so a chip
+//! showing the color can be drawn under them. Clicking the chip lets the user pick another color,
+//! which replaces the literal.
+
+use gettextrs::gettext;
+use gtk::*;
+use gxi_peer::Core;
+
+/// A color literal in a line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorLiteral {
+    /// Byte index of the start of the literal
+    pub start: usize,
+    /// Byte index of the end of the literal
+    pub end: usize,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// Between 0 and 1
+    pub alpha: f64,
+    /// Whether the literal is written in hex notation
+    pub hex: bool,
+}
+
+impl ColorLiteral {
+    /// Writes the color `red`, `green`, `blue`, `alpha` the way this literal is written. Hex
+    /// literals become `rgba()` ones if the color is translucent.
+    pub fn format(&self, red: u8, green: u8, blue: u8, alpha: f64) -> String {
+        if alpha >= 1.0 {
+            if self.hex {
+                format!("#{:02x}{:02x}{:02x}", red, green, blue)
+            } else {
+                format!("rgb({}, {}, {})", red, green, blue)
+            }
+        } else {
+            format!(
+                "rgba({}, {}, {}, {})",
+                red,
+                green,
+                blue,
+                format_alpha(alpha)
+            )
+        }
+    }
+
+    pub fn rgba(&self) -> gdk::RGBA {
+        gdk::RGBA {
+            red: f64::from(self.red) / 255.0,
+            green: f64::from(self.green) / 255.0,
+            blue: f64::from(self.blue) / 255.0,
+            alpha: self.alpha,
+        }
+    }
+}
+
+/// Writes `alpha` with at most two decimals and without trailing zeros, e.g. `0.5`
+fn format_alpha(alpha: f64) -> String {
+    let alpha = format!("{:.2}", alpha.max(0.0));
+    alpha
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Parses a `#RGB` or `#RRGGBB` literal at byte `start` of `text`
+fn parse_hex(text: &str, start: usize) -> Option<ColorLiteral> {
+    let digits = text[start + 1..]
+        .chars()
+        .take_while(char::is_ascii_hexdigit)
+        .count();
+    let end = start + 1 + digits;
+    if text[end..].chars().next().map_or(false, is_word_char) {
+        return None;
+    }
+    let value = |from: usize, len: usize| {
+        let value = u8::from_str_radix(&text[from..from + len], 16).unwrap_or(0);
+        if len == 1 {
+            value * 17
+        } else {
+            value
+        }
+    };
+    let (red, green, blue) = match digits {
+        3 => (
+            value(start + 1, 1),
+            value(start + 2, 1),
+            value(start + 3, 1),
+        ),
+        6 => (
+            value(start + 1, 2),
+            value(start + 3, 2),
+            value(start + 5, 2),
+        ),
+        _ => return None,
+    };
+    Some(ColorLiteral {
+        start,
+        end,
+        red,
+        green,
+        blue,
+        alpha: 1.0,
+        hex: true,
+    })
+}
+
+/// Parses a `rgb(r, g, b)` or `rgba(r, g, b, a)` literal at byte `start` of `text`
+fn parse_rgb(text: &str, start: usize) -> Option<ColorLiteral> {
+    let rest = &text[start..];
+    let args_start = if rest.starts_with("rgba(") {
+        5
+    } else if rest.starts_with("rgb(") {
+        4
+    } else {
+        return None;
+    };
+    let args_len = rest[args_start..].find(')')?;
+    let args: Vec<&str> = rest[args_start..args_start + args_len]
+        .split(',')
+        .map(str::trim)
+        .collect();
+    if args.len() != 3 && args.len() != 4 {
+        return None;
+    }
+    let alpha = match args.get(3) {
+        Some(alpha) => alpha
+            .parse::<f64>()
+            .ok()
+            .filter(|a| *a >= 0.0 && *a <= 1.0)?,
+        None => 1.0,
+    };
+    Some(ColorLiteral {
+        start,
+        end: start + args_start + args_len + 1,
+        red: args[0].parse().ok()?,
+        green: args[1].parse().ok()?,
+        blue: args[2].parse().ok()?,
+        alpha,
+        hex: false,
+    })
+}
+
+/// Returns the color literals in `text`
+pub fn find_colors(text: &str) -> Vec<ColorLiteral> {
+    let mut colors = Vec::new();
+    let mut ix = 0;
+    while let Some(c) = text[ix..].chars().next() {
+        let after_word = text[..ix].chars().next_back().map_or(false, is_word_char);
+        let color = match c {
+            '#' if !after_word => parse_hex(text, ix),
+            'r' if !after_word => parse_rgb(text, ix),
+            _ => None,
+        };
+        match color {
+            Some(color) => {
+                ix = color.end;
+                colors.push(color);
+            }
+            None => ix += c.len_utf8(),
+        }
+    }
+    colors
+}
+
+/// Lets the user pick a new color for `color` in `line` and replaces it with the picked one
+pub fn choose_color(
+    core: &Core,
+    view_id: &str,
+    parent: Option<&Window>,
+    line: u64,
+    color: ColorLiteral,
+) {
+    let dialog = ColorChooserDialog::new(Some(gettext("Choose Color").as_str()), parent);
+    dialog.set_use_alpha(true);
+    dialog.set_rgba(&color.rgba());
+    let ret = dialog.run();
+    let rgba = dialog.get_rgba();
+    dialog.destroy();
+
+    if ret == ResponseType::Ok {
+        let channel = |value: f64| (value * 255.0).round() as u8;
+        let literal = color.format(
+            channel(rgba.red),
+            channel(rgba.green),
+            channel(rgba.blue),
+            rgba.alpha,
+        );
+        core.gesture_point_select(view_id, line, color.start as u64);
+        core.gesture_range_select(view_id, line, color.end as u64);
+        core.insert(view_id, &literal);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find() {
+        let colors = find_colors("color: #fa0; background: rgba(0, 10, 255, 0.5);");
+        assert_eq!(colors.len(), 2);
+        assert_eq!((colors[0].start, colors[0].end), (7, 11));
+        assert_eq!(
+            (colors[0].red, colors[0].green, colors[0].blue),
+            (255, 170, 0)
+        );
+        assert_eq!((colors[1].start, colors[1].end), (25, 46));
+        assert_eq!(
+            (colors[1].red, colors[1].blue, colors[1].alpha),
+            (0, 255, 0.5)
+        );
+
+        assert_eq!(find_colors("#ffaa00")[0].green, 170);
+        assert!(find_colors("#ffaa0").is_empty());
+        assert!(find_colors("issue #123g").is_empty());
+        assert!(find_colors("a#fff argb(1, 2, 3)").is_empty());
+        assert!(find_colors("rgb(1, 2)").is_empty());
+        assert!(find_colors("rgb(256, 0, 0)").is_empty());
+    }
+
+    #[test]
+    fn format() {
+        let hex = find_colors("#fa0")[0];
+        assert_eq!(hex.format(0, 128, 255, 1.0), "#0080ff");
+        assert_eq!(hex.format(0, 128, 255, 0.5), "rgba(0, 128, 255, 0.5)");
+        let rgb = find_colors("rgb(1, 2, 3)")[0];
+        assert_eq!(rgb.format(4, 5, 6, 1.0), "rgb(4, 5, 6)");
+        assert_eq!(rgb.format(4, 5, 6, 0.0), "rgba(4, 5, 6, 0)");
+    }
+}
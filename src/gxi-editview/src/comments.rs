@@ -0,0 +1,172 @@
+/// How comments look like in a language
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommentTokens {
+    /// Comments which go until the end of the line, e.g. `//`
+    Line(&'static str),
+    /// Comments which have to be closed, e.g. `<!--` and `-->`. Every line is commented on its own.
+    Block(&'static str, &'static str),
+}
+
+/// Returns the comment tokens of `language`, as xi-editor (or rather syntect) calls it
+pub fn comment_tokens(language: &str) -> Option<CommentTokens> {
+    use self::CommentTokens::*;
+
+    let tokens = match language {
+        "Rust" | "C" | "C++" | "C#" | "Objective-C" | "Objective-C++" | "Go" | "Java"
+        | "JavaScript" | "TypeScript" | "Scala" | "D" | "Groovy" | "Swift" | "Kotlin" | "Dart"
+        | "ActionScript" => Line("//"),
+        "Python"
+        | "Ruby"
+        | "Perl"
+        | "Shell-Unix-Generic"
+        | "Bourne Again Shell (bash)"
+        | "Makefile"
+        | "YAML"
+        | "TOML"
+        | "R"
+        | "Tcl"
+        | "CMake"
+        | "Dockerfile"
+        | "Nim"
+        | "Julia"
+        | "Elixir"
+        | "fish"
+        | "Git Ignore"
+        | "Git Config" => Line("#"),
+        "Lua" | "SQL" | "Haskell" | "Literate Haskell" | "Ada" | "Elm" => Line("--"),
+        "LaTeX" | "TeX" | "Erlang" | "MATLAB" | "BibTeX" => Line("%"),
+        "Lisp" | "Clojure" | "Scheme" | "Racket" | "Assembly x86 (NASM)" => Line(";"),
+        "Batch File" => Line("REM"),
+        "VimL" => Line("\""),
+        "HTML" | "XML" | "Markdown" | "MultiMarkdown" => Block("<!--", "-->"),
+        "CSS" | "SCSS" => Block("/*", "*/"),
+        "OCaml" => Block("(*", "*)"),
+        _ => return None,
+    };
+    Some(tokens)
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Returns the commented line without indentation, if `line` is commented
+fn uncomment(line: &str, tokens: CommentTokens) -> Option<String> {
+    let text = line.trim();
+    let uncommented = match tokens {
+        CommentTokens::Line(start) => text.get(start.len()..).filter(|_| text.starts_with(start)),
+        CommentTokens::Block(start, end) => {
+            if text.len() >= start.len() + end.len()
+                && text.starts_with(start)
+                && text.ends_with(end)
+            {
+                text.get(start.len()..text.len() - end.len())
+                    .map(str::trim_end)
+            } else {
+                None
+            }
+        }
+    }?;
+    // Remove the space we add when commenting
+    let uncommented = if uncommented.starts_with(' ') {
+        &uncommented[1..]
+    } else {
+        uncommented
+    };
+    Some(uncommented.to_string())
+}
+
+/// Comments `lines` or uncomments them if all of them are commented already. Blank lines are left
+/// alone. Comment tokens are inserted at the indentation of the least indented line, so
+/// commented blocks stay aligned. Returns the new lines, without newlines.
+pub fn toggle_comment(lines: &[&str], tokens: CommentTokens) -> Vec<String> {
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|l| l.trim_end_matches(|c| c == '\n' || c == '\r'))
+        .collect();
+    let is_blank = |line: &&str| line.trim().is_empty();
+
+    let uncommented: Option<Vec<String>> = lines
+        .iter()
+        .filter(|l| !is_blank(l))
+        .map(|l| uncomment(l, tokens))
+        .collect();
+    if let Some(uncommented) = uncommented.filter(|u| !u.is_empty()) {
+        let mut uncommented = uncommented.into_iter();
+        return lines
+            .iter()
+            .map(|line| {
+                if is_blank(line) {
+                    line.to_string()
+                } else {
+                    let indent = &line[..indentation(line)];
+                    format!("{}{}", indent, uncommented.next().unwrap_or_default())
+                }
+            })
+            .collect();
+    }
+
+    let indent = lines
+        .iter()
+        .filter(|l| !is_blank(l))
+        .map(|l| indentation(l))
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| {
+            if is_blank(line) {
+                return line.to_string();
+            }
+            let (indent, text) = line.split_at(indent);
+            match tokens {
+                CommentTokens::Line(start) => format!("{}{} {}", indent, start, text),
+                CommentTokens::Block(start, end) => {
+                    format!("{}{} {} {}", indent, start, text, end)
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toggle_line_comments() {
+        let tokens = comment_tokens("Rust").unwrap();
+        let lines = ["fn foo() {\n", "\n", "    bar();\n", "}\n"];
+        let commented = toggle_comment(&lines, tokens);
+        assert_eq!(
+            commented,
+            vec!["// fn foo() {", "", "//     bar();", "// }"]
+        );
+
+        let commented: Vec<&str> = commented.iter().map(String::as_str).collect();
+        assert_eq!(
+            toggle_comment(&commented, tokens),
+            vec!["fn foo() {", "", "    bar();", "}"]
+        );
+        // Lines which are only partially commented are commented once more
+        assert_eq!(
+            toggle_comment(&["    //foo", "    bar"], tokens),
+            vec!["    // //foo", "    // bar"]
+        );
+        assert_eq!(toggle_comment(&["    //foo"], tokens), vec!["    foo"]);
+    }
+
+    #[test]
+    fn toggle_block_comments() {
+        let tokens = comment_tokens("HTML").unwrap();
+        let commented = toggle_comment(&["  <p>foo</p>"], tokens);
+        assert_eq!(commented, vec!["  <!-- <p>foo</p> -->"]);
+        assert_eq!(
+            toggle_comment(&[commented[0].as_str()], tokens),
+            vec!["  <p>foo</p>"]
+        );
+        assert_eq!(comment_tokens("Plain Text"), None);
+        // JSON has no comments, commenting out a line would make it invalid
+        assert_eq!(comment_tokens("JSON"), None);
+    }
+}
@@ -0,0 +1,147 @@
+//! A popup below the cursor which offers completions of what's being typed. The EditView keeps
+//! the focus while it's shown and forwards Up, Down, Enter, Tab and Escape to it.
+
+use gtk::*;
+use std::cell::RefCell;
+
+/// Something the text in front of the cursor can be completed with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    /// What's shown in the popup
+    pub label: String,
+    /// What's inserted at the cursor
+    pub insert: String,
+}
+
+/// Where completions come from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompletionSource {
+    /// Entries of the directory of the path in front of the cursor
+    Path,
+    /// Words which are in the open documents already
+    Word,
+}
+
+pub struct CompletionPopup {
+    popover: Popover,
+    scrolled_window: ScrolledWindow,
+    list_box: ListBox,
+    completions: RefCell<Vec<Completion>>,
+}
+
+impl CompletionPopup {
+    /// Creates the (hidden) popup, which points at the cursor of `edit_area`
+    pub fn new(edit_area: &Layout) -> Self {
+        let popover = Popover::new(Some(edit_area));
+        popover.set_modal(false);
+        popover.set_position(PositionType::Bottom);
+        let list_box = ListBox::new();
+        list_box.set_selection_mode(SelectionMode::Browse);
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        #[cfg(feature = "gtk_v3_22")]
+        {
+            scrolled_window.set_propagate_natural_height(true);
+            scrolled_window.set_max_content_height(250);
+        }
+        #[cfg(not(feature = "gtk_v3_22"))]
+        scrolled_window.set_min_content_height(150);
+        scrolled_window.add(&list_box);
+        scrolled_window.show_all();
+        popover.add(&scrolled_window);
+
+        Self {
+            popover,
+            scrolled_window,
+            list_box,
+            completions: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Shows `completions` next to `cursor` (in coordinates of the edit area), or hides the popup if
+    /// there are none
+    pub fn show(&self, completions: Vec<Completion>, cursor: &gdk::Rectangle) {
+        if completions.is_empty() {
+            self.hide();
+            return;
+        }
+
+        for row in self.list_box.get_children() {
+            self.list_box.remove(&row);
+        }
+        for completion in &completions {
+            let label = Label::new(Some(completion.label.as_str()));
+            label.set_halign(Align::Start);
+            label.get_style_context().add_class("monospace");
+            let row = ListBoxRow::new();
+            row.set_can_focus(false);
+            row.add(&label);
+            row.show_all();
+            self.list_box.add(&row);
+        }
+        self.list_box
+            .select_row(self.list_box.get_row_at_index(0).as_ref());
+        self.completions.replace(completions);
+
+        self.popover.set_pointing_to(cursor);
+        if !self.popover.is_visible() {
+            #[cfg(feature = "gtk_v3_22")]
+            self.popover.popup();
+            #[cfg(not(feature = "gtk_v3_22"))]
+            self.popover.show();
+        }
+    }
+
+    pub fn hide(&self) {
+        self.popover.hide();
+        self.completions.borrow_mut().clear();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.popover.is_visible()
+    }
+
+    /// Selects the next (or previous) completion, wrapping around at the end of the list
+    pub fn select_next(&self, backwards: bool) {
+        let len = self.completions.borrow().len() as i32;
+        if len == 0 {
+            return;
+        }
+        let current = self
+            .list_box
+            .get_selected_row()
+            .map_or(0, |row| row.get_index());
+        let next = if backwards {
+            (current - 1 + len) % len
+        } else {
+            (current + 1) % len
+        };
+        if let Some(row) = self.list_box.get_row_at_index(next) {
+            self.list_box.select_row(Some(&row));
+            self.scroll_to(&row);
+        }
+    }
+
+    /// Scrolls the list so `row` is visible
+    fn scroll_to(&self, row: &ListBoxRow) {
+        let vadj = match self.scrolled_window.get_vadjustment() {
+            Some(vadj) => vadj,
+            None => return,
+        };
+        let alloc = row.get_allocation();
+        let (top, bottom) = (f64::from(alloc.y), f64::from(alloc.y + alloc.height));
+        if top < vadj.get_value() {
+            vadj.set_value(top);
+        } else if bottom > vadj.get_value() + vadj.get_page_size() {
+            vadj.set_value(bottom - vadj.get_page_size());
+        }
+    }
+
+    /// Hides the popup and returns the selected completion
+    pub fn take_selected(&self) -> Option<Completion> {
+        let index = self.list_box.get_selected_row()?.get_index();
+        let completion = self.completions.borrow().get(index as usize).cloned();
+        self.hide();
+        completion
+    }
+}
@@ -0,0 +1,131 @@
+//! Gives features which need the whole text of a document access to it. The line cache usually
+//! only holds the lines around the visible ones, so the missing lines have to be requested from
+//! xi-editor first; whoever asks for the text is called back once they've arrived.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// How often the missing lines are requested again before giving up, in case xi-editor doesn't
+/// send them or they're invalidated by edits before we get to use them
+pub const MAX_ATTEMPTS: u8 = 10;
+
+type TextCallback = Box<dyn FnOnce(&str)>;
+
+pub struct DocumentText {
+    /// The text as of the last update, so asking for it repeatedly doesn't mean joining all lines
+    /// every time
+    cached: RefCell<Option<Rc<str>>>,
+    /// Callbacks which wait for the missing lines
+    waiting: RefCell<Vec<TextCallback>>,
+    /// How often we've requested the missing lines for the current `waiting` callbacks
+    attempts: Cell<u8>,
+}
+
+impl Default for DocumentText {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DocumentText {
+    pub fn new() -> Self {
+        Self {
+            cached: RefCell::new(None),
+            waiting: RefCell::new(Vec::new()),
+            attempts: Cell::new(0),
+        }
+    }
+
+    /// Returns the cached text, or the one `build` returns if there's none cached. `build` returns
+    /// None if it doesn't have all of the text.
+    pub fn get<F>(&self, build: F) -> Option<Rc<str>>
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        let mut cached = self.cached.borrow_mut();
+        if cached.is_none() {
+            *cached = build().map(Rc::from);
+        }
+        cached.clone()
+    }
+
+    /// Drops the cached text, e.g. because the document has changed
+    pub fn invalidate(&self) {
+        self.cached.replace(None);
+    }
+
+    /// Queues `callback` until the text is available. Returns true if nobody was waiting yet, in
+    /// which case the missing lines have to be requested.
+    pub fn wait(&self, callback: TextCallback) -> bool {
+        let mut waiting = self.waiting.borrow_mut();
+        waiting.push(callback);
+        if waiting.len() == 1 {
+            self.attempts.set(1);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        !self.waiting.borrow().is_empty()
+    }
+
+    /// Counts another request for the missing lines. Returns false if we've tried often enough
+    /// already, in which case the waiting callbacks are dropped.
+    pub fn retry(&self) -> bool {
+        if self.attempts.get() >= MAX_ATTEMPTS {
+            self.waiting.borrow_mut().clear();
+            return false;
+        }
+        self.attempts.set(self.attempts.get() + 1);
+        true
+    }
+
+    /// Calls all waiting callbacks with `text`
+    pub fn serve(&self, text: &str) {
+        let waiting = self.waiting.replace(Vec::new());
+        for callback in waiting {
+            callback(text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn caching() {
+        let document = DocumentText::new();
+        assert_eq!(document.get(|| None), None);
+        assert_eq!(document.get(|| Some("a".to_string())), Some(Rc::from("a")));
+        assert_eq!(document.get(|| Some("b".to_string())), Some(Rc::from("a")));
+        document.invalidate();
+        assert_eq!(document.get(|| Some("b".to_string())), Some(Rc::from("b")));
+    }
+
+    #[test]
+    fn waiting() {
+        let document = DocumentText::new();
+        let served = Rc::new(RefCell::new(Vec::new()));
+        assert!(document.wait(Box::new(enclose!((served) move |text| {
+            served.borrow_mut().push(text.to_string())
+        }))));
+        assert!(!document.wait(Box::new(enclose!((served) move |text| {
+            served.borrow_mut().push(text.to_string())
+        }))));
+        assert!(document.is_waiting());
+
+        document.serve("text");
+        assert!(!document.is_waiting());
+        assert_eq!(*served.borrow(), vec!["text", "text"]);
+
+        document.wait(Box::new(|_| ()));
+        for _ in 1..MAX_ATTEMPTS {
+            assert!(document.retry());
+        }
+        assert!(!document.retry());
+        assert!(!document.is_waiting());
+    }
+}
@@ -0,0 +1,4847 @@
+use crate::annotations::{Annotation, AnnotationContent, AnnotationId, Annotations};
+use crate::bookmarks::Bookmarks;
+use crate::brackets::{matching_bracket, BracketMatch};
+use crate::caret_history::CaretHistory;
+use crate::colors::{self, ColorLiteral};
+use crate::comments;
+use crate::completion::{Completion, CompletionPopup, CompletionSource};
+use crate::document::DocumentText;
+use crate::folds::{fold_region, is_foldable, Folds};
+use crate::fonts::Font;
+use crate::graphemes;
+use crate::gutter_markers::{GutterMarker, GutterMarkers, MarkerId};
+use crate::html_export::{self, HtmlOptions, StyledLine};
+use crate::infobars::InfoBars;
+use crate::line_transforms::{self, LineTransform};
+use crate::links::{self, Link};
+use crate::lists;
+use crate::main_state::{MainState, Settings};
+use crate::markdown_table;
+use crate::match_counter::MatchCounter;
+use crate::occurrences::{self, Occurrences};
+use crate::paste::paste_non_text;
+use crate::path_completion;
+use crate::quick_marks::QuickMark;
+use crate::regex_replace;
+use crate::rtf_export;
+use crate::search_history::{self, HistoryCursor, REPLACE_HISTORY_KEY, SEARCH_HISTORY_KEY};
+use crate::search_ticks::SearchTicks;
+use crate::smart_punctuation;
+use crate::spell_check;
+use crate::theme::{
+    color_from_u32, set_long_line_source_color, set_margin_source_color,
+    set_misspelling_source_color, set_occurrence_source_color, set_source_color,
+    set_whitespace_source_color, PangoColor,
+};
+use crate::undo_grouping::UndoGrouping;
+use crate::view_item::*;
+use crate::vim::{self, Mode, Vim};
+use crate::whitespace::{self, WhitespaceOptions};
+use crate::word_completion;
+use crate::words;
+use cairo::Context;
+use gdk::enums::key;
+use gdk::*;
+use gettextrs::gettext;
+use gio::SettingsExt;
+use glib::{source, MainContext};
+use gtk::{self, *};
+use gxi_linecache::{Line, LineCache, StyleSpan};
+use gxi_peer::Core;
+use log::{debug, error, trace, warn};
+use pango::{self, ContextExt, LayoutExt, *};
+use pangocairo::functions::*;
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::cmp::{max, min};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+use std::u32;
+use syntect::highlighting::Color;
+
+/// Width of the fold gutter next to the line numbers in chars
+const FOLD_MARKER_COLUMNS: f64 = 2.0;
+/// How many lines around a missing one are requested when looking for a matching bracket
+const BRACKET_SEARCH_LINES: u64 = 500;
+/// Height of the chips drawn under color literals, in pixels
+const COLOR_CHIP_HEIGHT: f64 = 4.0;
+
+/// Returned by `EditView::get_text_size()` and used to adjust the scrollbars.
+pub struct TextSize {
+    /// The height of the entire document
+    height: f64,
+    /// The width of the entire document
+    width: f64,
+    /// If the height of the document is contained within the edit_area (if it's smaller)
+    contained_height: bool,
+    /// If the width of the document is contained within the edit_area (if it's smaller)
+    contained_width: bool,
+}
+
+/// The lines of a document with the byte ranges selected in each of them. Comparing two of them
+/// tells whether the text or the selection has changed in between.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectionSnapshot {
+    lines: Vec<(String, Vec<std::ops::Range<usize>>)>,
+}
+
+impl SelectionSnapshot {
+    /// The selected text, including the line endings of lines the selection continues after
+    pub fn selected_text(&self) -> String {
+        let mut selected = String::new();
+        for (text, ranges) in &self.lines {
+            for range in ranges {
+                let end = range.end.min(text.len());
+                selected.push_str(text.get(range.start.min(end)..end).unwrap_or_default());
+            }
+        }
+        selected
+    }
+
+    /// The whole text of the document
+    pub fn text(&self) -> String {
+        self.lines.iter().map(|(text, _)| text.as_str()).collect()
+    }
+}
+
+/// Which parts of the find/replace dialog are shown, used to restore it in the next session.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLayout {
+    /// If the find/replace dialog is open at all
+    pub search: bool,
+    /// If the replace entry is shown
+    pub replace: bool,
+    /// If the search options (regex, case sensitivity etc.) are shown
+    pub options: bool,
+}
+
+/// The EditView is the part of gxi that does the actual editing. This is where you edit documents.
+pub struct EditView {
+    core: Core,
+    main_state: Rc<RefCell<MainState>>,
+    pub view_id: String,
+    pub file_name: Option<String>,
+    /// The file the document is a temporary copy of, e.g. the actual file when editing via sudoedit
+    origin: Option<String>,
+    pub pristine: bool,
+    /// When the file was last modified as far as we know, i.e. when it was opened or saved
+    file_modified: Cell<Option<SystemTime>>,
+    /// Read-only views can be navigated but not edited, e.g. if another process has locked the file
+    read_only: bool,
+    pub root_widget: Grid,
+    pub top_bar: TopBar,
+    pub view_item: ViewItem,
+    line_cache: LineCache,
+    folds: Folds,
+    bookmarks: Bookmarks,
+    gutter_markers: GutterMarkers,
+    annotations: Annotations,
+    /// The whole text, for features which need more than the visible lines
+    document: DocumentText,
+    occurrences: Occurrences,
+    pub(crate) find_replace: FindReplace,
+    edit_font: Font,
+    /// Size of the edit font (in Pango units) without the zoom of the window applied
+    edit_font_size: i32,
+    interface_font: Font,
+    im_context: IMContextSimple,
+    /// Whether typed text replaces the text after the cursor instead of being inserted
+    overwrite: Cell<bool>,
+    /// The line and x position (in document coordinates) where an Alt+drag block selection started
+    block_selection_start: Cell<Option<(u64, f64)>>,
+    vim: RefCell<Vim>,
+    /// Whether lines have been dropped from the line cache to save memory
+    lines_evicted: bool,
+    /// Whether the selection should be extended to the matching bracket and the line we had to
+    /// request from xi-editor to find it
+    pending_bracket_match: Cell<Option<(bool, u64)>>,
+    /// Called with a file (and the line to go to) the user has Ctrl+clicked
+    file_opener: RefCell<Option<Rc<dyn Fn(&Path, Option<u64>)>>>,
+    /// Called with the name of a quick mark in another EditView the user wants to jump to
+    quick_mark_jumper: RefCell<Option<Rc<dyn Fn(char)>>>,
+    /// Called with a word and the file of the document to go to the definition of the word
+    definition_finder: RefCell<Option<Rc<dyn Fn(&str, Option<&str>) -> bool>>>,
+    /// Called with the lines with matches whenever the search has changed
+    search_results_handler: RefCell<Option<Rc<dyn Fn(Vec<u64>)>>>,
+    /// Called with the file name and language of the document when the file should be checked
+    /// for problems again
+    file_checker: RefCell<Option<Rc<dyn Fn(&str, &str)>>>,
+    /// Where the caret jumped from within the document
+    caret_history: RefCell<CaretHistory>,
+    /// Where we are in the search history while going through it with Up and Down
+    search_history_cursor: RefCell<HistoryCursor>,
+    /// Where we are in the replacement history while going through it with Up and Down
+    replace_history_cursor: RefCell<HistoryCursor>,
+    completion: CompletionPopup,
+    /// Which of the matches of the search is selected
+    match_counter: RefCell<MatchCounter>,
+    /// Where the completions come from while the completion popup is shown, or should be shown
+    /// once xi-editor has sent us the text the user has typed
+    completion_source: Cell<Option<CompletionSource>>,
+    /// Marks the lines with matches of the current search on the scrollbar
+    search_ticks: SearchTicks,
+    /// Where the cursor was when the search started, the search looks for the first match from
+    /// there and cancelling it moves the cursor back. `None` once the user has gone to a match.
+    search_origin: Cell<Option<(u64, usize)>>,
+    /// Notifications about the document, e.g. if it couldn't be saved
+    infobars: InfoBars,
+    /// Where the cursor was before the text of the primary selection was pasted with a middle
+    /// click, so undoing the paste moves it back there. Forgotten on the next key press or click.
+    primary_paste_origin: Rc<Cell<Option<(u64, u64)>>>,
+    /// Which of the text the user types is undone together
+    undo_grouping: RefCell<UndoGrouping>,
+}
+
+impl EditView {
+    /// Initialises a new EditView. Sets up scrollbars, the actual editing area, the fonts,
+    /// the syntax lang and connects all events which might happen during usage (e.g. scrolling)
+    pub fn new(
+        main_state: &Rc<RefCell<MainState>>,
+        core: &Core,
+        // The FindReplace dialog is relative to this
+        hamburger_button: &MenuButton,
+        file_name: Option<String>,
+        view_id: String,
+        parent: &ApplicationWindow,
+    ) -> Rc<RefCell<Self>> {
+        trace!("{}, '{}'", gettext("Creating new EditView"), view_id);
+        let view_item = ViewItem::new(&main_state.borrow());
+        let find_replace = FindReplace::new(&hamburger_button);
+        let pango_ctx = view_item.get_pango_ctx();
+        let im_context = IMContextSimple::new();
+        let interface_font = Self::get_interface_font(&main_state.borrow().settings, &pango_ctx);
+        let modified = file_name.as_ref().and_then(|f| file_modified(f));
+
+        let edit_view = Rc::new(RefCell::new(Self {
+            core: core.clone(),
+            main_state: main_state.clone(),
+            file_name,
+            origin: None,
+            pristine: true,
+            file_modified: Cell::new(modified),
+            read_only: false,
+            view_id,
+            root_widget: view_item.root_box.clone(),
+            top_bar: TopBar::new(),
+            view_item: view_item.clone(),
+            line_cache: LineCache::new(),
+            folds: Folds::new(),
+            bookmarks: Bookmarks::new(),
+            gutter_markers: GutterMarkers::new(),
+            annotations: Annotations::new(),
+            document: DocumentText::new(),
+            occurrences: Occurrences::new(),
+            edit_font: Self::get_edit_font(&pango_ctx, &main_state.borrow()),
+            edit_font_size: FontDescription::from_string(&main_state.borrow().settings.edit_font)
+                .get_size(),
+            interface_font,
+            find_replace: find_replace.clone(),
+            im_context: im_context.clone(),
+            overwrite: Cell::new(false),
+            block_selection_start: Cell::new(None),
+            vim: RefCell::new(Vim::new()),
+            lines_evicted: false,
+            pending_bracket_match: Cell::new(None),
+            file_opener: RefCell::new(None),
+            quick_mark_jumper: RefCell::new(None),
+            definition_finder: RefCell::new(None),
+            search_results_handler: RefCell::new(None),
+            file_checker: RefCell::new(None),
+            caret_history: RefCell::new(CaretHistory::default()),
+            search_history_cursor: RefCell::new(HistoryCursor::default()),
+            replace_history_cursor: RefCell::new(HistoryCursor::default()),
+            completion: CompletionPopup::new(&view_item.edit_area),
+            match_counter: RefCell::new(MatchCounter::default()),
+            completion_source: Cell::new(None),
+            search_ticks: SearchTicks::new(&view_item.ev_scrolled_window),
+            search_origin: Cell::new(None),
+            infobars: InfoBars::new(&view_item.infobar_box),
+            primary_paste_origin: Default::default(),
+            undo_grouping: Default::default(),
+        }));
+
+        edit_view.borrow_mut().update_title();
+        edit_view.borrow().update_zoom_label();
+        edit_view.borrow().update_reduced_mode_label();
+        edit_view.borrow().update_vim_mode_label();
+
+        view_item.connect_events(&edit_view);
+        find_replace.connect_events(&edit_view);
+        main_state
+            .borrow()
+            .spell_checker
+            .add_widget(&view_item.edit_area);
+        EditView::connect_im_events(&edit_view, &im_context);
+        //edit_view.borrow().connect_gschema(&gschema);
+
+        im_context.set_client_window(parent.get_window().as_ref());
+
+        edit_view
+    }
+
+    fn connect_im_events(edit_view: &Rc<RefCell<EditView>>, im_context: &IMContextSimple) {
+        im_context.connect_commit(enclose!((edit_view) move |_, text| {
+            let ev = edit_view.borrow();
+            if ev.read_only {
+                return;
+            }
+            ev.insert_typed(text);
+        }));
+    }
+
+    fn get_interface_font(settings: &Settings, pango_ctx: &pango::Context) -> Font {
+        Font::new(
+            &pango_ctx,
+            FontDescription::from_string(&settings.interface_font),
+        )
+    }
+
+    fn get_edit_font(pango_ctx: &pango::Context, main_state: &MainState) -> Font {
+        let mut font_desc = FontDescription::from_string(&main_state.settings.edit_font);
+        let size = f64::from(font_desc.get_size()) * main_state.zoom;
+        font_desc.set_size(size.round() as i32);
+        Font::new(pango_ctx, font_desc)
+    }
+}
+
+impl EditView {
+    /// Set the name of the file the EditView is currently editing and calls [update_title](struct.EditView.html#method.update_title)
+    pub fn set_file(&mut self, file_name: &str) {
+        trace!(
+            "{} 'FindReplace' {} '{}'",
+            gettext("Connecting"),
+            gettext("events for EditView"),
+            self.view_id
+        );
+        self.file_name = Some(file_name.to_string());
+        self.update_title();
+    }
+
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_ref().map(String::as_str)
+    }
+
+    /// Sets the file the document is a temporary copy of. The title shows its full path instead
+    /// of the name of the copy, so the user knows which file they're actually editing.
+    pub fn set_origin(&mut self, origin: Option<String>) {
+        self.origin = origin;
+        let tooltip = self.origin.as_ref().and(self.file_name.as_ref());
+        self.top_bar
+            .label
+            .set_tooltip_text(tooltip.map(String::as_str));
+        self.update_title();
+    }
+
+    /// The name of the document, e.g. the name of its file
+    pub fn display_name(&self) -> String {
+        match (&self.origin, &self.file_name) {
+            (Some(origin), _) => origin.clone(),
+            // Windows also accepts '/' as separator, so let Path figure out the name
+            (None, Some(f)) => Path::new(f)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| gettext("Untitled")),
+            (None, None) => gettext("Untitled"),
+        }
+    }
+
+    /// Update the title of the EditView to the currently set file_name
+    fn update_title(&self) {
+        let title = self.display_name();
+
+        let mut full_title = String::new();
+        if !self.pristine {
+            full_title.push('*');
+        }
+        full_title.push_str(&title);
+        if self.read_only {
+            full_title.push_str(&format!(" ({})", gettext("read-only")));
+        }
+
+        trace!(
+            "{} '{}': {}",
+            gettext("Setting title for EditView"),
+            self.view_id,
+            full_title
+        );
+        self.top_bar.label.set_text(&full_title);
+    }
+
+    /// The name of the current language, as xi-editor calls it
+    pub fn language(&self) -> String {
+        let lang = self
+            .view_item
+            .statusbar
+            .syntax_label
+            .get_text()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        // We show a localized 'Plain Text'
+        if lang == gettext("Plain Text") {
+            "Plain Text".to_string()
+        } else {
+            lang
+        }
+    }
+
+    pub fn tab_size(&self) -> u32 {
+        self.main_state.borrow().settings.tab_size
+    }
+
+    /// Shows `msg` above the text until the user closes it, e.g. if the document couldn't be saved
+    pub fn notify(&self, msg: &str, msg_type: MessageType) {
+        self.infobars.show(msg, msg_type);
+    }
+
+    /// Like `notify`, with a button labelled `action_label` which runs `action`
+    pub fn notify_with_action<F: Fn() + 'static>(
+        &self,
+        msg: &str,
+        msg_type: MessageType,
+        action_label: &str,
+        action: F,
+    ) {
+        self.infobars
+            .show_with_action(msg, msg_type, action_label, action);
+    }
+
+    /// Checks if the file has been changed on disk by another program since it's been opened or
+    /// saved, while the document has unsaved changes. Otherwise xi-editor reloads the document by
+    /// itself. Each change is only reported once.
+    pub fn has_disk_conflict(&self) -> bool {
+        let modified = self.file_name.as_ref().and_then(|f| file_modified(f));
+        let known = self.file_modified.replace(modified);
+        !self.pristine && known.is_some() && modified.is_some() && known != modified
+    }
+
+    /// The family of the font the text is shown in
+    pub fn font_family(&self) -> String {
+        self.edit_font
+            .font_desc
+            .get_family()
+            .map_or_else(|| "monospace".to_string(), |family| family.to_string())
+    }
+
+    /// The column of the right margin, which may be set per language
+    fn margin_column(&self) -> u32 {
+        self.main_state
+            .borrow()
+            .settings
+            .margin_column(&self.language())
+    }
+
+    /// Shows how many lines are longer than the right margin in the statusbar. xi-editor only
+    /// sends us the lines around the visible region, so lines it hasn't sent us yet aren't counted.
+    pub fn update_long_lines_label(&self) {
+        let label = &self.view_item.statusbar.long_lines_label;
+        if !self.main_state.borrow().settings.highlight_long_lines {
+            label.hide();
+            return;
+        }
+
+        let column = self.margin_column();
+        let tab_size = self.main_state.borrow().settings.tab_size;
+        let count = self
+            .line_cache
+            .lines
+            .iter()
+            .filter_map(Option::as_ref)
+            .filter(|line| line_columns(line.text(), tab_size) > column)
+            .count();
+
+        if count == 0 {
+            label.hide();
+        } else {
+            label.set_text(&format!("{}: {}", gettext("Long lines"), count));
+            label.show();
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Makes the EditView read-only (or editable again). Read-only EditViews ignore all input
+    /// which would change the document.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+        self.update_title();
+        self.find_replace.replace_button.set_sensitive(!read_only);
+        self.find_replace
+            .replace_all_button
+            .set_sensitive(!read_only);
+    }
+
+    /// Sets the edit font to the face of `font_desc` with the size set by the user, scaled with
+    /// the zoom of the window, and regenerates the font height/width etc.
+    fn set_edit_font(&mut self, mut font_desc: FontDescription) {
+        let zoom = self.main_state.borrow().zoom;
+        font_desc.set_size((f64::from(self.edit_font_size) * zoom).round() as i32);
+        self.edit_font = Font::new(&self.view_item.get_pango_ctx(), font_desc);
+        self.update_text_size();
+        self.place_annotation_widgets();
+        self.view_item.edit_area.queue_draw();
+        self.view_item.linecount.queue_draw();
+    }
+
+    /// Measures the fonts again, e.g. after the window has been moved to a monitor with another
+    /// scale factor or the font resolution of GTK has changed. The carets would end up in the
+    /// wrong places with the old metrics.
+    pub fn update_font_metrics(&mut self) {
+        let pango_ctx = self.view_item.get_pango_ctx();
+        self.interface_font =
+            Self::get_interface_font(&self.main_state.borrow().settings, &pango_ctx);
+        self.set_edit_font(self.edit_font.font_desc.clone());
+        self.update_visible_scroll_region();
+    }
+
+    /// Calls `update_font_metrics` once GTK has updated the Pango context of the EditView, which
+    /// happens after the scale factor or the screen have changed
+    pub fn schedule_font_metrics_update(edit_view: &Rc<RefCell<Self>>) {
+        let edit_view_weak = Rc::downgrade(edit_view);
+        glib::idle_add_local(move || {
+            if let Some(edit_view) = edit_view_weak.upgrade() {
+                edit_view.borrow_mut().update_font_metrics();
+            }
+            source::Continue(false)
+        });
+    }
+
+    /// Applies the zoom of the window (see `MainState::zoom`) after it has been changed
+    pub fn update_zoom(&mut self) {
+        self.set_edit_font(self.edit_font.font_desc.clone());
+        self.update_visible_scroll_region();
+        self.update_zoom_label();
+    }
+
+    /// Shows the zoom level in the statusbar, unless the text isn't zoomed
+    fn update_zoom_label(&self) {
+        let zoom = self.main_state.borrow().zoom;
+        let label = &self.view_item.statusbar.zoom_label;
+        if (zoom - 1.0).abs() < std::f64::EPSILON {
+            label.hide();
+        } else {
+            label.set_text(&format!("{}: {:.0}%", gettext("Zoom"), zoom * 100.0));
+            label.show();
+        }
+    }
+
+    /// Shows in the statusbar if background work is reduced, see `MainState::reduced_mode`
+    pub fn update_reduced_mode_label(&self) {
+        let reduced_mode = self.main_state.borrow().reduced_mode;
+        self.view_item
+            .statusbar
+            .reduced_mode_label
+            .set_visible(reduced_mode);
+    }
+
+    /// Zooms in or out if the user scrolls while holding Ctrl. The zoom is applied to the whole
+    /// window, so this is handled by the window's `zoom_in`/`zoom_out` actions.
+    pub fn handle_zoom_scroll(&self, es: &EventScroll) {
+        use gio::ActionGroupExt;
+
+        let zoom_in = match es.get_direction() {
+            ScrollDirection::Up => true,
+            ScrollDirection::Down => false,
+            ScrollDirection::Smooth => match es.get_delta() {
+                (_, dy) if dy < 0.0 => true,
+                (_, dy) if dy > 0.0 => false,
+                _ => return,
+            },
+            _ => return,
+        };
+
+        if let Some(app) = gio::Application::get_default() {
+            app.activate_action(if zoom_in { "zoom_in" } else { "zoom_out" }, None);
+        }
+    }
+
+    /// If xi-editor sends us a [config_changed](https://xi-editor.io/docs/frontend-protocol.html#config_changed)
+    /// msg we process it here, e.g. setting the font face/size xi-editor tells us. Most configs don't
+    /// need special handling by us though.
+    pub fn config_changed(&mut self, changes: &Value) {
+        trace!(
+            "{} 'config_changed' {} '{}': {:?}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            changes
+        );
+        if let Some(map) = changes.as_object() {
+            for (name, value) in map {
+                match name.as_ref() {
+                    "font_size" => {
+                        if let Some(font_size) = value.as_f64() {
+                            self.edit_font_size = font_size as i32 * pango::SCALE;
+                            self.set_edit_font(self.edit_font.font_desc.clone());
+                        }
+                    }
+                    "font_face" => {
+                        if let Some(font_face) = value.as_str() {
+                            debug!("{}: {}", gettext("Setting edit font to"), font_face);
+                            self.set_edit_font(FontDescription::from_string(font_face));
+                        }
+                    }
+                    "tab_size" => (),
+                    "auto_indent" => (),
+                    "autodetect_whitespace" => (),
+                    "plugin_search_path" => (),
+                    "scroll_past_end" => (),
+                    "translate_tabs_to_spaces" => (),
+                    "use_tab_stops" => (),
+                    "word_wrap" => (),
+                    "wrap_width" => (),
+                    "line_ending" => (),
+                    "surrounding_pairs" => (),
+                    "save_with_newline" => (),
+                    _ => {
+                        error!(
+                            "{}: {}",
+                            gettext("Unhandled config option, open a bug report!"),
+                            name
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// If xi-editor sends us a [update](https://xi-editor.io/docs/frontend-protocol.html#config_changed)
+    /// msg we process it here, setting the scrollbars upper limit accordingly, checking if the EditView
+    /// is pristine (_does not_ has unsaved changes) and queue a new draw of the EditView.
+    pub fn update(&mut self, params: &Value) {
+        trace!(
+            "{} 'update' {} '{}': {:?}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            params
+        );
+        let update = &params["update"];
+        self.line_cache.apply_update(update);
+        self.folds.apply_update(update, self.line_cache.height());
+        self.bookmarks
+            .apply_update(update, self.line_cache.height());
+        self.gutter_markers
+            .apply_update(update, self.line_cache.height());
+        for annotation in self
+            .annotations
+            .apply_update(update, self.line_cache.height())
+        {
+            self.drop_annotation(annotation);
+        }
+        self.document.invalidate();
+        if let Some((extend, missing)) = self.pending_bracket_match.take() {
+            self.find_matching_bracket(extend, Some(missing));
+        }
+        self.serve_text_requests();
+        self.evict_lines();
+        self.reveal_cursors();
+        self.update_occurrences();
+        if self.find_replace.search_bar.get_search_mode() {
+            self.update_match_counter();
+        }
+        if let Some(source) = self.completion_source.get() {
+            self.complete(source);
+        }
+
+        self.update_text_size();
+        self.place_annotation_widgets();
+
+        if let Some(pristine) = update["pristine"].as_bool() {
+            if self.pristine != pristine {
+                self.pristine = pristine;
+                self.update_title();
+                // The changes have been saved
+                if pristine {
+                    self.file_modified
+                        .set(self.file_name.as_ref().and_then(|f| file_modified(f)));
+                    self.check_file();
+                }
+            }
+        }
+
+        // self.change_scrollbar_visibility();
+
+        // The cursor might be on a different line now
+        self.update_regex_tester();
+        self.update_long_lines_label();
+
+        self.view_item.edit_area.queue_draw();
+        self.view_item.linecount.queue_draw();
+    }
+
+    /// Removes the highlights of the occurrences of the word at the cursor and highlights the ones
+    /// of the word the cursor is at now once it has rested there for a moment
+    pub fn update_occurrences(&self) {
+        self.occurrences.clear();
+        if !self.main_state.borrow().settings.highlight_occurrences {
+            self.view_item.edit_area.queue_draw();
+            return;
+        }
+
+        let word = self.cursor_position().and_then(|(line, col)| {
+            let text = self.line_cache.get_line(line)?.text();
+            occurrences::word_at(text, col).map(str::to_string)
+        });
+        if let Some(word) = word {
+            self.occurrences
+                .highlight_later(word, &self.view_item.edit_area);
+        }
+    }
+
+    /// Drops lines far away from the visible ones from the line cache if it takes up more memory
+    /// than the user allows. `update_visible_scroll_region` requests them again once they're
+    /// scrolled into view.
+    fn evict_lines(&mut self) {
+        let budget = self.main_state.borrow().settings.line_cache_budget;
+        if budget == 0 {
+            return;
+        }
+
+        let (first_line, last_line) = self.visible_lines();
+        // Keep a few screens worth of lines around the visible ones, so scrolling a bit doesn't
+        // have to wait for xi-editor
+        let margin = last_line.saturating_sub(first_line) * 2;
+        let keep = first_line.saturating_sub(margin)..last_line + margin;
+        let evicted = self
+            .line_cache
+            .evict(&[keep], budget as usize * 1024 * 1024);
+        if evicted > 0 {
+            debug!(
+                "{} {} {}",
+                gettext("Dropped"),
+                evicted,
+                gettext("lines from the line cache")
+            );
+            self.lines_evicted = true;
+        }
+    }
+
+    /// Sets the size of the edit_area to the size of the text, so the scrollbars have the
+    /// right range
+    fn update_text_size(&self) {
+        // update scrollbars to the new text width and height
+        let text_size = self.get_text_size();
+        let text_height = text_size.height;
+        let text_width = if text_size.contained_width {
+            text_size.width
+        } else {
+            text_size.width + self.edit_font.font_width * 4.0
+        };
+
+        {
+            // don't conflict with pango::LayoutExt;
+            use gtk::LayoutExt;
+            self.view_item
+                .edit_area
+                .set_size(text_width as u32, text_height as u32);
+        }
+    }
+
+    /// Unfolds all folds which hide a line containing a cursor, e.g. because the user moved the
+    /// cursor into a folded region.
+    fn reveal_cursors(&mut self) {
+        if self.folds.is_empty() {
+            return;
+        }
+        let n_invalid_before = self.line_cache.n_invalid_before;
+        let cursor_lines: Vec<u64> = self
+            .line_cache
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(false, |l| !l.cursor().is_empty()))
+            .map(|(ix, _)| n_invalid_before + ix as u64)
+            .collect();
+        for line in cursor_lines {
+            self.folds.reveal(line);
+        }
+    }
+
+    /// Folds the region starting at `line`, or unfolds it if it's folded already.
+    pub fn toggle_fold(&mut self, line: u64) {
+        trace!(
+            "{} 'toggle_fold' {} '{}': {}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            line
+        );
+        if !self.folds.unfold(line) {
+            let tab_size = self.main_state.borrow().settings.tab_size;
+            let line_cache = &self.line_cache;
+            let end = fold_region(
+                |n| line_cache.get_line(n).map(Line::text),
+                line,
+                line_cache.height(),
+                tab_size,
+            );
+            if let Some(end) = end {
+                self.folds.fold(line, end);
+                // Move cursors out of the region we just folded, we can't edit hidden lines
+                let cursor_hidden = (line + 1..=end).any(|n| {
+                    self.line_cache
+                        .get_line(n)
+                        .map_or(false, |l| !l.cursor().is_empty())
+                });
+                if cursor_hidden {
+                    let col = self
+                        .line_cache
+                        .get_line(line)
+                        .map_or(0, |l| strip_line_ending(l.text()).len());
+                    self.core
+                        .gesture_point_select(&self.view_id, line, col as u64);
+                }
+            }
+        }
+
+        self.update_text_size();
+        self.place_annotation_widgets();
+        self.update_visible_scroll_region();
+        self.view_item.edit_area.queue_draw();
+        self.view_item.linecount.queue_draw();
+    }
+
+    /// Toggles the fold of the line that has been clicked on in the fold gutter, or lets the
+    /// markers of the line know they've been clicked
+    pub(crate) fn handle_linecount_button_press(&mut self, eb: &EventButton) -> Inhibit {
+        if eb.get_button() != 1 || eb.get_event_type() != EventType::ButtonPress {
+            return Inhibit(false);
+        }
+        let (x, y) = eb.get_position();
+        let line = match self.gutter_line_at(y) {
+            Some(line) => line,
+            None => return Inhibit(false),
+        };
+
+        if x < self.marker_column_width() {
+            let handlers: Vec<Rc<dyn Fn(u64)>> = self
+                .gutter_markers
+                .at(line)
+                .into_iter()
+                .filter_map(|marker| marker.on_click.clone())
+                .collect();
+            if handlers.is_empty() {
+                return Inhibit(false);
+            }
+            // The handlers may need this EditView, which is borrowed while we handle the click
+            glib::idle_add_local(move || {
+                for handler in &handlers {
+                    handler(line);
+                }
+                source::Continue(false)
+            });
+            return Inhibit(true);
+        }
+
+        let gutter_width = f64::from(self.view_item.linecount.get_allocated_width());
+        if x < gutter_width - self.interface_font.font_width * FOLD_MARKER_COLUMNS {
+            return Inhibit(false);
+        }
+        self.toggle_fold(line);
+        Inhibit(true)
+    }
+
+    /// Shows the tooltips of the markers of the line the pointer is on
+    pub(crate) fn handle_linecount_query_tooltip(&self, x: f64, y: f64, tooltip: &Tooltip) -> bool {
+        if x >= self.marker_column_width() {
+            return false;
+        }
+        let tooltips: Vec<String> = self
+            .gutter_line_at(y)
+            .map(|line| self.markers_at(line))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|marker| marker.tooltip)
+            .collect();
+        if tooltips.is_empty() {
+            return false;
+        }
+        tooltip.set_text(Some(&tooltips.join("\n")));
+        true
+    }
+
+    /// The line at `y` (in pixels from the top of the gutter), if there's one
+    fn gutter_line_at(&self, y: f64) -> Option<u64> {
+        let row = ((y + self.view_item.vadj.get_value()) / self.edit_font.font_height) as u64;
+        let line = self.folds.line_at_row(row);
+        if line < self.line_cache.height() {
+            Some(line)
+        } else {
+            None
+        }
+    }
+
+    /// Maps x|y pixel coordinates to the line num and col. This can be used e.g. for
+    /// determining the first and last line, by setting the y coordinate to 0 and the
+    /// last pixel.
+    pub fn da_px_to_cell(&self, x: f64, y: f64) -> (u64, u64) {
+        trace!(
+            "{} 'da_px_to_cell' {} '{}': x: {} y: {}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            x,
+            y
+        );
+        let x = x + self.view_item.hadj.get_value();
+        let y = y + self.view_item.vadj.get_value();
+
+        let mut y = y - self.edit_font.font_descent;
+        if y < 0.0 {
+            y = 0.0;
+        }
+        let line_num = self
+            .folds
+            .line_at_row((y / self.edit_font.font_height) as u64);
+        (self.index_at_x(line_num, x), line_num)
+    }
+
+    /// Returns the byte index of `line_num` at `x` (in document coordinates)
+    fn index_at_x(&self, line_num: u64, x: f64) -> u64 {
+        if let Some(line) = self.line_cache.get_line(line_num) {
+            let pango_ctx = self.view_item.get_pango_ctx();
+
+            let layout = self.create_layout_for_line(&pango_ctx, line, &self.get_tabs());
+            let (_, index, trailing) = layout.xy_to_index((x * f64::from(pango::SCALE)) as i32, 0);
+            graphemes::click_index(strip_line_ending(line.text()), index as usize, trailing) as u64
+        } else {
+            0
+        }
+    }
+
+    /// Allocate the space our DrawingArea needs.
+    pub(crate) fn da_size_allocate(&self, da_width: i32, da_height: i32) {
+        debug!(
+            "{}: {}: {}, {}: {}",
+            gettext("Allocating DrawingArea size"),
+            gettext("Width"),
+            da_width,
+            gettext("Height"),
+            da_height,
+        );
+
+        self.update_visible_scroll_region();
+    }
+
+    /// This updates the part of the document that's visible to the user, e.g. when scrolling.
+    /// This requests the required lines from xi-editor to add them to the line cache and then
+    /// adjusts the scrolling to the visible region.
+    pub(crate) fn update_visible_scroll_region(&self) {
+        trace!(
+            "{} 'update_visible_scroll_region' {} '{}'",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id
+        );
+        let (first_line, last_line) = self.visible_lines();
+
+        debug!(
+            "{} {} {}",
+            gettext("Updating visible scroll region"),
+            first_line,
+            last_line
+        );
+
+        self.core.scroll(&self.view_id, first_line, last_line);
+
+        // xi-editor thinks we still have the lines we dropped, so it won't send them by itself
+        if self.lines_evicted && first_line < last_line {
+            for (first, last) in self.line_cache.get_missing(first_line, last_line) {
+                self.core.request_lines(&self.view_id, first, last);
+            }
+        }
+    }
+
+    /// The first and (exclusive) last line which are visible
+    fn visible_lines(&self) -> (u64, u64) {
+        let da_height = self.view_item.edit_area.get_allocated_height();
+        let num_lines = self.line_cache.height();
+        let vadj = &self.view_item.vadj;
+        let first_row = (vadj.get_value() / self.edit_font.font_height) as u64;
+        let last_row =
+            (vadj.get_value() + f64::from(da_height) / self.edit_font.font_height) as u64 + 1;
+        let first_line = self.folds.line_at_row(first_row);
+        let last_line = min(self.folds.line_at_row(last_row), num_lines);
+        (first_line, last_line)
+    }
+
+    /// The (zero based) line at the top of the EditView, e.g. to scroll previews along with it
+    pub fn first_visible_line(&self) -> u64 {
+        self.visible_lines().0
+    }
+
+    /// Returns the width&height of the entire document
+    fn get_text_size(&self) -> TextSize {
+        trace!(
+            "{} 'get_text_size' {} '{}'",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id
+        );
+
+        let mut contained_height = false;
+        let mut contained_width = false;
+
+        let da_width = f64::from(self.view_item.edit_area.get_allocated_width());
+        let da_height = f64::from(self.view_item.edit_area.get_allocated_height());
+        let num_rows = self.line_cache.height() - self.folds.hidden_lines();
+
+        let all_text_height =
+            num_rows as f64 * self.edit_font.font_height + self.edit_font.font_descent;
+        let height = if da_height > all_text_height {
+            contained_height = true;
+            da_height
+        } else {
+            all_text_height
+        };
+
+        let vadj = &self.view_item.vadj;
+        let first_row = (vadj.get_value() / self.edit_font.font_height) as u64;
+        let last_row = (vadj.get_value() + da_height / self.edit_font.font_height) as u64 + 1;
+        let last_row = min(last_row, num_rows);
+        // Set this to pango::SCALE, we divide by that later on.
+        let mut max_width = pango::SCALE;
+
+        let pango_ctx = self.view_item.get_pango_ctx();
+        let tabs = self.get_tabs();
+
+        // Determine the longest line as per Pango. Creating layouts with Pango here is kind of expensive
+        // here, but it's hard determining an accurate width otherwise.
+        let mut i = self.folds.line_at_row(first_row);
+        for _ in first_row..last_row {
+            if let Some(line) = self.line_cache.get_line(i) {
+                let layout = self.create_layout_for_line(&pango_ctx, line, &tabs);
+                max_width = max(max_width, layout.get_extents().1.width);
+            }
+            i = self.folds.next_visible_line(i);
+        }
+
+        let render_width = f64::from(max_width / pango::SCALE);
+
+        let width = if da_width > render_width {
+            contained_width = true;
+            da_width
+        } else {
+            render_width
+        };
+
+        TextSize {
+            width,
+            height,
+            contained_height,
+            contained_width,
+        }
+    }
+
+    /// Handles the drawing of the EditView. This is called when we get a update from xi-editor or if
+    /// gtk requests us to draw the EditView. This draws the background, all lines and the cursor.
+    pub fn handle_da_draw(&self, cr: &Context) -> Inhibit {
+        const CURSOR_WIDTH: f64 = 2.0;
+
+        // let foreground = self.main_state.borrow().theme.foreground;
+        let theme = &self.main_state.borrow().theme;
+
+        let da_width = self.view_item.edit_area.get_allocated_width();
+        let da_height = self.view_item.edit_area.get_allocated_height();
+
+        //debug!("Drawing");
+        // cr.select_font_face("Mono", ::cairo::enums::FontSlant::Normal, ::cairo::enums::FontWeight::Normal);
+        // let mut font_options = cr.get_font_options();
+        // debug!("font options: {:?} {:?} {:?}", font_options, font_options.get_antialias(), font_options.get_hint_style());
+        // font_options.set_hint_style(HintStyle::Full);
+
+        // let (text_width, text_height) = self.get_text_size();
+        let num_rows = self.line_cache.height() - self.folds.hidden_lines();
+
+        let vadj = &self.view_item.vadj;
+        let hadj = &self.view_item.hadj;
+        trace!(
+            "{}  {}: {}/{}; {}: {}/{}",
+            gettext("Drawing EditView"),
+            gettext("vertical adjustment"),
+            vadj.get_value(),
+            vadj.get_upper(),
+            gettext("horizontal adjustment"),
+            hadj.get_value(),
+            hadj.get_upper()
+        );
+
+        let first_row = (vadj.get_value() / self.edit_font.font_height) as u64;
+        let last_row =
+            ((vadj.get_value() + f64::from(da_height)) / self.edit_font.font_height) as u64 + 1;
+        let last_row = min(last_row, num_rows);
+
+        let pango_ctx = self.view_item.get_pango_ctx();
+        pango_ctx.set_font_description(&self.edit_font.font_desc);
+
+        let until_margin_width = self.edit_font.font_width * f64::from(self.margin_column());
+        let highlight_long_lines = self.main_state.borrow().settings.highlight_long_lines;
+
+        // Draw a line at x chars
+        if self.main_state.borrow().settings.right_margin {
+            // Draw editing background
+            set_source_color(cr, theme.background);
+            cr.rectangle(0.0, 0.0, until_margin_width, f64::from(da_height));
+            cr.fill();
+
+            set_margin_source_color(cr, theme.background);
+            cr.rectangle(
+                until_margin_width - self.view_item.hadj.get_value(),
+                0.0,
+                f64::from(da_width) + self.view_item.vadj.get_value(),
+                f64::from(da_height),
+            );
+            cr.fill();
+        } else {
+            // Draw editing background
+            set_source_color(cr, theme.background);
+            cr.rectangle(0.0, 0.0, f64::from(da_width), f64::from(da_height));
+            cr.fill();
+        }
+
+        set_source_color(cr, theme.foreground);
+
+        let tabs = self.get_tabs();
+        let whitespace = WhitespaceOptions::from_settings(&self.main_state.borrow().settings);
+        let occurrence_word = self.occurrences.word();
+        let show_color_chips = self.main_state.borrow().settings.show_color_chips;
+        let spell_check_language = if self.main_state.borrow().spell_checker.is_enabled() {
+            Some(self.language())
+        } else {
+            None
+        };
+
+        let mut next_line = self.folds.line_at_row(first_row);
+        for row in first_row..last_row {
+            let i = next_line;
+            next_line = self.folds.next_visible_line(i);
+            // Keep track of the starting x position
+            if let Some(line) = self.line_cache.get_line(i) {
+                if self.main_state.borrow().settings.highlight_line && !line.cursor().is_empty() {
+                    set_source_color(cr, theme.line_highlight);
+                    cr.rectangle(
+                        0.0,
+                        self.edit_font.font_height * row as f64 - vadj.get_value(),
+                        f64::from(da_width),
+                        self.edit_font.font_height,
+                    );
+                    cr.fill();
+                    set_source_color(cr, theme.foreground);
+                }
+
+                cr.move_to(
+                    -hadj.get_value(),
+                    self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                );
+
+                let pango_ctx = self.view_item.get_pango_ctx();
+                let layout = self.create_layout_for_line(&pango_ctx, line, &tabs);
+
+                if let Some(word) = &occurrence_word {
+                    set_occurrence_source_color(cr, theme.foreground);
+                    self.draw_occurrences(
+                        cr,
+                        &layout,
+                        line,
+                        word,
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                    set_source_color(cr, theme.foreground);
+                    cr.move_to(
+                        -hadj.get_value(),
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                }
+
+                // Tint the part of the line which is beyond the right margin
+                if highlight_long_lines {
+                    let line_width = f64::from(layout.get_extents().1.width / pango::SCALE);
+                    if line_width > until_margin_width {
+                        set_long_line_source_color(cr);
+                        cr.rectangle(
+                            until_margin_width - hadj.get_value(),
+                            self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                            line_width - until_margin_width,
+                            self.edit_font.font_height,
+                        );
+                        cr.fill();
+                        set_source_color(cr, theme.foreground);
+                        cr.move_to(
+                            -hadj.get_value(),
+                            self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                        );
+                    }
+                }
+
+                // debug!("width={}", layout.get_extents().1.width);
+                update_layout(cr, &layout);
+                show_layout(cr, &layout);
+
+                if show_color_chips {
+                    self.draw_color_chips(
+                        cr,
+                        &layout,
+                        line,
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                    set_source_color(cr, theme.foreground);
+                }
+
+                if let Some(language) = &spell_check_language {
+                    set_misspelling_source_color(cr);
+                    self.draw_misspellings(
+                        cr,
+                        &layout,
+                        line,
+                        language,
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                    set_source_color(cr, theme.foreground);
+                }
+
+                // Show that there's more text hidden behind this line
+                if self.folds.is_folded(i) {
+                    let line_width = f64::from(layout.get_extents().1.width / pango::SCALE);
+                    cr.move_to(
+                        line_width + self.edit_font.font_width - hadj.get_value(),
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                    let fold_layout = pango::Layout::new(&pango_ctx);
+                    fold_layout.set_font_description(Some(&self.edit_font.font_desc));
+                    fold_layout.set_text("\u{22ef}");
+                    update_layout(cr, &fold_layout);
+                    show_layout(cr, &fold_layout);
+                }
+
+                let texts = self.annotations.texts_at(i);
+                if !texts.is_empty() {
+                    let line_width = f64::from(layout.get_extents().1.width / pango::SCALE);
+                    // Leave room for the fold marker
+                    let columns = if self.folds.is_folded(i) { 4.0 } else { 2.0 };
+                    self.draw_annotation_texts(
+                        cr,
+                        &pango_ctx,
+                        &texts,
+                        line_width + self.edit_font.font_width * columns - hadj.get_value(),
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                    set_source_color(cr, theme.foreground);
+                }
+
+                if whitespace.is_enabled() {
+                    set_whitespace_source_color(cr, theme.invisibles, theme.foreground);
+                    self.draw_whitespace(
+                        cr,
+                        &pango_ctx,
+                        &layout,
+                        line,
+                        &whitespace,
+                        self.edit_font.font_height * (row as f64) - vadj.get_value(),
+                    );
+                }
+
+                let layout_line = layout.get_line(0);
+                if layout_line.is_none() {
+                    continue;
+                }
+                let layout_line = layout_line.unwrap();
+
+                // Set cursor color, block cursors are translucent so the char they cover stays
+                // readable
+                let caret_color = self.main_state.borrow().caret_color();
+                let block_cursor = self.has_block_cursor();
+                if block_cursor {
+                    set_source_color(cr, caret_color.map(|c| Color { a: c.a / 2, ..c }));
+                } else {
+                    set_source_color(cr, caret_color);
+                }
+
+                for c in line.cursor() {
+                    // Without rounding to pixels, so the caret doesn't drift away from the text
+                    // in long lines with proportional chars
+                    let x = f64::from(layout_line.index_to_x(*c as i32, false))
+                        / f64::from(pango::SCALE);
+                    let width = if block_cursor {
+                        // Cover the grapheme after the cursor, e.g. a whole emoji or a letter
+                        // with its accents
+                        let text = strip_line_ending(line.text());
+                        let end = graphemes::grapheme_end(text, *c as usize);
+                        if end > *c as usize {
+                            f64::from(layout_line.index_to_x(end as i32, false))
+                                / f64::from(pango::SCALE)
+                                - x
+                        } else {
+                            self.edit_font.font_width
+                        }
+                    } else {
+                        CURSOR_WIDTH
+                    };
+                    // Draw the cursor
+                    cr.rectangle(
+                        x - hadj.get_value(),
+                        (self.edit_font.font_ascent + self.edit_font.font_descent) * row as f64
+                            - vadj.get_value(),
+                        width,
+                        self.edit_font.font_ascent + self.edit_font.font_descent,
+                    );
+                    cr.fill();
+                }
+            }
+        }
+
+        // Check the words we haven't seen yet, we're drawn again once they have been
+        if spell_check_language.is_some() {
+            self.main_state.borrow().spell_checker.flush();
+        }
+
+        Inhibit(false)
+    }
+
+    /// Draws the `texts` of annotations next to each other in italics, starting at `x`
+    fn draw_annotation_texts(
+        &self,
+        cr: &Context,
+        pango_ctx: &pango::Context,
+        texts: &[(&str, Option<Color>)],
+        mut x: f64,
+        y: f64,
+    ) {
+        let theme = &self.main_state.borrow().theme;
+        let mut font_desc = self.edit_font.font_desc.clone();
+        font_desc.set_style(pango::Style::Italic);
+        for (text, color) in texts {
+            let layout = pango::Layout::new(pango_ctx);
+            layout.set_font_description(Some(&font_desc));
+            layout.set_text(text);
+            match color {
+                Some(_) => set_source_color(cr, *color),
+                None => set_whitespace_source_color(cr, theme.invisibles, theme.foreground),
+            }
+            cr.move_to(x, y);
+            update_layout(cr, &layout);
+            show_layout(cr, &layout);
+            x += f64::from(layout.get_extents().1.width / pango::SCALE)
+                + self.edit_font.font_width * 2.0;
+        }
+    }
+
+    /// Draws a background behind the occurrences of `word` in `line`, which is drawn with `layout`
+    /// at `y`
+    fn draw_occurrences(
+        &self,
+        cr: &Context,
+        layout: &pango::Layout,
+        line: &Line,
+        word: &str,
+        y: f64,
+    ) {
+        let layout_line = match layout.get_line(0) {
+            Some(layout_line) => layout_line,
+            None => return,
+        };
+        for (start, end) in occurrences::word_ranges(line.text(), word) {
+            let x_start = layout_line.index_to_x(start as i32, false) / pango::SCALE;
+            let x_end = layout_line.index_to_x(end as i32, false) / pango::SCALE;
+            cr.rectangle(
+                f64::from(x_start) - self.view_item.hadj.get_value(),
+                y,
+                f64::from(x_end - x_start),
+                self.edit_font.font_height,
+            );
+        }
+        cr.fill();
+    }
+
+    /// Draws wavy lines under the misspelled words of `line` (in the language `language`), which
+    /// is drawn with `layout` at `y`. Words with a cursor in them aren't marked, they're probably
+    /// still being typed.
+    fn draw_misspellings(
+        &self,
+        cr: &Context,
+        layout: &pango::Layout,
+        line: &Line,
+        language: &str,
+        y: f64,
+    ) {
+        const WAVE_WIDTH: f64 = 2.0;
+        const WAVE_HEIGHT: f64 = 1.5;
+
+        let layout_line = match layout.get_line(0) {
+            Some(layout_line) => layout_line,
+            None => return,
+        };
+        let main_state = self.main_state.borrow();
+        let text = strip_line_ending(line.text());
+        let bottom = y + self.edit_font.font_height - 1.0;
+        let x_at = |index: usize| {
+            f64::from(layout_line.index_to_x(index as i32, false)) / f64::from(pango::SCALE)
+                - self.view_item.hadj.get_value()
+        };
+        for range in spell_check::words(text, &spell_check::checked_ranges(text, language)) {
+            let typing = line
+                .cursor()
+                .iter()
+                .any(|c| range.start as u64 <= *c && *c <= range.end as u64);
+            if typing || !main_state.spell_checker.is_misspelled(&text[range.clone()]) {
+                continue;
+            }
+            let (mut x, x_end) = (x_at(range.start), x_at(range.end));
+            cr.move_to(x, bottom);
+            let mut up = true;
+            while x < x_end {
+                x = (x + WAVE_WIDTH).min(x_end);
+                cr.line_to(x, if up { bottom - WAVE_HEIGHT } else { bottom });
+                up = !up;
+            }
+        }
+        cr.set_line_width(1.0);
+        cr.stroke();
+    }
+
+    /// The range of the misspelled word at (byte) column `col` of `line`, the word and the
+    /// suggestions for it
+    fn misspelling_at(
+        &self,
+        line: u64,
+        col: usize,
+    ) -> Option<(std::ops::Range<usize>, String, Vec<String>)> {
+        let main_state = self.main_state.borrow();
+        if !main_state.spell_checker.is_enabled() {
+            return None;
+        }
+        let text = strip_line_ending(self.line_cache.get_line(line)?.text());
+        let ranges = spell_check::checked_ranges(text, &self.language());
+        spell_check::words(text, &ranges)
+            .into_iter()
+            .find(|range| range.start <= col && col <= range.end)
+            .and_then(|range| {
+                let word = &text[range.clone()];
+                let suggestions = main_state.spell_checker.suggestions(word)?;
+                Some((range.clone(), word.to_string(), suggestions))
+            })
+    }
+
+    /// Replaces the text in `range` (in bytes) of `line` by `replacement`
+    fn replace_range(&self, line: u64, range: std::ops::Range<usize>, replacement: &str) {
+        self.core
+            .gesture_point_select(&self.view_id, line, range.start as u64);
+        self.core
+            .gesture_range_select(&self.view_id, line, range.end as u64);
+        self.core.insert(&self.view_id, replacement);
+    }
+
+    /// Draws a chip in the color of each color literal of `line` under it. `line` is drawn with
+    /// `layout` at `y`.
+    fn draw_color_chips(&self, cr: &Context, layout: &pango::Layout, line: &Line, y: f64) {
+        let layout_line = match layout.get_line(0) {
+            Some(layout_line) => layout_line,
+            None => return,
+        };
+        for color in colors::find_colors(line.text()) {
+            let x_start = layout_line.index_to_x(color.start as i32, false) / pango::SCALE;
+            let x_end = layout_line.index_to_x(color.end as i32, false) / pango::SCALE;
+            let rgba = color.rgba();
+            cr.set_source_rgba(rgba.red, rgba.green, rgba.blue, rgba.alpha);
+            cr.rectangle(
+                f64::from(x_start) - self.view_item.hadj.get_value(),
+                y + self.edit_font.font_height - COLOR_CHIP_HEIGHT,
+                f64::from(x_end - x_start),
+                COLOR_CHIP_HEIGHT,
+            );
+            cr.fill();
+        }
+    }
+
+    /// Returns the color literal whose chip is at `x`, `y` (in widget coordinates) and its line
+    fn color_chip_at(&self, x: f64, y: f64) -> Option<(u64, ColorLiteral)> {
+        if !self.main_state.borrow().settings.show_color_chips {
+            return None;
+        }
+        let y = y + self.view_item.vadj.get_value();
+        let row = (y / self.edit_font.font_height) as u64;
+        if y - row as f64 * self.edit_font.font_height
+            < self.edit_font.font_height - COLOR_CHIP_HEIGHT
+        {
+            return None;
+        }
+        let line_num = self.folds.line_at_row(row);
+        let col = self.index_at_x(line_num, x + self.view_item.hadj.get_value()) as usize;
+        let line = self.line_cache.get_line(line_num)?;
+        colors::find_colors(line.text())
+            .into_iter()
+            .find(|color| color.start <= col && col < color.end)
+            .map(|color| (line_num, color))
+    }
+
+    /// Lets the user pick a new color for the color literal `color` in `line`
+    fn edit_color(&self, line: u64, color: ColorLiteral) {
+        if self.read_only {
+            return;
+        }
+        let core = self.core.clone();
+        let view_id = self.view_id.clone();
+        let parent = self
+            .root_widget
+            .get_toplevel()
+            .and_then(|w| w.downcast::<Window>().ok());
+        // The dialog runs its own main loop, so only open it once we're done handling the click
+        glib::idle_add_local(move || {
+            colors::choose_color(&core, &view_id, parent.as_ref(), line, color);
+            source::Continue(false)
+        });
+    }
+
+    /// Draws markers on top of the whitespace of `line`, which is drawn with `layout` at `y`.
+    /// The markers are drawn over the text instead of replacing the whitespace in the layout, so
+    /// tabs keep their width and the styles xi-editor sent us still line up.
+    fn draw_whitespace(
+        &self,
+        cr: &Context,
+        pango_ctx: &pango::Context,
+        layout: &pango::Layout,
+        line: &Line,
+        options: &WhitespaceOptions,
+        y: f64,
+    ) {
+        let selections = selection_ranges(&line.styles);
+        let markers = whitespace::markers(line.text(), &selections, options);
+        if markers.is_empty() {
+            return;
+        }
+
+        let marker_layout = pango::Layout::new(pango_ctx);
+        marker_layout.set_font_description(Some(&self.edit_font.font_desc));
+        for (ix, marker) in markers {
+            let pos = layout.index_to_pos(ix as i32);
+            let mut x = f64::from(pos.x / pango::SCALE) - self.view_item.hadj.get_value();
+            // Center the dot on the space
+            if marker == whitespace::SPACE_MARKER {
+                x += (f64::from(pos.width / pango::SCALE) - self.edit_font.font_width) / 2.0;
+            }
+            cr.move_to(x, y);
+            marker_layout.set_text(marker);
+            update_layout(cr, &marker_layout);
+            show_layout(cr, &marker_layout);
+        }
+    }
+
+    /// This draws the linecount. We have this as our own widget to make sure we don't mess up text
+    /// selection etc.
+    pub fn handle_linecount_draw(&self, cr: &Context) -> Inhibit {
+        trace!(
+            "{} 'linecount_draw' {} '{}'",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id
+        );
+        let theme = &self.main_state.borrow().theme;
+        let linecount_height = self.view_item.linecount.get_allocated_height();
+
+        let num_lines = self.line_cache.height();
+        let num_rows = num_lines - self.folds.hidden_lines();
+
+        let vadj = &self.view_item.vadj;
+
+        let first_row = (vadj.get_value() / self.edit_font.font_height) as u64;
+        let last_row = ((vadj.get_value() + f64::from(linecount_height))
+            / self.edit_font.font_height) as u64
+            + 1;
+        let last_row = min(last_row, num_rows);
+        let first_line = self.folds.line_at_row(first_row);
+        let last_line = min(self.folds.line_at_row(last_row), num_lines);
+
+        let pango_ctx = self.view_item.get_pango_ctx();
+
+        // Make the linecount at least 6 chars big
+        let linecount_width = if format!("  {}  ", last_line).len() > 6 {
+            let width = self.interface_font.font_width * format!("  {}  ", last_line).len() as f64;
+            // Make sure the linecount_width is even to properly center the line number
+            if width % 2.0 == 0.0 {
+                width
+            } else {
+                width + 1.0
+            }
+        } else {
+            self.interface_font.font_width * 6.0
+        };
+
+        let fold_gutter_width = self.interface_font.font_width * FOLD_MARKER_COLUMNS;
+        let marker_width = self.marker_column_width();
+
+        // Draw linecount background
+        set_source_color(cr, theme.background);
+        cr.rectangle(
+            0.0,
+            0.0,
+            marker_width + linecount_width + fold_gutter_width,
+            f64::from(linecount_height),
+        );
+        cr.fill();
+
+        let tab_size = self.main_state.borrow().settings.tab_size;
+        let get_line_text = |n| self.line_cache.get_line(n).map(Line::text);
+
+        //FIXME: Xi sends us the 'ln' (logical linenumber) param for this, but that isn't updated on every draw!
+        let mut current_line = first_line;
+        let center_diff = (self.edit_font.font_height - self.interface_font.font_height) / 2.0;
+
+        set_source_color(cr, theme.foreground);
+        let mut next_line = first_line;
+        for row in first_row..last_row {
+            let i = next_line;
+            next_line = self.folds.next_visible_line(i);
+            // Keep track of the starting x position
+            if let Some(line) = self.line_cache.get_line(i) {
+                let y = self.edit_font.font_height * (row as f64) - vadj.get_value() + center_diff;
+                if line.line_num().is_some() {
+                    current_line += 1;
+                    cr.move_to(marker_width, y);
+
+                    let linecount_layout = self.create_layout_for_linecount(
+                        &pango_ctx,
+                        current_line,
+                        linecount_width as usize,
+                    );
+                    update_layout(cr, &linecount_layout);
+                    show_layout(cr, &linecount_layout);
+                }
+
+                let marker = if self.folds.is_folded(i) {
+                    Some("\u{25b8}")
+                } else if is_foldable(get_line_text, i, num_lines, tab_size) {
+                    Some("\u{25be}")
+                } else {
+                    None
+                };
+                if let Some(marker) = marker {
+                    cr.move_to(marker_width + linecount_width, y);
+                    let marker_layout = pango::Layout::new(&pango_ctx);
+                    marker_layout.set_font_description(Some(&self.interface_font.font_desc));
+                    marker_layout.set_text(marker);
+                    update_layout(cr, &marker_layout);
+                    show_layout(cr, &marker_layout);
+                }
+
+                // Only the marker added last is drawn if a line has several
+                if let Some(marker) = self.markers_at(i).last() {
+                    let top = self.edit_font.font_height * (row as f64) - vadj.get_value();
+                    self.draw_gutter_marker(cr, marker, top);
+                    set_source_color(cr, theme.foreground);
+                }
+            }
+            // Hidden lines still count
+            current_line += next_line - i - 1;
+        }
+
+        // Set the appropriate size for the linecount DrawingArea, otherwise it's only 1 px wide.
+        self.view_item.linecount.set_size_request(
+            (marker_width + linecount_width + fold_gutter_width) as i32,
+            -1,
+        );
+        Inhibit(false)
+    }
+
+    /// Width of the column of the gutter markers, which is only there if there are any
+    fn marker_column_width(&self) -> f64 {
+        if self.gutter_markers.is_empty() && self.bookmarks.is_empty() {
+            0.0
+        } else {
+            self.edit_font.font_height
+        }
+    }
+
+    /// The markers of `line`, including its bookmark
+    fn markers_at(&self, line: u64) -> Vec<GutterMarker> {
+        let mut markers = Vec::new();
+        if self.bookmarks.contains(line) {
+            markers.push(GutterMarker {
+                source: "bookmarks".to_string(),
+                tooltip: Some(gettext("Bookmark")),
+                ..GutterMarker::default()
+            });
+        }
+        markers.extend(self.gutter_markers.at(line).into_iter().cloned());
+        markers
+    }
+
+    /// Draws `marker` into the marker column, in the row starting at `top`
+    fn draw_gutter_marker(&self, cr: &Context, marker: &GutterMarker, top: f64) {
+        let size = self.edit_font.font_height;
+        let pixbuf = marker.icon.as_ref().and_then(|icon| {
+            IconTheme::get_default()?
+                .load_icon(icon, size as i32, IconLookupFlags::FORCE_SIZE)
+                .ok()?
+        });
+        if let Some(pixbuf) = pixbuf {
+            cr.set_source_pixbuf(&pixbuf, 0.0, top);
+            cr.paint();
+            return;
+        }
+        set_source_color(
+            cr,
+            marker
+                .color
+                .or_else(|| self.main_state.borrow().caret_color()),
+        );
+        cr.arc(
+            size / 2.0,
+            top + size / 2.0,
+            size / 4.0,
+            0.0,
+            2.0 * std::f64::consts::PI,
+        );
+        cr.fill();
+    }
+
+    /// Creates a pango layout for a particular linecount (the count on the left) in the linecache
+    fn create_layout_for_linecount(
+        &self,
+        pango_ctx: &pango::Context,
+        n: u64,
+        padding: usize,
+    ) -> pango::Layout {
+        let line_view = format!(
+            "{:^offset$}",
+            n,
+            offset = padding / self.interface_font.font_width as usize + 1
+        );
+        let layout = pango::Layout::new(pango_ctx);
+        layout.set_alignment(pango::Alignment::Center);
+        layout.set_font_description(Some(&self.interface_font.font_desc));
+        layout.set_text(line_view.as_str());
+        layout
+    }
+
+    /// The tab stops of the text: Pango repeats the first one, so tabs expand to the next multiple
+    /// of `tab_size` columns
+    fn get_tabs(&self) -> TabArray {
+        let mut tabs = TabArray::new(1, false);
+        tabs.set_tab(
+            0,
+            TabAlign::Left,
+            tab_width(
+                self.edit_font.font_width,
+                self.main_state.borrow().settings.tab_size,
+            ),
+        );
+
+        tabs
+    }
+
+    /// Checks how wide a line is, with tabs expanded like they are when it's drawn
+    pub fn line_width(&self, line_string: &str) -> f64 {
+        let line = Line::from_json(
+            &serde_json::json!({
+                "text": line_string,
+            }),
+            None,
+        );
+        let pango_ctx = self.view_item.get_pango_ctx();
+        let linecount_layout = self.create_layout_for_line(&pango_ctx, &line, &self.get_tabs());
+
+        f64::from(linecount_layout.get_extents().1.width) / f64::from(pango::SCALE)
+    }
+
+    /// Creates a pango layout for a particular line in the linecache
+    fn create_layout_for_line(
+        &self,
+        pango_ctx: &pango::Context,
+        line: &Line,
+        tabs: &TabArray,
+    ) -> pango::Layout {
+        let line_view = strip_line_ending(line.text());
+
+        // let layout = create_layout(cr).unwrap();
+        let layout = pango::Layout::new(pango_ctx);
+        layout.set_tabs(Some(tabs));
+        layout.set_font_description(Some(&self.edit_font.font_desc));
+        layout.set_text(line_view);
+
+        let mut ix = 0;
+        let attr_list = pango::AttrList::new();
+        for style in &line.styles {
+            let start_index = (ix + style.start) as u32;
+            let end_index = (ix + style.start + style.len as i64) as u32;
+            let main_state = self.main_state.borrow();
+            let line_style = main_state.styles.get(&style.id);
+
+            if let Some(foreground) = line_style.and_then(|s| s.fg_color) {
+                let pango_color = PangoColor::from(color_from_u32(foreground));
+                let mut attr =
+                    Attribute::new_foreground(pango_color.r, pango_color.g, pango_color.b).unwrap();
+                attr.set_start_index(start_index);
+                attr.set_end_index(end_index);
+                attr_list.insert(attr);
+            }
+
+            if let Some(background) = line_style.and_then(|s| s.bg_color) {
+                let pango_color = PangoColor::from(color_from_u32(background));
+                let mut attr =
+                    Attribute::new_background(pango_color.r, pango_color.g, pango_color.b).unwrap();
+                attr.set_start_index(start_index);
+                attr.set_end_index(end_index);
+                attr_list.insert(attr);
+            }
+
+            if let Some(weight) = line_style.and_then(|s| s.weight) {
+                let mut attr =
+                    Attribute::new_weight(pango::Weight::__Unknown(weight as i32)).unwrap();
+                attr.set_start_index(start_index);
+                attr.set_end_index(end_index);
+                attr_list.insert(attr);
+            }
+
+            if let Some(italic) = line_style.and_then(|s| s.italic) {
+                let mut attr = if italic {
+                    Attribute::new_style(pango::Style::Italic).unwrap()
+                } else {
+                    Attribute::new_style(pango::Style::Normal).unwrap()
+                };
+                attr.set_start_index(start_index);
+                attr.set_end_index(end_index);
+                attr_list.insert(attr);
+            }
+
+            if let Some(underline) = line_style.and_then(|s| s.underline) {
+                let mut attr = if underline {
+                    Attribute::new_underline(pango::Underline::Single).unwrap()
+                } else {
+                    Attribute::new_underline(pango::Underline::None).unwrap()
+                };
+                attr.set_start_index(start_index);
+                attr.set_end_index(end_index);
+                attr_list.insert(attr);
+            }
+
+            ix += style.start + style.len as i64;
+        }
+
+        layout.set_attributes(Some(&attr_list));
+        layout
+    }
+
+    /// Scrolls vertically to the line specified and horizontally to the column specified.
+    pub fn scroll_to(&self, line: u64, col: u64) {
+        trace!(
+            "{} 'scroll_to' {} '{}': l: {} c: {}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            line,
+            col
+        );
+        self.caret_history.borrow_mut().moved((line, col as usize));
+
+        self.view_item
+            .statusbar
+            .line_label
+            .set_text(&format!("{}: {}", gettext("Line"), line + 1));
+        self.view_item
+            .statusbar
+            .column_label
+            .set_text(&format!("{}: {}", gettext("Column"), col));
+
+        {
+            // The new height is the current last line + 1
+            let new_height = self.edit_font.font_height * self.folds.row_of_line(line) as f64;
+            let vadj = &self.view_item.vadj;
+            // Keep the lines the user has asked for visible around the cursor, so it's never at
+            // the very edge of the view, e.g. during find. Rows beyond half of the view can't be
+            // kept visible on both sides.
+            let visible_rows = vadj.get_page_size() / self.edit_font.font_height;
+            let offset_rows = f64::from(self.main_state.borrow().settings.scroll_offset)
+                .min(((visible_rows - 1.0) / 2.0).floor())
+                .max(0.0);
+            let padding = self.edit_font.font_height * offset_rows;
+            // If the cursor is above our current view (or its top margin), this is true
+            if new_height - padding < vadj.get_value() {
+                vadj.set_value(new_height - padding);
+            // If it's below our current view (or its bottom margin), this is true
+            } else if new_height + self.edit_font.font_height + padding
+                > vadj.get_value() + vadj.get_page_size()
+                && (vadj.get_page_size() as u32 != 0 && vadj.get_page_size() as u32 != 1)
+            {
+                vadj.set_value(
+                    new_height
+                        + self.edit_font.font_height
+                        + padding
+                        // These two aren't included in the font height and we need them to line up with the line
+                        + self.edit_font.font_ascent
+                        + self.edit_font.font_descent
+                        - vadj.get_page_size(),
+                );
+            }
+        }
+
+        {
+            // Collect all styles with id 0/1 (selections/find results) to make sure they're in the frame
+            if let Some(line) = self.line_cache.get_line(line) {
+                let line_selections: Vec<&StyleSpan> = line
+                    .styles
+                    .iter()
+                    .filter(|s| s.id == 0 || s.id == 1)
+                    .collect();
+
+                let mut begin_selection = None;
+                let mut end_selection = None;
+
+                for x in line_selections {
+                    if let Some(cur) = begin_selection {
+                        // Make sure to use the lowest value of any selection so it's in the view
+                        begin_selection = Some(min(cur, x.start));
+                    } else {
+                        begin_selection = Some(x.start);
+                    }
+                    if let Some(cur) = end_selection {
+                        // Make sure to use the highest value of any selection so it's in the view
+                        end_selection = Some(max(cur, x.start + x.len as i64));
+                    } else {
+                        end_selection = Some(x.start + x.len as i64);
+                    }
+                }
+
+                let mut line_text = line.text().to_string();
+                // Only measure width up to the right column
+                line_text.truncate(col as usize);
+                let line_length = self.line_width(&line_text);
+
+                let min = min(
+                    begin_selection.unwrap_or(line_length as i64),
+                    line_length as i64,
+                ) as f64;
+                let max = max(
+                    end_selection.unwrap_or(line_length as i64),
+                    line_length as i64,
+                ) as f64;
+
+                trace!("Horizontal scrolling to min: {}; max: {}", min, max);
+
+                let padding = self.edit_font.font_width * 4.0;
+                let hadj = &self.view_item.hadj;
+
+                // If the cursor/selection is to the left of our current view, this is true
+                if min < hadj.get_value() {
+                    hadj.set_value(min - padding);
+                // If the cursor/selection is to the right of our current view, this is true
+                } else if max > hadj.get_value() + hadj.get_page_size()
+                    && hadj.get_page_size() != 0.0
+                {
+                    hadj.set_value(max - hadj.get_page_size() + padding);
+                }
+            } else {
+                warn!("{}", gettext("Couldn't update hscrollbar value because I couldn't get the line to scroll to!"));
+            }
+        }
+    }
+
+    /// Handles button presses such as Shift, Ctrl etc. and primary pasting (i.e. via Ctrl+V, not
+    /// via middle mouse click).
+    pub fn handle_button_press(&self, eb: &EventButton) -> Inhibit {
+        trace!(
+            "{} 'button_press' {} '{}': {:?}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            eb
+        );
+        self.view_item.ev_scrolled_window.grab_focus();
+
+        let (x, y) = eb.get_position();
+        let (col, line) = self.da_px_to_cell(x, y);
+        self.block_selection_start.set(None);
+        self.primary_paste_origin.set(None);
+        self.undo_grouping.borrow_mut().interrupt();
+
+        match eb.get_button() {
+            1 => {
+                if eb
+                    .get_state()
+                    .contains(ModifierType::CONTROL_MASK | ModifierType::MOD1_MASK)
+                {
+                    if !self.go_to_definition_at(line, col as usize) {
+                        self.core.gesture_point_select(&self.view_id, line, col);
+                    }
+                } else if eb.get_state().contains(ModifierType::MOD1_MASK) {
+                    let x = x + self.view_item.hadj.get_value();
+                    self.block_selection_start.set(Some((line, x)));
+                    self.core.gesture_point_select(&self.view_id, line, col);
+                } else if eb.get_state().contains(ModifierType::SHIFT_MASK) {
+                    self.core.gesture_range_select(&self.view_id, line, col);
+                } else if eb.get_state().contains(ModifierType::CONTROL_MASK) {
+                    if let Some(link) = self.link_at(line, col) {
+                        self.open_link(link);
+                    } else {
+                        self.core.gesture_toggle_sel(&self.view_id, line, col);
+                    }
+                } else if let Some((line, color)) = self.color_chip_at(x, y) {
+                    self.edit_color(line, color);
+                } else if eb.get_event_type() == EventType::DoubleButtonPress {
+                    self.select_word_at(line, col as usize);
+                } else if eb.get_event_type() == EventType::TripleButtonPress {
+                    self.core.gesture_line_select(&self.view_id, line, col);
+                } else {
+                    self.core.gesture_point_select(&self.view_id, line, col);
+                }
+            }
+            2 => {
+                self.do_paste_primary(&self.view_id, line, col);
+            }
+            _ => {}
+        }
+        Inhibit(false)
+    }
+
+    /// Shows the context menu for the right click `eb`. Clicking outside of the selection moves
+    /// the cursor there first, like in other editors.
+    pub fn show_context_menu(edit_view: &Rc<RefCell<Self>>, eb: &EventButton) {
+        let ev = edit_view.borrow();
+        ev.view_item.ev_scrolled_window.grab_focus();
+        let (x, y) = eb.get_position();
+        let (col, line) = ev.da_px_to_cell(x, y);
+        let has_selection = ev.line_cache.get_line(line).map_or(false, |l| {
+            selection_ranges(&l.styles)
+                .iter()
+                .any(|range| range.start <= col as usize && col as usize <= range.end)
+        });
+        if !has_selection {
+            ev.core.gesture_point_select(&ev.view_id, line, col);
+        }
+        let link = ev.link_at(line, col);
+        let misspelling = ev.misspelling_at(line, col as usize);
+        let editable = !ev.read_only;
+        let view_id = ev.view_id.clone();
+
+        let menu = Menu::new();
+        let add_item = |label: &str, sensitive: bool, f: Box<dyn Fn(&EditView)>| {
+            let item = MenuItem::new_with_mnemonic(label);
+            item.set_sensitive(sensitive);
+            item.connect_activate(enclose!((edit_view) move |_| f(&edit_view.borrow())));
+            menu.append(&item);
+        };
+        let add_separator = || menu.append(&SeparatorMenuItem::new());
+
+        if let Some((range, word, suggestions)) = misspelling {
+            if suggestions.is_empty() {
+                add_item(&gettext("No Suggestions"), false, Box::new(|_| {}));
+            }
+            for suggestion in suggestions {
+                // Suggestions aren't mnemonics
+                let label = suggestion.replace('_', "__");
+                let range = range.clone();
+                add_item(
+                    &label,
+                    editable,
+                    Box::new(move |ev| ev.replace_range(line, range.clone(), &suggestion)),
+                );
+            }
+            add_item(
+                &gettext("_Add to Dictionary"),
+                true,
+                Box::new(move |ev| {
+                    ev.main_state
+                        .borrow()
+                        .spell_checker
+                        .add_to_dictionary(&word)
+                }),
+            );
+            add_separator();
+        }
+        if let Some(link) = link {
+            let label = match link {
+                Link::Url(_) => gettext("_Open Link"),
+                Link::File(..) => gettext("_Open File"),
+            };
+            add_item(&label, true, Box::new(move |ev| ev.open_link(link.clone())));
+            add_separator();
+        }
+        add_item(
+            &gettext("_Undo"),
+            editable,
+            Box::new(enclose!((view_id) move |ev| ev.core.undo(&view_id))),
+        );
+        add_item(
+            &gettext("_Redo"),
+            editable,
+            Box::new(enclose!((view_id) move |ev| ev.core.redo(&view_id))),
+        );
+        add_separator();
+        add_item(
+            &gettext("Cu_t"),
+            editable && has_selection,
+            Box::new(enclose!((view_id) move |ev| ev.do_cut(&view_id))),
+        );
+        add_item(
+            &gettext("_Copy"),
+            has_selection,
+            Box::new(enclose!((view_id) move |ev| ev.do_copy(&view_id))),
+        );
+        add_item(
+            &gettext("_Paste"),
+            editable,
+            Box::new(enclose!((view_id) move |ev| ev.do_paste(&view_id))),
+        );
+        add_separator();
+        add_item(
+            &gettext("Select _All"),
+            true,
+            Box::new(move |ev| ev.core.select_all(&view_id)),
+        );
+        add_separator();
+        add_item(
+            &gettext("Change _Syntax…"),
+            true,
+            Box::new(|ev| ev.view_item.statusbar.show_syntax_menu()),
+        );
+
+        menu.set_attach_widget(Some(&ev.view_item.edit_area));
+        // Items are activated after the menu has been deactivated
+        menu.connect_deactivate(|menu| {
+            let menu = menu.clone();
+            glib::idle_add_local(move || {
+                menu.destroy();
+                source::Continue(false)
+            });
+        });
+        menu.show_all();
+        #[cfg(feature = "gtk_v3_22")]
+        menu.popup_at_pointer(Some(&**eb));
+        #[cfg(not(feature = "gtk_v3_22"))]
+        menu.popup_easy(eb.get_button(), eb.get_time());
+    }
+
+    /// Sets the function which opens files the user Ctrl+clicks, with the (zero based) line to go
+    /// to if the path is followed by one (e.g. `src/main.rs:12`)
+    pub fn connect_open_file<F: Fn(&Path, Option<u64>) + 'static>(&self, f: F) {
+        self.file_opener.replace(Some(Rc::new(f)));
+    }
+
+    /// Sets the function which goes to the definition of a word the user Ctrl+Alt+clicks (or
+    /// presses F12 on). It's called with the word and the file of the document and returns
+    /// whether it can look up definitions for the document, otherwise the click moves the cursor.
+    pub fn connect_go_to_definition<F: Fn(&str, Option<&str>) -> bool + 'static>(&self, f: F) {
+        self.definition_finder.replace(Some(Rc::new(f)));
+    }
+
+    /// Goes to the definition of the word at the cursor. Returns whether definitions can be looked
+    /// up for the document.
+    pub fn go_to_definition(&self) -> bool {
+        match self.cursor_position() {
+            Some((line, col)) => self.go_to_definition_at(line, col),
+            None => false,
+        }
+    }
+
+    /// Goes to the definition of the word at byte `col` of `line`
+    fn go_to_definition_at(&self, line: u64, col: usize) -> bool {
+        let word = match self
+            .line_cache
+            .get_line(line)
+            .and_then(|line| occurrences::word_at(line.text(), col))
+        {
+            Some(word) => word.to_string(),
+            None => return false,
+        };
+        match self.definition_finder.borrow().clone() {
+            Some(find) => find(&word, self.file_name.as_ref().map(String::as_str)),
+            None => false,
+        }
+    }
+
+    /// Sets the function which switches to the EditView of a quick mark which isn't in this one
+    pub fn connect_jump_to_quick_mark<F: Fn(char) + 'static>(&self, f: F) {
+        self.quick_mark_jumper.replace(Some(Rc::new(f)));
+    }
+
+    /// Sets the function which is told about the lines with matches of the search, e.g. to list
+    /// them in a panel. It's called with no lines once the search is closed.
+    pub fn connect_search_results<F: Fn(Vec<u64>) + 'static>(&self, f: F) {
+        self.search_results_handler.replace(Some(Rc::new(f)));
+    }
+
+    /// Sets the function which is called with the file name and language of the document after
+    /// it has been saved or its language has changed (e.g. once it's known after opening the
+    /// file), to check the file for problems
+    pub fn connect_check_file<F: Fn(&str, &str) + 'static>(&self, f: F) {
+        self.file_checker.replace(Some(Rc::new(f)));
+    }
+
+    /// Checks the file for problems with the function set by `connect_check_file`
+    pub fn check_file(&self) {
+        if let Some(file_name) = &self.file_name {
+            if let Some(checker) = self.file_checker.borrow().clone() {
+                checker(file_name, &self.language());
+            }
+        }
+    }
+
+    /// Shows how many errors and warnings have been found in the file in the statusbar
+    pub fn set_diagnostic_counts(&self, errors: usize, warnings: usize) {
+        let label = &self.view_item.statusbar.diagnostics_label;
+        if errors == 0 && warnings == 0 {
+            label.hide();
+        } else {
+            label.set_text(&format!(
+                "{}: {}  {}: {}",
+                gettext("Errors"),
+                errors,
+                gettext("Warnings"),
+                warnings
+            ));
+            label.show();
+        }
+    }
+
+    /// Remembers the cursor position as the quick mark `name`
+    pub fn set_quick_mark(&self, name: char) {
+        if let Some((line, col)) = self.cursor_position() {
+            debug!("{} '{}'", gettext("Setting quick mark"), name);
+            let mark = QuickMark {
+                view_id: self.view_id.clone(),
+                line,
+                col,
+            };
+            self.main_state.borrow_mut().quick_marks.set(name, mark);
+        }
+    }
+
+    /// Moves the cursor to the quick mark `name`. If it's in another EditView, the function set
+    /// with `connect_jump_to_quick_mark` has to switch to that first.
+    pub fn jump_to_quick_mark(&self, name: char) {
+        let mark = match self.main_state.borrow().quick_marks.get(name) {
+            Some(mark) => mark.clone(),
+            None => return,
+        };
+        if mark.view_id == self.view_id {
+            self.goto_quick_mark(&mark);
+        } else if let Some(jump) = self.quick_mark_jumper.borrow().clone() {
+            // The other EditView can only be borrowed once we're done with this one
+            glib::idle_add_local(move || {
+                jump(name);
+                source::Continue(false)
+            });
+        }
+    }
+
+    /// Moves the caret back to where it jumped from last (Alt+-)
+    pub fn caret_back(&self) {
+        let pos = self.caret_history.borrow_mut().back();
+        if let Some((line, col)) = pos {
+            self.core
+                .gesture_point_select(&self.view_id, line, col as u64);
+        }
+    }
+
+    /// Moves the caret to where `caret_back` moved it away from (Alt+=)
+    pub fn caret_forward(&self) {
+        let pos = self.caret_history.borrow_mut().forward();
+        if let Some((line, col)) = pos {
+            self.core
+                .gesture_point_select(&self.view_id, line, col as u64);
+        }
+    }
+
+    /// Moves the cursor to `mark`, which is in this EditView
+    pub fn goto_quick_mark(&self, mark: &QuickMark) {
+        self.core
+            .gesture_point_select(&self.view_id, mark.line, mark.col as u64);
+    }
+
+    /// Returns the URL or path of an existing file at `col` of `line`. Relative paths are
+    /// relative to the directory of the document.
+    fn link_at(&self, line: u64, col: u64) -> Option<Link> {
+        let text = self.line_cache.get_line(line)?.text();
+        links::link_at(text, col as usize, &self.base_dir()?, Path::is_file)
+    }
+
+    /// The directory relative paths in the document are relative to, i.e. the one the document is
+    /// in or the working directory if it hasn't been saved yet
+    fn base_dir(&self) -> Option<PathBuf> {
+        self.file_name
+            .as_ref()
+            .and_then(|f| Path::new(f).parent())
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+    }
+
+    fn open_link(&self, link: Link) {
+        debug!("{}: {:?}", gettext("Opening link"), link);
+        match link {
+            Link::Url(url) => {
+                if let Err(e) =
+                    gio::AppInfo::launch_default_for_uri(&url, None::<&gio::AppLaunchContext>)
+                {
+                    warn!("{} '{}': {}", gettext("Failed to open URL"), url, e);
+                }
+            }
+            Link::File(path, line) => {
+                // Line numbers after paths start at 1
+                let line = line.map(|line| line.saturating_sub(1));
+                if let Some(open_file) = self.file_opener.borrow().clone() {
+                    // Switching to the file's tab may need this EditView, which is borrowed while
+                    // we handle the click
+                    glib::idle_add_local(move || {
+                        open_file(&path, line);
+                        source::Continue(false)
+                    });
+                }
+            }
+        }
+    }
+
+    /// Handle selecting line(s) by dragging the mouse across them while having the left mouse
+    /// button clicked.
+    pub fn handle_drag(&self, em: &EventMotion) -> Inhibit {
+        if em.get_state().contains(ModifierType::BUTTON1_MASK) {
+            let (x, y) = em.get_position();
+            let (col, line) = self.da_px_to_cell(x, y);
+            match self.block_selection_start.get() {
+                Some(start) => {
+                    self.select_block(start, (line, x + self.view_item.hadj.get_value()))
+                }
+                None => self.core.drag(&self.view_id, line, col),
+            }
+        }
+        Inhibit(false)
+    }
+
+    /// Selects the rectangle between `start` and `end`, given as line and x position (in document
+    /// coordinates), by selecting the part of every line that lies within it (Alt+drag).
+    fn select_block(&self, start: (u64, f64), end: (u64, f64)) {
+        let (start_line, start_x) = start;
+        let (end_line, end_x) = end;
+        let lines: Vec<u64> = if start_line <= end_line {
+            (start_line..=end_line).collect()
+        } else {
+            (end_line..=start_line).rev().collect()
+        };
+
+        for (i, line) in lines.into_iter().enumerate() {
+            let start_col = self.index_at_x(line, start_x);
+            if i == 0 {
+                self.core
+                    .gesture_point_select(&self.view_id, line, start_col);
+            } else {
+                self.core
+                    .gesture_add_point_select(&self.view_id, line, start_col);
+            }
+            self.core
+                .drag(&self.view_id, line, self.index_at_x(line, end_x));
+        }
+    }
+
+    /// Handles all (special) key press events, e.g. copy, pasting, PgUp/Down etc.
+    // Allow this to be a long function since splitting up the matching into multiple functions
+    // would be a pain
+    #[allow(clippy::cyclomatic_complexity)]
+    pub(crate) fn handle_key_press_event(&self, ek: &EventKey) -> Inhibit {
+        trace!(
+            "{} 'key_press_event' {} '{}': {:?}",
+            gettext("Handling"),
+            gettext("for EditView"),
+            self.view_id,
+            ek
+        );
+        debug!(
+            "{}: {}={:?}, {}={:?}, {}={:?} {}={:?} {}={:?}",
+            gettext("Processing key press"),
+            gettext("value"),
+            ek.get_keyval(),
+            gettext("state"),
+            ek.get_state(),
+            gettext("length"),
+            ek.get_length(),
+            gettext("group"),
+            ek.get_group(),
+            gettext("unicode"),
+            ::gdk::keyval_to_unicode(ek.get_keyval())
+        );
+        let view_id = &self.view_id;
+        let ch = ::gdk::keyval_to_unicode(ek.get_keyval());
+
+        let alt = ek.get_state().contains(ModifierType::MOD1_MASK);
+        let ctrl = ek.get_state().contains(ModifierType::CONTROL_MASK);
+        let meta = ek.get_state().contains(ModifierType::META_MASK);
+        let shift = ek.get_state().contains(ModifierType::SHIFT_MASK);
+        let norm = !alt && !ctrl && !meta;
+        // Holding Ctrl before pressing Z mustn't forget where the cursor was before the paste
+        let paste_origin = if is_modifier_key(ek.get_keyval()) {
+            self.primary_paste_origin.get()
+        } else {
+            self.primary_paste_origin.take()
+        };
+        // Typed text is inserted once the input method commits it, other keys (e.g. moving the
+        // cursor) end the current undo group
+        if !is_modifier_key(ek.get_keyval()) && !(norm && ch.map_or(false, |c| c >= ' ')) {
+            self.undo_grouping.borrow_mut().interrupt();
+        }
+
+        if self.completion.is_visible() && self.handle_completion_key(ek) {
+            return Inhibit(true);
+        }
+
+        if self.handle_vim_key(ek) {
+            return Inhibit(true);
+        }
+
+        if self.read_only && modifies_document(ek.get_keyval(), ch, alt, ctrl, norm) {
+            debug!("{}", gettext("Ignoring key press in read-only EditView"));
+            return Inhibit(true);
+        }
+
+        match ek.get_keyval() {
+            key::Delete if norm => self.core.delete_forward(view_id),
+            key::BackSpace if norm => {
+                if !self.end_list() {
+                    self.core.delete_backward(view_id);
+                }
+            }
+            key::BackSpace if ctrl => self.core.delete_word_backward(view_id),
+            key::Return | key::KP_Enter => {
+                if norm && !shift {
+                    self.expand_abbreviation();
+                }
+                if !(norm && !shift && self.continue_list()) {
+                    self.core.insert_newline(&view_id);
+                }
+            }
+            key::Tab if norm && !shift => {
+                if !self.move_to_table_cell(false) {
+                    self.core.insert_tab(view_id);
+                }
+            }
+            key::Tab | key::ISO_Left_Tab if norm && shift => {
+                if !self.move_to_table_cell(true) {
+                    self.core.outdent(view_id);
+                }
+            }
+            key::Up if alt && !ctrl && shift => self.core.add_selection_above(view_id),
+            key::Down if alt && !ctrl && shift => self.core.add_selection_below(view_id),
+            key::Left if alt && !ctrl && shift => {
+                self.core.move_left_and_modify_selection(view_id);
+            }
+            key::Right if alt && !ctrl && shift => {
+                self.core.move_right_and_modify_selection(view_id);
+            }
+            key::Up if alt && !ctrl && !shift => self.move_lines(true),
+            key::Down if alt && !ctrl && !shift => self.move_lines(false),
+            key::Up if norm && !shift => self.core.move_up(view_id),
+            key::Down if norm && !shift => self.core.move_down(view_id),
+            key::Left if norm && !shift => self.core.move_left(view_id),
+            key::Right if norm && !shift => self.core.move_right(view_id),
+            key::Up if norm && shift => {
+                self.core.move_up_and_modify_selection(view_id);
+            }
+            key::Down if norm && shift => {
+                self.core.move_down_and_modify_selection(view_id);
+            }
+            key::Left if norm && shift => {
+                self.core.move_left_and_modify_selection(view_id);
+            }
+            key::Right if norm && shift => {
+                self.core.move_right_and_modify_selection(view_id);
+            }
+            key::Left if ctrl && !shift => self.move_word(false, false),
+            key::Right if ctrl && !shift => self.move_word(true, false),
+            key::Left if ctrl && shift => self.move_word(false, true),
+            key::Right if ctrl && shift => self.move_word(true, true),
+            key::Home if norm && !shift => {
+                self.core.move_to_left_end_of_line(view_id);
+            }
+            key::End if norm && !shift => {
+                self.core.move_to_right_end_of_line(view_id);
+            }
+            key::Home if norm && shift => {
+                self.core
+                    .move_to_left_end_of_line_and_modify_selection(view_id);
+            }
+            key::End if norm && shift => {
+                self.core
+                    .move_to_right_end_of_line_and_modify_selection(view_id);
+            }
+            key::Home if ctrl && !shift => {
+                self.core.move_to_beginning_of_document(view_id);
+            }
+            key::End if ctrl && !shift => {
+                self.core.move_to_end_of_document(view_id);
+            }
+            key::Home if ctrl && shift => {
+                self.core
+                    .move_to_beginning_of_document_and_modify_selection(view_id);
+            }
+            key::End if ctrl && shift => {
+                self.core
+                    .move_to_end_of_document_and_modify_selection(view_id);
+            }
+            key::Page_Up if norm => self.move_page(false, shift),
+            key::Page_Down if norm => self.move_page(true, shift),
+            key::Escape => {
+                self.stop_search();
+            }
+            key::Insert | key::KP_Insert if norm && !shift => self.toggle_overwrite(),
+            _ => {
+                if let Some(ch) = ch {
+                    match ch {
+                        'a' if ctrl => {
+                            self.core.select_all(view_id);
+                        }
+                        'c' if ctrl => {
+                            self.do_copy(view_id);
+                        }
+                        'v' if ctrl => {
+                            self.do_paste(view_id);
+                        }
+                        'x' if ctrl => {
+                            self.do_cut(view_id);
+                        }
+                        'z' if ctrl => {
+                            self.core.undo(view_id);
+                            if let Some((line, col)) = paste_origin {
+                                self.core.gesture_point_select(view_id, line, col);
+                            }
+                        }
+                        'Z' if ctrl && shift => {
+                            self.core.redo(view_id);
+                        }
+                        ' ' if ctrl => {
+                            // Fall back to the words of the open documents if there's no path
+                            if self.path_completions().is_empty() {
+                                self.complete(CompletionSource::Word);
+                            } else {
+                                self.complete(CompletionSource::Path);
+                            }
+                        }
+                        c if (norm) && c >= '\u{0020}' => {
+                            debug!("inserting key");
+                            self.im_context.filter_keypress(ek);
+                        }
+                        _ => {
+                            warn!("unhandled key: {:?}", ch);
+                        }
+                    }
+                } else {
+                    debug!("Inserting non char key");
+                    self.im_context.filter_keypress(ek);
+                }
+            }
+        };
+        Inhibit(true)
+    }
+
+    /// Copies text to the clipboard
+    fn do_cut(&self, view_id: &str) {
+        debug!("{}", gettext("Cutting text"));
+
+        let (clipboard_tx, clipboard_rx) =
+            MainContext::channel::<Option<String>>(glib::PRIORITY_HIGH);
+        let main_context = MainContext::default();
+
+        clipboard_rx.attach(Some(&main_context), move |text_opt| {
+            if let Some(text) = text_opt {
+                Clipboard::get(&SELECTION_CLIPBOARD).set_text(&text);
+            }
+            source::Continue(false)
+        });
+
+        self.core.cut(view_id, clipboard_tx);
+    }
+
+    /// Copies text to the clipboard
+    fn do_copy(&self, view_id: &str) {
+        debug!("{}", gettext("Copying text"));
+        let (clipboard_tx, clipboard_rx) =
+            MainContext::channel::<Option<String>>(glib::PRIORITY_HIGH);
+        let main_context = MainContext::default();
+
+        clipboard_rx.attach(Some(&main_context), move |text_opt| {
+            if let Some(text) = text_opt {
+                Clipboard::get(&SELECTION_CLIPBOARD).set_text(&text);
+            }
+            source::Continue(false)
+        });
+
+        self.core.copy(view_id, clipboard_tx);
+    }
+
+    /// Pastes text from the clipboard into the EditView
+    fn do_paste(&self, view_id: &str) {
+        // if let Some(text) = Clipboard::get(&SELECTION_CLIPBOARD).wait_for_text() {
+        //     self.core.insert(view_id, &text);
+        // }
+        debug!("{}", gettext("Pasting text"));
+        let view_id2 = view_id.to_string().clone();
+        let core = self.core.clone();
+        let file_name = self.file_name.clone();
+        let language = self.language();
+        let parent = self
+            .root_widget
+            .get_toplevel()
+            .and_then(|w| w.downcast::<Window>().ok());
+        Clipboard::get(&SELECTION_CLIPBOARD).request_text(move |clipboard, text| {
+            if let Some(clip_content) = text {
+                core.insert(&view_id2, &clip_content);
+            } else {
+                paste_non_text(
+                    clipboard,
+                    core.clone(),
+                    view_id2.clone(),
+                    file_name.clone(),
+                    &language,
+                    parent.clone(),
+                );
+            }
+        });
+    }
+
+    fn do_paste_primary(&self, view_id: &str, line: u64, col: u64) {
+        if self.read_only {
+            return;
+        }
+        // if let Some(text) = Clipboard::get(&SELECTION_PRIMARY).wait_for_text() {
+        //     self.core.insert(view_id, &text);
+        // }
+        debug!("{}", gettext("Pasting primary text"));
+        let view_id2 = view_id.to_string().clone();
+        let core = self.core.clone();
+        let origin = self.cursor_position().map(|(line, col)| (line, col as u64));
+        let primary_paste_origin = self.primary_paste_origin.clone();
+        Clipboard::get(&SELECTION_PRIMARY).request_text(move |_, text| {
+            core.gesture_point_select(&view_id2, line, col);
+            if let Some(clip_content) = text {
+                // Unlike inserted text, pasted text is an undo group of its own in xi-editor, so
+                // it isn't undone together with what the user has typed before
+                core.paste(&view_id2, &clip_content);
+                primary_paste_origin.set(origin);
+            }
+        });
+    }
+
+    /// Resize the EditView
+    pub(crate) fn do_resize(&self, view_id: &str, width: i32, height: i32) {
+        trace!("{} '{}'", gettext("Resizing EditView"), view_id);
+        self.core.resize(view_id, width, height);
+    }
+
+    /// Moves the selected lines, or the line with the cursor, up or down by a line (Alt+Up/Down).
+    /// xi-editor can't move lines, so they're replaced together with the line they're moved past,
+    /// which makes it a single edit.
+    fn move_lines(&self, up: bool) {
+        if self.read_only {
+            return;
+        }
+        let (first, lines) = match self.selected_lines() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let last = first + lines.len() as u64 - 1;
+        let other = if up {
+            match first.checked_sub(1) {
+                Some(other) => other,
+                None => return,
+            }
+        } else {
+            last + 1
+        };
+        let other_text = match self.line_cache.get_line(other) {
+            Some(line) => line.text(),
+            None => return,
+        };
+        let has_selection = (first..=last)
+            .filter_map(|n| self.line_cache.get_line(n))
+            .any(|line| selection_ranges(&line.styles).iter().any(|r| !r.is_empty()));
+        let cursor = self.cursor_position();
+
+        let stripped: Vec<&str> = lines.iter().map(|&line| strip_line_ending(line)).collect();
+        let new_lines = line_transforms::move_lines(&stripped, strip_line_ending(other_text), up);
+        let (replaced_first, replaced) = if up {
+            (
+                other,
+                Some(other_text)
+                    .into_iter()
+                    .chain(lines)
+                    .collect::<Vec<&str>>(),
+            )
+        } else {
+            (first, lines.into_iter().chain(Some(other_text)).collect())
+        };
+        if !self.replace_lines(replaced_first, &replaced, &new_lines) {
+            return;
+        }
+
+        // Keep the moved lines selected, or the cursor where it was in them
+        let (new_first, new_last) = if up {
+            (first - 1, last - 1)
+        } else {
+            (first + 1, last + 1)
+        };
+        if has_selection {
+            let last_len = stripped.last().map_or(0, |line| line.len());
+            self.core.gesture_point_select(&self.view_id, new_first, 0);
+            self.core
+                .gesture_range_select(&self.view_id, new_last, last_len as u64);
+        } else if let Some((line, col)) = cursor {
+            let line = if up { line - 1 } else { line + 1 };
+            self.core
+                .gesture_point_select(&self.view_id, line, col as u64);
+        }
+    }
+
+    /// Duplicates the line with the cursor, or the selected lines (Ctrl+Shift+D)
+    pub fn duplicate_line(&self) {
+        if !self.read_only {
+            self.core.duplicate_line(&self.view_id);
+        }
+    }
+
+    /// Joins the selected lines, or the current line with the next one (Ctrl+J). xi-editor can't
+    /// join lines, so they're replaced with the joined line.
+    pub fn join_lines(&self) {
+        if self.read_only {
+            return;
+        }
+        let (first, mut lines) = match self.selected_lines() {
+            Some(selected) => selected,
+            None => return,
+        };
+        if lines.len() == 1 {
+            match self.line_cache.get_line(first + 1) {
+                Some(next) => lines.push(next.text()),
+                None => return,
+            }
+        }
+        let stripped: Vec<&str> = lines.iter().map(|&line| strip_line_ending(line)).collect();
+        let joined = line_transforms::join_lines(&stripped);
+        self.replace_lines(first, &lines, &[joined]);
+    }
+
+    /// Swaps the characters around the cursor, or the selections if there are multiple (Ctrl+T)
+    pub fn transpose(&self) {
+        if !self.read_only {
+            self.core.transpose(&self.view_id);
+        }
+    }
+
+    /// Returns the line and (byte) column of the first cursor, if it's in the line cache
+    fn cursor_position(&self) -> Option<(u64, usize)> {
+        let n_invalid_before = self.line_cache.n_invalid_before;
+        self.line_cache
+            .lines
+            .iter()
+            .enumerate()
+            .find_map(|(ix, line)| {
+                let col = *line.as_ref()?.cursor().first()?;
+                Some((n_invalid_before + ix as u64, col as usize))
+            })
+    }
+
+    /// Selects the word at the cursor, or if something is selected already adds a cursor at the
+    /// next occurrence of the selected text (Ctrl+D)
+    pub fn select_next_occurrence(&self) {
+        let has_selection = self.line_cache.lines.iter().flatten().any(|line| {
+            selection_ranges(&line.styles)
+                .iter()
+                .any(|range| !range.is_empty())
+        });
+        if has_selection {
+            self.core.selection_for_find(&self.view_id, true);
+            self.core.find_next_add_selection(&self.view_id, true);
+        } else if let Some((line, col)) = self.cursor_position() {
+            self.select_word_at(line, col);
+        }
+    }
+
+    /// Selects the word at (byte) column `col` of `line`, finding its boundaries according to
+    /// Unicode. xi-editor only knows ASCII words, we ask it if the line isn't loaded.
+    fn select_word_at(&self, line: u64, col: usize) {
+        if let Some(l) = self.line_cache.get_line(line) {
+            let range = words::word_at(strip_line_ending(l.text()), col);
+            self.core
+                .gesture_point_select(&self.view_id, line, range.start as u64);
+            self.core
+                .gesture_range_select(&self.view_id, line, range.end as u64);
+        } else {
+            self.core
+                .gesture_word_select(&self.view_id, line, col as u64);
+        }
+    }
+
+    /// How many cursors (and so selections) there are in the lines of the line cache, which are
+    /// the ones around the visible part of the document
+    pub fn cursor_count(&self) -> usize {
+        self.line_cache
+            .lines
+            .iter()
+            .flatten()
+            .map(|line| line.cursor().len())
+            .sum()
+    }
+
+    /// Moves the cursor to the end of the next word (or the start of the previous one if
+    /// `forward` is false), extending the selection if `modify` is true. Words are found according
+    /// to Unicode within the line, xi-editor moves across lines and multiple cursors.
+    fn move_word(&self, forward: bool, modify: bool) {
+        let view_id = &self.view_id;
+        let target = self
+            .cursor_position()
+            .filter(|_| self.cursor_count() == 1)
+            .and_then(|(line, col)| {
+                let text = strip_line_ending(self.line_cache.get_line(line)?.text());
+                let col = if forward {
+                    words::next_word_end(text, col)?
+                } else {
+                    words::prev_word_start(text, col)?
+                };
+                Some((line, col as u64))
+            });
+        match (target, forward, modify) {
+            (Some((line, col)), _, false) => self.core.gesture_point_select(view_id, line, col),
+            (Some((line, col)), _, true) => self.core.gesture_range_select(view_id, line, col),
+            (None, true, false) => self.core.move_word_right(view_id),
+            (None, true, true) => self.core.move_word_right_and_modify_selection(view_id),
+            (None, false, false) => self.core.move_word_left(view_id),
+            (None, false, true) => self.core.move_word_left_and_modify_selection(view_id),
+        }
+    }
+
+    /// Searches for the selected text (or the word at the cursor) and goes to its next (or
+    /// previous) occurrence without opening the search bar (Ctrl+F3 and Ctrl+Shift+F3)
+    pub fn find_selection(&self, forward: bool) {
+        let case_sensitive = self.find_replace.case_sensitive_button.get_active();
+        self.core.selection_for_find(&self.view_id, case_sensitive);
+        if forward {
+            self.core.find_next(&self.view_id, Some(true), Some(false));
+        } else {
+            self.core.find_previous(&self.view_id, Some(true));
+        }
+    }
+
+    /// Returns the first line and the rows of the Markdown table `line` is part of, if it is
+    fn table_at(&self, line: u64) -> Option<(u64, Vec<&str>)> {
+        let get_row = |n: u64| {
+            self.line_cache
+                .get_line(n)
+                .map(Line::text)
+                .filter(|text| markdown_table::is_table_row(text))
+        };
+        get_row(line)?;
+
+        let mut first = line;
+        while first > 0 && get_row(first - 1).is_some() {
+            first -= 1;
+        }
+        let rows = (first..)
+            .map(get_row)
+            .take_while(Option::is_some)
+            .flatten()
+            .collect();
+        Some((first, rows))
+    }
+
+    /// The first line and the text of the lines which are selected or have a cursor on them. A
+    /// selection ending at the start of a line doesn't include that line.
+    fn selected_lines(&self) -> Option<(u64, Vec<&str>)> {
+        let is_selected = |line: &Line| {
+            selection_ranges(&line.styles)
+                .iter()
+                .any(|range| !range.is_empty())
+        };
+        let n_invalid_before = self.line_cache.n_invalid_before;
+        let lines: Vec<u64> = self
+            .line_cache
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.as_ref()
+                    .map_or(false, |l| is_selected(l) || !l.cursor().is_empty())
+            })
+            .map(|(ix, _)| n_invalid_before + ix as u64)
+            .collect();
+
+        let first = *lines.first()?;
+        let mut last = *lines.last()?;
+        if last > first {
+            let last_line = self.line_cache.get_line(last)?;
+            if !is_selected(last_line) && last_line.cursor() == [0] {
+                last -= 1;
+            }
+        }
+        let text = (first..=last)
+            .map(|n| self.line_cache.get_line(n).map(Line::text))
+            .collect::<Option<Vec<&str>>>()?;
+        Some((first, text))
+    }
+
+    /// The text and the selection of the document, if xi-editor has sent us all of its lines (see
+    /// `with_text`)
+    pub fn selection_snapshot(&self) -> Option<SelectionSnapshot> {
+        let lines = (0..self.line_cache.height())
+            .map(|n| {
+                let line = self.line_cache.get_line(n)?;
+                Some((line.text().to_string(), selection_ranges(&line.styles)))
+            })
+            .collect::<Option<_>>()?;
+        Some(SelectionSnapshot { lines })
+    }
+
+    /// The first and last (zero based) line which is selected or has a cursor on it, like
+    /// `selected_lines`
+    pub fn selected_line_range(&self) -> Option<(u64, u64)> {
+        let (first, lines) = self.selected_lines()?;
+        Some((first, first + lines.len() as u64 - 1))
+    }
+
+    /// Replaces `lines`, which start at the line `first`, with `new_lines`. Nothing is sent to
+    /// xi-editor if they're equal, so no undo group is created for no-ops. Returns whether the
+    /// lines have been changed.
+    fn replace_lines(&self, first: u64, lines: &[&str], new_lines: &[String]) -> bool {
+        let trim = |line: &str| line.trim_end_matches(|c| c == '\n' || c == '\r').len();
+        let changed = lines.len() != new_lines.len()
+            || lines
+                .iter()
+                .zip(new_lines)
+                .any(|(old, new)| old[..trim(old)] != new[..]);
+        if changed {
+            let last = first + lines.len() as u64 - 1;
+            let last_len = lines.last().map_or(0, |line| trim(line));
+            self.core.gesture_point_select(&self.view_id, first, 0);
+            self.core
+                .gesture_range_select(&self.view_id, last, last_len as u64);
+            let text = new_lines.join("\n");
+            if text.is_empty() {
+                self.core.delete_backward(&self.view_id);
+            } else {
+                self.core.insert(&self.view_id, &text);
+            }
+        }
+        changed
+    }
+
+    /// Replaces the table starting at `first` with `new_rows` and moves the cursor to the start of
+    /// the given cell of it.
+    fn replace_table(
+        &self,
+        first: u64,
+        rows: &[&str],
+        new_rows: &[String],
+        (row, cell): (usize, usize),
+    ) {
+        self.replace_lines(first, rows, new_rows);
+        if let Some(col) = new_rows
+            .get(row)
+            .and_then(|r| markdown_table::cell_start(r, cell))
+        {
+            self.core
+                .gesture_point_select(&self.view_id, first + row as u64, col as u64);
+        }
+    }
+
+    /// Aligns the pipes of the Markdown table the cursor is in
+    pub fn format_table(&self) {
+        if self.read_only {
+            return;
+        }
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return,
+        };
+        if let Some((first, rows)) = self.table_at(line) {
+            let row = (line - first) as usize;
+            let cell = markdown_table::cell_at(rows[row], col);
+            let new_rows = markdown_table::reflow(&rows);
+            self.replace_table(first, &rows, &new_rows, (row, cell));
+        }
+    }
+
+    /// Moves the cursor to the next (or previous) cell of the Markdown table it's in and formats the
+    /// table on the way, so Tab can be used to fill in tables. Tab in the last cell appends a row.
+    /// Returns false if the cursor isn't in a table.
+    fn move_to_table_cell(&self, backwards: bool) -> bool {
+        if self.language() != "Markdown" {
+            return false;
+        }
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return false,
+        };
+        let (first, rows) = match self.table_at(line) {
+            Some(table) => table,
+            None => return false,
+        };
+
+        let row = (line - first) as usize;
+        let cell = markdown_table::cell_at(rows[row], col);
+        let mut new_rows = markdown_table::reflow(&rows);
+        let target = match markdown_table::next_cell(&new_rows, row, cell, backwards) {
+            Some(target) => target,
+            None if backwards => (row, cell),
+            None => {
+                // reflow pads the new row to the width of the table
+                new_rows.push(markdown_table::empty_row(1));
+                let with_new_row: Vec<&str> = new_rows.iter().map(String::as_str).collect();
+                new_rows = markdown_table::reflow(&with_new_row);
+                (new_rows.len() - 1, 0)
+            }
+        };
+        self.replace_table(first, &rows, &new_rows, target);
+        true
+    }
+
+    /// Inserts an empty Markdown table with a header row, `rows` further rows and `columns` columns
+    /// and moves the cursor into its first cell.
+    pub fn insert_table(&self, rows: usize, columns: usize) {
+        if self.read_only {
+            return;
+        }
+        let (line, col) = self.cursor_position().unwrap_or((0, 0));
+        let mut table = markdown_table::new_table(rows, columns);
+        // Tables have to start on a line of their own
+        let first = if col > 0 {
+            table.insert(0, '\n');
+            line + 1
+        } else {
+            line
+        };
+        let first_cell = table
+            .lines()
+            .find(|l| !l.is_empty())
+            .and_then(|l| markdown_table::cell_start(l, 0))
+            .unwrap_or(0);
+        self.core.insert(&self.view_id, &table);
+        self.core
+            .gesture_point_select(&self.view_id, first, first_cell as u64);
+    }
+
+    /// Inserts text the user has typed, replacing straight quotes and dashes with typographic ones
+    /// if the user wants that. In overwrite mode the text replaces the text after the cursor.
+    fn insert_typed(&self, text: &str) {
+        if text == " " {
+            self.expand_abbreviation();
+        }
+        let text = match self.smart_punctuation(text) {
+            Some((replaced, replacement)) => {
+                for _ in 0..replaced {
+                    self.core.delete_backward(&self.view_id);
+                }
+                replacement
+            }
+            None => text,
+        };
+        let timeout = self.main_state.borrow().settings.undo_group_timeout;
+        let starts_group = self.undo_grouping.borrow_mut().starts_group(
+            text,
+            Instant::now(),
+            Duration::from_millis(u64::from(timeout)),
+        );
+        if starts_group {
+            self.end_undo_group();
+        }
+        for _ in 0..self.overwritten_chars(text) {
+            self.core.delete_forward(&self.view_id);
+        }
+        self.core.insert(&self.view_id, text);
+
+        // Typing a '/' may start a path, the completions are shown once xi-editor has sent us the
+        // updated line. While completions are shown they're updated the same way.
+        if self.completion_source.get().is_none() && text.ends_with('/') && self.completes_paths() {
+            self.completion_source.set(Some(CompletionSource::Path));
+        }
+    }
+
+    /// Moves the cursor a page up or down, extending the selection if `modify_selection` is true.
+    /// How far depends on the `page_scroll_percent` setting, and if `page_keeps_caret` is set the
+    /// view is scrolled by as much so the cursor stays in the same place on screen. Otherwise
+    /// xi-editor scrolls to the cursor once it has moved.
+    fn move_page(&self, down: bool, modify_selection: bool) {
+        let (percent, keeps_caret) = {
+            let settings = &self.main_state.borrow().settings;
+            (settings.page_scroll_percent, settings.page_keeps_caret)
+        };
+        let view_id = &self.view_id;
+        if percent >= 100 && !keeps_caret {
+            match (down, modify_selection) {
+                (false, false) => self.core.page_up(view_id),
+                (true, false) => self.core.page_down(view_id),
+                (false, true) => self.core.page_up_and_modify_selection(view_id),
+                (true, true) => self.core.page_down_and_modify_selection(view_id),
+            }
+            return;
+        }
+
+        let vadj = &self.view_item.vadj;
+        let font_height = self.edit_font.font_height;
+        let visible_rows = vadj.get_page_size() / font_height;
+        let rows = ((visible_rows * f64::from(percent) / 100.0) as u64).max(1);
+        // Moving line by line keeps the column the cursor was in, like the arrow keys do
+        for _ in 0..rows {
+            match (down, modify_selection) {
+                (false, false) => self.core.move_up(view_id),
+                (true, false) => self.core.move_down(view_id),
+                (false, true) => self.core.move_up_and_modify_selection(view_id),
+                (true, true) => self.core.move_down_and_modify_selection(view_id),
+            }
+        }
+        if keeps_caret {
+            // The adjustment keeps the value within the document
+            let delta = rows as f64 * font_height;
+            let value = vadj.get_value();
+            vadj.set_value(if down { value + delta } else { value - delta });
+        }
+    }
+
+    /// Makes xi-editor start a new undo group for the next insertion
+    fn end_undo_group(&self) {
+        self.core
+            .end_undo_group(&self.view_id, self.cursor_at_end());
+    }
+
+    /// Whether a cursor is at the end of the document. If the last line isn't in the line cache
+    /// the cursors aren't there either.
+    fn cursor_at_end(&self) -> bool {
+        let last_line = self.line_cache.height().saturating_sub(1);
+        self.line_cache.get_line(last_line).map_or(false, |line| {
+            let len = line
+                .text()
+                .trim_end_matches(|c| c == '\n' || c == '\r')
+                .len();
+            line.cursor().contains(&(len as u64))
+        })
+    }
+
+    /// Whether typing paths offers completions of them in the language of the document
+    fn completes_paths(&self) -> bool {
+        let language = self.language();
+        self.main_state
+            .borrow()
+            .settings
+            .path_completion_languages
+            .iter()
+            .any(|l| *l == language)
+    }
+
+    /// Shows the completions from `source` of the text in front of the cursor, or hides the
+    /// completion popup if there are none
+    fn complete(&self, source: CompletionSource) {
+        // Read-only documents can't be completed in
+        if self.read_only {
+            self.hide_completion();
+            return;
+        }
+        let completions = match source {
+            CompletionSource::Path => self.path_completions(),
+            CompletionSource::Word => self.word_completions(),
+        };
+        match self.cursor_rect() {
+            Some(rect) if !completions.is_empty() => {
+                self.completion_source.set(Some(source));
+                self.completion.show(completions, &rect);
+            }
+            _ => self.hide_completion(),
+        }
+    }
+
+    fn hide_completion(&self) {
+        self.completion_source.set(None);
+        self.completion.hide();
+    }
+
+    /// Returns the completions of the path in front of the cursor
+    fn path_completions(&self) -> Vec<Completion> {
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return Vec::new(),
+        };
+        let text = self.line_cache.get_line(line).map_or("", Line::text);
+        match (path_completion::path_before(text, col), self.base_dir()) {
+            (Some(path), Some(base_dir)) => {
+                path_completion::complete(path, &base_dir, path_completion::read_dir)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the completions of the word in front of the cursor with the words of the open
+    /// documents, the ones close to the cursor first
+    fn word_completions(&self) -> Vec<Completion> {
+        let (cursor_line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return Vec::new(),
+        };
+        let text = self.line_cache.get_line(cursor_line).map_or("", Line::text);
+        let prefix = match word_completion::word_before(text, col) {
+            Some(prefix) => prefix,
+            None => return Vec::new(),
+        };
+        let lines: Vec<(u64, &str)> = (0..self.line_cache.height())
+            .filter_map(|ix| self.line_cache.get_line(ix).map(|line| (ix, line.text())))
+            .collect();
+        word_completion::complete(
+            prefix,
+            &lines,
+            cursor_line,
+            &self.main_state.borrow().buffer_words,
+            &self.view_id,
+        )
+    }
+
+    /// Remembers the words of the document for completing words in the other documents, and hides
+    /// the completion popup
+    pub fn handle_focus_out(&self) {
+        self.hide_completion();
+        if let Some(text) = self.document_text() {
+            self.main_state
+                .borrow_mut()
+                .buffer_words
+                .set(&self.view_id, word_completion::count_words(&text));
+        }
+    }
+
+    /// Handles the keys which choose a completion while the completion popup is shown. Returns
+    /// whether the key has been handled.
+    fn handle_completion_key(&self, ek: &EventKey) -> bool {
+        if self.read_only {
+            return false;
+        }
+        match ek.get_keyval() {
+            key::Up => self.completion.select_next(true),
+            key::Down => self.completion.select_next(false),
+            key::Return | key::KP_Enter | key::Tab => {
+                if let Some(completion) = self.completion.take_selected() {
+                    self.core.insert(&self.view_id, &completion.insert);
+                    // Completing a directory offers the entries of that one next
+                    if completion.insert.ends_with('/') {
+                        return true;
+                    }
+                }
+                self.hide_completion();
+            }
+            key::Escape => self.hide_completion(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns where the first cursor is, in coordinates of the edit area
+    fn cursor_rect(&self) -> Option<gdk::Rectangle> {
+        let (line_num, col) = self.cursor_position()?;
+        let line = self.line_cache.get_line(line_num)?;
+        let pango_ctx = self.view_item.get_pango_ctx();
+        let layout = self.create_layout_for_line(&pango_ctx, line, &self.get_tabs());
+        let pos = layout.index_to_pos(col as i32);
+        let x = f64::from(pos.x / pango::SCALE) - self.view_item.hadj.get_value();
+        let y = self.edit_font.font_height * self.folds.row_of_line(line_num) as f64
+            - self.view_item.vadj.get_value();
+        Some(gdk::Rectangle {
+            x: x as i32,
+            y: y as i32,
+            width: 1,
+            height: self.edit_font.font_height as i32,
+        })
+    }
+
+    /// How many chars after the cursor typing `text` overwrites. Overwriting stops at the end of
+    /// the line, and nothing is overwritten if text is selected since typing replaces the
+    /// selection anyway.
+    fn overwritten_chars(&self, text: &str) -> usize {
+        if !self.overwrite.get() {
+            return 0;
+        }
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return 0,
+        };
+        let line = match self.line_cache.get_line(line) {
+            Some(line) => line,
+            None => return 0,
+        };
+        if selection_ranges(&line.styles).iter().any(|r| !r.is_empty()) {
+            return 0;
+        }
+        let rest = line
+            .text()
+            .get(col..)
+            .unwrap_or_default()
+            .trim_end_matches(|c| c == '\n' || c == '\r');
+        min(text.chars().count(), rest.chars().count())
+    }
+
+    /// Whether the cursor is drawn as a block, which it is in overwrite mode and in Vim's normal and
+    /// visual mode
+    fn has_block_cursor(&self) -> bool {
+        self.overwrite.get()
+            || (self.main_state.borrow().settings.vim_mode
+                && self.vim.borrow().mode() != Mode::Insert)
+    }
+
+    /// Shows the mode of the Vim emulation in the statusbar, if it's enabled
+    pub fn update_vim_mode_label(&self) {
+        let label = &self.view_item.statusbar.vim_mode_label;
+        label.set_visible(self.main_state.borrow().settings.vim_mode);
+        label.set_text(&match self.vim.borrow().mode() {
+            Mode::Normal => gettext("NORMAL"),
+            Mode::Insert => gettext("INSERT"),
+            Mode::Visual => gettext("VISUAL"),
+        });
+        // The cursor's shape depends on the mode
+        self.view_item.edit_area.queue_draw();
+    }
+
+    /// Handles keys for the Vim emulation, if it's enabled. Returns whether the key has been
+    /// handled, otherwise it's handled as usual, e.g. in insert mode or for arrow keys.
+    fn handle_vim_key(&self, ek: &EventKey) -> bool {
+        if !self.main_state.borrow().settings.vim_mode {
+            return false;
+        }
+        let mode = self.vim.borrow().mode();
+
+        if ek.get_keyval() == key::Escape {
+            match mode {
+                // Like Vim, step back onto the last inserted char
+                Mode::Insert => self.core.move_left(&self.view_id),
+                Mode::Visual => self.collapse_selection(),
+                // Escape still closes the search bar in normal mode
+                Mode::Normal => return false,
+            }
+            self.vim.borrow_mut().escape();
+            self.update_vim_mode_label();
+            return true;
+        }
+        if mode == Mode::Insert {
+            return false;
+        }
+
+        let state = ek.get_state();
+        let ch = match ::gdk::keyval_to_unicode(ek.get_keyval()) {
+            Some(ch) if ch >= '\u{0020}' => ch,
+            _ => return false,
+        };
+        let command = if state.contains(ModifierType::MOD1_MASK) {
+            return false;
+        } else if state.contains(ModifierType::CONTROL_MASK) {
+            // Leave other shortcuts, e.g. Ctrl+C, alone
+            match self.vim.borrow_mut().handle_ctrl_char(ch) {
+                Some(command) => Some(command),
+                None => return false,
+            }
+        } else {
+            self.vim.borrow_mut().handle_char(ch)
+        };
+
+        if let Some((command, count)) = command {
+            debug!(
+                "{}: {:?} x{}",
+                gettext("Running Vim command"),
+                command,
+                count
+            );
+            self.run_vim_command(command, count);
+        }
+        self.update_vim_mode_label();
+        true
+    }
+
+    fn run_vim_command(&self, command: vim::Command, count: u32) {
+        use crate::vim::Command::*;
+
+        let view_id = &self.view_id;
+        if command.is_movement() {
+            let visual = self.vim.borrow().mode() == Mode::Visual;
+            for _ in 0..count {
+                match (command, visual) {
+                    (Left, false) => self.core.move_left(view_id),
+                    (Left, true) => self.core.move_left_and_modify_selection(view_id),
+                    (Right, false) => self.core.move_right(view_id),
+                    (Right, true) => self.core.move_right_and_modify_selection(view_id),
+                    (Up, false) => self.core.move_up(view_id),
+                    (Up, true) => self.core.move_up_and_modify_selection(view_id),
+                    (Down, false) => self.core.move_down(view_id),
+                    (Down, true) => self.core.move_down_and_modify_selection(view_id),
+                    // The line cache doesn't follow the cursor while we're repeating movements
+                    (WordForward, visual) if count == 1 => self.move_word(true, visual),
+                    (WordBackward, visual) if count == 1 => self.move_word(false, visual),
+                    (WordForward, false) => self.core.move_word_right(view_id),
+                    (WordForward, true) => self.core.move_word_right_and_modify_selection(view_id),
+                    (WordBackward, false) => self.core.move_word_left(view_id),
+                    (WordBackward, true) => self.core.move_word_left_and_modify_selection(view_id),
+                    (LineStart, false) => self.core.move_to_left_end_of_line(view_id),
+                    (LineStart, true) => self
+                        .core
+                        .move_to_left_end_of_line_and_modify_selection(view_id),
+                    (LineEnd, false) => self.core.move_to_right_end_of_line(view_id),
+                    (LineEnd, true) => self
+                        .core
+                        .move_to_right_end_of_line_and_modify_selection(view_id),
+                    (DocumentStart, false) => self.core.move_to_beginning_of_document(view_id),
+                    (DocumentStart, true) => self
+                        .core
+                        .move_to_beginning_of_document_and_modify_selection(view_id),
+                    (DocumentEnd, false) => self.core.move_to_end_of_document(view_id),
+                    (DocumentEnd, true) => self
+                        .core
+                        .move_to_end_of_document_and_modify_selection(view_id),
+                    _ => {}
+                }
+            }
+            return;
+        }
+
+        let modifies_document = match command {
+            OpenLineBelow | OpenLineAbove | DeleteChar | DeleteLine | PasteAfter | PasteBefore
+            | Undo | Redo | DeleteSelection => true,
+            _ => false,
+        };
+        if self.read_only && modifies_document {
+            debug!("{}", gettext("Ignoring Vim command in read-only EditView"));
+            return;
+        }
+
+        match command {
+            Append => self.core.move_right(view_id),
+            InsertAtLineStart => self.core.move_to_left_end_of_line(view_id),
+            AppendAtLineEnd => self.core.move_to_right_end_of_line(view_id),
+            OpenLineBelow => {
+                self.core.move_to_right_end_of_line(view_id);
+                self.core.insert_newline(view_id);
+            }
+            OpenLineAbove => {
+                self.core.move_to_left_end_of_line(view_id);
+                self.core.insert_newline(view_id);
+                self.core.move_up(view_id);
+            }
+            DeleteChar => {
+                for _ in 0..count {
+                    self.core.delete_forward(view_id);
+                }
+            }
+            DeleteLine => {
+                self.select_lines_below(count);
+                self.do_cut(view_id);
+            }
+            YankLine => {
+                let cursor = self.cursor_position();
+                self.select_lines_below(count);
+                self.do_copy(view_id);
+                if let Some((line, col)) = cursor {
+                    self.core.gesture_point_select(view_id, line, col as u64);
+                }
+            }
+            PasteAfter => self.vim_paste(true),
+            PasteBefore => self.vim_paste(false),
+            Undo => {
+                for _ in 0..count {
+                    self.core.undo(view_id);
+                }
+            }
+            Redo => {
+                for _ in 0..count {
+                    self.core.redo(view_id);
+                }
+            }
+            Search => self.start_search(),
+            SearchNext => {
+                for _ in 0..count {
+                    self.core.find_next(view_id, Some(true), Some(false));
+                }
+            }
+            SearchPrevious => {
+                for _ in 0..count {
+                    self.core.find_previous(view_id, Some(true));
+                }
+            }
+            DeleteSelection => self.do_cut(view_id),
+            YankSelection => {
+                self.do_copy(view_id);
+                self.collapse_selection();
+            }
+            SetMark(name) => self.set_quick_mark(name),
+            JumpToMark(name) => self.jump_to_quick_mark(name),
+            _ => {}
+        }
+    }
+
+    /// Selects `count` lines, starting with the one the cursor is on
+    fn select_lines_below(&self, count: u32) {
+        self.core.move_to_left_end_of_line(&self.view_id);
+        for _ in 0..count {
+            self.core.move_down_and_modify_selection(&self.view_id);
+        }
+    }
+
+    /// Removes the selection, leaving the cursor where it is
+    fn collapse_selection(&self) {
+        if let Some((line, col)) = self.cursor_position() {
+            self.core
+                .gesture_point_select(&self.view_id, line, col as u64);
+        }
+    }
+
+    /// Pastes the clipboard after or before the cursor. Whole lines (like the ones `yy` copies) are
+    /// pasted below or above the current line instead, like Vim does.
+    fn vim_paste(&self, after: bool) {
+        let core = self.core.clone();
+        let view_id = self.view_id.clone();
+        Clipboard::get(&SELECTION_CLIPBOARD).request_text(move |_, text| {
+            let text = match text {
+                Some(text) => text,
+                None => return,
+            };
+            if text.ends_with('\n') {
+                if after {
+                    core.move_to_right_end_of_line(&view_id);
+                    core.insert(&view_id, &format!("\n{}", text.trim_end_matches('\n')));
+                } else {
+                    core.move_to_left_end_of_line(&view_id);
+                    core.insert(&view_id, &text);
+                }
+            } else {
+                if after {
+                    core.move_right(&view_id);
+                }
+                core.insert(&view_id, &text);
+            }
+        });
+    }
+
+    /// Adds `marker` to the gutter next to `line`. It moves along with the line and is removed
+    /// with it.
+    pub fn add_gutter_marker(&mut self, line: u64, marker: GutterMarker) -> MarkerId {
+        let id = self.gutter_markers.add(line, marker);
+        self.view_item.linecount.queue_draw();
+        id
+    }
+
+    /// Removes the gutter marker `id`. Returns whether it was still there.
+    pub fn remove_gutter_marker(&mut self, id: MarkerId) -> bool {
+        let removed = self.gutter_markers.remove(id);
+        self.view_item.linecount.queue_draw();
+        removed
+    }
+
+    /// Removes all gutter markers added by `source`
+    pub fn clear_gutter_markers(&mut self, source: &str) {
+        self.gutter_markers.clear(source);
+        self.view_item.linecount.queue_draw();
+    }
+
+    /// The line the gutter marker `id` is on now
+    pub fn gutter_marker_line(&self, id: MarkerId) -> Option<u64> {
+        self.gutter_markers.line(id)
+    }
+
+    /// Anchors `annotation` to `line`. It moves along with the line and is removed with it.
+    pub fn add_annotation(&mut self, line: u64, annotation: Annotation) -> AnnotationId {
+        if let AnnotationContent::Widget(widget) = &annotation.content {
+            self.view_item.edit_area.put(widget, 0, 0);
+        }
+        let id = self.annotations.add(line, annotation);
+        self.place_annotation_widgets();
+        self.view_item.edit_area.queue_draw();
+        id
+    }
+
+    /// Removes the annotation `id`. Returns whether it was still there.
+    pub fn remove_annotation(&mut self, id: AnnotationId) -> bool {
+        match self.annotations.remove(id) {
+            Some(annotation) => {
+                self.drop_annotation(annotation);
+                self.view_item.edit_area.queue_draw();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes all annotations added by `source`
+    pub fn clear_annotations(&mut self, source: &str) {
+        for annotation in self.annotations.clear(source) {
+            self.drop_annotation(annotation);
+        }
+        self.view_item.edit_area.queue_draw();
+    }
+
+    /// The line the annotation `id` is on now
+    pub fn annotation_line(&self, id: AnnotationId) -> Option<u64> {
+        self.annotations.line(id)
+    }
+
+    /// Takes the widget of a removed annotation out of the text area
+    fn drop_annotation(&self, annotation: Annotation) {
+        if let AnnotationContent::Widget(widget) = annotation.content {
+            self.view_item.edit_area.remove(&widget);
+        }
+    }
+
+    /// Moves the widgets of annotations to their anchors, e.g. after lines have been inserted
+    /// above them. Widgets on hidden lines or lines we don't have are hidden.
+    fn place_annotation_widgets(&self) {
+        let pango_ctx = self.view_item.get_pango_ctx();
+        let tabs = self.get_tabs();
+        for (line_num, index, widget) in self.annotations.widgets() {
+            let row = self.folds.row_of_line(line_num);
+            let line = self
+                .line_cache
+                .get_line(line_num)
+                .filter(|_| self.folds.line_at_row(row) == line_num);
+            let line = match line {
+                Some(line) => line,
+                None => {
+                    widget.set_child_visible(false);
+                    continue;
+                }
+            };
+            let layout = self.create_layout_for_line(&pango_ctx, line, &tabs);
+            let index = min(index, strip_line_ending(line.text()).len());
+            let x = layout
+                .get_line(0)
+                .map_or(0, |l| l.index_to_x(index as i32, false) / pango::SCALE);
+            let y = self.edit_font.font_height * row as f64;
+            self.view_item.edit_area.move_(widget, x, y as i32);
+            widget.set_child_visible(true);
+        }
+    }
+
+    /// Bookmarks the line the cursor is on, or removes its bookmark (Ctrl+F2)
+    pub fn toggle_bookmark(&mut self) {
+        if let Some((line, _)) = self.cursor_position() {
+            let bookmarked = self.bookmarks.toggle(line);
+            debug!(
+                "{} {}: {}",
+                gettext("Setting bookmark of line"),
+                line,
+                bookmarked
+            );
+            self.view_item.linecount.queue_draw();
+        }
+    }
+
+    /// Moves the cursor to the next bookmark (F2) or to the previous one (Shift+F2)
+    pub fn goto_bookmark(&self, forward: bool) {
+        let line = self.cursor_position().map_or(0, |(line, _)| line);
+        let bookmark = if forward {
+            self.bookmarks.next(line)
+        } else {
+            self.bookmarks.previous(line)
+        };
+        if let Some(bookmark) = bookmark {
+            self.core.gesture_point_select(&self.view_id, bookmark, 0);
+        }
+    }
+
+    /// Moves the cursor to the bracket matching the one at the cursor (Ctrl+% or Ctrl+M), or
+    /// extends the selection up to it if `extend` is true (Ctrl+Shift+M)
+    pub fn goto_matching_bracket(&self, extend: bool) {
+        self.find_matching_bracket(extend, None);
+    }
+
+    /// Does the work of `goto_matching_bracket`. If the search runs into a line we don't have, the
+    /// lines around it are requested from xi-editor and the search is repeated once they arrive.
+    /// `previously_missing` is the line we requested last time, if we still don't have it we give
+    /// up.
+    fn find_matching_bracket(&self, extend: bool, previously_missing: Option<u64>) {
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return,
+        };
+        let height = self.line_cache.height();
+        let get_line = |n| self.line_cache.get_line(n).map(Line::text);
+
+        match matching_bracket(get_line, line, col, height) {
+            BracketMatch::Found(match_line, match_col) => {
+                if !extend {
+                    self.core
+                        .gesture_point_select(&self.view_id, match_line, match_col as u64);
+                } else if (match_line, match_col) > (line, col) {
+                    // Brackets are ASCII, so the selection contains the closing one like this
+                    self.core
+                        .gesture_range_select(&self.view_id, match_line, match_col as u64 + 1);
+                } else {
+                    self.core
+                        .gesture_range_select(&self.view_id, match_line, match_col as u64);
+                }
+            }
+            BracketMatch::Missing(missing) if previously_missing != Some(missing) => {
+                debug!(
+                    "{} {}",
+                    gettext("Requesting lines to find matching bracket around line"),
+                    missing
+                );
+                self.pending_bracket_match.set(Some((extend, missing)));
+                self.core.request_lines(
+                    &self.view_id,
+                    missing.saturating_sub(BRACKET_SEARCH_LINES),
+                    min(missing + BRACKET_SEARCH_LINES, height),
+                );
+            }
+            _ => debug!("{}", gettext("No matching bracket found")),
+        }
+    }
+
+    /// Switches between inserting and overwriting text (Insert)
+    pub fn toggle_overwrite(&self) {
+        self.overwrite.set(!self.overwrite.get());
+        debug!(
+            "{}: {}",
+            gettext("Setting overwrite mode"),
+            self.overwrite.get()
+        );
+        self.view_item
+            .statusbar
+            .overwrite_label
+            .set_visible(self.overwrite.get());
+        self.view_item.edit_area.queue_draw();
+    }
+
+    /// Replaces the abbreviation in front of the cursor by its expansion, if there is one
+    fn expand_abbreviation(&self) {
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return,
+        };
+        let line = match self.line_cache.get_line(line) {
+            Some(line) => line,
+            None => return,
+        };
+        if selection_ranges(&line.styles).iter().any(|r| !r.is_empty()) {
+            return;
+        }
+        let word = match word_completion::word_before(line.text(), col) {
+            Some(word) => word,
+            None => return,
+        };
+        let expansion = match self
+            .main_state
+            .borrow()
+            .settings
+            .abbreviations
+            .expansion(&self.language(), word)
+        {
+            Some(expansion) => expansion.to_string(),
+            None => return,
+        };
+        for _ in word.chars() {
+            self.core.delete_backward(&self.view_id);
+        }
+        self.core.insert(&self.view_id, &expansion);
+    }
+
+    /// See `smart_punctuation::substitute`
+    fn smart_punctuation(&self, typed: &str) -> Option<(usize, &'static str)> {
+        if !self.main_state.borrow().settings.smart_punctuation
+            || !smart_punctuation::is_prose_language(&self.language())
+        {
+            return None;
+        }
+        let (line, col) = self.cursor_position()?;
+        let line = self.line_cache.get_line(line)?;
+        // Typing replaces the selection, so the text before the cursor doesn't matter then
+        if selection_ranges(&line.styles).iter().any(|r| !r.is_empty()) {
+            return None;
+        }
+        smart_punctuation::substitute(line.text().get(..col)?, typed)
+    }
+
+    /// Continues the list the cursor is in when Enter is pressed, renumbering the following items
+    /// of ordered lists. Enter on an empty item ends the list instead. Returns false if the cursor
+    /// isn't in a list (or lists aren't continued in this language), in which case Enter should
+    /// just insert a newline.
+    fn continue_list(&self) -> bool {
+        if !self
+            .main_state
+            .borrow()
+            .settings
+            .continues_lists(&self.language())
+        {
+            return false;
+        }
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return false,
+        };
+        let text = match self.line_cache.get_line(line) {
+            Some(l) => l.text(),
+            None => return false,
+        };
+        let item = match lists::parse(text) {
+            Some(item) if col >= item.content_start => item,
+            _ => return false,
+        };
+
+        if item.is_empty {
+            self.replace_lines(line, &[text], &[String::new()]);
+            return true;
+        }
+
+        let prefix = item.next_prefix();
+        let following: Vec<&str> = (line + 1..)
+            .map(|n| self.line_cache.get_line(n).map(Line::text))
+            .take_while(Option::is_some)
+            .flatten()
+            .collect();
+        let renumbered = lists::renumber(&following, &item);
+
+        self.core.insert(&self.view_id, &format!("\n{}", prefix));
+        // The following lines have been moved down by the newline we've just inserted
+        if self.replace_lines(line + 2, &following[..renumbered.len()], &renumbered) {
+            self.core
+                .gesture_point_select(&self.view_id, line + 1, prefix.len() as u64);
+        }
+        true
+    }
+
+    /// Ends the list when Backspace is pressed right after the marker of an empty list item by
+    /// removing the marker. Returns false if Backspace should just delete a char.
+    fn end_list(&self) -> bool {
+        if !self
+            .main_state
+            .borrow()
+            .settings
+            .continues_lists(&self.language())
+        {
+            return false;
+        }
+        let (line, col) = match self.cursor_position() {
+            Some(pos) => pos,
+            None => return false,
+        };
+        let text = match self.line_cache.get_line(line) {
+            Some(l) => l.text(),
+            None => return false,
+        };
+        match lists::parse(text) {
+            Some(ref item) if item.is_empty && col == item.content_start => {
+                self.replace_lines(line, &[text], &[String::new()])
+            }
+            _ => false,
+        }
+    }
+
+    /// Comments the selected lines (or the line of the cursor) or uncomments them if they're
+    /// commented already (Ctrl+/). xi-editor doesn't know how comments look like, so we use the
+    /// comment tokens of the language of the EditView.
+    pub fn toggle_comment(&self) {
+        if self.read_only {
+            return;
+        }
+        let language = self.language();
+        let tokens = match comments::comment_tokens(&language) {
+            Some(tokens) => tokens,
+            None => {
+                debug!("{} '{}'", gettext("No comment tokens known for"), language);
+                return;
+            }
+        };
+
+        if let Some((first, lines)) = self.selected_lines() {
+            let new_lines = comments::toggle_comment(&lines, tokens);
+            self.replace_lines(first, &lines, &new_lines);
+            // Keep multiple lines selected, so they can be uncommented again right away
+            if new_lines.len() > 1 {
+                let last = first + new_lines.len() as u64 - 1;
+                let last_len = new_lines.last().map_or(0, String::len);
+                self.core.gesture_point_select(&self.view_id, first, 0);
+                self.core
+                    .gesture_range_select(&self.view_id, last, last_len as u64);
+            }
+        }
+    }
+
+    /// Sorts, reverses or deduplicates the selected lines, or all lines if nothing is selected.
+    /// Each selection is extended to whole lines and transformed on its own. We only know which
+    /// lines are selected if they're in the line cache, so all lines are requested first.
+    pub fn transform_lines(edit_view: &Rc<RefCell<Self>>, transform: LineTransform) {
+        let ev = edit_view.borrow();
+        if ev.read_only {
+            return;
+        }
+        let edit_view_weak = Rc::downgrade(edit_view);
+        // The EditView is borrowed while the text is handed to us
+        ev.with_text(move |_| {
+            glib::idle_add_local(move || {
+                if let Some(edit_view) = edit_view_weak.upgrade() {
+                    edit_view.borrow().transform_selected_lines(transform);
+                }
+                source::Continue(false)
+            });
+        });
+    }
+
+    /// Transforms the lines of each selection, or all lines if nothing is selected, as one edit
+    fn transform_selected_lines(&self, transform: LineTransform) {
+        let lines = match (0..self.line_cache.height())
+            .map(|n| self.line_cache.get_line(n))
+            .collect::<Option<Vec<&Line>>>()
+        {
+            Some(lines) => lines,
+            None => return,
+        };
+        let selections: Vec<_> = lines
+            .iter()
+            .map(|line| {
+                let len = strip_line_ending(line.text()).len();
+                (selection_ranges(&line.styles), len)
+            })
+            .collect();
+        let mut regions = line_transforms::selected_regions(&selections);
+        if regions.is_empty() {
+            // The empty line after a trailing newline stays at the end
+            let mut last = match lines.len().checked_sub(1) {
+                Some(last) => last,
+                None => return,
+            };
+            if last > 0 && lines[last].text().is_empty() {
+                last -= 1;
+            }
+            regions.push((0, last));
+        }
+
+        let first = regions[0].0;
+        let last = regions[regions.len() - 1].1;
+        let old_lines: Vec<&str> = lines[first..=last].iter().map(|line| line.text()).collect();
+        let mut new_lines: Vec<String> = old_lines
+            .iter()
+            .map(|line| strip_line_ending(line).to_string())
+            .collect();
+        // From the last one, so the lines of the other regions stay where they are
+        for &(start, end) in regions.iter().rev() {
+            let region = start - first..=end - first;
+            let transformed = transform.apply(&new_lines[region.clone()].join("\n"));
+            let transformed: Vec<String> = transformed.split('\n').map(str::to_string).collect();
+            new_lines.splice(region, transformed);
+        }
+        if !self.replace_lines(first as u64, &old_lines, &new_lines) {
+            debug!("{}", gettext("Nothing to transform"));
+        }
+    }
+
+    /// Calls `f` with the selected text once xi-editor has sent it to us. `f` isn't called if
+    /// nothing is selected.
+    pub fn with_selection<F: Fn(String) + 'static>(&self, f: F) {
+        self.with_selected_text(move |text| {
+            if !text.is_empty() {
+                f(text);
+            }
+        });
+    }
+
+    /// Like `with_selection`, but `f` is called with an empty string if nothing is selected
+    pub fn with_selected_text<F: Fn(String) + 'static>(&self, f: F) {
+        let (selection_tx, selection_rx) =
+            MainContext::channel::<Option<String>>(glib::PRIORITY_HIGH);
+        let main_context = MainContext::default();
+
+        selection_rx.attach(Some(&main_context), move |selection| {
+            f(selection.unwrap_or_default());
+            source::Continue(false)
+        });
+
+        self.core.copy(&self.view_id, selection_tx);
+    }
+
+    /// Replaces the selection with `text`, or inserts it at the cursor if nothing is selected
+    pub fn insert_text(&self, text: &str) {
+        if !self.read_only {
+            self.core.insert(&self.view_id, text);
+        }
+    }
+
+    /// Opens the find dialog (Ctrl+F)
+    pub fn start_search(&self) {
+        if self.find_replace.search_bar.get_search_mode() {
+            // If you've enabled the replace dialog, Ctrl+F brings back the find dialog (and as such
+            // collapses the replace dialog) instead of stopping the entire search
+            if self.find_replace.replace_revealer.get_reveal_child() {
+                self.find_replace.show_replace_button.set_active(false);
+            } else {
+                self.stop_search();
+            }
+        } else {
+            self.find_replace.search_bar.set_search_mode(true);
+            #[cfg(feature = "gtk_v3_22")]
+            self.find_replace.popover.popup();
+            #[cfg(not(feature = "gtk_v3_22"))]
+            self.find_replace.popover.show();
+            self.find_replace
+                .option_revealer
+                .set_reveal_child(self.find_replace.show_options_button.get_active());
+            self.find_replace
+                .replace_revealer
+                .set_reveal_child(self.find_replace.show_replace_button.get_active());
+            self.find_replace.search_entry.grab_focus();
+            if let Some(needle) = self.find_replace.search_entry.get_text() {
+                // No need to pass the actual values of case_sensitive etc. to Xi here, we as soon
+                // as we start typing something into the search box/flick one of the switches we call
+                // EditView::search_changed() anyway, which does that for us.
+                self.core.find(&self.view_id, &needle, false, false, false);
+            }
+        }
+    }
+
+    /// Opens the replace dialog (Ctrl+R)
+    pub fn start_replace(&self) {
+        if self.find_replace.search_bar.get_search_mode() {
+            // If you've enabled the replace dialog, Ctrl+R will collapse the entire Popover, if the
+            // Popover is already open but the replace dialog is hidden (e.g. because Ctrl+F has been
+            // pressed before) we'll expand the replace dialog instead of closing the entire Popover
+            if self.find_replace.replace_revealer.get_reveal_child() {
+                self.stop_replace();
+            } else {
+                self.find_replace.show_replace_button.set_active(true);
+            }
+        } else {
+            self.find_replace.show_replace_button.set_active(true);
+            self.find_replace.search_bar.set_search_mode(true);
+            #[cfg(feature = "gtk_v3_22")]
+            self.find_replace.popover.popup();
+            #[cfg(not(feature = "gtk_v3_22"))]
+            self.find_replace.popover.show();
+            self.find_replace
+                .option_revealer
+                .set_reveal_child(self.find_replace.show_options_button.get_active());
+            self.show_replace();
+            self.find_replace.search_entry.grab_focus();
+        }
+    }
+
+    pub fn show_replace(&self) {
+        self.find_replace.replace_revealer.set_reveal_child(true);
+    }
+
+    pub fn hide_replace(&self) {
+        self.find_replace.replace_revealer.set_reveal_child(false);
+    }
+
+    pub(crate) fn show_findreplace_opts(&self) {
+        self.find_replace.option_revealer.set_reveal_child(true);
+    }
+
+    pub(crate) fn hide_findreplace_opts(&self) {
+        self.find_replace.option_revealer.set_reveal_child(false);
+    }
+
+    pub fn stop_replace(&self) {
+        self.find_replace.show_replace_button.set_active(false);
+        self.hide_replace();
+        self.stop_search();
+    }
+
+    /// Closes the find/replace dialog
+    pub fn stop_search(&self) {
+        #[cfg(feature = "gtk_v3_22")]
+        self.find_replace.popover.popdown();
+        #[cfg(not(feature = "gtk_v3_22"))]
+        self.find_replace.popover.hide();
+        self.find_replace.show_replace_button.set_active(false);
+        self.find_replace.show_options_button.set_active(false);
+        self.find_replace.search_bar.set_search_mode(false);
+        self.view_item.ev_scrolled_window.grab_focus();
+        self.remember_search(false);
+    }
+
+    /// Returns the search (or replacement) history, the most recent string first
+    fn search_history(&self, replace: bool) -> Vec<String> {
+        let key = if replace {
+            REPLACE_HISTORY_KEY
+        } else {
+            SEARCH_HISTORY_KEY
+        };
+        self.main_state
+            .borrow()
+            .settings
+            .gschema
+            .settings
+            .get_strv(key)
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    /// Adds the search string (and the replacement, if `replace` is true) to the history, unless
+    /// the window is in private mode
+    fn remember_search(&self, replace: bool) {
+        let mut entries = vec![(
+            SEARCH_HISTORY_KEY,
+            &self.find_replace.search_entry,
+            &self.search_history_cursor,
+        )];
+        if replace {
+            entries.push((
+                REPLACE_HISTORY_KEY,
+                &self.find_replace.replace_entry,
+                &self.replace_history_cursor,
+            ));
+        }
+        for (key, entry, cursor) in entries {
+            cursor.borrow_mut().reset();
+            if self.main_state.borrow().private {
+                continue;
+            }
+            let text = entry.get_text().map(|s| s.to_string()).unwrap_or_default();
+            let mut history = self.search_history(key == REPLACE_HISTORY_KEY);
+            search_history::remember(&mut history, &text);
+            let history: Vec<&str> = history.iter().map(String::as_str).collect();
+            self.main_state
+                .borrow()
+                .settings
+                .gschema
+                .settings
+                .set_strv(key, &history);
+        }
+    }
+
+    /// Handles keys pressed in the search (or replace) entry: Shift+Enter goes to the previous
+    /// match, Up and Down go through the history like in a shell
+    pub(crate) fn handle_search_entry_key(&self, replace: bool, ek: &EventKey) -> Inhibit {
+        let shift = ek.get_state().contains(ModifierType::SHIFT_MASK);
+        if !replace && shift && (ek.get_keyval() == key::Return || ek.get_keyval() == key::KP_Enter)
+        {
+            self.find_prev();
+            return Inhibit(true);
+        }
+
+        let (entry, cursor) = if replace {
+            (
+                &self.find_replace.replace_entry,
+                &self.replace_history_cursor,
+            )
+        } else {
+            (&self.find_replace.search_entry, &self.search_history_cursor)
+        };
+        let history = self.search_history(replace);
+        let text = match ek.get_keyval() {
+            key::Up => {
+                let current = entry.get_text().map(|s| s.to_string()).unwrap_or_default();
+                cursor.borrow_mut().older(&history, &current)
+            }
+            key::Down => cursor.borrow_mut().newer(&history),
+            _ => return Inhibit(false),
+        };
+        if let Some(text) = text {
+            entry.set_text(&text);
+            entry.set_position(-1);
+        }
+        Inhibit(true)
+    }
+
+    /// Shows the search (or replacement) history in a popover below the icon of its entry
+    pub(crate) fn show_search_history(&self, replace: bool) {
+        let entry = if replace {
+            &self.find_replace.replace_entry
+        } else {
+            &self.find_replace.search_entry
+        };
+        let popover = Popover::new(Some(entry));
+        popover.set_pointing_to(&entry.get_icon_area(EntryIconPosition::Primary));
+        let vbox = gtk::Box::new(Orientation::Vertical, 0);
+        vbox.set_border_width(6);
+
+        let history = self.search_history(replace);
+        if history.is_empty() {
+            let label = Label::new(Some(gettext("No recent searches").as_str()));
+            label.set_sensitive(false);
+            vbox.pack_start(&label, false, true, 6);
+        }
+        for s in history {
+            let button = ModelButton::new();
+            // Keep multi-line strings on one line
+            button.set_property_text(Some(s.replace('\n', "↵").as_str()));
+            button.connect_clicked(enclose!((entry, popover) move |_| {
+                entry.set_text(&s);
+                entry.set_position(-1);
+                popover.hide();
+            }));
+            vbox.pack_start(&button, false, true, 0);
+        }
+
+        popover.add(&vbox);
+        vbox.show_all();
+        popover.connect_closed(|popover| popover.destroy());
+        #[cfg(feature = "gtk_v3_22")]
+        popover.popup();
+        #[cfg(not(feature = "gtk_v3_22"))]
+        popover.show();
+    }
+
+    /// Returns which parts of the find/replace dialog are currently shown
+    pub fn search_layout(&self) -> SearchLayout {
+        let search = self.find_replace.search_bar.get_search_mode();
+        SearchLayout {
+            search,
+            replace: search && self.find_replace.show_replace_button.get_active(),
+            options: search && self.find_replace.show_options_button.get_active(),
+        }
+    }
+
+    /// Opens the find/replace dialog as described by `layout`
+    pub fn restore_search_layout(&self, layout: SearchLayout) {
+        if layout.replace {
+            self.start_replace();
+        } else if layout.search {
+            self.start_search();
+        } else {
+            return;
+        }
+
+        self.find_replace
+            .show_options_button
+            .set_active(layout.options);
+    }
+
+    /// Highlights the matches of the search while the find/replace dialog is open
+    pub(crate) fn search_mode_changed(&self, active: bool) {
+        self.core.highlight_find(&self.view_id, active);
+        self.search_origin
+            .set(if active { self.cursor_position() } else { None });
+        if !active {
+            self.search_ticks.clear();
+            self.match_counter.borrow_mut().clear();
+            if let Some(handler) = self.search_results_handler.borrow().clone() {
+                handler(Vec::new());
+            }
+        }
+    }
+
+    /// Shows which of the matches is selected, e.g. "3 of 12"
+    fn update_match_counter(&self) {
+        if let Some((line_num, _)) = self.cursor_position() {
+            if let Some(line) = self.line_cache.get_line(line_num) {
+                // xi-editor uses the style id 1 for matches
+                let matches = style_ranges(&line.styles, 1);
+                let selection = selection_ranges(&line.styles)
+                    .into_iter()
+                    .find(|r| !r.is_empty());
+                self.match_counter
+                    .borrow_mut()
+                    .update(line_num, &matches, selection);
+            }
+        }
+        // Don't hide that the pattern is invalid
+        let fr = &self.find_replace;
+        if !fr.search_entry.get_style_context().has_class("error") {
+            let match_counter = self.match_counter.borrow();
+            fr.find_status_label.set_text(&match_counter.label());
+            fr.announce(&match_counter.announcement());
+        }
+    }
+
+    /// Displays how many matches have been found in the find/replace dialog and marks the lines
+    /// they're on on the scrollbar.
+    pub fn find_status(&self, queries: &Value) {
+        if let Some(queries) = queries.as_array() {
+            for query in queries {
+                if let Some(query_obj) = query.as_object() {
+                    let lines: Vec<u64> = query_obj
+                        .get("lines")
+                        .and_then(Value::as_array)
+                        .map_or_else(Vec::new, |lines| {
+                            lines.iter().filter_map(Value::as_u64).collect()
+                        });
+                    if let Some(matches) = query_obj["matches"].as_u64() {
+                        self.match_counter
+                            .borrow_mut()
+                            .set_matches(matches, lines.clone());
+                        self.update_match_counter();
+                    }
+                    if query_obj.contains_key("lines") {
+                        let color = self
+                            .main_state
+                            .borrow()
+                            .find_highlight_style()
+                            .bg_color
+                            .map_or(Color::BLACK, color_from_u32);
+                        self.search_ticks
+                            .set(&lines, self.line_cache.height(), color);
+                        if let Some(handler) = self.search_results_handler.borrow().clone() {
+                            handler(lines);
+                        }
+                    }
+                }
+                debug!("query {}", query);
+            }
+        }
+    }
+
+    /// Displays what chars will be replaced in the replace dialog
+    //TODO: Handle preserve_case
+    pub fn replace_status(&self, status: &Value) {
+        if let Some(chars) = status["chars"].as_str() {
+            self.find_replace.replace_entry.set_text(chars);
+        }
+    }
+
+    /// Go to the next match in the find/replace dialog
+    pub fn find_next(&self) {
+        self.search_origin.set(None);
+        self.remember_search(false);
+        self.match_counter.borrow_mut().navigating(true);
+        self.core.find_next(&self.view_id, Some(true), Some(true));
+    }
+
+    /// Go the to previous match in the find/replace dialog
+    pub fn find_prev(&self) {
+        self.search_origin.set(None);
+        self.remember_search(false);
+        self.match_counter.borrow_mut().navigating(false);
+        self.core.find_previous(&self.view_id, Some(true));
+    }
+
+    /// Tells xi-editor that we're searching for a different string (or none) now. The search
+    /// entry only emits `search-changed` once the user has stopped typing for a moment, so we
+    /// don't have to debounce this ourselves.
+    pub fn search_changed(&self, s: Option<String>) {
+        let needle = s.unwrap_or_default();
+        let regex = self.find_replace.use_regex_button.get_active();
+        let whole_worlds = self.find_replace.whole_word_button.get_active();
+        let case_sensitive = self.find_replace.case_sensitive_button.get_active();
+        // Point out invalid patterns in the search bar instead of having xi-editor send an alert
+        if regex {
+            if let Err(e) = regex_replace::build_regex(&needle, case_sensitive, whole_worlds) {
+                self.show_search_error(Some(&e.to_string()));
+                self.update_regex_tester();
+                return;
+            }
+        }
+        self.show_search_error(None);
+        self.core
+            .find(&self.view_id, &needle, case_sensitive, regex, whole_worlds);
+        self.update_regex_tester();
+
+        // Go to the first match after where the search started while the user is typing
+        if let Some((line, col)) = self.search_origin.get() {
+            self.core
+                .gesture_point_select(&self.view_id, line, col as u64);
+            if !needle.is_empty() {
+                self.core.find_next(&self.view_id, Some(true), Some(true));
+            }
+        }
+    }
+
+    /// Closes the find/replace dialog and moves the cursor back to where it was before searching,
+    /// unless the user has gone to a match with Enter or the arrow buttons (Escape)
+    pub fn cancel_search(&self) {
+        if let Some((line, col)) = self.search_origin.take() {
+            self.core
+                .gesture_point_select(&self.view_id, line, col as u64);
+        }
+        self.stop_search();
+    }
+
+    /// Marks the search entry as invalid and shows `error` in its tooltip, or clears the mark
+    fn show_search_error(&self, error: Option<&str>) {
+        let fr = &self.find_replace;
+        let style_context = fr.search_entry.get_style_context();
+        match error {
+            Some(error) => {
+                style_context.add_class("error");
+                fr.find_status_label
+                    .set_text(&gettext("Invalid regular expression"));
+                fr.announce(&gettext("Invalid regular expression"));
+                fr.search_entry.set_tooltip_text(Some(error));
+            }
+            None => {
+                style_context.remove_class("error");
+                fr.search_entry.set_tooltip_text(None);
+            }
+        }
+    }
+
+    /// Returns the regex we have to replace matches with ourselves, because `replacement` refers
+    /// to its capture groups, which xi-editor doesn't support
+    fn group_ref_regex(&self, replacement: &str) -> Option<Regex> {
+        let fr = &self.find_replace;
+        if !fr.use_regex_button.get_active() || !regex_replace::has_group_refs(replacement) {
+            return None;
+        }
+        let needle = fr.search_entry.get_text()?;
+        regex_replace::build_regex(
+            &needle,
+            fr.case_sensitive_button.get_active(),
+            fr.whole_word_button.get_active(),
+        )
+        .ok()
+    }
+
+    /// Returns the line the (first) cursor is on, if it's in the line cache
+    fn get_cursor_line(&self) -> Option<&Line> {
+        self.line_cache
+            .lines
+            .iter()
+            .filter_map(Option::as_ref)
+            .find(|l| !l.cursor().is_empty())
+    }
+
+    /// If the regex tester is enabled this shows the capture groups of the regex in the search
+    /// entry on the line the cursor is on, which helps crafting replace expressions.
+    pub(crate) fn update_regex_tester(&self) {
+        let fr = &self.find_replace;
+        let active = fr.search_bar.get_search_mode()
+            && fr.use_regex_button.get_active()
+            && fr.regex_tester_button.get_active();
+        fr.regex_tester_revealer.set_reveal_child(active);
+        if !active {
+            return;
+        }
+
+        let needle = fr
+            .search_entry
+            .get_text()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        if needle.is_empty() {
+            fr.regex_tester_label.set_text("");
+            return;
+        }
+
+        let text = self
+            .get_cursor_line()
+            .map(|l| strip_line_ending(l.text()).to_string())
+            .unwrap_or_default();
+        let description = match RegexBuilder::new(&needle)
+            .case_insensitive(!fr.case_sensitive_button.get_active())
+            .build()
+        {
+            Ok(re) => match describe_captures(&re, &text) {
+                Some(groups) => groups
+                    .iter()
+                    .map(|(group, captured)| format!("{}: \u{201c}{}\u{201d}", group, captured))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                None => gettext("No match on the current line"),
+            },
+            Err(e) => format!("{}: {}", gettext("Invalid regular expression"), e),
+        };
+        fr.regex_tester_label.set_text(&description);
+    }
+
+    /// Replace _one_ match with the replacement string
+    pub fn replace(&self) {
+        if self.read_only {
+            return;
+        }
+        self.search_origin.set(None);
+        self.remember_search(true);
+        if let Some(replace_chars) = self.find_replace.replace_entry.get_text() {
+            if let Some(re) = self.group_ref_regex(&replace_chars) {
+                // Replace the selected match and select the next one, if the selection isn't a
+                // match this only selects the next one
+                let core = self.core.clone();
+                let view_id = self.view_id.clone();
+                let replacement = replace_chars.to_string();
+                self.with_selected_text(move |selection| {
+                    if let Some(replaced) =
+                        regex_replace::replace_match(&re, &selection, &replacement)
+                    {
+                        core.insert(&view_id, &replaced);
+                    }
+                    core.find_next(&view_id, Some(true), Some(true));
+                });
+                return;
+            }
+            self.core
+                .replace(&self.view_id, replace_chars.as_str(), false);
+            self.core.replace_next(&self.view_id);
+        }
+    }
+
+    /// Replace _all_ matches with the replacement string
+    pub fn replace_all(&self) {
+        if self.read_only {
+            return;
+        }
+        self.search_origin.set(None);
+        self.remember_search(true);
+        if let Some(replace_chars) = self.find_replace.replace_entry.get_text() {
+            if let Some(re) = self.group_ref_regex(&replace_chars) {
+                let core = self.core.clone();
+                let view_id = self.view_id.clone();
+                let replacement = regex_replace::to_regex_syntax(&replace_chars);
+                let line = self.cursor_position().map(|(line, _)| line);
+                self.with_text(move |text| {
+                    let replaced = re.replace_all(text, replacement.as_str());
+                    if replaced != text {
+                        // Replacing all of the text makes this a single edit, which can be undone
+                        // at once
+                        core.select_all(&view_id);
+                        core.insert(&view_id, &replaced);
+                        if let Some(line) = line {
+                            core.goto_line(&view_id, line);
+                        }
+                    }
+                });
+                return;
+            }
+            self.core
+                .replace(&self.view_id, replace_chars.as_str(), false);
+            self.core.replace_all(&self.view_id);
+        }
+    }
+
+    /// The text of the document, if xi-editor has sent us all of its lines. Use `with_text` to get
+    /// it in any case.
+    pub fn document_text(&self) -> Option<Rc<str>> {
+        self.document.get(|| self.line_cache.text())
+    }
+
+    /// Calls `callback` with the text of the document. If we don't have all of its lines, they're
+    /// requested from xi-editor and `callback` is called once they've arrived, while the EditView
+    /// is borrowed mutably; so it mustn't borrow the EditView itself.
+    pub fn with_text<F>(&self, callback: F)
+    where
+        F: FnOnce(&str) + 'static,
+    {
+        if let Some(text) = self.document_text() {
+            callback(&*text);
+        } else if self.document.wait(Box::new(callback)) {
+            self.request_missing_lines();
+        }
+    }
+
+    /// Replaces all occurrences of `needle` in the document with `replacement` through
+    /// xi-editor's find/replace, so only the matches are changed and it can be undone in one go.
+    /// The document isn't saved; that's up to the user. Read-only documents are left alone.
+    pub fn replace_in_document(&self, needle: &str, replacement: &str) {
+        if needle.is_empty() || self.read_only {
+            return;
+        }
+        let line = self.cursor_position().map(|(line, _)| line);
+        self.core.find(&self.view_id, needle, true, false, false);
+        self.core.replace(&self.view_id, replacement, false);
+        self.core.replace_all(&self.view_id);
+        if let Some(line) = line {
+            self.core.goto_line(&self.view_id, line);
+        }
+
+        // Put back whatever the search bar was searching for
+        let fr = &self.find_replace;
+        if fr.search_bar.get_search_mode() {
+            self.search_changed(fr.search_entry.get_text().map(|s| s.to_string()));
+        } else {
+            self.core.find(&self.view_id, "", false, false, false);
+        }
+    }
+
+    /// `line` with its syntax highlighting. Selections and search matches are left out.
+    fn styled_line(&self, line: &Line) -> StyledLine {
+        let main_state = self.main_state.borrow();
+        let mut styles = Vec::new();
+        let mut ix = 0;
+        for style in &line.styles {
+            let start = ix + style.start;
+            ix = start + style.len as i64;
+            // Ids 0 and 1 are the selections and search matches
+            if style.id < 2 || start < 0 {
+                continue;
+            }
+            if let Some(line_style) = main_state.styles.get(&style.id) {
+                let start = start as usize;
+                styles.push((start..start + style.len, *line_style));
+            }
+        }
+        StyledLine {
+            text: strip_line_ending(line.text()).to_string(),
+            styles,
+        }
+    }
+
+    /// The lines of the document with their syntax highlighting, if xi-editor has sent us all of
+    /// them
+    pub fn styled_lines(&self) -> Option<Vec<StyledLine>> {
+        (0..self.line_cache.height())
+            .map(|n| Some(self.styled_line(self.line_cache.get_line(n)?)))
+            .collect()
+    }
+
+    /// The selected parts of the lines with their syntax highlighting, one per line, if
+    /// xi-editor has sent us all lines (see `with_text`)
+    fn selected_styled_lines(&self) -> Option<Vec<StyledLine>> {
+        let mut selected = Vec::new();
+        for n in 0..self.line_cache.height() {
+            let line = self.line_cache.get_line(n)?;
+            let styled = self.styled_line(line);
+            for range in selection_ranges(&line.styles) {
+                if range.is_empty() {
+                    continue;
+                }
+                // The selection includes the line ending if it continues on the next line
+                let end = range.end.min(styled.text.len());
+                let start = range.start.min(end);
+                let styles = styled
+                    .styles
+                    .iter()
+                    .filter(|(r, _)| r.start < end && r.end > start)
+                    .map(|(r, style)| (r.start.max(start) - start..r.end.min(end) - start, *style))
+                    .collect();
+                selected.push(StyledLine {
+                    text: styled.text.get(start..end).unwrap_or_default().to_string(),
+                    styles,
+                });
+            }
+        }
+        Some(selected)
+    }
+
+    /// The options HTML (and RTF) is rendered with, so it looks like it does in gxi
+    fn html_options(&self, line_numbers: bool) -> HtmlOptions {
+        let main_state = self.main_state.borrow();
+        HtmlOptions {
+            title: self.display_name(),
+            line_numbers,
+            font_family: self.font_family(),
+            font_size: f64::from(self.edit_font_size) / f64::from(pango::SCALE),
+            tab_size: main_state.settings.tab_size,
+            foreground: main_state.theme.foreground,
+            background: main_state.theme.background,
+        }
+    }
+
+    /// Renders the document into a standalone HTML document with the colors of the theme, if
+    /// xi-editor has sent us all of its lines (see `with_text`)
+    pub fn to_html(&self, line_numbers: bool) -> Option<String> {
+        let lines = self.styled_lines()?;
+        Some(html_export::to_html(
+            &lines,
+            &self.html_options(line_numbers),
+        ))
+    }
+
+    /// Copies the selection with its syntax highlighting, as HTML and RTF so it keeps its colors
+    /// when pasted into mails or documents, and as plain text for everything else. We only know
+    /// what's selected for the lines in the line cache, so all lines are requested first.
+    pub fn copy_with_formatting(edit_view: &Rc<RefCell<Self>>) {
+        let edit_view_weak = Rc::downgrade(edit_view);
+        // The EditView is borrowed while the text is handed to us
+        edit_view.borrow().with_text(move |_| {
+            glib::idle_add_local(move || {
+                if let Some(edit_view) = edit_view_weak.upgrade() {
+                    edit_view.borrow().copy_selection_with_formatting();
+                }
+                source::Continue(false)
+            });
+        });
+    }
+
+    /// Puts the selection on the clipboard as HTML, RTF and plain text. Nothing is copied unless
+    /// we have all lines, so the clipboard doesn't end up with only a part of the selection.
+    fn copy_selection_with_formatting(&self) {
+        let lines = match self.selected_styled_lines() {
+            Some(lines) => lines,
+            None => {
+                self.notify(
+                    &gettext("Not all lines of the document have been loaded"),
+                    MessageType::Error,
+                );
+                return;
+            }
+        };
+        if lines.is_empty() {
+            return;
+        }
+        debug!("{}", gettext("Copying text with formatting"));
+        let options = self.html_options(false);
+        let html = html_export::to_html_fragment(&lines, &options);
+        let rtf = rtf_export::to_rtf(&lines, &options);
+        let text = lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        let targets = [
+            TargetEntry::new("text/html", TargetFlags::empty(), 0),
+            TargetEntry::new("text/rtf", TargetFlags::empty(), 1),
+            TargetEntry::new("UTF8_STRING", TargetFlags::empty(), 2),
+            TargetEntry::new("text/plain;charset=utf-8", TargetFlags::empty(), 2),
+        ];
+        Clipboard::get(&SELECTION_CLIPBOARD).set_with_data(&targets, move |_, data, info| {
+            let target = data.get_target();
+            match info {
+                0 => data.set(&target, 8, html.as_bytes()),
+                1 => data.set(&target, 8, rtf.as_bytes()),
+                _ => {
+                    data.set_text(&text);
+                }
+            }
+        });
+    }
+
+    /// Hands the text to the callbacks passed to `with_text` if we have all lines now
+    fn serve_text_requests(&self) {
+        if !self.document.is_waiting() {
+            return;
+        }
+        if let Some(text) = self.document_text() {
+            self.document.serve(&text);
+        } else if self.document.retry() {
+            self.request_missing_lines();
+        } else {
+            warn!(
+                "{} '{}'",
+                gettext("Gave up waiting for the text of EditView"),
+                self.view_id
+            );
+        }
+    }
+
+    /// Asks xi-editor for all lines which aren't in the line cache
+    fn request_missing_lines(&self) {
+        let height = self.line_cache.height();
+        if height == 0 {
+            return;
+        }
+        for (first, last) in self.line_cache.get_missing(0, height) {
+            self.core.request_lines(&self.view_id, first, last);
+        }
+    }
+
+    /// Returns true if this EditView is empty (contains no text)
+    pub fn is_empty(&self) -> bool {
+        self.line_cache.is_empty()
+    }
+
+    pub fn set_language(&self, lang: &str) {
+        debug!("{} '{:?}'", gettext("Changing language to"), lang);
+        self.core.set_language(&self.view_id, &lang);
+    }
+
+    pub fn language_changed(&self, syntax: Option<&str>) {
+        debug!("{} '{:?}'", gettext("Language has been changed to"), syntax);
+        if let Some(lang) = syntax {
+            // https://github.com/xi-editor/xi-editor/issues/1194
+            let lang = if lang == "" || lang == "Plain Text" {
+                gettext("Plain Text")
+            } else {
+                lang.to_string()
+            };
+            let syntax_treeview = &self.view_item.statusbar.syntax_treeview;
+            let lang_pos = self
+                .main_state
+                .borrow()
+                .avail_languages
+                .iter()
+                .position(|s| s == &lang);
+            if let Some(pos) = lang_pos {
+                syntax_treeview
+                    .get_selection()
+                    .select_path(&TreePath::new_from_string(&format!("{}", pos)));
+            } else {
+                warn!(
+                    "{}: '{}'",
+                    gettext("Couldn't determine what position the following language is in"),
+                    lang
+                )
+            }
+            self.check_file();
+        }
+    }
+}
+
+/// The byte ranges of the text of a line which are selected
+fn selection_ranges(styles: &[StyleSpan]) -> Vec<std::ops::Range<usize>> {
+    // xi-editor always uses the style id 0 for selections
+    style_ranges(styles, 0)
+}
+
+/// Returns the byte ranges of the spans with the style `id`
+fn style_ranges(styles: &[StyleSpan], id: usize) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut ix = 0;
+    for style in styles {
+        let start = ix + style.start;
+        ix = start + style.len as i64;
+        if style.id == id && start >= 0 {
+            ranges.push(start as usize..ix as usize);
+        }
+    }
+    ranges
+}
+
+/// Returns `text` without its line ending, which is `\r\n` for documents from Windows
+fn strip_line_ending(text: &str) -> &str {
+    text.trim_end_matches('\n').trim_end_matches('\r')
+}
+
+/// When the file `file_name` was last modified, if it exists
+fn file_modified(file_name: &str) -> Option<SystemTime> {
+    std::fs::metadata(file_name).and_then(|m| m.modified()).ok()
+}
+
+/// The distance between tab stops in Pango units for a font whose chars are `font_width` pixels
+/// wide. The width of a char mustn't be rounded to pixels first, otherwise the tab stops drift
+/// away from the columns of the text after a few tabs.
+fn tab_width(font_width: f64, tab_size: u32) -> i32 {
+    (font_width * f64::from(pango::SCALE)).round() as i32 * max(tab_size, 1) as i32
+}
+
+/// The amount of columns `text` takes up, with tabs expanded to the next tab stop
+fn line_columns(text: &str, tab_size: u32) -> u32 {
+    let tab_size = max(tab_size, 1);
+    strip_line_ending(text).chars().fold(0, |col, c| {
+        if c == '\t' {
+            col + tab_size - col % tab_size
+        } else {
+            col + 1
+        }
+    })
+}
+
+/// Whether `keyval` is a modifier like Ctrl or Shift, which is pressed before the actual key
+fn is_modifier_key(keyval: u32) -> bool {
+    match keyval {
+        key::Shift_L
+        | key::Shift_R
+        | key::Control_L
+        | key::Control_R
+        | key::Alt_L
+        | key::Alt_R
+        | key::Meta_L
+        | key::Meta_R
+        | key::Super_L
+        | key::Super_R
+        | key::ISO_Level3_Shift
+        | key::Caps_Lock => true,
+        _ => false,
+    }
+}
+
+/// Whether pressing the key `keyval` (with the given modifiers) changes the document
+fn modifies_document(keyval: u32, ch: Option<char>, alt: bool, ctrl: bool, norm: bool) -> bool {
+    match keyval {
+        key::Delete
+        | key::BackSpace
+        | key::Return
+        | key::KP_Enter
+        | key::Tab
+        | key::ISO_Left_Tab => true,
+        // Moves lines
+        key::Up | key::Down => alt && !ctrl,
+        _ => match ch {
+            Some('v') | Some('x') | Some('z') | Some('Z') | Some('D') if ctrl => true,
+            Some(c) => norm && c >= '\u{0020}',
+            // Keys without a char (e.g. dead keys) are passed to the input method, which takes
+            // care of ignoring them
+            None => false,
+        },
+    }
+}
+
+/// Lists the capture groups of the first match of `re` in `text` as (group, captured text) pairs.
+/// Groups which didn't participate in the match are shown as empty.
+fn describe_captures(re: &Regex, text: &str) -> Option<Vec<(String, String)>> {
+    let captures = re.captures(text)?;
+
+    Some(
+        re.capture_names()
+            .enumerate()
+            .map(|(i, name)| {
+                let group = match name {
+                    Some(name) => format!("${} ({})", i, name),
+                    None => format!("${}", i),
+                };
+                let captured = captures.get(i).map_or("", |m| m.as_str()).to_string();
+                (group, captured)
+            })
+            .collect(),
+    )
+}
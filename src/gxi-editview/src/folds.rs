@@ -0,0 +1,345 @@
+use crate::line_map::LineMap;
+use serde_json::Value;
+use std::cmp::min;
+use std::collections::BTreeMap;
+
+/// Keeps track of the folded (collapsed) regions of an EditView.
+///
+/// A fold is identified by its first line, which stays visible, and hides all lines after it up
+/// to and including its last line. Folds never overlap. Since xi-editor doesn't know about folds
+/// the EditView has to map between rows (what's visible on screen) and lines (the actual lines of
+/// the document) itself, see `line_at_row` and `row_of_line`.
+#[derive(Debug, Default)]
+pub struct Folds {
+    /// Maps the first line of a fold to its last line
+    folds: BTreeMap<u64, u64>,
+}
+
+impl Folds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    /// Returns whether `line` is the first line of a fold
+    pub fn is_folded(&self, line: u64) -> bool {
+        self.folds.contains_key(&line)
+    }
+
+    /// Folds the lines `start + 1..=end`. Folds inside of this region are merged into it.
+    pub fn fold(&mut self, start: u64, end: u64) {
+        if end <= start {
+            return;
+        }
+        let inner: Vec<u64> = self.folds.range(start..=end).map(|(s, _)| *s).collect();
+        for s in inner {
+            self.folds.remove(&s);
+        }
+        self.folds.insert(start, end);
+    }
+
+    /// Removes the fold starting at `start`. Returns false if there was no such fold.
+    pub fn unfold(&mut self, start: u64) -> bool {
+        self.folds.remove(&start).is_some()
+    }
+
+    /// Removes the fold which hides `line`, if there is one. Returns whether a fold was removed.
+    pub fn reveal(&mut self, line: u64) -> bool {
+        if let Some(start) = self.fold_hiding(line) {
+            self.folds.remove(&start);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the first line of the fold which hides `line`
+    fn fold_hiding(&self, line: u64) -> Option<u64> {
+        self.folds
+            .range(..line)
+            .next_back()
+            .filter(|(_, end)| line <= **end)
+            .map(|(start, _)| *start)
+    }
+
+    /// Amount of lines which are currently hidden
+    pub fn hidden_lines(&self) -> u64 {
+        self.folds.iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// Maps a row (as visible on the screen) to the line of the document
+    pub fn line_at_row(&self, row: u64) -> u64 {
+        let mut line = row;
+        for (start, end) in &self.folds {
+            if *start < line {
+                line += end - start;
+            } else {
+                break;
+            }
+        }
+        line
+    }
+
+    /// Maps a line of the document to the row it's displayed in. Hidden lines are mapped to the
+    /// row of the fold hiding them.
+    pub fn row_of_line(&self, line: u64) -> u64 {
+        let mut row = line;
+        for (start, end) in self.folds.range(..line) {
+            row -= min(*end, line) - start;
+        }
+        row
+    }
+
+    /// Returns the next line after `line` that isn't hidden
+    pub fn next_visible_line(&self, line: u64) -> u64 {
+        match self.folds.get(&line) {
+            Some(end) => end + 1,
+            None => line + 1,
+        }
+    }
+
+    /// Moves the folds according to the `ops` of an `update` xi-editor sent us. Folds whose
+    /// lines have been changed (apart from the first one, which is kept visible) are removed, so
+    /// edits never happen in hidden lines.
+    pub fn apply_update(&mut self, update: &Value, height: u64) {
+        if self.folds.is_empty() {
+            return;
+        }
+        let map = match LineMap::from_update(update) {
+            Some(map) => map,
+            None => return,
+        };
+
+        let folds = std::mem::replace(&mut self.folds, BTreeMap::new());
+        for (start, end) in folds {
+            if let (Some(new_start), Some(new_end)) = (map.map(start), map.map(end)) {
+                // Only keep the fold if nothing has been inserted into or removed from it
+                if new_end > new_start && new_end - new_start == end - start && new_end < height {
+                    self.folds.insert(new_start, new_end);
+                }
+            }
+        }
+    }
+}
+
+fn indentation(text: &str, tab_size: u32) -> Option<u32> {
+    let mut indent = 0;
+    for c in text.chars() {
+        match c {
+            ' ' => indent += 1,
+            '\t' => indent += tab_size - (indent % tab_size),
+            '\n' | '\r' => return None,
+            _ => return Some(indent),
+        }
+    }
+    // Lines which only consist of whitespace don't have a meaningful indentation
+    None
+}
+
+/// Determines the region which can be folded if the user folds the line `start`. `get_line`
+/// returns the text of a line or None if it isn't known (yet), in which case the region ends
+/// before it.
+///
+/// Lines ending with an opening bracket are folded up to the line containing the matching closing
+/// bracket, all other lines are folded up to the last following line which is indented further.
+/// The closing line is kept visible in both cases. Returns the last line of the region, if it
+/// contains any lines.
+pub fn fold_region<'a, F>(get_line: F, start: u64, height: u64, tab_size: u32) -> Option<u64>
+where
+    F: Fn(u64) -> Option<&'a str>,
+{
+    let text = get_line(start)?;
+    let start_indent = indentation(text, tab_size)?;
+
+    if let Some(open) = text
+        .trim_end()
+        .chars()
+        .last()
+        .filter(|c| is_open_bracket(*c))
+    {
+        if let Some(end) = bracket_region_end(&get_line, start, height, open) {
+            return Some(end).filter(|end| *end > start);
+        }
+    }
+
+    let mut end = None;
+    for line in start + 1..height {
+        let text = match get_line(line) {
+            Some(text) => text,
+            None => break,
+        };
+        match indentation(text, tab_size) {
+            Some(indent) if indent > start_indent => end = Some(line),
+            Some(_) => break,
+            // Empty lines belong to the region if it continues after them
+            None => (),
+        }
+    }
+    end
+}
+
+/// Whether `fold_region` would find a region for `start`. This only looks at the next line, so
+/// it's cheap enough to be called for every visible line when drawing.
+pub fn is_foldable<'a, F>(get_line: F, start: u64, height: u64, tab_size: u32) -> bool
+where
+    F: Fn(u64) -> Option<&'a str>,
+{
+    let text = match get_line(start) {
+        Some(text) => text,
+        None => return false,
+    };
+    let start_indent = match indentation(text, tab_size) {
+        Some(indent) => indent,
+        None => return false,
+    };
+    if text
+        .trim_end()
+        .chars()
+        .last()
+        .map_or(false, is_open_bracket)
+    {
+        return start + 1 < height;
+    }
+
+    (start + 1..height)
+        .map(|line| get_line(line))
+        .take_while(Option::is_some)
+        .filter_map(|text| text.and_then(|t| indentation(t, tab_size)))
+        .next()
+        .map_or(false, |indent| indent > start_indent)
+}
+
+fn is_open_bracket(c: char) -> bool {
+    c == '{' || c == '[' || c == '('
+}
+
+fn closing_bracket(open: char) -> char {
+    match open {
+        '{' => '}',
+        '[' => ']',
+        _ => ')',
+    }
+}
+
+/// Returns the line before the one containing the bracket matching the one `start` ends with
+fn bracket_region_end<'a, F>(get_line: &F, start: u64, height: u64, open: char) -> Option<u64>
+where
+    F: Fn(u64) -> Option<&'a str>,
+{
+    let close = closing_bracket(open);
+    let mut depth = 1;
+    for line in start + 1..height {
+        for c in get_line(line)?.chars() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(line - 1);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rows_and_lines() {
+        let mut folds = Folds::new();
+        folds.fold(2, 5);
+        folds.fold(8, 9);
+
+        assert_eq!(folds.hidden_lines(), 4);
+        assert_eq!(folds.line_at_row(2), 2);
+        assert_eq!(folds.line_at_row(3), 6);
+        assert_eq!(folds.line_at_row(5), 8);
+        assert_eq!(folds.line_at_row(6), 10);
+        assert_eq!(folds.row_of_line(4), 2);
+        assert_eq!(folds.row_of_line(6), 3);
+        assert_eq!(folds.row_of_line(10), 6);
+        assert_eq!(folds.next_visible_line(2), 6);
+        assert_eq!(folds.next_visible_line(6), 7);
+    }
+
+    #[test]
+    fn nested_folds() {
+        let mut folds = Folds::new();
+        folds.fold(3, 4);
+        folds.fold(1, 6);
+        assert!(!folds.is_folded(3));
+        assert_eq!(folds.hidden_lines(), 5);
+
+        assert!(folds.reveal(4));
+        assert!(folds.is_empty());
+    }
+
+    #[test]
+    fn update_moves_folds() {
+        let mut folds = Folds::new();
+        folds.fold(2, 4);
+        folds.fold(10, 12);
+
+        // Two lines inserted above the first fold, the header of the second one has been changed
+        folds.apply_update(
+            &json!({"ops": [
+                {"op": "copy", "n": 1},
+                {"op": "ins", "n": 2, "lines": []},
+                {"op": "copy", "n": 9},
+                {"op": "skip", "n": 1},
+                {"op": "ins", "n": 1, "lines": []},
+                {"op": "copy", "n": 5},
+            ]}),
+            18,
+        );
+        assert!(folds.is_folded(4));
+        assert!(folds.is_folded(12));
+        assert_eq!(folds.line_at_row(5), 7);
+
+        // A line has been removed from the first fold
+        folds.apply_update(
+            &json!({"ops": [
+                {"op": "copy", "n": 5},
+                {"op": "skip", "n": 1},
+                {"op": "copy", "n": 12},
+            ]}),
+            17,
+        );
+        assert!(!folds.is_folded(4));
+        assert!(folds.is_folded(11));
+    }
+
+    #[test]
+    fn regions() {
+        let text = [
+            "fn main() {\n",
+            "    if true {\n",
+            "        foo();\n",
+            "\n",
+            "        bar();\n",
+            "    }\n",
+            "}\n",
+            "def foo():\n",
+            "    pass\n",
+            "\n",
+            "x = 1\n",
+        ];
+        let get_line = |n: u64| text.get(n as usize).cloned();
+        let height = text.len() as u64;
+
+        assert_eq!(fold_region(get_line, 0, height, 4), Some(5));
+        assert_eq!(fold_region(get_line, 1, height, 4), Some(4));
+        assert_eq!(fold_region(get_line, 2, height, 4), None);
+        assert_eq!(fold_region(get_line, 7, height, 4), Some(8));
+        assert!(is_foldable(get_line, 1, height, 4));
+        assert!(is_foldable(get_line, 7, height, 4));
+        assert!(!is_foldable(get_line, 8, height, 4));
+    }
+}
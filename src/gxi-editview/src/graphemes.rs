@@ -0,0 +1,53 @@
+//! Grapheme clusters (UAX #29) are what users see as one character, e.g. an emoji made of several
+//! code points or a letter with combining marks. The caret must never end up inside of one.
+
+use unicode_segmentation::GraphemeCursor;
+
+/// The byte index of the end of the grapheme cluster starting at (or containing) `index`, or the
+/// length of `text` if `index` is at its end
+pub fn grapheme_end(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    let mut cursor = GraphemeCursor::new(index, text.len(), true);
+    cursor
+        .next_boundary(text, 0)
+        .ok()
+        .and_then(|end| end)
+        .unwrap_or_else(|| text.len())
+}
+
+/// Where the caret goes when the user clicks at `index` of `text`: Pango tells us the
+/// `trailing` number of chars if the click was on the right half of a grapheme, in which case
+/// the caret goes after the whole grapheme, not just after its first char.
+pub fn click_index(text: &str, index: usize, trailing: i32) -> usize {
+    if trailing > 0 {
+        grapheme_end(text, index)
+    } else {
+        index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ends() {
+        let text = "ae\u{301}👩\u{200d}💻漢";
+        assert_eq!(grapheme_end(text, 0), 1);
+        // The combining accent belongs to the e
+        assert_eq!(grapheme_end(text, 1), 4);
+        // The emoji sequence is one grapheme
+        assert_eq!(grapheme_end(text, 4), 15);
+        assert_eq!(grapheme_end(text, 15), 18);
+        assert_eq!(grapheme_end(text, 18), 18);
+    }
+
+    #[test]
+    fn clicks() {
+        let text = "ae\u{301}";
+        assert_eq!(click_index(text, 1, 0), 1);
+        assert_eq!(click_index(text, 1, 2), 4);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::line_items::LineItems;
+use serde_json::Value;
+use std::rc::Rc;
+use syntect::highlighting::Color;
+
+/// A marker in the gutter next to the line numbers, e.g. for a diagnostic, a changed line or a
+/// breakpoint. Everything that marks lines there goes through these, so the gutter is drawn in
+/// one place.
+#[derive(Clone, Default)]
+pub struct GutterMarker {
+    /// What added the marker, e.g. `"diagnostics"`, so it can remove all of its markers at once
+    pub source: String,
+    /// Name of the icon shown. Without one a dot is drawn.
+    pub icon: Option<String>,
+    /// Color of the dot, or the color of the caret if None
+    pub color: Option<Color>,
+    pub tooltip: Option<String>,
+    /// Called with the line of the marker when it's clicked
+    pub on_click: Option<Rc<dyn Fn(u64)>>,
+}
+
+/// Identifies a marker, to remove it again
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MarkerId(u64);
+
+/// The markers of an EditView. They follow their lines when lines are inserted or removed above
+/// them.
+#[derive(Default)]
+pub struct GutterMarkers {
+    markers: LineItems<GutterMarker>,
+}
+
+impl GutterMarkers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    pub fn add(&mut self, line: u64, marker: GutterMarker) -> MarkerId {
+        MarkerId(self.markers.add(line, marker))
+    }
+
+    /// Removes the marker `id`. Returns whether it was still there, markers of removed lines are
+    /// gone already.
+    pub fn remove(&mut self, id: MarkerId) -> bool {
+        self.markers.remove(id.0).is_some()
+    }
+
+    /// Removes all markers added by `source`
+    pub fn clear(&mut self, source: &str) {
+        self.markers.remove_where(|marker| marker.source == source);
+    }
+
+    /// The line marker `id` is on now
+    pub fn line(&self, id: MarkerId) -> Option<u64> {
+        self.markers.line(id.0)
+    }
+
+    /// The markers of `line`, the one added last is last
+    pub fn at(&self, line: u64) -> Vec<&GutterMarker> {
+        self.markers.at(line).collect()
+    }
+
+    /// Moves the markers along with their lines according to an `update` xi-editor sent us.
+    /// Markers of removed lines are removed too.
+    pub fn apply_update(&mut self, update: &Value, height: u64) {
+        self.markers.apply_update(update, height);
+    }
+}
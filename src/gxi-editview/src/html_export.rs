@@ -0,0 +1,219 @@
+//! Renders documents with their syntax highlighting into standalone HTML, so they look the same
+//! in a browser as they do in gxi. All CSS is inline, the file doesn't need anything else.
+
+use crate::theme::{color_from_u32, LineStyle};
+use std::ops::Range;
+use syntect::highlighting::Color;
+
+/// A line of a document with the styles of its parts. The ranges are byte offsets into `text`.
+#[derive(Clone, Debug)]
+pub struct StyledLine {
+    pub text: String,
+    pub styles: Vec<(Range<usize>, LineStyle)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HtmlOptions {
+    /// Title of the HTML document, e.g. the file name
+    pub title: String,
+    /// Whether each line is prefixed with its number
+    pub line_numbers: bool,
+    pub font_family: String,
+    /// Size of the font in points
+    pub font_size: f64,
+    pub tab_size: u32,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn css_color(c: Color) -> String {
+    if c.a == 255 {
+        format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+    } else {
+        format!(
+            "rgba({}, {}, {}, {:.2})",
+            c.r,
+            c.g,
+            c.b,
+            f64::from(c.a) / 255.0
+        )
+    }
+}
+
+/// The CSS declarations for `style`, e.g. `color: #ff0000; font-weight: 700`
+fn style_css(style: &LineStyle) -> String {
+    let mut declarations = Vec::new();
+    if let Some(fg) = style.fg_color {
+        declarations.push(format!("color: {}", css_color(color_from_u32(fg))));
+    }
+    if let Some(bg) = style.bg_color {
+        declarations.push(format!("background: {}", css_color(color_from_u32(bg))));
+    }
+    if let Some(weight) = style.weight {
+        declarations.push(format!("font-weight: {}", weight));
+    }
+    if style.italic == Some(true) {
+        declarations.push("font-style: italic".to_string());
+    }
+    if style.underline == Some(true) {
+        declarations.push("text-decoration: underline".to_string());
+    }
+    declarations.join("; ")
+}
+
+/// Renders `line` with a `<span>` for each of its styled parts
+fn line_html(line: &StyledLine) -> String {
+    let mut html = String::new();
+    let mut pos = 0;
+    for (range, style) in &line.styles {
+        // Overlapping styles (which xi-editor shouldn't send) are cut off
+        let start = range.start.max(pos);
+        let (before, styled) = match (line.text.get(pos..start), line.text.get(start..range.end)) {
+            (Some(before), Some(styled)) if !styled.is_empty() => (before, styled),
+            _ => continue,
+        };
+        html.push_str(&escape(before));
+        let css = style_css(style);
+        if css.is_empty() {
+            html.push_str(&escape(styled));
+        } else {
+            html.push_str(&format!(
+                "<span style=\"{}\">{}</span>",
+                css,
+                escape(styled)
+            ));
+        }
+        pos = range.end;
+    }
+    html.push_str(&escape(line.text.get(pos..).unwrap_or_default()));
+    html
+}
+
+/// Renders `lines` into a `<pre>` element, e.g. for the clipboard. The title is left out.
+pub fn to_html_fragment(lines: &[StyledLine], options: &HtmlOptions) -> String {
+    let mut pre_css = vec![
+        format!(
+            "font-family: \"{}\", monospace",
+            escape(&options.font_family)
+        ),
+        format!("font-size: {}pt", options.font_size),
+        format!("tab-size: {}", options.tab_size),
+        "margin: 0".to_string(),
+        "padding: 1em".to_string(),
+    ];
+    if let Some(fg) = options.foreground {
+        pre_css.push(format!("color: {}", css_color(fg)));
+    }
+    if let Some(bg) = options.background {
+        pre_css.push(format!("background: {}", css_color(bg)));
+    }
+
+    let width = lines.len().to_string().len();
+    let body: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if options.line_numbers {
+                format!(
+                    "<span style=\"opacity: 0.5; user-select: none\">{:>width$}  </span>{}",
+                    i + 1,
+                    line_html(line),
+                    width = width
+                )
+            } else {
+                line_html(line)
+            }
+        })
+        .collect();
+
+    format!(
+        "<pre style=\"{}\">{}</pre>",
+        pre_css.join("; "),
+        body.join("\n")
+    )
+}
+
+/// Renders `lines` into a standalone HTML document
+pub fn to_html(lines: &[StyledLine], options: &HtmlOptions) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body style=\"margin: 0\">\n{}\n</body>\n</html>\n",
+        escape(&options.title),
+        to_html_fragment(lines, options)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn style(fg_color: Option<u32>, weight: Option<u32>) -> LineStyle {
+        LineStyle {
+            fg_color,
+            bg_color: None,
+            weight,
+            italic: None,
+            underline: None,
+        }
+    }
+
+    #[test]
+    fn styled_line() {
+        let line = StyledLine {
+            text: "let a = b < c;".to_string(),
+            styles: vec![
+                (0..3, style(Some(0xffff_0000), Some(700))),
+                (8..9, style(None, None)),
+                (10..11, style(Some(0x8000_ff00), None)),
+            ],
+        };
+        assert_eq!(
+            line_html(&line),
+            "<span style=\"color: #ff0000; font-weight: 700\">let</span> a = b \
+             <span style=\"color: rgba(0, 255, 0, 0.50)\">&lt;</span> c;"
+        );
+    }
+
+    #[test]
+    fn document() {
+        let lines: Vec<StyledLine> = (0..10)
+            .map(|_| StyledLine {
+                text: "x".to_string(),
+                styles: Vec::new(),
+            })
+            .collect();
+        let options = HtmlOptions {
+            title: "a&b.rs".to_string(),
+            line_numbers: true,
+            font_family: "Source Code Pro".to_string(),
+            font_size: 12.0,
+            tab_size: 4,
+            foreground: None,
+            background: Some(Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255,
+            }),
+        };
+        let html = to_html(&lines, &options);
+        assert!(html.contains("<title>a&amp;b.rs</title>"));
+        assert!(html.contains("background: #000000"));
+        assert!(html.contains(">10  </span>x</pre>"));
+        assert!(html.contains("> 1  </span>x\n"));
+    }
+}
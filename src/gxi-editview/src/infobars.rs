@@ -0,0 +1,93 @@
+//! Non-modal notifications, shown as `InfoBar`s above the text of an EditView or above the
+//! notebook of the window. Unlike dialogs they don't interrupt the user, who closes them once
+//! they've read them.
+
+use gtk::*;
+use log::{error, info, warn};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const INFOBAR_GLADE_SRC: &str = include_str!("ui/infobar.glade");
+/// At most this many notifications are shown at once, the oldest ones are closed for new ones
+const MAX_SHOWN: usize = 3;
+
+pub struct InfoBars {
+    area: gtk::Box,
+    /// The notifications which are shown, oldest first, with their messages
+    shown: Rc<RefCell<Vec<(String, InfoBar)>>>,
+}
+
+impl InfoBars {
+    /// Shows notifications in `area`, which should be a vertical box
+    pub fn new(area: &gtk::Box) -> Self {
+        Self {
+            area: area.clone(),
+            shown: Default::default(),
+        }
+    }
+
+    /// Shows `msg` until the user closes it. The same message isn't shown twice, it's moved to
+    /// the top instead.
+    pub fn show(&self, msg: &str, msg_type: MessageType) {
+        self.add(msg, msg_type, None);
+    }
+
+    /// Like `show`, but with a button labelled `action_label` next to the close button, which
+    /// runs `action` and closes the notification
+    pub fn show_with_action<F: Fn() + 'static>(
+        &self,
+        msg: &str,
+        msg_type: MessageType,
+        action_label: &str,
+        action: F,
+    ) {
+        self.add(msg, msg_type, Some((action_label, Box::new(action))));
+    }
+
+    fn add(&self, msg: &str, msg_type: MessageType, action: Option<(&str, Box<dyn Fn()>)>) {
+        match msg_type {
+            MessageType::Error => error!("{}", msg),
+            MessageType::Warning => warn!("{}", msg),
+            _ => info!("{}", msg),
+        }
+
+        let mut shown = self.shown.borrow_mut();
+        if let Some(ix) = shown.iter().position(|(shown_msg, _)| shown_msg == msg) {
+            shown.remove(ix).1.destroy();
+        }
+        while shown.len() >= MAX_SHOWN {
+            shown.remove(0).1.destroy();
+        }
+
+        let builder = Builder::new_from_string(INFOBAR_GLADE_SRC);
+        let infobar: InfoBar = builder.get_object("infobar").unwrap();
+        let label: Label = builder.get_object("infobar_label").unwrap();
+        label.set_text(msg);
+        infobar.set_message_type(msg_type);
+        // Connected before the handler closing the infobar, which disconnects it
+        if let Some((action_label, action)) = action {
+            infobar.add_button(action_label, ResponseType::Accept);
+            infobar.connect_response(move |_, response| {
+                if response == ResponseType::Accept {
+                    action();
+                }
+            });
+        }
+        let shown_rc = self.shown.clone();
+        infobar.connect_response(move |infobar, _| {
+            shown_rc.borrow_mut().retain(|(_, shown)| shown != infobar);
+            infobar.destroy();
+        });
+
+        self.area.pack_start(&infobar, false, true, 0);
+        self.area.reorder_child(&infobar, 0);
+        shown.push((msg.to_string(), infobar));
+    }
+
+    /// Closes all notifications
+    pub fn clear(&self) {
+        for (_, infobar) in self.shown.borrow_mut().drain(..) {
+            infobar.destroy();
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! The editing component of gxi: a GTK widget showing a document of xi-editor, with its line
+//! cache and theme rendering. It doesn't need the rest of gxi, so other GTK applications can embed
+//! it, too.
+//!
+//! An application talks to xi-core through a `gxi_peer::Core` and keeps one `MainState` (the
+//! settings, theme and styles) for all of its `EditView`s:
+//!
+//! 1. Create a view: send xi-core a `new_view` request with `Core::send_request` and pass the
+//!    view id it answers with to `EditView::new`.
+//! 2. Feed updates: pass xi-core's notifications on to the `EditView` they're for (by their
+//!    `view_id`), e.g. `update` to `EditView::update`, `scroll_to` to `EditView::scroll_to` and
+//!    `config_changed` to `EditView::config_changed`. `theme_changed` and `def_style` go to the
+//!    `MainState` instead.
+//! 3. Get the widget: `EditView::root_widget` is what goes into the application's window.
+//!
+//! ```ignore
+//! let settings = Settings::from_gschema(gschema);
+//! let main_state = Rc::new(RefCell::new(MainState::new(settings)));
+//! let edit_view = EditView::new(&main_state, &core, &menu_button, None, view_id, &window);
+//! container.add(&edit_view.borrow().root_widget);
+//!
+//! // For every notification of xi-core:
+//! match method.as_str() {
+//!     "update" => edit_view.borrow_mut().update(&params),
+//!     "theme_changed" => main_state.borrow_mut().theme_changed(&params),
+//!     "def_style" => main_state.borrow_mut().def_style(&params),
+//!     _ => {}
+//! }
+//! ```
+//!
+//! The settings are read from a `GSchema` with the keys of gxi's schema
+//! (`com.github.Cogitri.gxi`), which the application has to install.
+
+#[macro_use]
+extern crate enclose;
+
+pub mod abbreviations;
+pub mod annotations;
+mod bookmarks;
+mod brackets;
+mod caret_history;
+mod colors;
+mod comments;
+mod completion;
+mod document;
+pub mod edit_view;
+mod folds;
+pub mod fonts;
+mod graphemes;
+pub mod gutter_markers;
+pub mod html_export;
+pub mod infobars;
+mod line_items;
+mod line_map;
+pub mod line_transforms;
+mod links;
+mod lists;
+pub mod main_state;
+mod markdown_table;
+mod match_counter;
+mod occurrences;
+mod paste;
+mod path_completion;
+mod quick_marks;
+mod regex_replace;
+pub mod rtf_export;
+mod search_history;
+mod search_ticks;
+mod smart_punctuation;
+pub mod spell_check;
+pub mod theme;
+mod undo_grouping;
+mod view_item;
+mod vim;
+mod whitespace;
+mod word_completion;
+mod words;
+
+pub use crate::edit_view::EditView;
+pub use crate::line_transforms::LineTransform;
+pub use crate::main_state::{MainState, Settings, WhitespaceMode};
@@ -0,0 +1,143 @@
+use crate::line_map::LineMap;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Things which belong to lines of a document, e.g. gutter markers or annotations. Each of them
+/// gets an id when it's added, so it can be removed again, and follows its line when lines are
+/// inserted or removed above it.
+pub struct LineItems<T> {
+    /// The items and their lines by id, i.e. in the order they've been added
+    items: BTreeMap<u64, (u64, T)>,
+    next_id: u64,
+}
+
+impl<T> Default for LineItems<T> {
+    fn default() -> Self {
+        Self {
+            items: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> LineItems<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Adds `item` to `line` and returns its id
+    pub fn add(&mut self, line: u64, item: T) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.items.insert(id, (line, item));
+        id
+    }
+
+    /// Removes the item `id` and returns it, unless it's gone already, e.g. with its line
+    pub fn remove(&mut self, id: u64) -> Option<T> {
+        self.items.remove(&id).map(|(_, item)| item)
+    }
+
+    /// Removes the items `remove` returns true for and returns them
+    pub fn remove_where<F: Fn(&T) -> bool>(&mut self, remove: F) -> Vec<T> {
+        let ids: Vec<u64> = self
+            .items
+            .iter()
+            .filter(|(_, (_, item))| remove(item))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.into_iter().filter_map(|id| self.remove(id)).collect()
+    }
+
+    /// The line item `id` is on now
+    pub fn line(&self, id: u64) -> Option<u64> {
+        self.items.get(&id).map(|(line, _)| *line)
+    }
+
+    /// The items of `line`, the one added last is last
+    pub fn at(&self, line: u64) -> impl Iterator<Item = &T> {
+        self.items
+            .values()
+            .filter(move |(l, _)| *l == line)
+            .map(|(_, item)| item)
+    }
+
+    /// All items and their lines, in the order they've been added
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &T)> {
+        self.items.values().map(|(line, item)| (*line, item))
+    }
+
+    /// Moves the items along with their lines according to an `update` xi-editor sent us. Items
+    /// of removed lines are removed too and returned.
+    pub fn apply_update(&mut self, update: &Value, height: u64) -> Vec<T> {
+        let mut removed = Vec::new();
+        if self.items.is_empty() {
+            return removed;
+        }
+        if let Some(map) = LineMap::from_update(update) {
+            let items = std::mem::replace(&mut self.items, BTreeMap::new());
+            for (id, (line, item)) in items {
+                match map.map(line).filter(|line| *line < height) {
+                    Some(line) => {
+                        self.items.insert(id, (line, item));
+                    }
+                    None => removed.push(item),
+                }
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn add_and_remove() {
+        let mut items = LineItems::new();
+        let a = items.add(2, "git");
+        items.add(2, "diagnostics");
+        let c = items.add(5, "diagnostics");
+        assert_eq!(
+            items.at(2).collect::<Vec<_>>(),
+            vec![&"git", &"diagnostics"]
+        );
+        assert_eq!(items.remove(a), Some("git"));
+        assert_eq!(items.remove(a), None);
+        assert_eq!(
+            items.remove_where(|item| *item == "diagnostics"),
+            vec!["diagnostics", "diagnostics"]
+        );
+        assert!(items.is_empty());
+        assert_eq!(items.line(c), None);
+    }
+
+    #[test]
+    fn update_moves_items() {
+        let mut items = LineItems::new();
+        let a = items.add(1, "a");
+        let b = items.add(4, "b");
+        let c = items.add(6, "c");
+        // Two lines are inserted at the start, line 1 is removed and so are the last lines
+        let removed = items.apply_update(
+            &json!({"ops": [
+                {"op": "ins", "n": 2},
+                {"op": "copy", "n": 1},
+                {"op": "skip", "n": 1},
+                {"op": "copy", "n": 5},
+            ]}),
+            7,
+        );
+        assert_eq!(removed, vec!["a", "c"]);
+        assert_eq!(items.line(a), None);
+        assert_eq!(items.line(b), Some(5));
+        assert_eq!(items.line(c), None);
+        assert_eq!(items.iter().collect::<Vec<_>>(), vec![(5, &"b")]);
+    }
+}
@@ -0,0 +1,95 @@
+use serde_json::Value;
+use std::cmp::min;
+
+/// Maps the lines from before an update xi-editor sent us to the lines after it, so state which
+/// belongs to lines (e.g. folds or bookmarks) can follow them when lines are inserted or removed
+/// above.
+#[derive(Debug, Default)]
+pub struct LineMap {
+    /// The old first line, the new first line and the amount of lines of every run of lines that
+    /// survived the update
+    moved: Vec<(u64, u64, u64)>,
+}
+
+impl LineMap {
+    /// Builds the map from the `ops` of an `update`, or returns None if there are no ops
+    pub fn from_update(update: &Value) -> Option<Self> {
+        let ops: Vec<(&str, u64)> = update["ops"]
+            .as_array()?
+            .iter()
+            .map(|op| {
+                (
+                    op["op"].as_str().unwrap_or_default(),
+                    op["n"].as_u64().unwrap_or(0),
+                )
+            })
+            .collect();
+        Some(Self::from_ops(&ops))
+    }
+
+    fn from_ops(ops: &[(&str, u64)]) -> Self {
+        let mut moved = Vec::new();
+        let mut old_ix = 0;
+        let mut new_ix = 0;
+        // Lines which have been skipped, if new lines are inserted directly afterwards we treat
+        // them as changed in place (e.g. because the cursor moved onto them).
+        let mut skipped = 0;
+
+        for &(op, n) in ops {
+            match op {
+                "copy" | "update" => {
+                    moved.push((old_ix, new_ix, n));
+                    old_ix += n;
+                    new_ix += n;
+                    skipped = 0;
+                }
+                "skip" => {
+                    old_ix += n;
+                    skipped += n;
+                }
+                "ins" => {
+                    let in_place = min(skipped, n);
+                    if in_place > 0 {
+                        moved.push((old_ix - skipped, new_ix, in_place));
+                    }
+                    new_ix += n;
+                    skipped = 0;
+                }
+                "invalidate" => {
+                    new_ix += n;
+                    skipped = 0;
+                }
+                _ => {}
+            }
+        }
+        Self { moved }
+    }
+
+    /// Returns the new number of the old `line`, or None if it has been removed
+    pub fn map(&self, line: u64) -> Option<u64> {
+        self.moved
+            .iter()
+            .find(|(old, _, n)| *old <= line && line < old + n)
+            .map(|(old, new, _)| new + (line - old))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn map_lines() {
+        // Lines 2 to 4 are removed and two lines are added at the end
+        let map = LineMap::from_ops(&[("copy", 2), ("skip", 3), ("copy", 1), ("ins", 2)]);
+        assert_eq!(map.map(1), Some(1));
+        assert_eq!(map.map(2), None);
+        assert_eq!(map.map(4), None);
+        assert_eq!(map.map(5), Some(2));
+
+        // Line 2 is changed and a line is inserted after it
+        let map = LineMap::from_ops(&[("copy", 2), ("skip", 1), ("ins", 2), ("copy", 3)]);
+        assert_eq!(map.map(2), Some(2));
+        assert_eq!(map.map(3), Some(4));
+    }
+}
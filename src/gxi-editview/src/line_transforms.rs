@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Ways to rearrange the selected lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineTransform {
+    SortAscending,
+    SortDescending,
+    Reverse,
+    /// Removes duplicate lines, keeping the first occurrence of each
+    Unique,
+}
+
+impl LineTransform {
+    /// Transforms the lines of `text`. A trailing newline stays at the end.
+    pub fn apply(self, text: &str) -> String {
+        let (text, trailing_newline) = if text.ends_with('\n') {
+            (&text[..text.len() - 1], "\n")
+        } else {
+            (text, "")
+        };
+        let mut lines: Vec<&str> = text.split('\n').collect();
+
+        match self {
+            LineTransform::SortAscending => lines.sort(),
+            LineTransform::SortDescending => lines.sort_by(|a, b| b.cmp(a)),
+            LineTransform::Reverse => lines.reverse(),
+            LineTransform::Unique => {
+                let mut seen = HashSet::new();
+                lines.retain(|line| seen.insert(*line));
+            }
+        }
+
+        let mut transformed = lines.join("\n");
+        transformed.push_str(trailing_newline);
+        transformed
+    }
+}
+
+/// The first and last line of each selection, from the selected byte ranges of each line and the
+/// length of the line without its line ending. A selection which includes the line ending of a
+/// line continues on the next one, selections on the same line count as one.
+pub fn selected_regions(lines: &[(Vec<Range<usize>>, usize)]) -> Vec<(usize, usize)> {
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    let mut continues = false;
+    for (n, (ranges, len)) in lines.iter().enumerate() {
+        let ranges: Vec<&Range<usize>> = ranges.iter().filter(|r| !r.is_empty()).collect();
+        if ranges.is_empty() {
+            continues = false;
+            continue;
+        }
+        if continues && ranges[0].start == 0 {
+            if let Some(region) = regions.last_mut() {
+                region.1 = n;
+            }
+        } else {
+            regions.push((n, n));
+        }
+        continues = ranges.last().map_or(false, |r| r.end > *len);
+    }
+    regions
+}
+
+/// Moves `lines` past the line `other`, which is the line above them if `up` and the one below
+/// them otherwise. Returns all of the lines in their new order.
+pub fn move_lines(lines: &[&str], other: &str, up: bool) -> Vec<String> {
+    let lines = lines.iter().map(|line| line.to_string());
+    if up {
+        lines.chain(Some(other.to_string())).collect()
+    } else {
+        Some(other.to_string()).into_iter().chain(lines).collect()
+    }
+}
+
+/// Joins `lines` into one line like Vim's `J` does: the indentation of the joined lines is
+/// removed and they're separated by a space, unless they're empty
+pub fn join_lines(lines: &[&str]) -> String {
+    let mut joined = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        let line = if i == 0 { line.trim_end() } else { line.trim() };
+        if i > 0 && !line.is_empty() && !joined.is_empty() {
+            joined.push(' ');
+        }
+        joined.push_str(line);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transforms() {
+        let text = "b\na\nc\na\n";
+        assert_eq!(LineTransform::SortAscending.apply(text), "a\na\nb\nc\n");
+        assert_eq!(LineTransform::SortDescending.apply(text), "c\nb\na\na\n");
+        assert_eq!(LineTransform::Reverse.apply(text), "a\nc\na\nb\n");
+        assert_eq!(LineTransform::Unique.apply(text), "b\na\nc\n");
+        assert_eq!(LineTransform::Reverse.apply("a\nb"), "b\na");
+    }
+
+    #[test]
+    fn finds_selected_regions() {
+        let lines = vec![
+            // The first selection starts in the middle of a line and spans two lines
+            (vec![2..4], 3),
+            (vec![0..1], 5),
+            (vec![], 5),
+            // Two selections on the same line
+            (vec![0..1, 3..4], 5),
+            (vec![0..2], 2),
+            (vec![], 0),
+        ];
+        assert_eq!(selected_regions(&lines), vec![(0, 1), (3, 3), (4, 4)]);
+        assert!(selected_regions(&[(vec![0..0], 3)]).is_empty());
+    }
+
+    #[test]
+    fn moves_lines() {
+        assert_eq!(move_lines(&["b", "c"], "a", true), vec!["b", "c", "a"]);
+        assert_eq!(move_lines(&["a", "b"], "c", false), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn joins_lines() {
+        assert_eq!(join_lines(&["    foo(a,", "        b) "]), "    foo(a, b)");
+        assert_eq!(join_lines(&["a", "", "  b"]), "a b");
+        assert_eq!(join_lines(&["", "b"]), "b");
+    }
+}
@@ -0,0 +1,109 @@
+//! Finds URLs and paths of existing files in the text, so they can be opened with Ctrl+click.
+//! Paths may be followed by a line number like `src/main.rs:12`, as compilers and grep print them.
+
+use std::path::{Path, PathBuf};
+
+/// Something the user can open by Ctrl+clicking it
+#[derive(Clone, Debug, PartialEq)]
+pub enum Link {
+    Url(String),
+    /// A file and the line to move the cursor to
+    File(PathBuf, Option<u64>),
+}
+
+const URL_SCHEMES: &[&str] = &["http://", "https://", "ftp://", "file://", "mailto:"];
+
+/// Chars which end a link, e.g. the quotes or brackets around it
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "\"'`<>()[]{}".contains(c)
+}
+
+/// Returns the delimited part of `text` around byte `col`, without trailing punctuation
+fn token_at(text: &str, col: usize) -> Option<&str> {
+    if col > text.len() || !text.is_char_boundary(col) {
+        return None;
+    }
+    let start = text[..col]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| !is_delimiter(*c))
+        .last()
+        .map_or(col, |(ix, _)| ix);
+    let end = text[col..]
+        .char_indices()
+        .find(|(_, c)| is_delimiter(*c))
+        .map_or(text.len(), |(ix, _)| col + ix);
+    Some(text[start..end].trim_end_matches(|c| ".,;:!?".contains(c))).filter(|t| !t.is_empty())
+}
+
+/// Splits a `path:line` or `path:line:column` into the path and the line
+fn split_line(token: &str) -> (&str, Option<u64>) {
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let mut path = token;
+    let mut line = None;
+    // The number stripped last is the line
+    for _ in 0..2 {
+        match path.rfind(':') {
+            Some(ix) if is_number(&path[ix + 1..]) => {
+                line = path[ix + 1..].parse().ok();
+                path = &path[..ix];
+            }
+            _ => break,
+        }
+    }
+    (path, line)
+}
+
+/// Returns the link at byte `col` of `text`. Relative paths are relative to `base_dir`, they
+/// only count as link if `is_file` says there's a file with that path.
+pub fn link_at<F>(text: &str, col: usize, base_dir: &Path, is_file: F) -> Option<Link>
+where
+    F: Fn(&Path) -> bool,
+{
+    let token = token_at(text, col)?;
+    if URL_SCHEMES.iter().any(|scheme| token.starts_with(scheme)) {
+        return Some(Link::Url(token.to_string()));
+    }
+
+    let (path, line) = split_line(token);
+    let path = if path.starts_with("~/") {
+        glib::get_home_dir()?.join(&path[2..])
+    } else {
+        base_dir.join(path)
+    };
+    if is_file(&path) {
+        Some(Link::File(path, line))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn links() {
+        let base = Path::new("/project");
+        let is_file = |p: &Path| p == Path::new("/project/src/main.rs");
+
+        assert_eq!(
+            link_at("see <https://gxi.cogitri.dev>.", 10, base, is_file),
+            Some(Link::Url("https://gxi.cogitri.dev".to_string()))
+        );
+        assert_eq!(
+            link_at("error at src/main.rs:12:5: oops", 12, base, is_file),
+            Some(Link::File(PathBuf::from("/project/src/main.rs"), Some(12)))
+        );
+        assert_eq!(
+            link_at("(src/main.rs)", 3, base, is_file),
+            Some(Link::File(PathBuf::from("/project/src/main.rs"), None))
+        );
+        assert_eq!(
+            link_at("/project/src/main.rs:3", 0, base, is_file),
+            Some(Link::File(PathBuf::from("/project/src/main.rs"), Some(3)))
+        );
+        assert_eq!(link_at("src/lib.rs", 2, base, is_file), None);
+        assert_eq!(link_at("a  b", 2, base, is_file), None);
+    }
+}
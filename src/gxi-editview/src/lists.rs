@@ -0,0 +1,174 @@
+/// The marker of a list item, e.g. `-` or `1.`
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Marker {
+    Bullet(char),
+    /// A bullet followed by a checkbox, like `- [ ]`
+    Task(char),
+    /// The number of the item and the char following it, either `.` or `)`
+    Number(u64, char),
+}
+
+/// An item of a Markdown (or plain text) list
+#[derive(Debug, PartialEq)]
+pub struct ListItem<'a> {
+    indent: &'a str,
+    marker: Marker,
+    /// Byte index of the start of the item's text
+    pub content_start: usize,
+    /// Whether the item consists of nothing but its marker
+    pub is_empty: bool,
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// Parses `line` as list item, if it is one. The marker has to be followed by whitespace, so
+/// e.g. `*emphasis*` or `---` aren't mistaken for list items.
+pub fn parse(line: &str) -> Option<ListItem> {
+    let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+    let text = line.trim_start_matches(is_blank);
+    let indent = &line[..line.len() - text.len()];
+
+    let first = text.chars().next()?;
+    let (marker, rest) = if first == '-' || first == '*' || first == '+' {
+        (Marker::Bullet(first), &text[1..])
+    } else {
+        let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        // CommonMark limits ordered list numbers to 9 digits
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        let delimiter = text[digits..]
+            .chars()
+            .next()
+            .filter(|c| *c == '.' || *c == ')')?;
+        (
+            Marker::Number(text[..digits].parse().ok()?, delimiter),
+            &text[digits + 1..],
+        )
+    };
+    if !rest.starts_with(is_blank) {
+        return None;
+    }
+
+    let mut content = rest.trim_start_matches(is_blank);
+    let marker = match marker {
+        Marker::Bullet(c) if ["[ ]", "[x]", "[X]"].iter().any(|b| content.starts_with(b)) => {
+            let after = &content[3..];
+            if !after.is_empty() && !after.starts_with(is_blank) {
+                marker
+            } else {
+                content = after.trim_start_matches(is_blank);
+                Marker::Task(c)
+            }
+        }
+        _ => marker,
+    };
+
+    Some(ListItem {
+        indent,
+        marker,
+        content_start: line.len() - content.len(),
+        is_empty: content.is_empty(),
+    })
+}
+
+impl<'a> ListItem<'a> {
+    /// What the item after this one starts with, e.g. `2. ` after `1. foo`. Tasks are continued
+    /// with an unchecked checkbox.
+    pub fn next_prefix(&self) -> String {
+        match self.marker {
+            Marker::Bullet(c) => format!("{}{} ", self.indent, c),
+            Marker::Task(c) => format!("{}{} [ ] ", self.indent, c),
+            Marker::Number(n, delimiter) => format!("{}{}{} ", self.indent, n + 1, delimiter),
+        }
+    }
+}
+
+/// Renumbers the items of an ordered list following a newly inserted item after `item`. `lines`
+/// are the lines following the new item. Returns the new text of the lines up to the last item
+/// that belongs to the list, without newlines. Nested items are left alone.
+pub fn renumber(lines: &[&str], item: &ListItem) -> Vec<String> {
+    let (mut number, delimiter) = match item.marker {
+        Marker::Number(n, delimiter) => (n + 2, delimiter),
+        _ => return Vec::new(),
+    };
+
+    let mut renumbered = Vec::new();
+    for line in lines {
+        let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+        match parse(line) {
+            Some(ListItem {
+                indent,
+                marker: Marker::Number(_, d),
+                content_start,
+                ..
+            }) if indent == item.indent && d == delimiter => {
+                let content = &line[content_start..];
+                renumbered.push(format!("{}{}{} {}", indent, number, delimiter, content));
+                number += 1;
+            }
+            // Lines indented further belong to the previous item
+            _ if line.starts_with(item.indent)
+                && line[item.indent.len()..].starts_with(is_blank)
+                && !line.trim().is_empty() =>
+            {
+                renumbered.push(line.to_string());
+            }
+            _ => break,
+        }
+    }
+    // Only return the lines which belong to the list up to its last item
+    while renumbered.last().map_or(false, |l| {
+        parse(l).map_or(true, |i| i.indent != item.indent)
+    }) {
+        renumbered.pop();
+    }
+    renumbered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_items() {
+        let item = parse("  - foo\n").unwrap();
+        assert_eq!(item.content_start, 4);
+        assert_eq!(item.next_prefix(), "  - ");
+        assert!(!item.is_empty);
+
+        let item = parse("* [x] done").unwrap();
+        assert_eq!(item.content_start, 6);
+        assert_eq!(item.next_prefix(), "* [ ] ");
+        assert!(parse("- [ ] ").unwrap().is_empty);
+        assert_eq!(parse("- [link](url)").unwrap().next_prefix(), "- ");
+
+        assert_eq!(parse("9) bar").unwrap().next_prefix(), "10) ");
+        assert!(parse("1. ").unwrap().is_empty);
+
+        assert_eq!(parse("-"), None);
+        assert_eq!(parse("*emphasis*"), None);
+        assert_eq!(parse("---"), None);
+        assert_eq!(parse("1.5 apples"), None);
+    }
+
+    #[test]
+    fn renumber_items() {
+        let item = parse("1. foo").unwrap();
+        let lines = [
+            "2. bar\n",
+            "   more bar\n",
+            "   1. nested\n",
+            "3. baz\n",
+            "\n",
+            "4. other",
+        ];
+        assert_eq!(
+            renumber(&lines, &item),
+            vec!["3. bar", "   more bar", "   1. nested", "4. baz"]
+        );
+        assert!(renumber(&["- foo"], &parse("- bar").unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use crate::abbreviations::Abbreviations;
+use crate::quick_marks::QuickMarks;
+use crate::spell_check::SpellChecker;
+use crate::theme::{color_from_hex, u32_from_color, LineStyle};
+use crate::word_completion::BufferWords;
+use gettextrs::gettext;
+use gio::SettingsExt;
+use gxi_config_storage::{GSchema, GSchemaExt};
+use log::error;
+use serde_json::Value;
+use std::collections::HashMap;
+use syntect::highlighting::{Color, ThemeSettings};
+
+/// Where whitespace is made visible, if drawing it is enabled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhitespaceMode {
+    All,
+    /// Only draw whitespace which is selected
+    Selection,
+    /// Only draw whitespace at the end of lines
+    Trailing,
+}
+
+impl Default for WhitespaceMode {
+    fn default() -> Self {
+        WhitespaceMode::All
+    }
+}
+
+impl WhitespaceMode {
+    /// Parses the value of the `draw-whitespace-mode` GSettings key
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "selection" => WhitespaceMode::Selection,
+            "trailing" => WhitespaceMode::Trailing,
+            _ => WhitespaceMode::All,
+        }
+    }
+}
+
+/// A Struct containing setting switches for the EditView
+pub struct Settings {
+    pub gschema: GSchema,
+    pub trailing_spaces: bool,
+    pub draw_spaces: bool,
+    pub draw_tabs: bool,
+    pub draw_newlines: bool,
+    pub whitespace_mode: WhitespaceMode,
+    pub highlight_line: bool,
+    /// Whether the other occurrences of the word at the cursor are highlighted
+    pub highlight_occurrences: bool,
+    /// Whether chips showing the color of color literals are drawn under them
+    pub show_color_chips: bool,
+    pub right_margin: bool,
+    pub column_right_margin: u32,
+    pub highlight_long_lines: bool,
+    /// Overrides `column_right_margin` for some languages
+    pub long_line_columns: HashMap<String, u32>,
+    /// Whether Enter continues lists, see `continues_lists`
+    pub continue_lists: bool,
+    pub continue_lists_languages: Vec<String>,
+    /// Languages in which typing a path offers completions of it
+    pub path_completion_languages: Vec<String>,
+    /// Whether straight quotes and dashes are replaced by typographic ones in prose
+    pub smart_punctuation: bool,
+    /// After how many milliseconds without typing a new undo group is started, 0 to only group
+    /// by words
+    pub undo_group_timeout: u32,
+    /// How much of the view (in percent) Page Up and Page Down scroll by
+    pub page_scroll_percent: u32,
+    /// Whether paging scrolls the view along with the cursor, so it stays in the same place on
+    /// screen
+    pub page_keeps_caret: bool,
+    /// How many lines are kept visible above and below the cursor when scrolling to it
+    pub scroll_offset: u32,
+    /// Words which are expanded when typing a space or pressing Enter after them
+    pub abbreviations: Abbreviations,
+    /// Whether keys are handled like in Vim's normal, insert and visual modes
+    pub vim_mode: bool,
+    /// How much memory (in MiB) the line cache of a document may take up, 0 for no limit
+    pub line_cache_budget: u32,
+    /// Overrides the caret color of the theme
+    pub caret_color: Option<Color>,
+    /// Overrides the selection color of the theme
+    pub selection_color: Option<Color>,
+    pub interface_font: String,
+    pub edit_font: String,
+    pub tab_size: u32,
+}
+
+impl Settings {
+    /// Reads the settings from `gschema`, which has to have the keys of gxi's schema
+    /// (`com.github.Cogitri.gxi`). The interface font is GTK's.
+    pub fn from_gschema(gschema: GSchema) -> Self {
+        let interface_font = {
+            use gtk::SettingsExt;
+            let gtk_settings = gtk::Settings::get_default().unwrap();
+            gtk_settings
+                .get_property_gtk_font_name()
+                .unwrap()
+                .to_string()
+        };
+
+        let whitespace_mode: String = gschema.get_key("draw-whitespace-mode");
+        let caret_color: String = gschema.get_key("caret-color");
+        let selection_color: String = gschema.get_key("selection-color");
+
+        Self {
+            trailing_spaces: gschema.get_key("draw-trailing-spaces"),
+            draw_spaces: gschema.get_key("draw-spaces"),
+            draw_tabs: gschema.get_key("draw-tabs"),
+            draw_newlines: gschema.get_key("draw-newlines"),
+            whitespace_mode: WhitespaceMode::from_name(&whitespace_mode),
+            highlight_line: gschema.get_key("highlight-line"),
+            highlight_occurrences: gschema.get_key("highlight-occurrences"),
+            show_color_chips: gschema.get_key("show-color-chips"),
+            right_margin: gschema.get_key("draw-right-margin"),
+            column_right_margin: gschema.get_key("column-right-margin"),
+            highlight_long_lines: gschema.get_key("highlight-long-lines"),
+            long_line_columns: long_line_columns(&gschema),
+            continue_lists: gschema.get_key("continue-lists"),
+            continue_lists_languages: languages(&gschema, "continue-lists-languages"),
+            path_completion_languages: languages(&gschema, "path-completion-languages"),
+            caret_color: color_from_hex(&caret_color),
+            selection_color: color_from_hex(&selection_color),
+            smart_punctuation: gschema.get_key("smart-punctuation"),
+            undo_group_timeout: gschema.get_key("undo-group-timeout"),
+            page_scroll_percent: gschema.get_key("page-scroll-percent"),
+            page_keeps_caret: gschema.get_key("page-keeps-caret"),
+            scroll_offset: gschema.get_key("scroll-offset"),
+            abbreviations: abbreviations(&gschema),
+            vim_mode: gschema.get_key("vim-mode"),
+            line_cache_budget: gschema.get_key("line-cache-budget"),
+            edit_font: gschema.get_key("font"),
+            tab_size: gschema.get_key("tab-size"),
+            interface_font,
+            gschema,
+        }
+    }
+
+    /// The column of the right margin for documents of the language `language`
+    pub fn margin_column(&self, language: &str) -> u32 {
+        self.long_line_columns
+            .get(language)
+            .cloned()
+            .unwrap_or(self.column_right_margin)
+    }
+
+    /// Whether lists should be continued when pressing Enter in documents of the language
+    /// `language`. This is only useful for prose, lists in code are usually something else.
+    pub fn continues_lists(&self, language: &str) -> bool {
+        self.continue_lists && self.continue_lists_languages.iter().any(|l| l == language)
+    }
+}
+
+pub struct MainState {
+    pub themes: Vec<String>,
+    pub theme_name: String,
+    pub theme: ThemeSettings,
+    pub styles: HashMap<usize, LineStyle>,
+    pub fonts: Vec<String>,
+    pub avail_languages: Vec<String>,
+    pub selected_language: String,
+    pub settings: Settings,
+    /// Factor the edit font of all EditViews of the window is scaled with, 1.0 is 100%
+    pub zoom: f64,
+    /// Whether background work is reduced because we're on a metered connection or low on battery
+    pub reduced_mode: bool,
+    /// In private mode we don't remember anything about what the user did in the window, e.g. what
+    /// they searched for
+    pub private: bool,
+    /// Whether GTK uses a high contrast theme
+    pub high_contrast: bool,
+    /// The quick marks of all EditViews of the window
+    pub quick_marks: QuickMarks,
+    /// The words of the documents of the window, as of when their EditView last lost the focus
+    pub buffer_words: BufferWords,
+    /// Spell checks the documents of the window
+    pub spell_checker: SpellChecker,
+}
+
+/// Reads a list of languages, like the 'continue-lists-languages' key
+pub fn languages(gschema: &GSchema, key: &str) -> Vec<String> {
+    gschema
+        .settings
+        .get_strv(key)
+        .iter()
+        .map(|language| language.to_string())
+        .collect()
+}
+
+/// Parses the `language=column` entries of the 'long-line-columns' key
+pub fn long_line_columns(gschema: &GSchema) -> HashMap<String, u32> {
+    gschema
+        .settings
+        .get_strv("long-line-columns")
+        .iter()
+        .filter_map(|entry| {
+            let mut split = entry.rsplitn(2, '=');
+            let column = split.next()?.trim().parse().ok()?;
+            let language = split.next()?.trim().to_string();
+            Some((language, column))
+        })
+        .collect()
+}
+
+/// Reads the 'abbreviations' key
+pub fn abbreviations(gschema: &GSchema) -> Abbreviations {
+    let entries = gschema.settings.get_strv("abbreviations");
+    Abbreviations::parse(entries.iter().map(|entry| entry.as_str()))
+}
+
+impl MainState {
+    /// The state of a window without a theme yet, which xi-editor sends in its `theme_changed`
+    /// notification
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            themes: Default::default(),
+            theme_name: Default::default(),
+            theme: Default::default(),
+            styles: Default::default(),
+            fonts: Default::default(),
+            avail_languages: Default::default(),
+            selected_language: Default::default(),
+            settings,
+            zoom: 1.0,
+            reduced_mode: false,
+            private: false,
+            high_contrast: false,
+            quick_marks: Default::default(),
+            buffer_words: Default::default(),
+            spell_checker: Default::default(),
+        }
+    }
+
+    /// Handles xi-editor's `theme_changed` notification
+    pub fn theme_changed(&mut self, params: &Value) {
+        let theme: ThemeSettings = match serde_json::from_value(params["theme"].clone()) {
+            Err(e) => {
+                error!("{}: {}", gettext("Failed to convert theme settings"), e);
+                return;
+            }
+            Ok(ts) => ts,
+        };
+        self.theme = theme;
+        self.update_builtin_styles();
+    }
+
+    /// Handles xi-editor's `def_style` notification
+    pub fn def_style(&mut self, params: &Value) {
+        let style: LineStyle = match serde_json::from_value(params.clone()) {
+            Err(e) => {
+                error!("{}: {}", gettext("Failed to convert style"), e);
+                return;
+            }
+            Ok(style) => style,
+        };
+        if let Some(id) = params["id"].as_u64() {
+            self.styles.insert(id as usize, style);
+        }
+    }
+
+    /// Updates the styles of selections and search matches, e.g. after the theme or their color
+    /// has been changed
+    // FIXME: Use annotations instead of constructing the selection style here
+    pub fn update_builtin_styles(&mut self) {
+        let selection_style = self.selection_style();
+        self.styles.insert(0, selection_style);
+        let find_highlight_style = self.find_highlight_style();
+        self.styles.insert(1, find_highlight_style);
+    }
+
+    /// The color of the caret. The user may override the caret color of the theme, in high
+    /// contrast mode the caret has the color of the text so it's easy to spot.
+    pub fn caret_color(&self) -> Option<Color> {
+        self.settings.caret_color.or(if self.high_contrast {
+            self.theme.foreground
+        } else {
+            self.theme.caret
+        })
+    }
+
+    /// The style of selected text. Selections are inverted in high contrast mode.
+    pub fn selection_style(&self) -> LineStyle {
+        let (fg_color, bg_color) = match self.settings.selection_color {
+            // Keep the colors of the syntax highlighting
+            Some(color) => (None, Some(color)),
+            None if self.high_contrast => (self.theme.background, self.theme.foreground),
+            None => (self.theme.selection_foreground, self.theme.selection),
+        };
+        LineStyle {
+            fg_color: fg_color.map(u32_from_color),
+            bg_color: bg_color.map(u32_from_color),
+            weight: None,
+            italic: None,
+            underline: None,
+        }
+    }
+
+    /// The style of the matches of the current search, which xi-editor gives the style id 1. In
+    /// high contrast mode they're underlined as well.
+    pub fn find_highlight_style(&self) -> LineStyle {
+        let (fg_color, bg_color) = if self.high_contrast {
+            (self.theme.background, self.theme.foreground)
+        } else {
+            (
+                self.theme.find_highlight_foreground,
+                self.theme.find_highlight.or(self.theme.selection),
+            )
+        };
+        LineStyle {
+            fg_color: fg_color.map(u32_from_color),
+            bg_color: bg_color.map(u32_from_color),
+            weight: None,
+            italic: None,
+            underline: if self.high_contrast { Some(true) } else { None },
+        }
+    }
+}
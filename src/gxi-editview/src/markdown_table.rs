@@ -0,0 +1,232 @@
+//! Helpers for editing Markdown (GFM) tables, e.g.
+//!
+//! ```text
+//! | Name | Value |
+//! | :--- | ----: |
+//! | foo  |    42 |
+//! ```
+//!
+//! xi-editor doesn't know about tables, so the EditView reflows them itself and sends the result
+//! to xi-editor as a regular edit.
+
+use std::cmp::{max, min};
+
+/// Separator cells have at least this many dashes, shorter ones aren't recognized by all renderers
+const MIN_WIDTH: usize = 3;
+
+/// Whether `line` is a row of a table
+pub fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// Returns the byte indices of all pipes in `line` which separate cells, i.e. which aren't escaped
+fn pipes(line: &str) -> Vec<usize> {
+    let mut pipes = Vec::new();
+    let mut escaped = false;
+    for (ix, c) in line.char_indices() {
+        if c == '|' && !escaped {
+            pipes.push(ix);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    pipes
+}
+
+/// Splits a row into its (trimmed) cells
+fn cells(line: &str) -> Vec<&str> {
+    let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+    let mut bounds = pipes(line);
+    // The trailing pipe is optional
+    if bounds.len() == 1 || !line.trim_end().ends_with('|') {
+        bounds.push(line.len());
+    }
+    bounds
+        .windows(2)
+        .map(|w| line[w[0] + 1..w[1]].trim())
+        .collect()
+}
+
+/// Whether `line` is the row separating the header from the body
+pub fn is_separator_row(line: &str) -> bool {
+    let cells = cells(line);
+    !cells.is_empty() && cells.iter().all(|c| is_separator(c))
+}
+
+/// Whether `cell` is part of the row separating the header from the body, like `:---:`
+fn is_separator(cell: &str) -> bool {
+    let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+    !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+}
+
+fn separator(cell: &str, width: usize) -> String {
+    let left = cell.starts_with(':');
+    let right = cell.len() > 1 && cell.ends_with(':');
+    let dashes = width - left as usize - right as usize;
+    format!(
+        "{}{}{}",
+        if left { ":" } else { "" },
+        "-".repeat(dashes),
+        if right { ":" } else { "" }
+    )
+}
+
+/// Aligns the pipes of the table consisting of `rows`. Rows with less cells than others are
+/// padded with empty cells. Returns the new rows, without newlines.
+pub fn reflow(rows: &[&str]) -> Vec<String> {
+    let indent: String = rows
+        .first()
+        .map(|r| r.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+    let is_sep: Vec<bool> = rows.iter().map(|r| is_separator_row(r)).collect();
+    let rows: Vec<Vec<&str>> = rows.iter().map(|r| cells(r)).collect();
+
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut widths = vec![MIN_WIDTH; columns];
+    for (row, _) in rows.iter().zip(&is_sep).filter(|(_, sep)| !**sep) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = max(*width, cell.chars().count());
+        }
+    }
+
+    rows.iter()
+        .zip(is_sep)
+        .map(|(row, sep)| {
+            let mut line = indent.clone();
+            line.push('|');
+            for (i, width) in widths.iter().enumerate() {
+                let cell = row.get(i).cloned().unwrap_or("");
+                let cell = if sep {
+                    separator(cell, *width)
+                } else {
+                    let padding = width - cell.chars().count();
+                    format!("{}{}", cell, " ".repeat(padding))
+                };
+                line.push_str(&format!(" {} |", cell));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Returns the index of the cell the byte index `col` of `line` is in
+pub fn cell_at(line: &str, col: usize) -> usize {
+    pipes(line)
+        .iter()
+        .filter(|p| **p < col)
+        .count()
+        .saturating_sub(1)
+}
+
+/// Returns the byte index where the content of the cell `cell` of `line` starts, if it has that
+/// many cells
+pub fn cell_start(line: &str, cell: usize) -> Option<usize> {
+    let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+    let pipes = pipes(line);
+    // The last pipe closes the last cell
+    if cell + 1 >= pipes.len() {
+        return None;
+    }
+    let (start, end) = (pipes[cell] + 1, pipes[cell + 1]);
+    let content = line[start..end].trim_start();
+    if content.is_empty() {
+        // Keep a space between the pipe and the cursor
+        Some(min(start + 1, end))
+    } else {
+        Some(end - content.len())
+    }
+}
+
+/// Returns the row and cell after (or before, if `backwards` is set) the cell `cell` of the row
+/// `row` of the (reflowed) table `rows`, skipping the separator row. Returns None if there is no
+/// such cell, i.e. if `cell` is the last (or first) cell of the table.
+pub fn next_cell(
+    rows: &[String],
+    row: usize,
+    cell: usize,
+    backwards: bool,
+) -> Option<(usize, usize)> {
+    let columns = cells(rows.get(row)?).len();
+    let (mut row, mut cell) = (row, cell);
+    loop {
+        if backwards {
+            if cell > 0 {
+                cell -= 1;
+            } else {
+                row = row.checked_sub(1)?;
+                cell = columns.saturating_sub(1);
+            }
+        } else if cell + 1 < columns {
+            cell += 1;
+        } else {
+            row += 1;
+            cell = 0;
+        }
+        if !is_separator_row(rows.get(row)?) {
+            return Some((row, cell));
+        }
+    }
+}
+
+/// An empty row with the given amount of cells
+pub fn empty_row(columns: usize) -> String {
+    "|".repeat(columns + 1)
+}
+
+/// Creates an empty table with a header row and `rows` further rows
+pub fn new_table(rows: usize, columns: usize) -> String {
+    let empty_row = empty_row(columns);
+    let separator_row = format!("|{}", "---|".repeat(columns));
+    let mut table = vec![empty_row.as_str(), separator_row.as_str()];
+    for _ in 0..rows {
+        table.push(&empty_row);
+    }
+    let mut text = reflow(&table).join("\n");
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reflow_table() {
+        let table = [
+            "| Name | Value |\n",
+            "|:-|-:|\n",
+            "| a \\| b | 42\n",
+            "|x|\n",
+        ];
+        assert_eq!(
+            reflow(&table),
+            vec![
+                "| Name   | Value |",
+                "| :----- | ----: |",
+                "| a \\| b | 42    |",
+                "| x      |       |",
+            ]
+        );
+        assert_eq!(
+            new_table(1, 2),
+            "|     |     |\n| --- | --- |\n|     |     |\n"
+        );
+    }
+
+    #[test]
+    fn cells_of_line() {
+        let line = "| foo | bar |\n";
+        assert_eq!(cell_at(line, 0), 0);
+        assert_eq!(cell_at(line, 3), 0);
+        assert_eq!(cell_at(line, 8), 1);
+        assert_eq!(cell_start(line, 0), Some(2));
+        assert_eq!(cell_start(line, 1), Some(8));
+        assert_eq!(cell_start(line, 2), None);
+
+        let table = reflow(&["| a | b |", "|---|---|", "| c | d |"]);
+        assert_eq!(next_cell(&table, 0, 0, false), Some((0, 1)));
+        assert_eq!(next_cell(&table, 0, 1, false), Some((2, 0)));
+        assert_eq!(next_cell(&table, 2, 1, false), None);
+        assert_eq!(next_cell(&table, 2, 0, true), Some((0, 1)));
+        assert_eq!(next_cell(&table, 0, 0, true), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Keeps track of which of the matches of the current search is selected, for the "3 of 12"
+//! label of the find/replace dialog, and notices when going to the next match wraps around. What
+//! the label shows is announced to screen readers as well.
+
+use gettextrs::gettext;
+use std::ops::Range;
+
+#[derive(Debug, Default)]
+pub struct MatchCounter {
+    total: u64,
+    /// The line of each match, as xi-editor sends them in `find_status`
+    lines: Vec<u64>,
+    /// Index of the selected match
+    current: Option<usize>,
+    /// Whether we're going to the next (`true`) or previous match
+    direction: Option<bool>,
+    wrapped: bool,
+    /// Whether going to the next match has wrapped around to the first one (as opposed to going
+    /// to the previous match wrapping around to the last one)
+    wrapped_to_top: bool,
+}
+
+impl MatchCounter {
+    pub fn set_matches(&mut self, total: u64, lines: Vec<u64>) {
+        self.total = total;
+        self.lines = lines;
+    }
+
+    /// Called before going to the next (or previous, if `forward` is false) match
+    pub fn navigating(&mut self, forward: bool) {
+        self.direction = Some(forward);
+        self.wrapped = false;
+    }
+
+    /// Finds out which match is selected. `matches` are the byte ranges of the matches on the
+    /// `line` the cursor is on, `selection` is the selection on it.
+    pub fn update(&mut self, line: u64, matches: &[Range<usize>], selection: Option<Range<usize>>) {
+        let before = self.lines.iter().filter(|l| **l < line).count();
+        let current = selection
+            .and_then(|selection| matches.iter().position(|m| *m == selection))
+            .map(|ix| before + ix);
+        if current != self.current {
+            if let (Some(forward), Some(previous), Some(current)) =
+                (self.direction.take(), self.current, current)
+            {
+                self.wrapped = if forward {
+                    current < previous
+                } else {
+                    current > previous
+                };
+                self.wrapped_to_top = forward;
+            }
+            self.current = current;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Text of the label showing the number of matches
+    pub fn label(&self) -> String {
+        if self.total == 0 {
+            return gettext("No results");
+        }
+        let count = match self.current {
+            Some(current) => format!("{} {} {}", current + 1, gettext("of"), self.total),
+            None => format!("{} {}", self.total, gettext("Results")),
+        };
+        if self.wrapped {
+            format!("{} ({})", count, gettext("wrapped around"))
+        } else {
+            count
+        }
+    }
+
+    /// What screen readers announce, the same as `label` but in words, e.g. "Match 3 of 12" or
+    /// "Wrapped to top: Match 1 of 12"
+    pub fn announcement(&self) -> String {
+        if self.total == 0 {
+            return gettext("No matches");
+        }
+        let count = match self.current {
+            Some(current) => format!(
+                "{} {} {} {}",
+                gettext("Match"),
+                current + 1,
+                gettext("of"),
+                self.total
+            ),
+            None if self.total == 1 => gettext("1 match"),
+            None => format!("{} {}", self.total, gettext("matches")),
+        };
+        if !self.wrapped {
+            return count;
+        }
+        let wrapped = if self.wrapped_to_top {
+            gettext("Wrapped to top")
+        } else {
+            gettext("Wrapped to bottom")
+        };
+        format!("{}: {}", wrapped, count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count() {
+        let mut counter = MatchCounter::default();
+        counter.set_matches(3, vec![1, 4, 4]);
+        counter.update(4, &[2..5, 8..11], None);
+        assert_eq!(counter.current, None);
+
+        counter.navigating(true);
+        counter.update(4, &[2..5, 8..11], Some(8..11));
+        assert_eq!(counter.current, Some(2));
+        assert!(!counter.wrapped);
+
+        counter.navigating(true);
+        // xi-editor may send an update before the selection has moved
+        counter.update(4, &[2..5, 8..11], Some(8..11));
+        counter.update(1, &[0..3], Some(0..3));
+        assert_eq!(counter.current, Some(0));
+        assert!(counter.wrapped);
+
+        counter.navigating(false);
+        counter.update(4, &[2..5, 8..11], Some(8..11));
+        assert!(counter.wrapped);
+        counter.navigating(false);
+        counter.update(4, &[2..5, 8..11], Some(2..5));
+        assert!(!counter.wrapped);
+    }
+
+    #[test]
+    fn announcement() {
+        let mut counter = MatchCounter::default();
+        assert_eq!(counter.announcement(), "No matches");
+        counter.set_matches(1, vec![4]);
+        assert_eq!(counter.announcement(), "1 match");
+        counter.set_matches(3, vec![1, 4, 4]);
+        assert_eq!(counter.announcement(), "3 matches");
+
+        counter.navigating(true);
+        counter.update(4, &[2..5, 8..11], Some(8..11));
+        assert_eq!(counter.announcement(), "Match 3 of 3");
+        counter.navigating(true);
+        counter.update(1, &[0..3], Some(0..3));
+        assert_eq!(counter.announcement(), "Wrapped to top: Match 1 of 3");
+        counter.navigating(false);
+        counter.update(4, &[2..5, 8..11], Some(8..11));
+        assert_eq!(counter.announcement(), "Wrapped to bottom: Match 3 of 3");
+    }
+}
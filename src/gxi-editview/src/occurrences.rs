@@ -0,0 +1,117 @@
+//! Highlights the other occurrences of the word at the cursor once the cursor has rested on it
+//! for a moment. This only looks at the lines we draw anyway, so it doesn't interfere with the
+//! search of xi-editor.
+
+use glib::{source, SourceId};
+use gtk::WidgetExt;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// How long (in milliseconds) the cursor has to rest on a word before its occurrences are
+/// highlighted
+const IDLE_DELAY: u32 = 500;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the word at (or right before) byte `col` of `text`
+pub fn word_at(text: &str, col: usize) -> Option<&str> {
+    if col > text.len() || !text.is_char_boundary(col) {
+        return None;
+    }
+    let start = text[..col]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(col, |(ix, _)| ix);
+    let end = text[col..]
+        .char_indices()
+        .find(|(_, c)| !is_word_char(*c))
+        .map_or(text.len(), |(ix, _)| col + ix);
+    Some(&text[start..end]).filter(|word| !word.is_empty())
+}
+
+/// Returns the byte ranges of all occurrences of `word` in `text` which aren't part of a longer
+/// word
+pub fn word_ranges(text: &str, word: &str) -> Vec<(usize, usize)> {
+    text.match_indices(word)
+        .map(|(start, _)| (start, start + word.len()))
+        .filter(|(start, end)| {
+            !text[..*start]
+                .chars()
+                .next_back()
+                .map_or(false, is_word_char)
+                && !text[*end..].chars().next().map_or(false, is_word_char)
+        })
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Occurrences {
+    /// The word whose occurrences are highlighted
+    word: Rc<RefCell<Option<String>>>,
+    /// The timeout which starts highlighting once the cursor has rested long enough
+    timeout: Rc<RefCell<Option<SourceId>>>,
+}
+
+impl Occurrences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn word(&self) -> Option<String> {
+        self.word.borrow().clone()
+    }
+
+    /// Removes the highlights and stops waiting for the cursor to rest, e.g. because the
+    /// document has been edited
+    pub fn clear(&self) {
+        self.word.replace(None);
+        if let Some(id) = self.timeout.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+    }
+
+    /// Highlights the occurrences of `word` in `widget` unless `clear` is called within the
+    /// next `IDLE_DELAY` milliseconds
+    pub fn highlight_later<W: WidgetExt + Clone + 'static>(&self, word: String, widget: &W) {
+        self.clear();
+        let word_cell = self.word.clone();
+        let timeout = self.timeout.clone();
+        let widget = widget.clone();
+        let id = glib::timeout_add_local(IDLE_DELAY, move || {
+            timeout.replace(None);
+            word_cell.replace(Some(word.clone()));
+            widget.queue_draw();
+            source::Continue(false)
+        });
+        self.timeout.replace(Some(id));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words() {
+        assert_eq!(word_at("let foo_bar = 1;", 6), Some("foo_bar"));
+        assert_eq!(word_at("let foo_bar = 1;", 4), Some("foo_bar"));
+        // Right after the word
+        assert_eq!(word_at("let foo_bar = 1;", 11), Some("foo_bar"));
+        assert_eq!(word_at("a = b", 2), None);
+        assert_eq!(word_at("größe", 3), None);
+        assert_eq!(word_at("größe", 4), Some("größe"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            word_ranges("foo(foo_bar, foo) + foo", "foo"),
+            vec![(0, 3), (13, 16), (20, 23)]
+        );
+        assert_eq!(word_ranges("barfoo", "foo"), vec![]);
+    }
+}
@@ -0,0 +1,172 @@
+use gettextrs::gettext;
+use gio::FileExt;
+use gtk::*;
+use gxi_peer::Core;
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
+
+/// Returned by the dialogs we show if the clipboard contains files or an image instead of text
+#[derive(Debug, PartialEq)]
+enum PasteAction {
+    Insert = 300,
+    Cancel = 301,
+}
+
+/// Handles pasting if the clipboard doesn't contain any text. If it contains files we offer to
+/// insert their paths. If it contains an image and the document's `language` has Markdown links,
+/// we offer to save it next to the document and to insert a link to it. Otherwise we'd silently
+/// insert nothing.
+pub(crate) fn paste_non_text(
+    clipboard: &Clipboard,
+    core: Core,
+    view_id: String,
+    file_name: Option<String>,
+    language: &str,
+    parent: Option<Window>,
+) {
+    if clipboard.wait_is_uris_available() {
+        clipboard.request_uris(move |_, uris| {
+            let paths: Vec<String> = uris
+                .iter()
+                .map(|uri| {
+                    gio::File::new_for_uri(uri)
+                        .get_path()
+                        .map(|p| p.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| uri.to_string())
+                })
+                .collect();
+            let msg = gettext("The clipboard contains files instead of text. Insert their paths?");
+            if ask(parent.as_ref(), &msg, &gettext("Insert Paths")) {
+                core.insert(&view_id, &paths.join("\n"));
+            }
+        });
+    } else if has_markdown_links(language) && clipboard.wait_is_image_available() {
+        let file_name = match file_name {
+            Some(file_name) => file_name,
+            None => {
+                let msg = gettext("The clipboard contains an image. Save the document first to paste images next to it.");
+                inform(parent.as_ref(), &msg);
+                return;
+            }
+        };
+        clipboard.request_image(move |_, image| {
+            let msg = gettext("The clipboard contains an image instead of text. Save it next to the document and insert a link to it?");
+            if !ask(parent.as_ref(), &msg, &gettext("Save Image")) {
+                return;
+            }
+
+            let path = image_path(Path::new(&file_name));
+            debug!("{} {:?}", gettext("Saving pasted image to"), path);
+            match image.savev(&path, "png", &[]) {
+                Ok(()) => {
+                    let name = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    core.insert(&view_id, &format!("![]({})", percent_encode(&name)));
+                }
+                Err(e) => {
+                    warn!("{} {:?}: {}", gettext("Failed to save image to"), path, e);
+                    inform(
+                        parent.as_ref(),
+                        &format!("{}: {}", gettext("Couldn't save image"), e),
+                    );
+                }
+            }
+        });
+    } else {
+        debug!(
+            "{}",
+            gettext("Clipboard doesn't contain anything we can paste")
+        );
+    }
+}
+
+/// Whether documents of `language` are prose in which we can link to images with Markdown, e.g.
+/// notes in plain text. Code never wants that.
+fn has_markdown_links(language: &str) -> bool {
+    match language {
+        "Markdown" | "MultiMarkdown" | "Plain Text" => true,
+        _ => false,
+    }
+}
+
+/// Percent-encodes everything but unreserved characters of `name`, so it can be used as the URL
+/// of a Markdown link even if it contains spaces or parentheses
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Finds a free name for an image pasted into `document`, e.g. `notes-image1.png` for `notes.md`
+fn image_path(document: &Path) -> PathBuf {
+    let stem = document
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (1..)
+        .map(|n| document.with_file_name(format!("{}-image{}.png", stem, n)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Asks the user if they want to do `action`, returns true if they do
+fn ask(parent: Option<&Window>, msg: &str, action: &str) -> bool {
+    let dialog = MessageDialog::new(
+        parent,
+        DialogFlags::all(),
+        MessageType::Question,
+        ButtonsType::None,
+        msg,
+    );
+    dialog.add_button(
+        &gettext("Cancel"),
+        ResponseType::Other(PasteAction::Cancel as u16),
+    );
+    dialog.add_button(action, ResponseType::Other(PasteAction::Insert as u16));
+    dialog.set_default_response(ResponseType::Other(PasteAction::Insert as u16));
+    let ret = dialog.run();
+    dialog.destroy();
+
+    ret == ResponseType::Other(PasteAction::Insert as u16)
+}
+
+fn inform(parent: Option<&Window>, msg: &str) {
+    let dialog = MessageDialog::new(
+        parent,
+        DialogFlags::all(),
+        MessageType::Info,
+        ButtonsType::Ok,
+        msg,
+    );
+    dialog.run();
+    dialog.destroy();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encodes_file_names() {
+        assert_eq!(percent_encode("notes-image1.png"), "notes-image1.png");
+        assert_eq!(
+            percent_encode("my notes (draft)-image1.png"),
+            "my%20notes%20%28draft%29-image1.png"
+        );
+        assert_eq!(percent_encode("über-image1.png"), "%C3%BCber-image1.png");
+    }
+
+    #[test]
+    fn images_in_prose_only() {
+        assert!(has_markdown_links("Markdown"));
+        assert!(has_markdown_links("Plain Text"));
+        assert!(!has_markdown_links("Rust"));
+    }
+}
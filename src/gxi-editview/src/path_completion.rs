@@ -0,0 +1,131 @@
+//! Completes paths like `./src/` or `/etc/` with the entries of the directory, which helps with
+//! editing scripts and config files.
+
+use crate::completion::Completion;
+use std::path::{Path, PathBuf};
+
+/// Chars which can't be part of a path we complete, e.g. the quotes around it
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "\"'`=(),;<>|&$".contains(c)
+}
+
+/// Returns the path in front of byte `col` of `text`, if there is something which looks like one
+pub fn path_before(text: &str, col: usize) -> Option<&str> {
+    let before = text.get(..col)?;
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| !is_delimiter(*c))
+        .last()
+        .map_or(col, |(ix, _)| ix);
+    let token = &before[start..];
+    let looks_like_path = ["/", "./", "../", "~/"]
+        .iter()
+        .any(|prefix| token.starts_with(prefix));
+    if looks_like_path {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Returns the directory `path` is in and the part of the file name typed so far. Relative
+/// paths are relative to `base_dir`.
+fn split_path(path: &str, base_dir: &Path) -> Option<(PathBuf, String)> {
+    let ix = path.rfind('/')?;
+    let (dir, name) = (&path[..=ix], &path[ix + 1..]);
+    let dir = if dir.starts_with("~/") {
+        glib::get_home_dir()?.join(&dir[2..])
+    } else {
+        base_dir.join(dir)
+    };
+    Some((dir, name.to_string()))
+}
+
+/// Returns the completions of `path` with the entries of its directory, which `read_dir` lists
+/// as name and whether the entry is a directory. Hidden files are only offered once a '.' has
+/// been typed.
+pub fn complete<F>(path: &str, base_dir: &Path, read_dir: F) -> Vec<Completion>
+where
+    F: Fn(&Path) -> Vec<(String, bool)>,
+{
+    let (dir, typed) = match split_path(path, base_dir) {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let mut entries: Vec<(String, bool)> = read_dir(&dir)
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(&typed) && name != &typed)
+        .filter(|(name, _)| typed.starts_with('.') || !name.starts_with('.'))
+        .collect();
+    // Directories first, since they're the ones the user has to go through
+    entries.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+    entries
+        .into_iter()
+        .map(|(name, is_dir)| {
+            let label = if is_dir { format!("{}/", name) } else { name };
+            Completion {
+                insert: label[typed.len()..].to_string(),
+                label,
+            }
+        })
+        .collect()
+}
+
+/// Lists the entries of `dir` for `complete`
+pub fn read_dir(dir: &Path) -> Vec<(String, bool)> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| {
+                    let is_dir = entry.path().is_dir();
+                    (entry.file_name().to_string_lossy().into_owned(), is_dir)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths() {
+        assert_eq!(path_before("cd ./src/ma", 11), Some("./src/ma"));
+        assert_eq!(path_before("x=\"/etc/", 8), Some("/etc/"));
+        assert_eq!(path_before("a/b", 3), None);
+        assert_eq!(path_before("1 / 2", 3), Some("/"));
+        assert_eq!(path_before("src/", 4), None);
+    }
+
+    #[test]
+    fn completions() {
+        let read_dir = |dir: &Path| {
+            assert_eq!(dir, Path::new("/project/./src/"));
+            vec![
+                ("main.rs".to_string(), false),
+                ("macros".to_string(), true),
+                (".hidden".to_string(), false),
+                ("lib.rs".to_string(), false),
+            ]
+        };
+        let base = Path::new("/project");
+        assert_eq!(
+            complete("./src/ma", base, read_dir),
+            vec![
+                Completion {
+                    label: "macros/".to_string(),
+                    insert: "cros/".to_string()
+                },
+                Completion {
+                    label: "main.rs".to_string(),
+                    insert: "in.rs".to_string()
+                },
+            ]
+        );
+        assert_eq!(complete("./src/", base, read_dir).len(), 3);
+        assert_eq!(complete("./src/.", base, read_dir).len(), 1);
+    }
+}
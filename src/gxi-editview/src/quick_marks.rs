@@ -0,0 +1,71 @@
+//! Quick marks are positions the user can jump back to, set with `m` + letter in Vim mode or
+//! Ctrl+Shift+digit. Unlike bookmarks they aren't saved and may point into any of the open
+//! documents.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuickMark {
+    pub view_id: String,
+    pub line: u64,
+    /// Byte column
+    pub col: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct QuickMarks {
+    marks: HashMap<char, QuickMark>,
+}
+
+/// Marks are named by a lowercase letter (Vim) or a digit from 1 to 9 (Ctrl+Shift+digit)
+pub fn is_valid_name(name: char) -> bool {
+    name.is_ascii_lowercase() || ('1'..='9').contains(&name)
+}
+
+impl QuickMarks {
+    /// Sets the mark `name`, replacing the mark of that name if there is one
+    pub fn set(&mut self, name: char, mark: QuickMark) {
+        if is_valid_name(name) {
+            self.marks.insert(name, mark);
+        }
+    }
+
+    pub fn get(&self, name: char) -> Option<&QuickMark> {
+        self.marks.get(&name)
+    }
+
+    /// Drops the marks of a view which has been closed
+    pub fn remove_view(&mut self, view_id: &str) {
+        self.marks.retain(|_, mark| mark.view_id != view_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mark(view_id: &str, line: u64) -> QuickMark {
+        QuickMark {
+            view_id: view_id.to_string(),
+            line,
+            col: 0,
+        }
+    }
+
+    #[test]
+    fn marks() {
+        let mut marks = QuickMarks::default();
+        marks.set('a', mark("view-id-1", 3));
+        marks.set('1', mark("view-id-2", 5));
+        marks.set('0', mark("view-id-2", 7));
+        marks.set('A', mark("view-id-2", 7));
+        assert_eq!(marks.get('a'), Some(&mark("view-id-1", 3)));
+        assert_eq!(marks.get('0'), None);
+        assert_eq!(marks.get('A'), None);
+
+        marks.set('a', mark("view-id-2", 4));
+        marks.remove_view("view-id-2");
+        assert_eq!(marks.get('a'), None);
+        assert_eq!(marks.get('1'), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! Replacing regex matches with strings which refer to capture groups, like `$1` or `\1`.
+//! xi-editor inserts replacement strings as they are, so we expand the references ourselves.
+
+use regex::{Regex, RegexBuilder};
+
+/// Builds the regex xi-editor searches for with these options
+pub fn build_regex(
+    needle: &str,
+    case_sensitive: bool,
+    whole_words: bool,
+) -> Result<Regex, regex::Error> {
+    let pattern = if whole_words {
+        format!(r"\b(?:{})\b", needle)
+    } else {
+        needle.to_string()
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .multi_line(true)
+        .build()
+}
+
+/// Whether `replacement` refers to capture groups, e.g. `$1`, `${name}` or `\1`
+pub fn has_group_refs(replacement: &str) -> bool {
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek().cloned()) {
+            ('$', Some(next)) if next.is_alphanumeric() || next == '_' || next == '{' => {
+                return true
+            }
+            ('\\', Some(next)) if next.is_ascii_digit() => return true,
+            // Skip escaped chars, e.g. `$$` or `\\`
+            ('$', Some('$')) | ('\\', Some('\\')) => {
+                chars.next();
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Turns `replacement` into the syntax of the regex crate, i.e. `\1` and `$1` become `${1}`
+/// and `\\` becomes `\`. The regex crate would take `$1a` to refer to a group named `1a`.
+pub fn to_regex_syntax(replacement: &str) -> String {
+    let mut result = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek().cloned()) {
+            ('\\', Some(next)) | ('$', Some(next)) if next.is_ascii_digit() => {
+                result.push_str("${");
+                while let Some(digit) = chars.peek().cloned().filter(char::is_ascii_digit) {
+                    result.push(digit);
+                    chars.next();
+                }
+                result.push('}');
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            ('$', Some('$')) => {
+                result.push_str("$$");
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Returns the replacement for `text` if `re` matches all of it
+pub fn replace_match(re: &Regex, text: &str, replacement: &str) -> Option<String> {
+    let captures = re.captures(text)?;
+    let whole = captures.get(0)?;
+    if whole.start() != 0 || whole.end() != text.len() {
+        return None;
+    }
+    let mut result = String::new();
+    captures.expand(&to_regex_syntax(replacement), &mut result);
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn refs() {
+        assert!(has_group_refs("$1"));
+        assert!(has_group_refs("a ${name} b"));
+        assert!(has_group_refs(r"\2-\1"));
+        assert!(!has_group_refs("costs $$5"));
+        assert!(!has_group_refs(r"a\\1"));
+        assert!(!has_group_refs("plain $"));
+    }
+
+    #[test]
+    fn syntax() {
+        assert_eq!(to_regex_syntax(r"\2-\1"), "${2}-${1}");
+        assert_eq!(to_regex_syntax(r"\12x"), "${12}x");
+        assert_eq!(to_regex_syntax(r"a\\1"), r"a\1");
+        assert_eq!(to_regex_syntax("$1a $$1"), "${1}a $$1");
+    }
+
+    #[test]
+    fn replace() {
+        let re = build_regex(r"(\w+)=(\w+)", true, false).unwrap();
+        assert_eq!(replace_match(&re, "a=b", r"\2=\1"), Some("b=a".to_string()));
+        assert_eq!(replace_match(&re, "a=b;", "$2"), None);
+        assert_eq!(
+            re.replace_all("a=b, c=d", to_regex_syntax("$2=$1").as_str()),
+            "b=a, d=c"
+        );
+
+        let re = build_regex("ab", false, true).unwrap();
+        assert!(re.is_match("x AB y"));
+        assert!(!re.is_match("xab"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Renders documents with their syntax highlighting into RTF, which word processors and mail
+//! clients understand when pasting, if they don't take HTML.
+
+use crate::html_export::{HtmlOptions, StyledLine};
+use crate::theme::color_from_u32;
+use syntect::highlighting::Color;
+
+/// Escapes `text` for RTF. Chars outside of ASCII are written as (signed 16 bit) UTF-16 code
+/// units with `?` as the fallback for readers which don't know Unicode.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\t' => escaped.push_str("\\tab "),
+            c if c.is_ascii() => escaped.push(c),
+            c => {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    escaped.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+/// The index of `color` in the color table (which starts at 1, 0 is the default color), adding
+/// it if it isn't there yet
+fn color_index(colors: &mut Vec<Color>, color: Color) -> usize {
+    let ix = colors.iter().position(|c| *c == color).unwrap_or_else(|| {
+        colors.push(color);
+        colors.len() - 1
+    });
+    ix + 1
+}
+
+/// Renders `line` with a group for each of its styled parts
+fn line_rtf(line: &StyledLine, colors: &mut Vec<Color>) -> String {
+    let mut rtf = String::new();
+    let mut pos = 0;
+    for (range, style) in &line.styles {
+        let start = range.start.max(pos);
+        let (before, styled) = match (line.text.get(pos..start), line.text.get(start..range.end)) {
+            (Some(before), Some(styled)) if !styled.is_empty() => (before, styled),
+            _ => continue,
+        };
+        rtf.push_str(&escape(before));
+        let mut control = String::new();
+        if let Some(fg) = style.fg_color {
+            control.push_str(&format!("\\cf{}", color_index(colors, color_from_u32(fg))));
+        }
+        if style.weight.map_or(false, |weight| weight >= 600) {
+            control.push_str("\\b");
+        }
+        if style.italic == Some(true) {
+            control.push_str("\\i");
+        }
+        if style.underline == Some(true) {
+            control.push_str("\\ul");
+        }
+        if control.is_empty() {
+            rtf.push_str(&escape(styled));
+        } else {
+            rtf.push_str(&format!("{{{} {}}}", control, escape(styled)));
+        }
+        pos = range.end;
+    }
+    rtf.push_str(&escape(line.text.get(pos..).unwrap_or_default()));
+    rtf
+}
+
+/// Renders `lines` into an RTF document. The title and line numbers of the `options` are left
+/// out.
+pub fn to_rtf(lines: &[StyledLine], options: &HtmlOptions) -> String {
+    let mut colors = Vec::new();
+    if let Some(fg) = options.foreground {
+        colors.push(fg);
+    }
+    let body: Vec<String> = lines
+        .iter()
+        .map(|line| line_rtf(line, &mut colors))
+        .collect();
+    let color_table: String = colors
+        .iter()
+        .map(|c| format!("\\red{}\\green{}\\blue{};", c.r, c.g, c.b))
+        .collect();
+    let default_color = if options.foreground.is_some() {
+        "\\cf1"
+    } else {
+        ""
+    };
+    format!(
+        "{{\\rtf1\\ansi\\deff0{{\\fonttbl{{\\f0\\fmodern {};}}}}{{\\colortbl;{}}}\n\\f0\\fs{}{} {}}}",
+        escape(&options.font_family),
+        color_table,
+        (options.font_size * 2.0).round() as u32,
+        default_color,
+        body.join("\\line\n")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::theme::LineStyle;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("{a\\b}"), "\\{a\\\\b\\}");
+        assert_eq!(escape("ä😀"), "\\u228?\\u-10179?\\u-8704?");
+    }
+
+    #[test]
+    fn document() {
+        let style = LineStyle {
+            fg_color: Some(0xffff_0000),
+            bg_color: None,
+            weight: Some(700),
+            italic: None,
+            underline: None,
+        };
+        let lines = vec![
+            StyledLine {
+                text: "fn main() {".to_string(),
+                styles: vec![(0..2, style)],
+            },
+            StyledLine {
+                text: "}".to_string(),
+                styles: Vec::new(),
+            },
+        ];
+        let options = HtmlOptions {
+            title: String::new(),
+            line_numbers: false,
+            font_family: "Monospace".to_string(),
+            font_size: 11.0,
+            tab_size: 4,
+            foreground: None,
+            background: None,
+        };
+        assert_eq!(
+            to_rtf(&lines, &options),
+            "{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Monospace;}}\
+             {\\colortbl;\\red255\\green0\\blue0;}\n\\f0\\fs22 \
+             {\\cf1\\b fn} main() \\{\\line\n\\}}"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! The strings the user has searched for and replaced matches with recently. They're kept in the
+//! GSchema, so all windows share them and they're still there after restarting gxi.
+
+/// GSchema key of the recent search strings
+pub const SEARCH_HISTORY_KEY: &str = "search-history";
+/// GSchema key of the recent replacement strings
+pub const REPLACE_HISTORY_KEY: &str = "replace-history";
+/// How many strings each history holds
+pub const MAX_HISTORY: usize = 20;
+
+/// Moves `s` to the front of `history`
+pub fn remember(history: &mut Vec<String>, s: &str) {
+    if s.is_empty() {
+        return;
+    }
+    history.retain(|h| h != s);
+    history.insert(0, s.to_string());
+    history.truncate(MAX_HISTORY);
+}
+
+/// Where the user is in a history while cycling through it with Up and Down, like in a shell
+#[derive(Debug, Default)]
+pub struct HistoryCursor {
+    /// Index of the string shown in the entry, `None` if it's the one the user has typed
+    index: Option<usize>,
+    /// What the user had typed before going through the history
+    draft: String,
+}
+
+impl HistoryCursor {
+    /// Returns the string older than the one shown (Up). `current` is what's in the entry.
+    pub fn older(&mut self, history: &[String], current: &str) -> Option<String> {
+        let index = match self.index {
+            None => {
+                self.draft = current.to_string();
+                0
+            }
+            Some(index) => index + 1,
+        };
+        let s = history.get(index)?;
+        self.index = Some(index);
+        Some(s.clone())
+    }
+
+    /// Returns the string newer than the one shown (Down), or what the user had typed at the end
+    pub fn newer(&mut self, history: &[String]) -> Option<String> {
+        match self.index? {
+            0 => {
+                self.index = None;
+                Some(self.draft.clone())
+            }
+            index => {
+                self.index = Some(index - 1);
+                history.get(index - 1).cloned()
+            }
+        }
+    }
+
+    /// Forgets where we are, e.g. after a string has been added to the history
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.draft.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remember_strings() {
+        let mut history = vec!["a".to_string(), "b".to_string()];
+        remember(&mut history, "b");
+        remember(&mut history, "");
+        assert_eq!(history, vec!["b", "a"]);
+        for i in 0..MAX_HISTORY {
+            remember(&mut history, &i.to_string());
+        }
+        assert_eq!(history.len(), MAX_HISTORY);
+    }
+
+    #[test]
+    fn cycle() {
+        let history = vec!["new".to_string(), "old".to_string()];
+        let mut cursor = HistoryCursor::default();
+        assert_eq!(cursor.newer(&history), None);
+        assert_eq!(cursor.older(&history, "typed"), Some("new".to_string()));
+        assert_eq!(cursor.older(&history, "new"), Some("old".to_string()));
+        assert_eq!(cursor.older(&history, "old"), None);
+        assert_eq!(cursor.newer(&history), Some("new".to_string()));
+        assert_eq!(cursor.newer(&history), Some("typed".to_string()));
+        assert_eq!(cursor.newer(&history), None);
+    }
+}
@@ -0,0 +1,118 @@
+//! Tick marks on the trough of the vertical scrollbar, showing where in the document the matches
+//! of the current search are.
+
+use gettextrs::gettext;
+use glib::{ObjectExt, ToValue};
+use gtk::*;
+use log::warn;
+use std::sync::{Arc, Mutex};
+use syntect::highlighting::Color;
+
+/// Height of a tick mark, in pixels
+const TICK_HEIGHT: f64 = 2.0;
+
+#[derive(Default)]
+struct Ticks {
+    /// Where the ticks are, as fraction of the scrollbar's height
+    positions: Vec<f64>,
+    color: (f64, f64, f64, f64),
+}
+
+impl Ticks {
+    fn draw(&self, scrollbar: &Widget, cr: &cairo::Context) {
+        if self.positions.is_empty() {
+            return;
+        }
+        let width = f64::from(scrollbar.get_allocated_width());
+        let height = f64::from(scrollbar.get_allocated_height());
+        let (red, green, blue, alpha) = self.color;
+        cr.set_source_rgba(red, green, blue, alpha);
+        for position in &self.positions {
+            let y = (position * height).floor().min(height - TICK_HEIGHT);
+            cr.rectangle(0.0, y, width, TICK_HEIGHT);
+        }
+        cr.fill();
+    }
+}
+
+/// Returns where the ticks for matches on `lines` go in a document with `height` lines, as
+/// fraction of the scrollbar's height. Lines with several matches get only one tick.
+fn tick_positions(lines: &[u64], height: u64) -> Vec<f64> {
+    let mut lines = lines.to_vec();
+    lines.sort();
+    lines.dedup();
+    lines
+        .into_iter()
+        .map(|line| line as f64 / height.max(1) as f64)
+        .collect()
+}
+
+pub struct SearchTicks {
+    ticks: Arc<Mutex<Ticks>>,
+    scrollbar: Option<Widget>,
+}
+
+impl SearchTicks {
+    /// Draws the ticks on the vertical scrollbar of `scrolled_window`
+    pub fn new(scrolled_window: &ScrolledWindow) -> Self {
+        let ticks = Arc::new(Mutex::new(Ticks::default()));
+        let scrollbar = scrolled_window.get_vscrollbar();
+        if let Some(scrollbar) = &scrollbar {
+            let ticks = ticks.clone();
+            // Connect after the scrollbar's own handler, so the ticks are drawn on top of its
+            // trough. GTK calls this on the main thread only, `connect` just wants it to be Send.
+            let res = scrollbar.connect("draw", true, move |values| {
+                let widget = values[0].get::<Widget>();
+                let cr = values[1].get::<cairo::Context>();
+                if let (Some(widget), Some(cr)) = (widget, cr) {
+                    ticks.lock().unwrap().draw(&widget, &cr);
+                }
+                Some(false.to_value())
+            });
+            if let Err(e) = res {
+                warn!(
+                    "{}: {}",
+                    gettext("Failed to draw search matches on scrollbar"),
+                    e
+                );
+            }
+        }
+
+        Self { ticks, scrollbar }
+    }
+
+    /// Shows ticks for the matches on `lines` of a document with `height` lines
+    pub fn set(&self, lines: &[u64], height: u64, color: Color) {
+        {
+            let mut ticks = self.ticks.lock().unwrap();
+            ticks.positions = tick_positions(lines, height);
+            ticks.color = (
+                f64::from(color.r) / 255.0,
+                f64::from(color.g) / 255.0,
+                f64::from(color.b) / 255.0,
+                f64::from(color.a) / 255.0,
+            );
+        }
+        if let Some(scrollbar) = &self.scrollbar {
+            scrollbar.queue_draw();
+        }
+    }
+
+    pub fn clear(&self) {
+        self.ticks.lock().unwrap().positions.clear();
+        if let Some(scrollbar) = &self.scrollbar {
+            scrollbar.queue_draw();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        assert_eq!(tick_positions(&[3, 1, 3], 4), vec![0.25, 0.75]);
+        assert_eq!(tick_positions(&[0], 0), vec![0.0]);
+    }
+}
@@ -0,0 +1,66 @@
+const LEFT_DOUBLE_QUOTE: &str = "\u{201c}";
+const RIGHT_DOUBLE_QUOTE: &str = "\u{201d}";
+const LEFT_SINGLE_QUOTE: &str = "\u{2018}";
+/// Also used as apostrophe
+const RIGHT_SINGLE_QUOTE: &str = "\u{2019}";
+const EN_DASH: &str = "\u{2013}";
+const EM_DASH: &str = "\u{2014}";
+
+/// Whether typographic punctuation makes sense in documents of `language`. Code never wants it.
+pub fn is_prose_language(language: &str) -> bool {
+    match language {
+        "Markdown" | "MultiMarkdown" | "Plain Text" | "reStructuredText" => true,
+        _ => false,
+    }
+}
+
+/// Returns what should be inserted instead of the `typed` text and how many chars before the
+/// cursor it replaces, if anything. `before` is the text of the line before the cursor.
+///
+/// Straight quotes are replaced by curly ones, `--` by an en dash and `---` by an em dash.
+pub fn substitute(before: &str, typed: &str) -> Option<(usize, &'static str)> {
+    // Inline code spans are left alone
+    if before.matches('`').count() % 2 == 1 {
+        return None;
+    }
+    // Lines of dashes are horizontal rules, table separators or front matter in Markdown
+    let only_rule = before
+        .chars()
+        .all(|c| c == '-' || c == '|' || c == ':' || c.is_whitespace());
+    let opens = before.chars().last().map_or(true, |c| {
+        c.is_whitespace() || "([{<-/\u{2013}\u{2014}".contains(c)
+    });
+
+    match typed {
+        "\"" if opens => Some((0, LEFT_DOUBLE_QUOTE)),
+        "\"" => Some((0, RIGHT_DOUBLE_QUOTE)),
+        "'" if opens => Some((0, LEFT_SINGLE_QUOTE)),
+        "'" => Some((0, RIGHT_SINGLE_QUOTE)),
+        "-" if only_rule => None,
+        "-" if before.ends_with('-') => Some((1, EN_DASH)),
+        "-" if before.ends_with(EN_DASH) => Some((1, EM_DASH)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(substitute("He said ", "\""), Some((0, LEFT_DOUBLE_QUOTE)));
+        assert_eq!(
+            substitute("He said “hi", "\""),
+            Some((0, RIGHT_DOUBLE_QUOTE))
+        );
+        assert_eq!(substitute("don", "'"), Some((0, RIGHT_SINGLE_QUOTE)));
+        assert_eq!(substitute("", "'"), Some((0, LEFT_SINGLE_QUOTE)));
+        assert_eq!(substitute("1990-", "-"), Some((1, EN_DASH)));
+        assert_eq!(substitute("wait\u{2013}", "-"), Some((1, EM_DASH)));
+        assert_eq!(substitute("wait", "-"), None);
+        assert_eq!(substitute("Use `\"", "\""), None);
+        assert_eq!(substitute("--", "-"), None);
+        assert_eq!(substitute("| :-", "-"), None);
+    }
+}
@@ -0,0 +1,451 @@
+//! Spell checks prose documents and the comments and strings of code. Words are checked by a
+//! dictionary of Enchant on a worker thread, so drawing never waits for it: words which haven't
+//! been checked yet are sent to the worker after drawing and the EditViews are drawn again once
+//! it has answered. Checking needs the `spell_check` feature.
+
+use crate::comments::{self, CommentTokens};
+use crate::smart_punctuation;
+use gettextrs::gettext;
+use glib::MainContext;
+use gtk::{IsA, Widget, WidgetExt};
+use log::{debug, warn};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How many suggestions are offered for a misspelled word
+const MAX_SUGGESTIONS: usize = 5;
+
+/// The byte ranges of `line` which are spell checked: all of it in prose (except for inline code),
+/// in code only the comments and, in languages with line comments, the strings
+pub fn checked_ranges(line: &str, language: &str) -> Vec<Range<usize>> {
+    if smart_punctuation::is_prose_language(language) {
+        let mut start = 0;
+        return line
+            .split('`')
+            .map(|part| {
+                let range = start..start + part.len();
+                start = range.end + 1;
+                range
+            })
+            .step_by(2)
+            .filter(|range| !range.is_empty())
+            .collect();
+    }
+
+    let tokens = comments::comment_tokens(language);
+    let check_strings = match tokens {
+        Some(CommentTokens::Line(_)) => true,
+        _ => false,
+    };
+    let mut ranges = Vec::new();
+    // Where the string we're in started, after its quote
+    let mut string_start = None;
+    let mut ix = 0;
+    while ix < line.len() {
+        let rest = &line[ix..];
+        let c = rest.chars().next().unwrap();
+        match (string_start, tokens) {
+            (Some(start), _) => {
+                if c == '\\' {
+                    ix += rest.chars().take(2).map(char::len_utf8).sum::<usize>();
+                    continue;
+                } else if c == '"' {
+                    ranges.push(start..ix);
+                    string_start = None;
+                }
+            }
+            (None, Some(CommentTokens::Line(token))) if rest.starts_with(token) => {
+                ranges.push(ix + token.len()..line.len());
+                return ranges;
+            }
+            (None, Some(CommentTokens::Block(start, end))) if rest.starts_with(start) => {
+                let comment_start = ix + start.len();
+                ix = line[comment_start..]
+                    .find(end)
+                    .map_or(line.len(), |end_ix| comment_start + end_ix);
+                ranges.push(comment_start..ix);
+                ix += end.len();
+                continue;
+            }
+            (None, _) if c == '"' && check_strings => string_start = Some(ix + 1),
+            _ => {}
+        }
+        ix += c.len_utf8();
+    }
+    // Strings may continue on the next line
+    if let Some(start) = string_start {
+        ranges.push(start..line.len());
+    }
+    ranges
+}
+
+/// Whether `word` should be spell checked. Identifiers, like words with digits or underscores and
+/// camelCase ones, and acronyms aren't.
+fn is_checkable(word: &str) -> bool {
+    word.chars().count() > 1
+        && word
+            .chars()
+            .all(|c| c.is_alphabetic() || c == '\'' || c == '\u{2019}')
+        && !word.chars().skip(1).any(char::is_uppercase)
+}
+
+/// The byte ranges of the words in the `ranges` of `line` which should be spell checked
+pub fn words(line: &str, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    ranges
+        .iter()
+        .flat_map(|range| {
+            line[range.clone()]
+                .split_word_bound_indices()
+                .filter(|(_, word)| is_checkable(word))
+                .map(move |(start, word)| range.start + start..range.start + start + word.len())
+        })
+        .collect()
+}
+
+#[cfg(feature = "spell_check")]
+mod backend {
+    /// A dictionary of Enchant, which picks the spell checker (e.g. Hunspell) installed for the
+    /// language
+    pub struct Dictionary {
+        dict: enchant::Dict,
+        _broker: enchant::Broker,
+    }
+
+    impl Dictionary {
+        pub fn new(language: &str) -> Result<Self, String> {
+            let mut broker = enchant::Broker::new();
+            let dict = broker.request_dict(language)?;
+            Ok(Self {
+                dict,
+                _broker: broker,
+            })
+        }
+
+        pub fn check(&self, word: &str) -> bool {
+            // Words the dictionary can't handle aren't marked as misspelled
+            self.dict.check(word).unwrap_or(true)
+        }
+
+        pub fn suggest(&self, word: &str) -> Vec<String> {
+            self.dict.suggest(word)
+        }
+
+        /// Adds `word` to the user's personal dictionary
+        pub fn add(&mut self, word: &str) {
+            self.dict.add(word);
+        }
+    }
+
+    pub fn languages() -> Vec<String> {
+        let mut broker = enchant::Broker::new();
+        broker
+            .list_dicts()
+            .into_iter()
+            .map(|dict| dict.lang)
+            .collect()
+    }
+}
+
+#[cfg(not(feature = "spell_check"))]
+mod backend {
+    use gettextrs::gettext;
+
+    pub struct Dictionary;
+
+    impl Dictionary {
+        pub fn new(_language: &str) -> Result<Self, String> {
+            Err(gettext("gxi has been built without spell checking"))
+        }
+
+        pub fn check(&self, _word: &str) -> bool {
+            true
+        }
+
+        pub fn suggest(&self, _word: &str) -> Vec<String> {
+            Vec::new()
+        }
+
+        pub fn add(&mut self, _word: &str) {}
+    }
+
+    pub fn languages() -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The languages dictionaries are installed for, e.g. `en_US`. Empty if gxi has been built
+/// without spell checking.
+pub fn languages() -> Vec<String> {
+    let mut languages = backend::languages();
+    languages.sort();
+    languages.dedup();
+    languages
+}
+
+/// What the worker thread is asked to do. Requests are tagged with the generation of the
+/// language, so answers for a language which isn't used anymore can be told apart.
+enum Request {
+    SetLanguage(u32, String),
+    Check(Vec<String>),
+    Add(String),
+}
+
+enum Response {
+    /// The checked words of a generation, with suggestions for the misspelled ones
+    Checked(u32, Vec<(String, Option<Vec<String>>)>),
+    /// The dictionary of the language couldn't be loaded
+    Error(String, String),
+}
+
+fn run_worker(requests: mpsc::Receiver<Request>, responses: glib::Sender<Response>) {
+    let mut dictionary = None;
+    let mut generation = 0;
+    for request in requests {
+        match request {
+            Request::SetLanguage(new_generation, language) => {
+                generation = new_generation;
+                dictionary = match backend::Dictionary::new(&language) {
+                    Ok(dictionary) => Some(dictionary),
+                    Err(e) => {
+                        let _ = responses.send(Response::Error(language, e));
+                        None
+                    }
+                };
+            }
+            Request::Check(words) => {
+                let dictionary = match &dictionary {
+                    Some(dictionary) => dictionary,
+                    None => continue,
+                };
+                let results = words
+                    .into_iter()
+                    .map(|word| {
+                        let suggestions = if dictionary.check(&word) {
+                            None
+                        } else {
+                            let mut suggestions = dictionary.suggest(&word);
+                            suggestions.truncate(MAX_SUGGESTIONS);
+                            Some(suggestions)
+                        };
+                        (word, suggestions)
+                    })
+                    .collect();
+                if responses
+                    .send(Response::Checked(generation, results))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Request::Add(word) => {
+                if let Some(dictionary) = &mut dictionary {
+                    dictionary.add(&word);
+                }
+            }
+        }
+    }
+}
+
+/// Spell checks the words of all EditViews of a window
+#[derive(Default)]
+pub struct SpellChecker {
+    /// The language of the dictionary, empty if spell checking is off
+    language: RefCell<String>,
+    /// Increased whenever the language changes
+    generation: Rc<Cell<u32>>,
+    /// The checked words, with suggestions for the misspelled ones
+    words: Rc<RefCell<HashMap<String, Option<Vec<String>>>>>,
+    /// The words which have been sent to the worker or are about to be
+    pending: Rc<RefCell<HashSet<String>>>,
+    /// The words which are sent to the worker once drawing has finished
+    queued: RefCell<Vec<String>>,
+    /// Started once spell checking is turned on
+    requests: RefCell<Option<mpsc::Sender<Request>>>,
+    /// Drawn again once words have been checked
+    widgets: Rc<RefCell<Vec<glib::WeakRef<Widget>>>>,
+}
+
+impl SpellChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks words with the dictionary of `language` from now on, an empty `language` turns
+    /// spell checking off
+    pub fn set_language(&self, language: &str) {
+        if *self.language.borrow() == language {
+            return;
+        }
+        debug!(
+            "{}: '{}'",
+            gettext("Setting spell check language"),
+            language
+        );
+        self.language.replace(language.to_string());
+        self.generation.set(self.generation.get() + 1);
+        self.words.borrow_mut().clear();
+        self.pending.borrow_mut().clear();
+        self.queued.borrow_mut().clear();
+        if !language.is_empty() {
+            self.send(Request::SetLanguage(
+                self.generation.get(),
+                language.to_string(),
+            ));
+        }
+        redraw(&self.widgets);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.language.borrow().is_empty()
+    }
+
+    /// Draws `widget` again whenever words have been checked
+    pub fn add_widget<W: IsA<Widget>>(&self, widget: &W) {
+        self.widgets
+            .borrow_mut()
+            .push(widget.upcast_ref::<Widget>().downgrade());
+    }
+
+    /// Whether `word` is misspelled. Words which haven't been checked yet count as correct until
+    /// they have been, after the next `flush`.
+    pub fn is_misspelled(&self, word: &str) -> bool {
+        if let Some(suggestions) = self.words.borrow().get(word) {
+            return suggestions.is_some();
+        }
+        if self.pending.borrow_mut().insert(word.to_string()) {
+            self.queued.borrow_mut().push(word.to_string());
+        }
+        false
+    }
+
+    /// What `word` could be meant to be, `None` if it isn't misspelled (or hasn't been checked yet)
+    pub fn suggestions(&self, word: &str) -> Option<Vec<String>> {
+        self.words.borrow().get(word).cloned().unwrap_or_default()
+    }
+
+    /// Sends the words `is_misspelled` has been asked about to the worker
+    pub fn flush(&self) {
+        let queued: Vec<String> = self.queued.borrow_mut().drain(..).collect();
+        if !queued.is_empty() {
+            self.send(Request::Check(queued));
+        }
+    }
+
+    /// Adds `word` to the user's personal dictionary, so it isn't marked as misspelled anymore
+    pub fn add_to_dictionary(&self, word: &str) {
+        self.words.borrow_mut().insert(word.to_string(), None);
+        self.send(Request::Add(word.to_string()));
+        redraw(&self.widgets);
+    }
+
+    fn send(&self, request: Request) {
+        let mut requests = self.requests.borrow_mut();
+        let requests = requests.get_or_insert_with(|| self.start_worker());
+        if requests.send(request).is_err() {
+            warn!("{}", gettext("The spell checker has stopped"));
+        }
+    }
+
+    fn start_worker(&self) -> mpsc::Sender<Request> {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (response_tx, response_rx) = MainContext::channel(glib::PRIORITY_DEFAULT_IDLE);
+        thread::spawn(move || run_worker(request_rx, response_tx));
+
+        let generation = self.generation.clone();
+        let words = self.words.clone();
+        let pending = self.pending.clone();
+        let widgets = self.widgets.clone();
+        response_rx.attach(None, move |response| {
+            match response {
+                Response::Checked(checked_generation, results) => {
+                    if checked_generation == generation.get() {
+                        let mut words = words.borrow_mut();
+                        let mut pending = pending.borrow_mut();
+                        for (word, suggestions) in results {
+                            pending.remove(&word);
+                            words.insert(word, suggestions);
+                        }
+                        redraw(&widgets);
+                    }
+                }
+                Response::Error(language, e) => warn!(
+                    "{} '{}': {}",
+                    gettext("Couldn't load the spell checking dictionary for"),
+                    language,
+                    e
+                ),
+            }
+            glib::Continue(true)
+        });
+        request_tx
+    }
+}
+
+/// Draws the `widgets` which still exist again
+fn redraw(widgets: &RefCell<Vec<glib::WeakRef<Widget>>>) {
+    widgets
+        .borrow_mut()
+        .retain(|widget| match widget.upgrade() {
+            Some(widget) => {
+                widget.queue_draw();
+                true
+            }
+            None => false,
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checked<'a>(line: &'a str, language: &str) -> Vec<&'a str> {
+        checked_ranges(line, language)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect()
+    }
+
+    #[test]
+    fn code() {
+        assert_eq!(
+            checked(r#"let s = "a \"qoute\""; // teh end"#, "Rust"),
+            vec![r#"a \"qoute\""#, " teh end"]
+        );
+        // Comment tokens in strings don't start comments
+        assert_eq!(
+            checked(r##"x = "#1" # note"##, "Python"),
+            vec!["#1", " note"]
+        );
+        assert_eq!(checked("\"unterminated", "C"), vec!["unterminated"]);
+        // Strings in markup are attribute values
+        assert_eq!(
+            checked(r#"<a href="x"><!-- one --> <!-- two"#, "HTML"),
+            vec![" one ", " two"]
+        );
+        assert!(checked("plain(code);", "Rust").is_empty());
+    }
+
+    #[test]
+    fn prose() {
+        assert_eq!(
+            checked("Run `cargo bild` to build", "Markdown"),
+            vec!["Run ", " to build"]
+        );
+        assert_eq!(checked("All of it", "Plain Text"), vec!["All of it"]);
+    }
+
+    #[test]
+    fn checked_words() {
+        let line = "Don't chek fooBar, foo_bar, HTML, x1 or ümlaut";
+        let words: Vec<&str> = words(line, &[0..line.len()])
+            .into_iter()
+            .map(|range| &line[range])
+            .collect();
+        assert_eq!(words, vec!["Don't", "chek", "or", "ümlaut"]);
+    }
+}
@@ -50,6 +50,50 @@ pub fn set_source_color(cr: &cairo::Context, color: Option<Color>) {
     }
 }
 
+/// Sets the color the part of lines beyond the right margin is tinted with. This is translucent,
+/// so it works with both light and dark themes.
+pub fn set_long_line_source_color(cr: &cairo::Context) {
+    cr.set_source_rgba(1.0, 0.4, 0.0, 0.15);
+}
+
+/// Sets the color of the wavy lines under misspelled words
+pub fn set_misspelling_source_color(cr: &cairo::Context) {
+    cr.set_source_rgba(0.88, 0.11, 0.14, 0.9);
+}
+
+/// Sets the color occurrences of the word at the cursor are highlighted with, a faded
+/// foreground color
+pub fn set_occurrence_source_color(cr: &cairo::Context, foreground: Option<Color>) {
+    match foreground {
+        Some(c) => cr.set_source_rgba(
+            f64::from(c.r) / 255.0,
+            f64::from(c.g) / 255.0,
+            f64::from(c.b) / 255.0,
+            0.12,
+        ),
+        None => cr.set_source_rgba(0.2, 0.2, 0.2, 0.12),
+    }
+}
+
+/// Sets the color of whitespace markers. Uses the theme's color for invisibles if it has one,
+/// otherwise a faded foreground color.
+pub fn set_whitespace_source_color(
+    cr: &cairo::Context,
+    invisibles: Option<Color>,
+    foreground: Option<Color>,
+) {
+    match (invisibles, foreground) {
+        (Some(_), _) => set_source_color(cr, invisibles),
+        (None, Some(c)) => cr.set_source_rgba(
+            f64::from(c.r) / 255.0,
+            f64::from(c.g) / 255.0,
+            f64::from(c.b) / 255.0,
+            0.4,
+        ),
+        (None, None) => cr.set_source_rgba(0.2, 0.2, 0.2, 0.4),
+    }
+}
+
 /// Used for the right hand margin to make the margin a bit darker than the original background
 pub fn set_margin_source_color(cr: &cairo::Context, color: Option<Color>) {
     let source_color = if let Some(c) = color {
@@ -93,3 +137,41 @@ pub fn color_from_u32(c: u32) -> Color {
 pub fn u32_from_color(c: Color) -> u32 {
     (u32::from(c.a) << 24) | (u32::from(c.r) << 16) | (u32::from(c.g) << 8) | u32::from(c.b)
 }
+
+/// Parses colors like `#rrggbb`, as they're stored in the settings
+pub fn color_from_hex(hex: &str) -> Option<Color> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color {
+        r: component(0)?,
+        g: component(2)?,
+        b: component(4)?,
+        a: 0xff,
+    })
+}
+
+/// Formats a color like `#rrggbb`, see `color_from_hex`
+pub fn hex_from_color(c: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        let color = color_from_hex("#1a2B3c").unwrap();
+        assert_eq!(
+            (color.r, color.g, color.b, color.a),
+            (0x1a, 0x2b, 0x3c, 0xff)
+        );
+        assert_eq!(hex_from_color(color), "#1a2b3c");
+        assert!(color_from_hex("").is_none());
+        assert!(color_from_hex("#12345").is_none());
+        assert!(color_from_hex("#12345g").is_none());
+    }
+}
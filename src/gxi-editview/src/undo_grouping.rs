@@ -0,0 +1,71 @@
+//! Decides which typed text is undone together. Undoing goes back a word or a burst of typing at a
+//! time: a new undo group is started when a word starts after a space or punctuation, after the
+//! user has paused typing for a while and after anything else, e.g. moving the cursor.
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+pub struct UndoGrouping {
+    /// When the user last typed and the last char of that text, `None` if the user has done
+    /// something else since then
+    last_typed: Option<(Instant, char)>,
+}
+
+impl UndoGrouping {
+    /// Whether `text`, typed at `now`, starts a new undo group. Pausing longer than `timeout`
+    /// starts one, unless it's zero.
+    pub fn starts_group(&mut self, text: &str, now: Instant, timeout: Duration) -> bool {
+        let last_typed = self.last_typed.take();
+        if let Some(last_char) = text.chars().last() {
+            self.last_typed = Some((now, last_char));
+        }
+        match (last_typed, text.chars().next()) {
+            (Some((time, last_char)), Some(first_char)) => {
+                let paused = timeout > Duration::from_secs(0) && now.duration_since(time) > timeout;
+                let new_word = first_char.is_alphanumeric() && !last_char.is_alphanumeric();
+                paused || new_word
+            }
+            (None, _) => true,
+            (_, None) => false,
+        }
+    }
+
+    /// Ends the current undo group, e.g. because the user has moved the cursor
+    pub fn interrupt(&mut self) {
+        self.last_typed = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words() {
+        let mut grouping = UndoGrouping::default();
+        let now = Instant::now();
+        let timeout = Duration::from_secs(1);
+        let starts: Vec<bool> = ["h", "i", " ", "y", "o", "u", "!", "\u{201c}", "a"]
+            .iter()
+            .map(|text| grouping.starts_group(text, now, timeout))
+            .collect();
+        assert_eq!(
+            starts,
+            vec![true, false, false, true, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn pauses_and_interruptions() {
+        let mut grouping = UndoGrouping::default();
+        let now = Instant::now();
+        let timeout = Duration::from_millis(500);
+        assert!(grouping.starts_group("a", now, timeout));
+        assert!(!grouping.starts_group("b", now + Duration::from_millis(400), timeout));
+        assert!(grouping.starts_group("c", now + Duration::from_millis(1000), timeout));
+        // Without a timeout only words are grouped
+        assert!(!grouping.starts_group("d", now + Duration::from_secs(60), Duration::from_secs(0)));
+        grouping.interrupt();
+        assert!(grouping.starts_group("e", now + Duration::from_secs(60), timeout));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::edit_view::EditView;
 use crate::main_state::MainState;
+use atk::AtkObjectExt;
 use gdk::{Cursor, CursorType, DisplayManager, WindowExt};
 use gettextrs::gettext;
 use gtk::*;
@@ -19,12 +20,27 @@ pub struct EvBar {
     syntax_popover: Popover,
     pub line_label: Label,
     pub column_label: Label,
+    pub long_lines_label: Label,
+    pub zoom_label: Label,
+    pub reduced_mode_label: Label,
+    pub overwrite_label: Label,
+    pub vim_mode_label: Label,
+    pub diagnostics_label: Label,
+}
+
+impl EvBar {
+    /// Opens the list of syntaxes, as if the user had clicked on the current one
+    pub fn show_syntax_menu(&self) {
+        self.syntax_menu_button.set_active(true);
+    }
 }
 
 /// The ViewItem contains the various GTK parts related to the edit_area of the EditView
 #[derive(Clone)]
 pub struct ViewItem {
     pub root_box: Grid,
+    /// Notifications about the document are shown in here, above the text
+    pub infobar_box: gtk::Box,
     pub ev_scrolled_window: ScrolledWindow,
     pub edit_area: gtk::Layout,
     pub linecount: gtk::Layout,
@@ -50,10 +66,16 @@ impl ViewItem {
             syntax_menu_button: builder.get_object("syntax_menu_button").unwrap(),
             line_label: builder.get_object("line_label").unwrap(),
             column_label: builder.get_object("column_label").unwrap(),
+            long_lines_label: builder.get_object("long_lines_label").unwrap(),
+            zoom_label: builder.get_object("zoom_label").unwrap(),
+            reduced_mode_label: builder.get_object("reduced_mode_label").unwrap(),
+            overwrite_label: builder.get_object("overwrite_label").unwrap(),
+            vim_mode_label: builder.get_object("vim_mode_label").unwrap(),
+            diagnostics_label: builder.get_object("diagnostics_label").unwrap(),
         };
 
         // Creation of a model with two rows.
-        let list_model: ListStore = builder.get_object("syntax_liststore").unwrap();;
+        let list_model: ListStore = builder.get_object("syntax_liststore").unwrap();
 
         for lang in main_state.avail_languages.iter() {
             // Localize 'Plain Text'
@@ -70,6 +92,7 @@ impl ViewItem {
         }
 
         let ev_scrolled_window = builder.get_object("ev_scrolled_window").unwrap();
+        let infobar_box = builder.get_object("infobar_box").unwrap();
         let hbox: Grid = builder.get_object("ev_root_widget").unwrap();
         hbox.show_all();
 
@@ -80,6 +103,7 @@ impl ViewItem {
             vadj,
             statusbar,
             ev_scrolled_window,
+            infobar_box,
             root_box: hbox,
         }
     }
@@ -94,6 +118,10 @@ impl ViewItem {
 
         self.ev_scrolled_window
             .connect_button_press_event(enclose!((edit_view) move |_,eb| {
+                if eb.get_button() == 3 && eb.get_event_type() == gdk::EventType::ButtonPress {
+                    EditView::show_context_menu(&edit_view, eb);
+                    return Inhibit(true);
+                }
                 edit_view.borrow().handle_button_press(eb)
             }));
 
@@ -107,6 +135,12 @@ impl ViewItem {
                 edit_view.borrow().handle_key_press_event(ek)
             }));
 
+        self.ev_scrolled_window
+            .connect_focus_out_event(enclose!((edit_view) move |_, _| {
+                edit_view.borrow().handle_focus_out();
+                Inhibit(false)
+            }));
+
         self.ev_scrolled_window
             .connect_motion_notify_event(enclose!((edit_view) move |_,em| {
                edit_view.borrow().handle_drag(em)
@@ -129,11 +163,33 @@ impl ViewItem {
             edit_view.borrow().do_resize(&edit_view.borrow().view_id,alloc.width, alloc.height);
         }));
 
+        // The metrics of the fonts change with the scale factor and the resolution of the monitor
+        self.edit_area
+            .connect_property_scale_factor_notify(enclose!((edit_view) move |_| {
+                EditView::schedule_font_metrics_update(&edit_view);
+            }));
+
+        self.edit_area
+            .connect_screen_changed(enclose!((edit_view) move |_, _| {
+                EditView::schedule_font_metrics_update(&edit_view);
+            }));
+
         self.linecount
             .connect_draw(enclose!((edit_view) move |_,ctx| {
                 edit_view.borrow().handle_linecount_draw(&ctx)
             }));
 
+        self.linecount
+            .connect_button_press_event(enclose!((edit_view) move |_,eb| {
+                edit_view.borrow_mut().handle_linecount_button_press(eb)
+            }));
+
+        self.linecount.set_has_tooltip(true);
+        self.linecount
+            .connect_query_tooltip(enclose!((edit_view) move |_,x,y,_,tooltip| {
+                edit_view.borrow().handle_linecount_query_tooltip(f64::from(x), f64::from(y), tooltip)
+            }));
+
         self.statusbar
             .syntax_treeview
             .get_selection()
@@ -148,12 +204,20 @@ impl ViewItem {
                         } else {
                              edit_view.borrow().set_language(&lang);
                         }
+                        // The right margin may be different for this language
+                        edit_view.borrow().update_long_lines_label();
+                        edit_view.borrow().view_item.edit_area.queue_draw();
                     }
                 }
             }));
 
         self.ev_scrolled_window
-            .connect_scroll_event(enclose!((edit_view) move |_,_| {
+            .connect_scroll_event(enclose!((edit_view) move |_,es| {
+                // Ctrl+scroll zooms instead of scrolling
+                if es.get_state().contains(gdk::ModifierType::CONTROL_MASK) {
+                    edit_view.borrow().handle_zoom_scroll(es);
+                    return Inhibit(true);
+                }
                 edit_view.borrow().update_visible_scroll_region();
                 Inhibit(false)
             }));
@@ -223,6 +287,9 @@ pub struct FindReplace {
     pub use_regex_button: CheckButton,
     pub case_sensitive_button: CheckButton,
     pub whole_word_button: CheckButton,
+    pub regex_tester_button: CheckButton,
+    pub regex_tester_revealer: Revealer,
+    pub regex_tester_label: Label,
 }
 
 impl FindReplace {
@@ -247,6 +314,9 @@ impl FindReplace {
         let whole_word_button = builder.get_object("whole_word_button").unwrap();
         let show_replace_button = builder.get_object("show_replace_button").unwrap();
         let show_options_button = builder.get_object("show_options_button").unwrap();
+        let regex_tester_button = builder.get_object("regex_tester_button").unwrap();
+        let regex_tester_revealer = builder.get_object("regex_tester_revealer").unwrap();
+        let regex_tester_label = builder.get_object("regex_tester_label").unwrap();
 
         popover.set_position(PositionType::Bottom);
         #[cfg(not(feature = "gtk_v3_22"))]
@@ -270,6 +340,20 @@ impl FindReplace {
             option_revealer,
             find_status_label,
             search_bar,
+            regex_tester_button,
+            regex_tester_revealer,
+            regex_tester_label,
+        }
+    }
+
+    /// Tells screen readers about `text`, e.g. how many matches there are. It becomes the
+    /// description of the search entry, which screen readers read when it changes since the entry
+    /// has the focus while searching.
+    pub fn announce(&self, text: &str) {
+        if let Some(accessible) = self.search_entry.get_accessible() {
+            if accessible.get_description().as_ref().map(|d| d.as_str()) != Some(text) {
+                accessible.set_description(text);
+            }
         }
     }
 
@@ -314,11 +398,36 @@ impl FindReplace {
 
         self.search_bar
             .connect_property_search_mode_enabled_notify(enclose!((ev) move |sb| {
+                ev.borrow().search_mode_changed(sb.get_search_mode());
                 if ! sb.get_search_mode() {
                     ev.borrow().stop_search();
                 }
             }));
 
+        self.search_entry
+            .connect_key_press_event(enclose!((ev) move |_, ek| {
+                ev.borrow().handle_search_entry_key(false, ek)
+            }));
+
+        self.search_entry
+            .connect_icon_press(enclose!((ev) move |_, pos, _| {
+                if pos == EntryIconPosition::Primary {
+                    ev.borrow().show_search_history(false);
+                }
+            }));
+
+        self.replace_entry
+            .connect_key_press_event(enclose!((ev) move |_, ek| {
+                ev.borrow().handle_search_entry_key(true, ek)
+            }));
+
+        self.replace_entry
+            .connect_icon_press(enclose!((ev) move |_, pos, _| {
+                if pos == EntryIconPosition::Primary {
+                    ev.borrow().show_search_history(true);
+                }
+            }));
+
         self.search_entry
             .connect_search_changed(enclose!((ev) move |w| {
                 if let Some(text) = w.get_text() {
@@ -353,7 +462,13 @@ impl FindReplace {
         };
 
         self.use_regex_button
-            .connect_toggled(enclose!((ev) move |_| restart_search(ev.clone())));
+            .connect_toggled(enclose!((ev) move |btn| {
+                ev.borrow().find_replace.regex_tester_button.set_sensitive(btn.get_active());
+                restart_search(ev.clone())
+            }));
+
+        self.regex_tester_button
+            .connect_toggled(enclose!((ev) move |_| ev.borrow().update_regex_tester()));
 
         self.whole_word_button
             .connect_toggled(enclose!((ev) move |_| restart_search(ev.clone())));
@@ -367,7 +482,7 @@ impl FindReplace {
 
         self.search_entry
             .connect_stop_search(enclose!((ev) move |_| {
-                ev.borrow().stop_search();
+                ev.borrow().cancel_search();
             }));
 
         self.replace_button.connect_clicked(enclose!((ev) move |_| {
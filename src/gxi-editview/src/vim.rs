@@ -0,0 +1,258 @@
+//! A small subset of Vim's modal editing. This only keeps track of the mode and translates keys
+//! into `Command`s, the `EditView` carries them out.
+
+/// The mode the editor is in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Normal,
+    /// Keys are handled like without Vim emulation
+    Insert,
+    Visual,
+}
+
+/// Something a key (sequence) in normal or visual mode does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// `h`
+    Left,
+    /// `l`
+    Right,
+    /// `k`
+    Up,
+    /// `j`
+    Down,
+    /// `w`
+    WordForward,
+    /// `b`
+    WordBackward,
+    /// `0`
+    LineStart,
+    /// `$`
+    LineEnd,
+    /// `gg`
+    DocumentStart,
+    /// `G`
+    DocumentEnd,
+    /// `i`
+    Insert,
+    /// `a`
+    Append,
+    /// `I`
+    InsertAtLineStart,
+    /// `A`
+    AppendAtLineEnd,
+    /// `o`
+    OpenLineBelow,
+    /// `O`
+    OpenLineAbove,
+    /// `x`
+    DeleteChar,
+    /// `dd`
+    DeleteLine,
+    /// `yy`
+    YankLine,
+    /// `p`
+    PasteAfter,
+    /// `P`
+    PasteBefore,
+    /// `u`
+    Undo,
+    /// `Ctrl+R`
+    Redo,
+    /// `/`
+    Search,
+    /// `n`
+    SearchNext,
+    /// `N`
+    SearchPrevious,
+    /// `v`
+    Visual,
+    /// `d` or `x` in visual mode
+    DeleteSelection,
+    /// `y` in visual mode
+    YankSelection,
+    /// `m` followed by the name of the mark
+    SetMark(char),
+    /// `` ` `` followed by the name of the mark
+    JumpToMark(char),
+}
+
+impl Command {
+    /// Whether the command moves the cursor, which extends the selection in visual mode
+    pub fn is_movement(self) -> bool {
+        use self::Command::*;
+
+        match self {
+            Left | Right | Up | Down | WordForward | WordBackward | LineStart | LineEnd
+            | DocumentStart | DocumentEnd => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Vim {
+    mode: Mode,
+    /// The count typed before a command, e.g. the `3` of `3dd`
+    count: Option<u32>,
+    /// The first key of a two key command, e.g. the first `d` of `dd`
+    pending: Option<char>,
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Normal,
+            count: None,
+            pending: None,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Goes back to normal mode (Escape), cancelling commands which have only been typed partially
+    pub fn escape(&mut self) {
+        self.mode = Mode::Normal;
+        self.count = None;
+        self.pending = None;
+    }
+
+    /// Handles `ch` being typed in normal or visual mode. Returns the command to run and how often
+    /// it should be repeated, if `ch` completes a command.
+    pub fn handle_char(&mut self, ch: char) -> Option<(Command, u32)> {
+        use self::Command::*;
+
+        if let Some(digit) = ch.to_digit(10) {
+            // A lone 0 moves to the start of the line instead of starting a count
+            if digit != 0 || self.count.is_some() {
+                let count = self.count.unwrap_or(0).saturating_mul(10);
+                self.count = Some(count.saturating_add(digit));
+                return None;
+            }
+        }
+
+        let pending = self.pending.take();
+        let command = match (self.mode, pending, ch) {
+            (Mode::Normal, Some('d'), 'd') => DeleteLine,
+            (Mode::Normal, Some('y'), 'y') => YankLine,
+            (_, Some('g'), 'g') => DocumentStart,
+            (Mode::Normal, Some('m'), _) => SetMark(ch),
+            (_, Some('`'), _) => JumpToMark(ch),
+            // Unknown sequences are dropped, like Vim does
+            (_, Some(_), _) => {
+                self.count = None;
+                return None;
+            }
+            (Mode::Normal, None, 'd')
+            | (Mode::Normal, None, 'y')
+            | (Mode::Normal, None, 'm')
+            | (_, None, 'g')
+            | (_, None, '`') => {
+                self.pending = Some(ch);
+                return None;
+            }
+            (_, None, 'h') => Left,
+            (_, None, 'l') => Right,
+            (_, None, 'k') => Up,
+            (_, None, 'j') => Down,
+            (_, None, 'w') => WordForward,
+            (_, None, 'b') => WordBackward,
+            (_, None, '0') => LineStart,
+            (_, None, '$') => LineEnd,
+            (_, None, 'G') => DocumentEnd,
+            (Mode::Normal, None, 'i') => Insert,
+            (Mode::Normal, None, 'a') => Append,
+            (Mode::Normal, None, 'I') => InsertAtLineStart,
+            (Mode::Normal, None, 'A') => AppendAtLineEnd,
+            (Mode::Normal, None, 'o') => OpenLineBelow,
+            (Mode::Normal, None, 'O') => OpenLineAbove,
+            (Mode::Normal, None, 'x') => DeleteChar,
+            (Mode::Normal, None, 'p') => PasteAfter,
+            (Mode::Normal, None, 'P') => PasteBefore,
+            (Mode::Normal, None, 'u') => Undo,
+            (Mode::Normal, None, '/') => Search,
+            (Mode::Normal, None, 'n') => SearchNext,
+            (Mode::Normal, None, 'N') => SearchPrevious,
+            (Mode::Normal, None, 'v') => Visual,
+            (Mode::Visual, None, 'd') | (Mode::Visual, None, 'x') => DeleteSelection,
+            (Mode::Visual, None, 'y') => YankSelection,
+            _ => {
+                self.count = None;
+                return None;
+            }
+        };
+        Some(self.run(command))
+    }
+
+    /// Handles Ctrl+`ch` in normal or visual mode
+    pub fn handle_ctrl_char(&mut self, ch: char) -> Option<(Command, u32)> {
+        match (self.mode, ch) {
+            (Mode::Normal, 'r') => Some(self.run(Command::Redo)),
+            _ => None,
+        }
+    }
+
+    /// Switches modes as `command` requires and returns it along with its count
+    fn run(&mut self, command: Command) -> (Command, u32) {
+        use self::Command::*;
+
+        self.mode = match command {
+            Insert | Append | InsertAtLineStart | AppendAtLineEnd | OpenLineBelow
+            | OpenLineAbove => Mode::Insert,
+            Visual => Mode::Visual,
+            DeleteSelection | YankSelection => Mode::Normal,
+            _ => self.mode,
+        };
+        self.pending = None;
+        (command, self.count.take().unwrap_or(1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let mut vim = Vim::new();
+        assert_eq!(vim.handle_char('j'), Some((Command::Down, 1)));
+        assert_eq!(vim.handle_char('3'), None);
+        assert_eq!(vim.handle_char('d'), None);
+        assert_eq!(vim.handle_char('d'), Some((Command::DeleteLine, 3)));
+        assert_eq!(vim.handle_char('0'), Some((Command::LineStart, 1)));
+        assert_eq!(vim.handle_char('1'), None);
+        assert_eq!(vim.handle_char('0'), None);
+        assert_eq!(vim.handle_char('l'), Some((Command::Right, 10)));
+        // Unknown sequences are dropped
+        assert_eq!(vim.handle_char('d'), None);
+        assert_eq!(vim.handle_char('z'), None);
+        assert_eq!(vim.handle_char('x'), Some((Command::DeleteChar, 1)));
+        assert_eq!(vim.handle_char('m'), None);
+        assert_eq!(vim.handle_char('a'), Some((Command::SetMark('a'), 1)));
+        assert_eq!(vim.handle_char('`'), None);
+        assert_eq!(vim.handle_char('a'), Some((Command::JumpToMark('a'), 1)));
+    }
+
+    #[test]
+    fn modes() {
+        let mut vim = Vim::new();
+        assert_eq!(vim.handle_char('v'), Some((Command::Visual, 1)));
+        assert_eq!(vim.mode(), Mode::Visual);
+        assert_eq!(vim.handle_char('w'), Some((Command::WordForward, 1)));
+        assert_eq!(vim.handle_char('y'), Some((Command::YankSelection, 1)));
+        assert_eq!(vim.mode(), Mode::Normal);
+        assert_eq!(vim.handle_char('o'), Some((Command::OpenLineBelow, 1)));
+        assert_eq!(vim.mode(), Mode::Insert);
+        vim.escape();
+        assert_eq!(vim.mode(), Mode::Normal);
+        assert_eq!(vim.handle_ctrl_char('r'), Some((Command::Redo, 1)));
+    }
+}
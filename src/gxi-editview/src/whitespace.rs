@@ -0,0 +1,138 @@
+use crate::main_state::{Settings, WhitespaceMode};
+use std::ops::Range;
+
+/// Drawn on top of spaces
+pub const SPACE_MARKER: &str = "\u{b7}";
+/// Drawn at the start of tabs
+pub const TAB_MARKER: &str = "\u{2192}";
+/// Drawn after the last char of a line
+pub const NEWLINE_MARKER: &str = "\u{ac}";
+
+/// Which whitespace should be made visible, see `markers`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WhitespaceOptions {
+    pub spaces: bool,
+    pub tabs: bool,
+    pub newlines: bool,
+    pub mode: WhitespaceMode,
+    /// Trailing spaces are drawn regardless of `spaces` and `mode`
+    pub trailing_spaces: bool,
+}
+
+impl WhitespaceOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            spaces: settings.draw_spaces,
+            tabs: settings.draw_tabs,
+            newlines: settings.draw_newlines,
+            mode: settings.whitespace_mode,
+            trailing_spaces: settings.trailing_spaces,
+        }
+    }
+
+    /// Whether any whitespace has to be drawn at all
+    pub fn is_enabled(&self) -> bool {
+        self.spaces || self.tabs || self.newlines || self.trailing_spaces
+    }
+}
+
+/// Returns the byte index and marker of every whitespace char in `text` that should be drawn.
+/// `selections` are the byte ranges of `text` which are selected.
+pub fn markers(
+    text: &str,
+    selections: &[Range<usize>],
+    options: &WhitespaceOptions,
+) -> Vec<(usize, &'static str)> {
+    let content = text.trim_end_matches(|c| c == '\n' || c == '\r');
+    let trailing_start = content.trim_end_matches(|c| c == ' ' || c == '\t').len();
+    let is_selected = |ix: usize| selections.iter().any(|s| s.contains(&ix));
+    let in_mode = |ix: usize| match options.mode {
+        WhitespaceMode::All => true,
+        WhitespaceMode::Selection => is_selected(ix),
+        WhitespaceMode::Trailing => ix >= trailing_start,
+    };
+
+    let mut markers = Vec::new();
+    for (ix, c) in content.char_indices() {
+        let marker = match c {
+            ' ' if (options.spaces && in_mode(ix))
+                || (options.trailing_spaces && ix >= trailing_start) =>
+            {
+                SPACE_MARKER
+            }
+            '\t' if options.tabs && in_mode(ix) => TAB_MARKER,
+            _ => continue,
+        };
+        markers.push((ix, marker));
+    }
+
+    let has_newline = content.len() < text.len();
+    // Trailing mode has no use for newline markers, every newline is at the end of its line
+    let newline_in_mode = match options.mode {
+        WhitespaceMode::All => true,
+        WhitespaceMode::Selection => is_selected(content.len()),
+        WhitespaceMode::Trailing => false,
+    };
+    if options.newlines && has_newline && newline_in_mode {
+        markers.push((content.len(), NEWLINE_MARKER));
+    }
+
+    markers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        let text = "\tfoo bar  \n";
+        let mut options = WhitespaceOptions {
+            spaces: true,
+            tabs: true,
+            newlines: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            markers(text, &[], &options),
+            vec![
+                (0, TAB_MARKER),
+                (4, SPACE_MARKER),
+                (8, SPACE_MARKER),
+                (9, SPACE_MARKER),
+                (10, NEWLINE_MARKER),
+            ]
+        );
+
+        options.mode = WhitespaceMode::Trailing;
+        assert_eq!(
+            markers(text, &[], &options),
+            vec![(8, SPACE_MARKER), (9, SPACE_MARKER)]
+        );
+
+        options.mode = WhitespaceMode::Selection;
+        assert_eq!(markers(text, &[], &options), vec![]);
+        assert_eq!(
+            markers(text, &[3..11], &options),
+            vec![
+                (4, SPACE_MARKER),
+                (8, SPACE_MARKER),
+                (9, SPACE_MARKER),
+                (10, NEWLINE_MARKER),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_spaces() {
+        let options = WhitespaceOptions {
+            trailing_spaces: true,
+            mode: WhitespaceMode::Selection,
+            ..Default::default()
+        };
+        assert_eq!(
+            markers("a b \t \n", &[], &options),
+            vec![(3, SPACE_MARKER), (5, SPACE_MARKER)]
+        );
+    }
+}
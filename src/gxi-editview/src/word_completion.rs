@@ -0,0 +1,147 @@
+//! Completes words with the ones already in the open documents, like Vim's Ctrl+N. Words near the
+//! cursor come first, then the ones used often. This works for any language, since it doesn't
+//! need to know anything about it.
+
+use crate::completion::Completion;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Shorter words aren't worth completing
+const MIN_WORD_LEN: usize = 3;
+/// How many completions are offered at most
+const MAX_COMPLETIONS: usize = 50;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the start of the word in front of byte `col` of `text`
+pub fn word_before(text: &str, col: usize) -> Option<&str> {
+    let before = text.get(..col)?;
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()?
+        .0;
+    Some(&before[start..])
+}
+
+/// Returns the words of `text` which are long enough to be completed
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c| !is_word_char(c))
+        .filter(|word| word.chars().count() >= MIN_WORD_LEN)
+}
+
+/// Counts how often each word is in `text`
+pub fn count_words(text: &str) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+    for word in words(text) {
+        *counts.entry(word.to_string()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The words of the documents which aren't the one being edited, by view id
+#[derive(Debug, Default)]
+pub struct BufferWords {
+    words: HashMap<String, HashMap<String, u32>>,
+}
+
+impl BufferWords {
+    pub fn set(&mut self, view_id: &str, words: HashMap<String, u32>) {
+        self.words.insert(view_id.to_string(), words);
+    }
+
+    pub fn remove_view(&mut self, view_id: &str) {
+        self.words.remove(view_id);
+    }
+
+    /// Adds up how often each word is in the documents other than the one of `view_id`
+    fn counts_except(&self, view_id: &str) -> HashMap<&str, u32> {
+        let mut counts = HashMap::new();
+        for (_, words) in self.words.iter().filter(|(id, _)| *id != view_id) {
+            for (word, count) in words {
+                *counts.entry(word.as_str()).or_insert(0) += count;
+            }
+        }
+        counts
+    }
+}
+
+/// Returns the completions of `prefix`. `lines` are the lines of the document (with their line
+/// number) the cursor is on line `cursor_line` of, `others` are the words of the other documents.
+pub fn complete(
+    prefix: &str,
+    lines: &[(u64, &str)],
+    cursor_line: u64,
+    others: &BufferWords,
+    view_id: &str,
+) -> Vec<Completion> {
+    // How far the word is from the cursor at the closest and how often it's used
+    let mut candidates: HashMap<&str, (u64, u32)> = HashMap::new();
+    for (line, text) in lines {
+        let distance = if *line > cursor_line {
+            line - cursor_line
+        } else {
+            cursor_line - line
+        };
+        for word in words(text).filter(|w| w.starts_with(prefix) && *w != prefix) {
+            let candidate = candidates.entry(word).or_insert((distance, 0));
+            candidate.0 = candidate.0.min(distance);
+            candidate.1 += 1;
+        }
+    }
+    for (word, count) in others.counts_except(view_id) {
+        if word.starts_with(prefix) && word != prefix {
+            let candidate = candidates.entry(word).or_insert((u64::max_value(), 0));
+            candidate.1 += count;
+        }
+    }
+
+    let mut candidates: Vec<(&str, (u64, u32))> = candidates.into_iter().collect();
+    candidates.sort_by_key(|(word, (distance, count))| (*distance, Reverse(*count), *word));
+    candidates
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .map(|(word, _)| Completion {
+            label: word.to_string(),
+            insert: word[prefix.len()..].to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prefix() {
+        assert_eq!(word_before("let foo_b", 9), Some("foo_b"));
+        assert_eq!(word_before("foo(", 4), None);
+        assert_eq!(word_before("größe", 4), Some("grö"));
+    }
+
+    #[test]
+    fn completions() {
+        let lines = [(0, "value valid"), (5, "validate(va"), (6, "value value")];
+        let mut others = BufferWords::default();
+        let mut words = count_words("vanilla vanilla variable");
+        words.insert("validate".to_string(), 1);
+        others.set("view-id-2", words);
+        others.set("view-id-1", count_words("vast"));
+
+        let labels: Vec<String> = complete("va", &lines, 5, &others, "view-id-1")
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(
+            labels,
+            vec!["validate", "value", "valid", "vanilla", "variable"]
+        );
+        assert_eq!(
+            complete("vari", &lines, 5, &others, "view-id-1")[0].insert,
+            "able"
+        );
+    }
+}
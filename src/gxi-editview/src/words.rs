@@ -0,0 +1,87 @@
+//! Word boundaries according to Unicode (UAX #29), so selecting and moving by words works for
+//! CJK text, contractions like "don't" and characters with combining marks, not just ASCII
+//! letters. All indices are byte offsets into the line.
+
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Whether `segment` is a word, rather than whitespace or punctuation
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+/// The word (or run of whitespace or punctuation) at `index`, which double clicking there selects
+pub fn word_at(line: &str, index: usize) -> Range<usize> {
+    let segments: Vec<(usize, &str)> = line.split_word_bound_indices().collect();
+    // At the end of a word we select the word rather than what follows it
+    let at = |(start, segment): &(usize, &str)| *start <= index && index < start + segment.len();
+    let found = segments
+        .iter()
+        .position(at)
+        .map(|ix| {
+            let (start, segment) = segments[ix];
+            if !is_word(segment) && start == index && ix > 0 && is_word(segments[ix - 1].1) {
+                ix - 1
+            } else {
+                ix
+            }
+        })
+        .or_else(|| segments.len().checked_sub(1));
+    match found {
+        Some(ix) => {
+            let (start, segment) = segments[ix];
+            start..start + segment.len()
+        }
+        None => index..index,
+    }
+}
+
+/// Where moving a word to the right from `index` goes: the end of the next word. None if there's
+/// no word after `index` in the line.
+pub fn next_word_end(line: &str, index: usize) -> Option<usize> {
+    line.split_word_bound_indices()
+        .map(|(start, segment)| (start + segment.len(), segment))
+        .find(|(end, segment)| *end > index && is_word(segment))
+        .map(|(end, _)| end)
+}
+
+/// Where moving a word to the left from `index` goes: the start of the previous word. None if
+/// there's no word before `index` in the line.
+pub fn prev_word_start(line: &str, index: usize) -> Option<usize> {
+    line.split_word_bound_indices()
+        .filter(|(start, segment)| *start < index && is_word(segment))
+        .map(|(start, _)| start)
+        .last()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words_at() {
+        let line = "let don't = 漢字かな;";
+        assert_eq!(&line[word_at(line, 5)], "don't");
+        // The end of a word belongs to it
+        assert_eq!(&line[word_at(line, 9)], "don't");
+        assert_eq!(&line[word_at(line, 10)], "=");
+        assert_eq!(&line[word_at(line, 12)], "漢");
+        assert_eq!(&line[word_at(line, 100)], ";");
+        assert_eq!(word_at("", 0), 0..0);
+        // Combining characters are part of their word
+        let line = "cafe\u{301} au lait";
+        assert_eq!(&line[word_at(line, 1)], "cafe\u{301}");
+    }
+
+    #[test]
+    fn moving() {
+        let line = "foo (bar, don't)";
+        assert_eq!(next_word_end(line, 0), Some(3));
+        assert_eq!(next_word_end(line, 3), Some(8));
+        assert_eq!(next_word_end(line, 8), Some(15));
+        assert_eq!(next_word_end(line, 15), None);
+        assert_eq!(prev_word_start(line, 16), Some(10));
+        assert_eq!(prev_word_start(line, 10), Some(5));
+        assert_eq!(prev_word_start(line, 0), None);
+    }
+}
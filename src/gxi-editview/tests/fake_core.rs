@@ -0,0 +1,112 @@
+//! Drives an EditView with messages from `FakeCore` instead of xi-core. It needs a display and
+//! `glib-compile-schemas` for gxi's GSchema, without them the test is skipped.
+
+use glib::MainContext;
+use gtk::*;
+use gxi_config_storage::GSchema;
+use gxi_editview::{EditView, MainState, Settings};
+use gxi_peer::{Core, CoreMsg, FakeCore, SharedQueue};
+use serde_json::json;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const APP_ID: &str = "com.github.Cogitri.gxi";
+const VIEW_ID: &str = "view-id-1";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Compiles gxi's GSchema into a temporary directory and has GIO use it, keeping the settings in
+/// memory. Returns false if it couldn't be compiled.
+fn use_gschema() -> bool {
+    let dir = env::temp_dir().join(format!("gxi-editview-schemas-{}", std::process::id()));
+    let schema = format!("{}.gschema.xml", APP_ID);
+    let source = concat!(env!("CARGO_MANIFEST_DIR"), "/../../data/").to_string() + &schema;
+    let compiled = fs::create_dir_all(&dir)
+        .and_then(|_| fs::copy(&source, dir.join(&schema)))
+        .and_then(|_| Command::new("glib-compile-schemas").arg(&dir).status())
+        .map_or(false, |status| status.success());
+    env::set_var("GSETTINGS_SCHEMA_DIR", &dir);
+    env::set_var("GSETTINGS_BACKEND", "memory");
+    compiled
+}
+
+/// Waits for the next message xi-core (i.e. `FakeCore`) has sent us
+fn next_msg(shared_queue: &SharedQueue) -> Option<CoreMsg> {
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        // Bind the result, so the queue isn't locked while sleeping
+        let msg = shared_queue.queue_rx.lock().pop();
+        match msg {
+            Ok(msg) => return Some(msg),
+            Err(_) => thread::sleep(Duration::from_millis(5)),
+        }
+    }
+    None
+}
+
+#[test]
+fn handles_updates() {
+    if !use_gschema() || gtk::init().is_err() {
+        eprintln!("Skipping, it needs glib-compile-schemas and a display");
+        return;
+    }
+    let shared_queue = SharedQueue::new();
+    let (err_tx, _err_rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+    let (xi_peer, xi_rx, fake_core) = FakeCore::new();
+    let core = Core::new(xi_peer, xi_rx, err_tx, shared_queue.clone());
+
+    let settings = Settings::from_gschema(GSchema::new(APP_ID));
+    let main_state = Rc::new(RefCell::new(MainState::new(settings)));
+    let application = Application::new(None, ApplicationFlags::empty()).unwrap();
+    let window = ApplicationWindow::new(&application);
+    let edit_view = EditView::new(
+        &main_state,
+        &core,
+        &MenuButton::new(),
+        None,
+        VIEW_ID.to_string(),
+        &window,
+    );
+
+    fake_core.notify(
+        "update",
+        json!({
+            "view_id": VIEW_ID,
+            "update": {
+                "ops": [{
+                    "op": "ins",
+                    "n": 2,
+                    "lines": [
+                        { "text": "hello\n", "ln": 1, "cursor": [5] },
+                        { "text": "world", "ln": 2 },
+                    ],
+                }],
+                "pristine": true,
+            },
+        }),
+    );
+    match next_msg(&shared_queue) {
+        Some(CoreMsg::Notification { method, params, .. }) => {
+            assert_eq!(method, "update");
+            edit_view.borrow_mut().update(&params);
+        }
+        msg => panic!("Expected an update from xi-core, got {:?}", msg),
+    }
+    assert_eq!(
+        edit_view
+            .borrow()
+            .document_text()
+            .map(|text| text.to_string()),
+        Some("hello\nworld".to_string())
+    );
+
+    fake_core.clear();
+    edit_view.borrow().insert_text("!");
+    let insert = fake_core.wait_for("insert", TIMEOUT).unwrap();
+    assert_eq!(insert["params"]["view_id"], VIEW_ID);
+    assert_eq!(insert["params"]["params"]["chars"], "!");
+}
@@ -1,6 +1,8 @@
 use log::{error, trace};
 use serde_json::Value;
 use std::cmp::min;
+use std::mem::size_of;
+use std::ops::Range;
 
 #[derive(Copy, Clone, Debug)]
 pub struct StyleSpan {
@@ -77,6 +79,14 @@ impl Line {
     pub fn line_num(&self) -> &Option<u64> {
         &self.line_num
     }
+
+    /// Approximate amount of memory the line takes up, in bytes
+    fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.text.capacity()
+            + self.cursor.capacity() * size_of::<u64>()
+            + self.styles.capacity() * size_of::<StyleSpan>()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -118,6 +128,12 @@ impl LineCache {
             None
         }
     }
+    /// Returns the text of the whole document, or None if xi-editor hasn't sent us all lines (yet)
+    pub fn text(&self) -> Option<String> {
+        (0..self.height())
+            .map(|n| self.get_line(n).map(|line| line.text.as_str()))
+            .collect()
+    }
     pub fn get_missing(&self, first: u64, last: u64) -> Vec<(u64, u64)> {
         let mut ret = Vec::new();
         let last = min(last, self.height());
@@ -249,6 +265,72 @@ impl LineCache {
         //debug!("lc after update {:?}", self);
     }
 
+    /// Approximate amount of memory the cached lines take up, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| line.as_ref().map_or(0, Line::memory_usage) + size_of::<Option<Line>>())
+            .sum()
+    }
+
+    /// Drops cached lines outside of the lines in `keep` (e.g. the visible ones) until the cache
+    /// takes up at most `budget` bytes, dropping the lines furthest away from the kept ones first.
+    /// Lines with cursors are always kept. Dropped lines are missing afterwards, so they have to be
+    /// requested from xi-editor again once they're needed. Returns the amount of dropped lines.
+    pub fn evict(&mut self, keep: &[Range<u64>], budget: usize) -> usize {
+        let mut usage = self.memory_usage();
+        if usage <= budget {
+            return 0;
+        }
+
+        let distance = |line: u64| {
+            keep.iter()
+                .map(|range| {
+                    if line < range.start {
+                        range.start - line
+                    } else if line >= range.end {
+                        line + 1 - range.end
+                    } else {
+                        0
+                    }
+                })
+                .min()
+                .unwrap_or(u64::max_value())
+        };
+        let mut candidates: Vec<(u64, usize)> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(false, |l| l.cursor.is_empty()))
+            .map(|(ix, _)| (distance(self.n_invalid_before + ix as u64), ix))
+            .filter(|(distance, _)| *distance > 0)
+            .collect();
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut evicted = 0;
+        for (_, ix) in candidates {
+            if usage <= budget {
+                break;
+            }
+            if let Some(line) = self.lines[ix].take() {
+                usage -= line.memory_usage();
+                evicted += 1;
+            }
+        }
+
+        // Missing lines at the start and end don't need to be stored
+        let leading = self.lines.iter().take_while(|line| line.is_none()).count();
+        self.lines.drain(..leading);
+        self.n_invalid_before += leading as u64;
+        while let Some(None) = self.lines.last() {
+            self.lines.pop();
+            self.n_invalid_after += 1;
+        }
+
+        trace!("evicted {} lines, {} bytes left", evicted, usage);
+        evicted
+    }
+
     /// Returns true if this Linecache only contains one line, which doesn't contain any text
     pub fn is_empty(&self) -> bool {
         if self.height() == 1 {
@@ -335,6 +417,50 @@ mod test {
         println!("LINE CACHE: {:?}", linecache);
     }
 
+    #[test]
+    fn test_evict() {
+        let mut linecache = LineCache::new();
+        let lines: Vec<_> = (0..10)
+            .map(|i| {
+                if i == 1 {
+                    json!({"text": format!("{}\n", i), "cursor": [0]})
+                } else {
+                    json!({"text": format!("{}\n", i)})
+                }
+            })
+            .collect();
+        linecache.apply_update(&json!({
+            "ops": [{"op":"ins", "n": 10, "lines": lines}]
+        }));
+
+        let usage = linecache.memory_usage();
+        assert_eq!(linecache.evict(&[4..6], usage), 0);
+        // Dropping everything but the kept lines and the one with the cursor
+        assert_eq!(linecache.evict(&[4..6], 0), 7);
+        assert_eq!(linecache.height(), 10);
+        assert!(linecache.get_line(0).is_none());
+        assert!(linecache.get_line(1).is_some());
+        assert!(linecache.get_line(3).is_none());
+        assert_eq!(linecache.get_line(5).unwrap().text(), "5\n");
+        assert!(linecache.get_line(6).is_none());
+        assert_eq!(linecache.n_invalid_before, 1);
+        assert_eq!(linecache.n_invalid_after, 4);
+        assert_eq!(linecache.get_missing(0, 10), vec![(0, 1), (2, 4), (6, 10)]);
+
+        // The lines furthest away go first
+        let lines: Vec<_> = (0..10)
+            .map(|i| json!({ "text": format!("{}\n", i) }))
+            .collect();
+        let mut linecache = LineCache::new();
+        linecache.apply_update(&json!({
+            "ops": [{"op":"ins", "n": 10, "lines": lines}]
+        }));
+        let budget = linecache.memory_usage() - 1;
+        assert_eq!(linecache.evict(&[0..2], budget), 1);
+        assert!(linecache.get_line(9).is_none());
+        assert!(linecache.get_line(8).is_some());
+    }
+
     #[test]
     fn test_empty() {
         let mut linecache = LineCache::new();
@@ -0,0 +1,284 @@
+//! A stand-in for xi-core, to test how gxi handles messages without spawning xi-core. It takes
+//! the place of `XiPeer::new()`: what gxi sends is recorded instead of edited, requests are
+//! answered with scripted results and notifications (e.g. a recorded session of xi-core, one JSON
+//! message per line) are sent to gxi as if xi-core had sent them. It's only built for tests and
+//! with the `test-util` feature, which other crates enable in their `dev-dependencies`.
+//!
+//! ```ignore
+//! let (xi_peer, xi_rx, fake_core) = FakeCore::new();
+//! let core = Core::new(xi_peer, xi_rx, err_tx, shared_queue.clone());
+//! fake_core.respond_with("new_view", json!("view-id-1"));
+//! fake_core.replay(include_str!("update.jsonl")).unwrap();
+//! ```
+
+use crate::xi_thread::XiPeer;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use gettextrs::gettext;
+use log::{debug, error};
+use parking_lot::Mutex;
+use serde_json::{self, json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often `wait_for` looks at the recorded messages again
+const WAIT_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The method of a message gxi has sent. Edits are sent as `edit` notifications (or requests),
+/// their own method (e.g. `insert`) is returned for them.
+pub fn method_of(msg: &Value) -> Option<&str> {
+    match msg["method"].as_str()? {
+        "edit" => msg["params"]["method"].as_str(),
+        method => Some(method),
+    }
+}
+
+pub struct FakeCore {
+    /// Sends messages to gxi as if they came from xi-core
+    to_gxi: Sender<Value>,
+    /// Everything gxi has sent, in order
+    recorded: Arc<Mutex<Vec<Value>>>,
+    /// The results requests are answered with, by method. Requests without one are answered
+    /// with `null`.
+    results: Arc<Mutex<HashMap<String, Value>>>,
+}
+
+impl FakeCore {
+    /// Returns the peer and receiver to pass to `Core::new()` and the fake xi-core they're
+    /// connected to
+    pub fn new() -> (XiPeer, Receiver<Value>, FakeCore) {
+        let (to_core_tx, to_core_rx) = unbounded::<String>();
+        let (to_gxi_tx, to_gxi_rx) = unbounded();
+        let fake_core = FakeCore {
+            to_gxi: to_gxi_tx.clone(),
+            recorded: Default::default(),
+            results: Default::default(),
+        };
+
+        let recorded = fake_core.recorded.clone();
+        let results = fake_core.results.clone();
+        thread::spawn(move || {
+            for msg in to_core_rx {
+                let msg: Value = match serde_json::from_str(&msg) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        error!("{} '{}': {}", gettext("Invalid message for xi"), msg, e);
+                        continue;
+                    }
+                };
+                debug!("FakeCore <-- {}", msg);
+                let reply = msg["id"].as_u64().map(|id| {
+                    let result = method_of(&msg)
+                        .and_then(|method| results.lock().get(method).cloned())
+                        .unwrap_or(Value::Null);
+                    json!({ "id": id, "result": result })
+                });
+                // Recorded before answering, so the request is there once gxi has the result
+                recorded.lock().push(msg);
+                if let Some(reply) = reply {
+                    if to_gxi_tx.send(reply).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        (XiPeer::from_sender(to_core_tx), to_gxi_rx, fake_core)
+    }
+
+    /// Answers requests with the method `method` (e.g. `new_view` or `copy`) with `result`
+    pub fn respond_with(&self, method: &str, result: Value) {
+        self.results.lock().insert(method.to_string(), result);
+    }
+
+    /// Sends the notification `method` to gxi
+    pub fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "method": method, "params": params }));
+    }
+
+    /// Sends `msg` to gxi as it is, e.g. a notification or an error
+    pub fn send(&self, msg: Value) {
+        debug!("FakeCore --> {}", msg);
+        self.to_gxi
+            .send(msg)
+            .unwrap_or_else(|e| error!("{}: {}", gettext("Failed to send msg to gxi"), e));
+    }
+
+    /// Sends the messages of `script` to gxi in order, one JSON message per line. Empty lines and
+    /// lines starting with `//` are skipped.
+    pub fn replay(&self, script: &str) -> Result<(), serde_json::Error> {
+        let msgs = script
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        for msg in msgs {
+            self.send(msg);
+        }
+        Ok(())
+    }
+
+    /// Everything gxi has sent so far, in order
+    pub fn recorded(&self) -> Vec<Value> {
+        self.recorded.lock().clone()
+    }
+
+    /// The methods of everything gxi has sent so far, see `method_of`
+    pub fn recorded_methods(&self) -> Vec<String> {
+        self.recorded
+            .lock()
+            .iter()
+            .filter_map(|msg| method_of(msg).map(str::to_string))
+            .collect()
+    }
+
+    /// Forgets what gxi has sent so far
+    pub fn clear(&self) {
+        self.recorded.lock().clear();
+    }
+
+    /// Waits up to `timeout` for gxi to send a message with the method `method` and returns the
+    /// first one. Messages are recorded on another thread, so they may not be there right after
+    /// gxi has sent them.
+    pub fn wait_for(&self, method: &str, timeout: Duration) -> Option<Value> {
+        let start = Instant::now();
+        loop {
+            let found = self
+                .recorded
+                .lock()
+                .iter()
+                .find(|msg| method_of(msg) == Some(method))
+                .cloned();
+            if found.is_some() || start.elapsed() > timeout {
+                return found;
+            }
+            thread::sleep(WAIT_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::ErrorMsg;
+    use crate::rpc::Core;
+    use crate::shared_queue::{CoreMsg, SharedQueue};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn core() -> (Core, FakeCore, SharedQueue, glib::Receiver<ErrorMsg>) {
+        let shared_queue = SharedQueue::new();
+        let (err_tx, err_rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let (xi_peer, xi_rx, fake_core) = FakeCore::new();
+        let core = Core::new(xi_peer, xi_rx, err_tx, shared_queue.clone());
+        (core, fake_core, shared_queue, err_rx)
+    }
+
+    #[test]
+    fn records_rpcs() {
+        let (core, fake_core, _shared_queue, _err_rx) = core();
+        core.insert("view-id-1", "foo");
+        core.save("view-id-1", "/tmp/foo");
+
+        let save = fake_core.wait_for("save", TIMEOUT).unwrap();
+        assert_eq!(save["params"]["file_path"], "/tmp/foo");
+        assert_eq!(fake_core.recorded_methods(), vec!["insert", "save"]);
+        fake_core.clear();
+        assert!(fake_core.recorded().is_empty());
+    }
+
+    /// Waits until gxi has sent `n` messages and returns their methods
+    fn wait_for_methods(fake_core: &FakeCore, n: usize) -> Vec<String> {
+        let start = Instant::now();
+        while fake_core.recorded().len() < n && start.elapsed() < TIMEOUT {
+            thread::sleep(WAIT_INTERVAL);
+        }
+        fake_core.recorded_methods()
+    }
+
+    /// How many undo groups xi-core makes of the commands `methods`, like its `Editor` does: an
+    /// insertion is added to the current group if the edit type before it was an insertion too,
+    /// other commands set the edit type to "other" once the next command comes in.
+    fn undo_groups(methods: &[String]) -> usize {
+        let mut groups = 0;
+        let (mut last_insert, mut this_insert) = (false, false);
+        for method in methods {
+            if method == "insert" {
+                this_insert = true;
+                if groups == 0 || !last_insert {
+                    groups += 1;
+                }
+                last_insert = this_insert;
+            } else {
+                last_insert = this_insert;
+                this_insert = false;
+            }
+        }
+        groups
+    }
+
+    #[test]
+    fn ends_undo_groups() {
+        let (core, fake_core, _shared_queue, _err_rx) = core();
+        core.insert("view-id-1", "foo");
+        core.insert("view-id-1", "bar");
+        assert_eq!(undo_groups(&wait_for_methods(&fake_core, 2)), 1);
+        fake_core.clear();
+
+        // A single command in between isn't enough
+        core.insert("view-id-1", "foo");
+        core.scroll("view-id-1", 0, 10);
+        core.insert("view-id-1", "bar");
+        assert_eq!(undo_groups(&wait_for_methods(&fake_core, 3)), 1);
+        fake_core.clear();
+
+        for &at_end in &[false, true] {
+            core.insert("view-id-1", "foo");
+            core.end_undo_group("view-id-1", at_end);
+            core.insert("view-id-1", "bar");
+            let methods = wait_for_methods(&fake_core, 4);
+            assert_eq!(methods.len(), 4);
+            assert_eq!(undo_groups(&methods), 2);
+            fake_core.clear();
+        }
+    }
+
+    #[test]
+    fn answers_requests() {
+        let (core, fake_core, _shared_queue, _err_rx) = core();
+        fake_core.respond_with("copy", json!("selected text"));
+        let (tx, rx) = std::sync::mpsc::channel();
+        core.send_request(
+            "edit",
+            &json!({ "view_id": "view-id-1", "method": "copy", "params": {} }),
+            move |result| tx.send(result.clone()).unwrap(),
+        );
+        assert_eq!(rx.recv_timeout(TIMEOUT).unwrap(), json!("selected text"));
+    }
+
+    #[test]
+    fn replays_notifications() {
+        let (_core, fake_core, shared_queue, _err_rx) = core();
+        let script = r#"
+            // A theme and the first lines of a document
+            {"method": "theme_changed", "params": {"name": "InspiredGitHub", "theme": {}}}
+
+            {"method": "update", "params": {"view_id": "view-id-1", "update": {"ops": []}}}
+        "#;
+        fake_core.replay(script).unwrap();
+        assert!(fake_core.replay("{not json").is_err());
+
+        let mut methods = Vec::new();
+        let start = Instant::now();
+        while methods.len() < 2 && start.elapsed() < TIMEOUT {
+            let msg = shared_queue.queue_rx.lock().pop();
+            match msg {
+                Ok(CoreMsg::Notification { method, .. }) => methods.push(method),
+                _ => thread::sleep(WAIT_INTERVAL),
+            }
+        }
+        assert_eq!(methods, vec!["theme_changed", "update"]);
+    }
+}
@@ -2,11 +2,17 @@
 extern crate enclose;
 
 pub mod errors;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fake_core;
 pub mod rpc;
+pub mod sandbox;
 pub mod shared_queue;
 pub mod xi_thread;
 
 pub use crate::errors::ErrorMsg;
+#[cfg(any(test, feature = "test-util"))]
+pub use crate::fake_core::FakeCore;
 pub use crate::rpc::Core;
+pub use crate::sandbox::Sandbox;
 pub use crate::shared_queue::{CoreMsg, SharedQueue};
 pub use crate::xi_thread::XiPeer;
@@ -233,6 +233,12 @@ impl Core {
     pub fn outdent(&self, view_id: &str) {
         self.send_edit_cmd(view_id, "outdent", &json!({}))
     }
+    pub fn duplicate_line(&self, view_id: &str) {
+        self.send_edit_cmd(view_id, "duplicate_line", &json!({}))
+    }
+    pub fn transpose(&self, view_id: &str) {
+        self.send_edit_cmd(view_id, "transpose", &json!({}))
+    }
     pub fn move_up(&self, view_id: &str) {
         self.send_edit_cmd(view_id, "move_up", &json!({}))
     }
@@ -321,9 +327,21 @@ impl Core {
     pub fn page_down_and_modify_selection(&self, view_id: &str) {
         self.send_edit_cmd(view_id, "page_down_and_modify_selection", &json!({}))
     }
+    /// Adds a cursor to the line above every selection, used for column editing
+    pub fn add_selection_above(&self, view_id: &str) {
+        self.send_edit_cmd(view_id, "add_selection_above", &json!({}))
+    }
+    /// Adds a cursor to the line below every selection, used for column editing
+    pub fn add_selection_below(&self, view_id: &str) {
+        self.send_edit_cmd(view_id, "add_selection_below", &json!({}))
+    }
     pub fn select_all(&self, view_id: &str) {
         self.send_edit_cmd(view_id, "select_all", &json!({}))
     }
+    /// Moves the cursor to the (zero based) `line`
+    pub fn goto_line(&self, view_id: &str, line: u64) {
+        self.send_edit_cmd(view_id, "goto_line", &json!({ "line": line }))
+    }
 
     /// moves the cursor to a point (click)
     pub fn gesture_point_select(&self, view_id: &str, line: u64, col: u64) {
@@ -358,6 +376,23 @@ impl Core {
             }),
         )
     }
+    /// adds a new cursor at a point, which following drags turn into a selection
+    pub fn gesture_add_point_select(&self, view_id: &str, line: u64, col: u64) {
+        self.send_edit_cmd(
+            view_id,
+            "gesture",
+            &json!({
+                "line": line,
+                "col": col,
+                "ty": {
+                    "select": {
+                        "granularity": "point",
+                        "multi": true,
+                    },
+                },
+            }),
+        )
+    }
     /// modifies the selection to include a point (shift+click)
     pub fn gesture_range_select(&self, view_id: &str, line: u64, col: u64) {
         self.send_edit_cmd(
@@ -419,6 +454,21 @@ impl Core {
         )
     }
 
+    /// Makes xi-editor start a new undo group with the next edit. It adds an edit to the current
+    /// group if the command before it was an edit of the same kind, and commands which change the
+    /// selection count in between once there have been two of them. So each selection is
+    /// extended by a character and shrunk back again, which leaves it as it was. At the end of
+    /// the document that's done the other way around (`at_end`).
+    pub fn end_undo_group(&self, view_id: &str, at_end: bool) {
+        if at_end {
+            self.move_left_and_modify_selection(view_id);
+            self.move_right_and_modify_selection(view_id);
+        } else {
+            self.move_right_and_modify_selection(view_id);
+            self.move_left_and_modify_selection(view_id);
+        }
+    }
+
     /// Notifies the back-end of the visible scroll region, defined as the first and last
     /// (non-inclusive) formatted lines. The visible scroll region is used to compute movement
     /// distance for page up and page down commands, and also controls the size of the fragment
@@ -427,6 +477,12 @@ impl Core {
         self.send_edit_cmd(view_id, "scroll", &json!([first, last]))
     }
 
+    /// Asks xi-editor to send the lines `first` to `last` again, e.g. because we dropped them from
+    /// the line cache
+    pub fn request_lines(&self, view_id: &str, first: u64, last: u64) {
+        self.send_edit_cmd(view_id, "request_lines", &json!([first, last]))
+    }
+
     pub fn drag(&self, view_id: &str, line: u64, col: u64) {
         self.send_edit_cmd(
             view_id,
@@ -526,6 +582,18 @@ impl Core {
             }),
         )
     }
+    /// Selects the next match of the search query in addition to the current selections
+    pub fn find_next_add_selection(&self, view_id: &str, wrap_around: bool) {
+        self.send_edit_cmd(
+            view_id,
+            "find_next",
+            &json!({
+                "wrap_around": wrap_around,
+                "allow_same": false,
+                "modify_selection": "add",
+            }),
+        )
+    }
     pub fn find_previous(&self, view_id: &str, wrap_around: Option<bool>) {
         self.send_edit_cmd(
             view_id,
@@ -536,6 +604,18 @@ impl Core {
         )
     }
 
+    /// Makes the current selection, or the word at the cursor if nothing is selected, the search
+    /// query
+    pub fn selection_for_find(&self, view_id: &str, case_sensitive: bool) {
+        self.send_edit_cmd(
+            view_id,
+            "selection_for_find",
+            &json!({
+                "case_sensitive": case_sensitive,
+            }),
+        )
+    }
+
     pub fn highlight_find(&self, view_id: &str, visible: bool) {
         self.send_edit_cmd(
             view_id,
@@ -572,6 +652,20 @@ impl Core {
         );
     }
 
+    pub fn start_plugin(&self, view_id: &str, plugin_name: &str) {
+        self.send_notification(
+            "plugin",
+            &json!({ "command": "start", "view_id": view_id, "plugin_name": plugin_name }),
+        );
+    }
+
+    pub fn stop_plugin(&self, view_id: &str, plugin_name: &str) {
+        self.send_notification(
+            "plugin",
+            &json!({ "command": "stop", "view_id": view_id, "plugin_name": plugin_name }),
+        );
+    }
+
     //TODO: Use something other than a Value for `changes`
     pub fn modify_user_config(&self, domain: &str, changes: &Value) {
         self.send_notification(
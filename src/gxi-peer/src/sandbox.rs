@@ -0,0 +1,81 @@
+//! Detects whether gxi runs in a sandbox and how to spawn programs from there. Inside Flatpak gxi
+//! only sees its runtime and its own bundle, so the user's tools (e.g. diff tools or
+//! speech-dispatcher) have to be started on the host via `flatpak-spawn --host`.
+
+use crate::errors::ErrorMsg;
+use gettextrs::gettext;
+use log::debug;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where Flatpak bundles install xi plugins to
+const FLATPAK_PLUGIN_DIR: &str = "/app/libexec/gxi/plugins";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sandbox {
+    /// gxi runs directly on the host
+    None,
+    Flatpak,
+}
+
+impl Sandbox {
+    pub fn detect() -> Self {
+        // Flatpak mounts this into every sandbox
+        if Path::new("/.flatpak-info").exists() {
+            Sandbox::Flatpak
+        } else {
+            Sandbox::None
+        }
+    }
+
+    /// Creates a `Command` which runs `program` on the host
+    pub fn host_command(self, program: &str) -> Command {
+        match self {
+            Sandbox::None => Command::new(program),
+            Sandbox::Flatpak => {
+                debug!(
+                    "{} '{}'",
+                    gettext("Spawning on the host via flatpak-spawn"),
+                    program
+                );
+                let mut command = Command::new("flatpak-spawn");
+                command.arg("--host").arg(program);
+                command
+            }
+        }
+    }
+
+    /// Returns the directory xi-core starts plugins from, `default` unless sandboxed. xi-core
+    /// can't start plugins on the host, so inside Flatpak they have to be part of the bundle; if
+    /// they're missing there the bundle is broken, which is returned as error.
+    pub fn plugin_dir(self, default: &str) -> Result<PathBuf, ErrorMsg> {
+        match self {
+            Sandbox::None => Ok(PathBuf::from(default)),
+            Sandbox::Flatpak => {
+                let dir = PathBuf::from(FLATPAK_PLUGIN_DIR);
+                if dir.is_dir() {
+                    Ok(dir)
+                } else {
+                    Err(ErrorMsg {
+                        msg: format!(
+                            "{} '{}'. {}",
+                            gettext("Couldn't find xi's plugins in the Flatpak sandbox at"),
+                            FLATPAK_PLUGIN_DIR,
+                            gettext("Syntax highlighting won't be available.")
+                        ),
+                        fatal: false,
+                    })
+                }
+            }
+        }
+    }
+
+    /// A directory for temporary files other programs can read. Inside Flatpak `/tmp` is private
+    /// to the sandbox, but the cache directory is shared with the host under the same path.
+    pub fn shared_tmp_dir(self) -> PathBuf {
+        match self {
+            Sandbox::None => glib::get_tmp_dir().unwrap_or_else(std::env::temp_dir),
+            Sandbox::Flatpak => glib::get_user_cache_dir().unwrap_or_else(std::env::temp_dir),
+        }
+    }
+}
@@ -36,6 +36,12 @@ impl XiPeer {
         let peer = XiPeer { tx: to_core_tx };
         (peer, from_core_rx)
     }
+
+    /// A peer which sends the messages to `tx` instead of xi-core, see `FakeCore`
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn from_sender(tx: Sender<String>) -> XiPeer {
+        XiPeer { tx }
+    }
 }
 
 struct ChanReader(Receiver<String>);
@@ -1,4 +1,5 @@
 use crate::globals;
+use crate::whats_new;
 use gettextrs::gettext;
 use gtk::*;
 use log::trace;
@@ -28,6 +29,13 @@ impl AboutWin {
         about_dialog.set_authors(&["Brian Vincent", "Rasmus Thomsen"]);
 
         about_dialog.set_transient_for(Some(parent));
+
+        about_dialog.add_button(&gettext("Show Changelog"), ResponseType::Other(1));
+        about_dialog.connect_response(|about_dialog, response| {
+            if response == ResponseType::Other(1) {
+                whats_new::show_dialog(about_dialog, &gettext("Changelog"), whats_new::CHANGELOG);
+            }
+        });
         trace!("{}", gettext("Showing about window"));
         about_dialog.show_all();
 
@@ -0,0 +1,48 @@
+//! The work we do in the background without the user asking for it, like refreshing the file
+//! tree to show the files other programs have created. It's done while the window is active, so
+//! it's paused while the user is idle (see `idle`) and done right away once they're back. Some of
+//! it is skipped in reduced mode (on a metered connection or low battery), see
+//! `MainWin::is_reduced_mode`.
+
+use gio::{Cancellable, FileExt};
+
+/// Work which is done every now and then in the background
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Job {
+    /// Reading the directories shown in the file tree again, to show the files other programs
+    /// have created or deleted. For folders on remote filesystems (e.g. mounted via SSH or SMB)
+    /// that's a round trip over the network.
+    RefreshFileTree { remote: bool },
+}
+
+/// Whether `job` should be done now. In reduced mode remote folders aren't refreshed, local ones
+/// are since that's cheap.
+pub fn should_run(job: Job, reduced_mode: bool) -> bool {
+    match job {
+        Job::RefreshFileTree { remote } => !(remote && reduced_mode),
+    }
+}
+
+/// Checks if `file_name` is on a remote filesystem, e.g. a network share
+pub fn is_remote(file_name: &str) -> bool {
+    gio::File::new_for_path(file_name)
+        .query_filesystem_info("filesystem::remote", None::<&Cancellable>)
+        .map_or(false, |info| {
+            info.get_attribute_boolean("filesystem::remote")
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reduced_mode() {
+        let local = Job::RefreshFileTree { remote: false };
+        let remote = Job::RefreshFileTree { remote: true };
+        assert!(should_run(local, false));
+        assert!(should_run(remote, false));
+        assert!(should_run(local, true));
+        assert!(!should_run(remote, true));
+    }
+}
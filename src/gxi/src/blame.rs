@@ -0,0 +1,276 @@
+//! Shows who last changed each line of a document and when, at the end of the line. The blame of
+//! the committed file is computed with libgit2 in the background and cached by the revision of
+//! the file. It's mapped to the lines of the document with a diff, lines which aren't committed
+//! yet are marked as such.
+
+use crate::diff::{self, RowKind};
+use gettextrs::gettext;
+use glib::{source, MainContext};
+use gtk::MessageType;
+use gxi_editview::annotations::{Annotation, AnnotationContent};
+use gxi_editview::EditView;
+use log::debug;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The source of the annotations, see `Annotation::source`
+const SOURCE: &str = "blame";
+
+/// How many blames are cached at most, older ones are forgotten once there are more
+const CACHE_SIZE: usize = 32;
+
+/// Who last changed a line and when
+#[derive(Clone, Debug, PartialEq)]
+struct LineBlame {
+    author: String,
+    /// Seconds since the epoch
+    time: i64,
+}
+
+/// Lines last changed together, as libgit2 reports them (in the order of the lines)
+struct Hunk {
+    lines: usize,
+    blame: LineBlame,
+}
+
+/// A file as of the current commit and the blames of its lines
+struct Committed {
+    lines: Vec<String>,
+    blames: Vec<LineBlame>,
+}
+
+/// The blame of each line of a file, from the `hunks` of its blame
+fn line_blames(hunks: &[Hunk]) -> Vec<LineBlame> {
+    hunks
+        .iter()
+        .flat_map(|hunk| (0..hunk.lines).map(move |_| hunk.blame.clone()))
+        .collect()
+}
+
+/// The blames of the (zero based) lines of `text`, the committed file changed or not. `None` for
+/// lines which have been added or changed since.
+fn document_blames(committed: &Committed, text: &str) -> Vec<(u64, Option<LineBlame>)> {
+    let left: Vec<&str> = committed.lines.iter().map(String::as_str).collect();
+    let right: Vec<&str> = text.lines().collect();
+    diff::align(&left, &right)
+        .into_iter()
+        .filter_map(|row| {
+            let line = row.right? as u64;
+            let blame = match row.kind {
+                RowKind::Same => row.left.and_then(|l| committed.blames.get(l).cloned()),
+                _ => None,
+            };
+            Some((line, blame))
+        })
+        .collect()
+}
+
+/// The text shown after a line, e.g. "Jane Doe, 05/03/19"
+fn annotation_text(blame: &Option<LineBlame>) -> String {
+    match blame {
+        Some(blame) => {
+            let date = glib::DateTime::new_from_unix_local(blame.time)
+                .format("%x")
+                .map(|date| date.to_string())
+                .unwrap_or_default();
+            format!("{}, {}", blame.author, date)
+        }
+        None => gettext("Not Committed Yet"),
+    }
+}
+
+/// Committed files and their blames, by their path in the repository and the id of their blob
+type Cache = HashMap<(PathBuf, git2::Oid), Arc<Committed>>;
+
+/// Reads the file `path` as of the current commit and blames it
+fn committed(path: &Path, cache: &Mutex<Cache>) -> Result<Arc<Committed>, String> {
+    let not_in_repo = || gettext("The file isn't part of a git repository");
+    let path = path.canonicalize().map_err(|e| e.to_string())?;
+    let repo = git2::Repository::discover(&path).map_err(|_| not_in_repo())?;
+    let workdir = repo
+        .workdir()
+        .and_then(|dir| dir.canonicalize().ok())
+        .ok_or_else(not_in_repo)?;
+    let relative = path.strip_prefix(&workdir).map_err(|_| not_in_repo())?;
+    let blob = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .and_then(|tree| tree.get_path(relative))
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|_| gettext("The file hasn't been committed yet"))?;
+
+    let key = (relative.to_path_buf(), blob.id());
+    if let Some(committed) = cache.lock().unwrap().get(&key) {
+        debug!("{} '{}'", gettext("Using cached blame of"), path.display());
+        return Ok(committed.clone());
+    }
+
+    let hunks: Vec<Hunk> = repo
+        .blame_file(relative, None)
+        .map_err(|e| e.message().to_string())?
+        .iter()
+        .map(|hunk| {
+            let signature = hunk.final_signature();
+            Hunk {
+                lines: hunk.lines_in_hunk(),
+                blame: LineBlame {
+                    author: signature.name().unwrap_or_default().to_string(),
+                    time: signature.when().seconds(),
+                },
+            }
+        })
+        .collect();
+    let committed = Arc::new(Committed {
+        lines: String::from_utf8_lossy(blob.content())
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        blames: line_blames(&hunks),
+    });
+
+    let mut cache = cache.lock().unwrap();
+    if cache.len() >= CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(key, committed.clone());
+    Ok(committed)
+}
+
+/// Which EditViews show the blame of their document
+#[derive(Default)]
+pub struct Blame {
+    /// The ids of the views showing it
+    shown: Rc<RefCell<HashSet<String>>>,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl Blame {
+    /// Shows the blame in `edit_view` if it doesn't show it yet, otherwise hides it
+    pub fn toggle(&self, edit_view: &Rc<RefCell<EditView>>) {
+        let view_id = edit_view.borrow().view_id.clone();
+        if self.shown.borrow_mut().remove(&view_id) {
+            edit_view.borrow_mut().clear_annotations(SOURCE);
+            return;
+        }
+
+        let path = match edit_view.borrow().file_name.clone() {
+            Some(file_name) => PathBuf::from(file_name),
+            None => {
+                edit_view.borrow().notify(
+                    &gettext("Save the document to see who changed its lines"),
+                    MessageType::Info,
+                );
+                return;
+            }
+        };
+        self.shown.borrow_mut().insert(view_id);
+        let shown = self.shown.clone();
+        let cache = self.cache.clone();
+        edit_view
+            .borrow()
+            .with_text(enclose!((edit_view) move |text| {
+                Self::load(edit_view, path, text.to_string(), shown, cache);
+            }));
+    }
+
+    /// Forgets about the view `view_id`, e.g. after it has been closed
+    pub fn remove_view(&self, view_id: &str) {
+        self.shown.borrow_mut().remove(view_id);
+    }
+
+    /// Blames `text` in the background and shows the result in `edit_view`, unless it's been
+    /// hidden in the meantime
+    fn load(
+        edit_view: Rc<RefCell<EditView>>,
+        path: PathBuf,
+        text: String,
+        shown: Rc<RefCell<HashSet<String>>>,
+        cache: Arc<Mutex<Cache>>,
+    ) {
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        rx.attach(
+            Some(&MainContext::default()),
+            move |result: Result<Vec<(u64, Option<LineBlame>)>, String>| {
+                let mut ev = edit_view.borrow_mut();
+                if !shown.borrow().contains(&ev.view_id) {
+                    return source::Continue(false);
+                }
+                ev.clear_annotations(SOURCE);
+                match result {
+                    Ok(lines) => {
+                        for (line, blame) in lines {
+                            let annotation = Annotation {
+                                source: SOURCE.to_string(),
+                                index: 0,
+                                content: AnnotationContent::Text {
+                                    text: annotation_text(&blame),
+                                    color: None,
+                                },
+                            };
+                            ev.add_annotation(line, annotation);
+                        }
+                    }
+                    Err(e) => {
+                        shown.borrow_mut().remove(&ev.view_id);
+                        let msg = format!("{}: {}", gettext("Couldn't blame the file"), e);
+                        ev.notify(&msg, MessageType::Error);
+                    }
+                }
+                source::Continue(false)
+            },
+        );
+        thread::spawn(move || {
+            let result =
+                committed(&path, &cache).map(|committed| document_blames(&committed, &text));
+            tx.send(result).ok();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line_blame(author: &str) -> LineBlame {
+        LineBlame {
+            author: author.to_string(),
+            time: 1_556_841_600,
+        }
+    }
+
+    #[test]
+    fn blames_of_lines() {
+        let hunks = vec![
+            Hunk {
+                lines: 2,
+                blame: line_blame("Jane"),
+            },
+            Hunk {
+                lines: 1,
+                blame: line_blame("John"),
+            },
+        ];
+        let committed = Committed {
+            lines: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            blames: line_blames(&hunks),
+        };
+        assert_eq!(
+            committed.blames,
+            vec![line_blame("Jane"), line_blame("Jane"), line_blame("John")]
+        );
+        assert_eq!(
+            document_blames(&committed, "a\nnew\nb\nchanged\n"),
+            vec![
+                (0, Some(line_blame("Jane"))),
+                (1, None),
+                (2, Some(line_blame("Jane"))),
+                (3, None),
+            ]
+        );
+    }
+}
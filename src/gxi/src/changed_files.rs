@@ -0,0 +1,277 @@
+//! "Changed Files", a popover listing the files which have been changed in the working tree of the
+//! current project, so the files one is working on can be opened quickly. The first nine are
+//! numbered and opened by pressing their number.
+
+use gettextrs::gettext;
+use glib::{source, MainContext};
+use gtk::*;
+use log::debug;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+
+/// How many files are shown in the list
+const MAX_RESULTS: usize = 100;
+/// How many files can be opened by pressing their number
+const NUMBERED: usize = 9;
+
+/// A changed file: its path, its path relative to the project and how it has changed
+type ChangedFile = (String, String, &'static str);
+
+/// The letter `git status --short` shows for a file with the `status`. Deleted files can't be
+/// opened and aren't shown.
+fn status_letter(status: git2::Status) -> Option<&'static str> {
+    if status.intersects(git2::Status::WT_DELETED | git2::Status::INDEX_DELETED) {
+        None
+    } else if status.contains(git2::Status::WT_NEW) {
+        Some("?")
+    } else if status.contains(git2::Status::INDEX_NEW) {
+        Some("A")
+    } else if status.intersects(git2::Status::WT_RENAMED | git2::Status::INDEX_RENAMED) {
+        Some("R")
+    } else if status.intersects(
+        git2::Status::WT_MODIFIED
+            | git2::Status::INDEX_MODIFIED
+            | git2::Status::WT_TYPECHANGE
+            | git2::Status::INDEX_TYPECHANGE,
+    ) {
+        Some("M")
+    } else if status.contains(git2::Status::CONFLICTED) {
+        Some("U")
+    } else {
+        None
+    }
+}
+
+/// The files which have been changed (or added) in the working tree of the repository `dir` is
+/// in, sorted by path
+fn list_changed(dir: &Path) -> Result<Vec<ChangedFile>, String> {
+    let repo = git2::Repository::discover(dir)
+        .map_err(|_| gettext("The file isn't part of a git repository"))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| gettext("The file isn't part of a git repository"))?
+        .to_path_buf();
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| e.message().to_string())?;
+    let mut files: Vec<ChangedFile> = statuses
+        .iter()
+        .filter_map(|entry| {
+            let letter = status_letter(entry.status())?;
+            let rel_path = entry.path()?.to_string();
+            let path = workdir.join(&rel_path).to_string_lossy().into_owned();
+            Some((path, rel_path, letter))
+        })
+        .collect();
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
+/// The index of the file to open for a key press, for the keys 1 to 9 (on the keypad too)
+fn numbered_index(key: gdk::enums::key::Key) -> Option<usize> {
+    let number = gdk::keyval_to_unicode(key)?.to_digit(10)? as usize;
+    if number >= 1 && number <= NUMBERED {
+        Some(number - 1)
+    } else {
+        None
+    }
+}
+
+pub struct ChangedFiles {
+    popover: Popover,
+    list_box: ListBox,
+    status_label: Label,
+    /// The paths of the files in the rows of the list
+    shown: RefCell<Vec<String>>,
+    /// Incremented whenever the popover is shown, so results of older listings are ignored
+    generation: Cell<usize>,
+    /// Called with the path of the file the user has chosen
+    open_handler: RefCell<Option<Rc<dyn Fn(&str)>>>,
+}
+
+impl ChangedFiles {
+    pub fn new(relative_to: &HeaderBar) -> Rc<Self> {
+        let popover = Popover::new(Some(relative_to));
+        popover.set_position(PositionType::Bottom);
+
+        let title = Label::new(Some(gettext("Changed Files").as_str()));
+        title.get_style_context().add_class("dim-label");
+        let list_box = ListBox::new();
+        list_box.set_selection_mode(SelectionMode::Browse);
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        #[cfg(feature = "gtk_v3_22")]
+        {
+            scrolled_window.set_propagate_natural_height(true);
+            scrolled_window.set_max_content_height(350);
+        }
+        #[cfg(not(feature = "gtk_v3_22"))]
+        scrolled_window.set_min_content_height(200);
+        scrolled_window.set_size_request(400, -1);
+        scrolled_window.add(&list_box);
+        let status_label = Label::new(None);
+        status_label.get_style_context().add_class("dim-label");
+
+        let vbox = Box::new(Orientation::Vertical, 6);
+        vbox.set_border_width(6);
+        vbox.pack_start(&title, false, false, 0);
+        vbox.pack_start(&scrolled_window, true, true, 0);
+        vbox.pack_start(&status_label, false, false, 0);
+        vbox.show_all();
+        popover.add(&vbox);
+
+        let changed_files = Rc::new(Self {
+            popover,
+            list_box,
+            status_label,
+            shown: RefCell::new(Vec::new()),
+            generation: Cell::new(0),
+            open_handler: RefCell::new(None),
+        });
+
+        changed_files
+            .popover
+            .connect_key_press_event(enclose!((changed_files) move |_, ek| {
+                match numbered_index(ek.get_keyval()) {
+                    Some(index) => {
+                        changed_files.open(index);
+                        Inhibit(true)
+                    }
+                    None => Inhibit(false),
+                }
+            }));
+        changed_files
+            .list_box
+            .connect_row_activated(enclose!((changed_files) move |_, row| {
+                changed_files.open(row.get_index() as usize);
+            }));
+
+        changed_files
+    }
+
+    /// Sets the function which opens the file the user has chosen
+    pub fn connect_open<F: Fn(&str) + 'static>(&self, f: F) {
+        self.open_handler.replace(Some(Rc::new(f)));
+    }
+
+    /// Shows the popover with the files changed in the git repository `dir` is in
+    pub fn show(changed_files: &Rc<Self>, dir: &Path) {
+        let generation = changed_files.generation.get() + 1;
+        changed_files.generation.set(generation);
+        changed_files.set_files(Vec::new());
+        changed_files
+            .status_label
+            .set_text(&gettext("Listing changed files…"));
+        changed_files.status_label.show();
+        #[cfg(feature = "gtk_v3_22")]
+        changed_files.popover.popup();
+        #[cfg(not(feature = "gtk_v3_22"))]
+        changed_files.popover.show();
+        changed_files.list_box.grab_focus();
+
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        rx.attach(
+            Some(&MainContext::default()),
+            enclose!((changed_files) move |result: Result<Vec<ChangedFile>, String>| {
+                if changed_files.generation.get() != generation {
+                    return source::Continue(false);
+                }
+                match result {
+                    Ok(files) if files.is_empty() => {
+                        changed_files.status_label.set_text(&gettext("No files have been changed"));
+                    }
+                    Ok(files) => {
+                        debug!("{} {}", gettext("Changed files:"), files.len());
+                        changed_files.status_label.hide();
+                        changed_files.set_files(files);
+                    }
+                    Err(e) => changed_files.status_label.set_text(&e),
+                }
+                source::Continue(false)
+            }),
+        );
+        let dir = dir.to_path_buf();
+        thread::spawn(move || {
+            tx.send(list_changed(&dir)).ok();
+        });
+    }
+
+    fn set_files(&self, files: Vec<ChangedFile>) {
+        for row in self.list_box.get_children() {
+            self.list_box.remove(&row);
+        }
+        let mut shown = self.shown.borrow_mut();
+        shown.clear();
+        for (i, (path, rel_path, letter)) in files.into_iter().take(MAX_RESULTS).enumerate() {
+            let number = if i < NUMBERED {
+                (i + 1).to_string()
+            } else {
+                String::new()
+            };
+            let number_label = Label::new(Some(number.as_str()));
+            number_label.get_style_context().add_class("dim-label");
+            number_label.set_width_chars(1);
+            let letter_label = Label::new(Some(letter));
+            letter_label.get_style_context().add_class("dim-label");
+            letter_label.set_width_chars(1);
+            let name_label = Label::new(Some(rel_path.as_str()));
+            name_label.set_halign(Align::Start);
+            name_label.set_ellipsize(pango::EllipsizeMode::Start);
+
+            let hbox = Box::new(Orientation::Horizontal, 12);
+            hbox.set_margin_start(6);
+            hbox.set_margin_end(6);
+            hbox.set_margin_top(3);
+            hbox.set_margin_bottom(3);
+            hbox.pack_start(&number_label, false, false, 0);
+            hbox.pack_start(&letter_label, false, false, 0);
+            hbox.pack_start(&name_label, true, true, 0);
+            hbox.set_tooltip_text(Some(path.as_str()));
+            self.list_box.add(&hbox);
+            shown.push(path);
+        }
+        self.list_box.show_all();
+        if let Some(row) = self.list_box.get_row_at_index(0) {
+            self.list_box.select_row(Some(&row));
+            row.grab_focus();
+        }
+    }
+
+    /// Opens the file in the row `index`
+    fn open(&self, index: usize) {
+        let path = match self.shown.borrow().get(index) {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        self.popover.hide();
+        if let Some(open) = self.open_handler.borrow().clone() {
+            open(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_letters() {
+        assert_eq!(status_letter(git2::Status::WT_MODIFIED), Some("M"));
+        assert_eq!(
+            status_letter(git2::Status::INDEX_MODIFIED | git2::Status::WT_MODIFIED),
+            Some("M")
+        );
+        assert_eq!(status_letter(git2::Status::WT_NEW), Some("?"));
+        assert_eq!(status_letter(git2::Status::INDEX_NEW), Some("A"));
+        assert_eq!(status_letter(git2::Status::WT_DELETED), None);
+        assert_eq!(status_letter(git2::Status::CURRENT), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Shows the MD5, SHA-1 and SHA-256 checksums of the selection or a file, e.g. to verify a
+//! download against the checksum published next to it.
+
+use gdk::SELECTION_CLIPBOARD;
+use gettextrs::gettext;
+use glib::{Checksum, ChecksumType};
+use gtk::*;
+
+const ALGORITHMS: &[(&str, ChecksumType)] = &[
+    ("MD5", ChecksumType::Md5),
+    ("SHA-1", ChecksumType::Sha1),
+    ("SHA-256", ChecksumType::Sha256),
+];
+
+/// Returns the name of each algorithm with the hex encoded checksum of `data`
+pub fn checksums(data: &[u8]) -> Vec<(&'static str, String)> {
+    ALGORITHMS
+        .iter()
+        .map(|(name, checksum_type)| {
+            let mut checksum = Checksum::new(*checksum_type);
+            checksum.update(data);
+            (*name, checksum.get_string().unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Shows the checksums of `data`, each with a button copying it to the clipboard. `what` says what
+/// the checksums are of, e.g. the file name.
+pub fn show_dialog(parent: &ApplicationWindow, what: &str, data: &[u8]) {
+    let dialog = MessageDialog::new(
+        Some(parent),
+        DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Info,
+        ButtonsType::Close,
+        gettext("Checksums").as_str(),
+    );
+    dialog.set_property_secondary_text(Some(what));
+
+    let grid = Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    for (i, (name, checksum)) in checksums(data).into_iter().enumerate() {
+        let name_label = Label::new(Some(name));
+        name_label.set_halign(Align::Start);
+        let checksum_label = Label::new(Some(checksum.as_str()));
+        checksum_label.set_selectable(true);
+        checksum_label.set_halign(Align::Start);
+        checksum_label.get_style_context().add_class("monospace");
+        let copy_button = Button::new_from_icon_name(Some("edit-copy-symbolic"), IconSize::Button);
+        copy_button.set_tooltip_text(Some(gettext("Copy to Clipboard").as_str()));
+        copy_button.connect_clicked(move |_| {
+            Clipboard::get(&SELECTION_CLIPBOARD).set_text(&checksum);
+        });
+
+        grid.attach(&name_label, 0, i as i32, 1, 1);
+        grid.attach(&checksum_label, 1, i as i32, 1, 1);
+        grid.attach(&copy_button, 2, i as i32, 1, 1);
+    }
+    if let Some(message_area) = dialog
+        .get_message_area()
+        .and_then(|w| w.downcast::<gtk::Box>().ok())
+    {
+        message_area.pack_start(&grid, false, false, 0);
+    }
+    grid.show_all();
+
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.show();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_checksums() {
+        let sums = checksums(b"abc");
+        assert_eq!(
+            sums[0],
+            ("MD5", "900150983cd24fb0d6963f7d28e17f72".to_string())
+        );
+        assert_eq!(
+            sums[1],
+            (
+                "SHA-1",
+                "a9993e364706816aba3e25717850c26c9cd0d89d".to_string()
+            )
+        );
+        assert_eq!(
+            sums[2],
+            (
+                "SHA-256",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! Options of gxi's command line which have to be known before the `Application` is created, e.g.
+//! because they're part of its application id. GApplication only parses options at startup, so we
+//! have to take them out of the arguments ourselves.
+
+/// Removes `--OPTION VALUE` (or `--OPTION=VALUE`) from the command line arguments `args`,
+/// returning the value and the remaining arguments. Arguments after `--` are left alone.
+pub fn take_option(args: Vec<String>, option: &str) -> (Option<String>, Vec<String>) {
+    let flag = format!("--{}", option);
+    let prefix = format!("--{}=", option);
+    let mut value = None;
+    let mut remaining = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            remaining.push(arg);
+            remaining.extend(args.by_ref());
+        } else if arg == flag {
+            value = args.next();
+        } else if arg.starts_with(&prefix) {
+            value = Some(arg[prefix.len()..].to_string());
+        } else {
+            remaining.push(arg);
+        }
+    }
+    (value.filter(|value| !value.is_empty()), remaining)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            take_option(args(&["gxi", "--window", "work", "file.txt"]), "window"),
+            (Some("work".to_string()), args(&["gxi", "file.txt"]))
+        );
+        assert_eq!(
+            take_option(
+                args(&["gxi", "--profile=writing", "--window=notes"]),
+                "profile"
+            ),
+            (
+                Some("writing".to_string()),
+                args(&["gxi", "--window=notes"])
+            )
+        );
+        assert_eq!(
+            take_option(args(&["gxi", "--", "--window"]), "window"),
+            (None, args(&["gxi", "--", "--window"]))
+        );
+    }
+}
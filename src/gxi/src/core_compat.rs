@@ -0,0 +1,129 @@
+//! Checks whether xi-core speaks the protocol gxi expects. xi-core doesn't tell frontends its
+//! version, so we infer it from the notifications it sends: after `client_started` it always
+//! sends some notifications gxi relies on, if they don't arrive it's older than gxi expects. If
+//! it sends notifications gxi doesn't know, it's newer. Either way the user is warned once, with
+//! the features which won't work, instead of things failing later on without explanation.
+
+use gettextrs::gettext;
+use std::collections::BTreeSet;
+
+/// How long (in seconds) after starting we give xi-core to send its startup notifications
+pub const CHECK_DELAY: u32 = 10;
+
+/// The notifications xi-core sends after `client_started`
+const STARTUP_NOTIFICATIONS: &[&str] = &[
+    "available_themes",
+    "available_languages",
+    "available_plugins",
+];
+
+/// The feature of gxi which needs the startup notification `method`
+fn feature(method: &str) -> String {
+    match method {
+        "available_themes" => gettext("Choosing the syntax highlighting theme"),
+        "available_languages" => gettext("Choosing the language of documents"),
+        "available_plugins" => gettext("Starting and stopping plugins"),
+        _ => method.to_string(),
+    }
+}
+
+/// The notifications xi-core has sent so far
+#[derive(Default)]
+pub struct CoreCompat {
+    seen: BTreeSet<String>,
+    /// Notifications gxi doesn't know how to handle
+    unknown: BTreeSet<String>,
+    checked: bool,
+}
+
+impl CoreCompat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notes that xi-core has sent the notification `method`, which gxi handles if `known`
+    pub fn note(&mut self, method: &str, known: bool) {
+        if known {
+            self.seen.insert(method.to_string());
+        } else {
+            self.unknown.insert(method.to_string());
+        }
+    }
+
+    /// The features which won't work because xi-core never sent the notifications they need
+    fn missing_features(&self) -> Vec<String> {
+        STARTUP_NOTIFICATIONS
+            .iter()
+            .filter(|method| !self.seen.contains(**method))
+            .map(|method| feature(method))
+            .collect()
+    }
+
+    /// The warning for the user if xi-core doesn't seem to be compatible with gxi. Only returns
+    /// a warning the first time it's called.
+    pub fn check(&mut self) -> Option<String> {
+        if self.checked {
+            return None;
+        }
+        self.checked = true;
+        let mut msgs = Vec::new();
+        let missing = self.missing_features();
+        if !missing.is_empty() {
+            msgs.push(format!(
+                "{}\n{}",
+                gettext("xi-core seems to be older than this version of gxi supports. These features won't be available:"),
+                missing
+                    .iter()
+                    .map(|feature| format!(" • {}", feature))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ));
+        }
+        if !self.unknown.is_empty() {
+            msgs.push(format!(
+                "{} {}",
+                gettext("xi-core seems to be newer than this version of gxi supports, some of its features won't be available. It sent messages gxi doesn't know:"),
+                self.unknown.iter().cloned().collect::<Vec<String>>().join(", ")
+            ));
+        }
+        if msgs.is_empty() {
+            None
+        } else {
+            Some(msgs.join("\n\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compatible() {
+        let mut compat = CoreCompat::new();
+        for method in STARTUP_NOTIFICATIONS {
+            compat.note(method, true);
+        }
+        assert_eq!(compat.check(), None);
+    }
+
+    #[test]
+    fn older_and_newer() {
+        let mut compat = CoreCompat::new();
+        compat.note("available_themes", true);
+        assert_eq!(
+            compat.missing_features(),
+            vec![
+                "Choosing the language of documents",
+                "Starting and stopping plugins"
+            ]
+        );
+        compat.note("available_languages", true);
+        compat.note("available_plugins", true);
+        compat.note("show_hover", false);
+        let msg = compat.check().unwrap();
+        assert!(msg.contains("show_hover"));
+        // The user is only warned once
+        assert_eq!(compat.check(), None);
+    }
+}
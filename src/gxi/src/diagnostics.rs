@@ -0,0 +1,419 @@
+//! A panel at the bottom of the window listing the problems linters have found in the open files,
+//! grouped by file. The linter of a language is set in the 'lint-commands' key, it's run whenever
+//! a file of that language has been opened or saved. Its output is expected in the format most
+//! compilers and linters use (or can be told to use), e.g. `src/main.rs:3:5: error: message`.
+//! Activating a problem moves the cursor to it.
+
+use crate::panels::{Panels, Slot};
+use crate::project;
+use gettextrs::gettext;
+use glib::{source, MainContext, Type};
+use gtk::*;
+use gxi_editview::gutter_markers::GutterMarker;
+use gxi_editview::EditView;
+use gxi_peer::Sandbox;
+use log::debug;
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+
+/// Name the panel is registered under, for the `app.toggle_panel` action
+pub const PANEL_NAME: &str = "diagnostics";
+
+/// The source of the gutter markers, see `GutterMarker::source`
+const SOURCE: &str = "diagnostics";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    /// The severity the linter has given as e.g. `error` or `warning`
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "error" | "fatal error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "note" | "info" | "help" | "style" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+
+    fn icon_name(self) -> &'static str {
+        match self {
+            Severity::Error => "dialog-error-symbolic",
+            Severity::Warning => "dialog-warning-symbolic",
+            Severity::Info => "dialog-information-symbolic",
+        }
+    }
+}
+
+/// A problem in a file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Zero based
+    pub line: u64,
+    /// One based, as linters report it. Not every linter does.
+    pub column: Option<u64>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The lint command for documents of the language `language` from the 'language=command'
+/// `entries` of the 'lint-commands' key
+pub fn lint_command<S: AsRef<str>>(entries: &[S], language: &str) -> Option<String> {
+    entries.iter().find_map(|entry| {
+        let mut split = entry.as_ref().splitn(2, '=');
+        let entry_language = split.next()?.trim();
+        let command = split.next()?.trim();
+        if entry_language == language && !command.is_empty() {
+            Some(command.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `path` (relative to `dir` unless it's absolute) is the file `file`
+fn is_file(path: &str, dir: &Path, file: &Path) -> bool {
+    let path = dir.join(path);
+    if path == file {
+        return true;
+    }
+    match (path.canonicalize(), file.canonicalize()) {
+        (Ok(path), Ok(file)) => path == file,
+        _ => false,
+    }
+}
+
+/// Picks the problems of `file` out of the `output` of a linter which ran in `dir`, sorted by
+/// line. Lines which don't look like a problem (e.g. summaries or the code the problem is in) are
+/// skipped.
+fn parse(output: &str, dir: &Path, file: &Path) -> Vec<Diagnostic> {
+    let location_re =
+        Regex::new(r"^(?P<path>[^:]+):(?P<line>\d+):(?:(?P<column>\d+):)?\s*(?P<rest>.*)$")
+            .unwrap();
+    let severity_re = Regex::new(
+        r"(?i)^(?P<severity>fatal error|error|warning|note|info|help|style)(?:\[[^\]]*\])?:\s*(?P<message>.*)$",
+    )
+    .unwrap();
+
+    let mut diagnostics: Vec<Diagnostic> = output
+        .lines()
+        .filter_map(|line| {
+            let caps = location_re.captures(line.trim_end())?;
+            if !is_file(&caps["path"], dir, file) {
+                return None;
+            }
+            let line = caps["line"].parse::<u64>().ok()?.checked_sub(1)?;
+            let column = caps.name("column").and_then(|c| c.as_str().parse().ok());
+            let rest = &caps["rest"];
+            // Linters which don't give a severity (e.g. flake8) usually only report warnings
+            let (severity, message) = match severity_re.captures(rest) {
+                Some(caps) => (
+                    Severity::parse(&caps["severity"])?,
+                    caps["message"].to_string(),
+                ),
+                None => (Severity::Warning, rest.to_string()),
+            };
+            Some(Diagnostic {
+                line,
+                column,
+                severity,
+                message,
+            })
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| (d.line, d.column, d.severity));
+    diagnostics
+}
+
+/// Runs the lint `command` on `file` in the root of its project and returns the problems it has
+/// found in the file. The file name is passed as last argument, unless the command contains `%f`
+/// which is replaced by it then.
+fn lint(command: &str, file: &Path) -> Result<Vec<Diagnostic>, String> {
+    debug!("{} '{}': {}", gettext("Linting"), file.display(), command);
+    let dir = project::project_root(file);
+    let script = if command.contains("%f") {
+        command.replace("%f", "\"$1\"")
+    } else {
+        format!("{} \"$1\"", command)
+    };
+    let output = Sandbox::detect()
+        .host_command("sh")
+        .arg("-c")
+        .arg(script)
+        .arg("sh")
+        .arg(file)
+        .current_dir(&dir)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    // Linters report problems on stdout or stderr and exit with an error when they find some
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let diagnostics = parse(&format!("{}\n{}", stdout, stderr), &dir, file);
+    if diagnostics.is_empty() && !output.status.success() && !stderr.trim().is_empty() {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(diagnostics)
+}
+
+pub struct Diagnostics {
+    root: ScrolledWindow,
+    tree_store: TreeStore,
+    treeview: TreeView,
+    /// The problems of the open files, by file name
+    files: RefCell<BTreeMap<String, Vec<Diagnostic>>>,
+}
+
+impl Diagnostics {
+    pub fn new(panels: &Panels) -> Rc<Self> {
+        // Icon name, file name or position, message, file name and line
+        let tree_store = TreeStore::new(&[
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::U64,
+        ]);
+        let treeview = TreeView::new_with_model(&tree_store);
+        treeview.set_headers_visible(false);
+        treeview.set_enable_search(false);
+
+        let tree_column = TreeViewColumn::new();
+        let icon_renderer = CellRendererPixbuf::new();
+        tree_column.pack_start(&icon_renderer, false);
+        tree_column.add_attribute(&icon_renderer, "icon-name", 0);
+        let renderer = CellRendererText::new();
+        tree_column.pack_start(&renderer, true);
+        tree_column.add_attribute(&renderer, "text", 1);
+        treeview.append_column(&tree_column);
+
+        let renderer = CellRendererText::new();
+        let tree_column = TreeViewColumn::new();
+        tree_column.pack_start(&renderer, true);
+        tree_column.add_attribute(&renderer, "text", 2);
+        treeview.append_column(&tree_column);
+
+        let root = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        root.set_vexpand(true);
+        root.add(&treeview);
+        treeview.show();
+
+        let diagnostics = Rc::new(Self {
+            root,
+            tree_store,
+            treeview,
+            files: Default::default(),
+        });
+        panels.register(
+            PANEL_NAME,
+            &gettext("Diagnostics"),
+            Slot::Bottom,
+            &diagnostics.root,
+        );
+
+        diagnostics
+    }
+
+    /// Sets the function which is called with the file and (zero based) line of the problem the
+    /// user has activated
+    pub fn connect_activate<F: Fn(&str, u64) + 'static>(diagnostics: &Rc<Self>, f: F) {
+        diagnostics
+            .treeview
+            .connect_row_activated(move |tv, path, _| {
+                let location = tv
+                    .get_model()
+                    .and_then(|model| model.get_iter(path).map(|iter| (model, iter)))
+                    .and_then(|(model, iter)| {
+                        // The rows of the files themselves don't have an icon
+                        model.get_value(&iter, 0).get::<String>()?;
+                        let file = model.get_value(&iter, 3).get::<String>()?;
+                        let line = model.get_value(&iter, 4).get::<u64>()?;
+                        Some((file, line))
+                    });
+                if let Some((file, line)) = location {
+                    f(&file, line);
+                }
+            });
+    }
+
+    /// Lints the file `file_name` of `edit_view` with `command` in the background and shows the
+    /// problems found. Without a command (e.g. after the language of the document has changed to
+    /// one without a linter) the problems shown are cleared.
+    pub fn check(
+        diagnostics: &Rc<Self>,
+        edit_view: &Rc<RefCell<EditView>>,
+        file_name: &str,
+        command: Option<String>,
+    ) {
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        let weak_ev = Rc::downgrade(edit_view);
+        let weak_diagnostics = Rc::downgrade(diagnostics);
+        let file = file_name.to_string();
+        rx.attach(
+            Some(&MainContext::default()),
+            move |result: Result<Vec<Diagnostic>, String>| {
+                let (diagnostics, edit_view) = match (weak_diagnostics.upgrade(), weak_ev.upgrade())
+                {
+                    (Some(diagnostics), Some(edit_view)) => (diagnostics, edit_view),
+                    _ => return source::Continue(false),
+                };
+                match result {
+                    Ok(found) => diagnostics.set(&edit_view, &file, found),
+                    Err(e) => {
+                        let msg = format!("{}: {}", gettext("Couldn't run the linter"), e);
+                        edit_view.borrow().notify(&msg, MessageType::Warning);
+                    }
+                }
+                source::Continue(false)
+            },
+        );
+
+        match command {
+            Some(command) => {
+                let file = file_name.to_string();
+                thread::spawn(move || {
+                    tx.send(lint(&command, Path::new(&file))).ok();
+                });
+            }
+            // Still goes through the channel, the EditView may be borrowed right now
+            None => {
+                tx.send(Ok(Vec::new())).ok();
+            }
+        }
+    }
+
+    /// Forgets the problems of the file `file_name`, e.g. after it has been closed
+    pub fn remove_file(&self, file_name: &str) {
+        if self.files.borrow_mut().remove(file_name).is_some() {
+            self.refresh();
+        }
+    }
+
+    /// Shows the problems `found` in the file `file_name` of `edit_view` in the panel, the gutter
+    /// and the statusbar
+    fn set(&self, edit_view: &Rc<RefCell<EditView>>, file_name: &str, found: Vec<Diagnostic>) {
+        {
+            let mut ev = edit_view.borrow_mut();
+            ev.clear_gutter_markers(SOURCE);
+            for diagnostic in &found {
+                let marker = GutterMarker {
+                    source: SOURCE.to_string(),
+                    icon: Some(diagnostic.severity.icon_name().to_string()),
+                    tooltip: Some(diagnostic.message.clone()),
+                    ..Default::default()
+                };
+                ev.add_gutter_marker(diagnostic.line, marker);
+            }
+            let count = |severity| found.iter().filter(|d| d.severity == severity).count();
+            ev.set_diagnostic_counts(count(Severity::Error), count(Severity::Warning));
+        }
+
+        if found.is_empty() {
+            self.files.borrow_mut().remove(file_name);
+        } else {
+            self.files.borrow_mut().insert(file_name.to_string(), found);
+        }
+        self.refresh();
+    }
+
+    fn refresh(&self) {
+        self.tree_store.clear();
+        for (file_name, found) in self.files.borrow().iter() {
+            let title = format!(
+                "{} ({})",
+                Path::new(file_name)
+                    .file_name()
+                    .map_or_else(|| file_name.clone(), |n| n.to_string_lossy().into_owned()),
+                found.len()
+            );
+            let file_iter = self.tree_store.insert_with_values(
+                None,
+                None,
+                &[1, 2, 3, 4],
+                &[&title, &file_name, &file_name, &0u64],
+            );
+            for diagnostic in found {
+                let position = match diagnostic.column {
+                    Some(column) => format!("{}:{}", diagnostic.line + 1, column),
+                    None => (diagnostic.line + 1).to_string(),
+                };
+                self.tree_store.insert_with_values(
+                    Some(&file_iter),
+                    None,
+                    &[0, 1, 2, 3, 4],
+                    &[
+                        &diagnostic.severity.icon_name(),
+                        &position,
+                        &diagnostic.message,
+                        &file_name,
+                        &diagnostic.line,
+                    ],
+                );
+            }
+        }
+        self.treeview.expand_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lint_commands() {
+        let entries = [
+            "Python=flake8",
+            "Rust = cargo clippy --message-format short",
+        ];
+        assert_eq!(lint_command(&entries, "Python"), Some("flake8".to_string()));
+        assert_eq!(
+            lint_command(&entries, "Rust"),
+            Some("cargo clippy --message-format short".to_string())
+        );
+        assert_eq!(lint_command(&entries, "C"), None);
+    }
+
+    #[test]
+    fn parse_output() {
+        let dir = Path::new("/project");
+        let file = Path::new("/project/src/main.rs");
+        let output = "\
+src/main.rs:3:5: error[E0425]: cannot find value `x` in this scope
+src/lib.rs:1:1: warning: unused import
+/project/src/main.rs:1: warning: missing documentation
+src/main.rs:2:10: E231 missing whitespace after ','
+error: aborting due to previous error
+";
+        assert_eq!(
+            parse(output, dir, file),
+            vec![
+                Diagnostic {
+                    line: 0,
+                    column: None,
+                    severity: Severity::Warning,
+                    message: "missing documentation".to_string(),
+                },
+                Diagnostic {
+                    line: 1,
+                    column: Some(10),
+                    severity: Severity::Warning,
+                    message: "E231 missing whitespace after ','".to_string(),
+                },
+                Diagnostic {
+                    line: 2,
+                    column: Some(5),
+                    severity: Severity::Error,
+                    message: "cannot find value `x` in this scope".to_string(),
+                },
+            ]
+        );
+    }
+}
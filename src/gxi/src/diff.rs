@@ -0,0 +1,251 @@
+//! Compares two texts line by line for the side by side diff view. The lines of both sides are
+//! aligned in rows, so unchanged lines are next to each other and lines only one side has are
+//! next to an empty row. Changed lines are also compared word by word, to highlight what exactly
+//! has changed.
+
+use std::ops::Range;
+
+/// Above this many (lines times lines) the longest common subsequence isn't looked for, the
+/// lines between the common start and end of the texts are all shown as changed instead
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    /// The items are the same on both sides
+    Equal,
+    /// The item of the left side isn't on the right one
+    Delete,
+    /// The item of the right side isn't on the left one
+    Insert,
+}
+
+/// How the sides differ in `a` and `b`, in the order of the items. The longest common subsequence
+/// is searched for between the common start and end of `a` and `b`, unless that'd take too long.
+fn diff_ops<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops = vec![Op::Equal; prefix];
+    if a_mid.len() * b_mid.len() > MAX_CELLS {
+        ops.extend(a_mid.iter().map(|_| Op::Delete));
+        ops.extend(b_mid.iter().map(|_| Op::Insert));
+    } else {
+        // lengths[i][j] is the length of the longest common subsequence of a_mid[i..] and b_mid[j..]
+        let width = b_mid.len() + 1;
+        let mut lengths = vec![0u32; (a_mid.len() + 1) * width];
+        for i in (0..a_mid.len()).rev() {
+            for j in (0..b_mid.len()).rev() {
+                lengths[i * width + j] = if a_mid[i] == b_mid[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a_mid.len() && j < b_mid.len() {
+            if a_mid[i] == b_mid[j] {
+                ops.push(Op::Equal);
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                ops.push(Op::Delete);
+                i += 1;
+            } else {
+                ops.push(Op::Insert);
+                j += 1;
+            }
+        }
+        ops.extend((i..a_mid.len()).map(|_| Op::Delete));
+        ops.extend((j..b_mid.len()).map(|_| Op::Insert));
+    }
+    ops.extend(std::iter::repeat(Op::Equal).take(suffix));
+    ops
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RowKind {
+    Same,
+    /// The line has been changed, the row has the old and the new version of it
+    Changed,
+    /// The line is only on the left side
+    Removed,
+    /// The line is only on the right side
+    Added,
+}
+
+/// A row of the diff view, with the (zero based) line of each side shown in it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Row {
+    pub kind: RowKind,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+}
+
+/// Aligns the lines of `left` and `right` in rows. Of removed lines followed by added ones, as
+/// many as possible are paired up as changed lines.
+pub fn align(left: &[&str], right: &[&str]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let (mut l, mut r) = (0, 0);
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+
+    fn flush(rows: &mut Vec<Row>, removed: &mut Vec<usize>, added: &mut Vec<usize>) {
+        for i in 0..removed.len().max(added.len()) {
+            let (left, right) = (removed.get(i).cloned(), added.get(i).cloned());
+            let kind = match (left, right) {
+                (Some(_), Some(_)) => RowKind::Changed,
+                (Some(_), None) => RowKind::Removed,
+                _ => RowKind::Added,
+            };
+            rows.push(Row { kind, left, right });
+        }
+        removed.clear();
+        added.clear();
+    }
+
+    for op in diff_ops(left, right) {
+        match op {
+            Op::Equal => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(Row {
+                    kind: RowKind::Same,
+                    left: Some(l),
+                    right: Some(r),
+                });
+                l += 1;
+                r += 1;
+            }
+            Op::Delete => {
+                removed.push(l);
+                l += 1;
+            }
+            Op::Insert => {
+                added.push(r);
+                r += 1;
+            }
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+/// The first row of each run of rows which aren't the same on both sides, to go from change to
+/// change
+pub fn hunks(rows: &[Row]) -> Vec<usize> {
+    (0..rows.len())
+        .filter(|&i| rows[i].kind != RowKind::Same && (i == 0 || rows[i - 1].kind == RowKind::Same))
+        .collect()
+}
+
+/// Splits `line` into words, runs of whitespace and single other chars, as byte ranges
+fn tokens(line: &str) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            1
+        } else if c.is_whitespace() {
+            2
+        } else {
+            0
+        }
+    };
+    let mut last_class = 0;
+    for (ix, c) in line.char_indices() {
+        let c_class = class(c);
+        match tokens.last_mut() {
+            Some(last) if c_class != 0 && c_class == last_class => last.end = ix + c.len_utf8(),
+            _ => tokens.push(ix..ix + c.len_utf8()),
+        }
+        last_class = c_class;
+    }
+    tokens
+}
+
+/// The byte ranges of `left` and `right` which differ, word by word
+pub fn changed_ranges(left: &str, right: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let (left_tokens, right_tokens) = (tokens(left), tokens(right));
+    let left_words: Vec<&str> = left_tokens.iter().map(|t| &left[t.clone()]).collect();
+    let right_words: Vec<&str> = right_tokens.iter().map(|t| &right[t.clone()]).collect();
+
+    fn add(ranges: &mut Vec<Range<usize>>, token: &Range<usize>) {
+        match ranges.last_mut() {
+            Some(last) if last.end == token.start => last.end = token.end,
+            _ => ranges.push(token.clone()),
+        }
+    }
+
+    let (mut left_ranges, mut right_ranges) = (Vec::new(), Vec::new());
+    let (mut l, mut r) = (0, 0);
+    for op in diff_ops(&left_words, &right_words) {
+        match op {
+            Op::Equal => {
+                l += 1;
+                r += 1;
+            }
+            Op::Delete => {
+                add(&mut left_ranges, &left_tokens[l]);
+                l += 1;
+            }
+            Op::Insert => {
+                add(&mut right_ranges, &right_tokens[r]);
+                r += 1;
+            }
+        }
+    }
+    (left_ranges, right_ranges)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(kind: RowKind, left: Option<usize>, right: Option<usize>) -> Row {
+        Row { kind, left, right }
+    }
+
+    #[test]
+    fn alignment() {
+        let left = ["a", "b", "c", "d", "e"];
+        let right = ["a", "B", "c", "x", "y", "e", "f"];
+        assert_eq!(
+            align(&left, &right),
+            vec![
+                row(RowKind::Same, Some(0), Some(0)),
+                row(RowKind::Changed, Some(1), Some(1)),
+                row(RowKind::Same, Some(2), Some(2)),
+                row(RowKind::Changed, Some(3), Some(3)),
+                row(RowKind::Added, None, Some(4)),
+                row(RowKind::Same, Some(4), Some(5)),
+                row(RowKind::Added, None, Some(6)),
+            ]
+        );
+        assert_eq!(
+            align(&["a", "b"], &[]),
+            vec![
+                row(RowKind::Removed, Some(0), None),
+                row(RowKind::Removed, Some(1), None)
+            ]
+        );
+        assert!(align(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn hunk_starts() {
+        let rows = align(&["a", "b", "c", "d"], &["x", "b", "y", "z"]);
+        assert_eq!(hunks(&rows), vec![0, 2]);
+    }
+
+    #[test]
+    fn intra_line() {
+        let (left, right) = changed_ranges("let foo = bar(1);", "let foo = baz(1, 2);");
+        assert_eq!(left, vec![10..13]);
+        assert_eq!(right, vec![10..13, 15..18]);
+        assert_eq!(changed_ranges("same", "same"), (Vec::new(), Vec::new()));
+    }
+}
@@ -0,0 +1,265 @@
+//! A window showing the differences between two texts side by side, e.g. two open documents or a
+//! document and its saved file. Removed and added lines are highlighted and the words which have
+//! changed within a line are highlighted more strongly. The buttons in the header bar (or Alt+Up
+//! and Alt+Down) go from change to change.
+
+use crate::diff::{self, Row, RowKind};
+use gdk::enums::key;
+use gdk::ModifierType;
+use gettextrs::gettext;
+use gtk::*;
+use std::cell::Cell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// How far from the top of the window (0.0 is the top, 1.0 the bottom) a change is shown when
+/// going to it
+const HUNK_ALIGN: f64 = 0.3;
+
+/// One of the sides of the diff view
+struct Side {
+    numbers: TextView,
+    text: TextView,
+}
+
+impl Side {
+    fn new() -> Self {
+        let numbers = TextView::new();
+        numbers.set_editable(false);
+        numbers.set_cursor_visible(false);
+        numbers.set_monospace(true);
+        numbers.set_justification(Justification::Right);
+        numbers.set_left_margin(6);
+        numbers.set_right_margin(6);
+        numbers.get_style_context().add_class("dim-label");
+
+        let text = TextView::new();
+        text.set_editable(false);
+        text.set_monospace(true);
+        text.set_hexpand(true);
+        text.set_left_margin(6);
+
+        Self { numbers, text }
+    }
+
+    /// Fills the side with the `lines` in the `rows`, using `line_of` to find the line of the
+    /// side in a row. `changed` is how the lines only this side has are shown, `RowKind::Removed`
+    /// on the left and `RowKind::Added` on the right. `ranges` returns the parts of the line in a
+    /// row which differ from the other side.
+    fn fill(
+        &self,
+        lines: &[&str],
+        rows: &[Row],
+        line_of: impl Fn(&Row) -> Option<usize>,
+        changed: RowKind,
+        ranges: impl Fn(usize) -> Vec<Range<usize>>,
+    ) {
+        let buffer = self.text.get_buffer().unwrap();
+        let numbers_buffer = self.numbers.get_buffer().unwrap();
+        let (line_tag, word_tag) = if changed == RowKind::Removed {
+            ("removed", "removed-word")
+        } else {
+            ("added", "added-word")
+        };
+        create_tags(&buffer);
+
+        let text: Vec<&str> = rows
+            .iter()
+            .map(|row| line_of(row).map_or("", |line| lines[line]))
+            .collect();
+        buffer.set_text(&text.join("\n"));
+        let numbers: Vec<String> = rows
+            .iter()
+            .map(|row| line_of(row).map_or_else(String::new, |line| (line + 1).to_string()))
+            .collect();
+        numbers_buffer.set_text(&numbers.join("\n"));
+
+        for (ix, row) in rows.iter().enumerate() {
+            let tag = match (row.kind, line_of(row)) {
+                (RowKind::Same, _) => continue,
+                (_, None) => "filler",
+                (RowKind::Changed, Some(_)) => {
+                    for range in ranges(ix) {
+                        let start = buffer.get_iter_at_line_index(ix as i32, range.start as i32);
+                        let end = buffer.get_iter_at_line_index(ix as i32, range.end as i32);
+                        buffer.apply_tag_by_name(word_tag, &start, &end);
+                    }
+                    line_tag
+                }
+                _ => line_tag,
+            };
+            let start = buffer.get_iter_at_line(ix as i32);
+            let mut end = start.clone();
+            end.forward_line();
+            buffer.apply_tag_by_name(tag, &start, &end);
+        }
+    }
+}
+
+/// The tags highlighting the changes. The colors are translucent so they work with light and
+/// dark themes.
+fn create_tags(buffer: &TextBuffer) {
+    let tag_table = buffer.get_tag_table().unwrap();
+    let tags = [
+        ("removed", "rgba(224, 27, 36, 0.15)", true),
+        ("removed-word", "rgba(224, 27, 36, 0.35)", false),
+        ("added", "rgba(46, 194, 126, 0.15)", true),
+        ("added-word", "rgba(46, 194, 126, 0.35)", false),
+        ("filler", "rgba(128, 128, 128, 0.12)", true),
+    ];
+    for (name, color, paragraph) in tags.iter() {
+        let tag = TextTag::new(Some(*name));
+        if *paragraph {
+            tag.set_property_paragraph_background(Some(*color));
+        } else {
+            tag.set_property_background(Some(*color));
+        }
+        tag_table.add(&tag);
+    }
+}
+
+pub struct DiffView {
+    window: Window,
+    left: Side,
+    position_label: Label,
+    /// The first row of each change
+    hunks: Vec<usize>,
+    /// The change which has been gone to last
+    current: Cell<Option<usize>>,
+}
+
+impl DiffView {
+    /// Shows the differences between `left_text` (named `left_title`) and `right_text` in a new
+    /// window
+    pub fn show(
+        parent: &ApplicationWindow,
+        left_title: &str,
+        left_text: &str,
+        right_title: &str,
+        right_text: &str,
+    ) {
+        let left_lines: Vec<&str> = left_text.lines().collect();
+        let right_lines: Vec<&str> = right_text.lines().collect();
+        let rows = diff::align(&left_lines, &right_lines);
+
+        let window = Window::new(WindowType::Toplevel);
+        window.set_transient_for(Some(parent));
+        window.set_default_size(1000, 700);
+        let header_bar = HeaderBar::new();
+        header_bar.set_show_close_button(true);
+        header_bar.set_title(Some(gettext("Compare").as_str()));
+        header_bar.set_subtitle(Some(
+            format!("{} \u{2194} {}", left_title, right_title).as_str(),
+        ));
+        let prev_button = Button::new_from_icon_name(Some("go-up-symbolic"), IconSize::Button);
+        prev_button.set_tooltip_text(Some(gettext("Previous Change (Alt+Up)").as_str()));
+        let next_button = Button::new_from_icon_name(Some("go-down-symbolic"), IconSize::Button);
+        next_button.set_tooltip_text(Some(gettext("Next Change (Alt+Down)").as_str()));
+        let nav_box = gtk::Box::new(Orientation::Horizontal, 0);
+        nav_box.get_style_context().add_class("linked");
+        nav_box.pack_start(&prev_button, false, false, 0);
+        nav_box.pack_start(&next_button, false, false, 0);
+        header_bar.pack_start(&nav_box);
+        let position_label = Label::new(None);
+        position_label.get_style_context().add_class("dim-label");
+        header_bar.pack_start(&position_label);
+        window.set_titlebar(Some(&header_bar));
+
+        let left = Side::new();
+        let right = Side::new();
+        let intra_line: Vec<(Vec<Range<usize>>, Vec<Range<usize>>)> = rows
+            .iter()
+            .map(|row| match (row.kind, row.left, row.right) {
+                (RowKind::Changed, Some(l), Some(r)) => {
+                    diff::changed_ranges(left_lines[l], right_lines[r])
+                }
+                _ => Default::default(),
+            })
+            .collect();
+        left.fill(
+            &left_lines,
+            &rows,
+            |row| row.left,
+            RowKind::Removed,
+            |ix| intra_line[ix].0.clone(),
+        );
+        right.fill(
+            &right_lines,
+            &rows,
+            |row| row.right,
+            RowKind::Added,
+            |ix| intra_line[ix].1.clone(),
+        );
+
+        // The rows of both sides line up, so scrolling them together keeps them aligned
+        let sides_box = gtk::Box::new(Orientation::Horizontal, 0);
+        sides_box.pack_start(&left.numbers, false, false, 0);
+        sides_box.pack_start(&left.text, true, true, 0);
+        sides_box.pack_start(&Separator::new(Orientation::Vertical), false, false, 0);
+        sides_box.pack_start(&right.numbers, false, false, 0);
+        sides_box.pack_start(&right.text, true, true, 0);
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.add(&sides_box);
+        window.add(&scrolled_window);
+
+        let diff_view = Rc::new(Self {
+            window,
+            left,
+            position_label,
+            hunks: diff::hunks(&rows),
+            current: Cell::new(None),
+        });
+        diff_view.update_position();
+
+        prev_button.connect_clicked(enclose!((diff_view) move |_| diff_view.go_to_hunk(false)));
+        next_button.connect_clicked(enclose!((diff_view) move |_| diff_view.go_to_hunk(true)));
+        diff_view
+            .window
+            .connect_key_press_event(enclose!((diff_view) move |_, ek| {
+                if !ek.get_state().contains(ModifierType::MOD1_MASK) {
+                    return Inhibit(false);
+                }
+                match ek.get_keyval() {
+                    key::Up => diff_view.go_to_hunk(false),
+                    key::Down => diff_view.go_to_hunk(true),
+                    _ => return Inhibit(false),
+                }
+                Inhibit(true)
+            }));
+
+        diff_view.window.show_all();
+        diff_view.go_to_hunk(true);
+    }
+
+    /// Scrolls to the next (or previous if `next` is false) change
+    fn go_to_hunk(&self, next: bool) {
+        if self.hunks.is_empty() {
+            return;
+        }
+        let current = match (self.current.get(), next) {
+            (None, _) => 0,
+            (Some(ix), true) => (ix + 1).min(self.hunks.len() - 1),
+            (Some(ix), false) => ix.saturating_sub(1),
+        };
+        self.current.set(Some(current));
+        self.update_position();
+
+        let text = &self.left.text;
+        if let Some(buffer) = text.get_buffer() {
+            let iter = buffer.get_iter_at_line(self.hunks[current] as i32);
+            if let Some(mark) = buffer.create_mark(None, &iter, true) {
+                text.scroll_to_mark(&mark, 0.0, true, 0.0, HUNK_ALIGN);
+                buffer.delete_mark(&mark);
+            }
+        }
+    }
+
+    fn update_position(&self) {
+        let text = match (self.hunks.len(), self.current.get()) {
+            (0, _) => gettext("No differences"),
+            (n, None) => format!("{} {}", n, gettext("changes")),
+            (n, Some(ix)) => format!("{} {} {} {}", gettext("Change"), ix + 1, gettext("of"), n),
+        };
+        self.position_label.set_text(&text);
+    }
+}
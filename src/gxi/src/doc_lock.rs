@@ -0,0 +1,174 @@
+use gettextrs::gettext;
+use log::{debug, warn};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The process holding a lock on a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockOwner {
+    pub user: String,
+    pub host: String,
+    pub pid: u32,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            user: glib::get_user_name()
+                .map(|u| u.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            host: glib::get_host_name()
+                .map(|h| h.to_string())
+                .unwrap_or_default(),
+            pid: std::process::id(),
+        }
+    }
+
+    /// Parses the content of a lock file, which looks like `user@host.pid`. Emacs appends
+    /// `:boot_time` to this, which we ignore.
+    fn parse(content: &str) -> Option<Self> {
+        let content = content.trim().split(':').next()?;
+        let at = content.find('@')?;
+        let dot = content.rfind('.')?;
+        if dot < at {
+            return None;
+        }
+
+        Some(Self {
+            user: content[..at].to_string(),
+            host: content[at + 1..dot].to_string(),
+            pid: content[dot + 1..].parse().ok()?,
+        })
+    }
+
+    fn is_current(&self) -> bool {
+        *self == Self::current()
+    }
+
+    /// Checks if the process holding the lock is still running. We can only check this for
+    /// processes on our host, locks of other hosts (e.g. on NFS shares) are assumed to be valid.
+    fn is_alive(&self) -> bool {
+        if self.host != Self::current().host {
+            return true;
+        }
+        Self::pid_exists(self.pid)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn pid_exists(pid: u32) -> bool {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+
+    /// Only Linux has `/proc` to look the process up in, elsewhere we play it safe
+    #[cfg(not(target_os = "linux"))]
+    fn pid_exists(_pid: u32) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{}.{}", self.user, self.host, self.pid)
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// Another (running) process holds the lock
+    HeldBy(LockOwner),
+    /// We couldn't create the lock file, e.g. because the directory isn't writable
+    Io(io::Error),
+}
+
+/// A lock on a document, which tells other instances of gxi that we're editing it. The lock is
+/// a file next to the document, named like the ones Emacs uses, so Emacs respects it too.
+/// It's removed once the `DocLock` is dropped.
+#[derive(Debug)]
+pub struct DocLock {
+    path: PathBuf,
+}
+
+impl DocLock {
+    /// Locks `file`, unless another running process holds a lock on it already.
+    pub fn acquire(file: &Path) -> Result<Self, LockError> {
+        if let Some(owner) = lock_owner(file) {
+            if !owner.is_current() && owner.is_alive() {
+                return Err(LockError::HeldBy(owner));
+            }
+            debug!("{} {}", gettext("Replacing stale lock of"), owner);
+        }
+
+        Self::steal(file).map_err(LockError::Io)
+    }
+
+    /// Locks `file`, even if another process holds a lock on it.
+    pub fn steal(file: &Path) -> io::Result<Self> {
+        let path = lock_path(file);
+        // Emacs' lock files are symlinks, make sure we don't write to the file they point to
+        if fs::symlink_metadata(&path).is_ok() {
+            fs::remove_file(&path)?;
+        }
+        fs::write(&path, LockOwner::current().to_string())?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for DocLock {
+    fn drop(&mut self) {
+        // Only remove the lock if nobody stole it in the meantime
+        if read_lock(&self.path).map_or(false, |owner| owner.is_current()) {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!(
+                    "{} {:?}: {}",
+                    gettext("Failed to remove lock file"),
+                    self.path,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Path of the lock file for `file`, e.g. `/home/user/.#foo.txt` for `/home/user/foo.txt`
+fn lock_path(file: &Path) -> PathBuf {
+    let name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    file.with_file_name(format!(".#{}", name))
+}
+
+fn read_lock(path: &Path) -> Option<LockOwner> {
+    let content = fs::read_link(path)
+        .map(|target| target.to_string_lossy().into_owned())
+        .or_else(|_| fs::read_to_string(path))
+        .ok()?;
+    LockOwner::parse(&content)
+}
+
+/// Returns who holds the lock on `file`, if anyone does
+pub fn lock_owner(file: &Path) -> Option<LockOwner> {
+    read_lock(&lock_path(file))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_owner() {
+        assert_eq!(
+            LockOwner::parse("user@host.example.org.1234:1559740101"),
+            Some(LockOwner {
+                user: "user".to_string(),
+                host: "host.example.org".to_string(),
+                pid: 1234,
+            })
+        );
+        assert_eq!(LockOwner::parse("garbage"), None);
+        assert_eq!(LockOwner::parse("user.name@host"), None);
+    }
+}
@@ -2,10 +2,37 @@ use gettextrs::gettext;
 use gio::prelude::*;
 use gtk::prelude::*;
 use gtk::*;
+use gxi_editview::infobars::InfoBars;
 use gxi_peer::ErrorMsg;
 use log::error;
+use std::cell::RefCell;
+use std::rc::Rc;
 
-/// A simple `ErrorDialog` used for if stuff goes south.
+thread_local! {
+    /// Where `show_error` shows non-fatal errors, once the window has been set up
+    static INFOBARS: RefCell<Option<Rc<InfoBars>>> = RefCell::new(None);
+}
+
+/// Shows non-fatal errors passed to `show_error` in `infobars` from now on
+pub fn set_infobars(infobars: &Rc<InfoBars>) {
+    INFOBARS.with(|cell| cell.replace(Some(infobars.clone())));
+}
+
+/// Shows `err_msg` to the user. Fatal errors get an `ErrorDialog`, others are shown in an infobar
+/// of the window (unless it hasn't been set up yet) so they don't interrupt the user.
+pub fn show_error(err_msg: ErrorMsg) {
+    if !err_msg.fatal {
+        let infobars = INFOBARS.with(|cell| cell.borrow().clone());
+        if let Some(infobars) = infobars {
+            infobars.show(&err_msg.msg, MessageType::Error);
+            return;
+        }
+    }
+    ErrorDialog::new(err_msg);
+}
+
+/// A simple `ErrorDialog` used for if stuff goes south. Only used for fatal errors once the
+/// window has been set up, see `show_error`.
 pub struct ErrorDialog {
     pub dialog: MessageDialog,
     pub msg: ErrorMsg,
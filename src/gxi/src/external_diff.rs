@@ -0,0 +1,57 @@
+use gettextrs::gettext;
+use gxi_peer::Sandbox;
+use log::{debug, warn};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Writes `content` to a temporary file for the diff tool. The file is named after `name`, so the
+/// tool shows something recognizable as title.
+pub fn temp_file(name: &str, content: &str) -> io::Result<PathBuf> {
+    let dir = Sandbox::detect()
+        .shared_tmp_dir()
+        .join(format!("gxi-diff-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(name);
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Opens `left` and `right` in the diff tool `tool`, e.g. `meld` or `kdiff3 --auto`. `temp_files`
+/// are removed once the tool exits.
+pub fn open(tool: &str, left: &Path, right: &Path, temp_files: Vec<PathBuf>) -> io::Result<()> {
+    let mut args = tool.split_whitespace();
+    let program = args.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            gettext("No external diff tool has been set"),
+        )
+    })?;
+
+    debug!(
+        "{} '{}' {:?} {:?}",
+        gettext("Running diff tool"),
+        tool,
+        left,
+        right
+    );
+    let mut child = Sandbox::detect()
+        .host_command(program)
+        .args(args)
+        .arg(left)
+        .arg(right)
+        .spawn()?;
+
+    thread::spawn(move || {
+        if let Err(e) = child.wait() {
+            warn!("{}: {}", gettext("Failed to wait for diff tool"), e);
+        }
+        for file in temp_files {
+            let _ = fs::remove_file(file);
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! A popover showing a few lines of a file, so search results can be peeked at without opening a
+//! tab for each of them. The lines are read in the background once the pointer has rested on a
+//! result for a moment.
+
+use glib::{source, MainContext, SourceId};
+use gtk::*;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::rc::Rc;
+use std::thread;
+
+/// How long (in milliseconds) the pointer has to rest on a result before its preview is shown
+const HOVER_DELAY: u32 = 400;
+/// How many lines before and after the line of the result are shown
+const CONTEXT_LINES: u32 = 3;
+/// Longer lines are cut off, the preview shouldn't cover the whole screen
+const MAX_LINE_LEN: usize = 120;
+
+/// Reads the lines around the (zero based) `line` of the file at `path`. Returns the number of the
+/// first line read and the lines, or `None` if the file can't be read or isn't UTF-8.
+fn read_context(path: &str, line: u32) -> Option<(u32, Vec<String>)> {
+    let file = fs::File::open(path).ok()?;
+    let first = line.saturating_sub(CONTEXT_LINES);
+    let lines = BufReader::new(file)
+        .lines()
+        .skip(first as usize)
+        .take((line - first + CONTEXT_LINES + 1) as usize)
+        .collect::<Result<Vec<String>, _>>()
+        .ok()?;
+    Some((first, lines))
+}
+
+/// Returns the Pango markup for `lines`, which start at the zero based line `first`, with line
+/// numbers and the line `highlighted` in bold
+fn markup(first: u32, lines: &[String], highlighted: u32) -> String {
+    let width = (first as usize + lines.len()).to_string().len();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let num = first + i as u32;
+            let text: String = text
+                .replace('\t', "    ")
+                .chars()
+                .take(MAX_LINE_LEN)
+                .collect();
+            let line = format!(
+                "{:>width$}  {}",
+                num + 1,
+                glib::markup_escape_text(&text),
+                width = width
+            );
+            if num == highlighted {
+                format!("<b>{}</b>", line)
+            } else {
+                format!("<span alpha=\"70%\">{}</span>", line)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub struct FilePreview {
+    popover: Popover,
+    label: Label,
+    /// The file and line whose preview is shown, or about to be shown
+    shown: Rc<RefCell<Option<(String, u32)>>>,
+    /// The timeout which reads the lines once the pointer has rested long enough
+    timeout: Rc<RefCell<Option<SourceId>>>,
+}
+
+impl FilePreview {
+    /// Creates the (hidden) preview, which points at `relative_to`
+    pub fn new<W: IsA<Widget>>(relative_to: &W) -> Rc<Self> {
+        let popover = Popover::new(Some(relative_to));
+        popover.set_modal(false);
+        let label = Label::new(None);
+        label.set_xalign(0.0);
+        label.get_style_context().add_class("monospace");
+        label.set_margin_start(6);
+        label.set_margin_end(6);
+        label.set_margin_top(6);
+        label.set_margin_bottom(6);
+        label.show();
+        popover.add(&label);
+
+        Rc::new(Self {
+            popover,
+            label,
+            shown: Rc::new(RefCell::new(None)),
+            timeout: Rc::new(RefCell::new(None)),
+        })
+    }
+
+    /// Shows the lines around the (zero based) `line` of the file at `path` next to `rect` (in
+    /// coordinates of the widget the preview points at), unless `hide` is called within the next
+    /// `HOVER_DELAY` milliseconds
+    pub fn show_later(preview: &Rc<Self>, path: &str, line: u32, rect: gdk::Rectangle) {
+        let target = Some((path.to_string(), line));
+        if *preview.shown.borrow() == target {
+            return;
+        }
+        preview.hide();
+        preview.shown.replace(target);
+
+        let path = path.to_string();
+        let id = glib::timeout_add_local(
+            HOVER_DELAY,
+            enclose!((preview) move || {
+                preview.timeout.replace(None);
+                Self::load(&preview, path.clone(), line, rect);
+                source::Continue(false)
+            }),
+        );
+        preview.timeout.replace(Some(id));
+    }
+
+    /// Reads the lines in the background and shows them if the preview is still wanted then
+    fn load(preview: &Rc<Self>, path: String, line: u32, rect: gdk::Rectangle) {
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        let target = Some((path.clone(), line));
+        let preview = preview.clone();
+        rx.attach(
+            Some(&MainContext::default()),
+            move |context: Option<(u32, Vec<String>)>| {
+                if *preview.shown.borrow() == target {
+                    if let Some((first, lines)) = context {
+                        preview.label.set_markup(&markup(first, &lines, line));
+                        preview.popover.set_pointing_to(&rect);
+                        #[cfg(feature = "gtk_v3_22")]
+                        preview.popover.popup();
+                        #[cfg(not(feature = "gtk_v3_22"))]
+                        preview.popover.show();
+                    }
+                }
+                source::Continue(false)
+            },
+        );
+        thread::spawn(move || {
+            tx.send(read_context(&path, line)).ok();
+        });
+    }
+
+    pub fn hide(&self) {
+        self.shown.replace(None);
+        if let Some(id) = self.timeout.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+        self.popover.hide();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preview_markup() {
+        let lines = vec!["fn main() {".to_string(), "\tlet a = 1 < 2;".to_string()];
+        assert_eq!(
+            markup(8, &lines, 9),
+            "<span alpha=\"70%\"> 9  fn main() {</span>\n<b>10      let a = 1 &lt; 2;</b>"
+        );
+    }
+}
@@ -0,0 +1,682 @@
+//! A sidebar showing the files of a folder the user has chosen, e.g. the project they're working
+//! on. Directories are only read once they're expanded, so large folders open quickly. Files and
+//! directories can be created, renamed (right in the tree) and moved to the trash from here.
+
+use crate::errors;
+use crate::panels::{Panels, Slot};
+use gettextrs::gettext;
+use gio::{Cancellable, FileExt};
+use glib::Type;
+use gtk::*;
+use gxi_peer::ErrorMsg;
+use log::debug;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Name the panel is registered under, for the `app.toggle_panel` action
+pub const PANEL_NAME: &str = "file-tree";
+
+const ICON_COLUMN: u32 = 0;
+const NAME_COLUMN: u32 = 1;
+/// The path of the entry, or an empty string for the placeholder row of directories which haven't
+/// been read yet
+const PATH_COLUMN: u32 = 2;
+const IS_DIR_COLUMN: u32 = 3;
+
+/// Sorts directories first, then files, each by name
+fn sort_entries(entries: &mut Vec<(String, bool)>) {
+    entries.sort_by_key(|(name, is_dir)| (!is_dir, name.to_lowercase(), name.clone()));
+}
+
+/// The names of the entries of `dir` and whether they're directories. Hidden entries are left
+/// out.
+fn entries(dir: &Path) -> Vec<(String, bool)> {
+    let mut entries: Vec<(String, bool)> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| {
+                    (
+                        entry.file_name().to_string_lossy().into_owned(),
+                        entry.path().is_dir(),
+                    )
+                })
+                .filter(|(name, _)| !name.starts_with('.'))
+                .collect()
+        })
+        .unwrap_or_default();
+    sort_entries(&mut entries);
+    entries
+}
+
+/// How the `shown` entries of a directory have to be changed to match the ones `on_disk`, both
+/// sorted by `sort_entries`: the indices of the shown entries which are gone and the indices of
+/// the entries on disk which are new
+fn changes(shown: &[(String, bool)], on_disk: &[(String, bool)]) -> (Vec<usize>, Vec<usize>) {
+    let gone = (0..shown.len())
+        .filter(|&ix| !on_disk.contains(&shown[ix]))
+        .collect();
+    let new = (0..on_disk.len())
+        .filter(|&ix| !shown.contains(&on_disk[ix]))
+        .collect();
+    (gone, new)
+}
+
+/// Returns `base`, or `base` with a number appended if `exists` says it's taken already
+fn unique_name<F: Fn(&str) -> bool>(base: &str, exists: F) -> String {
+    (1..)
+        .map(|i| {
+            if i == 1 {
+                base.to_string()
+            } else {
+                format!("{} {}", base, i)
+            }
+        })
+        .find(|name| !exists(name))
+        .unwrap()
+}
+
+fn icon_name(path: &Path, is_dir: bool) -> String {
+    if is_dir {
+        return "folder-symbolic".to_string();
+    }
+    let (content_type, _) = gio::content_type_guess(Some(path), &[]);
+    gio::content_type_get_generic_icon_name(&content_type)
+        .map_or_else(|| "text-x-generic".to_string(), |name| name.to_string())
+        + "-symbolic"
+}
+
+fn icon_button(icon_name: &str, tooltip: &str) -> Button {
+    let button = Button::new_from_icon_name(Some(icon_name), IconSize::Menu);
+    button.set_relief(ReliefStyle::None);
+    button.set_tooltip_text(Some(tooltip));
+    button
+}
+
+pub struct FileTree {
+    root: Box,
+    folder_label: Label,
+    tree_store: TreeStore,
+    treeview: TreeView,
+    name_column: TreeViewColumn,
+    name_renderer: CellRendererText,
+    /// The folder the tree is rooted at
+    folder: RefCell<Option<PathBuf>>,
+    /// Called with the path of files which have been activated
+    open_handler: RefCell<Option<Rc<dyn Fn(&str)>>>,
+    /// Called with the old and new path of entries which have been renamed
+    rename_handler: RefCell<Option<Rc<dyn Fn(&Path, &Path)>>>,
+}
+
+impl FileTree {
+    pub fn new(panels: &Panels) -> Rc<Self> {
+        let tree_store = TreeStore::new(&[Type::String, Type::String, Type::String, Type::Bool]);
+        let treeview = TreeView::new_with_model(&tree_store);
+        treeview.set_headers_visible(false);
+        treeview.set_search_column(NAME_COLUMN as i32);
+
+        let name_column = TreeViewColumn::new();
+        let icon_renderer = CellRendererPixbuf::new();
+        name_column.pack_start(&icon_renderer, false);
+        name_column.add_attribute(&icon_renderer, "icon-name", ICON_COLUMN as i32);
+        let name_renderer = CellRendererText::new();
+        name_renderer.set_property_ellipsize(pango::EllipsizeMode::End);
+        name_column.pack_start(&name_renderer, true);
+        name_column.add_attribute(&name_renderer, "text", NAME_COLUMN as i32);
+        treeview.append_column(&name_column);
+
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.add(&treeview);
+
+        let folder_label = Label::new(Some(gettext("No folder open").as_str()));
+        folder_label.set_ellipsize(pango::EllipsizeMode::Middle);
+        folder_label.set_xalign(0.0);
+        folder_label.set_margin_start(6);
+        let choose_button = icon_button("folder-open-symbolic", &gettext("Open Folder…"));
+        let new_file_button = icon_button("document-new-symbolic", &gettext("New File"));
+        let new_folder_button = icon_button("folder-new-symbolic", &gettext("New Folder"));
+        let rename_button = icon_button("document-edit-symbolic", &gettext("Rename"));
+        let trash_button = icon_button("user-trash-symbolic", &gettext("Move to Trash"));
+        let header = Box::new(Orientation::Horizontal, 0);
+        header.pack_start(&folder_label, true, true, 0);
+        for button in &[
+            &choose_button,
+            &new_file_button,
+            &new_folder_button,
+            &rename_button,
+            &trash_button,
+        ] {
+            header.pack_start(*button, false, false, 0);
+        }
+
+        let root = Box::new(Orientation::Vertical, 0);
+        root.pack_start(&header, false, false, 0);
+        root.pack_start(&scrolled_window, true, true, 0);
+        root.show_all();
+
+        let file_tree = Rc::new(Self {
+            root,
+            folder_label,
+            tree_store,
+            treeview,
+            name_column,
+            name_renderer,
+            folder: RefCell::new(None),
+            open_handler: RefCell::new(None),
+            rename_handler: RefCell::new(None),
+        });
+
+        file_tree
+            .treeview
+            .connect_test_expand_row(enclose!((file_tree) move |_, iter, _| {
+                file_tree.load_children(iter);
+                Inhibit(false)
+            }));
+        file_tree
+            .treeview
+            .connect_row_activated(enclose!((file_tree) move |tv, path, _| {
+                let (entry, is_dir) = match file_tree.entry_at(path) {
+                    Some(entry) => entry,
+                    None => return,
+                };
+                if is_dir {
+                    if tv.row_expanded(path) {
+                        tv.collapse_row(path);
+                    } else {
+                        tv.expand_row(path, false);
+                    }
+                } else if let Some(handler) = &*file_tree.open_handler.borrow() {
+                    handler(&entry.to_string_lossy());
+                }
+            }));
+        file_tree
+            .treeview
+            .connect_key_press_event(enclose!((file_tree) move |_, ev| {
+                if ev.get_keyval() == gdk::enums::key::Delete {
+                    Self::trash_selected(&file_tree);
+                    Inhibit(true)
+                } else {
+                    Inhibit(false)
+                }
+            }));
+        file_tree.name_renderer.connect_edited(
+            enclose!((file_tree) move |renderer, path, new_name| {
+                renderer.set_property_editable(false);
+                file_tree.rename(&path, new_name);
+            }),
+        );
+        file_tree
+            .name_renderer
+            .connect_editing_canceled(|renderer| renderer.set_property_editable(false));
+
+        choose_button
+            .connect_clicked(enclose!((file_tree) move |_| Self::choose_folder(&file_tree)));
+        new_file_button.connect_clicked(enclose!((file_tree) move |_| file_tree.create(false)));
+        new_folder_button.connect_clicked(enclose!((file_tree) move |_| file_tree.create(true)));
+        rename_button.connect_clicked(enclose!((file_tree) move |_| file_tree.start_rename()));
+        trash_button
+            .connect_clicked(enclose!((file_tree) move |_| Self::trash_selected(&file_tree)));
+
+        panels.register(PANEL_NAME, &gettext("Files"), Slot::Left, &file_tree.root);
+
+        file_tree
+    }
+
+    /// Sets the function which is called with the path of files the user activates
+    pub fn connect_open<F: Fn(&str) + 'static>(&self, f: F) {
+        self.open_handler.replace(Some(Rc::new(f)));
+    }
+
+    /// Sets the function which is called with the old and new path of files and directories the
+    /// user renames
+    pub fn connect_rename<F: Fn(&Path, &Path) + 'static>(&self, f: F) {
+        self.rename_handler.replace(Some(Rc::new(f)));
+    }
+
+    /// The folder the tree is rooted at
+    pub fn folder(&self) -> Option<PathBuf> {
+        self.folder.borrow().clone()
+    }
+
+    /// Roots the tree at `folder`
+    pub fn set_folder(&self, folder: &Path) {
+        debug!("{} {:?}", gettext("Showing files of"), folder);
+        self.folder.replace(Some(folder.to_path_buf()));
+        self.folder_label.set_text(
+            &folder
+                .file_name()
+                .map_or_else(|| folder.to_string_lossy(), |name| name.to_string_lossy()),
+        );
+        self.folder_label
+            .set_tooltip_text(Some(folder.to_string_lossy().as_ref()));
+        self.tree_store.clear();
+        self.fill(None, folder);
+    }
+
+    /// Lets the user choose the folder the tree is rooted at
+    fn choose_folder(file_tree: &Rc<Self>) {
+        let window = file_tree
+            .root
+            .get_toplevel()
+            .and_then(|toplevel| toplevel.downcast::<Window>().ok());
+        let fcn = FileChooserNative::new(
+            Some(gettext("Open Folder").as_str()),
+            window.as_ref(),
+            FileChooserAction::SelectFolder,
+            Some(gettext("Open").as_str()),
+            Some(gettext("Cancel").as_str()),
+        );
+        if let Some(folder) = file_tree.folder() {
+            fcn.set_current_folder(&folder);
+        }
+        fcn.connect_response(enclose!((file_tree) move |fcn, res| {
+            if res == ResponseType::Accept {
+                if let Some(folder) = fcn.get_filename() {
+                    file_tree.set_folder(&folder);
+                }
+            }
+        }));
+        fcn.run();
+    }
+
+    /// Adds the entries of `dir` below `parent`
+    fn fill(&self, parent: Option<&TreeIter>, dir: &Path) {
+        for (name, is_dir) in entries(dir) {
+            self.insert(parent, None, dir, &name, is_dir);
+        }
+    }
+
+    /// Adds the entry `name` of `dir` below `parent` at `position` (or at the end). Directories
+    /// get a placeholder child, so they can be expanded before they've been read.
+    fn insert(
+        &self,
+        parent: Option<&TreeIter>,
+        position: Option<u32>,
+        dir: &Path,
+        name: &str,
+        is_dir: bool,
+    ) {
+        let path = dir.join(name);
+        let iter = self.tree_store.insert_with_values(
+            parent,
+            position,
+            &[ICON_COLUMN, NAME_COLUMN, PATH_COLUMN, IS_DIR_COLUMN],
+            &[
+                &icon_name(&path, is_dir),
+                &name,
+                &path.to_string_lossy().into_owned(),
+                &is_dir,
+            ],
+        );
+        if is_dir {
+            self.tree_store.insert_with_values(
+                Some(&iter),
+                None,
+                &[PATH_COLUMN, IS_DIR_COLUMN],
+                &[&"", &false],
+            );
+        }
+    }
+
+    /// Reads the directory of `iter` if it hasn't been read yet
+    fn load_children(&self, iter: &TreeIter) {
+        let placeholder = match self.tree_store.iter_children(Some(iter)) {
+            Some(child) => child,
+            None => return,
+        };
+        let loaded = self
+            .tree_store
+            .get_value(&placeholder, PATH_COLUMN as i32)
+            .get::<String>()
+            .map_or(false, |path| !path.is_empty());
+        if loaded {
+            return;
+        }
+        self.tree_store.remove(&placeholder);
+        if let Some(dir) = self.path_of(iter) {
+            self.fill(Some(iter), &dir);
+        }
+    }
+
+    /// Reads the directory of `iter` (or the folder, if it's None) again, e.g. after an entry has
+    /// been added to it
+    fn reload(&self, iter: Option<&TreeIter>) {
+        let dir = match iter {
+            Some(iter) => self.path_of(iter),
+            None => self.folder(),
+        };
+        let dir = match dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        while let Some(child) = self.tree_store.iter_children(iter) {
+            self.tree_store.remove(&child);
+        }
+        self.fill(iter, &dir);
+    }
+
+    /// Adds and removes the entries which have been created or deleted by other programs. Unlike
+    /// `reload` it keeps expanded directories expanded.
+    pub fn refresh(&self) {
+        if let Some(folder) = self.folder() {
+            self.update(None, &folder);
+        }
+    }
+
+    /// Makes the entries below `parent` match the ones of `dir` on disk, and those of the
+    /// directories below it which have been read already
+    fn update(&self, parent: Option<&TreeIter>, dir: &Path) {
+        let children = self.children(parent);
+        let shown: Vec<(String, bool)> = children.iter().map(|(_, entry)| entry.clone()).collect();
+        let on_disk = entries(dir);
+        let (gone, new) = changes(&shown, &on_disk);
+        for &ix in gone.iter().rev() {
+            self.tree_store.remove(&children[ix].0);
+        }
+        for ix in new {
+            let (name, is_dir) = &on_disk[ix];
+            self.insert(parent, Some(ix as u32), dir, name, *is_dir);
+        }
+
+        for (iter, (name, is_dir)) in self.children(parent) {
+            // Directories which haven't been read yet only have the placeholder
+            let read = self
+                .tree_store
+                .iter_children(Some(&iter))
+                .map_or(true, |child| self.path_of(&child).is_some());
+            if is_dir && read {
+                self.update(Some(&iter), &dir.join(name));
+            }
+        }
+    }
+
+    /// The rows below `parent` with the entries they show, without placeholders
+    fn children(&self, parent: Option<&TreeIter>) -> Vec<(TreeIter, (String, bool))> {
+        let mut children = Vec::new();
+        let iter = match self.tree_store.iter_children(parent) {
+            Some(iter) => iter,
+            None => return children,
+        };
+        loop {
+            if self.path_of(&iter).is_some() {
+                let name = self
+                    .tree_store
+                    .get_value(&iter, NAME_COLUMN as i32)
+                    .get::<String>()
+                    .unwrap_or_default();
+                let is_dir = self
+                    .tree_store
+                    .get_value(&iter, IS_DIR_COLUMN as i32)
+                    .get::<bool>()
+                    .unwrap_or(false);
+                children.push((iter.clone(), (name, is_dir)));
+            }
+            if !self.tree_store.iter_next(&iter) {
+                return children;
+            }
+        }
+    }
+
+    fn path_of(&self, iter: &TreeIter) -> Option<PathBuf> {
+        self.tree_store
+            .get_value(iter, PATH_COLUMN as i32)
+            .get::<String>()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// The path of the entry at the row `path` and whether it's a directory
+    fn entry_at(&self, path: &TreePath) -> Option<(PathBuf, bool)> {
+        let iter = self.tree_store.get_iter(path)?;
+        let is_dir = self
+            .tree_store
+            .get_value(&iter, IS_DIR_COLUMN as i32)
+            .get::<bool>()
+            .unwrap_or(false);
+        Some((self.path_of(&iter)?, is_dir))
+    }
+
+    fn selected(&self) -> Option<TreeIter> {
+        self.treeview
+            .get_selection()
+            .get_selected()
+            .map(|(_, iter)| iter)
+    }
+
+    /// Finds the row of the entry at `path` below `parent`
+    fn find_child(&self, parent: Option<&TreeIter>, path: &Path) -> Option<TreeIter> {
+        let iter = self.tree_store.iter_children(parent)?;
+        loop {
+            if self.path_of(&iter).as_ref().map(PathBuf::as_path) == Some(path) {
+                return Some(iter);
+            }
+            if !self.tree_store.iter_next(&iter) {
+                return None;
+            }
+        }
+    }
+
+    /// Lets the user type a new name for the selected entry
+    fn start_rename(&self) {
+        if let Some(iter) = self.selected() {
+            self.edit_name(&iter);
+        }
+    }
+
+    fn edit_name(&self, iter: &TreeIter) {
+        let path = self.tree_store.get_path(iter);
+        self.name_renderer.set_property_editable(true);
+        self.treeview
+            .set_cursor(&path, Some(&self.name_column), true);
+    }
+
+    /// Renames the entry at the row `path` to `new_name`
+    fn rename(&self, path: &TreePath, new_name: &str) {
+        let iter = match self.tree_store.get_iter(path) {
+            Some(iter) => iter,
+            None => return,
+        };
+        let old_path = match self.path_of(&iter) {
+            Some(old_path) => old_path,
+            None => return,
+        };
+        let new_path = old_path.with_file_name(new_name);
+        if new_name.is_empty() || new_name.contains('/') || new_path == old_path {
+            return;
+        }
+        if new_path.exists() {
+            errors::show_error(ErrorMsg {
+                msg: format!("'{}' {}", new_name, gettext("exists already")),
+                fatal: false,
+            });
+            return;
+        }
+        debug!(
+            "{} {:?} {} {:?}",
+            gettext("Renaming"),
+            old_path,
+            gettext("to"),
+            new_path
+        );
+        if let Err(e) = fs::rename(&old_path, &new_path) {
+            errors::show_error(ErrorMsg {
+                msg: format!(
+                    "{} '{}': {}",
+                    gettext("Couldn't rename"),
+                    old_path.display(),
+                    e
+                ),
+                fatal: false,
+            });
+            return;
+        }
+
+        let parent = self.tree_store.iter_parent(&iter);
+        self.reload(parent.as_ref());
+        if let Some(iter) = self.find_child(parent.as_ref(), &new_path) {
+            self.treeview.get_selection().select_iter(&iter);
+        }
+        if let Some(handler) = &*self.rename_handler.borrow() {
+            handler(&old_path, &new_path);
+        }
+    }
+
+    /// Creates a new file (or directory) next to the selected entry, or in it if it's an
+    /// expanded directory, and lets the user name it
+    fn create(&self, is_dir: bool) {
+        let selected = self.selected();
+        let parent = match &selected {
+            Some(iter) => {
+                let row = self.tree_store.get_path(iter);
+                if self.treeview.row_expanded(&row) {
+                    Some(iter.clone())
+                } else {
+                    self.tree_store.iter_parent(iter)
+                }
+            }
+            None => None,
+        };
+        let dir = match parent
+            .as_ref()
+            .map_or_else(|| self.folder(), |p| self.path_of(p))
+        {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        let base = if is_dir {
+            gettext("New Folder")
+        } else {
+            gettext("New File")
+        };
+        let path = dir.join(unique_name(&base, |name| dir.join(name).exists()));
+        let res = if is_dir {
+            fs::create_dir(&path)
+        } else {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map(|_| ())
+        };
+        if let Err(e) = res {
+            errors::show_error(ErrorMsg {
+                msg: format!("{} '{}': {}", gettext("Couldn't create"), path.display(), e),
+                fatal: false,
+            });
+            return;
+        }
+
+        self.reload(parent.as_ref());
+        if let Some(iter) = self.find_child(parent.as_ref(), &path) {
+            self.edit_name(&iter);
+        }
+    }
+
+    /// Asks whether the selected entry should be moved to the trash and does so
+    fn trash_selected(file_tree: &Rc<Self>) {
+        let iter = match file_tree.selected() {
+            Some(iter) => iter,
+            None => return,
+        };
+        let path = match file_tree.path_of(&iter) {
+            Some(path) => path,
+            None => return,
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let window = file_tree
+            .root
+            .get_toplevel()
+            .and_then(|toplevel| toplevel.downcast::<Window>().ok());
+        let dialog = MessageDialog::new(
+            window.as_ref(),
+            DialogFlags::all(),
+            MessageType::Question,
+            ButtonsType::None,
+            &format!("{} '{}'?", gettext("Move to the trash:"), name),
+        );
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog
+            .add_button(&gettext("Move to Trash"), ResponseType::Accept)
+            .get_style_context()
+            .add_class("destructive-action");
+        dialog.set_default_response(ResponseType::Cancel);
+        let res = dialog.run();
+        dialog.destroy();
+        if res != ResponseType::Accept {
+            return;
+        }
+
+        debug!("{} {:?}", gettext("Moving to the trash"), path);
+        match gio::File::new_for_path(&path).trash(None::<&Cancellable>) {
+            Ok(()) => {
+                file_tree.tree_store.remove(&iter);
+            }
+            Err(e) => errors::show_error(ErrorMsg {
+                msg: format!(
+                    "{} '{}': {}",
+                    gettext("Couldn't move to the trash"),
+                    path.display(),
+                    e
+                ),
+                fatal: false,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sorted_entries() {
+        let mut entries = vec![
+            ("main.rs".to_string(), false),
+            ("src".to_string(), true),
+            ("Cargo.toml".to_string(), false),
+            ("benches".to_string(), true),
+        ];
+        sort_entries(&mut entries);
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["benches", "src", "Cargo.toml", "main.rs"]);
+    }
+
+    #[test]
+    fn entry_changes() {
+        let entry = |name: &str, is_dir| (name.to_string(), is_dir);
+        let shown = vec![
+            entry("src", true),
+            entry("build.rs", false),
+            entry("main.rs", false),
+        ];
+        let on_disk = vec![
+            entry("src", true),
+            entry("tests", true),
+            entry("main.rs", false),
+            entry("README.md", false),
+        ];
+        assert_eq!(changes(&shown, &on_disk), (vec![1], vec![1, 3]));
+        assert_eq!(changes(&on_disk, &on_disk), (vec![], vec![]));
+    }
+
+    #[test]
+    fn unique_names() {
+        let taken = ["New File", "New File 2"];
+        assert_eq!(
+            unique_name("New File", |name| taken.contains(&name)),
+            "New File 3"
+        );
+        assert_eq!(
+            unique_name("New Folder", |name| taken.contains(&name)),
+            "New Folder"
+        );
+    }
+}
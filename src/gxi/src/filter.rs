@@ -0,0 +1,75 @@
+//! Pipes text through shell commands like `sort`, `jq .` or `base64`, for "Filter Selection
+//! Through Command…".
+
+use gettextrs::gettext;
+use gxi_peer::Sandbox;
+use log::debug;
+use std::io::Write;
+use std::process::Stdio;
+use std::thread;
+
+/// How many of the recently used commands are remembered
+pub const MAX_RECENT_COMMANDS: usize = 10;
+
+/// Runs `command` with `sh -c`, writes `input` to its stdin and returns what it wrote to stdout.
+/// If the command fails, what it wrote to stderr is returned as error.
+pub fn run(command: &str, input: String) -> Result<String, String> {
+    debug!("{} '{}'", gettext("Filtering text through"), command);
+    let mut child = Sandbox::detect()
+        .host_command("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // The command may fill up the stdout pipe before it has read all of its input, so we have to
+    // write to it while we read its output
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    // Commands like `head` don't read all of their input, that's fine
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            output.status.to_string()
+        } else {
+            stderr
+        });
+    }
+    String::from_utf8(output.stdout).map_err(|_| gettext("The output isn't valid UTF-8"))
+}
+
+/// Moves `command` to the front of the recently used `commands`
+pub fn remember(commands: &mut Vec<String>, command: &str) {
+    commands.retain(|c| c != command);
+    commands.insert(0, command.to_string());
+    commands.truncate(MAX_RECENT_COMMANDS);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        assert_eq!(run("sort", "b\na\n".to_string()), Ok("a\nb\n".to_string()));
+        assert!(run("exit 1", String::new()).is_err());
+    }
+
+    #[test]
+    fn recent_commands() {
+        let mut commands = vec!["sort".to_string(), "jq .".to_string()];
+        remember(&mut commands, "jq .");
+        assert_eq!(commands, vec!["jq .", "sort"]);
+        for i in 0..MAX_RECENT_COMMANDS {
+            remember(&mut commands, &i.to_string());
+        }
+        assert_eq!(commands.len(), MAX_RECENT_COMMANDS);
+        assert_eq!(commands[0], (MAX_RECENT_COMMANDS - 1).to_string());
+    }
+}
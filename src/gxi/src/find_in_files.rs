@@ -0,0 +1,871 @@
+use crate::file_preview::FilePreview;
+use crate::gxi_dirs;
+use crate::main_win::MainWin;
+use crate::progress::Progress;
+use crate::project::project_root_for;
+use crate::session::PanelState;
+use gettextrs::gettext;
+use glib::MainContext;
+use gtk::*;
+use log::{debug, trace, warn};
+use serde_derive::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const SRC: &str = include_str!("ui/find_in_files.glade");
+
+/// Name of the file (inside gxi's config dir) the per-project search scopes are saved in
+const SCOPES_FILE_NAME: &str = "search_scopes.json";
+
+/// Stop searching once we've found this many matches, the list isn't of much use beyond that
+const MAX_MATCHES: usize = 5000;
+
+/// The files a find in files search should look at.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SearchScope {
+    /// Glob patterns, e.g. `*.rs`. Patterns starting with `!` exclude files, e.g. `!target/**`
+    pub patterns: Vec<String>,
+    /// Whether files ignored by the project's `.gitignore` should be skipped
+    pub respect_gitignore: bool,
+}
+
+impl Default for SearchScope {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            respect_gitignore: true,
+        }
+    }
+}
+
+impl SearchScope {
+    /// Creates a `SearchScope` from a whitespace or comma separated list of glob patterns
+    pub fn parse(patterns: &str, respect_gitignore: bool) -> Self {
+        Self {
+            patterns: patterns
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|p| !p.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+            respect_gitignore,
+        }
+    }
+
+    pub fn patterns_string(&self) -> String {
+        self.patterns.join(" ")
+    }
+}
+
+fn scopes_file() -> Option<PathBuf> {
+    gxi_dirs::state_file(SCOPES_FILE_NAME)
+}
+
+fn load_scopes() -> HashMap<String, SearchScope> {
+    scopes_file()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Loads the `SearchScope` the user has last used for the project at `project_root`
+pub fn load_scope(project_root: &Path) -> SearchScope {
+    load_scopes()
+        .remove(&project_root.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Remembers `scope` for the project at `project_root`
+pub fn save_scope(project_root: &Path, scope: &SearchScope) {
+    let path = match scopes_file() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let mut scopes = load_scopes();
+    scopes.insert(project_root.to_string_lossy().into_owned(), scope.clone());
+
+    let res = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&scopes).unwrap()));
+    if let Err(e) = res {
+        warn!(
+            "{} {:?}: {}",
+            gettext("Failed to save search scopes to"),
+            path,
+            e
+        );
+    }
+}
+
+/// Checks if `path` (relative to the project root, with `/` as separator) matches `pattern`.
+/// `*` and `?` don't match `/`, while `**` matches across directories. Like in `.gitignore`
+/// files, patterns without a `/` are matched against the file name only.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches('/');
+    let pattern_chars: Vec<char> = pattern.chars().collect();
+
+    if pattern.contains('/') {
+        let path_chars: Vec<char> = path.chars().collect();
+        glob_match_from(&pattern_chars, &path_chars)
+    } else {
+        let path = path.trim_end_matches('/');
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let name_chars: Vec<char> = name.chars().collect();
+        glob_match_from(&pattern_chars, &name_chars)
+    }
+}
+
+fn glob_match_from(p: &[char], s: &[char]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            if p.get(2) == Some(&'/') {
+                // `**/` matches zero or more whole directories
+                let rest = &p[3..];
+                (0..=s.len()).any(|i| (i == 0 || s[i - 1] == '/') && glob_match_from(rest, &s[i..]))
+            } else {
+                let rest = &p[2..];
+                (0..=s.len()).any(|i| glob_match_from(rest, &s[i..]))
+            }
+        }
+        Some('*') => {
+            let rest = &p[1..];
+            for i in 0..=s.len() {
+                if glob_match_from(rest, &s[i..]) {
+                    return true;
+                }
+                if i < s.len() && s[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !s.is_empty() && s[0] != '/' && glob_match_from(&p[1..], &s[1..]),
+        Some(c) => !s.is_empty() && s[0] == *c && glob_match_from(&p[1..], &s[1..]),
+    }
+}
+
+/// Reads the patterns of the `.gitignore` in the project's root. Negated patterns aren't supported.
+fn read_gitignore(root: &Path) -> Vec<String> {
+    fs::read_to_string(root.join(".gitignore"))
+        .map(|content| {
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with('!'))
+                .map(|l| l.trim_end_matches('/').to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Decides which files and directories of a project are searched
+struct FileFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl FileFilter {
+    fn new(root: &Path, scope: &SearchScope) -> Self {
+        let mut include = Vec::new();
+        let mut exclude = vec![".git".to_string()];
+
+        for pattern in &scope.patterns {
+            if pattern.starts_with('!') {
+                exclude.push(pattern[1..].to_string());
+            } else {
+                include.push(pattern.clone());
+            }
+        }
+
+        if scope.respect_gitignore {
+            exclude.extend(read_gitignore(root));
+        }
+
+        Self { include, exclude }
+    }
+
+    fn is_excluded(&self, rel_path: &str) -> bool {
+        self.exclude.iter().any(|p| glob_matches(p, rel_path))
+    }
+
+    fn skips_dir(&self, rel_path: &str) -> bool {
+        self.is_excluded(rel_path) || self.is_excluded(&format!("{}/", rel_path))
+    }
+
+    fn searches_file(&self, rel_path: &str) -> bool {
+        !self.is_excluded(rel_path)
+            && (self.include.is_empty() || self.include.iter().any(|p| glob_matches(p, rel_path)))
+    }
+}
+
+/// Visits all files in `dir` which pass the `filter`. Stops once `visit` returns false.
+fn walk(
+    root: &Path,
+    dir: &Path,
+    filter: &FileFilter,
+    visit: &mut dyn FnMut(&Path, &str) -> bool,
+) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("{} {:?}: {}", gettext("Couldn't read directory"), dir, e);
+            return true;
+        }
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let rel_path = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace(std::path::MAIN_SEPARATOR, "/");
+        // Don't follow symlinks, they might lead to cycles
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+
+        if file_type.is_dir() {
+            if !filter.skips_dir(&rel_path) && !walk(root, &path, filter, visit) {
+                return false;
+            }
+        } else if file_type.is_file() && filter.searches_file(&rel_path) && !visit(&path, &rel_path)
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Lists up to `max` files of the project at `root` which aren't ignored by its `.gitignore`, with
+/// their paths relative to `root`
+pub fn list_files(root: &Path, max: usize) -> Vec<(PathBuf, String)> {
+    let filter = FileFilter::new(root, &SearchScope::default());
+    let mut files = Vec::new();
+    walk(root, root, &filter, &mut |path, rel_path| {
+        files.push((path.to_path_buf(), rel_path.to_string()));
+        files.len() < max
+    });
+    files
+}
+
+/// A line in a file which contains the text we're searching for
+pub struct SearchMatch {
+    pub path: String,
+    pub rel_path: String,
+    /// Zero based line number
+    pub line: u32,
+    pub text: String,
+}
+
+enum SearchMsg {
+    Match(SearchMatch),
+    Done(usize),
+}
+
+/// Searches `needle` in the file at `path`. Binary (non UTF-8) files don't yield any matches.
+fn search_file(path: &Path, rel_path: &str, needle: &str) -> Vec<SearchMatch> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut matches = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return Vec::new(),
+        };
+        if line.contains(needle) {
+            matches.push(SearchMatch {
+                path: path.to_string_lossy().into_owned(),
+                rel_path: rel_path.to_string(),
+                line: i as u32,
+                text: line.trim().to_string(),
+            });
+        }
+    }
+
+    matches
+}
+
+/// The lines of a file in which the text we're searching for is going to be replaced
+pub struct FileReplacement {
+    pub path: String,
+    pub rel_path: String,
+    /// Zero based line number, the line and what it's going to be replaced with
+    pub lines: Vec<(u32, String, String)>,
+}
+
+impl FileReplacement {
+    /// How many matches are going to be replaced
+    fn count(&self, needle: &str) -> usize {
+        self.lines
+            .iter()
+            .map(|(_, old, _)| old.matches(needle).count())
+            .sum()
+    }
+
+    /// The changes to the file as a unified diff, for previewing them
+    pub fn diff(&self) -> String {
+        let mut diff = format!("--- a/{0}\n+++ b/{0}\n", self.rel_path);
+        for (line, old, new) in &self.lines {
+            diff.push_str(&format!(
+                "@@ -{0} +{0} @@\n-{1}\n+{2}\n",
+                line + 1,
+                old,
+                new
+            ));
+        }
+        diff
+    }
+}
+
+/// Finds the lines of the file at `path` in which `needle` would be replaced by `replacement`.
+/// Binary (non UTF-8) files are left alone.
+fn replacements_in_file(
+    path: &Path,
+    rel_path: &str,
+    needle: &str,
+    replacement: &str,
+) -> Option<FileReplacement> {
+    let content = fs::read_to_string(path).ok()?;
+    let lines: Vec<(u32, String, String)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains(needle))
+        .map(|(i, line)| {
+            (
+                i as u32,
+                line.to_string(),
+                line.replace(needle, replacement),
+            )
+        })
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(FileReplacement {
+            path: path.to_string_lossy().into_owned(),
+            rel_path: rel_path.to_string(),
+            lines,
+        })
+    }
+}
+
+/// Replaces `needle` in the file at `path`, leaving everything else (like line endings) as it is.
+/// The new content is written to a temporary file next to it first, which then replaces the
+/// original, so the file is never left half written.
+fn replace_in_file(path: &str, needle: &str, replacement: &str) -> io::Result<()> {
+    let path = Path::new(path);
+    let content = fs::read_to_string(path)?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.gxi-replace", name));
+    let result = fs::write(&tmp, content.replace(needle, replacement))
+        .and_then(|_| fs::set_permissions(&tmp, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// The find in files window, which searches all files of the current project for a string
+pub struct FindInFiles {
+    main_win: Rc<MainWin>,
+    pub window: Window,
+    search_entry: SearchEntry,
+    replace_entry: Entry,
+    patterns_entry: Entry,
+    gitignore_checkbutton: CheckButton,
+    status_label: Label,
+    list_store: ListStore,
+    project_root: RefCell<PathBuf>,
+    /// Incremented on every search, so running searches know when they've been superseded
+    generation: Arc<AtomicUsize>,
+}
+
+impl FindInFiles {
+    pub fn new(
+        main_win: &Rc<MainWin>,
+        parent: &ApplicationWindow,
+        file_name: Option<&str>,
+    ) -> Rc<Self> {
+        let builder = Builder::new_from_string(SRC);
+
+        let window: Window = builder.get_object("find_in_files_win").unwrap();
+        let search_entry: SearchEntry = builder.get_object("search_entry").unwrap();
+        let replace_entry: Entry = builder.get_object("replace_entry").unwrap();
+        let replace_button: Button = builder.get_object("replace_button").unwrap();
+        let patterns_entry: Entry = builder.get_object("patterns_entry").unwrap();
+        let gitignore_checkbutton: CheckButton =
+            builder.get_object("gitignore_checkbutton").unwrap();
+        let search_button: Button = builder.get_object("search_button").unwrap();
+        let status_label: Label = builder.get_object("status_label").unwrap();
+        let list_store: ListStore = builder.get_object("results_liststore").unwrap();
+        let treeview: TreeView = builder.get_object("results_treeview").unwrap();
+
+        let fif = Rc::new(Self {
+            main_win: main_win.clone(),
+            window: window.clone(),
+            search_entry: search_entry.clone(),
+            replace_entry: replace_entry.clone(),
+            patterns_entry: patterns_entry.clone(),
+            gitignore_checkbutton,
+            status_label,
+            list_store,
+            project_root: RefCell::new(project_root_for(file_name)),
+            generation: Arc::new(AtomicUsize::new(0)),
+        });
+        fif.load_project_scope();
+
+        search_entry.connect_activate(enclose!((fif) move |_| fif.start_search()));
+        patterns_entry.connect_activate(enclose!((fif) move |_| fif.start_search()));
+        search_button.connect_clicked(enclose!((fif) move |_| fif.start_search()));
+        replace_entry.connect_activate(enclose!((fif) move |_| Self::start_replace(&fif)));
+        replace_button.connect_clicked(enclose!((fif) move |_| Self::start_replace(&fif)));
+
+        treeview.connect_row_activated(enclose!((main_win) move |tv, path, _| {
+            if let Some(model) = tv.get_model() {
+                if let Some(iter) = model.get_iter(path) {
+                    let file = model.get_value(&iter, 0).get::<String>();
+                    let line = model.get_value(&iter, 1).get::<u32>();
+                    if let (Some(file), Some(line)) = (file, line) {
+                        main_win.open_file_at(&file, u64::from(line));
+                    }
+                }
+            }
+        }));
+
+        let preview = FilePreview::new(&treeview);
+        treeview.connect_motion_notify_event(enclose!((preview) move |tv, em| {
+            let (x, y) = em.get_position();
+            let target = tv.get_path_at_pos(x as i32, y as i32).and_then(|(path, _, _, _)| {
+                let path = path?;
+                let model = tv.get_model()?;
+                let iter = model.get_iter(&path)?;
+                let file = model.get_value(&iter, 0).get::<String>()?;
+                let line = model.get_value(&iter, 1).get::<u32>()?;
+                Some((path, file, line))
+            });
+            match target {
+                Some((path, file, line)) => {
+                    // The row's area is in coordinates of the bin window, like the event
+                    let area = tv.get_cell_area(Some(&path), None::<&TreeViewColumn>);
+                    let (x, y) = tv.convert_bin_window_to_widget_coords(x as i32, area.y);
+                    let rect = gdk::Rectangle { x, y, width: 1, height: area.height };
+                    FilePreview::show_later(&preview, &file, line, rect);
+                }
+                None => preview.hide(),
+            }
+            Inhibit(false)
+        }));
+        treeview.connect_leave_notify_event(enclose!((preview) move |_, _| {
+            preview.hide();
+            Inhibit(false)
+        }));
+
+        window.connect_delete_event(|w, _| Inhibit(w.hide_on_delete()));
+
+        window.set_transient_for(Some(parent));
+        window.show_all();
+
+        fif
+    }
+
+    /// Shows the window again, searching the project of `file_name` now
+    pub fn present(&self, file_name: Option<&str>) {
+        let project_root = project_root_for(file_name);
+        if *self.project_root.borrow() != project_root {
+            self.project_root.replace(project_root);
+            self.load_project_scope();
+            self.list_store.clear();
+        }
+        self.window.present();
+        self.search_entry.grab_focus();
+    }
+
+    /// Whether the window is shown and how big it is, so it can be restored in the next session
+    pub fn panel_state(&self) -> PanelState {
+        let (width, height) = self.window.get_size();
+        PanelState {
+            open: self.window.is_visible(),
+            width,
+            height,
+        }
+    }
+
+    pub fn restore_size(&self, state: &PanelState) {
+        if state.width > 0 && state.height > 0 {
+            self.window.resize(state.width, state.height);
+        }
+    }
+
+    fn load_project_scope(&self) {
+        let project_root = self.project_root.borrow();
+        let scope = load_scope(&project_root);
+        self.patterns_entry.set_text(&scope.patterns_string());
+        self.gitignore_checkbutton
+            .set_active(scope.respect_gitignore);
+        self.window.set_title(&format!(
+            "{} – {}",
+            gettext("Find in Files"),
+            project_root.to_string_lossy()
+        ));
+    }
+
+    /// Returns the scope the user has entered, remembering it for the project
+    fn scope(&self) -> SearchScope {
+        let patterns = self
+            .patterns_entry
+            .get_text()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        let scope = SearchScope::parse(&patterns, self.gitignore_checkbutton.get_active());
+        if !self.main_win.is_private() {
+            save_scope(&self.project_root.borrow(), &scope);
+        }
+        scope
+    }
+
+    fn start_search(&self) {
+        let needle = self
+            .search_entry
+            .get_text()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        let scope = self.scope();
+        let root = self.project_root.borrow().clone();
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.list_store.clear();
+        if needle.is_empty() {
+            self.status_label.set_text("");
+            return;
+        }
+        self.status_label.set_text(&gettext("Searching…"));
+
+        trace!(
+            "{} '{}' {:?} {:?}",
+            gettext("Searching in files for"),
+            needle,
+            root,
+            scope
+        );
+
+        let (search_tx, search_rx) = MainContext::channel::<SearchMsg>(glib::PRIORITY_DEFAULT);
+
+        // Shown until the receiver below is dropped
+        let operation = Progress::start(self.main_win.progress(), &gettext("Searching in files"));
+        let cancelled = operation.cancelled_flag();
+        let current_generation = self.generation.clone();
+        let list_store = self.list_store.clone();
+        let status_label = self.status_label.clone();
+        search_rx.attach(Some(&MainContext::default()), move |msg| {
+            if current_generation.load(Ordering::SeqCst) != generation {
+                return glib::source::Continue(false);
+            }
+            match msg {
+                SearchMsg::Match(m) => {
+                    let location = format!("{}:{}", m.rel_path, m.line + 1);
+                    list_store.insert_with_values(
+                        None,
+                        &[0, 1, 2, 3],
+                        &[&m.path, &m.line, &location, &m.text],
+                    );
+                    glib::source::Continue(true)
+                }
+                SearchMsg::Done(n) => {
+                    if operation.is_cancelled() {
+                        status_label.set_text(&gettext("Search cancelled"));
+                    } else {
+                        status_label.set_text(&format!("{} {}", n, gettext("Results")));
+                    }
+                    glib::source::Continue(false)
+                }
+            }
+        });
+
+        let current_generation = self.generation.clone();
+        thread::spawn(move || {
+            let filter = FileFilter::new(&root, &scope);
+            let mut count = 0;
+            walk(&root, &root, &filter, &mut |path, rel_path| {
+                if current_generation.load(Ordering::SeqCst) != generation
+                    || cancelled.load(Ordering::SeqCst)
+                {
+                    return false;
+                }
+                for m in search_file(path, rel_path, &needle) {
+                    if search_tx.send(SearchMsg::Match(m)).is_err() {
+                        return false;
+                    }
+                    count += 1;
+                    if count >= MAX_MATCHES {
+                        return false;
+                    }
+                }
+                true
+            });
+            search_tx.send(SearchMsg::Done(count)).ok();
+        });
+    }
+
+    /// Searches the files of the project for what's going to be replaced and shows a preview of
+    /// the changes, which are applied once the user confirms them
+    fn start_replace(fif: &Rc<Self>) {
+        let needle = fif
+            .search_entry
+            .get_text()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        let replacement = fif
+            .replace_entry
+            .get_text()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        if needle.is_empty() {
+            return;
+        }
+        let scope = fif.scope();
+        let root = fif.project_root.borrow().clone();
+
+        let generation = fif.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        fif.list_store.clear();
+        fif.status_label.set_text(&gettext("Searching…"));
+
+        trace!(
+            "{} '{}' {} '{}' {:?} {:?}",
+            gettext("Replacing in files"),
+            needle,
+            gettext("with"),
+            replacement,
+            root,
+            scope
+        );
+
+        let (replace_tx, replace_rx) =
+            MainContext::channel::<Vec<FileReplacement>>(glib::PRIORITY_DEFAULT);
+
+        let operation = Progress::start(fif.main_win.progress(), &gettext("Searching in files"));
+        let cancelled = operation.cancelled_flag();
+        let current_generation = fif.generation.clone();
+        replace_rx.attach(
+            Some(&MainContext::default()),
+            enclose!((fif, needle, replacement) move |files| {
+                if operation.is_cancelled() {
+                    fif.status_label.set_text(&gettext("Search cancelled"));
+                } else if current_generation.load(Ordering::SeqCst) == generation {
+                    fif.show_replace_preview(&needle, &replacement, files);
+                }
+                glib::source::Continue(false)
+            }),
+        );
+
+        let current_generation = fif.generation.clone();
+        thread::spawn(move || {
+            let filter = FileFilter::new(&root, &scope);
+            let mut files = Vec::new();
+            let mut count = 0;
+            walk(&root, &root, &filter, &mut |path, rel_path| {
+                if current_generation.load(Ordering::SeqCst) != generation
+                    || cancelled.load(Ordering::SeqCst)
+                {
+                    return false;
+                }
+                if let Some(file) = replacements_in_file(path, rel_path, &needle, &replacement) {
+                    count += file.lines.len();
+                    files.push(file);
+                }
+                count < MAX_MATCHES
+            });
+            replace_tx.send(files).ok();
+        });
+    }
+
+    /// Shows the changes replacing `needle` with `replacement` makes to `files` as a diff, and
+    /// applies them if the user confirms
+    fn show_replace_preview(&self, needle: &str, replacement: &str, files: Vec<FileReplacement>) {
+        if files.is_empty() {
+            self.status_label.set_text(&gettext("No results"));
+            return;
+        }
+        let count: usize = files.iter().map(|f| f.count(needle)).sum();
+        let summary = format!(
+            "{} {} {} {}",
+            count,
+            gettext("replacements in"),
+            files.len(),
+            gettext("files")
+        );
+        self.status_label.set_text(&summary);
+
+        let dialog = Dialog::new();
+        dialog.set_title(&gettext("Replace in Files"));
+        dialog.set_transient_for(Some(&self.window));
+        dialog.set_modal(true);
+        dialog.set_default_size(700, 500);
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog
+            .add_button(&gettext("Replace All"), ResponseType::Accept)
+            .get_style_context()
+            .add_class("destructive-action");
+
+        let text_view = TextView::new();
+        text_view.set_editable(false);
+        text_view.set_monospace(true);
+        if let Some(buffer) = text_view.get_buffer() {
+            let diffs: Vec<String> = files.iter().map(FileReplacement::diff).collect();
+            buffer.set_text(&diffs.join("\n"));
+        }
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.add(&text_view);
+        let summary_label = Label::new(Some(summary.as_str()));
+        summary_label.set_halign(Align::Start);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+        content_area.pack_start(&summary_label, false, false, 0);
+        content_area.pack_start(&scrolled_window, true, true, 0);
+        content_area.show_all();
+
+        let main_win = self.main_win.clone();
+        let status_label = self.status_label.clone();
+        let (needle, replacement) = (needle.to_string(), replacement.to_string());
+        dialog.connect_response(move |dialog, res| {
+            if res == ResponseType::Accept {
+                let failed = apply_replacements(&main_win, &needle, &replacement, &files);
+                if !failed.is_empty() {
+                    status_label.set_text(&format!(
+                        "{}: {}",
+                        gettext("These files couldn't be changed, see the log for details"),
+                        failed.join(", ")
+                    ));
+                } else {
+                    status_label.set_text(&gettext("Replaced all matches"));
+                }
+            }
+            dialog.destroy();
+        });
+        dialog.show();
+    }
+}
+
+/// Replaces `needle` with `replacement` in `files`. Files which are open are changed through
+/// xi-editor, so their unsaved changes aren't lost and the replacement can be undone; saving them
+/// is left to the user, unless they're open read-only. The others are rewritten on disk. Returns
+/// the relative paths of the files which couldn't be changed.
+fn apply_replacements(
+    main_win: &MainWin,
+    needle: &str,
+    replacement: &str,
+    files: &[FileReplacement],
+) -> Vec<String> {
+    let mut failed = Vec::new();
+    for file in files {
+        if let Some(edit_view) = main_win.views_of_file(&file.path).first() {
+            let ev = edit_view.borrow();
+            if ev.is_read_only() {
+                warn!(
+                    "{} '{}'",
+                    gettext("Not replacing in file which is open read-only"),
+                    file.path
+                );
+                failed.push(file.rel_path.clone());
+            } else {
+                ev.replace_in_document(needle, replacement);
+            }
+        } else if let Err(e) = replace_in_file(&file.path, needle, replacement) {
+            warn!(
+                "{} '{}': {}",
+                gettext("Failed to replace in file"),
+                file.path,
+                e
+            );
+            failed.push(file.rel_path.clone());
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_file_name() {
+        assert!(glob_matches("*.rs", "src/main.rs"));
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(!glob_matches("*.rs", "src/main.rs.orig"));
+        assert!(glob_matches("main.?s", "src/main.rs"));
+    }
+
+    #[test]
+    fn glob_paths() {
+        assert!(glob_matches("target/**", "target/debug/gxi"));
+        assert!(glob_matches("target/**", "target/"));
+        assert!(!glob_matches("target/**", "src/target.rs"));
+        assert!(glob_matches("src/*.rs", "src/main.rs"));
+        assert!(!glob_matches("src/*.rs", "src/ui/main.rs"));
+        assert!(glob_matches("**/ui/*.glade", "src/gxi/src/ui/gxi.glade"));
+        assert!(glob_matches("**/ui/*.glade", "ui/gxi.glade"));
+        assert!(!glob_matches("**/ui/*.glade", "src/gui/gxi.glade"));
+    }
+
+    #[test]
+    fn replacement_diff() {
+        let file = FileReplacement {
+            path: "/project/src/main.rs".to_string(),
+            rel_path: "src/main.rs".to_string(),
+            lines: vec![(
+                4,
+                "let foo = foo();".to_string(),
+                "let bar = bar();".to_string(),
+            )],
+        };
+        assert_eq!(file.count("foo"), 2);
+        assert_eq!(
+            file.diff(),
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -5 +5 @@\n-let foo = foo();\n+let bar = bar();\n"
+        );
+    }
+
+    #[test]
+    fn replace_on_disk() {
+        let dir = std::env::temp_dir().join(format!("gxi-replace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.rs");
+        fs::write(&path, "let foo = foo();\r\n").unwrap();
+        replace_in_file(path.to_str().unwrap(), "foo", "bar").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "let bar = bar();\r\n");
+        // The temporary file is gone again
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scope_filter() {
+        let scope = SearchScope::parse("*.rs, !target/**", false);
+        assert_eq!(scope.patterns, vec!["*.rs", "!target/**"]);
+
+        let filter = FileFilter::new(Path::new("/nonexistent"), &scope);
+        assert!(filter.searches_file("src/main.rs"));
+        assert!(!filter.searches_file("README.md"));
+        assert!(filter.skips_dir("target"));
+        assert!(filter.skips_dir(".git"));
+        assert!(!filter.skips_dir("src"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Lets the text take up the whole screen in fullscreen mode. The header bar moves over the text
+//! then and is only revealed (along with the tabs) when the pointer touches the top edge of the
+//! screen. It's hidden again a while after the pointer has left it.
+
+use gdk::{EventMask, NotifyType, WindowState};
+use gettextrs::gettext;
+use glib::{source, SourceId};
+use gtk::*;
+use log::debug;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// How long (in milliseconds) the header bar stays revealed after the pointer has left it
+const HIDE_DELAY: u32 = 1000;
+/// How close (in pixels) to the top edge of the screen the pointer has to get to reveal the
+/// header bar
+const HOT_ZONE_HEIGHT: i32 = 2;
+
+pub struct Fullscreen {
+    header_bar: HeaderBar,
+    /// Holds the header bar while we aren't fullscreen
+    titlebar_box: Box,
+    /// Everything below the header bar
+    content_box: Box,
+    notebook: Notebook,
+    /// Holds the header bar while we're fullscreen
+    revealer: Revealer,
+    /// Notices when the pointer leaves the revealed header bar
+    header_event_box: EventBox,
+    /// Notices when the pointer touches the top edge of the screen
+    hot_zone: EventBox,
+    leave_button: Button,
+    fullscreen: Cell<bool>,
+    /// Whether the header bar is only revealed when the pointer is at the top edge of the screen
+    auto_hide: Cell<bool>,
+    hide_timeout: RefCell<Option<SourceId>>,
+}
+
+impl Fullscreen {
+    pub fn new(builder: &Builder, notebook: &Notebook, auto_hide: bool) -> Rc<Self> {
+        let window: ApplicationWindow = builder.get_object("appwindow").unwrap();
+        let overlay: Overlay = builder.get_object("overlay").unwrap();
+
+        let revealer = Revealer::new();
+        revealer.set_valign(Align::Start);
+        revealer.set_transition_type(RevealerTransitionType::SlideDown);
+        let header_event_box = EventBox::new();
+        revealer.add(&header_event_box);
+        overlay.add_overlay(&revealer);
+
+        let hot_zone = EventBox::new();
+        hot_zone.set_valign(Align::Start);
+        hot_zone.set_size_request(-1, HOT_ZONE_HEIGHT);
+        hot_zone.set_no_show_all(true);
+        overlay.add_overlay(&hot_zone);
+
+        let leave_button =
+            Button::new_from_icon_name(Some("view-restore-symbolic"), IconSize::Button);
+        leave_button.set_tooltip_text(Some(gettext("Leave Fullscreen").as_str()));
+        leave_button.set_action_name(Some("app.fullscreen"));
+
+        let fullscreen = Rc::new(Self {
+            header_bar: builder.get_object("header_bar").unwrap(),
+            titlebar_box: builder.get_object("titlebar_box").unwrap(),
+            content_box: builder.get_object("content_box").unwrap(),
+            notebook: notebook.clone(),
+            revealer,
+            header_event_box,
+            hot_zone,
+            leave_button,
+            fullscreen: Cell::new(false),
+            auto_hide: Cell::new(auto_hide),
+            hide_timeout: Default::default(),
+        });
+
+        window.connect_window_state_event(enclose!((fullscreen) move |_, event| {
+            let is_fullscreen = event.get_new_window_state().contains(WindowState::FULLSCREEN);
+            if is_fullscreen != fullscreen.fullscreen.get() {
+                Self::set_fullscreen(&fullscreen, is_fullscreen);
+            }
+            Inhibit(false)
+        }));
+
+        fullscreen.hot_zone.add_events(EventMask::ENTER_NOTIFY_MASK);
+        fullscreen
+            .hot_zone
+            .connect_enter_notify_event(enclose!((fullscreen) move |_, _| {
+                Self::reveal(&fullscreen, true);
+                Inhibit(false)
+            }));
+        fullscreen
+            .header_event_box
+            .add_events(EventMask::ENTER_NOTIFY_MASK | EventMask::LEAVE_NOTIFY_MASK);
+        fullscreen
+            .header_event_box
+            .connect_enter_notify_event(enclose!((fullscreen) move |_, _| {
+                fullscreen.cancel_hide();
+                Inhibit(false)
+            }));
+        fullscreen.header_event_box.connect_leave_notify_event(
+            enclose!((fullscreen) move |_, event| {
+                // Moving onto one of the buttons doesn't leave the header bar
+                if event.get_detail() != NotifyType::Inferior {
+                    Self::schedule_hide(&fullscreen);
+                }
+                Inhibit(false)
+            }),
+        );
+
+        fullscreen
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen.get()
+    }
+
+    /// Sets whether the header bar is hidden in fullscreen mode until the pointer touches the top
+    /// edge of the screen, otherwise it's always shown
+    pub fn set_auto_hide(fullscreen: &Rc<Self>, auto_hide: bool) {
+        fullscreen.auto_hide.set(auto_hide);
+        if fullscreen.fullscreen.get() {
+            Self::reveal(fullscreen, !auto_hide);
+        }
+    }
+
+    /// Moves the header bar over the text when the window goes fullscreen and back once it
+    /// doesn't anymore. GTK hides the titlebar of fullscreen windows.
+    fn set_fullscreen(fullscreen: &Rc<Self>, is_fullscreen: bool) {
+        debug!("{}: {}", gettext("Fullscreen"), is_fullscreen);
+        fullscreen.fullscreen.set(is_fullscreen);
+        fullscreen.cancel_hide();
+        let header_bar = &fullscreen.header_bar;
+        if is_fullscreen {
+            fullscreen.titlebar_box.remove(header_bar);
+            fullscreen.header_event_box.add(header_bar);
+            header_bar.set_show_close_button(false);
+            header_bar.pack_end(&fullscreen.leave_button);
+            fullscreen.leave_button.show();
+            fullscreen.revealer.show_all();
+            fullscreen.hot_zone.show();
+            Self::reveal(fullscreen, !fullscreen.auto_hide.get());
+        } else {
+            fullscreen.revealer.set_reveal_child(false);
+            fullscreen.hot_zone.hide();
+            fullscreen.revealer.hide();
+            header_bar.remove(&fullscreen.leave_button);
+            header_bar.set_show_close_button(true);
+            fullscreen.header_event_box.remove(header_bar);
+            fullscreen
+                .titlebar_box
+                .pack_start(header_bar, true, true, 0);
+            fullscreen.notebook.set_show_tabs(true);
+            fullscreen.content_box.set_margin_top(0);
+        }
+    }
+
+    /// Reveals (or hides) the header bar and the tabs. The text moves down to make room for them
+    /// while they're revealed.
+    fn reveal(fullscreen: &Rc<Self>, reveal: bool) {
+        fullscreen.cancel_hide();
+        if !fullscreen.fullscreen.get() {
+            return;
+        }
+        fullscreen.revealer.set_reveal_child(reveal);
+        fullscreen.notebook.set_show_tabs(reveal);
+        let margin = if reveal {
+            fullscreen.header_bar.get_preferred_height().1
+        } else {
+            0
+        };
+        fullscreen.content_box.set_margin_top(margin);
+    }
+
+    /// Hides the header bar after `HIDE_DELAY`, unless the pointer is back on it by then or one
+    /// of its menus is open
+    fn schedule_hide(fullscreen: &Rc<Self>) {
+        if !fullscreen.auto_hide.get() {
+            return;
+        }
+        fullscreen.cancel_hide();
+        let id = glib::timeout_add_local(
+            HIDE_DELAY,
+            enclose!((fullscreen) move || {
+                fullscreen.hide_timeout.replace(None);
+                if fullscreen.menu_open() {
+                    Self::schedule_hide(&fullscreen);
+                } else {
+                    Self::reveal(&fullscreen, false);
+                }
+                source::Continue(false)
+            }),
+        );
+        fullscreen.hide_timeout.replace(Some(id));
+    }
+
+    fn cancel_hide(&self) {
+        if let Some(id) = self.hide_timeout.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+    }
+
+    /// Whether the popover of one of the menu buttons of the header bar is open, e.g. the menu
+    fn menu_open(&self) -> bool {
+        self.header_bar
+            .get_children()
+            .into_iter()
+            .filter_map(|child| child.downcast::<MenuButton>().ok())
+            .any(|button| button.get_active())
+    }
+}
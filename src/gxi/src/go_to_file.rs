@@ -0,0 +1,340 @@
+//! "Go to File…" (Ctrl+P), a popover which finds files of the project by typing a few letters of
+//! their path, e.g. `mwin` for `src/main_win.rs`. Files opened recently are ranked higher.
+
+use crate::find_in_files;
+use gettextrs::gettext;
+use glib::{source, MainContext};
+use gtk::*;
+use log::debug;
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+
+/// How many of the recently opened files are remembered
+pub const MAX_RECENT_FILES: usize = 50;
+/// Stop listing the files of a project after this many, walking huge trees takes too long
+const MAX_FILES: usize = 50_000;
+/// How many files are shown in the list
+const MAX_RESULTS: usize = 100;
+/// The score a file gets for having been opened last. Files opened before get less.
+const RECENT_BONUS: i64 = 200;
+
+/// Moves `file` to the front of the recently opened `files`
+pub fn remember(files: &mut Vec<String>, file: &str) {
+    files.retain(|f| f != file);
+    files.insert(0, file.to_string());
+    files.truncate(MAX_RECENT_FILES);
+}
+
+/// Whether `c` starts a word, e.g. the `w` of `main_win` or `mainWin`
+fn starts_word(prev: char, c: char) -> bool {
+    match prev {
+        '/' | '\\' | '_' | '-' | '.' | ' ' => true,
+        _ => prev.is_lowercase() && c.is_uppercase(),
+    }
+}
+
+/// Scores how well `query` matches `path`, or returns None if the chars of the query (ignoring
+/// case and whitespace) don't all appear in it in order. Consecutive matches, matches at the
+/// start of words and matches in the file name score higher, long paths lower.
+fn fuzzy_score(query: &str, path: &str) -> Option<i64> {
+    let chars: Vec<char> = path.chars().collect();
+    let name_start = path.rfind('/').map_or(0, |ix| path[..=ix].chars().count());
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev_match = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let ix = (pos..chars.len()).find(|&ix| chars[ix].to_lowercase().eq(q.to_lowercase()))?;
+        score += 10;
+        if ix > 0 && prev_match == Some(ix - 1) {
+            score += 15;
+        }
+        if ix == 0 || starts_word(chars[ix - 1], chars[ix]) {
+            score += 20;
+        }
+        if ix >= name_start {
+            score += 10;
+        }
+        prev_match = Some(ix);
+        pos = ix + 1;
+    }
+    Some(score - chars.len() as i64)
+}
+
+/// The `files` (their paths and the names they're shown with) which match `query`, the best
+/// match first. Files are matched by the name they're shown with, `recent` files get a bonus.
+fn rank<'a>(
+    query: &str,
+    files: &'a [(String, String)],
+    recent: &[String],
+) -> Vec<&'a (String, String)> {
+    let mut matches: Vec<(i64, &(String, String))> = files
+        .iter()
+        .filter_map(|file| {
+            let score = fuzzy_score(query, &file.1)?;
+            let bonus = recent.iter().position(|r| *r == file.0).map_or(0, |i| {
+                RECENT_BONUS - i as i64 * RECENT_BONUS / MAX_RECENT_FILES as i64
+            });
+            Some((score + bonus, file))
+        })
+        .collect();
+    matches.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then_with(|| a.1.cmp(&b.1)));
+    matches.into_iter().map(|(_, file)| file).collect()
+}
+
+pub struct GoToFile {
+    popover: Popover,
+    entry: SearchEntry,
+    list_box: ListBox,
+    status_label: Label,
+    /// The paths of the files which can be opened and the names they're shown with
+    files: RefCell<Vec<(String, String)>>,
+    /// The recently opened files, the most recent one first
+    recent: RefCell<Vec<String>>,
+    /// The paths of the files in the rows of the list
+    shown: RefCell<Vec<String>>,
+    /// Incremented whenever the files are listed, so results of older listings are ignored
+    generation: Cell<usize>,
+    /// Called with the path of the file the user has chosen
+    open_handler: RefCell<Option<Rc<dyn Fn(&str)>>>,
+}
+
+impl GoToFile {
+    pub fn new(relative_to: &HeaderBar) -> Rc<Self> {
+        let popover = Popover::new(Some(relative_to));
+        popover.set_position(PositionType::Bottom);
+
+        let entry = SearchEntry::new();
+        entry.set_placeholder_text(Some(gettext("Go to File…").as_str()));
+        let list_box = ListBox::new();
+        list_box.set_selection_mode(SelectionMode::Browse);
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        scrolled_window.set_size_request(500, 350);
+        scrolled_window.add(&list_box);
+        let status_label = Label::new(None);
+        status_label.get_style_context().add_class("dim-label");
+
+        let vbox = Box::new(Orientation::Vertical, 6);
+        vbox.set_border_width(6);
+        vbox.pack_start(&entry, false, false, 0);
+        vbox.pack_start(&scrolled_window, true, true, 0);
+        vbox.pack_start(&status_label, false, false, 0);
+        vbox.show_all();
+        popover.add(&vbox);
+
+        let go_to_file = Rc::new(Self {
+            popover,
+            entry,
+            list_box,
+            status_label,
+            files: RefCell::new(Vec::new()),
+            recent: RefCell::new(Vec::new()),
+            shown: RefCell::new(Vec::new()),
+            generation: Cell::new(0),
+            open_handler: RefCell::new(None),
+        });
+
+        go_to_file
+            .entry
+            .connect_search_changed(enclose!((go_to_file) move |_| {
+                go_to_file.update_results();
+            }));
+        go_to_file
+            .entry
+            .connect_activate(enclose!((go_to_file) move |_| {
+                if let Some(row) = go_to_file.list_box.get_selected_row() {
+                    go_to_file.open_row(&row);
+                }
+            }));
+        // The focus stays in the entry while the selection is moved with the arrow keys
+        go_to_file
+            .entry
+            .connect_key_press_event(enclose!((go_to_file) move |_, ek| {
+                let step = match ek.get_keyval() {
+                    gdk::enums::key::Down => 1,
+                    gdk::enums::key::Up => -1,
+                    _ => return Inhibit(false),
+                };
+                go_to_file.move_selection(step);
+                Inhibit(true)
+            }));
+        go_to_file
+            .list_box
+            .connect_row_activated(enclose!((go_to_file) move |_, row| {
+                go_to_file.open_row(row);
+            }));
+
+        go_to_file
+    }
+
+    /// Sets the function which opens the file the user has chosen
+    pub fn connect_open<F: Fn(&str) + 'static>(&self, f: F) {
+        self.open_handler.replace(Some(Rc::new(f)));
+    }
+
+    /// Shows the popover with the files in `root` (shown relative to it) and the `open_files`
+    /// (shown with their full paths if they aren't in `root`), ranking the `recent` files higher
+    pub fn show(
+        go_to_file: &Rc<Self>,
+        root: Option<PathBuf>,
+        open_files: Vec<String>,
+        recent: Vec<String>,
+    ) {
+        let generation = go_to_file.generation.get() + 1;
+        go_to_file.generation.set(generation);
+        go_to_file.recent.replace(recent);
+        go_to_file.files.replace(
+            open_files
+                .into_iter()
+                .map(|file| {
+                    let name = root
+                        .as_ref()
+                        .and_then(|root| Path::new(&file).strip_prefix(root).ok())
+                        .map_or_else(|| file.clone(), |rel| rel.to_string_lossy().into_owned());
+                    (file, name)
+                })
+                .collect(),
+        );
+        go_to_file.entry.set_text("");
+        go_to_file.update_results();
+        #[cfg(feature = "gtk_v3_22")]
+        go_to_file.popover.popup();
+        #[cfg(not(feature = "gtk_v3_22"))]
+        go_to_file.popover.show();
+        go_to_file.entry.grab_focus();
+
+        let root = match root {
+            Some(root) => root,
+            None => return,
+        };
+        go_to_file.status_label.set_text(&gettext("Listing files…"));
+        go_to_file.status_label.show();
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        rx.attach(
+            Some(&MainContext::default()),
+            enclose!((go_to_file) move |listed: Vec<(PathBuf, String)>| {
+                if go_to_file.generation.get() == generation {
+                    debug!("{} {}", gettext("Files to go to:"), listed.len());
+                    go_to_file.add_files(listed);
+                }
+                source::Continue(false)
+            }),
+        );
+        thread::spawn(move || {
+            tx.send(find_in_files::list_files(&root, MAX_FILES)).ok();
+        });
+    }
+
+    /// Adds the `listed` files (with their paths relative to the project) to the open files, which
+    /// are in the list already
+    fn add_files(&self, listed: Vec<(PathBuf, String)>) {
+        {
+            let mut files = self.files.borrow_mut();
+            for (path, rel_path) in listed {
+                let path = path.to_string_lossy().into_owned();
+                if !files.iter().any(|(p, _)| *p == path) {
+                    files.push((path, rel_path));
+                }
+            }
+        }
+        self.status_label.hide();
+        self.update_results();
+    }
+
+    fn update_results(&self) {
+        for row in self.list_box.get_children() {
+            self.list_box.remove(&row);
+        }
+        let query = self
+            .entry
+            .get_text()
+            .map_or_else(String::new, |t| t.to_string());
+        let files = self.files.borrow();
+        let ranked = rank(&query, &files, &self.recent.borrow());
+        let mut shown = self.shown.borrow_mut();
+        shown.clear();
+        for (path, name) in ranked.into_iter().take(MAX_RESULTS) {
+            let label = Label::new(Some(name.as_str()));
+            label.set_halign(Align::Start);
+            label.set_ellipsize(pango::EllipsizeMode::Start);
+            label.set_margin_start(6);
+            label.set_margin_end(6);
+            label.set_margin_top(3);
+            label.set_margin_bottom(3);
+            label.set_tooltip_text(Some(path.as_str()));
+            self.list_box.add(&label);
+            shown.push(path.clone());
+        }
+        self.list_box.show_all();
+        if let Some(row) = self.list_box.get_row_at_index(0) {
+            self.list_box.select_row(Some(&row));
+        }
+    }
+
+    /// Moves the selection `step` rows down (or up, if it's negative)
+    fn move_selection(&self, step: i32) {
+        let index = self
+            .list_box
+            .get_selected_row()
+            .map_or(0, |row| row.get_index() + step);
+        if let Some(row) = self.list_box.get_row_at_index(index.max(0)) {
+            self.list_box.select_row(Some(&row));
+            row.grab_focus();
+            self.entry.grab_focus_without_selecting();
+        }
+    }
+
+    fn open_row(&self, row: &ListBoxRow) {
+        let path = match self.shown.borrow().get(row.get_index() as usize) {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        self.popover.hide();
+        if let Some(open) = self.open_handler.borrow().clone() {
+            open(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("xyz", "src/main_win.rs"), None);
+        assert!(fuzzy_score("mwin", "src/main_win.rs").is_some());
+        // Matches at the start of words and in the file name are better
+        assert!(fuzzy_score("edit", "src/edit_view.rs") > fuzzy_score("edit", "src/credits.rs"));
+        assert!(fuzzy_score("MainWin", "mainWin.ts") > fuzzy_score("MainWin", "maintainwind.rs"));
+    }
+
+    #[test]
+    fn ranking() {
+        let files: Vec<(String, String)> = ["/p/src/main.rs", "/p/src/main_win.rs", "/p/README.md"]
+            .iter()
+            .map(|path| (path.to_string(), path[3..].to_string()))
+            .collect();
+        let names = |ranked: Vec<&(String, String)>| -> Vec<String> {
+            ranked.into_iter().map(|file| file.1.clone()).collect()
+        };
+        assert_eq!(
+            names(rank("main", &files, &[])),
+            vec!["src/main.rs", "src/main_win.rs"]
+        );
+        assert_eq!(
+            names(rank("main", &files, &["/p/src/main_win.rs".to_string()])),
+            vec!["src/main_win.rs", "src/main.rs"]
+        );
+        assert_eq!(names(rank("", &files, &[])).len(), 3);
+    }
+
+    #[test]
+    fn recent_files() {
+        let mut files = vec!["a".to_string(), "b".to_string()];
+        remember(&mut files, "b");
+        assert_eq!(files, vec!["b", "a"]);
+    }
+}
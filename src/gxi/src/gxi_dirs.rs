@@ -0,0 +1,76 @@
+//! Where gxi keeps the data it generates. The XDG base directory spec splits this up: state
+//! which should survive restarts (e.g. the session) goes to `$XDG_STATE_HOME`, only files the
+//! user writes belong into `$XDG_CONFIG_HOME`, which is where older versions of gxi put
+//! everything. gxi doesn't cache anything on disk (yet), so there's no cache directory here.
+
+use crate::profile;
+use gettextrs::gettext;
+use log::{debug, warn};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APP_DIR_NAME: &str = "gxi";
+
+/// `$XDG_CONFIG_HOME/gxi`, where files the user writes (e.g. scripts) live. Older versions of gxi
+/// kept their state here, too.
+pub fn config_dir() -> Option<PathBuf> {
+    glib::get_user_config_dir().map(|d| d.join(APP_DIR_NAME))
+}
+
+/// `$XDG_STATE_HOME/gxi`, `~/.local/state/gxi` by default. GLib doesn't know about the state
+/// directory yet, so we have to look it up ourselves. Each profile has a state directory of its
+/// own in there.
+pub fn state_dir() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        // The spec says relative paths are invalid and have to be ignored
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| glib::get_home_dir().map(|home| home.join(".local").join("state")))
+        .map(|dir| profile::dir(dir.join(APP_DIR_NAME)))
+}
+
+/// Returns the path of the state file `name`. If an older version of gxi left the file in the
+/// config directory it's moved over.
+pub fn state_file(name: &str) -> Option<PathBuf> {
+    let path = state_dir()?.join(name);
+    // Older versions didn't know about profiles, their state belongs to the default one
+    let legacy = config_dir()
+        .filter(|_| profile::current().is_none())
+        .map(|d| d.join(name));
+    if let Some(legacy) = legacy {
+        if legacy.is_file() && !path.exists() {
+            migrate(&legacy, &path);
+        }
+    }
+    Some(path)
+}
+
+/// Moves `from` to `to`, copying it if they are on different filesystems
+fn migrate(from: &Path, to: &Path) {
+    debug!(
+        "{} {:?} {} {:?}",
+        gettext("Moving"),
+        from,
+        gettext("to"),
+        to
+    );
+    let res = to
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::rename(from, to).or_else(|_| fs::copy(from, to).map(|_| ())));
+    match res {
+        // If the old file sticks around there's no harm, we just don't read it anymore
+        Ok(()) => {
+            let _ = fs::remove_file(from);
+        }
+        Err(e) => warn!(
+            "{} {:?} {} {:?}: {}",
+            gettext("Failed to move"),
+            from,
+            gettext("to"),
+            to,
+            e
+        ),
+    }
+}
@@ -0,0 +1,149 @@
+//! Notices when the user hasn't touched the window for a while, so background work (e.g. polling
+//! and the jobs of `background`) can be paused until they're back. Any input resumes it right
+//! away.
+
+use gdk::EventType;
+use gettextrs::gettext;
+use glib::{source, SourceId};
+use gtk::*;
+use log::debug;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How much longer the user has to be away (after `since_input` without input) until they count
+/// as idle, `None` if they do already
+fn time_left(since_input: Duration, timeout: Duration) -> Option<Duration> {
+    timeout
+        .checked_sub(since_input)
+        .filter(|left| *left > Duration::from_secs(0))
+}
+
+pub struct IdleMonitor {
+    last_input: Cell<Instant>,
+    idle: Cell<bool>,
+    /// After how long without input the user counts as idle, `None` if never
+    timeout: Cell<Option<Duration>>,
+    /// Checks whether the user has become idle once the timeout has passed
+    check: RefCell<Option<SourceId>>,
+    /// Called with whether the user is idle now
+    changed_handlers: RefCell<Vec<Rc<dyn Fn(bool)>>>,
+    /// Sees clicks before the widgets, which usually handle them themselves
+    _click_gesture: GestureMultiPress,
+}
+
+impl IdleMonitor {
+    pub fn new(window: &ApplicationWindow) -> Rc<Self> {
+        let click_gesture = GestureMultiPress::new(window);
+        click_gesture.set_propagation_phase(PropagationPhase::Capture);
+        click_gesture.set_button(0);
+
+        let monitor = Rc::new(Self {
+            last_input: Cell::new(Instant::now()),
+            idle: Cell::new(false),
+            timeout: Cell::new(None),
+            check: RefCell::new(None),
+            changed_handlers: Default::default(),
+            _click_gesture: click_gesture.clone(),
+        });
+
+        click_gesture.connect_pressed(enclose!((monitor) move |_, _, _, _| {
+            Self::input(&monitor);
+        }));
+        // Key presses always reach the window first, motion and scrolling do unless a widget
+        // handles them
+        window.connect_event(enclose!((monitor) move |_, event| {
+            match event.get_event_type() {
+                EventType::KeyPress | EventType::MotionNotify | EventType::Scroll => {
+                    Self::input(&monitor)
+                }
+                _ => {}
+            }
+            Inhibit(false)
+        }));
+
+        monitor
+    }
+
+    /// Calls `f` with whether the user is idle now whenever that changes
+    pub fn connect_changed<F: Fn(bool) + 'static>(&self, f: F) {
+        self.changed_handlers.borrow_mut().push(Rc::new(f));
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle.get()
+    }
+
+    /// Sets after how long without input the user counts as idle, `None` to never count them as
+    /// idle
+    pub fn set_timeout(monitor: &Rc<Self>, timeout: Option<Duration>) {
+        if monitor.timeout.replace(timeout) == timeout {
+            return;
+        }
+        // Start over with the new timeout
+        Self::set_idle(monitor, false);
+        Self::check(monitor);
+    }
+
+    fn input(monitor: &Rc<Self>) {
+        monitor.last_input.set(Instant::now());
+        if monitor.idle.get() {
+            Self::set_idle(monitor, false);
+            Self::check(monitor);
+        }
+    }
+
+    /// Goes idle if the timeout has passed since the last input, otherwise checks again once it
+    /// might have. Input doesn't reschedule the check, which would be wasteful for every motion of
+    /// the pointer.
+    fn check(monitor: &Rc<Self>) {
+        if let Some(id) = monitor.check.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+        let timeout = match monitor.timeout.get() {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        match time_left(monitor.last_input.get().elapsed(), timeout) {
+            Some(left) => {
+                let id = glib::timeout_add_local(
+                    left.as_millis() as u32 + 1,
+                    enclose!((monitor) move || {
+                        monitor.check.replace(None);
+                        Self::check(&monitor);
+                        source::Continue(false)
+                    }),
+                );
+                monitor.check.replace(Some(id));
+            }
+            None => Self::set_idle(monitor, true),
+        }
+    }
+
+    fn set_idle(monitor: &Rc<Self>, idle: bool) {
+        if monitor.idle.replace(idle) == idle {
+            return;
+        }
+        debug!("{}: {}", gettext("User idle"), idle);
+        let handlers = monitor.changed_handlers.borrow().clone();
+        for handler in handlers {
+            handler(idle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn time_left_until_idle() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            time_left(Duration::from_secs(20), minute),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(time_left(minute, minute), None);
+        assert_eq!(time_left(2 * minute, minute), None);
+    }
+}
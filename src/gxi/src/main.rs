@@ -68,11 +68,52 @@
 extern crate enclose;
 
 mod about_win;
+mod background;
+mod blame;
+mod changed_files;
+mod checksum;
+mod cmdline;
+mod core_compat;
+mod diagnostics;
+mod diff;
+mod diff_view;
+mod doc_lock;
 mod errors;
+mod external_diff;
+mod file_preview;
+mod file_tree;
+mod filter;
+mod find_in_files;
+mod fullscreen;
 mod globals;
+mod go_to_file;
+mod gxi_dirs;
+mod idle;
 mod main_win;
+mod markdown_preview;
+mod outline;
+mod panels;
 mod panic_handler;
+mod permalink;
+mod power;
 mod prefs_win;
+mod printing;
+mod profile;
+mod progress;
+mod project;
+mod scripting;
+mod search_results;
+mod session;
+mod settings_backup;
+mod snippets;
+mod speech;
+mod sudoedit;
+mod tags;
+#[cfg(feature = "terminal")]
+mod terminal;
+mod unhandled_msgs;
+mod whats_new;
+mod window_name;
 
 use crate::main_win::MainWin;
 use crate::panic_handler::PanicHandler;
@@ -81,8 +122,7 @@ use gio::{ApplicationExt, ApplicationExtManual, ApplicationFlags, FileExt};
 use glib::MainContext;
 use gtk::Application;
 use gxi_config_storage::pref_storage::GSchemaExt;
-use gxi_config_storage::GSchema;
-use gxi_peer::{Core, CoreMsg, ErrorMsg, SharedQueue, XiPeer};
+use gxi_peer::{Core, CoreMsg, ErrorMsg, Sandbox, SharedQueue, XiPeer};
 use log::{debug, info, warn};
 use serde_json::{json, Value};
 use std::env::args;
@@ -96,7 +136,7 @@ fn main() {
         // hasn't changed), so let's only turn on warnings for gxi.
         env_logger::Builder::new()
             .filter_module("gxi", log::LevelFilter::Warn)
-            .filter_module("editview", log::LevelFilter::Warn)
+            .filter_module("gxi_editview", log::LevelFilter::Warn)
             .filter_module("gxi-config-storage", log::LevelFilter::Warn)
             .filter_module("gxi-linecache", log::LevelFilter::Warn)
             .filter_module("gxi-peer", log::LevelFilter::Warn)
@@ -113,21 +153,27 @@ fn main() {
     let (xi_peer, xi_rx) = XiPeer::new();
     let core = Core::new(xi_peer, xi_rx, err_tx, shared_queue.clone());
 
-    let application = Application::new(
-        Some("com.github.Cogitri.gxi"),
-        ApplicationFlags::HANDLES_OPEN,
-    )
-    .unwrap_or_else(|_| panic!("Failed to create the GTK+ application"));
+    let (profile_name, args) = cmdline::take_option(args().collect(), "profile");
+    if let Some(name) = profile_name {
+        profile::set(&name);
+    }
+    let (window_name, args) = cmdline::take_option(args, "window");
+    let app_id = window_name::application_id(
+        profile::current().as_ref().map(String::as_str),
+        window_name.as_ref().map(String::as_str),
+    );
+    let application = Application::new(Some(app_id.as_str()), ApplicationFlags::HANDLES_OPEN)
+        .unwrap_or_else(|_| panic!("Failed to create the GTK+ application"));
 
     let main_context = MainContext::default();
     main_context.acquire();
     // Used to create error msgs from threads other than the main thread
     err_rx.attach(Some(&main_context), |err_msg| {
-        crate::errors::ErrorDialog::new(err_msg);
+        crate::errors::show_error(err_msg);
         glib::source::Continue(false)
     });
 
-    application.connect_startup(enclose!((shared_queue, core) move |application| {
+    application.connect_startup(enclose!((shared_queue, core, window_name) move |app| {
         debug!("{}", gettext("Starting gxi"));
 
         glib::set_application_name("gxi");
@@ -147,14 +193,21 @@ fn main() {
             Err(TextDomainError::InvalidLocale(locale)) => warn!("Invalid locale {}", locale),
         }
 
-        core.client_started(None, crate::globals::PLUGIN_DIR.unwrap_or("/usr/local/libexec/gxi/plugins"));
+        let plugin_dir = Sandbox::detect().plugin_dir(&plugin_dir()).unwrap_or_else(|err_msg| {
+            crate::errors::show_error(err_msg);
+            plugin_dir().into()
+        });
+        core.client_started(None, &plugin_dir.to_string_lossy());
 
+        // Before anything can change the settings after an update
+        settings_backup::backup_if_updated(&profile::gschema());
         setup_config(&core);
 
         MainWin::new(
-            application,
+            app,
             shared_queue.clone(),
             core.clone(),
+            window_name.as_ref().map(String::as_str),
            );
     }));
 
@@ -202,11 +255,29 @@ fn main() {
         debug!("{}", gettext("Shutting down…"));
     });
 
-    application.run(&args().collect::<Vec<_>>());
+    application.run(&args);
+}
+
+/// The directory xi-core loads plugins from. gxi isn't installed to a fixed prefix on Windows and
+/// macOS, so unless a directory has been set at build time we look next to the executable there.
+fn plugin_dir() -> String {
+    if let Some(dir) = crate::globals::PLUGIN_DIR {
+        return dir.to_string();
+    }
+    #[cfg(any(windows, target_os = "macos"))]
+    {
+        if let Some(dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("plugins")))
+        {
+            return dir.to_string_lossy().into_owned();
+        }
+    }
+    "/usr/local/libexec/gxi/plugins".to_string()
 }
 
 fn setup_config(core: &Core) {
-    let gschema = GSchema::new("com.github.Cogitri.gxi");
+    let gschema = profile::gschema();
 
     let tab_size: u32 = gschema.get_key("tab-size");
     let autodetect_whitespace: bool = gschema.get_key("auto-indent");
@@ -1,22 +1,67 @@
 use crate::about_win::AboutWin;
-use crate::errors::ErrorDialog;
+use crate::background::{self, Job};
+use crate::blame::Blame;
+use crate::changed_files::ChangedFiles;
+use crate::checksum;
+use crate::core_compat::{self, CoreCompat};
+use crate::diagnostics::{self, Diagnostics};
+use crate::diff_view::DiffView;
+use crate::doc_lock::{DocLock, LockError, LockOwner};
+use crate::errors;
+use crate::external_diff;
+use crate::file_tree::{self, FileTree};
+use crate::filter;
+use crate::find_in_files::FindInFiles;
+use crate::fullscreen::Fullscreen;
+use crate::go_to_file::{self, GoToFile};
+use crate::idle::IdleMonitor;
+use crate::markdown_preview::{self, MarkdownPreview};
+use crate::outline::{self, Outline};
+use crate::panels::Panels;
+use crate::permalink;
+use crate::power::Power;
 use crate::prefs_win::PrefsWin;
-use editview::{theme::u32_from_color, theme::LineStyle, EditView, MainState, Settings};
+use crate::printing::{self, PrintJob};
+use crate::profile;
+use crate::progress::{Operation, Progress};
+use crate::project;
+use crate::scripting::{ScriptAction, Scripts};
+use crate::search_results::SearchResults;
+use crate::session::{PanelState, Session};
+use crate::snippets;
+use crate::speech;
+use crate::sudoedit;
+use crate::tags::{DefinitionPicker, Lookup, TagFiles};
+#[cfg(feature = "terminal")]
+use crate::terminal::{self, Terminal};
+use crate::unhandled_msgs::UnhandledMsgs;
+use crate::whats_new;
+use gdk::SELECTION_CLIPBOARD;
 use gettextrs::gettext;
-use gio::{ActionMapExt, ApplicationExt, SettingsExt, SimpleAction};
-use glib::MainContext;
+use gio::{ActionMapExt, ApplicationExt, NetworkMonitorExt, SettingsExt, SimpleAction};
+use glib::{MainContext, SourceId, ToVariant, VariantTy};
 use gtk::*;
 use gxi_config_storage::{GSchema, GSchemaExt};
+use gxi_editview::{
+    edit_view::{SearchLayout, SelectionSnapshot},
+    infobars::InfoBars,
+    main_state::{abbreviations, languages, long_line_columns},
+    theme::color_from_hex,
+    EditView, LineTransform, MainState, Settings, WhitespaceMode,
+};
 use gxi_peer::ErrorMsg;
 use gxi_peer::{Core, CoreMsg, SharedQueue};
 use log::{debug, error, info, trace, warn};
 use serde_derive::*;
 use serde_json::{self, json, Value};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::{BTreeMap, HashMap};
-use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
-use syntect::highlighting::ThemeSettings;
+use std::time::{Duration, Instant};
 
 /// Returned by an `ask_save_dialog` when we ask the user if he wants to either:
 /// - `Save`(save unsaved changes and close view)
@@ -29,6 +74,25 @@ enum SaveAction {
     Cancel = 102,
 }
 
+/// Returned by the dialog we show if another process has locked a file the user opens
+#[derive(Debug, PartialEq)]
+enum LockAction {
+    ReadOnly = 200,
+    Steal = 201,
+    Cancel = 202,
+}
+
+impl LockAction {
+    fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            200 => Some(LockAction::ReadOnly),
+            201 => Some(LockAction::Steal),
+            202 => Some(LockAction::Cancel),
+            _ => None,
+        }
+    }
+}
+
 impl SaveAction {
     fn from_i32(value: i32) -> Option<Self> {
         match value {
@@ -54,8 +118,9 @@ struct WinProp {
 }
 
 impl WinProp {
-    pub fn new(application: &Application) -> Self {
-        let gschema = GSchema::new(application.get_application_id().unwrap().as_str());
+    /// Reads the size of the window from the GSchema
+    pub fn load() -> Self {
+        let gschema = profile::gschema();
         Self {
             height: gschema.get_key("window-height"),
             width: gschema.get_key("window-width"),
@@ -83,16 +148,102 @@ pub struct MainWin {
     view_id_to_w: RefCell<HashMap<String, Widget>>,
     state: Rc<RefCell<MainState>>,
     properties: RefCell<WinProp>,
+    find_in_files: RefCell<Option<Rc<FindInFiles>>>,
+    /// Lines we should move the cursor to once the file (the key) has been opened
+    pending_lines: RefCell<HashMap<String, u64>>,
+    /// Layout of the panels in the last session
+    session: RefCell<Session>,
+    /// Panels docked at the sides and the bottom of the notebook
+    panels: Panels,
+    /// The panel listing the matches of the search
+    search_results: Rc<SearchResults>,
+    /// The panel listing the problems linters have found in the open files
+    diagnostics: Rc<Diagnostics>,
+    /// The sidebar showing the files of a folder
+    file_tree: Rc<FileTree>,
+    /// The "Go to File…" popover
+    go_to_file: Rc<GoToFile>,
+    /// The "Changed Files" popover
+    changed_files: Rc<ChangedFiles>,
+    /// The tags files definitions are looked up in
+    tag_files: TagFiles,
+    /// Lets the user pick a definition if there are several
+    definition_picker: Rc<DefinitionPicker>,
+    /// The panel listing the symbols of the current document
+    outline: Rc<Outline>,
+    markdown_preview: Rc<MarkdownPreview>,
+    #[cfg(feature = "terminal")]
+    terminal: Rc<Terminal>,
+    /// Whether we still have to restore the search bar's state of the last session on the
+    /// first EditView we open
+    restore_search: Cell<bool>,
+    /// Locks of the files opened in this window, see `DocLock`
+    doc_locks: RefCell<HashMap<String, DocLock>>,
+    /// Whether we've stopped the syntect plugin to save power
+    highlighting_paused: Cell<bool>,
+    /// The user's scripts, loaded once the window is shown
+    scripts: RefCell<Option<Rc<Scripts>>>,
+    /// What xi-core has sent, to check whether it's compatible with gxi
+    core_compat: RefCell<CoreCompat>,
+    /// The notifications from xi-core we don't handle, for bug reports
+    unhandled_msgs: RefCell<UnhandledMsgs>,
+    /// Notifications which aren't about a single document, shown above the notebook
+    infobars: Rc<InfoBars>,
+    /// Shows that we're busy with operations which take a while
+    progress: Rc<Progress>,
+    /// The operations of the huge files xi-editor is loading, by file name
+    loading_files: RefCell<HashMap<String, Operation>>,
+    /// Whether the window is focused and not minimized and the user isn't idle. Otherwise we save
+    /// power by polling less.
+    active: Arc<AtomicBool>,
+    /// Whether the computer is running on battery, as UPower tells us. Connected to UPower in
+    /// `init_deferred`, until then we assume that the computer is plugged in.
+    power: RefCell<Power>,
+    /// Checks the network and runs background jobs every `JOBS_POLL_INTERVAL` seconds
+    /// while the window is active
+    jobs_poll: RefCell<Option<SourceId>>,
+    /// Notices when the user hasn't touched the window for a while, to pause background work
+    idle: Rc<IdleMonitor>,
+    /// Which EditViews show who last changed their lines
+    blame: Blame,
+    /// Moves the header bar out of the way in fullscreen mode
+    fullscreen: Rc<Fullscreen>,
 }
 
 const GLADE_SRC: &str = include_str!("ui/gxi.glade");
+const SYNTECT_PLUGIN: &str = "xi-syntect-plugin";
+/// Factor the text is scaled with per zoom step
+const ZOOM_STEP: f64 = 1.1;
+const MIN_ZOOM: f64 = 0.3;
+const MAX_ZOOM: f64 = 4.0;
+/// How often (in seconds) we check if the network is metered and run background jobs
+const JOBS_POLL_INTERVAL: u32 = 30;
+/// How long we wait before checking for messages from xi-editor again while the window is in the
+/// background, instead of checking all the time
+const BACKGROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Opening at least this many files at once shows the progress and can be cancelled
+const OPEN_PROGRESS_MIN_FILES: usize = 5;
+/// Loading files bigger than this (in bytes) shows the progress
+const HUGE_FILE_SIZE: u64 = 20 * 1024 * 1024;
+/// Id of the saved file of the current document in the choices of "Compare Side by Side"
+const SAVED_FILE_ID: &str = "saved-file";
+/// Syntax themes used with GTK's high contrast themes if no theme with 'contrast' in its name is
+/// available
+const HIGH_CONTRAST_LIGHT_FALLBACK: &str = "InspiredGitHub";
+const HIGH_CONTRAST_DARK_FALLBACK: &str = "base16-eighties.dark";
 
 impl MainWin {
-    pub fn new(application: &Application, shared_queue: SharedQueue, core: Core) -> Rc<Self> {
+    pub fn new(
+        application: &Application,
+        shared_queue: SharedQueue,
+        core: Core,
+        window_name: Option<&str>,
+    ) -> Rc<Self> {
+        let start = Instant::now();
         let glade_src = GLADE_SRC;
         let builder = Builder::new_from_string(glade_src);
 
-        let properties = RefCell::new(WinProp::new(&application));
+        let properties = RefCell::new(WinProp::load());
         let window: ApplicationWindow = builder.get_object("appwindow").unwrap();
 
         if properties.borrow().is_maximized {
@@ -102,21 +253,40 @@ impl MainWin {
         }
 
         let notebook: Notebook = builder.get_object("notebook").unwrap();
+        let infobars = Rc::new(InfoBars::new(&builder.get_object("infobar_box").unwrap()));
+        errors::set_infobars(&infobars);
+        let session = Session::load(window_name);
+        let panels = Panels::new(&builder, &session);
+        let search_results = SearchResults::new(&panels);
+        let diagnostics = Diagnostics::new(&panels);
+        let file_tree = FileTree::new(&panels);
+        if let Some(folder) = session.file_tree_folder.as_ref() {
+            file_tree.set_folder(Path::new(folder));
+        }
+        let go_to_file = GoToFile::new(&builder.get_object("header_bar").unwrap());
+        let changed_files = ChangedFiles::new(&builder.get_object("header_bar").unwrap());
+        let definition_picker = DefinitionPicker::new(&builder.get_object("header_bar").unwrap());
+        let progress = Progress::new(&builder.get_object("header_bar").unwrap());
+        let outline = Outline::new(&panels);
+        let fullscreen = Fullscreen::new(
+            &builder,
+            &notebook,
+            properties
+                .borrow()
+                .gschema
+                .get_key("fullscreen-auto-hide-header-bar"),
+        );
+        let markdown_preview = MarkdownPreview::new(&panels);
+        #[cfg(feature = "terminal")]
+        let terminal = Terminal::new(&panels);
 
         let theme_name = properties.borrow().gschema.get_key("theme-name");
         debug!("{}: {}", gettext("Theme name"), &theme_name);
 
-        let settings = new_settings();
-
         let main_state = Rc::new(RefCell::new(MainState {
-            settings,
             theme_name,
-            themes: Default::default(),
-            theme: Default::default(),
-            styles: Default::default(),
-            fonts: Default::default(),
-            avail_languages: Default::default(),
-            selected_language: Default::default(),
+            high_contrast: is_high_contrast(&gtk_theme_name()),
+            ..MainState::new(Settings::from_gschema(profile::gschema()))
         }));
 
         let main_win = Rc::new(Self {
@@ -130,18 +300,97 @@ impl MainWin {
             view_id_to_w: Default::default(),
             state: main_state.clone(),
             properties,
+            find_in_files: Default::default(),
+            pending_lines: Default::default(),
+            session: RefCell::new(session),
+            panels,
+            search_results: search_results.clone(),
+            diagnostics: diagnostics.clone(),
+            file_tree: file_tree.clone(),
+            go_to_file: go_to_file.clone(),
+            changed_files: changed_files.clone(),
+            tag_files: Default::default(),
+            definition_picker: definition_picker.clone(),
+            outline: outline.clone(),
+            markdown_preview,
+            #[cfg(feature = "terminal")]
+            terminal,
+            restore_search: Cell::new(true),
+            doc_locks: Default::default(),
+            highlighting_paused: Cell::new(false),
+            scripts: Default::default(),
+            core_compat: RefCell::new(CoreCompat::new()),
+            unhandled_msgs: RefCell::new(UnhandledMsgs::new()),
+            infobars,
+            progress,
+            loading_files: Default::default(),
+            active: Arc::new(AtomicBool::new(true)),
+            power: Default::default(),
+            jobs_poll: Default::default(),
+            idle: IdleMonitor::new(&window),
+            blame: Default::default(),
+            fullscreen,
         });
 
         connect_settings_change(&main_win, &core);
 
+        SearchResults::connect_activate(
+            &search_results,
+            enclose!((main_win) move |edit_view, line| {
+                let ev = edit_view.borrow();
+                let idx = main_win.notebook.page_num(&ev.root_widget);
+                main_win.notebook.set_current_page(idx);
+                main_win.core.goto_line(&ev.view_id, line);
+                ev.view_item.ev_scrolled_window.grab_focus();
+            }),
+        );
+        Diagnostics::connect_activate(
+            &diagnostics,
+            enclose!((main_win) move |file_name, line| {
+                main_win.open_file_at(file_name, line);
+            }),
+        );
+        Outline::connect_activate(
+            &outline,
+            enclose!((main_win) move |edit_view, line| {
+                let ev = edit_view.borrow();
+                main_win.core.goto_line(&ev.view_id, line);
+                ev.view_item.ev_scrolled_window.grab_focus();
+            }),
+        );
+        file_tree.connect_open(enclose!((main_win) move |file_name| {
+            main_win.open_file(file_name, None);
+        }));
+        file_tree.connect_rename(enclose!((main_win) move |old, new| {
+            main_win.file_renamed(old, new);
+        }));
+        go_to_file.connect_open(enclose!((main_win) move |file_name| {
+            main_win.open_file(file_name, None);
+        }));
+        changed_files.connect_open(enclose!((main_win) move |file_name| {
+            main_win.open_file(file_name, None);
+        }));
+        definition_picker.connect_open(enclose!((main_win) move |file_name, line| {
+            main_win.open_file_at(file_name, line);
+        }));
+
         let (msg_tx, msg_rx) = MainContext::channel::<CoreMsg>(glib::PRIORITY_HIGH);
         let main_context = MainContext::default();
         main_context.acquire();
 
+        let active = main_win.active.clone();
         thread::spawn(move || loop {
-            if let Ok(msg) = shared_queue.queue_rx.lock().pop() {
-                trace!("{}: {:?}", gettext("Found message in queue"), msg);
-                msg_tx.send(msg).unwrap();
+            // Bind the result, so the queue isn't locked while sleeping
+            let msg = shared_queue.queue_rx.lock().pop();
+            match msg {
+                Ok(msg) => {
+                    trace!("{}: {:?}", gettext("Found message in queue"), msg);
+                    msg_tx.send(msg).unwrap();
+                }
+                Err(_) if !active.load(Ordering::Relaxed) => {
+                    thread::sleep(BACKGROUND_POLL_INTERVAL)
+                }
+                Err(_) => {}
             }
         });
 
@@ -159,6 +408,7 @@ impl MainWin {
         //This is called when the window is closed with the 'X' or via the application menu, etc.
         window.connect_delete_event(enclose!((main_win, window) move |_, _| {
             // Only destroy the window when the user has saved the changes or closes without saving
+            main_win.save_session();
             if Self::close_all(main_win.clone()) == SaveAction::Cancel {
                 debug!("{}", gettext("User chose to cancel exiting"));
                 Inhibit(true)
@@ -171,6 +421,10 @@ impl MainWin {
         }));
 
         window.connect_size_allocate(enclose!((main_win, window) move |_, _| {
+            // Restore the size the window had before going fullscreen next time
+            if main_win.fullscreen.is_fullscreen() {
+                return;
+            }
             let win_size = window.get_size();
             let maximized = window.is_maximized();
 
@@ -182,6 +436,38 @@ impl MainWin {
             }
         }));
 
+        {
+            use gtk::SettingsExt;
+            if let Some(gtk_settings) = gtk::Settings::get_default() {
+                gtk_settings.connect_property_gtk_theme_name_notify(
+                    enclose!((main_win) move |_| main_win.update_high_contrast()),
+                );
+                gtk_settings.connect_property_gtk_xft_dpi_notify(enclose!((main_win) move |_| {
+                    for edit_view in main_win.views.borrow().values() {
+                        EditView::schedule_font_metrics_update(edit_view);
+                    }
+                }));
+            }
+        }
+
+        // Offer to open a file or create a new document when the last tab has been closed
+        let notebook_stack: Stack = builder.get_object("notebook_stack").unwrap();
+        notebook.connect_page_added(enclose!((notebook_stack) move |_, _, _| {
+            notebook_stack.set_visible_child_name("documents");
+        }));
+        notebook.connect_page_removed(enclose!((notebook_stack) move |notebook, _, _| {
+            if notebook.get_n_pages() == 0 {
+                notebook_stack.set_visible_child_name("welcome");
+            }
+        }));
+
+        notebook.connect_switch_page(enclose!((main_win) move |_, w, _| {
+            let edit_view = main_win.w_to_ev.borrow().get(w).cloned();
+            main_win.update_subtitle(edit_view.as_ref());
+            main_win.outline.set_edit_view(edit_view.as_ref());
+            MarkdownPreview::set_edit_view(&main_win.markdown_preview, edit_view.as_ref());
+        }));
+
         {
             let open_action = SimpleAction::new("open", None);
             open_action.connect_activate(enclose!((main_win) move |_,_| {
@@ -214,6 +500,14 @@ impl MainWin {
             }));
             application.add_action(&about_action);
         }
+        {
+            let export_unhandled_msgs_action = SimpleAction::new("export_unhandled_msgs", None);
+            export_unhandled_msgs_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'export_unhandled_msgs' {}", gettext("Handling"), gettext("action"));
+                Self::export_unhandled_msgs(&main_win);
+            }));
+            application.add_action(&export_unhandled_msgs_action);
+        }
         {
             let find_action = SimpleAction::new("find", None);
             find_action.connect_activate(enclose!((main_win) move |_,_| {
@@ -230,6 +524,45 @@ impl MainWin {
             }));
             application.add_action(&replace_action);
         }
+        {
+            let find_in_files_action = SimpleAction::new("find_in_files", None);
+            find_in_files_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'find_in_files' {}", gettext("Handling"), gettext("action"));
+                Self::find_in_files(&main_win);
+            }));
+            application.add_action(&find_in_files_action);
+        }
+        {
+            let go_to_file_action = SimpleAction::new("go_to_file", None);
+            go_to_file_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'go_to_file' {}", gettext("Handling"), gettext("action"));
+                main_win.show_go_to_file();
+            }));
+            application.add_action(&go_to_file_action);
+        }
+        {
+            let changed_files_action = SimpleAction::new("changed_files", None);
+            changed_files_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'changed_files' {}", gettext("Handling"), gettext("action"));
+                main_win.show_changed_files();
+            }));
+            application.add_action(&changed_files_action);
+        }
+        {
+            let go_to_definition_action = SimpleAction::new("go_to_definition", None);
+            go_to_definition_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'go_to_definition' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    if !edit_view.borrow().go_to_definition() {
+                        main_win.notify(
+                            &gettext("Couldn't look up the definition, create a tags file for the project with 'ctags -R'"),
+                            MessageType::Info,
+                        );
+                    }
+                }
+            }));
+            application.add_action(&go_to_definition_action);
+        }
         {
             let save_action = SimpleAction::new("save", None);
             save_action.connect_activate(enclose!((main_win) move |_,_| {
@@ -246,6 +579,78 @@ impl MainWin {
             }));
             application.add_action(&save_as_action);
         }
+        {
+            let rename_case_action = SimpleAction::new("rename_case", None);
+            rename_case_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'rename_case' {}", gettext("Handling"), gettext("action"));
+                Self::rename_with_case_change(&main_win);
+            }));
+            application.add_action(&rename_case_action);
+        }
+        {
+            let export_action = SimpleAction::new("export", None);
+            export_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'export' {}", gettext("Handling"), gettext("action"));
+                Self::export(&main_win);
+            }));
+            application.add_action(&export_action);
+        }
+        {
+            let print_action = SimpleAction::new("print", None);
+            print_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'print' {}", gettext("Handling"), gettext("action"));
+                Self::print(&main_win, false);
+            }));
+            application.add_action(&print_action);
+        }
+        {
+            let print_preview_action = SimpleAction::new("print_preview", None);
+            print_preview_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'print_preview' {}", gettext("Handling"), gettext("action"));
+                Self::print(&main_win, true);
+            }));
+            application.add_action(&print_preview_action);
+        }
+        #[cfg(feature = "terminal")]
+        {
+            let open_terminal_action = SimpleAction::new("open_terminal", None);
+            open_terminal_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'open_terminal' {}", gettext("Handling"), gettext("action"));
+                main_win.open_terminal();
+            }));
+            application.add_action(&open_terminal_action);
+        }
+        // There's no action for the menu entry without VTE, so don't show it at all
+        #[cfg(not(feature = "terminal"))]
+        {
+            let open_terminal_button: Widget =
+                main_win.builder.get_object("open_terminal_button").unwrap();
+            open_terminal_button.hide();
+        }
+        {
+            let name_window_action = SimpleAction::new("name_window", None);
+            name_window_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'name_window' {}", gettext("Handling"), gettext("action"));
+                Self::name_window(&main_win);
+            }));
+            application.add_action(&name_window_action);
+        }
+        {
+            let compare_with_action = SimpleAction::new("compare_with", None);
+            compare_with_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'compare_with' {}", gettext("Handling"), gettext("action"));
+                Self::compare_with(&main_win);
+            }));
+            application.add_action(&compare_with_action);
+        }
+        {
+            let compare_side_by_side_action = SimpleAction::new("compare_side_by_side", None);
+            compare_side_by_side_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'compare_side_by_side' {}", gettext("Handling"), gettext("action"));
+                Self::compare_side_by_side(&main_win);
+            }));
+            application.add_action(&compare_side_by_side_action);
+        }
         {
             let close_action = SimpleAction::new("close", None);
             close_action.connect_activate(enclose!((main_win) move |_,_| {
@@ -268,6 +673,7 @@ impl MainWin {
             quit_action.connect_activate(enclose!((main_win) move |_,_| {
                 trace!("{} 'quit' {}", gettext("Handling"), gettext("action"));
                 // Same as in connect_destroy, only quit if the user saves or wants to close without saving
+                main_win.save_session();
                 if Self::close_all(main_win.clone()) == SaveAction::Cancel {
                     debug!("{}", gettext("User chose to not quit application"));
                 } else {
@@ -277,6 +683,61 @@ impl MainWin {
             }));
             application.add_action(&quit_action);
         }
+        {
+            let toggle_panel_action =
+                SimpleAction::new("toggle_panel", Some(VariantTy::new("s").unwrap()));
+            toggle_panel_action.connect_activate(enclose!((main_win) move |_, name| {
+                trace!("{} 'toggle_panel' {}", gettext("Handling"), gettext("action"));
+                if let Some(name) = name.as_ref().and_then(|name| name.get_str()) {
+                    main_win.panels.toggle(name);
+                }
+            }));
+            application.add_action(&toggle_panel_action);
+        }
+        {
+            let caret_back_action = SimpleAction::new("caret_back", None);
+            caret_back_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'caret_back' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().caret_back();
+                }
+            }));
+            application.add_action(&caret_back_action);
+        }
+        {
+            let caret_forward_action = SimpleAction::new("caret_forward", None);
+            caret_forward_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'caret_forward' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().caret_forward();
+                }
+            }));
+            application.add_action(&caret_forward_action);
+        }
+        {
+            let set_quick_mark_action =
+                SimpleAction::new("set_quick_mark", Some(VariantTy::new("s").unwrap()));
+            set_quick_mark_action.connect_activate(enclose!((main_win) move |_, name| {
+                trace!("{} 'set_quick_mark' {}", gettext("Handling"), gettext("action"));
+                let name = name.as_ref().and_then(|name| name.get_str()).and_then(|name| name.chars().next());
+                if let (Some(edit_view), Some(name)) = (main_win.get_current_edit_view(), name) {
+                    edit_view.borrow().set_quick_mark(name);
+                }
+            }));
+            application.add_action(&set_quick_mark_action);
+        }
+        {
+            let jump_to_quick_mark_action =
+                SimpleAction::new("jump_to_quick_mark", Some(VariantTy::new("s").unwrap()));
+            jump_to_quick_mark_action.connect_activate(enclose!((main_win) move |_, name| {
+                trace!("{} 'jump_to_quick_mark' {}", gettext("Handling"), gettext("action"));
+                let name = name.as_ref().and_then(|name| name.get_str()).and_then(|name| name.chars().next());
+                if let Some(name) = name {
+                    main_win.jump_to_quick_mark(name);
+                }
+            }));
+            application.add_action(&jump_to_quick_mark_action);
+        }
         {
             let auto_indent_action = SimpleAction::new_stateful(
                 "auto_indent",
@@ -313,45 +774,555 @@ impl MainWin {
 
             application.add_action(&space_indent_action);
         }
+        {
+            let private_mode_action =
+                SimpleAction::new_stateful("private_mode", None, &false.to_variant());
+
+            private_mode_action.connect_change_state(enclose!((main_win) move |action, value| {
+                if let Some(value) = value.as_ref() {
+                    action.set_state(value);
+                    main_win.set_private(value.get::<bool>().unwrap());
+                }
+            }));
+
+            application.add_action(&private_mode_action);
+        }
+        {
+            let duplicate_line_action = SimpleAction::new("duplicate_line", None);
+            duplicate_line_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'duplicate_line' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().duplicate_line();
+                }
+            }));
+            application.add_action(&duplicate_line_action);
+        }
+        {
+            let join_lines_action = SimpleAction::new("join_lines", None);
+            join_lines_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'join_lines' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().join_lines();
+                }
+            }));
+            application.add_action(&join_lines_action);
+        }
+        {
+            let transpose_action = SimpleAction::new("transpose", None);
+            transpose_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'transpose' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().transpose();
+                }
+            }));
+            application.add_action(&transpose_action);
+        }
+        {
+            let select_next_occurrence_action = SimpleAction::new("select_next_occurrence", None);
+            select_next_occurrence_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'select_next_occurrence' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().select_next_occurrence();
+                }
+            }));
+            application.add_action(&select_next_occurrence_action);
+        }
+        {
+            let find_selection_next_action = SimpleAction::new("find_selection_next", None);
+            find_selection_next_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'find_selection_next' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().find_selection(true);
+                }
+            }));
+            application.add_action(&find_selection_next_action);
+        }
+        {
+            let find_selection_prev_action = SimpleAction::new("find_selection_prev", None);
+            find_selection_prev_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'find_selection_prev' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().find_selection(false);
+                }
+            }));
+            application.add_action(&find_selection_prev_action);
+        }
+        {
+            let toggle_bookmark_action = SimpleAction::new("toggle_bookmark", None);
+            toggle_bookmark_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'toggle_bookmark' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow_mut().toggle_bookmark();
+                }
+            }));
+            application.add_action(&toggle_bookmark_action);
+        }
+        for (name, forward) in &[("next_bookmark", true), ("previous_bookmark", false)] {
+            let (name, forward) = (*name, *forward);
+            let action = SimpleAction::new(name, None);
+            action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} '{}' {}", gettext("Handling"), name, gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().goto_bookmark(forward);
+                }
+            }));
+            application.add_action(&action);
+        }
+        for (name, extend) in &[
+            ("goto_matching_bracket", false),
+            ("select_to_matching_bracket", true),
+        ] {
+            let (name, extend) = (*name, *extend);
+            let action = SimpleAction::new(name, None);
+            action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} '{}' {}", gettext("Handling"), name, gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().goto_matching_bracket(extend);
+                }
+            }));
+            application.add_action(&action);
+        }
+        {
+            let toggle_comment_action = SimpleAction::new("toggle_comment", None);
+            toggle_comment_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'toggle_comment' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().toggle_comment();
+                }
+            }));
+            application.add_action(&toggle_comment_action);
+        }
+        for (name, transform) in &[
+            ("sort_lines", LineTransform::SortAscending),
+            ("sort_lines_descending", LineTransform::SortDescending),
+            ("reverse_lines", LineTransform::Reverse),
+            ("unique_lines", LineTransform::Unique),
+        ] {
+            let (name, transform) = (*name, *transform);
+            let action = SimpleAction::new(name, None);
+            action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} '{}' {}", gettext("Handling"), name, gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    EditView::transform_lines(&edit_view, transform);
+                }
+            }));
+            application.add_action(&action);
+        }
+        {
+            let format_table_action = SimpleAction::new("format_table", None);
+            format_table_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'format_table' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().format_table();
+                }
+            }));
+            application.add_action(&format_table_action);
+        }
+        {
+            let insert_table_action = SimpleAction::new("insert_table", None);
+            insert_table_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'insert_table' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    if let Some((rows, columns)) = Self::ask_table_size(&main_win) {
+                        edit_view.borrow().insert_table(rows, columns);
+                    }
+                }
+            }));
+            application.add_action(&insert_table_action);
+        }
+        {
+            let insert_date_time_action = SimpleAction::new("insert_date_time", None);
+            insert_date_time_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'insert_date_time' {}", gettext("Handling"), gettext("action"));
+                main_win.insert_date_time();
+            }));
+            application.add_action(&insert_date_time_action);
+        }
+        {
+            let insert_uuid_action = SimpleAction::new("insert_uuid", None);
+            insert_uuid_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'insert_uuid' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    edit_view.borrow().insert_text(&snippets::uuid_v4());
+                }
+            }));
+            application.add_action(&insert_uuid_action);
+        }
+        {
+            let insert_file_path_action = SimpleAction::new("insert_file_path", None);
+            insert_file_path_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'insert_file_path' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    let ev = edit_view.borrow();
+                    if let Some(file_name) = &ev.file_name {
+                        ev.insert_text(file_name);
+                    }
+                }
+            }));
+            application.add_action(&insert_file_path_action);
+        }
+        {
+            let checksums_action = SimpleAction::new("checksums", None);
+            checksums_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'checksums' {}", gettext("Handling"), gettext("action"));
+                Self::show_checksums(&main_win);
+            }));
+            application.add_action(&checksums_action);
+        }
+        {
+            let copy_permalink_action = SimpleAction::new("copy_permalink", None);
+            copy_permalink_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'copy_permalink' {}", gettext("Handling"), gettext("action"));
+                main_win.copy_permalink();
+            }));
+            application.add_action(&copy_permalink_action);
+        }
+        {
+            let fullscreen_action =
+                SimpleAction::new_stateful("fullscreen", None, &false.to_variant());
+
+            fullscreen_action.connect_change_state(enclose!((main_win) move |_, value| {
+                if let Some(value) = value.as_ref() {
+                    // The state changes once the window has, it may also leave fullscreen on
+                    // its own
+                    if value.get::<bool>().unwrap() {
+                        main_win.window.fullscreen();
+                    } else {
+                        main_win.window.unfullscreen();
+                    }
+                }
+            }));
+            main_win.window.connect_window_state_event(
+                enclose!((main_win, fullscreen_action) move |_, _| {
+                    fullscreen_action.set_state(&main_win.fullscreen.is_fullscreen().to_variant());
+                    Inhibit(false)
+                }),
+            );
 
+            application.add_action(&fullscreen_action);
+        }
+        {
+            let toggle_blame_action = SimpleAction::new("toggle_blame", None);
+            toggle_blame_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'toggle_blame' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    main_win.blame.toggle(&edit_view);
+                }
+            }));
+            application.add_action(&toggle_blame_action);
+        }
+        {
+            let copy_formatted_action = SimpleAction::new("copy_formatted", None);
+            copy_formatted_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'copy_formatted' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    EditView::copy_with_formatting(&edit_view);
+                }
+            }));
+            application.add_action(&copy_formatted_action);
+        }
+        {
+            let filter_selection_action = SimpleAction::new("filter_selection", None);
+            filter_selection_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'filter_selection' {}", gettext("Handling"), gettext("action"));
+                Self::filter_selection(&main_win);
+            }));
+            application.add_action(&filter_selection_action);
+        }
+        {
+            let speak_selection_action = SimpleAction::new("speak_selection", None);
+            speak_selection_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'speak_selection' {}", gettext("Handling"), gettext("action"));
+                if let Some(edit_view) = main_win.get_current_edit_view() {
+                    main_win.speak_selection(&edit_view.borrow());
+                }
+            }));
+            application.add_action(&speak_selection_action);
+        }
+        {
+            let stop_speaking_action = SimpleAction::new("stop_speaking", None);
+            stop_speaking_action.connect_activate(move |_, _| {
+                trace!(
+                    "{} 'stop_speaking' {}",
+                    gettext("Handling"),
+                    gettext("action")
+                );
+                if let Err(e) = speech::stop() {
+                    warn!("{}: {}", gettext("Failed to stop speaking"), e);
+                }
+            });
+            application.add_action(&stop_speaking_action);
+        }
+        {
+            let zoom_in_action = SimpleAction::new("zoom_in", None);
+            zoom_in_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'zoom_in' {}", gettext("Handling"), gettext("action"));
+                let zoom = main_win.state.borrow().zoom;
+                main_win.set_zoom(zoom * ZOOM_STEP);
+            }));
+            application.add_action(&zoom_in_action);
+        }
+        {
+            let zoom_out_action = SimpleAction::new("zoom_out", None);
+            zoom_out_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'zoom_out' {}", gettext("Handling"), gettext("action"));
+                let zoom = main_win.state.borrow().zoom;
+                main_win.set_zoom(zoom / ZOOM_STEP);
+            }));
+            application.add_action(&zoom_out_action);
+        }
+        {
+            let zoom_reset_action = SimpleAction::new("zoom_reset", None);
+            zoom_reset_action.connect_activate(enclose!((main_win) move |_,_| {
+                trace!("{} 'zoom_reset' {}", gettext("Handling"), gettext("action"));
+                main_win.set_zoom(1.0);
+            }));
+            application.add_action(&zoom_reset_action);
+        }
+
+        main_win.update_replace_accel();
         /* Put keyboard shortcuts here*/
         if let Some(app) = window.get_application() {
             app.set_accels_for_action("app.find", &["<Primary>f"]);
+            app.set_accels_for_action("app.find_in_files", &["<Primary><Shift>f"]);
+            app.set_accels_for_action("app.go_to_file", &["<Primary>p"]);
+            app.set_accels_for_action("app.changed_files", &["<Primary><Shift>g"]);
+            app.set_accels_for_action("app.go_to_definition", &["F12"]);
+            app.set_accels_for_action("app.fullscreen", &["F11"]);
+            app.set_accels_for_action(
+                &format!("app.toggle_panel::{}", file_tree::PANEL_NAME),
+                &["<Primary><Shift>e"],
+            );
+            app.set_accels_for_action(
+                &format!("app.toggle_panel::{}", outline::PANEL_NAME),
+                &["<Primary><Shift>o"],
+            );
+            #[cfg(feature = "terminal")]
+            app.set_accels_for_action(
+                &format!("app.toggle_panel::{}", terminal::PANEL_NAME),
+                &["<Primary>grave"],
+            );
             app.set_accels_for_action("app.save", &["<Primary>s"]);
             app.set_accels_for_action("app.new", &["<Primary>n"]);
             app.set_accels_for_action("app.open", &["<Primary>o"]);
             app.set_accels_for_action("app.quit", &["<Primary>q"]);
-            app.set_accels_for_action("app.replace", &["<Primary>r"]);
             app.set_accels_for_action("app.close", &["<Primary>w"]);
+            app.set_accels_for_action("app.duplicate_line", &["<Primary><Shift>d"]);
+            app.set_accels_for_action("app.join_lines", &["<Primary>j"]);
+            app.set_accels_for_action("app.transpose", &["<Primary>t"]);
+            app.set_accels_for_action("app.select_next_occurrence", &["<Primary>d"]);
+            app.set_accels_for_action("app.find_selection_next", &["<Primary>F3"]);
+            app.set_accels_for_action("app.find_selection_prev", &["<Primary><Shift>F3"]);
+            app.set_accels_for_action("app.toggle_bookmark", &["<Primary>F2"]);
+            app.set_accels_for_action("app.next_bookmark", &["F2"]);
+            app.set_accels_for_action("app.previous_bookmark", &["<Shift>F2"]);
+            app.set_accels_for_action(
+                "app.goto_matching_bracket",
+                &["<Primary>percent", "<Primary>m"],
+            );
+            app.set_accels_for_action("app.select_to_matching_bracket", &["<Primary><Shift>m"]);
+            app.set_accels_for_action("app.toggle_comment", &["<Primary>slash"]);
+            app.set_accels_for_action("app.zoom_in", &["<Primary>plus", "<Primary>equal"]);
+            app.set_accels_for_action("app.zoom_out", &["<Primary>minus"]);
+            app.set_accels_for_action("app.zoom_reset", &["<Primary>0"]);
+            app.set_accels_for_action("app.caret_back", &["<Alt>minus"]);
+            app.set_accels_for_action("app.caret_forward", &["<Alt>equal"]);
+            for digit in 1..=9 {
+                app.set_accels_for_action(
+                    &format!("app.set_quick_mark::{}", digit),
+                    &[format!("<Primary><Shift>{}", digit).as_str()],
+                );
+                app.set_accels_for_action(
+                    &format!("app.jump_to_quick_mark::{}", digit),
+                    &[format!("<Primary>{}", digit).as_str()],
+                );
+            }
         }
 
         debug!("{}", gettext("Showing main window"));
         window.show_all();
+        debug!(
+            "{}: {:?}",
+            gettext("Time until the main window was shown"),
+            start.elapsed()
+        );
+
+        glib::idle_add_local(enclose!((main_win) move || {
+            Self::init_deferred(&main_win);
+            debug!(
+                "{}: {:?}",
+                gettext("Time until the deferred initialization was done"),
+                start.elapsed()
+            );
+            glib::Continue(false)
+        }));
 
         main_win
     }
-    /*
-    pub fn activate(_application: &Application, _shared_queue: Arc<Mutex<SharedQueue>>) {
-        // TODO
-        unimplemented!();
-    }
-    pub fn open(_application: &Application, _shared_queue: Arc<Mutex<SharedQueue>>) {
-        // TODO
-        unimplemented!();
-    }
-    */
-}
 
-impl MainWin {
-    pub fn handle_msg(main_win: &Rc<Self>, msg: CoreMsg) {
-        trace!("{}: {:?}", gettext("Handling CoreMsg"), msg);
-        match msg {
-            CoreMsg::NewViewReply { file_name, value } => {
-                Self::new_view_response(&main_win, file_name, &value)
-            }
-            CoreMsg::Notification { method, params, id } => {
-                match method.as_ref() {
-                    "alert" => main_win.alert(&params),
+    /// Does the setup which isn't needed to show the window and start typing, like checking the
+    /// power supply and network or reopening panels of the last session. It runs once GTK is idle,
+    /// so it doesn't hold up drawing the window on slow disks.
+    fn init_deferred(main_win: &Rc<Self>) {
+        main_win.power.replace(Power::new());
+        main_win
+            .power
+            .borrow()
+            .connect_changed(enclose!((main_win) move || {
+                main_win.update_highlighting();
+                main_win.update_reduced_mode();
+                main_win.update_idle_timeout();
+            }));
+        main_win.update_idle_timeout();
+        main_win.update_spell_check_language();
+        Self::update_activity(main_win);
+        main_win
+            .idle
+            .connect_changed(enclose!((main_win) move |_| Self::update_activity(&main_win)));
+        main_win
+            .window
+            .connect_property_is_active_notify(enclose!((main_win) move |_| {
+                Self::update_activity(&main_win);
+            }));
+        main_win
+            .window
+            .connect_window_state_event(enclose!((main_win) move |_, _| {
+                Self::update_activity(&main_win);
+                Inhibit(false)
+            }));
+
+        glib::timeout_add_seconds_local(
+            core_compat::CHECK_DELAY,
+            enclose!((main_win) move || {
+                let warning = main_win.core_compat.borrow_mut().check();
+                if let Some(msg) = warning {
+                    main_win.notify(&msg, MessageType::Warning);
+                }
+                glib::Continue(false)
+            }),
+        );
+
+        gio::NetworkMonitor::get_default().connect_property_network_metered_notify(
+            enclose!((main_win) move |_| main_win.update_reduced_mode()),
+        );
+        main_win.update_reduced_mode();
+
+        Self::load_scripts(main_win);
+        Self::restore_session(main_win);
+        whats_new::check(&main_win.window, &main_win.properties.borrow().gschema);
+    }
+
+    /// Runs the user's scripts and adds the commands they register to the Scripts menu
+    fn load_scripts(main_win: &Rc<Self>) {
+        let (scripts, errors) = Scripts::load();
+        if !errors.is_empty() {
+            let msg = format!(
+                "{}:\n{}",
+                gettext("Failed to load scripts"),
+                errors.join("\n")
+            );
+            main_win.notify(&msg, MessageType::Error);
+        }
+        let commands = scripts.commands();
+        let action_accels = scripts.action_accels();
+        main_win.scripts.replace(Some(Rc::new(scripts)));
+
+        let application = match main_win.window.get_application() {
+            Some(application) => application,
+            None => return,
+        };
+        for (action_name, accel) in action_accels {
+            application.set_accels_for_action(&format!("app.{}", action_name), &[accel.as_str()]);
+        }
+        if commands.is_empty() {
+            return;
+        }
+
+        let menu_button: ModelButton = main_win.builder.get_object("scripts_menu_button").unwrap();
+        let menu_box: Box = main_win.builder.get_object("scripts_menu_box").unwrap();
+        menu_button.show();
+
+        for command in commands {
+            let action_name = format!("script-{}", command.name);
+            let detailed_name = format!("app.{}", action_name);
+            let action = SimpleAction::new(&action_name, None);
+            let name = command.name.clone();
+            action.connect_activate(enclose!((main_win) move |_, _| {
+                trace!("{} '{}' {}", gettext("Handling"), name, gettext("action"));
+                Self::run_script_command(&main_win, &name);
+            }));
+            application.add_action(&action);
+            if let Some(accel) = &command.accel {
+                application.set_accels_for_action(&detailed_name, &[accel.as_str()]);
+            }
+
+            let button = ModelButton::new();
+            button.set_property_text(Some(command.label.as_str()));
+            button.set_action_name(Some(detailed_name.as_str()));
+            button.show();
+            menu_box.pack_start(&button, false, true, 0);
+        }
+    }
+
+    /// Runs the script command `name` on the selection of the current EditView and does what the
+    /// command asks for
+    fn run_script_command(main_win: &Rc<Self>, name: &str) {
+        let name = name.to_string();
+        let run = enclose!((main_win) move |selection: String| {
+            let scripts = match main_win.scripts.borrow().clone() {
+                Some(scripts) => scripts,
+                None => return,
+            };
+            match scripts.run(&name, &selection) {
+                Ok(actions) => {
+                    for action in actions {
+                        match action {
+                            ScriptAction::Open(path) => {
+                                main_win.req_new_view(Some(&path.to_string_lossy()))
+                            }
+                            ScriptAction::Insert(text) => {
+                                if let Some(edit_view) = main_win.get_current_edit_view() {
+                                    edit_view.borrow().insert_text(&text);
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    let msg = format!("{} '{}': {}", gettext("Script command failed"), name, e);
+                    main_win.notify(&msg, MessageType::Error);
+                }
+            }
+        });
+
+        match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view.borrow().with_selected_text(run),
+            None => run(String::new()),
+        }
+    }
+    /*
+    pub fn activate(_application: &Application, _shared_queue: Arc<Mutex<SharedQueue>>) {
+        // TODO
+        unimplemented!();
+    }
+    pub fn open(_application: &Application, _shared_queue: Arc<Mutex<SharedQueue>>) {
+        // TODO
+        unimplemented!();
+    }
+    */
+}
+
+impl MainWin {
+    pub fn handle_msg(main_win: &Rc<Self>, msg: CoreMsg) {
+        trace!("{}: {:?}", gettext("Handling CoreMsg"), msg);
+        match msg {
+            CoreMsg::NewViewReply { file_name, value } => {
+                Self::new_view_response(&main_win, file_name, &value)
+            }
+            CoreMsg::Notification { method, params, id } => {
+                let mut known = true;
+                match method.as_ref() {
+                    "alert" => main_win.alert(&params),
                     "available_themes" => main_win.available_themes(&params),
                     "available_plugins" => main_win.available_plugins(&params),
                     "config_changed" => main_win.config_changed(&params),
@@ -367,23 +1338,32 @@ impl MainWin {
                     "plugin_started" => main_win.plugin_started(&params),
                     "plugin_stopped" => main_win.plugin_stopped(&params),
                     _ => {
+                        known = false;
                         error!(
                             "{}: {}",
                             gettext("!!! UNHANDLED NOTIFICATION, PLEASE OPEN A BUGREPORT!"),
                             method
                         );
+                        main_win
+                            .unhandled_msgs
+                            .borrow_mut()
+                            .record(&method, &params);
                     }
                 };
+                main_win.core_compat.borrow_mut().note(&method, known);
             }
         };
     }
 
+    /// Shows `msg` above the notebook until the user closes it, for notifications which aren't
+    /// about a single document
+    pub fn notify(&self, msg: &str, msg_type: MessageType) {
+        self.infobars.show(msg, msg_type);
+    }
+
     pub fn alert(&self, params: &Value) {
         if let Some(msg) = params["msg"].as_str() {
-            ErrorDialog::new(ErrorMsg {
-                msg: msg.to_string(),
-                fatal: false,
-            });
+            self.notify(msg, MessageType::Warning);
         }
     }
 
@@ -398,6 +1378,13 @@ impl MainWin {
             }
         }
 
+        if state.high_contrast {
+            let gtk_theme = gtk_theme_name();
+            if let Some(theme_name) = high_contrast_theme(&state.themes, is_dark(&gtk_theme)) {
+                state.theme_name = theme_name;
+            }
+        }
+
         if !state.themes.contains(&state.theme_name) {
             error!(
                 "{} {} {}",
@@ -418,29 +1405,45 @@ impl MainWin {
     }
 
     pub fn theme_changed(&self, params: &Value) {
-        let theme_settings = params["theme"].clone();
-        let theme: ThemeSettings = match serde_json::from_value(theme_settings) {
-            Err(e) => {
-                error!("{}: {}", gettext("Failed to convert theme settings"), e);
-                return;
-            }
-            Ok(ts) => ts,
-        };
+        self.state.borrow_mut().theme_changed(params);
+    }
 
-        // FIXME: Use annotations instead of constructing the selection style here
-        let selection_style = LineStyle {
-            fg_color: theme
-                .selection_foreground
-                .and_then(|s| Some(u32_from_color(s))),
-            bg_color: theme.selection.and_then(|s| Some(u32_from_color(s))),
-            weight: None,
-            italic: None,
-            underline: None,
-        };
+    /// Updates the style of selections after their color has been changed and redraws
+    fn update_selection_style(&self) {
+        self.state.borrow_mut().update_builtin_styles();
+        if let Some(ev) = self.get_current_edit_view() {
+            ev.borrow().view_item.edit_area.queue_draw();
+        }
+    }
 
-        let mut state = self.state.borrow_mut();
-        state.theme = theme;
-        state.styles.insert(0, selection_style);
+    /// Switches to a high contrast syntax theme (and back) when GTK's high contrast theme is
+    /// enabled (or disabled)
+    fn update_high_contrast(&self) {
+        let gtk_theme = gtk_theme_name();
+        let high_contrast = is_high_contrast(&gtk_theme);
+        if high_contrast == self.state.borrow().high_contrast {
+            return;
+        }
+
+        debug!(
+            "{}: {}",
+            gettext("Setting high contrast mode"),
+            high_contrast
+        );
+        {
+            let mut state = self.state.borrow_mut();
+            state.high_contrast = high_contrast;
+            let theme_name = if high_contrast {
+                high_contrast_theme(&state.themes, is_dark(&gtk_theme))
+            } else {
+                Some(state.settings.gschema.get_key("theme-name"))
+            };
+            if let Some(theme_name) = theme_name.filter(|t| *t != state.theme_name) {
+                self.core.set_theme(&theme_name);
+                state.theme_name = theme_name;
+            }
+        }
+        self.update_selection_style();
     }
 
     pub fn available_plugins(&self, params: &Value) {
@@ -448,17 +1451,15 @@ impl MainWin {
 
         if let Some(available_plugins) = params["plugins"].as_array() {
             for x in available_plugins {
-                if x["name"] == "xi-syntect-plugin" {
+                if x["name"] == SYNTECT_PLUGIN {
                     has_syntect = true;
                 }
             }
         }
 
         if !has_syntect {
-            ErrorDialog::new(ErrorMsg {
-                msg: format!("{}: {:?}", gettext("Couldn't find syntect plugin, functionality will be limited! Only found the following plugins"), params["plugins"].as_array()),
-                fatal: false,
-            });
+            let msg = format!("{}: {:?}", gettext("Couldn't find syntect plugin, functionality will be limited! Only found the following plugins"), params["plugins"].as_array());
+            self.notify(&msg, MessageType::Warning);
         }
     }
 
@@ -484,19 +1485,16 @@ impl MainWin {
     }
 
     pub fn def_style(&self, params: &Value) {
-        let style: LineStyle = serde_json::from_value(params.clone()).unwrap();
-
-        if let Some(id) = params["id"].as_u64() {
-            let mut state = self.state.borrow_mut();
-            state.styles.insert(id as usize, style);
-        }
+        self.state.borrow_mut().def_style(params);
     }
 
     pub fn update(&self, params: &Value) {
         trace!("{} 'update': {:?}", gettext("Handling"), params);
         let views = self.views.borrow();
         if let Some(ev) = params["view_id"].as_str().and_then(|id| views.get(id)) {
-            ev.borrow_mut().update(params)
+            ev.borrow_mut().update(params);
+            Outline::document_changed(&self.outline, &ev.borrow().view_id);
+            MarkdownPreview::document_changed(&self.markdown_preview, &ev.borrow().view_id);
         }
     }
 
@@ -529,7 +1527,16 @@ impl MainWin {
 
     fn plugin_stopped(&self, params: &Value) {
         if let Some(plugin) = params["plugin"].as_str() {
+            if plugin == SYNTECT_PLUGIN && self.highlighting_paused.get() {
+                debug!("{}", gettext("Syntax highlighting has been paused"));
+                return;
+            }
+
             let err_code = params["code"].as_u64();
+            let msg_type = match err_code {
+                Some(0) => MessageType::Info,
+                _ => MessageType::Warning,
+            };
 
             let err_msg = match err_code {
                 Some(0) => gettext("has stopped due to an user-initiated exit"),
@@ -541,16 +1548,21 @@ impl MainWin {
                 None => gettext("has crashed"),
             };
 
-            ErrorDialog::new(ErrorMsg {
-                msg: format!(
-                    "{} {} {} {}",
-                    gettext("Plugin"),
-                    plugin,
-                    err_msg,
-                    gettext("functionality will be limited")
-                ),
-                fatal: false,
-            });
+            let msg = format!(
+                "{} {} {} {}",
+                gettext("Plugin"),
+                plugin,
+                err_msg,
+                gettext("functionality will be limited")
+            );
+            // Plugins run per view, so we show it in the view if xi-editor tells us which one
+            let edit_view = params["view_id"]
+                .as_str()
+                .and_then(|view_id| self.views.borrow().get(view_id).cloned());
+            match edit_view {
+                Some(edit_view) => edit_view.borrow().notify(&msg, msg_type),
+                None => self.notify(&msg, msg_type),
+            }
         }
     }
 
@@ -593,137 +1605,1475 @@ impl MainWin {
         }
     }
 
-    pub fn language_changed(&self, params: &Value) {
-        debug!("{} 'language_changed' {:?}", gettext("Handling"), params);
-        let views = self.views.borrow();
-        if let Some(ev) = params["view_id"].as_str().and_then(|id| views.get(id)) {
-            ev.borrow().language_changed(params["language_id"].as_str())
+    pub fn language_changed(&self, params: &Value) {
+        debug!("{} 'language_changed' {:?}", gettext("Handling"), params);
+        let views = self.views.borrow();
+        if let Some(ev) = params["view_id"].as_str().and_then(|id| views.get(id)) {
+            ev.borrow().language_changed(params["language_id"].as_str());
+            Outline::document_changed(&self.outline, &ev.borrow().view_id);
+            MarkdownPreview::document_changed(&self.markdown_preview, &ev.borrow().view_id);
+        }
+    }
+
+    /// Display the FileChooserNative for opening, send the result to the Xi core.
+    /// Don't use FileChooserDialog here, it doesn't work for Flatpaks.
+    /// This may call the GTK main loop.  There must not be any RefCell borrows out while this
+    /// function runs.
+    pub fn handle_open_button(main_win: &Rc<Self>) {
+        let fcn = FileChooserNative::new(
+            Some(gettext("Open a file to edit").as_str()),
+            Some(&main_win.window),
+            FileChooserAction::Open,
+            Some(gettext("Open").as_str()),
+            Some(gettext("Cancel").as_str()),
+        );
+        fcn.set_transient_for(Some(&main_win.window.clone()));
+        fcn.set_select_multiple(true);
+
+        fcn.connect_response(enclose!((main_win) move |fcd, res| {
+            debug!(
+                "{}: {:#?}",
+                gettext("FileChooserNative open response"),
+                res
+            );
+
+            if res == ResponseType::Accept {
+                Self::open_files(&main_win, fcd.get_filenames());
+            }
+        }));
+
+        fcn.run();
+    }
+
+    /// Opens `files` one after the other, so the window stays responsive while many files are
+    /// opened. Opening many files shows the progress, and the user can cancel opening the rest.
+    fn open_files(main_win: &Rc<Self>, files: Vec<PathBuf>) {
+        let total = files.len();
+        let operation = if total >= OPEN_PROGRESS_MIN_FILES {
+            Some(Progress::start(
+                &main_win.progress,
+                &gettext("Opening files"),
+            ))
+        } else {
+            None
+        };
+        let mut files = files.into_iter().enumerate();
+        // The operation is dropped with the closure once we're done
+        glib::idle_add_local(enclose!((main_win) move || {
+            if operation.as_ref().map_or(false, Operation::is_cancelled) {
+                return glib::Continue(false);
+            }
+            let (i, file) = match files.next() {
+                Some(next) => next,
+                None => return glib::Continue(false),
+            };
+            if let Some(operation) = &operation {
+                operation.set_label(&format!("{} ({}/{})", gettext("Opening files"), i + 1, total));
+            }
+            let file_str = file.to_string_lossy().into_owned();
+            match std::fs::File::open(&file) {
+                Ok(_) => main_win.req_new_view(Some(&file_str)),
+                Err(e) => {
+                    let err_msg = format!("{} '{}': {}", &gettext("Couldn't open file"), &file_str, &e.to_string());
+                    main_win.notify(&err_msg, MessageType::Error);
+                }
+            }
+            glib::Continue(true)
+        }));
+    }
+
+    /// Shows that we're busy with operations which take a while, e.g. searching in files
+    pub fn progress(&self) -> &Rc<Progress> {
+        &self.progress
+    }
+
+    pub fn handle_save_button(main_win: &Rc<Self>) {
+        if let Some(edit_view) = main_win.get_current_edit_view() {
+            // Don't overwrite a file another process has locked, ask where to save it instead
+            let read_only = edit_view.borrow().is_read_only();
+            if edit_view.borrow().file_name.is_some() && !read_only {
+                let ev = edit_view.borrow();
+                let core = main_win.core.clone();
+                core.save(&ev.view_id, ev.file_name.as_ref().unwrap());
+            } else {
+                Self::save_as(main_win, &edit_view);
+            }
+        }
+    }
+
+    fn current_save_as(main_win: &Rc<Self>) {
+        if let Some(edit_view) = main_win.get_current_edit_view() {
+            Self::save_as(main_win, &edit_view);
+        }
+    }
+
+    /// Display the FileChooserNative, send the result to the Xi core.
+    /// Don't use FileChooserDialog here, it doesn't work for Flatpaks.
+    /// This may call the GTK main loop.  There must not be any RefCell borrows out while this
+    /// function runs.
+    fn save_as(main_win: &Rc<Self>, edit_view: &Rc<RefCell<EditView>>) {
+        let fcn = FileChooserNative::new(
+            Some(gettext("Save file").as_str()),
+            Some(&main_win.window),
+            FileChooserAction::Save,
+            Some(gettext("Save").as_str()),
+            Some(gettext("Cancel").as_str()),
+        );
+        fcn.set_transient_for(Some(&main_win.window.clone()));
+        fcn.set_current_name("");
+
+        fcn.connect_response(enclose!((edit_view, main_win) move |fcd, res| {
+            debug!(
+                "{}: {:#?}",
+                gettext("FileChooserNative save response"),
+                res
+            );
+
+            if res == ResponseType::Accept {
+                for file in fcd.get_filenames() {
+                    let file_str = &file.to_string_lossy().into_owned();
+                    if let Some(file) = fcd.get_filename() {
+                        match &std::fs::OpenOptions::new().write(true).create(true).open(&file) {
+                            Ok(_) => {
+                                debug!("{} {:?}", gettext("Saving file"), &file);
+                                let view_id = edit_view.borrow().view_id.clone();
+                                let file = file.to_string_lossy();
+                                main_win.core.save(&view_id, &file);
+                                edit_view.borrow_mut().set_file(&file);
+                            }
+                        Err(e) => {
+                            let err_msg = format!("{} '{}': {}", &gettext("Couldn't save file"), &file_str, &e.to_string());
+                            edit_view.borrow().notify(&err_msg, MessageType::Error);
+                        }
+                    }
+                }
+            }
+                }
+        }));
+
+        fcn.run();
+    }
+
+    /// Asks where to export the current document to and saves it there as HTML or PDF,
+    /// highlighted with the colors of the current theme
+    fn export(main_win: &Rc<Self>) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let gschema = main_win.properties.borrow().gschema.clone();
+
+        let fcn = FileChooserNative::new(
+            Some(gettext("Export As").as_str()),
+            Some(&main_win.window),
+            FileChooserAction::Save,
+            Some(gettext("Export").as_str()),
+            Some(gettext("Cancel").as_str()),
+        );
+        fcn.set_do_overwrite_confirmation(true);
+        fcn.set_current_name(&format!("{}.html", edit_view.borrow().display_name()));
+        let html_filter = FileFilter::new();
+        html_filter.set_name(Some(gettext("HTML").as_str()));
+        html_filter.add_pattern("*.html");
+        html_filter.add_pattern("*.htm");
+        fcn.add_filter(&html_filter);
+        let pdf_filter = FileFilter::new();
+        pdf_filter.set_name(Some(gettext("PDF").as_str()));
+        pdf_filter.add_pattern("*.pdf");
+        fcn.add_filter(&pdf_filter);
+        #[cfg(feature = "gtk_v3_22")]
+        {
+            let line_numbers: bool = gschema.get_key("export-line-numbers");
+            fcn.add_choice(
+                "line-numbers",
+                &gettext("Line numbers"),
+                &["hide", "show"],
+                &[
+                    &gettext("Without line numbers"),
+                    &gettext("With line numbers"),
+                ],
+            );
+            fcn.set_choice("line-numbers", if line_numbers { "show" } else { "hide" });
+        }
+
+        fcn.connect_response(enclose!((edit_view) move |fcn, res| {
+            if res != ResponseType::Accept {
+                return;
+            }
+            let mut path = match fcn.get_filename() {
+                Some(path) => path,
+                None => return,
+            };
+            // The extension the user typed decides the format, otherwise the filter they chose
+            let pdf = match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("pdf") => true,
+                Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                    false
+                }
+                _ => {
+                    let pdf = fcn.get_filter().map_or(false, |filter| filter == pdf_filter);
+                    path.set_extension(if pdf { "pdf" } else { "html" });
+                    pdf
+                }
+            };
+            #[cfg(feature = "gtk_v3_22")]
+            {
+                let show = fcn.get_choice("line-numbers").map_or(false, |choice| choice == "show");
+                gschema.set_key("export-line-numbers", show).unwrap();
+            }
+            Self::write_export(&edit_view, path, pdf, gschema.get_key("export-line-numbers"));
+        }));
+
+        fcn.run();
+    }
+
+    /// Saves the notifications from xi-core we don't handle to a file, which can be attached to a
+    /// bug report
+    fn export_unhandled_msgs(main_win: &Rc<Self>) {
+        let fcn = FileChooserNative::new(
+            Some(gettext("Export Unhandled Messages").as_str()),
+            Some(&main_win.window),
+            FileChooserAction::Save,
+            Some(gettext("Export").as_str()),
+            Some(gettext("Cancel").as_str()),
+        );
+        fcn.set_do_overwrite_confirmation(true);
+        fcn.set_current_name("gxi-unhandled-messages.txt");
+
+        fcn.connect_response(enclose!((main_win) move |fcn, res| {
+            if res != ResponseType::Accept {
+                return;
+            }
+            let path = match fcn.get_filename() {
+                Some(path) => path,
+                None => return,
+            };
+            let report = main_win
+                .unhandled_msgs
+                .borrow()
+                .report(whats_new::current_version());
+            if let Err(e) = std::fs::write(&path, report) {
+                let msg = format!("{} '{}': {}", gettext("Couldn't export to"), path.display(), e);
+                main_win.notify(&msg, MessageType::Error);
+            }
+        }));
+
+        fcn.run();
+    }
+
+    /// Writes the document of `edit_view` to `path` as HTML (or PDF if `pdf` is true) once
+    /// xi-editor has sent us all of its lines
+    fn write_export(
+        edit_view: &Rc<RefCell<EditView>>,
+        path: PathBuf,
+        pdf: bool,
+        line_numbers: bool,
+    ) {
+        let edit_view_weak = Rc::downgrade(edit_view);
+        edit_view.borrow().with_text(move |_| {
+            // The EditView is still borrowed while we're called
+            glib::idle_add_local(move || {
+                let edit_view = match edit_view_weak.upgrade() {
+                    Some(edit_view) => edit_view,
+                    None => return glib::source::Continue(false),
+                };
+                let res = if pdf {
+                    match PrintJob::from_edit_view(&edit_view.borrow()) {
+                        Some(job) => printing::write_pdf(&path, &job),
+                        None => Err(gettext("Not all lines of the document have been loaded")),
+                    }
+                } else {
+                    match edit_view.borrow().to_html(line_numbers) {
+                        Some(html) => std::fs::write(&path, html).map_err(|e| e.to_string()),
+                        None => Err(gettext("Not all lines of the document have been loaded")),
+                    }
+                };
+                if let Err(e) = res {
+                    let msg = format!(
+                        "{} '{}': {}",
+                        gettext("Couldn't export to"),
+                        path.display(),
+                        e
+                    );
+                    edit_view.borrow().notify(&msg, MessageType::Error);
+                }
+                glib::source::Continue(false)
+            });
+        });
+    }
+
+    /// Prints the current document, or shows a preview of the printout if `preview` is true
+    fn print(main_win: &Rc<Self>, preview: bool) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let edit_view_weak = Rc::downgrade(&edit_view);
+        edit_view.borrow().with_text(enclose!((main_win) move |_| {
+            // The EditView is still borrowed while we're called
+            glib::idle_add_local(enclose!((main_win, edit_view_weak) move || {
+                let edit_view = match edit_view_weak.upgrade() {
+                    Some(edit_view) => edit_view,
+                    None => return glib::source::Continue(false),
+                };
+                let job = PrintJob::from_edit_view(&edit_view.borrow());
+                let res = match job {
+                    Some(job) => printing::print(&main_win.window, job, preview),
+                    None => Err(gettext("Not all lines of the document have been loaded")),
+                };
+                if let Err(e) = res {
+                    let msg = format!("{}: {}", gettext("Couldn't print"), e);
+                    edit_view.borrow().notify(&msg, MessageType::Error);
+                }
+                glib::source::Continue(false)
+            }));
+        }));
+    }
+
+    /// Renames the file of the current EditView, e.g. to change the case of its name. On
+    /// case-insensitive filesystems saving as `Foo.txt` would simply overwrite `foo.txt`, so we
+    /// rename the file ourselves and tell xi-editor to save the document under the new name.
+    fn rename_with_case_change(main_win: &Rc<Self>) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let old_path = match edit_view.borrow().file_name.clone() {
+            Some(file_name) => PathBuf::from(file_name),
+            // Unsaved documents don't have a name to change yet
+            None => return Self::save_as(main_win, &edit_view),
+        };
+        let old_name = old_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let new_name = match Self::ask_new_name(main_win, &old_name) {
+            Some(new_name) => new_name,
+            None => return,
+        };
+        let new_path = old_path.with_file_name(&new_name);
+        debug!(
+            "{} {:?} {} {:?}",
+            gettext("Renaming"),
+            old_path,
+            gettext("to"),
+            new_path
+        );
+
+        if let Err(e) = rename_two_step(&old_path, &new_path) {
+            let msg = format!(
+                "{} '{}' {} '{}': {}",
+                gettext("Couldn't rename"),
+                old_name,
+                gettext("to"),
+                new_name,
+                e
+            );
+            edit_view.borrow().notify(&msg, MessageType::Error);
+            return;
+        }
+
+        let old_file = old_path.to_string_lossy().into_owned();
+        let new_file = new_path.to_string_lossy().into_owned();
+        let view_id = edit_view.borrow().view_id.clone();
+        main_win.core.save(&view_id, &new_file);
+        edit_view.borrow_mut().set_file(&new_file);
+
+        // Move the lock to the new name, the old lock is released first since it might be the
+        // same file as the new one on case-insensitive filesystems
+        if main_win.doc_locks.borrow_mut().remove(&old_file).is_some() {
+            match DocLock::acquire(&new_path) {
+                Ok(lock) => {
+                    main_win.doc_locks.borrow_mut().insert(new_file, lock);
+                }
+                Err(e) => warn!("{} {:?}: {:?}", gettext("Failed to lock"), new_path, e),
+            }
+        }
+    }
+
+    /// Shows the terminal and changes to the directory of the current file
+    #[cfg(feature = "terminal")]
+    fn open_terminal(&self) {
+        let file_name = self
+            .get_current_edit_view()
+            .and_then(|ev| ev.borrow().file_name.clone());
+        if let Some(dir) = file_name.as_ref().and_then(|f| Path::new(f).parent()) {
+            Terminal::open_in(&self.terminal, dir);
+        }
+        self.panels.show(terminal::PANEL_NAME);
+    }
+
+    /// Points the EditViews of files which have been renamed from `old` to `new` (or moved along
+    /// with their directory) at their new names
+    fn file_renamed(&self, old: &Path, new: &Path) {
+        for edit_view in self.views.borrow().values() {
+            let old_file = match edit_view.borrow().file_name.clone() {
+                Some(file_name) => file_name,
+                None => continue,
+            };
+            let new_path = match Path::new(&old_file).strip_prefix(old) {
+                Ok(rest) if rest.as_os_str().is_empty() => new.to_path_buf(),
+                Ok(rest) => new.join(rest),
+                Err(_) => continue,
+            };
+            let new_file = new_path.to_string_lossy().into_owned();
+            edit_view.borrow_mut().set_file(&new_file);
+
+            if self.doc_locks.borrow_mut().remove(&old_file).is_some() {
+                match DocLock::acquire(&new_path) {
+                    Ok(lock) => {
+                        self.doc_locks.borrow_mut().insert(new_file, lock);
+                    }
+                    Err(e) => warn!("{} {:?}: {:?}", gettext("Failed to lock"), new_path, e),
+                }
+            }
+        }
+    }
+
+    /// Asks the user for the new name of a file. Returns None if they cancel or don't change it.
+    fn ask_new_name(main_win: &Rc<Self>, old_name: &str) -> Option<String> {
+        let dialog = MessageDialog::new(
+            Some(&main_win.window),
+            DialogFlags::all(),
+            MessageType::Question,
+            ButtonsType::None,
+            gettext("Rename file").as_str(),
+        );
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog.add_button(&gettext("Rename"), ResponseType::Accept);
+        dialog.set_default_response(ResponseType::Accept);
+
+        let entry = Entry::new();
+        entry.set_text(old_name);
+        entry.set_activates_default(true);
+        if let Some(message_area) = dialog
+            .get_message_area()
+            .and_then(|w| w.downcast::<gtk::Box>().ok())
+        {
+            message_area.pack_start(&entry, false, false, 0);
+        }
+        entry.show();
+
+        let res = dialog.run();
+        let new_name = entry.get_text().map(|s| s.trim().to_string());
+        dialog.destroy();
+
+        if res != ResponseType::Accept {
+            return None;
+        }
+        new_name
+            .filter(|n| !n.is_empty() && n != old_name && !n.contains(std::path::MAIN_SEPARATOR))
+    }
+
+    /// Asks the user for a name for the window. The window keeps the name it was started with
+    /// until gxi is restarted, but its session is saved under the new one, so `gxi --window NAME`
+    /// brings it back.
+    fn name_window(main_win: &Rc<Self>) {
+        let dialog = MessageDialog::new(
+            Some(&main_win.window),
+            DialogFlags::all(),
+            MessageType::Question,
+            ButtonsType::None,
+            gettext("Name Window").as_str(),
+        );
+        dialog.set_property_secondary_text(Some(
+            gettext("The new name is used once gxi has been restarted, then files can be opened in this window with gxi --window NAME").as_str(),
+        ));
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog.add_button(&gettext("Rename"), ResponseType::Accept);
+        dialog.set_default_response(ResponseType::Accept);
+
+        let entry = Entry::new();
+        {
+            let session = main_win.session.borrow();
+            let name = session
+                .new_window_name
+                .as_ref()
+                .unwrap_or(&session.window_name);
+            if let Some(name) = name {
+                entry.set_text(name);
+            }
+        }
+        entry.set_activates_default(true);
+        if let Some(message_area) = dialog
+            .get_message_area()
+            .and_then(|w| w.downcast::<gtk::Box>().ok())
+        {
+            message_area.pack_start(&entry, false, false, 0);
+        }
+        entry.show();
+
+        let res = dialog.run();
+        let name = entry.get_text().map(|s| s.trim().to_string());
+        dialog.destroy();
+
+        if res == ResponseType::Accept {
+            debug!("{}: {:?}", gettext("Naming window"), name);
+            let name = name.filter(|n| !n.is_empty());
+            let mut session = main_win.session.borrow_mut();
+            session.new_window_name = if name == session.window_name {
+                None
+            } else {
+                Some(name)
+            };
+        }
+    }
+
+    /// Asks the user how many rows and columns a new Markdown table should have
+    fn ask_table_size(main_win: &Rc<Self>) -> Option<(usize, usize)> {
+        let dialog = MessageDialog::new(
+            Some(&main_win.window),
+            DialogFlags::all(),
+            MessageType::Question,
+            ButtonsType::None,
+            gettext("Insert Table").as_str(),
+        );
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog.add_button(&gettext("Insert"), ResponseType::Accept);
+        dialog.set_default_response(ResponseType::Accept);
+
+        let grid = Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        let rows_spin_button = SpinButton::new_with_range(1.0, 100.0, 1.0);
+        rows_spin_button.set_value(2.0);
+        let columns_spin_button = SpinButton::new_with_range(1.0, 20.0, 1.0);
+        columns_spin_button.set_value(3.0);
+        for (i, (label, spin_button)) in [
+            (gettext("Rows"), &rows_spin_button),
+            (gettext("Columns"), &columns_spin_button),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let label = Label::new(Some(label.as_str()));
+            label.set_halign(Align::Start);
+            spin_button.set_activates_default(true);
+            grid.attach(&label, 0, i as i32, 1, 1);
+            grid.attach(*spin_button, 1, i as i32, 1, 1);
+        }
+        if let Some(message_area) = dialog
+            .get_message_area()
+            .and_then(|w| w.downcast::<gtk::Box>().ok())
+        {
+            message_area.pack_start(&grid, false, false, 0);
+        }
+        grid.show_all();
+
+        let res = dialog.run();
+        let size = (
+            rows_spin_button.get_value_as_int() as usize,
+            columns_spin_button.get_value_as_int() as usize,
+        );
+        dialog.destroy();
+
+        if res == ResponseType::Accept {
+            Some(size)
+        } else {
+            None
+        }
+    }
+
+    /// Inserts the current date and time, formatted as set in the preferences
+    fn insert_date_time(&self) {
+        let edit_view = match self.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let format: String = self
+            .state
+            .borrow()
+            .settings
+            .gschema
+            .get_key("date-time-format");
+        match snippets::date_time(&format) {
+            Some(date_time) => edit_view.borrow().insert_text(&date_time),
+            None => {
+                let msg = format!("{} '{}'", gettext("Invalid date/time format"), format);
+                self.notify(&msg, MessageType::Error);
+            }
+        }
+    }
+
+    /// Shows the checksums of the selection or, if nothing is selected, of the current file. If
+    /// the file has unsaved changes, the text as shown is checksummed instead.
+    fn show_checksums(main_win: &Rc<Self>) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let window = main_win.window.clone();
+        edit_view.borrow().with_selected_text(enclose!((edit_view) move |selection| {
+            if !selection.is_empty() {
+                checksum::show_dialog(&window, &gettext("Selection"), selection.as_bytes());
+                return;
+            }
+
+            let ev = edit_view.borrow();
+            match (&ev.file_name, ev.pristine) {
+                (Some(file_name), true) => match std::fs::read(file_name) {
+                    Ok(data) => checksum::show_dialog(&window, file_name, &data),
+                    Err(e) => {
+                        let msg = format!("{} '{}': {}", gettext("Couldn't read"), file_name, e);
+                        ev.notify(&msg, MessageType::Error);
+                    }
+                },
+                (file_name, _) => {
+                    let what = file_name.clone().unwrap_or_else(|| gettext("Untitled"));
+                    ev.with_text(enclose!((window) move |text| {
+                        checksum::show_dialog(&window, &what, text.as_bytes());
+                    }));
+                }
+            }
+        }));
+    }
+
+    /// Copies the link to the selected lines of the current file on GitHub or GitLab, at the
+    /// current commit
+    fn copy_permalink(&self) {
+        let edit_view = match self.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let ev = edit_view.borrow();
+        let file_name = match &ev.file_name {
+            Some(file_name) => file_name,
+            None => return,
+        };
+        let lines = ev.selected_line_range().unwrap_or((0, 0));
+        let edit_view_weak = Rc::downgrade(&edit_view);
+        permalink::for_file(Path::new(file_name), lines, move |link| match link {
+            Ok(link) => Clipboard::get(&SELECTION_CLIPBOARD).set_text(&link),
+            Err(e) => {
+                if let Some(edit_view) = edit_view_weak.upgrade() {
+                    let msg = format!("{}: {}", gettext("Couldn't build a permalink"), e);
+                    edit_view.borrow().notify(&msg, MessageType::Error);
+                }
+            }
+        });
+    }
+
+    /// Asks the user for a shell command and replaces the selection of the current EditView (or
+    /// all of its text if nothing is selected) with the output of the command fed with it
+    fn filter_selection(main_win: &Rc<Self>) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        if edit_view.borrow().is_read_only() {
+            return;
+        }
+        // xi-editor sends us the text of all selections at once, so we couldn't tell which part
+        // of the output replaces which of them
+        if edit_view.borrow().cursor_count() > 1 {
+            main_win.notify(
+                &gettext("Only a single selection can be filtered through a command"),
+                MessageType::Info,
+            );
+            return;
+        }
+        let command = match Self::ask_filter_command(main_win) {
+            Some(command) => command,
+            None => return,
+        };
+        if !main_win.is_private() {
+            filter::remember(&mut main_win.session.borrow_mut().filter_commands, &command);
+        }
+
+        let core = main_win.core.clone();
+        let edit_view_weak = Rc::downgrade(&edit_view);
+        // We only know what's selected if we have all lines, and the EditView is still borrowed
+        // while we're called
+        edit_view.borrow().with_text(move |_| {
+            glib::idle_add_local(enclose!((core, edit_view_weak, command) move || {
+                if let Some(edit_view) = edit_view_weak.upgrade() {
+                    if let Some(snapshot) = edit_view.borrow().selection_snapshot() {
+                        Self::run_filter(core.clone(), edit_view_weak.clone(), command.clone(), snapshot);
+                    }
+                }
+                glib::Continue(false)
+            }));
+        });
+    }
+
+    /// Runs `command` in the background on the selection of `snapshot`, or all of its text if
+    /// nothing is selected, and replaces it with the output. Replacing the selection is a single
+    /// edit, so it can be undone at once. If the text or the selection of the EditView has changed
+    /// since `snapshot` was taken, the output is dropped, since it'd replace the wrong text.
+    fn run_filter(
+        core: Core,
+        edit_view_weak: Weak<RefCell<EditView>>,
+        command: String,
+        snapshot: SelectionSnapshot,
+    ) {
+        let selection = snapshot.selected_text();
+        let whole_document = selection.is_empty();
+        let input = if whole_document {
+            snapshot.text()
+        } else {
+            selection
+        };
+        let (output_tx, output_rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        let main_context = MainContext::default();
+
+        output_rx.attach(Some(&main_context), enclose!((command) move |output| {
+            match output {
+                Ok(output) => {
+                    Self::apply_filter_output(&core, &edit_view_weak, &snapshot, output, whole_document);
+                }
+                Err(e) => {
+                    let msg = format!("{} '{}': {}", gettext("Couldn't filter the text through"), command, e);
+                    errors::show_error(ErrorMsg { msg, fatal: false });
+                }
+            }
+            glib::Continue(false)
+        }));
+
+        thread::spawn(move || {
+            let _ = output_tx.send(filter::run(&command, input));
+        });
+    }
+
+    /// Replaces the filtered text with `output` once we have all lines of the EditView again,
+    /// unless its text or selection differs from `snapshot` by then
+    fn apply_filter_output(
+        core: &Core,
+        edit_view_weak: &Weak<RefCell<EditView>>,
+        snapshot: &SelectionSnapshot,
+        output: String,
+        whole_document: bool,
+    ) {
+        let edit_view = match edit_view_weak.upgrade() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let core = core.clone();
+        let snapshot = snapshot.clone();
+        let edit_view_weak = edit_view_weak.clone();
+        // The EditView is still borrowed while we're called
+        edit_view.borrow().with_text(move |_| {
+            glib::idle_add_local(enclose!((core, edit_view_weak) move || {
+                let edit_view = match edit_view_weak.upgrade() {
+                    Some(edit_view) => edit_view,
+                    None => return glib::Continue(false),
+                };
+                let ev = edit_view.borrow();
+                if ev.selection_snapshot().as_ref() != Some(&snapshot) {
+                    ev.notify(
+                        &gettext("The document or the selection has changed while the command was running, so its output hasn't been inserted"),
+                        MessageType::Warning,
+                    );
+                } else if !ev.is_read_only() {
+                    if whole_document {
+                        core.select_all(&ev.view_id);
+                    }
+                    core.insert(&ev.view_id, &output);
+                }
+                glib::Continue(false)
+            }));
+        });
+    }
+
+    /// Asks the user for the command to filter the selection through, offering the recently used
+    /// ones
+    fn ask_filter_command(main_win: &Rc<Self>) -> Option<String> {
+        let dialog = MessageDialog::new(
+            Some(&main_win.window),
+            DialogFlags::all(),
+            MessageType::Question,
+            ButtonsType::None,
+            gettext("Filter Selection Through Command").as_str(),
+        );
+        dialog.set_property_secondary_text(Some(
+            gettext("The selection, or the whole document if nothing is selected, is replaced with the output of the command.").as_str(),
+        ));
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog.add_button(&gettext("Filter"), ResponseType::Accept);
+        dialog.set_default_response(ResponseType::Accept);
+
+        let combo_box = ComboBoxText::new_with_entry();
+        for command in &main_win.session.borrow().filter_commands {
+            combo_box.append_text(command);
+        }
+        if let Some(entry) = combo_box
+            .get_child()
+            .and_then(|w| w.downcast::<Entry>().ok())
+        {
+            entry.set_activates_default(true);
+            entry.set_placeholder_text(Some("sort"));
+        }
+        if let Some(message_area) = dialog
+            .get_message_area()
+            .and_then(|w| w.downcast::<gtk::Box>().ok())
+        {
+            message_area.pack_start(&combo_box, false, false, 0);
+        }
+        combo_box.show_all();
+
+        let res = dialog.run();
+        let command = combo_box.get_active_text().map(|s| s.trim().to_string());
+        dialog.destroy();
+
+        if res != ResponseType::Accept {
+            return None;
+        }
+        command.filter(|c| !c.is_empty())
+    }
+
+    /// Reads the selected text of `edit_view` aloud with the rate and voice the user has set up
+    fn speak_selection(&self, edit_view: &EditView) {
+        let (rate, voice): (i32, String) = {
+            let gschema = &self.state.borrow().settings.gschema;
+            (
+                gschema.get_key("speech-rate"),
+                gschema.get_key("speech-voice"),
+            )
+        };
+        edit_view.with_selection(move |text| {
+            if let Err(e) = speech::speak(&text, rate, &voice) {
+                let msg = format!(
+                    "{}: {}",
+                    gettext("Couldn't read the selection aloud, please make sure speech-dispatcher is installed"),
+                    e
+                );
+                errors::show_error(ErrorMsg { msg, fatal: false });
+            }
+        });
+    }
+
+    /// Asks the user for a file and compares the current document with it in the external diff
+    /// tool the user has set up.
+    fn compare_with(main_win: &Rc<Self>) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+
+        let fcn = FileChooserNative::new(
+            Some(gettext("Compare With…").as_str()),
+            Some(&main_win.window),
+            FileChooserAction::Open,
+            Some(gettext("Compare").as_str()),
+            Some(gettext("Cancel").as_str()),
+        );
+        fcn.set_transient_for(Some(&main_win.window.clone()));
+
+        fcn.connect_response(enclose!((edit_view, main_win) move |fcd, res| {
+            if res == ResponseType::Accept {
+                if let Some(file) = fcd.get_filename() {
+                    main_win.open_external_diff(&edit_view.borrow(), &file);
+                }
+            }
+        }));
+
+        fcn.run();
+    }
+
+    /// Asks the user which other open document (or the saved file of the current document) to
+    /// compare the current document with, then shows the differences side by side
+    fn compare_side_by_side(main_win: &Rc<Self>) {
+        let edit_view = match main_win.get_current_edit_view() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+
+        let dialog = MessageDialog::new(
+            Some(&main_win.window),
+            DialogFlags::all(),
+            MessageType::Question,
+            ButtonsType::None,
+            gettext("Compare Side by Side").as_str(),
+        );
+        dialog.set_property_secondary_text(Some(
+            gettext("Choose what to compare the current document with.").as_str(),
+        ));
+        dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+        dialog.add_button(&gettext("Compare"), ResponseType::Accept);
+        dialog.set_default_response(ResponseType::Accept);
+
+        // The ids are the view ids of the other documents, in the order of their tabs
+        let combo_box = ComboBoxText::new();
+        let file_name = edit_view.borrow().file_name.clone();
+        if file_name.is_some() {
+            combo_box.append(Some(SAVED_FILE_ID), &gettext("Saved File"));
+        }
+        for i in 0..main_win.notebook.get_n_pages() {
+            let other = main_win
+                .notebook
+                .get_nth_page(Some(i))
+                .and_then(|w| main_win.w_to_ev.borrow().get(&w).cloned());
+            if let Some(other) = other.filter(|other| !Rc::ptr_eq(other, &edit_view)) {
+                let other = other.borrow();
+                combo_box.append(Some(other.view_id.as_str()), &other.display_name());
+            }
+        }
+        combo_box.set_active(Some(0));
+        if let Some(message_area) = dialog
+            .get_message_area()
+            .and_then(|w| w.downcast::<gtk::Box>().ok())
+        {
+            message_area.pack_start(&combo_box, false, false, 0);
+        }
+        combo_box.show_all();
+
+        let res = dialog.run();
+        let choice = combo_box.get_active_id();
+        dialog.destroy();
+        let choice = match choice {
+            Some(choice) if res == ResponseType::Accept => choice.to_string(),
+            _ => return,
+        };
+
+        let window = main_win.window.clone();
+        let ev = edit_view.borrow();
+        let title = ev.display_name();
+        if choice == SAVED_FILE_ID {
+            let file_name = file_name.unwrap();
+            let saved = match std::fs::read_to_string(&file_name) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    let msg = format!("{} '{}': {}", gettext("Couldn't read file"), file_name, e);
+                    ev.notify(&msg, MessageType::Error);
+                    return;
+                }
+            };
+            let saved_title = format!("{} ({})", title, gettext("saved"));
+            ev.with_text(move |text| DiffView::show(&window, &saved_title, &saved, &title, text));
+        } else if let Some(other) = main_win.views.borrow().get(&choice).cloned() {
+            // xi-editor may have to send us the text of either document first
+            ev.with_text(move |text| {
+                let (text, other_title) = (text.to_string(), other.borrow().display_name());
+                other.borrow().with_text(move |other_text| {
+                    DiffView::show(&window, &title, &text, &other_title, other_text);
+                });
+            });
+        }
+    }
+
+    /// Opens the document of `edit_view` and the file `other` in the external diff tool. Unsaved
+    /// changes are written to a temporary file, so the diff tool sees what the user sees. Since
+    /// xi-editor might have to send us the text first, the diff tool may be started later on.
+    fn open_external_diff(&self, edit_view: &EditView, other: &Path) {
+        let tool: String = self
+            .state
+            .borrow()
+            .settings
+            .gschema
+            .get_key("external-diff-tool");
+        if tool.trim().is_empty() {
+            let msg =
+                gettext("No external diff tool has been set, please set one in the preferences");
+            self.notify(&msg, MessageType::Error);
+            return;
+        }
+
+        let show_error = enclose!((tool) move |e: std::io::Error| {
+            let msg = format!("{} '{}': {}", gettext("Couldn't run diff tool"), tool, e);
+            errors::show_error(ErrorMsg { msg, fatal: false });
+        });
+
+        match (&edit_view.file_name, edit_view.pristine) {
+            (Some(file_name), true) => {
+                if let Err(e) = external_diff::open(&tool, Path::new(file_name), other, Vec::new())
+                {
+                    show_error(e);
+                }
+            }
+            (file_name, _) => {
+                let name = file_name
+                    .as_ref()
+                    .and_then(|f| Path::new(f).file_name())
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| gettext("Untitled"));
+                let other = other.to_path_buf();
+                edit_view.with_text(move |text| {
+                    let res = external_diff::temp_file(&name, text).and_then(|temp| {
+                        external_diff::open(&tool, &temp, &other, vec![temp.clone()])
+                    });
+                    if let Err(e) = res {
+                        show_error(e);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Does the work we do in the background by ourselves, unless it's skipped in reduced mode.
+    /// It's called while the window is active and the user isn't idle, see `update_activity`.
+    fn run_background_jobs(main_win: &Rc<Self>) {
+        let reduced_mode = main_win.is_reduced_mode();
+        Self::check_disk_changes(main_win);
+        if let Some(folder) = main_win.file_tree.folder() {
+            let remote = reduced_mode && background::is_remote(&folder.to_string_lossy());
+            if background::should_run(Job::RefreshFileTree { remote }, reduced_mode) {
+                main_win.file_tree.refresh();
+            }
+        }
+    }
+
+    /// Warns about documents with unsaved changes whose file has been changed on disk by another
+    /// program, since saving them would overwrite these changes. The user can compare both
+    /// versions in the external diff tool.
+    fn check_disk_changes(main_win: &Rc<Self>) {
+        for edit_view in main_win.views.borrow().values() {
+            let ev = edit_view.borrow();
+            if !ev.has_disk_conflict() {
+                continue;
+            }
+            let file_name = ev.file_name.clone().unwrap();
+            let msg = format!(
+                "{} '{}'",
+                gettext("Another program has changed the file"),
+                file_name
+            );
+            let edit_view_weak = Rc::downgrade(edit_view);
+            ev.notify_with_action(
+                &msg,
+                MessageType::Warning,
+                &gettext("Open in External Diff"),
+                enclose!((main_win) move || {
+                    if let Some(edit_view) = edit_view_weak.upgrade() {
+                        main_win.open_external_diff(&edit_view.borrow(), Path::new(&file_name));
+                    }
+                }),
+            );
+        }
+    }
+
+    fn prefs(main_win: Rc<Self>) {
+        let gschema = { &main_win.properties.borrow().gschema };
+        PrefsWin::new(&main_win.window, &main_win.state, &main_win.core, &gschema);
+    }
+
+    fn about(main_win: Rc<Self>) {
+        AboutWin::new(&main_win.window);
+    }
+
+    fn find(main_win: &Rc<Self>) {
+        if let Some(edit_view) = main_win.get_current_edit_view() {
+            edit_view.borrow().start_search();
+        }
+    }
+
+    fn replace(main_win: &Rc<Self>) {
+        if let Some(edit_view) = main_win.get_current_edit_view() {
+            edit_view.borrow().start_replace();
+        }
+    }
+
+    fn find_in_files(main_win: &Rc<Self>) {
+        let file_name = main_win
+            .get_current_edit_view()
+            .and_then(|ev| ev.borrow().file_name.clone());
+        let file_name = file_name.as_ref().map(String::as_str);
+
+        let existing = main_win.find_in_files.borrow().clone();
+        if let Some(find_in_files) = existing {
+            find_in_files.present(file_name);
+        } else {
+            let find_in_files = FindInFiles::new(main_win, &main_win.window, file_name);
+            main_win.find_in_files.replace(Some(find_in_files));
+        }
+    }
+
+    /// Shows the "Go to File…" popover with the files of the folder shown in the file tree, or of
+    /// the project of the current file, and the files which are open
+    fn show_go_to_file(&self) {
+        let root = self.file_tree.folder().or_else(|| {
+            let edit_view = self.get_current_edit_view()?;
+            let file_name = edit_view.borrow().file_name.clone()?;
+            Some(project::project_root(Path::new(&file_name)))
+        });
+        let open_files = self
+            .views
+            .borrow()
+            .values()
+            .filter_map(|ev| ev.borrow().file_name.clone())
+            .collect();
+        let recent = self.session.borrow().recent_files.clone();
+        GoToFile::show(&self.go_to_file, root, open_files, recent);
+    }
+
+    /// Shows the "Changed Files" popover for the project of the folder shown in the file tree, or
+    /// of the current file
+    fn show_changed_files(&self) {
+        let dir = self.file_tree.folder().or_else(|| {
+            let edit_view = self.get_current_edit_view()?;
+            let file_name = edit_view.borrow().file_name.clone()?;
+            Some(project::project_root(Path::new(&file_name)))
+        });
+        match dir {
+            Some(dir) => ChangedFiles::show(&self.changed_files, &dir),
+            None => self.notify(
+                &gettext("Open a file or folder of a git repository to see its changed files"),
+                MessageType::Info,
+            ),
+        }
+    }
+
+    /// Goes to the definition of `word` from the tags file of the project of `file_name`, letting
+    /// the user pick one if there are several. The tags file is read in the background. Returns
+    /// whether there's a tags file.
+    fn go_to_definition(main_win: &Rc<Self>, word: &str, file_name: Option<&str>) -> bool {
+        let file_name = match file_name {
+            Some(file_name) => file_name,
+            None => return false,
+        };
+        let root = project::project_root(Path::new(file_name));
+        let word = word.to_string();
+        let found = enclose!((main_win, word) move |lookup: Lookup| {
+            debug!("{} '{}': {:?}", gettext("Definitions of"), word, lookup);
+            for file in &lookup.outdated {
+                warn!(
+                    "{} '{}' {} '{}'",
+                    gettext("Couldn't find the definition of"),
+                    word,
+                    gettext("in"),
+                    file
+                );
+            }
+            match lookup.definitions.len() {
+                0 if !lookup.outdated.is_empty() => main_win.notify(
+                    &format!(
+                        "{} '{}', {}",
+                        gettext("Couldn't find the definition of"),
+                        word,
+                        gettext("the tags file is out of date")
+                    ),
+                    MessageType::Warning,
+                ),
+                0 => main_win.notify(
+                    &format!("{} '{}'", gettext("No definition found for"), word),
+                    MessageType::Info,
+                ),
+                1 => {
+                    let ((file, line), _) = &lookup.definitions[0];
+                    main_win.open_file_at(file, *line);
+                }
+                _ => main_win
+                    .definition_picker
+                    .show(&word, lookup.definitions, &root),
+            }
+        });
+        main_win
+            .tag_files
+            .definitions(&word, Path::new(file_name), found)
+    }
+
+    /// Ctrl+R opens the replace bar, unless the Vim keybindings are on: it's redo in Vim's normal
+    /// mode, and accelerators are handled before the EditView gets the key.
+    fn update_replace_accel(&self) {
+        let vim_mode = self.state.borrow().settings.vim_mode;
+        if let Some(app) = self.window.get_application() {
+            let accels: &[&str] = if vim_mode { &[] } else { &["<Primary>r"] };
+            app.set_accels_for_action("app.replace", accels);
+        }
+    }
+
+    /// Checks all open files for problems again, e.g. after the linters have been changed
+    fn check_files(&self) {
+        for edit_view in self.views.borrow().values() {
+            edit_view.borrow().check_file();
+        }
+    }
+
+    /// Saves which panels are open (and their size), so we can restore the layout on the next
+    /// startup. This has to happen before closing the EditViews, since the state of the search
+    /// bar is taken from the current one.
+    fn save_session(&self) {
+        if self.is_private() {
+            debug!("{}", gettext("Not saving session in private mode"));
+            return;
+        }
+        let mut session = self.session.borrow_mut();
+
+        if let Some(edit_view) = self.get_current_edit_view() {
+            let layout = edit_view.borrow().search_layout();
+            session.set_panel("search", PanelState::shown(layout.search));
+            session.set_panel("replace", PanelState::shown(layout.replace));
+            session.set_panel("search-options", PanelState::shown(layout.options));
+        }
+
+        let find_in_files_state = match &*self.find_in_files.borrow() {
+            Some(find_in_files) => find_in_files.panel_state(),
+            None => PanelState {
+                open: false,
+                ..session.panel("find-in-files")
+            },
+        };
+        session.set_panel("find-in-files", find_in_files_state);
+        self.panels.save_state(&mut session);
+        session.file_tree_folder = self
+            .file_tree
+            .folder()
+            .map(|folder| folder.to_string_lossy().into_owned());
+
+        session.save();
+    }
+
+    /// Whether this window is in private mode, in which nothing (e.g. the session) may be
+    /// persisted to disk.
+    pub fn is_private(&self) -> bool {
+        self.state.borrow().private
+    }
+
+    fn set_private(&self, private: bool) {
+        debug!("{}: {}", gettext("Setting private mode"), private);
+        self.state.borrow_mut().private = private;
+        self.update_subtitle(self.get_current_edit_view().as_ref());
+    }
+
+    /// Shows the profile and name of the window, the file the current EditView is a temporary
+    /// copy of (e.g. when editing via sudoedit) and whether we're in private mode in the subtitle
+    /// of the header bar.
+    fn update_subtitle(&self, edit_view: Option<&Rc<RefCell<EditView>>>) {
+        let mut subtitle = Vec::new();
+        if let Some(profile) = profile::current() {
+            subtitle.push(format!("{} {}", gettext("Profile"), profile));
+        }
+        if let Some(name) = self.session.borrow().window_name.as_ref() {
+            subtitle.push(name.clone());
+        }
+        if let Some(origin) = edit_view.and_then(|ev| ev.borrow().origin().map(str::to_string)) {
+            subtitle.push(format!(
+                "{} {} {}",
+                gettext("Editing"),
+                origin,
+                gettext("via sudoedit")
+            ));
+        }
+        if self.is_private() {
+            subtitle.push(gettext("Private Mode"));
+        }
+
+        let header_bar: HeaderBar = self.builder.get_object("header_bar").unwrap();
+        if subtitle.is_empty() {
+            header_bar.set_subtitle(None);
+        } else {
+            header_bar.set_subtitle(Some(subtitle.join(" — ").as_str()));
+        }
+    }
+
+    /// Scales the edit font of all EditViews of this window by `zoom`. This only changes how big
+    /// the text is drawn, the font size the user has set stays the same.
+    fn set_zoom(&self, zoom: f64) {
+        let zoom = zoom.max(MIN_ZOOM).min(MAX_ZOOM);
+        debug!("{}: {}", gettext("Setting zoom to"), zoom);
+        self.state.borrow_mut().zoom = zoom;
+        for edit_view in self.views.borrow().values() {
+            edit_view.borrow_mut().update_zoom();
         }
     }
 
-    /// Display the FileChooserNative for opening, send the result to the Xi core.
-    /// Don't use FileChooserDialog here, it doesn't work for Flatpaks.
-    /// This may call the GTK main loop.  There must not be any RefCell borrows out while this
-    /// function runs.
-    pub fn handle_open_button(main_win: &Rc<Self>) {
-        let fcn = FileChooserNative::new(
-            Some(gettext("Open a file to edit").as_str()),
-            Some(&main_win.window),
-            FileChooserAction::Open,
-            Some(gettext("Open").as_str()),
-            Some(gettext("Cancel").as_str()),
-        );
-        fcn.set_transient_for(Some(&main_win.window.clone()));
-        fcn.set_select_multiple(true);
+    /// Pauses syntax highlighting while on battery if the user wants us to, and resumes it once
+    /// the computer is plugged in again.
+    fn update_highlighting(&self) {
+        let gschema = &self.state.borrow().settings.gschema;
+        let highlight_on_battery: bool = gschema.get_key("highlight-on-battery");
+        let paused = !highlight_on_battery && self.power.borrow().on_battery();
+        if paused == self.highlighting_paused.get() {
+            return;
+        }
 
-        fcn.connect_response(enclose!((main_win) move |fcd, res| {
-            debug!(
-                "{}: {:#?}",
-                gettext("FileChooserNative open response"),
-                res
+        // xi-editor wants a view to start/stop plugins for, but they're stopped for all views
+        let view_id = match self.views.borrow().keys().next() {
+            Some(view_id) => view_id.clone(),
+            None => return,
+        };
+        debug!("{}: {}", gettext("Pausing syntax highlighting"), paused);
+        self.highlighting_paused.set(paused);
+        if paused {
+            self.core.stop_plugin(&view_id, SYNTECT_PLUGIN);
+        } else {
+            self.core.start_plugin(&view_id, SYNTECT_PLUGIN);
+        }
+    }
+
+    /// Saves power while the window is unfocused or minimized or the user is idle: Messages from
+    /// xi-editor are polled for less often, the network isn't checked and background jobs don't
+    /// run. Once the window is active again, they're done right away.
+    fn update_activity(main_win: &Rc<Self>) {
+        let minimized = main_win.window.get_window().map_or(false, |w| {
+            w.get_state().contains(gdk::WindowState::ICONIFIED)
+        });
+        let active = main_win.window.is_active() && !minimized && !main_win.idle.is_idle();
+        let was_active = main_win.active.swap(active, Ordering::Relaxed);
+        // The polling is set up the first time we're called, even if nothing has changed
+        if was_active == active && main_win.jobs_poll.borrow().is_some() == active {
+            return;
+        }
+        debug!("{}: {}", gettext("Window active"), active);
+
+        if let Some(id) = main_win.jobs_poll.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+        if active {
+            main_win.update_highlighting();
+            main_win.update_reduced_mode();
+            Self::run_background_jobs(main_win);
+            let id = glib::timeout_add_seconds_local(
+                JOBS_POLL_INTERVAL,
+                enclose!((main_win) move || {
+                    main_win.update_highlighting();
+                    main_win.update_reduced_mode();
+                    main_win.update_idle_timeout();
+                    Self::run_background_jobs(&main_win);
+                    glib::Continue(true)
+                }),
             );
+            main_win.jobs_poll.replace(Some(id));
+        }
+    }
 
-            if res == ResponseType::Accept {
-                for file in fcd.get_filenames() {
-                    let file_str = &file.to_string_lossy().into_owned();
-                    match &std::fs::File::open(file_str) {
-                        Ok(_) => main_win.req_new_view(Some(&file_str)),
-                        Err(e) => {
-                            let err_msg = format!("{} '{}': {}", &gettext("Couldn't open file"), &file_str, &e.to_string());
-                            ErrorDialog::new(ErrorMsg{msg: err_msg, fatal: false});
-                        }
-                    }
-                }
-            }
-        }));
+    /// Loads the dictionary of the spell checking language the user has chosen, if any
+    fn update_spell_check_language(&self) {
+        let state = self.state.borrow();
+        let language: String = state.settings.gschema.get_key("spell-check-language");
+        state.spell_checker.set_language(&language);
+    }
 
-        fcn.run();
+    /// Sets after how long without input the user counts as idle, which the user can set for when
+    /// the computer is plugged in and on battery
+    fn update_idle_timeout(&self) {
+        let key = if self.power.borrow().on_battery() {
+            "idle-pause-minutes-on-battery"
+        } else {
+            "idle-pause-minutes"
+        };
+        // Not borrowed while setting the timeout, the handlers of the idle monitor need the state
+        let minutes: u32 = self.state.borrow().settings.gschema.get_key(key);
+        let timeout = if minutes == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(u64::from(minutes) * 60))
+        };
+        IdleMonitor::set_timeout(&self.idle, timeout);
     }
 
-    pub fn handle_save_button(main_win: &Rc<Self>) {
-        if let Some(edit_view) = main_win.get_current_edit_view() {
-            if edit_view.borrow().file_name.is_some() {
-                let ev = edit_view.borrow();
-                let core = main_win.core.clone();
-                core.save(&ev.view_id, ev.file_name.as_ref().unwrap());
-            } else {
-                Self::save_as(main_win, &edit_view);
-            }
-        }
+    /// Whether some background work (see `background::should_run`) should be skipped because we're
+    /// on a metered connection or the battery is almost empty
+    pub fn is_reduced_mode(&self) -> bool {
+        self.state.borrow().reduced_mode
     }
 
-    fn current_save_as(main_win: &Rc<Self>) {
-        if let Some(edit_view) = main_win.get_current_edit_view() {
-            Self::save_as(main_win, &edit_view);
+    fn update_reduced_mode(&self) {
+        let metered = gio::NetworkMonitor::get_default().get_network_metered();
+        let reduced_mode = metered || self.power.borrow().is_low_battery();
+        if reduced_mode == self.is_reduced_mode() {
+            return;
+        }
+
+        debug!("{}: {}", gettext("Setting reduced mode"), reduced_mode);
+        self.state.borrow_mut().reduced_mode = reduced_mode;
+        for edit_view in self.views.borrow().values() {
+            edit_view.borrow().update_reduced_mode_label();
         }
     }
 
-    /// Display the FileChooserNative, send the result to the Xi core.
-    /// Don't use FileChooserDialog here, it doesn't work for Flatpaks.
-    /// This may call the GTK main loop.  There must not be any RefCell borrows out while this
-    /// function runs.
-    fn save_as(main_win: &Rc<Self>, edit_view: &Rc<RefCell<EditView>>) {
-        let fcn = FileChooserNative::new(
-            Some(gettext("Save file").as_str()),
-            Some(&main_win.window),
-            FileChooserAction::Save,
-            Some(gettext("Save").as_str()),
-            Some(gettext("Cancel").as_str()),
-        );
-        fcn.set_transient_for(Some(&main_win.window.clone()));
-        fcn.set_current_name("");
+    /// Opens files which are bigger than the size limit the user has set as plain text, so
+    /// they aren't highlighted.
+    fn limit_highlighting(&self, view_id: &str, file_name: &str) {
+        let limit: u32 = self
+            .state
+            .borrow()
+            .settings
+            .gschema
+            .get_key("highlight-size-limit");
+        if limit == 0 {
+            return;
+        }
 
-        fcn.connect_response(enclose!((edit_view, main_win) move |fcd, res| {
-            debug!(
-                "{}: {:#?}",
-                gettext("FileChooserNative save response"),
-                res
+        let size = std::fs::metadata(file_name).map(|m| m.len()).unwrap_or(0);
+        if size > u64::from(limit) * 1024 {
+            info!(
+                "{} '{}' {}",
+                gettext("Not highlighting"),
+                file_name,
+                gettext("because it's bigger than the size limit")
             );
+            self.core.set_language(view_id, "Plain Text");
+        }
+    }
 
-            if res == ResponseType::Accept {
-                for file in fcd.get_filenames() {
-                    let file_str = &file.to_string_lossy().into_owned();
-                    if let Some(file) = fcd.get_filename() {
-                        match &std::fs::OpenOptions::new().write(true).create(true).open(&file) {
-                            Ok(_) => {
-                                debug!("{} {:?}", gettext("Saving file"), &file);
-                                let view_id = edit_view.borrow().view_id.clone();
-                                let file = file.to_string_lossy();
-                                main_win.core.save(&view_id, &file);
-                                edit_view.borrow_mut().set_file(&file);
-                            }
-                        Err(e) => {
-                            let err_msg = format!("{} '{}': {}", &gettext("Couldn't save file"), &file_str, &e.to_string());
-                            ErrorDialog::new(ErrorMsg {msg: err_msg, fatal: false});
-                        }
-                    }
-                }
-            }
-                }
-        }));
-
-        fcn.run();
+    /// Reopens the panels which were open when gxi was closed the last time. The search bar is
+    /// restored once the first EditView has been created, see `restore_search_layout`.
+    fn restore_session(main_win: &Rc<Self>) {
+        let find_in_files_state = main_win.session.borrow().panel("find-in-files");
+        if find_in_files_state.open {
+            Self::find_in_files(main_win);
+        }
+        if let Some(find_in_files) = &*main_win.find_in_files.borrow() {
+            find_in_files.restore_size(&find_in_files_state);
+        }
     }
 
-    fn prefs(main_win: Rc<Self>) {
-        let gschema = { &main_win.properties.borrow().gschema };
-        PrefsWin::new(&main_win.window, &main_win.state, &main_win.core, &gschema);
+    fn restore_search_layout(&self, edit_view: &EditView) {
+        let session = self.session.borrow();
+        edit_view.restore_search_layout(SearchLayout {
+            search: session.panel("search").open,
+            replace: session.panel("replace").open,
+            options: session.panel("search-options").open,
+        });
     }
 
-    fn about(main_win: Rc<Self>) {
-        AboutWin::new(&main_win.window);
+    /// Opens `file_name` (or switches to its tab if it's open already) and moves the cursor
+    /// to the (zero based) `line`.
+    pub fn open_file_at(&self, file_name: &str, line: u64) {
+        self.open_file(file_name, Some(line));
     }
 
-    fn find(main_win: &Rc<Self>) {
-        if let Some(edit_view) = main_win.get_current_edit_view() {
-            edit_view.borrow().start_search();
+    /// Switches to the tab of `file_name`, opening it if it isn't open yet, and moves the cursor
+    /// to the (zero based) `line` if it's given
+    pub fn open_file(&self, file_name: &str, line: Option<u64>) {
+        let open_ev = self
+            .views
+            .borrow()
+            .values()
+            .find(|ev| ev.borrow().file_name.as_ref().map(String::as_str) == Some(file_name))
+            .cloned();
+
+        if let Some(ev) = open_ev {
+            let ev = ev.borrow();
+            let idx = self.notebook.page_num(&ev.root_widget);
+            self.notebook.set_current_page(idx);
+            if let Some(line) = line {
+                self.core.goto_line(&ev.view_id, line);
+            }
+        } else {
+            if let Some(line) = line {
+                self.pending_lines
+                    .borrow_mut()
+                    .insert(file_name.to_string(), line);
+            }
+            self.req_new_view(Some(file_name));
         }
     }
 
-    fn replace(main_win: &Rc<Self>) {
-        if let Some(edit_view) = main_win.get_current_edit_view() {
-            edit_view.borrow().start_replace();
-        }
+    /// Returns the EditViews which show the file `file_name`
+    pub fn views_of_file(&self, file_name: &str) -> Vec<Rc<RefCell<EditView>>> {
+        self.views
+            .borrow()
+            .values()
+            .filter(|ev| ev.borrow().file_name.as_ref().map(String::as_str) == Some(file_name))
+            .cloned()
+            .collect()
+    }
+
+    /// Switches to the tab the quick mark `name` is in and moves the cursor to it
+    fn jump_to_quick_mark(&self, name: char) {
+        let mark = match self.state.borrow().quick_marks.get(name) {
+            Some(mark) => mark.clone(),
+            None => return,
+        };
+        let edit_view = match self.views.borrow().get(&mark.view_id) {
+            Some(edit_view) => edit_view.clone(),
+            None => return,
+        };
+        let ev = edit_view.borrow();
+        let idx = self.notebook.page_num(&ev.root_widget);
+        self.notebook.set_current_page(idx);
+        ev.goto_quick_mark(&mark);
     }
 
     fn get_current_edit_view(&self) -> Option<Rc<RefCell<EditView>>> {
@@ -743,6 +3093,18 @@ impl MainWin {
         let mut params = json!({});
         if let Some(file_name) = file_name {
             params["file_path"] = json!(file_name);
+            // xi-editor reads the whole file before it replies
+            let size = std::fs::metadata(file_name).map_or(0, |m| m.len());
+            if size >= HUGE_FILE_SIZE {
+                let name = Path::new(file_name)
+                    .file_name()
+                    .map_or_else(|| file_name.into(), |name| name.to_string_lossy());
+                let label = format!("{} '{}'", gettext("Loading"), name);
+                self.loading_files.borrow_mut().insert(
+                    file_name.to_string(),
+                    Progress::start(&self.progress, &label),
+                );
+            }
         }
 
         let shared_queue = self.shared_queue.clone();
@@ -759,8 +3121,21 @@ impl MainWin {
     fn new_view_response(main_win: &Rc<Self>, file_name: Option<String>, value: &Value) {
         trace!("{}", gettext("Creating new EditView"));
         let mut old_ev = None;
+        let loading = file_name
+            .as_ref()
+            .and_then(|f| main_win.loading_files.borrow_mut().remove(f));
 
         if let Some(view_id) = value.as_str() {
+            // The user doesn't want the file anymore
+            if loading.map_or(false, |operation| operation.is_cancelled()) {
+                main_win.core.close_view(view_id);
+                return;
+            }
+
+            let pending_line = file_name
+                .as_ref()
+                .and_then(|f| main_win.pending_lines.borrow_mut().remove(f));
+
             let position = if let Some(curr_ev) = main_win.get_current_edit_view() {
                 if curr_ev.borrow().is_empty() {
                     old_ev = Some(curr_ev.clone());
@@ -785,10 +3160,25 @@ impl MainWin {
                 &main_win.state,
                 &main_win.core,
                 &hamburger_button,
-                file_name,
+                file_name.clone(),
                 view_id.to_string(),
                 &main_win.window,
             );
+            if let Some(file_name) = &file_name {
+                main_win.limit_highlighting(view_id, file_name);
+                if !main_win.is_private() {
+                    go_to_file::remember(
+                        &mut main_win.session.borrow_mut().recent_files,
+                        file_name,
+                    );
+                }
+            }
+            if let Some(origin) = file_name.and_then(|f| sudoedit::origin_of(Path::new(&f))) {
+                debug!("{}: {:?}", gettext("Editing sudoedit copy of"), origin);
+                edit_view
+                    .borrow_mut()
+                    .set_origin(Some(origin.to_string_lossy().into_owned()));
+            }
             {
                 let ev = edit_view.borrow();
                 let page_num = main_win.notebook.insert_page(
@@ -811,13 +3201,61 @@ impl MainWin {
                     .close_button
                     .connect_clicked(enclose!((main_win, edit_view) move |_| {
                         Self::close_view(&main_win, &edit_view);
+                        main_win.close_if_empty();
                     }));
+                ev.connect_open_file(enclose!((main_win) move |path, line| {
+                    main_win.open_file(&path.to_string_lossy(), line);
+                }));
+                ev.connect_jump_to_quick_mark(enclose!((main_win) move |name| {
+                    main_win.jump_to_quick_mark(name);
+                }));
+                let weak_ev = Rc::downgrade(&edit_view);
+                ev.connect_search_results(enclose!((main_win) move |lines| {
+                    if let Some(edit_view) = weak_ev.upgrade() {
+                        main_win.search_results.set_matches(&edit_view, lines);
+                    }
+                }));
+                ev.connect_go_to_definition(enclose!((main_win) move |word, file_name| {
+                    Self::go_to_definition(&main_win, word, file_name)
+                }));
+                let weak_ev = Rc::downgrade(&edit_view);
+                ev.connect_check_file(enclose!((main_win) move |file_name, language| {
+                    if let Some(edit_view) = weak_ev.upgrade() {
+                        let commands = main_win
+                            .state
+                            .borrow()
+                            .settings
+                            .gschema
+                            .settings
+                            .get_strv("lint-commands");
+                        let command = diagnostics::lint_command(&commands, language);
+                        Diagnostics::check(&main_win.diagnostics, &edit_view, file_name, command);
+                    }
+                }));
             }
 
             main_win
                 .views
                 .borrow_mut()
                 .insert(view_id.to_string(), edit_view);
+
+            if let Some(line) = pending_line {
+                main_win.core.goto_line(view_id, line);
+            }
+
+            let edit_view = main_win.views.borrow().get(view_id).cloned();
+            if let Some(edit_view) = edit_view {
+                Self::lock_document(main_win, &edit_view);
+            }
+
+            main_win.update_subtitle(main_win.get_current_edit_view().as_ref());
+            main_win.update_highlighting();
+
+            if main_win.restore_search.replace(false) {
+                if let Some(edit_view) = main_win.views.borrow().get(view_id) {
+                    main_win.restore_search_layout(&edit_view.borrow());
+                }
+            }
         }
         if let Some(empty_ev) = old_ev {
             Self::close_view(&main_win, &empty_ev);
@@ -861,12 +3299,28 @@ impl MainWin {
     fn close(main_win: &Rc<Self>) -> SaveAction {
         trace!("{}", gettext("Closing current Editview"));
         if let Some(edit_view) = main_win.get_current_edit_view() {
-            Self::close_view(&main_win, &edit_view)
+            let save_action = Self::close_view(&main_win, &edit_view);
+            main_win.close_if_empty();
+            save_action
         } else {
             SaveAction::Cancel
         }
     }
 
+    /// Closes the window once the user has closed its last tab, if they want that
+    fn close_if_empty(&self) {
+        let close_window: bool = self
+            .state
+            .borrow()
+            .settings
+            .gschema
+            .get_key("close-window-with-last-tab");
+        if close_window && self.notebook.get_n_pages() == 0 {
+            debug!("{}", gettext("Closing the window with its last tab"));
+            self.window.close();
+        }
+    }
+
     fn close_view(main_win: &Rc<Self>, edit_view: &Rc<RefCell<EditView>>) -> SaveAction {
         trace!(
             "{} {}",
@@ -947,35 +3401,196 @@ impl MainWin {
             }
             main_win.view_id_to_w.borrow_mut().remove(&view_id);
             main_win.views.borrow_mut().remove(&view_id);
+            {
+                let mut state = main_win.state.borrow_mut();
+                state.quick_marks.remove_view(&view_id);
+                state.buffer_words.remove_view(&view_id);
+            }
+            main_win.search_results.remove_view(&view_id);
+            main_win.blame.remove_view(&view_id);
             main_win.core.close_view(&view_id);
+
+            // Release the lock once no view of the file is left
+            if let Some(file_name) = edit_view.borrow().file_name.as_ref() {
+                let still_open = main_win
+                    .views
+                    .borrow()
+                    .values()
+                    .any(|ev| ev.borrow().file_name.as_ref() == Some(file_name));
+                if !still_open {
+                    main_win.doc_locks.borrow_mut().remove(file_name);
+                    main_win.diagnostics.remove_file(file_name);
+                }
+            }
         }
         save_action
     }
+
+    /// Locks the file of a newly opened EditView if the user enabled lock files. If another
+    /// process holds a lock on the file already, we ask the user whether to open it read-only,
+    /// steal the lock or to close it again.
+    fn lock_document(main_win: &Rc<Self>, edit_view: &Rc<RefCell<EditView>>) {
+        let file_name = match edit_view.borrow().file_name.clone() {
+            Some(file_name) => file_name,
+            None => return,
+        };
+        let lock_files: bool = main_win.properties.borrow().gschema.get_key("lock-files");
+        if !lock_files || main_win.doc_locks.borrow().contains_key(&file_name) {
+            return;
+        }
+
+        let path = Path::new(&file_name);
+        let lock = match DocLock::acquire(path) {
+            Ok(lock) => Some(lock),
+            Err(LockError::Io(e)) => {
+                warn!("{} '{}': {}", gettext("Couldn't lock file"), file_name, e);
+                None
+            }
+            Err(LockError::HeldBy(owner)) => {
+                match Self::ask_lock_dialog(main_win, &file_name, &owner) {
+                    LockAction::ReadOnly => {
+                        edit_view.borrow_mut().set_read_only(true);
+                        None
+                    }
+                    LockAction::Steal => match DocLock::steal(path) {
+                        Ok(lock) => Some(lock),
+                        Err(e) => {
+                            let msg =
+                                format!("{} '{}': {}", gettext("Couldn't lock file"), file_name, e);
+                            edit_view.borrow().notify(&msg, MessageType::Error);
+                            None
+                        }
+                    },
+                    LockAction::Cancel => {
+                        Self::close_view(main_win, edit_view);
+                        None
+                    }
+                }
+            }
+        };
+
+        if let Some(lock) = lock {
+            main_win.doc_locks.borrow_mut().insert(file_name, lock);
+        }
+    }
+
+    fn ask_lock_dialog(main_win: &Rc<Self>, file_name: &str, owner: &LockOwner) -> LockAction {
+        let lock_dialog = MessageDialog::new(
+            Some(&main_win.window),
+            DialogFlags::all(),
+            MessageType::Warning,
+            ButtonsType::None,
+            &format!(
+                "{} '{}' ({}: {})",
+                gettext("Another program is editing"),
+                file_name,
+                gettext("locked by"),
+                owner
+            ),
+        );
+        lock_dialog.add_button(
+            &gettext("Cancel"),
+            ResponseType::Other(LockAction::Cancel as u16),
+        );
+        lock_dialog.add_button(
+            &gettext("Steal Lock"),
+            ResponseType::Other(LockAction::Steal as u16),
+        );
+        lock_dialog.add_button(
+            &gettext("Open Read-Only"),
+            ResponseType::Other(LockAction::ReadOnly as u16),
+        );
+        lock_dialog.set_default_response(ResponseType::Other(LockAction::ReadOnly as u16));
+        let ret = lock_dialog.run();
+        lock_dialog.destroy();
+
+        LockAction::from_i32(ret.into()).unwrap_or(LockAction::Cancel)
+    }
 }
 
-pub fn new_settings() -> Settings {
-    let gschema = GSchema::new("com.github.Cogitri.gxi");
-    let interface_font = {
-        use gtk::SettingsExt;
-        let gtk_settings = gtk::Settings::get_default().unwrap();
-        gtk_settings
-            .get_property_gtk_font_name()
-            .unwrap()
-            .to_string()
-    };
+/// Renames `old` to `new` via a temporary name, which also works if they only differ in case on
+/// case-insensitive filesystems. Fails if `new` is another file that exists already.
+fn rename_two_step(old: &Path, new: &Path) -> std::io::Result<()> {
+    if new.exists() && !is_same_file(old, new) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            gettext("A file with that name exists already"),
+        ));
+    }
+
+    let name = old
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = old.with_file_name(format!(".{}.gxi-rename", name));
+    std::fs::rename(old, &tmp)?;
+    if let Err(e) = std::fs::rename(&tmp, new) {
+        // Put the file back where it was
+        let _ = std::fs::rename(&tmp, old);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
 
-    Settings {
-        trailing_spaces: gschema.get_key("draw-trailing-spaces"),
-        highlight_line: gschema.get_key("highlight-line"),
-        right_margin: gschema.get_key("draw-right-margin"),
-        column_right_margin: gschema.get_key("column-right-margin"),
-        edit_font: gschema.get_key("font"),
-        tab_size: gschema.get_key("tab-size"),
-        interface_font,
-        gschema,
+/// There are no inode numbers outside of Unix, so compare the canonical paths. Paths on Windows
+/// are case insensitive, which `canonicalize` takes care of.
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
     }
 }
 
+/// The name of the GTK theme, e.g. `Adwaita` or `HighContrastInverse`
+fn gtk_theme_name() -> String {
+    use gtk::SettingsExt;
+    gtk::Settings::get_default()
+        .and_then(|s| s.get_property_gtk_theme_name())
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
+
+fn is_high_contrast(gtk_theme: &str) -> bool {
+    gtk_theme.starts_with("HighContrast")
+}
+
+/// Whether the GTK theme `gtk_theme` is a dark one, e.g. `HighContrastInverse` or `Adwaita-dark`
+fn is_dark(gtk_theme: &str) -> bool {
+    gtk_theme.ends_with("Inverse") || gtk_theme.ends_with("-dark")
+}
+
+/// Picks the syntax theme to use with GTK's high contrast theme: one with 'contrast' in its name
+/// if there is one, otherwise the one of xi-editor's default themes with the most contrast.
+fn high_contrast_theme(themes: &[String], dark: bool) -> Option<String> {
+    let fallback = if dark {
+        HIGH_CONTRAST_DARK_FALLBACK
+    } else {
+        HIGH_CONTRAST_LIGHT_FALLBACK
+    };
+    let high_contrast: Vec<&String> = themes
+        .iter()
+        .filter(|t| t.to_lowercase().contains("contrast"))
+        .collect();
+    high_contrast
+        .iter()
+        .find(|t| t.to_lowercase().contains("dark") == dark)
+        .or_else(|| high_contrast.first())
+        .cloned()
+        .or_else(|| themes.iter().find(|t| *t == fallback))
+        .cloned()
+}
+
 pub fn connect_settings_change(main_win: &Rc<MainWin>, core: &Core) {
     let gschema = main_win.state.borrow().settings.gschema.clone();
     gschema
@@ -990,6 +3605,34 @@ pub fn connect_settings_change(main_win: &Rc<MainWin>, core: &Core) {
                         ev.borrow().view_item.edit_area.queue_draw();
                     }
                 }
+                "draw-spaces" => {
+                    let val = gschema.get_key("draw-spaces");
+                    main_win.state.borrow_mut().settings.draw_spaces = val;
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                    }
+                }
+                "draw-tabs" => {
+                    let val = gschema.get_key("draw-tabs");
+                    main_win.state.borrow_mut().settings.draw_tabs = val;
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                    }
+                }
+                "draw-newlines" => {
+                    let val = gschema.get_key("draw-newlines");
+                    main_win.state.borrow_mut().settings.draw_newlines = val;
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                    }
+                }
+                "draw-whitespace-mode" => {
+                    let val: String = gschema.get_key("draw-whitespace-mode");
+                    main_win.state.borrow_mut().settings.whitespace_mode = WhitespaceMode::from_name(&val);
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                    }
+                }
                 "highlight-line" => {
                     let val = gschema.get_key("highlight-line");
                     main_win.state.borrow_mut().settings.highlight_line = val;
@@ -997,6 +3640,24 @@ pub fn connect_settings_change(main_win: &Rc<MainWin>, core: &Core) {
                         ev.borrow().view_item.edit_area.queue_draw();
                     }
                 }
+                "highlight-occurrences" => {
+                    let val = gschema.get_key("highlight-occurrences");
+                    main_win.state.borrow_mut().settings.highlight_occurrences = val;
+                    for edit_view in main_win.views.borrow().values() {
+                        edit_view.borrow().update_occurrences();
+                    }
+                }
+                "fullscreen-auto-hide-header-bar" => {
+                    let val = gschema.get_key("fullscreen-auto-hide-header-bar");
+                    Fullscreen::set_auto_hide(&main_win.fullscreen, val);
+                }
+                "show-color-chips" => {
+                    let val = gschema.get_key("show-color-chips");
+                    main_win.state.borrow_mut().settings.show_color_chips = val;
+                    for edit_view in main_win.views.borrow().values() {
+                        edit_view.borrow().view_item.edit_area.queue_draw();
+                    }
+                }
                 "draw-right-margin" => {
                     let val = gschema.get_key("draw-right-margin");
                     main_win.state.borrow_mut().settings.right_margin = val;
@@ -1009,7 +3670,85 @@ pub fn connect_settings_change(main_win: &Rc<MainWin>, core: &Core) {
                     main_win.state.borrow_mut().settings.column_right_margin = val;
                     if let Some(ev) = main_win.get_current_edit_view() {
                         ev.borrow().view_item.edit_area.queue_draw();
+                        ev.borrow().update_long_lines_label();
+                    }
+                }
+                "highlight-long-lines" => {
+                    let val = gschema.get_key("highlight-long-lines");
+                    main_win.state.borrow_mut().settings.highlight_long_lines = val;
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                        ev.borrow().update_long_lines_label();
+                    }
+                }
+                "long-line-columns" => {
+                    let val = long_line_columns(&gschema);
+                    main_win.state.borrow_mut().settings.long_line_columns = val;
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                        ev.borrow().update_long_lines_label();
+                    }
+                }
+                "continue-lists" => {
+                    let val = gschema.get_key("continue-lists");
+                    main_win.state.borrow_mut().settings.continue_lists = val;
+                }
+                "continue-lists-languages" => {
+                    let val = languages(&gschema, key);
+                    main_win.state.borrow_mut().settings.continue_lists_languages = val;
+                }
+                "path-completion-languages" => {
+                    let val = languages(&gschema, key);
+                    main_win.state.borrow_mut().settings.path_completion_languages = val;
+                }
+                "abbreviations" => {
+                    let val = abbreviations(&gschema);
+                    main_win.state.borrow_mut().settings.abbreviations = val;
+                }
+                "caret-color" => {
+                    let val: String = gschema.get_key("caret-color");
+                    main_win.state.borrow_mut().settings.caret_color = color_from_hex(&val);
+                    if let Some(ev) = main_win.get_current_edit_view() {
+                        ev.borrow().view_item.edit_area.queue_draw();
+                    }
+                }
+                "selection-color" => {
+                    let val: String = gschema.get_key("selection-color");
+                    main_win.state.borrow_mut().settings.selection_color = color_from_hex(&val);
+                    main_win.update_selection_style();
+                }
+                "smart-punctuation" => {
+                    let val = gschema.get_key("smart-punctuation");
+                    main_win.state.borrow_mut().settings.smart_punctuation = val;
+                }
+                "spell-check-language" => main_win.update_spell_check_language(),
+                "vim-mode" => {
+                    let val = gschema.get_key("vim-mode");
+                    main_win.state.borrow_mut().settings.vim_mode = val;
+                    for edit_view in main_win.views.borrow().values() {
+                        edit_view.borrow().update_vim_mode_label();
                     }
+                    main_win.update_replace_accel();
+                }
+                "undo-group-timeout" => {
+                    let val = gschema.get_key("undo-group-timeout");
+                    main_win.state.borrow_mut().settings.undo_group_timeout = val;
+                }
+                "page-scroll-percent" => {
+                    let val = gschema.get_key("page-scroll-percent");
+                    main_win.state.borrow_mut().settings.page_scroll_percent = val;
+                }
+                "page-keeps-caret" => {
+                    let val = gschema.get_key("page-keeps-caret");
+                    main_win.state.borrow_mut().settings.page_keeps_caret = val;
+                }
+                "scroll-offset" => {
+                    let val = gschema.get_key("scroll-offset");
+                    main_win.state.borrow_mut().settings.scroll_offset = val;
+                }
+                "line-cache-budget" => {
+                    let val = gschema.get_key("line-cache-budget");
+                    main_win.state.borrow_mut().settings.line_cache_budget = val;
                 }
                 "translate-tabs-to-spaces" => {
                     let val: bool = gschema.get_key("translate-tabs-to-spaces");
@@ -1073,6 +3812,29 @@ pub fn connect_settings_change(main_win: &Rc<MainWin>, core: &Core) {
                 },
                 // We load these during startup
                 "window-height" | "window-width" | "window-maximized" => {}
+                "highlight-on-battery" => main_win.update_highlighting(),
+                "idle-pause-minutes" | "idle-pause-minutes-on-battery" => {
+                    main_win.update_idle_timeout()
+                }
+                // Only checked when opening files
+                "highlight-size-limit" => {}
+                // Only checked when comparing documents
+                "external-diff-tool" => {}
+                // Only checked when speaking text
+                "speech-rate" | "speech-voice" => {}
+                // Only checked when opening files
+                "lock-files" => {}
+                // Only read when exporting documents
+                "export-line-numbers" => {}
+                // Only checked on startup
+                "show-whats-new" | "last-version" => {}
+                // Only checked when inserting the date
+                "date-time-format" => {}
+                // Only read when searching
+                "search-history" | "replace-history" => {}
+                // Only checked when closing tabs
+                "close-window-with-last-tab" => {}
+                "lint-commands" => main_win.check_files(),
                 _key => {
                     warn!("{}: {}", gettext("Unknown key change event"), _key)
                 }
@@ -0,0 +1,585 @@
+//! A panel previewing Markdown documents as formatted text. The Markdown is rendered by a simple
+//! line based parser, which knows headings, paragraphs, lists, quotes, code and emphasis, but
+//! not HTML or tables. The preview is updated once the document has been left alone for a moment
+//! and scrolls along with the EditView.
+
+use crate::panels::{Panels, Slot};
+use gettextrs::gettext;
+use glib::{source, SignalHandlerId, SourceId};
+use gtk::*;
+use gxi_editview::EditView;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+/// Name the panel is registered under, for the `app.toggle_panel` action
+pub const PANEL_NAME: &str = "markdown-preview";
+
+/// How long (in milliseconds) the document has to be left alone before the preview is updated
+const REFRESH_DELAY: u32 = 300;
+/// Indentation of quotes, code blocks and each level of lists in pixels
+const INDENT: i32 = 16;
+
+/// How a part of the preview is formatted
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Style {
+    /// Headings with their level, 1 to 6
+    Heading(usize),
+    Emphasis,
+    Strong,
+    Code,
+    CodeBlock,
+    Quote,
+    Link,
+    /// Items of lists with their nesting level, starting at 0
+    ListItem(usize),
+    Rule,
+}
+
+impl Style {
+    fn tag_name(self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn create_tag(self) -> TextTag {
+        let tag = TextTag::new(Some(self.tag_name().as_str()));
+        match self {
+            Style::Heading(level) => {
+                let scales = [2.0, 1.6, 1.3, 1.15, 1.0, 0.9];
+                tag.set_property_scale(scales[level.min(6).max(1) - 1]);
+                tag.set_property_weight(700);
+                tag.set_property_pixels_below_lines(6);
+            }
+            Style::Emphasis => tag.set_property_style(pango::Style::Italic),
+            Style::Strong => tag.set_property_weight(700),
+            Style::Code => tag.set_property_family(Some("monospace")),
+            Style::CodeBlock => {
+                tag.set_property_family(Some("monospace"));
+                tag.set_property_left_margin(INDENT);
+                tag.set_property_wrap_mode(WrapMode::None);
+            }
+            Style::Quote => {
+                tag.set_property_left_margin(INDENT);
+                tag.set_property_style(pango::Style::Italic);
+            }
+            Style::Link => tag.set_property_underline(pango::Underline::Single),
+            Style::ListItem(level) => tag.set_property_left_margin(INDENT * (level as i32 + 1)),
+            Style::Rule => tag.set_property_justification(Justification::Center),
+        }
+        tag
+    }
+}
+
+/// A piece of text of the preview and how it's formatted
+#[derive(Clone, Debug, PartialEq)]
+struct Run {
+    text: String,
+    styles: Vec<Style>,
+}
+
+/// A heading, paragraph, list item etc. of the preview
+#[derive(Debug, PartialEq)]
+struct Block {
+    /// The (zero based) line of the document the block starts on, to scroll the preview along
+    line: u64,
+    runs: Vec<Run>,
+}
+
+/// Splits `text` into runs, formatting code, emphasis and links and adding the `base` styles of
+/// the block. Markers without a closing one are kept as they are.
+fn inline(text: &str, base: &[Style]) -> Vec<Run> {
+    let mut runs = Vec::new();
+    let mut current = String::new();
+    let mut styles = base.to_vec();
+    let mut rest = text;
+
+    fn push(runs: &mut Vec<Run>, current: &mut String, styles: &[Style]) {
+        if !current.is_empty() {
+            runs.push(Run {
+                text: std::mem::replace(current, String::new()),
+                styles: styles.to_vec(),
+            });
+        }
+    }
+    fn toggle(styles: &mut Vec<Style>, style: Style) {
+        match styles.iter().position(|s| *s == style) {
+            Some(ix) => {
+                styles.remove(ix);
+            }
+            None => styles.push(style),
+        }
+    }
+
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        let prev_alnum = current.chars().last().map_or(false, char::is_alphanumeric)
+            || (current.is_empty()
+                && runs
+                    .last()
+                    .and_then(|r: &Run| r.text.chars().last())
+                    .map_or(false, char::is_alphanumeric));
+        match c {
+            '\\' if after.starts_with(|c: char| c.is_ascii_punctuation()) => {
+                let escaped = after.chars().next().unwrap();
+                current.push(escaped);
+                rest = &after[escaped.len_utf8()..];
+                continue;
+            }
+            '`' => {
+                if let Some(end) = after.find('`') {
+                    push(&mut runs, &mut current, &styles);
+                    let mut code_styles = styles.clone();
+                    code_styles.push(Style::Code);
+                    runs.push(Run {
+                        text: after[..end].to_string(),
+                        styles: code_styles,
+                    });
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+            '*' | '_' => {
+                let strong = after.starts_with(c);
+                let marker = if strong { &rest[..2] } else { &rest[..1] };
+                let style = if strong {
+                    Style::Strong
+                } else {
+                    Style::Emphasis
+                };
+                let closing = styles.contains(&style);
+                // Underscores within words, e.g. in snake_case, aren't emphasis
+                let within_word = c == '_'
+                    && prev_alnum
+                    && rest[marker.len()..].starts_with(char::is_alphanumeric);
+                // Like in `2 * 3`, markers followed by a space don't start emphasis
+                let after_marker = &rest[marker.len()..];
+                let opening = after_marker.starts_with(|c: char| !c.is_whitespace())
+                    && after_marker.contains(marker);
+                if !within_word && (closing || opening) {
+                    push(&mut runs, &mut current, &styles);
+                    toggle(&mut styles, style);
+                    rest = &rest[marker.len()..];
+                    continue;
+                }
+            }
+            '[' => {
+                let link = after.find("](").and_then(|label_end| {
+                    let url_len = after[label_end + 2..].find(')')?;
+                    Some((label_end, label_end + 2 + url_len + 1))
+                });
+                if let Some((label_end, link_end)) = link {
+                    push(&mut runs, &mut current, &styles);
+                    let mut link_styles = styles.clone();
+                    link_styles.push(Style::Link);
+                    runs.extend(inline(&after[..label_end], &link_styles));
+                    rest = &after[link_end..];
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        current.push(c);
+        rest = after;
+    }
+    push(&mut runs, &mut current, &styles);
+    runs
+}
+
+/// The level of the heading `line` is, if it's one
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && line[level..].starts_with(|c: char| c.is_whitespace()) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+/// Whether `line` is a horizontal rule like `---` or `* * *`
+fn is_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && ['-', '*', '_'].iter().any(|m| chars.iter().all(|c| c == m))
+}
+
+/// Splits the list item `line` into its indentation, bullet (or number) and text
+fn list_item(line: &str) -> Option<(usize, String, &str)> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let mut chars = trimmed.char_indices();
+    let (bullet, text_start) = match chars.next()? {
+        (_, '-') | (_, '*') | (_, '+') => ("•".to_string(), 1),
+        (_, c) if c.is_ascii_digit() => {
+            let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+            if !trimmed[digits..].starts_with('.') && !trimmed[digits..].starts_with(')') {
+                return None;
+            }
+            (trimmed[..=digits].to_string(), digits + 1)
+        }
+        _ => return None,
+    };
+    let text = &trimmed[text_start..];
+    if !text.starts_with(' ') && !text.is_empty() {
+        return None;
+    }
+    Some((indent, bullet, text.trim_start()))
+}
+
+/// Renders the Markdown `text` into the blocks of the preview
+fn render(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        let start = i as u64;
+        i += 1;
+
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let fence = &trimmed[..3];
+            let mut code = Vec::new();
+            while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+                code.push(lines[i]);
+                i += 1;
+            }
+            // The closing fence
+            i += 1;
+            blocks.push(Block {
+                line: start,
+                runs: vec![Run {
+                    text: code.join("\n"),
+                    styles: vec![Style::CodeBlock],
+                }],
+            });
+        } else if let Some(level) = heading_level(trimmed) {
+            let heading = trimmed[level..].trim().trim_end_matches('#').trim_end();
+            blocks.push(Block {
+                line: start,
+                runs: inline(heading, &[Style::Heading(level)]),
+            });
+        } else if is_rule(trimmed) {
+            blocks.push(Block {
+                line: start,
+                runs: vec![Run {
+                    text: "\u{2014}".repeat(10),
+                    styles: vec![Style::Rule],
+                }],
+            });
+        } else if let Some((indent, bullet, text)) = list_item(line) {
+            let style = Style::ListItem(indent / 2);
+            let mut runs = vec![Run {
+                text: format!("{} ", bullet),
+                styles: vec![style],
+            }];
+            runs.extend(inline(text, &[style]));
+            blocks.push(Block { line: start, runs });
+        } else if trimmed.starts_with('>') {
+            let mut quote = vec![trimmed.trim_start_matches('>').trim()];
+            while i < lines.len() && lines[i].trim().starts_with('>') {
+                quote.push(lines[i].trim().trim_start_matches('>').trim());
+                i += 1;
+            }
+            blocks.push(Block {
+                line: start,
+                runs: inline(&quote.join(" "), &[Style::Quote]),
+            });
+        } else {
+            let mut paragraph = vec![trimmed];
+            while i < lines.len() {
+                let next = lines[i].trim();
+                if next.is_empty()
+                    || next.starts_with("```")
+                    || next.starts_with("~~~")
+                    || next.starts_with('>')
+                    || heading_level(next).is_some()
+                    || is_rule(next)
+                    || list_item(lines[i]).is_some()
+                {
+                    break;
+                }
+                paragraph.push(next);
+                i += 1;
+            }
+            blocks.push(Block {
+                line: start,
+                runs: inline(&paragraph.join(" "), &[]),
+            });
+        }
+    }
+    blocks
+}
+
+pub struct MarkdownPreview {
+    root: Stack,
+    text_view: TextView,
+    /// The EditView whose document is previewed
+    edit_view: RefCell<Weak<RefCell<EditView>>>,
+    /// The first line of each block of the preview and where it starts in the buffer
+    block_offsets: Rc<RefCell<Vec<(u64, i32)>>>,
+    /// The pending refresh after the document has been changed
+    timeout: RefCell<Option<SourceId>>,
+    /// Scrolls the preview along with the EditView
+    scroll_handler: RefCell<Option<(Adjustment, SignalHandlerId)>>,
+    /// Whether scrolling the preview along has been scheduled already
+    scroll_pending: Cell<bool>,
+}
+
+impl MarkdownPreview {
+    pub fn new(panels: &Panels) -> Rc<Self> {
+        let text_view = TextView::new();
+        text_view.set_editable(false);
+        text_view.set_cursor_visible(false);
+        text_view.set_wrap_mode(WrapMode::WordChar);
+        text_view.set_left_margin(12);
+        text_view.set_right_margin(12);
+        text_view.set_top_margin(12);
+        text_view.set_bottom_margin(12);
+        text_view.set_pixels_below_lines(8);
+
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.set_size_request(300, -1);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        scrolled_window.add(&text_view);
+        let placeholder = Label::new(Some(gettext("Not a Markdown document").as_str()));
+        placeholder.get_style_context().add_class("dim-label");
+
+        let root = Stack::new();
+        root.add_named(&scrolled_window, "preview");
+        root.add_named(&placeholder, "placeholder");
+        root.show_all();
+
+        let preview = Rc::new(Self {
+            root,
+            text_view,
+            edit_view: RefCell::new(Weak::new()),
+            block_offsets: Default::default(),
+            timeout: RefCell::new(None),
+            scroll_handler: RefCell::new(None),
+            scroll_pending: Cell::new(false),
+        });
+        // The document is only rendered while the panel is shown
+        preview
+            .root
+            .connect_map(enclose!((preview) move |_| preview.refresh()));
+        panels.register(
+            PANEL_NAME,
+            &gettext("Markdown Preview"),
+            Slot::Right,
+            &preview.root,
+        );
+
+        preview
+    }
+
+    /// Previews the document of `edit_view`, e.g. because the user has switched to its tab
+    pub fn set_edit_view(preview: &Rc<Self>, edit_view: Option<&Rc<RefCell<EditView>>>) {
+        if let Some((vadj, handler)) = preview.scroll_handler.borrow_mut().take() {
+            vadj.disconnect(handler);
+        }
+        if let Some(edit_view) = edit_view {
+            let vadj = edit_view.borrow().view_item.vadj.clone();
+            let handler = vadj.connect_value_changed(enclose!((preview) move |_| {
+                Self::schedule_scroll(&preview);
+            }));
+            preview.scroll_handler.replace(Some((vadj, handler)));
+        }
+        preview
+            .edit_view
+            .replace(edit_view.map_or_else(Weak::new, Rc::downgrade));
+        preview.refresh();
+    }
+
+    /// Updates the preview once the document of the EditView with the id `view_id` has been left
+    /// alone for a moment, if it's the one previewed
+    pub fn document_changed(preview: &Rc<Self>, view_id: &str) {
+        let shown = preview
+            .edit_view
+            .borrow()
+            .upgrade()
+            .map_or(false, |ev| ev.borrow().view_id == view_id);
+        if !shown || !preview.root.get_mapped() {
+            return;
+        }
+        if let Some(id) = preview.timeout.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+        let id = glib::timeout_add_local(
+            REFRESH_DELAY,
+            enclose!((preview) move || {
+                preview.timeout.replace(None);
+                preview.refresh();
+                source::Continue(false)
+            }),
+        );
+        preview.timeout.replace(Some(id));
+    }
+
+    /// Scrolls the preview to the block at the top of the EditView. The EditView may be borrowed
+    /// while it's scrolled, so that's done once it's free.
+    fn schedule_scroll(preview: &Rc<Self>) {
+        if preview.scroll_pending.replace(true) || !preview.root.get_mapped() {
+            return;
+        }
+        glib::idle_add_local(enclose!((preview) move || {
+            preview.scroll_pending.set(false);
+            preview.scroll_to_edit_view();
+            source::Continue(false)
+        }));
+    }
+
+    fn scroll_to_edit_view(&self) {
+        let first_line = match self.edit_view.borrow().upgrade() {
+            Some(edit_view) => edit_view.borrow().first_visible_line(),
+            None => return,
+        };
+        scroll_to_line(&self.text_view, &self.block_offsets.borrow(), first_line);
+    }
+
+    fn refresh(&self) {
+        if !self.root.get_mapped() {
+            return;
+        }
+        let edit_view = match self.edit_view.borrow().upgrade() {
+            Some(edit_view) => edit_view,
+            None => {
+                self.root.set_visible_child_name("placeholder");
+                return;
+            }
+        };
+        let ev = edit_view.borrow();
+        if ev.language() != "Markdown" {
+            self.root.set_visible_child_name("placeholder");
+            return;
+        }
+        self.root.set_visible_child_name("preview");
+        let text_view = self.text_view.clone();
+        let block_offsets = self.block_offsets.clone();
+        let first_line = ev.first_visible_line();
+        ev.with_text(move |text| {
+            let offsets = show_blocks(&text_view, &render(text));
+            // Keep the part of the document which is being edited in view
+            scroll_to_line(&text_view, &offsets, first_line);
+            block_offsets.replace(offsets);
+        });
+    }
+}
+
+/// Scrolls `text_view` to the block containing the `line` of the document, with the `offsets`
+/// returned by `show_blocks`. That's done with a mark, as the lines of the text may not have been
+/// measured yet.
+fn scroll_to_line(text_view: &TextView, offsets: &[(u64, i32)], line: u64) {
+    let offset = offsets
+        .iter()
+        .take_while(|(first_line, _)| *first_line <= line)
+        .last()
+        .map_or(0, |(_, offset)| *offset);
+    if let Some(buffer) = text_view.get_buffer() {
+        if let Some(mark) = buffer.create_mark(None, &buffer.get_iter_at_offset(offset), true) {
+            text_view.scroll_to_mark(&mark, 0.0, true, 0.0, 0.0);
+            buffer.delete_mark(&mark);
+        }
+    }
+}
+
+/// Replaces the text of `text_view` with `blocks`, returning the first line of each block and
+/// where it starts in the buffer
+fn show_blocks(text_view: &TextView, blocks: &[Block]) -> Vec<(u64, i32)> {
+    let buffer = match text_view.get_buffer() {
+        Some(buffer) => buffer,
+        None => return Vec::new(),
+    };
+    let tag_table = buffer.get_tag_table().unwrap();
+    buffer.set_text("");
+    let mut offsets = Vec::new();
+    let mut iter = buffer.get_end_iter();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            buffer.insert(&mut iter, "\n");
+        }
+        offsets.push((block.line, iter.get_offset()));
+        for run in &block.runs {
+            let tags: Vec<TextTag> = run
+                .styles
+                .iter()
+                .map(|style| {
+                    tag_table.lookup(&style.tag_name()).unwrap_or_else(|| {
+                        let tag = style.create_tag();
+                        tag_table.add(&tag);
+                        tag
+                    })
+                })
+                .collect();
+            let tag_refs: Vec<&TextTag> = tags.iter().collect();
+            buffer.insert_with_tags(&mut iter, &run.text, &tag_refs);
+        }
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(text: &str, styles: &[Style]) -> Run {
+        Run {
+            text: text.to_string(),
+            styles: styles.to_vec(),
+        }
+    }
+
+    #[test]
+    fn inline_styles() {
+        assert_eq!(
+            inline("a *b* **c** `d*` e", &[]),
+            vec![
+                run("a ", &[]),
+                run("b", &[Style::Emphasis]),
+                run(" ", &[]),
+                run("c", &[Style::Strong]),
+                run(" ", &[]),
+                run("d*", &[Style::Code]),
+                run(" e", &[]),
+            ]
+        );
+        assert_eq!(
+            inline("see [the _docs_](https://xi-editor.io)", &[Style::Quote]),
+            vec![
+                run("see ", &[Style::Quote]),
+                run("the ", &[Style::Quote, Style::Link]),
+                run("docs", &[Style::Quote, Style::Link, Style::Emphasis]),
+            ]
+        );
+        // Unclosed markers, snake_case and escapes are kept as they are
+        assert_eq!(
+            inline("2 * 3 = snake_case_name \\*", &[]),
+            vec![run("2 * 3 = snake_case_name *", &[])]
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let text = "# Title #\n\nSome\ntext.\n\n- one\n  - two\n1. three\n\n> quoted\n> more\n\n```rust\nfn main() {}\n```\n---\n";
+        let blocks = render(text);
+        let lines: Vec<u64> = blocks.iter().map(|b| b.line).collect();
+        assert_eq!(lines, vec![0, 2, 5, 6, 7, 9, 12, 15]);
+        assert_eq!(blocks[0].runs, vec![run("Title", &[Style::Heading(1)])]);
+        assert_eq!(blocks[1].runs, vec![run("Some text.", &[])]);
+        assert_eq!(
+            blocks[3].runs,
+            vec![
+                run("• ", &[Style::ListItem(1)]),
+                run("two", &[Style::ListItem(1)])
+            ]
+        );
+        assert_eq!(blocks[4].runs[0], run("1. ", &[Style::ListItem(0)]));
+        assert_eq!(blocks[5].runs, vec![run("quoted more", &[Style::Quote])]);
+        assert_eq!(
+            blocks[6].runs,
+            vec![run("fn main() {}", &[Style::CodeBlock])]
+        );
+        assert_eq!(blocks[7].runs[0].styles, vec![Style::Rule]);
+    }
+}
@@ -0,0 +1,346 @@
+//! A panel listing the functions, types and headings of the current document, so it can be
+//! navigated by its structure. The symbols are found with simple patterns per language and
+//! nested by their indentation (or the level of headings). Activating a symbol moves the cursor
+//! to it.
+
+use crate::panels::{Panels, Slot};
+use gettextrs::gettext;
+use glib::{source, SourceId, Type};
+use gtk::*;
+use gxi_editview::EditView;
+use log::debug;
+use regex::Regex;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// Name the panel is registered under, for the `app.toggle_panel` action
+pub const PANEL_NAME: &str = "outline";
+
+/// How long (in milliseconds) the document has to be left alone before the outline is updated
+const REFRESH_DELAY: u32 = 500;
+
+const LINE_COLUMN: u32 = 0;
+const MARKUP_COLUMN: u32 = 1;
+
+/// A function, type, heading etc. in a document
+#[derive(Clone, Debug, PartialEq)]
+struct Symbol {
+    /// Zero based line the symbol is defined on
+    line: u64,
+    /// Symbols nest in the symbol before them with a lower level, e.g. methods in their class.
+    /// It's the indentation of code and the level of headings.
+    level: usize,
+    /// What the symbol is, e.g. `fn` or `class`. Empty for headings.
+    kind: String,
+    name: String,
+}
+
+/// Patterns finding the symbols of `language`. Their `name` group is the name of the symbol and
+/// their `kind` group (if there's one) what it is. If they have a `level` group its length is the
+/// level of the symbol, otherwise the indentation of the line is.
+fn patterns(language: &str) -> &'static [&'static str] {
+    match language {
+        "Rust" => &[
+            r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default)\s+)*(?:extern\s+"[^"]*"\s+)?(?P<kind>fn|struct|enum|union|trait|mod|type|macro_rules!)\s*(?P<name>\w+)"#,
+            r"^\s*(?P<kind>impl)\b(?:<[^{]*?>)?\s*(?P<name>[^{]+?)\s*(?:\{|where\b|$)",
+        ],
+        "Python" => &[r"^\s*(?:async\s+)?(?P<kind>def|class)\s+(?P<name>\w+)"],
+        "Go" => &[
+            r"^(?P<kind>func)\s+(?:\([^)]*\)\s*)?(?P<name>\w+)",
+            r"^(?P<kind>type)\s+(?P<name>\w+)",
+        ],
+        "JavaScript" | "TypeScript" | "TypeScriptReact" => &[
+            r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?(?P<kind>function\*?|class|interface)\s+(?P<name>[\w$]+)",
+        ],
+        "C" | "C++" | "Objective-C" | "Java" | "C#" => &[
+            r"^\s*(?:(?:public|private|protected|internal|static|abstract|final|sealed|export|typedef)\s+)*(?P<kind>class|struct|enum|interface|namespace|union)\s+(?P<name>\w+)[^;]*$",
+            r"^\s*(?:(?:public|private|protected|internal|static|abstract|final|virtual|override|inline|extern|const|unsigned|signed)\s+)*[\w:<>,]+[\s*&]+(?P<name>[\w:~]+)\s*\([^;]*$",
+        ],
+        "Ruby" => &[r"^\s*(?P<kind>def|class|module)\s+(?P<name>[\w.:?!=]+)"],
+        "Shell-Unix-Generic" | "Bourne Again Shell (bash)" => {
+            &[r"^\s*(?:(?P<kind>function)\s+)?(?P<name>[\w-]+)\s*\(\)"]
+        }
+        "Markdown" => &[r"^(?P<level>#{1,6})\s+(?P<name>.+?)[\s#]*$"],
+        _ => &[],
+    }
+}
+
+/// Words which look like function names to the patterns of C-like languages, e.g. in `} else
+/// if (a) {`
+const KEYWORDS: &[&str] = &["if", "for", "while", "switch", "return", "catch", "sizeof"];
+
+/// Finds the symbols of `text`, which is written in `language`
+fn symbols(language: &str, text: &str, tab_size: usize) -> Vec<Symbol> {
+    let regexes: Vec<Regex> = patterns(language)
+        .iter()
+        .filter_map(|pattern| Regex::new(pattern).ok())
+        .collect();
+    if regexes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut symbols = Vec::new();
+    // Lines in fenced code blocks of Markdown documents aren't headings
+    let mut in_code_block = false;
+    for (line, line_text) in text.lines().enumerate() {
+        if language == "Markdown" && line_text.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            continue;
+        }
+        let captures = match regexes.iter().find_map(|re| re.captures(line_text)) {
+            Some(captures) => captures,
+            None => continue,
+        };
+        let name = captures.name("name").map_or("", |m| m.as_str()).trim();
+        if name.is_empty() || KEYWORDS.contains(&name) {
+            continue;
+        }
+        let level = match captures.name("level") {
+            Some(level) => level.as_str().len(),
+            None => line_text
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .map(|c| if c == '\t' { tab_size } else { 1 })
+                .sum(),
+        };
+        symbols.push(Symbol {
+            line: line as u64,
+            level,
+            kind: captures.name("kind").map_or("", |m| m.as_str()).to_string(),
+            name: name.to_string(),
+        });
+    }
+    symbols
+}
+
+fn markup(symbol: &Symbol) -> String {
+    if symbol.kind.is_empty() {
+        glib::markup_escape_text(&symbol.name).to_string()
+    } else {
+        format!(
+            "<span alpha=\"60%\">{}</span> {}",
+            glib::markup_escape_text(&symbol.kind),
+            glib::markup_escape_text(&symbol.name)
+        )
+    }
+}
+
+pub struct Outline {
+    root: Stack,
+    tree_store: TreeStore,
+    treeview: TreeView,
+    /// The EditView whose symbols are listed
+    edit_view: RefCell<Weak<RefCell<EditView>>>,
+    /// The pending refresh after the document has been changed
+    timeout: RefCell<Option<SourceId>>,
+}
+
+impl Outline {
+    pub fn new(panels: &Panels) -> Rc<Self> {
+        let tree_store = TreeStore::new(&[Type::U64, Type::String]);
+        let treeview = TreeView::new_with_model(&tree_store);
+        treeview.set_headers_visible(false);
+        treeview.set_enable_search(false);
+        let renderer = CellRendererText::new();
+        renderer.set_property_ellipsize(pango::EllipsizeMode::End);
+        let column = TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.add_attribute(&renderer, "markup", MARKUP_COLUMN as i32);
+        treeview.append_column(&column);
+
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        scrolled_window.add(&treeview);
+        let placeholder = Label::new(Some(gettext("No symbols").as_str()));
+        placeholder.get_style_context().add_class("dim-label");
+
+        let root = Stack::new();
+        root.add_named(&scrolled_window, "symbols");
+        root.add_named(&placeholder, "placeholder");
+        root.show_all();
+
+        let outline = Rc::new(Self {
+            root,
+            tree_store,
+            treeview,
+            edit_view: RefCell::new(Weak::new()),
+            timeout: RefCell::new(None),
+        });
+        // The document is only parsed while the panel is shown
+        outline
+            .root
+            .connect_map(enclose!((outline) move |_| outline.refresh()));
+        panels.register(PANEL_NAME, &gettext("Outline"), Slot::Right, &outline.root);
+
+        outline
+    }
+
+    /// Sets the function which is called with the EditView and line of the symbol the user has
+    /// activated
+    pub fn connect_activate<F: Fn(&Rc<RefCell<EditView>>, u64) + 'static>(
+        outline: &Rc<Self>,
+        f: F,
+    ) {
+        let weak = Rc::downgrade(outline);
+        outline.treeview.connect_row_activated(move |tv, path, _| {
+            let outline = match weak.upgrade() {
+                Some(outline) => outline,
+                None => return,
+            };
+            let edit_view = match outline.edit_view.borrow().upgrade() {
+                Some(edit_view) => edit_view,
+                None => return,
+            };
+            let line = tv
+                .get_model()
+                .and_then(|model| model.get_iter(path).map(|iter| (model, iter)))
+                .and_then(|(model, iter)| model.get_value(&iter, LINE_COLUMN as i32).get::<u64>());
+            if let Some(line) = line {
+                f(&edit_view, line);
+            }
+        });
+    }
+
+    /// Lists the symbols of `edit_view`, e.g. because the user has switched to its tab
+    pub fn set_edit_view(&self, edit_view: Option<&Rc<RefCell<EditView>>>) {
+        self.edit_view
+            .replace(edit_view.map_or_else(Weak::new, Rc::downgrade));
+        self.refresh();
+    }
+
+    /// Updates the outline once the document of the EditView with the id `view_id` has been left
+    /// alone for a moment, if it's the one listed
+    pub fn document_changed(outline: &Rc<Self>, view_id: &str) {
+        let shown = outline
+            .edit_view
+            .borrow()
+            .upgrade()
+            .map_or(false, |ev| ev.borrow().view_id == view_id);
+        if !shown || !outline.root.get_mapped() {
+            return;
+        }
+        if let Some(id) = outline.timeout.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+        let id = glib::timeout_add_local(
+            REFRESH_DELAY,
+            enclose!((outline) move || {
+                outline.timeout.replace(None);
+                outline.refresh();
+                source::Continue(false)
+            }),
+        );
+        outline.timeout.replace(Some(id));
+    }
+
+    fn refresh(&self) {
+        if !self.root.get_mapped() {
+            return;
+        }
+        let edit_view = match self.edit_view.borrow().upgrade() {
+            Some(edit_view) => edit_view,
+            None => {
+                show_symbols(&self.root, &self.treeview, &self.tree_store, &[]);
+                return;
+            }
+        };
+        let ev = edit_view.borrow();
+        let language = ev.language();
+        let tab_size = ev.tab_size() as usize;
+        let tree_store = self.tree_store.clone();
+        let treeview = self.treeview.clone();
+        let root = self.root.clone();
+        ev.with_text(move |text| {
+            let symbols = symbols(&language, text, tab_size);
+            debug!(
+                "{} {} {}",
+                gettext("Found"),
+                symbols.len(),
+                gettext("symbols")
+            );
+            show_symbols(&root, &treeview, &tree_store, &symbols);
+        });
+    }
+}
+
+/// Replaces the rows of `tree_store` with `symbols`, each nested in the symbol before it with a
+/// lower level, or shows the placeholder of the `root` stack if there are none
+fn show_symbols(root: &Stack, treeview: &TreeView, tree_store: &TreeStore, symbols: &[Symbol]) {
+    tree_store.clear();
+    let mut parents: Vec<(usize, TreeIter)> = Vec::new();
+    for symbol in symbols {
+        while parents
+            .last()
+            .map_or(false, |(level, _)| *level >= symbol.level)
+        {
+            parents.pop();
+        }
+        let iter = tree_store.insert_with_values(
+            parents.last().map(|(_, iter)| iter),
+            None,
+            &[LINE_COLUMN, MARKUP_COLUMN],
+            &[&symbol.line, &markup(symbol)],
+        );
+        parents.push((symbol.level, iter));
+    }
+    treeview.expand_all();
+    root.set_visible_child_name(if symbols.is_empty() {
+        "placeholder"
+    } else {
+        "symbols"
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(language: &str, text: &str) -> Vec<(u64, usize, String, String)> {
+        symbols(language, text, 4)
+            .into_iter()
+            .map(|s| (s.line, s.level, s.kind, s.name))
+            .collect()
+    }
+
+    #[test]
+    fn rust() {
+        let text = "pub struct Foo;\n\nimpl<T> Bar for Foo {\n    pub(crate) async fn new() -> Self {\n        if a {}\n    }\n}\n";
+        assert_eq!(
+            names("Rust", text),
+            vec![
+                (0, 0, "struct".to_string(), "Foo".to_string()),
+                (2, 0, "impl".to_string(), "Bar for Foo".to_string()),
+                (3, 4, "fn".to_string(), "new".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn c() {
+        let text = "struct point;\nstatic int\nmain(void);\nunsigned int add(int a, int b) {\n} else if (a) {\n";
+        assert_eq!(
+            names("C", text),
+            vec![(3, 0, "".to_string(), "add".to_string())]
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        let text = "# Title\n\n```sh\n# not a heading\n```\n## Section ##\n";
+        assert_eq!(
+            names("Markdown", text),
+            vec![
+                (0, 1, "".to_string(), "Title".to_string()),
+                (5, 2, "".to_string(), "Section".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_language() {
+        assert!(names("Plain Text", "fn main() {}").is_empty());
+    }
+}
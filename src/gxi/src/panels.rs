@@ -0,0 +1,293 @@
+//! Dockable panels at the left, right and bottom of the window. Features add their widgets as
+//! panels here instead of managing containers of their own. Panels can be shown and hidden via the
+//! `app.toggle_panel` action and which ones were open (and how big they were) is remembered in the
+//! session.
+
+use crate::session::{PanelState, Session};
+use gettextrs::gettext;
+use gtk::*;
+use log::{debug, warn};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Prefix of the names the state of the panels is saved under in the session
+const SESSION_PREFIX: &str = "panel-";
+
+/// Where panels can be docked
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Slot {
+    Left,
+    Right,
+    Bottom,
+}
+
+impl Slot {
+    /// Name the size of the slot is saved under in the session
+    fn session_name(self) -> &'static str {
+        match self {
+            Slot::Left => "left-panels",
+            Slot::Right => "right-panels",
+            Slot::Bottom => "bottom-panels",
+        }
+    }
+}
+
+/// Name the state of the panel `name` is saved under in the session
+fn session_name(name: &str) -> String {
+    format!("{}{}", SESSION_PREFIX, name)
+}
+
+/// The part of the window a slot takes up. It shows one of its panels at a time, with buttons to
+/// switch between them if there are several.
+struct SlotArea {
+    slot: Slot,
+    paned: Paned,
+    root: Box,
+    stack: Stack,
+    switcher: StackSwitcher,
+    /// Width (or height for the bottom slot) of the panels, 0 if they haven't got one yet
+    size: Cell<i32>,
+    /// Whether `size` has to be applied once `paned` has been allocated
+    size_pending: Cell<bool>,
+}
+
+impl SlotArea {
+    fn new(slot: Slot, paned: &Paned, state: PanelState) -> Rc<Self> {
+        let root = Box::new(Orientation::Vertical, 0);
+        let header = Box::new(Orientation::Horizontal, 6);
+        let switcher = StackSwitcher::new();
+        let stack = Stack::new();
+        let close_button =
+            Button::new_from_icon_name(Some("window-close-symbolic"), IconSize::Menu);
+        close_button.set_relief(ReliefStyle::None);
+        close_button.set_tooltip_text(Some(gettext("Close Panel").as_str()));
+
+        switcher.set_stack(Some(&stack));
+        header.pack_start(&switcher, true, false, 0);
+        header.pack_end(&close_button, false, false, 0);
+        root.pack_start(&header, false, false, 0);
+        root.pack_start(&stack, true, true, 0);
+        header.show_all();
+        stack.show();
+
+        // The panels keep their size when the window is resized
+        if slot == Slot::Left {
+            paned.pack1(&root, false, false);
+        } else {
+            paned.pack2(&root, false, false);
+        }
+
+        let size = match slot {
+            Slot::Bottom => state.height,
+            _ => state.width,
+        };
+        let area = Rc::new(Self {
+            slot,
+            paned: paned.clone(),
+            root,
+            stack,
+            switcher,
+            size: Cell::new(size),
+            size_pending: Cell::new(false),
+        });
+
+        close_button.connect_clicked(enclose!((area) move |_| {
+            if let Some(child) = area.stack.get_visible_child() {
+                child.hide();
+            }
+            area.update_visibility();
+        }));
+        paned.connect_size_allocate(enclose!((area) move |_, _| {
+            if area.size_pending.replace(false) {
+                area.apply_size();
+            }
+        }));
+
+        area
+    }
+
+    /// The size the panels currently have
+    fn current_size(&self) -> i32 {
+        let position = self.paned.get_position();
+        match self.slot {
+            Slot::Left => position,
+            Slot::Right => self.paned.get_allocated_width() - position,
+            Slot::Bottom => self.paned.get_allocated_height() - position,
+        }
+    }
+
+    fn apply_size(&self) {
+        let size = self.size.get();
+        if size <= 0 {
+            return;
+        }
+        let position = match self.slot {
+            Slot::Left => size,
+            Slot::Right => self.paned.get_allocated_width() - size,
+            Slot::Bottom => self.paned.get_allocated_height() - size,
+        };
+        self.paned.set_position(position);
+    }
+
+    /// Shows the slot if any of its panels are shown and hides it otherwise. Its size is restored
+    /// when it's shown again.
+    fn update_visibility(&self) {
+        let visible_panels = self
+            .stack
+            .get_children()
+            .iter()
+            .filter(|child| child.get_visible())
+            .count();
+        self.switcher.set_visible(visible_panels > 1);
+
+        if visible_panels == 0 && self.root.get_visible() {
+            self.size.set(self.current_size());
+            self.root.hide();
+        } else if visible_panels > 0 && !self.root.get_visible() {
+            self.root.show();
+            self.size_pending.set(true);
+            self.paned.queue_resize();
+        }
+    }
+
+    fn state(&self) -> PanelState {
+        let size = if self.root.get_visible() {
+            self.current_size()
+        } else {
+            self.size.get()
+        };
+        match self.slot {
+            Slot::Bottom => PanelState {
+                open: self.root.get_visible(),
+                width: 0,
+                height: size,
+            },
+            _ => PanelState {
+                open: self.root.get_visible(),
+                width: size,
+                height: 0,
+            },
+        }
+    }
+}
+
+pub struct Panels {
+    slots: HashMap<Slot, Rc<SlotArea>>,
+    /// Maps the names of the registered panels to their slot and widget
+    panels: RefCell<HashMap<String, (Slot, Widget)>>,
+    /// Panels which were open in the last session, they're shown once they're registered
+    restore: RefCell<Vec<String>>,
+}
+
+impl Panels {
+    /// Sets up the slots in the `left_paned`, `right_paned` and `bottom_paned` of `builder`,
+    /// with the sizes they had in `session`
+    pub fn new(builder: &Builder, session: &Session) -> Self {
+        let slots = [
+            (Slot::Left, "left_paned"),
+            (Slot::Right, "right_paned"),
+            (Slot::Bottom, "bottom_paned"),
+        ]
+        .iter()
+        .map(|(slot, paned_id)| {
+            let paned: Paned = builder.get_object(paned_id).unwrap();
+            let area = SlotArea::new(*slot, &paned, session.panel(slot.session_name()));
+            (*slot, area)
+        })
+        .collect();
+        let restore = session
+            .panels
+            .iter()
+            .filter(|(name, state)| name.starts_with(SESSION_PREFIX) && state.open)
+            .map(|(name, _)| name[SESSION_PREFIX.len()..].to_string())
+            .collect();
+
+        Self {
+            slots,
+            panels: Default::default(),
+            restore: RefCell::new(restore),
+        }
+    }
+
+    /// Adds `widget` as the panel `name` to `slot`. `title` is shown on the button switching to
+    /// it. The panel is hidden until it's shown via `show` or the `app.toggle_panel` action,
+    /// unless it was open in the last session.
+    pub fn register<P: IsA<Widget>>(&self, name: &str, title: &str, slot: Slot, widget: &P) {
+        debug!("{} '{}'", gettext("Registering panel"), name);
+        let area = &self.slots[&slot];
+        area.stack.add_titled(widget, name, title);
+        widget.hide();
+        self.panels
+            .borrow_mut()
+            .insert(name.to_string(), (slot, widget.clone().upcast()));
+
+        let restore = {
+            let mut restore = self.restore.borrow_mut();
+            let len = restore.len();
+            restore.retain(|n| n != name);
+            restore.len() != len
+        };
+        if restore {
+            self.show(name);
+        }
+    }
+
+    /// Shows the panel `name` and switches its slot to it
+    pub fn show(&self, name: &str) {
+        let (slot, widget) = match self.panels.borrow().get(name) {
+            Some(panel) => panel.clone(),
+            None => {
+                warn!("{} '{}'", gettext("Tried to show unknown panel"), name);
+                return;
+            }
+        };
+        let area = &self.slots[&slot];
+        widget.show();
+        area.stack.set_visible_child(&widget);
+        area.update_visibility();
+    }
+
+    pub fn hide(&self, name: &str) {
+        let (slot, widget) = match self.panels.borrow().get(name) {
+            Some(panel) => panel.clone(),
+            None => return,
+        };
+        widget.hide();
+        self.slots[&slot].update_visibility();
+    }
+
+    /// Whether the panel `name` is the one its slot shows
+    pub fn is_shown(&self, name: &str) -> bool {
+        self.panels
+            .borrow()
+            .get(name)
+            .map_or(false, |(slot, widget)| {
+                let area = &self.slots[slot];
+                area.root.get_visible() && area.stack.get_visible_child().as_ref() == Some(widget)
+            })
+    }
+
+    /// Hides the panel `name` if it's the one shown in its slot, shows it otherwise
+    pub fn toggle(&self, name: &str) {
+        if self.is_shown(name) {
+            self.hide(name);
+        } else {
+            self.show(name);
+        }
+    }
+
+    /// Saves which panels are shown and the sizes of the slots
+    pub fn save_state(&self, session: &mut Session) {
+        for area in self.slots.values() {
+            session.set_panel(area.slot.session_name(), area.state());
+        }
+        for (name, (_, widget)) in self.panels.borrow().iter() {
+            session.set_panel(&session_name(name), PanelState::shown(widget.get_visible()));
+        }
+        // Keep panels which were open in the last session, but haven't been registered this time
+        for name in self.restore.borrow().iter() {
+            session.set_panel(&session_name(name), PanelState::shown(true));
+        }
+    }
+}
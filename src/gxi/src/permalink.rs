@@ -0,0 +1,233 @@
+//! Builds links to lines of files on GitHub or GitLab, e.g. to point others at code in chats or
+//! issues. The links contain the hash of the current commit, so they keep pointing at the same
+//! lines after the file has changed. Asking git about the repository can take a while on slow
+//! disks, so it's done in the background.
+
+use crate::project;
+use gettextrs::gettext;
+use glib::{source, MainContext};
+use gxi_peer::Sandbox;
+use log::debug;
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Forge {
+    GitHub,
+    GitLab,
+}
+
+impl Forge {
+    fn detect(host: &str) -> Option<Self> {
+        if host.contains("github") {
+            Some(Forge::GitHub)
+        } else if host.contains("gitlab") {
+            Some(Forge::GitLab)
+        } else {
+            None
+        }
+    }
+
+    /// The anchor selecting the (one based) lines `first` to `last`
+    fn line_anchor(self, first: u64, last: u64) -> String {
+        match (self, first == last) {
+            (_, true) => format!("#L{}", first),
+            (Forge::GitHub, false) => format!("#L{}-L{}", first, last),
+            (Forge::GitLab, false) => format!("#L{}-{}", first, last),
+        }
+    }
+
+    fn blob_path(self) -> &'static str {
+        match self {
+            Forge::GitHub => "blob",
+            Forge::GitLab => "-/blob",
+        }
+    }
+}
+
+/// Turns the URL of a git remote into the URL of the repository's web page, e.g.
+/// `git@github.com:user/repo.git` into `https://github.com/user/repo`
+fn web_url(remote: &str) -> Option<String> {
+    let remote = remote.trim().trim_end_matches('/').trim_end_matches(".git");
+    let (host, path) = match remote.find("://") {
+        // https://host[:port]/path or ssh://[user@]host[:port]/path
+        Some(ix) => {
+            let scheme = &remote[..ix];
+            let rest = &remote[ix + 3..];
+            let slash = rest.find('/')?;
+            let authority = rest[..slash].rsplit('@').next()?;
+            let host = if scheme.starts_with("http") {
+                authority
+            } else {
+                // The port of the SSH server has nothing to do with the web server's
+                authority.split(':').next()?
+            };
+            (host, &rest[slash + 1..])
+        }
+        // scp-like syntax, [user@]host:path
+        None => {
+            let colon = remote.find(':')?;
+            (remote[..colon].rsplit('@').next()?, &remote[colon + 1..])
+        }
+    };
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some(format!("https://{}/{}", host, path.trim_start_matches('/')))
+}
+
+/// Percent-encodes the components of `path` and joins them with `/`
+fn url_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .map(|name| {
+            name.bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{:02X}", b),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// The link to the (zero based) lines `first` to `last` of the file at `path` (relative to the
+/// root of the repository) as of `commit`, in the repository `remote` points to. Returns None if
+/// the repository isn't on GitHub or GitLab.
+fn permalink(remote: &str, commit: &str, path: &Path, (first, last): (u64, u64)) -> Option<String> {
+    let base = web_url(remote)?;
+    let host = base["https://".len()..].split('/').next()?;
+    let forge = Forge::detect(host)?;
+    Some(format!(
+        "{}/{}/{}/{}{}",
+        base,
+        forge.blob_path(),
+        commit,
+        url_path(path),
+        forge.line_anchor(first + 1, last + 1)
+    ))
+}
+
+/// Runs git with `args` in `dir` and returns what it printed
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Sandbox::detect()
+        .host_command("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// The link to the (zero based) `lines` of `file` on GitHub or GitLab. The error explains why
+/// there's none.
+fn build(file: &Path, lines: (u64, u64)) -> Result<String, String> {
+    let root = project::project_root(file);
+    if !root.join(".git").exists() {
+        return Err(gettext("The file isn't part of a git repository"));
+    }
+    let path = file
+        .strip_prefix(&root)
+        .map_err(|_| gettext("The file isn't part of a git repository"))?;
+    let commit = git(&root, &["rev-parse", "HEAD"])?;
+    // The URL of the remote the current branch tracks, or of origin
+    let remote = git(&root, &["ls-remote", "--get-url"])?;
+    debug!(
+        "{} {:?} {} {} {}",
+        gettext("Building permalink for"),
+        path,
+        gettext("at"),
+        commit,
+        remote
+    );
+    permalink(&remote, &commit, path, lines)
+        .ok_or_else(|| gettext("The repository isn't on GitHub or GitLab"))
+}
+
+/// Builds the link to the (zero based) `lines` of `file` on GitHub or GitLab in the background
+/// and calls `callback` with it, or with the reason why there's none
+pub fn for_file<F>(file: &Path, lines: (u64, u64), callback: F)
+where
+    F: FnOnce(Result<String, String>) + 'static,
+{
+    let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+    let mut callback = Some(callback);
+    rx.attach(Some(&MainContext::default()), move |link| {
+        if let Some(callback) = callback.take() {
+            callback(link);
+        }
+        source::Continue(false)
+    });
+    let file: PathBuf = file.into();
+    thread::spawn(move || {
+        tx.send(build(&file, lines)).ok();
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn web_urls() {
+        assert_eq!(
+            web_url("git@github.com:Cogitri/gxi.git"),
+            Some("https://github.com/Cogitri/gxi".to_string())
+        );
+        assert_eq!(
+            web_url("https://gitlab.gnome.org/World/Rust/gtk-rs/"),
+            Some("https://gitlab.gnome.org/World/Rust/gtk-rs".to_string())
+        );
+        assert_eq!(
+            web_url("ssh://git@gitlab.com:2222/group/sub/project.git"),
+            Some("https://gitlab.com/group/sub/project".to_string())
+        );
+        assert_eq!(web_url("/srv/git/project.git"), None);
+    }
+
+    #[test]
+    fn permalinks() {
+        let commit = "0589e82";
+        assert_eq!(
+            permalink(
+                "git@github.com:Cogitri/gxi.git",
+                commit,
+                Path::new("src/gxi/src/main win.rs"),
+                (9, 19)
+            ),
+            Some(
+                "https://github.com/Cogitri/gxi/blob/0589e82/src/gxi/src/main%20win.rs#L10-L20"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            permalink(
+                "https://gitlab.com/group/project.git",
+                commit,
+                Path::new("README.md"),
+                (4, 4)
+            ),
+            Some("https://gitlab.com/group/project/-/blob/0589e82/README.md#L5".to_string())
+        );
+        assert_eq!(
+            permalink(
+                "https://git.sr.ht/~user/project",
+                commit,
+                Path::new("README.md"),
+                (4, 4)
+            ),
+            None
+        );
+    }
+}
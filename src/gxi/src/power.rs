@@ -0,0 +1,93 @@
+//! Whether the computer is running on battery and how full the battery is, as UPower tells us
+//! over D-Bus. Without UPower (e.g. in a sandbox without access to it) we assume that the
+//! computer is plugged in.
+
+use gettextrs::gettext;
+use gio::{BusType, Cancellable, DBusProxy, DBusProxyExt, DBusProxyFlags};
+use glib::{MainContext, ObjectExt};
+use log::{debug, warn};
+use std::sync::Mutex;
+
+const UPOWER_NAME: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+/// The battery of the computer as the desktop shows it, combined from all of its batteries
+const DISPLAY_DEVICE_PATH: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+const DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+/// Below this charge (in percent) the battery is considered low
+const LOW_BATTERY_PERCENTAGE: f64 = 20.0;
+
+/// A proxy for the object `path` of UPower, or None if we can't reach the system bus
+fn proxy(path: &str, interface: &str) -> Option<DBusProxy> {
+    DBusProxy::new_for_bus_sync(
+        BusType::System,
+        DBusProxyFlags::empty(),
+        None,
+        UPOWER_NAME,
+        path,
+        interface,
+        None::<&Cancellable>,
+    )
+    .map_err(|e| debug!("{}: {}", gettext("Couldn't connect to UPower"), e))
+    .ok()
+}
+
+/// UPower's view of the power supply. GIO caches the properties of the proxies and keeps them up
+/// to date with the signals UPower sends, so reading them doesn't mean a round trip. Without
+/// proxies (the default) the computer counts as plugged in.
+#[derive(Default)]
+pub struct Power {
+    upower: Option<DBusProxy>,
+    display_device: Option<DBusProxy>,
+}
+
+impl Power {
+    /// Connects to UPower. This waits for the system bus, so it shouldn't hold up showing the
+    /// window.
+    pub fn new() -> Self {
+        Self {
+            upower: proxy(UPOWER_PATH, UPOWER_NAME),
+            display_device: proxy(DISPLAY_DEVICE_PATH, DEVICE_INTERFACE),
+        }
+    }
+
+    /// Checks if the computer is running on battery, i.e. UPower's `OnBattery` property.
+    /// Computers without batteries are never on battery.
+    pub fn on_battery(&self) -> bool {
+        self.upower
+            .as_ref()
+            .and_then(|upower| upower.get_cached_property("OnBattery"))
+            .and_then(|on_battery| on_battery.get::<bool>())
+            .unwrap_or(false)
+    }
+
+    /// Checks if the computer is running on battery and the battery is almost empty
+    pub fn is_low_battery(&self) -> bool {
+        self.on_battery()
+            && self
+                .display_device
+                .as_ref()
+                .and_then(|device| device.get_cached_property("Percentage"))
+                .and_then(|percentage| percentage.get::<f64>())
+                .map_or(false, |p| p <= LOW_BATTERY_PERCENTAGE)
+    }
+
+    /// Calls `f` when the computer is plugged in or unplugged or the battery's charge changes
+    pub fn connect_changed<F: Fn() + 'static>(&self, f: F) {
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        rx.attach(None, move |()| {
+            f();
+            glib::Continue(true)
+        });
+        for proxy in self.upower.iter().chain(self.display_device.iter()) {
+            // GIO emits the signal on the main thread, `connect` just wants it to be Send
+            let tx = Mutex::new(tx.clone());
+            let res = proxy.connect("g-properties-changed", false, move |_| {
+                tx.lock().unwrap().send(()).ok();
+                None
+            });
+            if let Err(e) = res {
+                warn!("{}: {}", gettext("Failed to watch the power supply"), e);
+            }
+        }
+    }
+}
@@ -1,13 +1,18 @@
-use editview::MainState;
+use crate::settings_backup;
+use gdk::RGBA;
 use gettextrs::gettext;
 use gio::{SettingsBindFlags, SettingsExt};
 use gtk::*;
 use gxi_config_storage::{GSchema, GSchemaExt};
+use gxi_editview::spell_check;
+use gxi_editview::theme::{color_from_hex, hex_from_color};
+use gxi_editview::MainState;
 use gxi_peer::Core;
 use log::{debug, trace};
 use pango::*;
 use std::cell::RefCell;
 use std::rc::Rc;
+use syntect::highlighting::Color;
 
 pub struct PrefsWin {
     pub core: Core,
@@ -36,11 +41,86 @@ impl PrefsWin {
         let draw_trailing_spaces_checkbutton: ToggleButton = builder
             .get_object("draw_trailing_spaces_checkbutton")
             .unwrap();
+        let draw_spaces_checkbutton: ToggleButton =
+            builder.get_object("draw_spaces_checkbutton").unwrap();
+        let draw_tabs_checkbutton: ToggleButton =
+            builder.get_object("draw_tabs_checkbutton").unwrap();
+        let draw_newlines_checkbutton: ToggleButton =
+            builder.get_object("draw_newlines_checkbutton").unwrap();
+        let whitespace_mode_combo_box: ComboBoxText =
+            builder.get_object("whitespace_mode_combo_box").unwrap();
         let margin_checkbutton: ToggleButton = builder.get_object("margin_checkbutton").unwrap();
         let margin_spinbutton: SpinButton = builder.get_object("margin_spinbutton").unwrap();
         let highlight_line_checkbutton: ToggleButton =
             builder.get_object("highlight_line_checkbutton").unwrap();
+        let highlight_occurrences_checkbutton: ToggleButton = builder
+            .get_object("highlight_occurrences_checkbutton")
+            .unwrap();
+        let show_color_chips_checkbutton: ToggleButton =
+            builder.get_object("show_color_chips_checkbutton").unwrap();
+        let fullscreen_auto_hide_checkbutton: ToggleButton = builder
+            .get_object("fullscreen_auto_hide_checkbutton")
+            .unwrap();
+        let close_window_with_last_tab_checkbutton: ToggleButton = builder
+            .get_object("close_window_with_last_tab_checkbutton")
+            .unwrap();
         let tab_size_spinbutton: SpinButton = builder.get_object("tab_size_spinbutton").unwrap();
+        let highlight_on_battery_checkbutton: ToggleButton = builder
+            .get_object("highlight_on_battery_checkbutton")
+            .unwrap();
+        let idle_pause_minutes_spinbutton: SpinButton =
+            builder.get_object("idle_pause_minutes_spinbutton").unwrap();
+        let idle_pause_minutes_on_battery_spinbutton: SpinButton = builder
+            .get_object("idle_pause_minutes_on_battery_spinbutton")
+            .unwrap();
+        let highlight_size_limit_spinbutton: SpinButton = builder
+            .get_object("highlight_size_limit_spinbutton")
+            .unwrap();
+        let undo_group_timeout_spinbutton: SpinButton =
+            builder.get_object("undo_group_timeout_spinbutton").unwrap();
+        let page_scroll_percent_spinbutton: SpinButton = builder
+            .get_object("page_scroll_percent_spinbutton")
+            .unwrap();
+        let page_keeps_caret_checkbutton: ToggleButton =
+            builder.get_object("page_keeps_caret_checkbutton").unwrap();
+        let scroll_offset_spinbutton: SpinButton =
+            builder.get_object("scroll_offset_spinbutton").unwrap();
+        let line_cache_budget_spinbutton: SpinButton =
+            builder.get_object("line_cache_budget_spinbutton").unwrap();
+        let external_diff_tool_entry: Entry =
+            builder.get_object("external_diff_tool_entry").unwrap();
+        let date_time_format_entry: Entry = builder.get_object("date_time_format_entry").unwrap();
+        let lock_files_checkbutton: ToggleButton =
+            builder.get_object("lock_files_checkbutton").unwrap();
+        let highlight_long_lines_checkbutton: ToggleButton = builder
+            .get_object("highlight_long_lines_checkbutton")
+            .unwrap();
+        let continue_lists_checkbutton: ToggleButton =
+            builder.get_object("continue_lists_checkbutton").unwrap();
+        let smart_punctuation_checkbutton: ToggleButton =
+            builder.get_object("smart_punctuation_checkbutton").unwrap();
+        let spell_check_grid: Grid = builder.get_object("spell_check_grid").unwrap();
+        let spell_check_language_combo_box: ComboBoxText = builder
+            .get_object("spell_check_language_combo_box")
+            .unwrap();
+        let vim_mode_checkbutton: ToggleButton =
+            builder.get_object("vim_mode_checkbutton").unwrap();
+        let show_whats_new_checkbutton: ToggleButton =
+            builder.get_object("show_whats_new_checkbutton").unwrap();
+        let restore_settings_button: Button =
+            builder.get_object("restore_settings_button").unwrap();
+        let speech_rate_spinbutton: SpinButton =
+            builder.get_object("speech_rate_spinbutton").unwrap();
+        let speech_voice_entry: Entry = builder.get_object("speech_voice_entry").unwrap();
+        let custom_caret_color_checkbutton: ToggleButton = builder
+            .get_object("custom_caret_color_checkbutton")
+            .unwrap();
+        let caret_color_button: ColorButton = builder.get_object("caret_color_button").unwrap();
+        let custom_selection_color_checkbutton: ToggleButton = builder
+            .get_object("custom_selection_color_checkbutton")
+            .unwrap();
+        let selection_color_button: ColorButton =
+            builder.get_object("selection_color_button").unwrap();
 
         let font_desc: &String = &gschema.get_key("font");
         font_chooser_widget.set_font_desc(&FontDescription::from_string(font_desc));
@@ -104,6 +184,34 @@ impl PrefsWin {
             SettingsBindFlags::DEFAULT,
         );
 
+        gschema.settings.bind(
+            "draw-spaces",
+            &draw_spaces_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "draw-tabs",
+            &draw_tabs_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "draw-newlines",
+            &draw_newlines_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "draw-whitespace-mode",
+            &whitespace_mode_combo_box,
+            "active-id",
+            SettingsBindFlags::DEFAULT,
+        );
+
         gschema.settings.bind(
             "draw-right-margin",
             &margin_checkbutton,
@@ -118,6 +226,34 @@ impl PrefsWin {
             SettingsBindFlags::DEFAULT,
         );
 
+        gschema.settings.bind(
+            "highlight-occurrences",
+            &highlight_occurrences_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "show-color-chips",
+            &show_color_chips_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "fullscreen-auto-hide-header-bar",
+            &fullscreen_auto_hide_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "close-window-with-last-tab",
+            &close_window_with_last_tab_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
         gschema.settings.bind(
             "column-right-margin",
             &margin_spinbutton,
@@ -132,6 +268,189 @@ impl PrefsWin {
             SettingsBindFlags::DEFAULT,
         );
 
+        gschema.settings.bind(
+            "highlight-long-lines",
+            &highlight_long_lines_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "highlight-on-battery",
+            &highlight_on_battery_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "idle-pause-minutes",
+            &idle_pause_minutes_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "idle-pause-minutes-on-battery",
+            &idle_pause_minutes_on_battery_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "highlight-size-limit",
+            &highlight_size_limit_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "undo-group-timeout",
+            &undo_group_timeout_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "page-scroll-percent",
+            &page_scroll_percent_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "page-keeps-caret",
+            &page_keeps_caret_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "scroll-offset",
+            &scroll_offset_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "line-cache-budget",
+            &line_cache_budget_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "external-diff-tool",
+            &external_diff_tool_entry,
+            "text",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "date-time-format",
+            &date_time_format_entry,
+            "text",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "lock-files",
+            &lock_files_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "continue-lists",
+            &continue_lists_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "smart-punctuation",
+            &smart_punctuation_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        // Without dictionaries (or Enchant) there's nothing to choose from
+        let spell_check_languages = spell_check::languages();
+        if spell_check_languages.is_empty() {
+            spell_check_grid.set_no_show_all(true);
+            spell_check_grid.hide();
+        }
+        for language in &spell_check_languages {
+            spell_check_language_combo_box.append(Some(language.as_str()), language);
+        }
+        gschema.settings.bind(
+            "spell-check-language",
+            &spell_check_language_combo_box,
+            "active-id",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "vim-mode",
+            &vim_mode_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "show-whats-new",
+            &show_whats_new_checkbutton,
+            "active",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        match settings_backup::latest() {
+            Some((path, version)) => {
+                restore_settings_button.set_label(&format!(
+                    "{} {}…",
+                    gettext("Restore the Settings of gxi"),
+                    version
+                ));
+                restore_settings_button.connect_clicked(enclose!((gschema, window) move |_| {
+                    if confirm_restore(&window, &version) {
+                        settings_backup::restore(&gschema, &path);
+                    }
+                }));
+            }
+            None => {
+                restore_settings_button.set_sensitive(false);
+                restore_settings_button.set_tooltip_text(Some(
+                    gettext("There's no backup of the settings from before an update").as_str(),
+                ));
+            }
+        }
+
+        gschema.settings.bind(
+            "speech-rate",
+            &speech_rate_spinbutton,
+            "value",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        gschema.settings.bind(
+            "speech-voice",
+            &speech_voice_entry,
+            "text",
+            SettingsBindFlags::DEFAULT,
+        );
+
+        bind_color(
+            gschema,
+            "caret-color",
+            &custom_caret_color_checkbutton,
+            &caret_color_button,
+        );
+        bind_color(
+            gschema,
+            "selection-color",
+            &custom_selection_color_checkbutton,
+            &selection_color_button,
+        );
+
         let prefs_win = Self {
             core: core.clone(),
             window: window.clone(),
@@ -143,3 +462,72 @@ impl PrefsWin {
         prefs_win
     }
 }
+
+/// Asks whether the settings should be replaced by the ones backed up before updating from gxi
+/// `version`
+fn confirm_restore(parent: &Window, version: &str) -> bool {
+    let dialog = MessageDialog::new(
+        Some(parent),
+        DialogFlags::all(),
+        MessageType::Question,
+        ButtonsType::None,
+        &format!("{} {}?", gettext("Restore the settings of gxi"), version),
+    );
+    dialog.set_property_secondary_text(Some(
+        gettext(
+            "Settings you have changed since the update are set back to what they were before it.",
+        )
+        .as_str(),
+    ));
+    dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+    dialog
+        .add_button(&gettext("Restore"), ResponseType::Accept)
+        .get_style_context()
+        .add_class("destructive-action");
+    dialog.set_default_response(ResponseType::Cancel);
+    let res = dialog.run();
+    dialog.destroy();
+    res == ResponseType::Accept
+}
+
+/// Binds a color key, which is empty unless the user wants to override the color of the theme, to
+/// a checkbutton enabling the override and a button choosing the color.
+fn bind_color(
+    gschema: &GSchema,
+    key: &'static str,
+    checkbutton: &ToggleButton,
+    button: &ColorButton,
+) {
+    let value: String = gschema.get_key(key);
+    if let Some(color) = color_from_hex(&value) {
+        button.set_rgba(&RGBA {
+            red: f64::from(color.r) / 255.0,
+            green: f64::from(color.g) / 255.0,
+            blue: f64::from(color.b) / 255.0,
+            alpha: 1.0,
+        });
+        checkbutton.set_active(true);
+    }
+    button.set_sensitive(checkbutton.get_active());
+
+    let hex = |button: &ColorButton| {
+        let rgba = button.get_rgba();
+        let component = |c: f64| (c * 255.0).round() as u8;
+        hex_from_color(Color {
+            r: component(rgba.red),
+            g: component(rgba.green),
+            b: component(rgba.blue),
+            a: 0xff,
+        })
+    };
+
+    checkbutton.connect_toggled(enclose!((gschema, button) move |checkbutton| {
+        let active = checkbutton.get_active();
+        button.set_sensitive(active);
+        let value = if active { hex(&button) } else { String::new() };
+        gschema.set_key(key, value).unwrap();
+    }));
+    button.connect_color_set(enclose!((gschema) move |button| {
+        gschema.set_key(key, hex(button)).unwrap();
+    }));
+}
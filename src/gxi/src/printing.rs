@@ -0,0 +1,303 @@
+//! Prints documents with their syntax highlighting through GTK's print dialog, or writes them to
+//! PDF files. Each page has a header with the name of the file and the page number.
+
+use gettextrs::gettext;
+use gtk::*;
+use gxi_editview::html_export::StyledLine;
+use gxi_editview::theme::{color_from_u32, PangoColor};
+use gxi_editview::EditView;
+use pango::{Attribute, FontDescription, TabAlign, TabArray};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Size of the printed text in points. The size the text is shown with on screen depends on the
+/// screen and the zoom, so it isn't used.
+const FONT_SIZE: u32 = 10;
+/// Space (in points) between the header and the text
+const HEADER_GAP: f64 = 12.0;
+/// Size of the pages of PDF files in points (A4)
+const PDF_PAGE_SIZE: (f64, f64) = (595.0, 842.0);
+/// Margin around the text of PDF files in points. When printing the margins of the page setup are
+/// used.
+const PDF_MARGIN: f64 = 54.0;
+
+/// What's printed
+pub struct PrintJob {
+    /// Shown in the header of each page, e.g. the file name
+    pub title: String,
+    pub lines: Vec<StyledLine>,
+    pub font_family: String,
+    pub tab_size: u32,
+}
+
+impl PrintJob {
+    /// The job printing the document of `edit_view`, if xi-editor has sent us all of its lines
+    /// (see `EditView::with_text`)
+    pub fn from_edit_view(edit_view: &EditView) -> Option<Self> {
+        Some(Self {
+            title: edit_view.display_name(),
+            lines: edit_view.styled_lines()?,
+            font_family: edit_view.font_family(),
+            tab_size: edit_view.tab_size(),
+        })
+    }
+
+    fn font_desc(&self) -> FontDescription {
+        let mut font_desc = FontDescription::from_string(&self.font_family);
+        font_desc.set_size(FONT_SIZE as i32 * pango::SCALE);
+        font_desc
+    }
+}
+
+/// Splits lines with the `heights` into pages which are `page_height` high, returning the range
+/// of lines on each page. Lines higher than a page get a page of their own.
+fn paginate(heights: &[f64], page_height: f64) -> Vec<Range<usize>> {
+    let mut pages = Vec::new();
+    let mut start = 0;
+    let mut y = 0.0;
+    for (i, height) in heights.iter().enumerate() {
+        if y + height > page_height && i > start {
+            pages.push(start..i);
+            start = i;
+            y = 0.0;
+        }
+        y += height;
+    }
+    if start < heights.len() || pages.is_empty() {
+        pages.push(start..heights.len());
+    }
+    pages
+}
+
+fn layout_height(layout: &pango::Layout) -> f64 {
+    f64::from(layout.get_extents().1.height) / f64::from(pango::SCALE)
+}
+
+/// The layout for `line`, wrapped at `width`. Backgrounds of the theme are left out, they'd
+/// only waste ink.
+fn line_layout(
+    layout: pango::Layout,
+    font_desc: &FontDescription,
+    tabs: &TabArray,
+    width: f64,
+    line: &StyledLine,
+) -> pango::Layout {
+    layout.set_font_description(Some(font_desc));
+    layout.set_tabs(Some(tabs));
+    layout.set_width((width * f64::from(pango::SCALE)) as i32);
+    layout.set_wrap(pango::WrapMode::WordChar);
+    layout.set_text(&line.text);
+
+    let attr_list = pango::AttrList::new();
+    for (range, style) in &line.styles {
+        let mut attrs = Vec::new();
+        if let Some(foreground) = style.fg_color {
+            let c = PangoColor::from(color_from_u32(foreground));
+            attrs.push(Attribute::new_foreground(c.r, c.g, c.b).unwrap());
+        }
+        if let Some(weight) = style.weight {
+            attrs.push(Attribute::new_weight(pango::Weight::__Unknown(weight as i32)).unwrap());
+        }
+        if style.italic == Some(true) {
+            attrs.push(Attribute::new_style(pango::Style::Italic).unwrap());
+        }
+        if style.underline == Some(true) {
+            attrs.push(Attribute::new_underline(pango::Underline::Single).unwrap());
+        }
+        for mut attr in attrs {
+            attr.set_start_index(range.start as u32);
+            attr.set_end_index(range.end as u32);
+            attr_list.insert(attr);
+        }
+    }
+    layout.set_attributes(Some(&attr_list));
+    layout
+}
+
+/// The text of a job laid out on pages
+struct Pages {
+    layouts: Vec<pango::Layout>,
+    /// The range of layouts on each page
+    pages: Vec<Range<usize>>,
+    /// Height of the header, including the gap below it
+    header_height: f64,
+    width: f64,
+}
+
+impl Pages {
+    /// Lays out the text of `job` on pages of the size `width` x `height`, creating the layouts
+    /// with `new_layout`
+    fn new(
+        job: &PrintJob,
+        new_layout: &dyn Fn() -> pango::Layout,
+        width: f64,
+        height: f64,
+    ) -> Self {
+        let font_desc = job.font_desc();
+        let header_layout = new_layout();
+        header_layout.set_font_description(Some(&font_desc));
+        header_layout.set_text(&job.title);
+        let header_height = layout_height(&header_layout) + HEADER_GAP;
+
+        let space_layout = new_layout();
+        space_layout.set_font_description(Some(&font_desc));
+        space_layout.set_text(" ");
+        // In Pango units, a space is rarely a whole number of points wide
+        let mut tabs = TabArray::new(1, false);
+        tabs.set_tab(
+            0,
+            TabAlign::Left,
+            space_layout.get_extents().1.width * job.tab_size.max(1) as i32,
+        );
+
+        let layouts: Vec<pango::Layout> = job
+            .lines
+            .iter()
+            .map(|line| line_layout(new_layout(), &font_desc, &tabs, width, line))
+            .collect();
+        let heights: Vec<f64> = layouts.iter().map(layout_height).collect();
+        let pages = paginate(&heights, height - header_height);
+        Self {
+            layouts,
+            pages,
+            header_height,
+            width,
+        }
+    }
+
+    /// Draws page `page` (zero based) with its header to `cr`
+    fn draw(
+        &self,
+        job: &PrintJob,
+        cr: &cairo::Context,
+        new_layout: &dyn Fn() -> pango::Layout,
+        page: usize,
+    ) {
+        let range = match self.pages.get(page) {
+            Some(range) => range.clone(),
+            None => return,
+        };
+        let font_desc = job.font_desc();
+
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        let title_layout = new_layout();
+        let mut bold = font_desc.clone();
+        bold.set_weight(pango::Weight::Bold);
+        title_layout.set_font_description(Some(&bold));
+        title_layout.set_text(&job.title);
+        title_layout.set_ellipsize(pango::EllipsizeMode::Middle);
+        title_layout.set_width((self.width * 0.7 * f64::from(pango::SCALE)) as i32);
+        cr.move_to(0.0, 0.0);
+        pangocairo::functions::show_layout(cr, &title_layout);
+
+        let page_layout = new_layout();
+        page_layout.set_font_description(Some(&font_desc));
+        page_layout.set_text(&format!(
+            "{} {} {} {}",
+            gettext("Page"),
+            page + 1,
+            gettext("of"),
+            self.pages.len()
+        ));
+        let page_width = f64::from(page_layout.get_extents().1.width) / f64::from(pango::SCALE);
+        cr.move_to(self.width - page_width, 0.0);
+        pangocairo::functions::show_layout(cr, &page_layout);
+
+        cr.set_line_width(0.5);
+        cr.move_to(0.0, self.header_height - HEADER_GAP / 2.0);
+        cr.line_to(self.width, self.header_height - HEADER_GAP / 2.0);
+        cr.stroke();
+
+        let mut y = self.header_height;
+        for layout in &self.layouts[range] {
+            cr.move_to(0.0, y);
+            pangocairo::functions::show_layout(cr, layout);
+            y += layout_height(layout);
+        }
+    }
+}
+
+/// Shows the print dialog (or the preview, if `preview` is true) for `job`. The error is a
+/// message for the user.
+pub fn print(parent: &ApplicationWindow, job: PrintJob, preview: bool) -> Result<(), String> {
+    let operation = PrintOperation::new();
+    operation.set_job_name(&job.title);
+    operation.set_embed_page_setup(true);
+
+    let job = Rc::new(job);
+    // Set up once the paper size is known
+    let pages: Rc<RefCell<Option<Pages>>> = Default::default();
+
+    operation.connect_begin_print(enclose!((job, pages) move |operation, context| {
+        let new_layout = || context.create_pango_layout().unwrap();
+        let new_pages = Pages::new(&job, &new_layout, context.get_width(), context.get_height());
+        operation.set_n_pages(new_pages.pages.len() as i32);
+        pages.replace(Some(new_pages));
+    }));
+
+    operation.connect_draw_page(enclose!((job, pages) move |_, context, page| {
+        let cr = match context.get_cairo_context() {
+            Some(cr) => cr,
+            None => return,
+        };
+        if let Some(pages) = pages.borrow().as_ref() {
+            let new_layout = || context.create_pango_layout().unwrap();
+            pages.draw(&job, &cr, &new_layout, page as usize);
+        }
+    }));
+
+    let action = if preview {
+        PrintOperationAction::Preview
+    } else {
+        PrintOperationAction::PrintDialog
+    };
+    operation
+        .run(action, Some(parent))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Writes `job` to the PDF file at `path`. The error is a message for the user.
+pub fn write_pdf(path: &Path, job: &PrintJob) -> Result<(), String> {
+    let (page_width, page_height) = PDF_PAGE_SIZE;
+    let surface = cairo::PdfSurface::new(page_width, page_height, path)
+        .map_err(|status| format!("{:?}", status))?;
+    let cr = cairo::Context::new(&surface);
+    let new_layout = || pangocairo::functions::create_layout(&cr).unwrap();
+    let pages = Pages::new(
+        job,
+        &new_layout,
+        page_width - 2.0 * PDF_MARGIN,
+        page_height - 2.0 * PDF_MARGIN,
+    );
+    for page in 0..pages.pages.len() {
+        cr.save();
+        cr.translate(PDF_MARGIN, PDF_MARGIN);
+        pages.draw(job, &cr, &new_layout, page);
+        cr.restore();
+        cr.show_page();
+    }
+    surface.finish();
+    match cr.status() {
+        cairo::Status::Success => Ok(()),
+        status => Err(format!("{:?}", status)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pagination() {
+        assert_eq!(paginate(&[], 100.0), vec![0..0]);
+        assert_eq!(paginate(&[40.0, 40.0, 40.0], 100.0), vec![0..2, 2..3]);
+        // A line higher than the page gets a page of its own
+        assert_eq!(
+            paginate(&[40.0, 150.0, 40.0], 100.0),
+            vec![0..1, 1..2, 2..3]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Profiles are separate sets of settings, sessions and other state, e.g. a minimal "writing"
+//! profile next to a full-featured "coding" one. They're chosen via `gxi --profile NAME` (or the
+//! `GXI_PROFILE` environment variable) and can be used at the same time, since the profile is
+//! part of the application id. Without a profile gxi uses dconf and the usual directories.
+
+use crate::gxi_dirs;
+use crate::window_name::{self, APP_ID};
+use gettextrs::gettext;
+use gxi_config_storage::GSchema;
+use log::warn;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable holding the name of the profile. It's inherited by the processes we
+/// spawn, so a gxi started by them uses the same profile.
+const PROFILE_ENV: &str = "GXI_PROFILE";
+/// Subdirectory of gxi's directories where the profiles keep their files
+const PROFILES_DIR_NAME: &str = "profiles";
+const SETTINGS_FILE_NAME: &str = "settings.ini";
+
+thread_local! {
+    /// All GSchemas of a profile have to share one keyfile backend, otherwise they don't notice
+    /// each other's changes
+    static GSCHEMA: RefCell<Option<GSchema>> = RefCell::new(None);
+}
+
+/// Makes `name` the profile of this process. Has to be called before anything reads settings.
+pub fn set(name: &str) {
+    env::set_var(PROFILE_ENV, name);
+}
+
+/// Name of the profile in use, if any
+pub fn current() -> Option<String> {
+    env::var(PROFILE_ENV).ok().filter(|name| !name.is_empty())
+}
+
+/// The directory of the profile in the directory `dir`, e.g. `~/.local/state/gxi/profiles/NAME`
+pub fn dir(dir: PathBuf) -> PathBuf {
+    match current() {
+        Some(name) => dir
+            .join(PROFILES_DIR_NAME)
+            .join(window_name::sanitize(&name)),
+        None => dir,
+    }
+}
+
+/// The GSchema holding the settings of the profile in use
+pub fn gschema() -> GSchema {
+    GSCHEMA.with(|gschema| {
+        gschema
+            .borrow_mut()
+            .get_or_insert_with(load_gschema)
+            .clone()
+    })
+}
+
+/// Profiles store their settings in a keyfile in their config directory, since dconf only knows
+/// one set of keys per schema
+fn load_gschema() -> GSchema {
+    let profile_dir = match current().and_then(|_| gxi_dirs::config_dir().map(dir)) {
+        Some(profile_dir) => profile_dir,
+        None => return GSchema::new(APP_ID),
+    };
+    if let Err(e) = fs::create_dir_all(&profile_dir) {
+        warn!(
+            "{} {:?}: {}",
+            gettext("Failed to create the directory"),
+            profile_dir,
+            e
+        );
+    }
+    GSchema::new_with_keyfile(APP_ID, &profile_dir.join(SETTINGS_FILE_NAME))
+}
@@ -0,0 +1,214 @@
+//! Shows that gxi is busy with something which takes a while, e.g. searching in files or opening
+//! many files at once, with a spinner in the header bar. The popover of the spinner lists what's
+//! running and lets the user cancel it.
+
+use gettextrs::gettext;
+use gtk::*;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A running operation
+struct Running {
+    id: u64,
+    label: String,
+    /// Set once the user has cancelled the operation. It's still shown until it has stopped.
+    cancelled: Arc<AtomicBool>,
+}
+
+/// The running operations, without any widgets
+#[derive(Default)]
+struct Operations {
+    next_id: u64,
+    running: Vec<Running>,
+}
+
+impl Operations {
+    fn start(&mut self, label: &str) -> (u64, Arc<AtomicBool>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.running.push(Running {
+            id,
+            label: label.to_string(),
+            cancelled: cancelled.clone(),
+        });
+        (id, cancelled)
+    }
+
+    fn set_label(&mut self, id: u64, label: &str) {
+        if let Some(op) = self.running.iter_mut().find(|op| op.id == id) {
+            op.label = label.to_string();
+        }
+    }
+
+    fn cancel(&self, id: u64) {
+        if let Some(op) = self.running.iter().find(|op| op.id == id) {
+            op.cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn finish(&mut self, id: u64) {
+        self.running.retain(|op| op.id != id);
+    }
+
+    /// The tooltip of the spinner, None if nothing is running
+    fn summary(&self) -> Option<String> {
+        match self.running.as_slice() {
+            [] => None,
+            [op] => Some(op.label.clone()),
+            ops => Some(format!("{} {}", ops.len(), gettext("operations running"))),
+        }
+    }
+}
+
+/// A running operation shown by `Progress`. It's shown until this is dropped.
+pub struct Operation {
+    progress: Weak<Progress>,
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Operation {
+    /// Whether the user has cancelled the operation, which should stop then
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// The flag `is_cancelled` checks, for operations running in other threads
+    pub fn cancelled_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// Changes what's shown for the operation, e.g. to show how far it has got
+    pub fn set_label(&self, label: &str) {
+        if let Some(progress) = self.progress.upgrade() {
+            progress.operations.borrow_mut().set_label(self.id, label);
+            Progress::update(&progress);
+        }
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if let Some(progress) = self.progress.upgrade() {
+            progress.operations.borrow_mut().finish(self.id);
+            Progress::update(&progress);
+        }
+    }
+}
+
+pub struct Progress {
+    button: MenuButton,
+    spinner: Spinner,
+    /// Lists the running operations in the popover
+    list_box: Box,
+    operations: RefCell<Operations>,
+}
+
+impl Progress {
+    /// Adds the spinner to `header_bar`, it's only shown while something is running
+    pub fn new(header_bar: &HeaderBar) -> Rc<Self> {
+        let spinner = Spinner::new();
+        let button = MenuButton::new();
+        button.set_relief(ReliefStyle::None);
+        button.add(&spinner);
+        spinner.show();
+
+        let list_box = Box::new(Orientation::Vertical, 6);
+        list_box.set_border_width(6);
+        let popover = Popover::new(Some(&button));
+        popover.add(&list_box);
+        button.set_popover(Some(&popover));
+        header_bar.pack_end(&button);
+
+        Rc::new(Self {
+            button,
+            spinner,
+            list_box,
+            operations: Default::default(),
+        })
+    }
+
+    /// Shows `label` until the returned `Operation` is dropped
+    pub fn start(progress: &Rc<Self>, label: &str) -> Operation {
+        let (id, cancelled) = progress.operations.borrow_mut().start(label);
+        Self::update(progress);
+        Operation {
+            progress: Rc::downgrade(progress),
+            id,
+            cancelled,
+        }
+    }
+
+    /// Shows the running operations, or hides the spinner if there are none
+    fn update(progress: &Rc<Self>) {
+        for child in progress.list_box.get_children() {
+            progress.list_box.remove(&child);
+        }
+
+        let operations = progress.operations.borrow();
+        for op in &operations.running {
+            let label = Label::new(Some(op.label.as_str()));
+            label.set_halign(Align::Start);
+            label.set_hexpand(true);
+            let cancel_button = Button::new_with_label(&gettext("Cancel"));
+            let cancelled = op.cancelled.load(Ordering::SeqCst);
+            cancel_button.set_sensitive(!cancelled);
+            let id = op.id;
+            cancel_button.connect_clicked(enclose!((progress) move |_| {
+                progress.operations.borrow().cancel(id);
+                Self::update(&progress);
+            }));
+            let hbox = Box::new(Orientation::Horizontal, 12);
+            hbox.pack_start(&label, true, true, 0);
+            hbox.pack_start(&cancel_button, false, false, 0);
+            hbox.show_all();
+            progress.list_box.pack_start(&hbox, false, false, 0);
+        }
+
+        match operations.summary() {
+            Some(summary) => {
+                progress.button.set_tooltip_text(Some(summary.as_str()));
+                progress.spinner.start();
+                progress.button.show();
+            }
+            None => {
+                progress.spinner.stop();
+                progress.button.hide();
+                if let Some(popover) = progress.button.get_popover() {
+                    #[cfg(feature = "gtk_v3_22")]
+                    popover.popdown();
+                    #[cfg(not(feature = "gtk_v3_22"))]
+                    popover.hide();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn operations() {
+        let mut ops = Operations::default();
+        assert_eq!(ops.summary(), None);
+        let (a, a_cancelled) = ops.start("Searching in files");
+        assert_eq!(ops.summary(), Some("Searching in files".to_string()));
+        let (b, b_cancelled) = ops.start("Opening files");
+        assert_eq!(ops.summary(), Some("2 operations running".to_string()));
+        ops.cancel(b);
+        assert!(b_cancelled.load(Ordering::SeqCst));
+        assert!(!a_cancelled.load(Ordering::SeqCst));
+        // Cancelled operations are shown until they've stopped
+        assert_eq!(ops.running.len(), 2);
+        ops.finish(b);
+        ops.set_label(a, "Searching in files (42)");
+        assert_eq!(ops.summary(), Some("Searching in files (42)".to_string()));
+        ops.finish(a);
+        assert_eq!(ops.summary(), None);
+    }
+}
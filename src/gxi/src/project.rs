@@ -0,0 +1,28 @@
+use std::path::{Path, PathBuf};
+
+/// Determines the project a file belongs to. This is the closest parent directory containing a
+/// `.git` directory, or the directory of the file itself if it isn't part of a git repository.
+pub fn project_root(file: &Path) -> PathBuf {
+    let dir = if file.is_dir() {
+        file
+    } else {
+        file.parent().unwrap_or(file)
+    };
+
+    for ancestor in dir.ancestors() {
+        if ancestor.join(".git").exists() {
+            return ancestor.to_path_buf();
+        }
+    }
+
+    dir.to_path_buf()
+}
+
+/// The project root for an (optional) file name, falling back to the current working directory
+/// for unsaved documents.
+pub fn project_root_for(file_name: Option<&str>) -> PathBuf {
+    match file_name {
+        Some(f) => project_root(Path::new(f)),
+        None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+    }
+}
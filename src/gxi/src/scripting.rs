@@ -0,0 +1,312 @@
+//! Runs Lua scripts from the `scripts` directory in gxi's config dir, so users can add commands
+//! of their own. Scripts use the `gxi` table to talk to the editor:
+//!
+//! * `gxi.register_command(name, label, function)` adds a command, which can be run from the menu
+//! * `gxi.bind(name, accelerator)` binds a command to a key, e.g. `"<Primary><Alt>u"`. Some of
+//!   gxi's own commands can be bound too, e.g. `gxi.bind("insert_date_time", "<Primary>semicolon")`.
+//! * `gxi.selection()` returns the text which was selected when the command was run
+//! * `gxi.insert(text)` replaces the selection with `text`
+//! * `gxi.open(path)` opens a file
+//! * `gxi.log(message)` writes a message to gxi's log
+//!
+//! The functions only queue up what should be done, it's done once the command has finished.
+
+use crate::gxi_dirs;
+use gettextrs::gettext;
+use log::{debug, info, warn};
+use rlua::{Function, Lua};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const SCRIPTS_DIR_NAME: &str = "scripts";
+
+/// gxi's own actions which don't have a key by default, so scripts may bind them
+const BINDABLE_ACTIONS: &[&str] = &["insert_date_time", "insert_uuid", "insert_file_path"];
+
+/// Something a command wants the editor to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptAction {
+    Open(PathBuf),
+    Insert(String),
+}
+
+/// A command a script has registered
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptCommand {
+    pub name: String,
+    pub label: String,
+    pub accel: Option<String>,
+}
+
+/// State the functions in the `gxi` table share with us
+#[derive(Default)]
+struct Shared {
+    commands: Vec<ScriptCommand>,
+    /// Accelerators bound to gxi's own actions
+    action_accels: Vec<(String, String)>,
+    actions: Vec<ScriptAction>,
+    selection: String,
+}
+
+pub struct Scripts {
+    lua: Lua,
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// Name of the Lua registry entry the function of the command `name` is kept in
+fn registry_name(name: &str) -> String {
+    format!("gxi-command-{}", name)
+}
+
+/// Command names end up in action names, which may only contain alphanumeric chars, '-' and '.'
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+impl Scripts {
+    /// Creates the Lua state and runs the scripts in the scripts directory. Scripts which fail
+    /// are skipped, the error messages are returned along with the `Scripts`.
+    pub fn load() -> (Self, Vec<String>) {
+        let scripts = Self::new();
+        if let Err(e) = scripts.register_api() {
+            return (scripts, vec![e.to_string()]);
+        }
+
+        let mut errors = Vec::new();
+        for path in Self::script_paths() {
+            debug!("{} {:?}", gettext("Loading script"), path);
+            let res = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| scripts.exec(&path.to_string_lossy(), &source));
+            if let Err(e) = res {
+                errors.push(format!("{:?}: {}", path, e));
+            }
+        }
+
+        (scripts, errors)
+    }
+
+    /// A Lua state without the `gxi` table, see `register_api`
+    fn new() -> Self {
+        Self {
+            lua: Lua::new(),
+            shared: Default::default(),
+        }
+    }
+
+    /// Runs the script `source`, `name` is what it's called in error messages
+    fn exec(&self, name: &str, source: &str) -> Result<(), String> {
+        self.lua
+            .context(|ctx| ctx.load(source).set_name(name)?.exec())
+            .map_err(|e| e.to_string())
+    }
+
+    /// The `.lua` files in the scripts directory, sorted by name so they're always run in the
+    /// same order
+    fn script_paths() -> Vec<PathBuf> {
+        let dir = match gxi_dirs::config_dir() {
+            Some(dir) => dir.join(SCRIPTS_DIR_NAME),
+            None => return Vec::new(),
+        };
+        let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().map_or(false, |ext| ext == "lua"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.sort();
+        paths
+    }
+
+    fn register_api(&self) -> rlua::Result<()> {
+        self.lua.context(|ctx| {
+            let gxi = ctx.create_table()?;
+
+            let shared = self.shared.clone();
+            gxi.set(
+                "register_command",
+                ctx.create_function(
+                    move |ctx, (name, label, function): (String, String, Function)| {
+                        if !is_valid_name(&name) {
+                            return Err(rlua::Error::RuntimeError(format!(
+                                "{} '{}'",
+                                gettext("Invalid command name"),
+                                name
+                            )));
+                        }
+                        ctx.set_named_registry_value(&registry_name(&name), function)?;
+                        let mut shared = shared.lock().unwrap();
+                        shared.commands.retain(|command| command.name != name);
+                        shared.commands.push(ScriptCommand {
+                            name,
+                            label,
+                            accel: None,
+                        });
+                        Ok(())
+                    },
+                )?,
+            )?;
+
+            let shared = self.shared.clone();
+            gxi.set(
+                "bind",
+                ctx.create_function(move |_, (name, accel): (String, String)| {
+                    let mut shared = shared.lock().unwrap();
+                    match shared.commands.iter_mut().find(|c| c.name == name) {
+                        Some(command) => {
+                            command.accel = Some(accel);
+                            Ok(())
+                        }
+                        None if BINDABLE_ACTIONS.contains(&name.as_str()) => {
+                            shared.action_accels.push((name, accel));
+                            Ok(())
+                        }
+                        None => Err(rlua::Error::RuntimeError(format!(
+                            "{} '{}'",
+                            gettext("Unknown command"),
+                            name
+                        ))),
+                    }
+                })?,
+            )?;
+
+            let shared = self.shared.clone();
+            gxi.set(
+                "selection",
+                ctx.create_function(move |_, ()| Ok(shared.lock().unwrap().selection.clone()))?,
+            )?;
+
+            let shared = self.shared.clone();
+            gxi.set(
+                "insert",
+                ctx.create_function(move |_, text: String| {
+                    let action = ScriptAction::Insert(text);
+                    shared.lock().unwrap().actions.push(action);
+                    Ok(())
+                })?,
+            )?;
+
+            let shared = self.shared.clone();
+            gxi.set(
+                "open",
+                ctx.create_function(move |_, path: String| {
+                    let action = ScriptAction::Open(PathBuf::from(path));
+                    shared.lock().unwrap().actions.push(action);
+                    Ok(())
+                })?,
+            )?;
+
+            gxi.set(
+                "log",
+                ctx.create_function(|_, message: String| {
+                    info!("{}: {}", gettext("Script"), message);
+                    Ok(())
+                })?,
+            )?;
+
+            ctx.globals().set("gxi", gxi)
+        })
+    }
+
+    /// The commands the scripts have registered
+    pub fn commands(&self) -> Vec<ScriptCommand> {
+        self.shared.lock().unwrap().commands.clone()
+    }
+
+    /// The accelerators the scripts have bound to gxi's own actions, by action name
+    pub fn action_accels(&self) -> Vec<(String, String)> {
+        self.shared.lock().unwrap().action_accels.clone()
+    }
+
+    /// Runs the command `name` with `selection` being the selected text. Returns what the command
+    /// wants the editor to do.
+    pub fn run(&self, name: &str, selection: &str) -> Result<Vec<ScriptAction>, String> {
+        debug!("{} '{}'", gettext("Running script command"), name);
+        self.shared.lock().unwrap().selection = selection.to_string();
+        let res = self.lua.context(|ctx| {
+            let function: Function = ctx.named_registry_value(&registry_name(name))?;
+            function.call::<_, ()>(())
+        });
+
+        let mut shared = self.shared.lock().unwrap();
+        shared.selection.clear();
+        let actions = shared.actions.drain(..).collect();
+        res.map(|_| actions).map_err(|e| {
+            warn!("{} '{}': {}", gettext("Script command failed"), name, e);
+            e.to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("upper-case"));
+        assert!(is_valid_name("sort.lines2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("upper case"));
+        assert!(!is_valid_name("größe"));
+    }
+
+    #[test]
+    fn run_command() {
+        let scripts = Scripts::new();
+        scripts.register_api().unwrap();
+        scripts
+            .exec(
+                "test.lua",
+                r#"
+                gxi.register_command("upper-case", "Upper Case", function()
+                    gxi.insert(string.upper(gxi.selection()))
+                    gxi.open("/tmp/notes.txt")
+                end)
+                gxi.bind("upper-case", "<Primary><Alt>u")
+                gxi.bind("insert_date_time", "<Primary>semicolon")
+                "#,
+            )
+            .unwrap();
+
+        assert_eq!(
+            scripts.commands(),
+            vec![ScriptCommand {
+                name: "upper-case".to_string(),
+                label: "Upper Case".to_string(),
+                accel: Some("<Primary><Alt>u".to_string()),
+            }]
+        );
+        assert_eq!(
+            scripts.action_accels(),
+            vec![(
+                "insert_date_time".to_string(),
+                "<Primary>semicolon".to_string()
+            )]
+        );
+        assert_eq!(
+            scripts.run("upper-case", "hello"),
+            Ok(vec![
+                ScriptAction::Insert("HELLO".to_string()),
+                ScriptAction::Open(PathBuf::from("/tmp/notes.txt")),
+            ])
+        );
+        assert!(scripts.run("unknown", "").is_err());
+        assert!(scripts
+            .exec(
+                "invalid.lua",
+                r#"gxi.register_command("upper case", "", function() end)"#
+            )
+            .is_err());
+        assert!(scripts
+            .exec("unknown.lua", r#"gxi.bind("unknown", "<Primary>u")"#)
+            .is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! A panel at the bottom of the window listing all matches of the search in the current document,
+//! so they can be looked through at a glance instead of going from one match to the next.
+//! Activating a match moves the cursor to it.
+
+use crate::panels::{Panels, Slot};
+use gettextrs::gettext;
+use glib::Type;
+use gtk::*;
+use gxi_editview::EditView;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// Name the panel is registered under, for the `app.toggle_panel` action
+pub const PANEL_NAME: &str = "search-results";
+
+/// Longer lines are cut off in the list
+const MAX_EXCERPT_LEN: usize = 200;
+
+/// Returns the rows of the list for the matches on `lines` of `text`: the (zero based) line, the
+/// line number shown and the text of the line
+fn rows(text: &str, lines: &[u64]) -> Vec<(u64, String, String)> {
+    let text_lines: Vec<&str> = text.lines().collect();
+    let mut lines = lines.to_vec();
+    lines.dedup();
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            let excerpt = text_lines.get(line as usize)?.trim();
+            let excerpt = excerpt.chars().take(MAX_EXCERPT_LEN).collect();
+            Some((line, (line + 1).to_string(), excerpt))
+        })
+        .collect()
+}
+
+pub struct SearchResults {
+    root: ScrolledWindow,
+    list_store: ListStore,
+    treeview: TreeView,
+    /// The EditView the matches are in
+    edit_view: RefCell<Weak<RefCell<EditView>>>,
+    /// The lines with matches, as xi-editor has sent them in `find_status`
+    lines: RefCell<Vec<u64>>,
+}
+
+impl SearchResults {
+    pub fn new(panels: &Panels) -> Rc<Self> {
+        let list_store = ListStore::new(&[Type::U64, Type::String, Type::String]);
+        let treeview = TreeView::new_with_model(&list_store);
+        treeview.set_headers_visible(false);
+        treeview.set_enable_search(false);
+        for (column, monospace) in [(1, false), (2, true)].iter() {
+            let renderer = CellRendererText::new();
+            if *monospace {
+                renderer.set_property_family(Some("Monospace"));
+            } else {
+                renderer.set_property_xalign(1.0);
+            }
+            let tree_column = TreeViewColumn::new();
+            tree_column.pack_start(&renderer, true);
+            tree_column.add_attribute(&renderer, "text", *column);
+            treeview.append_column(&tree_column);
+        }
+
+        let root = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        root.set_vexpand(true);
+        root.add(&treeview);
+        treeview.show();
+
+        let search_results = Rc::new(Self {
+            root,
+            list_store,
+            treeview,
+            edit_view: RefCell::new(Weak::new()),
+            lines: RefCell::new(Vec::new()),
+        });
+        // The text of the lines is only looked up while the panel is shown
+        search_results
+            .root
+            .connect_map(enclose!((search_results) move |_| search_results.refresh()));
+        panels.register(
+            PANEL_NAME,
+            &gettext("Search Results"),
+            Slot::Bottom,
+            &search_results.root,
+        );
+
+        search_results
+    }
+
+    /// Sets the function which is called with the EditView and line of the match the user has
+    /// activated
+    pub fn connect_activate<F: Fn(&Rc<RefCell<EditView>>, u64) + 'static>(
+        search_results: &Rc<Self>,
+        f: F,
+    ) {
+        let weak = Rc::downgrade(search_results);
+        search_results
+            .treeview
+            .connect_row_activated(move |tv, path, _| {
+                let search_results = match weak.upgrade() {
+                    Some(search_results) => search_results,
+                    None => return,
+                };
+                let edit_view = match search_results.edit_view.borrow().upgrade() {
+                    Some(edit_view) => edit_view,
+                    None => return,
+                };
+                let line = tv
+                    .get_model()
+                    .and_then(|model| model.get_iter(path).map(|iter| (model, iter)))
+                    .and_then(|(model, iter)| model.get_value(&iter, 0).get::<u64>());
+                if let Some(line) = line {
+                    f(&edit_view, line);
+                }
+            });
+    }
+
+    /// Lists the matches on `lines` of `edit_view`
+    pub fn set_matches(&self, edit_view: &Rc<RefCell<EditView>>, lines: Vec<u64>) {
+        self.edit_view.replace(Rc::downgrade(edit_view));
+        self.lines.replace(lines);
+        if self.root.get_mapped() {
+            self.refresh();
+        }
+    }
+
+    /// Clears the list if it shows the matches of the EditView with the id `view_id`
+    pub fn remove_view(&self, view_id: &str) {
+        let shown = self
+            .edit_view
+            .borrow()
+            .upgrade()
+            .map_or(true, |ev| ev.borrow().view_id == view_id);
+        if shown {
+            self.edit_view.replace(Weak::new());
+            self.lines.borrow_mut().clear();
+            self.list_store.clear();
+        }
+    }
+
+    fn refresh(&self) {
+        self.list_store.clear();
+        let edit_view = match self.edit_view.borrow().upgrade() {
+            Some(edit_view) => edit_view,
+            None => return,
+        };
+        let lines = self.lines.borrow().clone();
+        if lines.is_empty() {
+            return;
+        }
+        let list_store = self.list_store.clone();
+        edit_view.borrow().with_text(move |text| {
+            list_store.clear();
+            for (line, number, excerpt) in rows(text, &lines) {
+                list_store.insert_with_values(None, &[0, 1, 2], &[&line, &number, &excerpt]);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_rows() {
+        let text = "fn main() {\n    let foo = 1;\n    foo + foo\n}\n";
+        assert_eq!(
+            rows(text, &[1, 2, 2, 7]),
+            vec![
+                (1, "2".to_string(), "let foo = 1;".to_string()),
+                (2, "3".to_string(), "foo + foo".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::gxi_dirs;
+use crate::window_name;
+use gettextrs::gettext;
+use log::{debug, warn};
+use serde_derive::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const SESSION_FILE_NAME: &str = "session.json";
+
+/// Whether a panel (e.g. the search bar) was open and how big it was
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct PanelState {
+    pub open: bool,
+    /// Width in pixels, 0 if the panel hasn't got a size of its own
+    #[serde(default)]
+    pub width: i32,
+    /// Height in pixels, 0 if the panel hasn't got a size of its own
+    #[serde(default)]
+    pub height: i32,
+}
+
+/// State of the last session, which is restored on the next startup
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Session {
+    /// Maps the name of a panel to its state
+    #[serde(default)]
+    pub panels: BTreeMap<String, PanelState>,
+    /// Commands the selection was recently filtered through, the most recent one first
+    #[serde(default)]
+    pub filter_commands: Vec<String>,
+    /// Name of the window, each named window has a session of its own
+    #[serde(default)]
+    pub window_name: Option<String>,
+    /// The name the user has given the window while it was open, if it differs from
+    /// `window_name`. The name is part of the application id, which can't be changed while gxi
+    /// is running, so it's only used from the next start on: the session is saved under it.
+    #[serde(skip)]
+    pub new_window_name: Option<Option<String>>,
+    /// The folder the file tree shows
+    #[serde(default)]
+    pub file_tree_folder: Option<String>,
+    /// Files which have been opened recently, the most recent one first
+    #[serde(default)]
+    pub recent_files: Vec<String>,
+}
+
+impl PanelState {
+    /// State of a panel which doesn't have a size of its own, e.g. the replace entry
+    pub fn shown(open: bool) -> Self {
+        Self {
+            open,
+            ..Default::default()
+        }
+    }
+}
+
+impl Session {
+    /// Path of the session of the window called `window_name`
+    fn path(window_name: Option<&str>) -> Option<PathBuf> {
+        match window_name {
+            Some(name) => {
+                gxi_dirs::state_file(&format!("session-{}.json", window_name::sanitize(name)))
+            }
+            None => gxi_dirs::state_file(SESSION_FILE_NAME),
+        }
+    }
+
+    /// Loads the last session of the window called `window_name`, or returns an empty one if
+    /// there is none (yet)
+    pub fn load(window_name: Option<&str>) -> Self {
+        let mut session: Self = Self::path(window_name)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| {
+                serde_json::from_str(&content)
+                    .map_err(|e| warn!("{}: {}", gettext("Failed to parse session"), e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        session.window_name = window_name.map(str::to_string);
+        session
+    }
+
+    pub fn save(&self) {
+        let window_name = self.new_window_name.as_ref().unwrap_or(&self.window_name);
+        let path = match Self::path(window_name.as_ref().map(String::as_str)) {
+            Some(path) => path,
+            None => return,
+        };
+        debug!("{} {:?}", gettext("Saving session to"), path);
+
+        let res = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, serde_json::to_string_pretty(self).unwrap()));
+        if let Err(e) = res {
+            warn!("{} {:?}: {}", gettext("Failed to save session to"), path, e);
+        }
+    }
+
+    pub fn panel(&self, name: &str) -> PanelState {
+        self.panels.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn set_panel(&mut self, name: &str, state: PanelState) {
+        self.panels.insert(name.to_string(), state);
+    }
+}
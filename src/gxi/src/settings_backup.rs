@@ -0,0 +1,159 @@
+//! Backs up the settings the user has made before gxi runs after an update, so they can be
+//! restored in the preferences if the update has changed or reset any of them. The backups are
+//! keyfiles in the state directory named after the version they were made of, e.g.
+//! `settings-backups/settings-0.7.0.ini`.
+
+use crate::gxi_dirs;
+use crate::whats_new;
+use crate::window_name::APP_ID;
+use gettextrs::gettext;
+use gio::SettingsExt;
+use gxi_config_storage::{GSchema, GSchemaExt};
+use log::{debug, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const BACKUP_DIR_NAME: &str = "settings-backups";
+/// How many backups are kept, older ones are removed
+const MAX_BACKUPS: usize = 5;
+/// Keys which aren't backed up, they're about gxi itself rather than what the user has set
+const SKIPPED_KEYS: &[&str] = &["last-version"];
+
+/// The name of the backup of the settings of `version`
+fn file_name(version: &str) -> String {
+    format!("settings-{}.ini", whats_new::normalize_version(version))
+}
+
+/// The version a backup is of, from its file name
+fn version_of(file_name: &str) -> Option<&str> {
+    const PREFIX: &str = "settings-";
+    const SUFFIX: &str = ".ini";
+    if !file_name.starts_with(PREFIX) || !file_name.ends_with(SUFFIX) {
+        return None;
+    }
+    let version = &file_name[PREFIX.len()..file_name.len() - SUFFIX.len()];
+    if version.is_empty() {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+fn backup_dir() -> Option<PathBuf> {
+    gxi_dirs::state_dir().map(|dir| dir.join(BACKUP_DIR_NAME))
+}
+
+/// The keys of the schema which are backed up
+fn keys(gschema: &GSchema) -> Vec<String> {
+    gschema
+        .settings
+        .get_property_settings_schema()
+        .map(|schema| schema.list_keys())
+        .unwrap_or_default()
+        .iter()
+        .map(|key| key.to_string())
+        .filter(|key| !SKIPPED_KEYS.contains(&key.as_str()))
+        .collect()
+}
+
+/// Backs up the settings the user has made if gxi has been updated since it was last run. Has to
+/// be called before anything changes the settings after an update and before `whats_new::check`
+/// remembers the version that's running.
+pub fn backup_if_updated(gschema: &GSchema) {
+    let last_version: String = gschema.get_key("last-version");
+    // On the first start there's nothing to back up yet
+    if last_version.is_empty()
+        || whats_new::normalize_version(&last_version)
+            == whats_new::normalize_version(whats_new::current_version())
+    {
+        return;
+    }
+    let dir = match backup_dir() {
+        Some(dir) => dir,
+        None => return,
+    };
+    if let Err(e) = fs::create_dir_all(&dir) {
+        warn!(
+            "{} {:?}: {}",
+            gettext("Failed to create the directory"),
+            dir,
+            e
+        );
+        return;
+    }
+    let path = dir.join(file_name(&last_version));
+    debug!("{} {:?}", gettext("Backing up the settings to"), path);
+    let backup = GSchema::new_with_keyfile(APP_ID, &path);
+    for key in keys(gschema) {
+        if let Some(value) = gschema.settings.get_user_value(&key) {
+            backup.settings.set_value(&key, &value);
+        }
+    }
+    gio::Settings::sync();
+    remove_old_backups();
+}
+
+/// The backups there are and the versions they're of, the newest one first
+fn backups() -> Vec<(PathBuf, String)> {
+    let entries = match backup_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        Some(entries) => entries,
+        None => return Vec::new(),
+    };
+    let mut backups: Vec<(SystemTime, PathBuf, String)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let version = version_of(&entry.file_name().to_string_lossy())?.to_string();
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path(), version))
+        })
+        .collect();
+    backups.sort_by(|a, b| b.0.cmp(&a.0));
+    backups
+        .into_iter()
+        .map(|(_, path, version)| (path, version))
+        .collect()
+}
+
+fn remove_old_backups() {
+    for (path, _) in backups().into_iter().skip(MAX_BACKUPS) {
+        debug!("{} {:?}", gettext("Removing old settings backup"), path);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("{} {:?}: {}", gettext("Failed to remove"), path, e);
+        }
+    }
+}
+
+/// The newest backup and the version it's of
+pub fn latest() -> Option<(PathBuf, String)> {
+    backups().into_iter().next()
+}
+
+/// Sets the keys which have been backed up to `path` to their backed up values. Keys the user
+/// hadn't set back then are reset to their defaults, since only the settings the user has made are
+/// backed up. Keys which the running version of gxi doesn't know anymore are skipped.
+pub fn restore(gschema: &GSchema, path: &Path) {
+    debug!("{} {:?}", gettext("Restoring the settings from"), path);
+    let backup = GSchema::new_with_keyfile(APP_ID, path);
+    for key in keys(gschema) {
+        match backup.settings.get_user_value(&key) {
+            Some(value) => {
+                gschema.settings.set_value(&key, &value);
+            }
+            None => gschema.settings.reset(&key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backup_names() {
+        assert_eq!(file_name("v0.7.0-12-g1234abc"), "settings-0.7.0.ini");
+        assert_eq!(version_of("settings-0.7.0.ini"), Some("0.7.0"));
+        assert_eq!(version_of("settings-.ini"), None);
+        assert_eq!(version_of("session.json"), None);
+    }
+}
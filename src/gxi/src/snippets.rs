@@ -0,0 +1,52 @@
+//! Text for the "Insert Date/Time" and "Insert UUID" commands
+
+use glib::DateTime;
+
+/// Returns the current local time formatted with the strftime-like `format`, see
+/// `g_date_time_format`. Returns `None` if `format` is invalid.
+pub fn date_time(format: &str) -> Option<String> {
+    DateTime::new_now_local()
+        .format(format)
+        .map(|s| s.to_string())
+}
+
+/// Returns a random (version 4) UUID
+pub fn uuid_v4() -> String {
+    let mut bytes = [0; 16];
+    for chunk in bytes.chunks_mut(4) {
+        chunk.copy_from_slice(&glib::random_int().to_be_bytes());
+    }
+    format_uuid_v4(bytes)
+}
+
+/// Formats random `bytes` as UUID, setting the version and variant bits as RFC 4122 asks for
+fn format_uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn uuid() {
+        assert_eq!(
+            format_uuid_v4([0xff; 16]),
+            "ffffffff-ffff-4fff-bfff-ffffffffffff"
+        );
+        assert_eq!(
+            format_uuid_v4([0; 16]),
+            "00000000-0000-4000-8000-000000000000"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Reads text aloud via speech-dispatcher. We use its `spd-say` client instead of linking to
+//! libspeechd, so gxi works fine on systems without speech-dispatcher, only this feature doesn't.
+
+use gettextrs::gettext;
+use gxi_peer::Sandbox;
+use log::{debug, warn};
+use std::io::{self, Write};
+use std::process::Stdio;
+use std::thread;
+
+const SPD_SAY: &str = "spd-say";
+
+/// Reads `text` aloud, interrupting whatever is read currently. `rate` goes from -100 (slowest)
+/// to 100 (fastest), `voice` is the name of a voice of the speech synthesizer or empty for its
+/// default voice.
+pub fn speak(text: &str, rate: i32, voice: &str) -> io::Result<()> {
+    stop()?;
+
+    let mut command = Sandbox::detect().host_command(SPD_SAY);
+    // In pipe mode spd-say reads the text from stdin, so it works for texts of any size
+    command
+        .arg("--pipe-mode")
+        .arg("--rate")
+        .arg(rate.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null());
+    if !voice.is_empty() {
+        command.arg("--synthesis-voice").arg(voice);
+    }
+
+    debug!(
+        "{} {} {} '{}'",
+        gettext("Speaking text with rate"),
+        rate,
+        gettext("and voice"),
+        voice
+    );
+    let mut child = command.spawn()?;
+    let text = text.to_string();
+    thread::spawn(move || {
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Err(e) = stdin.write_all(text.as_bytes()) {
+                warn!("{}: {}", gettext("Failed to send text to spd-say"), e);
+            }
+        }
+        if let Err(e) = child.wait() {
+            warn!("{}: {}", gettext("Failed to wait for spd-say"), e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Stops reading text aloud
+pub fn stop() -> io::Result<()> {
+    Sandbox::detect()
+        .host_command(SPD_SAY)
+        .arg("--cancel")
+        .status()
+        .map(|_| ())
+}
@@ -0,0 +1,129 @@
+//! sudoedit copies the files it should edit to a temporary directory, lets the editor (e.g. gxi, if
+//! it's set as `SUDO_EDITOR`) edit the copies and writes them back to the actual files once the
+//! editor exits. The copies are named like the original files with 8 random characters inserted
+//! before the extension, e.g. `/var/tmp/hostsXXXXXXXX` for `/etc/hosts` or
+//! `/var/tmp/nginxXXXXXXXX.conf` for `/etc/nginx/nginx.conf`.
+//!
+//! Since the name of the copy doesn't contain the directory of the original file, we look at the
+//! arguments of the sudoedit process which started us to find out which file is actually edited.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Amount of random characters sudoedit adds to the name of the copy
+const RANDOM_CHARS: usize = 8;
+/// How many parent processes we check for sudoedit. sudo may run the editor in a child process of
+/// its own, e.g. when it allocates a pty.
+const MAX_ANCESTORS: usize = 4;
+
+/// Returns the file `file` is a temporary copy of, if it has been created by sudoedit
+pub fn origin_of(file: &Path) -> Option<PathBuf> {
+    let name = file.file_name()?.to_str()?;
+    if !file.parent().map_or(false, is_temp_dir) {
+        return None;
+    }
+
+    sudoedit_files().into_iter().find(|orig| {
+        orig.file_name()
+            .and_then(|n| n.to_str())
+            .map_or(false, |orig_name| is_temp_copy_of(name, orig_name))
+    })
+}
+
+fn is_temp_dir(dir: &Path) -> bool {
+    dir == Path::new("/var/tmp")
+        || dir == Path::new("/tmp")
+        || env::var_os("TMPDIR").map_or(false, |tmp| dir == Path::new(&tmp))
+}
+
+/// Checks if `temp_name` is the name sudoedit would give to a copy of a file named `orig_name`
+fn is_temp_copy_of(temp_name: &str, orig_name: &str) -> bool {
+    // Like sudoedit we treat everything after the last dot as extension
+    let (stem, ext) = match orig_name.rfind('.') {
+        Some(i) => orig_name.split_at(i),
+        None => (orig_name, ""),
+    };
+
+    temp_name.len() == orig_name.len() + RANDOM_CHARS
+        && temp_name.starts_with(stem)
+        && temp_name.ends_with(ext)
+        && temp_name
+            .get(stem.len()..stem.len() + RANDOM_CHARS)
+            .map_or(false, |random| {
+                random.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+}
+
+/// The files passed to the sudoedit (or `sudo -e`) process we've been started by, if any
+fn sudoedit_files() -> Vec<PathBuf> {
+    let mut pid = parent_pid("self");
+    for _ in 0..MAX_ANCESTORS {
+        let current = match pid {
+            Some(current) if current > 1 => current.to_string(),
+            _ => break,
+        };
+        if let Some(args) = sudoedit_args(&current) {
+            // Relative paths are relative to the directory sudoedit has been started in
+            let cwd = fs::read_link(format!("/proc/{}/cwd", current)).unwrap_or_default();
+            return args.iter().map(|arg| cwd.join(arg)).collect();
+        }
+        pid = parent_pid(&current);
+    }
+
+    Vec::new()
+}
+
+/// Returns the non-option arguments of the process `pid` if it's sudoedit. These also contain the
+/// values of options like `-u user`, which doesn't matter since we only use them to find the file
+/// matching the name of the copy.
+fn sudoedit_args(pid: &str) -> Option<Vec<String>> {
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let mut args = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned());
+
+    let program = args.next()?;
+    let args: Vec<String> = args.collect();
+    let is_sudoedit = match Path::new(&program).file_name().and_then(|n| n.to_str()) {
+        Some("sudoedit") => true,
+        Some("sudo") => args.iter().any(|arg| arg == "-e" || arg == "--edit"),
+        _ => false,
+    };
+    if !is_sudoedit {
+        return None;
+    }
+
+    Some(
+        args.into_iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .collect(),
+    )
+}
+
+fn parent_pid(pid: &str) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The name of the program is in parentheses and may contain spaces, the parent's pid is the
+    // second field after it
+    stat[stat.rfind(')')? + 1..]
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn temp_copy_names() {
+        assert!(is_temp_copy_of("hostsAb3dE6gH", "hosts"));
+        assert!(is_temp_copy_of("nginxAb3dE6gH.conf", "nginx.conf"));
+        assert!(is_temp_copy_of("Ab3dE6gH.bashrc", ".bashrc"));
+        assert!(!is_temp_copy_of("nginx.confAb3dE6gH", "nginx.conf"));
+        assert!(!is_temp_copy_of("hosts.bak", "hosts"));
+        assert!(!is_temp_copy_of("hostsAb3-E6gH", "hosts"));
+    }
+}
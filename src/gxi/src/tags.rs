@@ -0,0 +1,396 @@
+//! Go to Definition (F12 or Ctrl+Alt+click) via the tags file of a project, as written by ctags,
+//! e.g. `ctags -R` or `universal-ctags -R --fields=+n`. The tags file is looked for in the
+//! directory of the document and its parents up to the root of the project and is read again
+//! whenever it has changed, in the background like the files the definitions are in. If a word
+//! is defined in several places the user picks one. Other editors go to definitions on Ctrl+click,
+//! but in gxi that adds a cursor, hence Ctrl+Alt+click.
+
+use crate::project;
+use gettextrs::gettext;
+use glib::{source, MainContext};
+use gtk::*;
+use log::debug;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+/// The names tags files usually have
+const TAGS_FILE_NAMES: &[&str] = &["tags", ".tags"];
+
+/// Where a tag is defined in its file
+#[derive(Clone, Debug, PartialEq)]
+enum Address {
+    /// Zero based
+    Line(u64),
+    /// A search pattern matching the line, e.g. `/^fn main() {$/`
+    Pattern(String),
+}
+
+/// A definition of a name
+#[derive(Clone, Debug, PartialEq)]
+struct Tag {
+    file: PathBuf,
+    address: Address,
+    /// What's defined, e.g. `function` or `f`. Not every tags file has it.
+    kind: Option<String>,
+}
+
+/// The definitions in a tags file by name
+type Tags = HashMap<String, Vec<Tag>>;
+
+/// The address of a tag from the third field of its line, e.g. `42` or `/^fn main() {$/;"`
+fn parse_address(address: &str) -> Option<Address> {
+    let address = address.trim_end_matches(";\"");
+    if let Ok(line) = address.parse::<u64>() {
+        return Some(Address::Line(line.checked_sub(1)?));
+    }
+    let delimiter = address.chars().next().filter(|c| *c == '/' || *c == '?')?;
+    let pattern = address[1..].trim_end_matches(delimiter);
+    // Only the delimiter and backslashes are escaped in the pattern
+    let pattern = pattern
+        .replace(&format!("\\{}", delimiter), &delimiter.to_string())
+        .replace("\\\\", "\\");
+    Some(Address::Pattern(pattern))
+}
+
+/// Reads the lines of a tags file, e.g. `main\tsrc/main.rs\t/^fn main() {$/;"\tf\tline:12`.
+/// Files are relative to `dir`, the directory of the tags file. Lines starting with `!_` are
+/// about the tags file itself and skipped.
+fn parse(content: &str, dir: &Path) -> Tags {
+    let mut tags = Tags::new();
+    for line in content.lines().filter(|line| !line.starts_with("!_")) {
+        let mut fields = line.split('\t');
+        let (name, file) = match (fields.next(), fields.next()) {
+            (Some(name), Some(file)) => (name, file),
+            _ => continue,
+        };
+        // The address may contain tabs, the extension fields start after `;"`
+        let rest: Vec<&str> = fields.collect();
+        let rest = rest.join("\t");
+        let (address, extensions) = match rest.find(";\"\t") {
+            Some(ix) => (&rest[..ix], &rest[ix + 3..]),
+            None => (rest.trim_end_matches(";\""), ""),
+        };
+        let mut address = match parse_address(address) {
+            Some(address) => address,
+            None => continue,
+        };
+        let mut kind = None;
+        for extension in extensions.split('\t') {
+            match extension.find(':') {
+                Some(ix) if &extension[..ix] == "line" => {
+                    if let Ok(line) = extension[ix + 1..].parse::<u64>() {
+                        address = Address::Line(line.saturating_sub(1));
+                    }
+                }
+                Some(ix) if &extension[..ix] == "kind" => {
+                    kind = Some(extension[ix + 1..].to_string())
+                }
+                None if !extension.is_empty() => kind = Some(extension.to_string()),
+                _ => (),
+            }
+        }
+        tags.entry(name.to_string()).or_default().push(Tag {
+            file: dir.join(file),
+            address,
+            kind,
+        });
+    }
+    tags
+}
+
+/// The (zero based) line `pattern` matches in `text`
+fn find_pattern(text: &str, pattern: &str) -> Option<u64> {
+    let start = pattern.starts_with('^');
+    let end = pattern.ends_with('$') && !pattern.ends_with("\\$");
+    let pattern = &pattern[if start { 1 } else { 0 }..pattern.len() - if end { 1 } else { 0 }];
+    text.lines()
+        .position(|line| match (start, end) {
+            (true, true) => line == pattern,
+            (true, false) => line.starts_with(pattern),
+            (false, true) => line.ends_with(pattern),
+            (false, false) => line.contains(pattern),
+        })
+        .map(|line| line as u64)
+}
+
+/// The tags file for `file`, in its directory or one of the parents up to its project's root
+fn tags_file(file: &Path) -> Option<PathBuf> {
+    let root = project::project_root(file);
+    file.parent()?
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&root))
+        .flat_map(|dir| TAGS_FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// A definition the user can go to: the file and (zero based) line
+pub type Definition = (String, u64);
+
+/// The tags files we've read and when they were last modified
+type Cache = HashMap<PathBuf, (SystemTime, Arc<Tags>)>;
+
+/// What looking up a name in a tags file has turned up
+#[derive(Debug, Default, PartialEq)]
+pub struct Lookup {
+    /// The definitions of the name and what they define
+    pub definitions: Vec<(Definition, Option<String>)>,
+    /// Files in which the pattern of a definition doesn't match any line, since they've been
+    /// changed after the tags file was written
+    pub outdated: Vec<String>,
+}
+
+/// The tags of the tags file `path`, read again if it has changed since we last read it
+fn load(path: &Path, cache: &Mutex<Cache>) -> Option<Arc<Tags>> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    if let Some((cached_modified, tags)) = cache.lock().unwrap().get(path) {
+        if *cached_modified == modified {
+            return Some(tags.clone());
+        }
+    }
+    debug!("{} {:?}", gettext("Reading tags file"), path);
+    let content = fs::read(path).ok()?;
+    let tags = Arc::new(parse(&String::from_utf8_lossy(&content), path.parent()?));
+    cache
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (modified, tags.clone()));
+    Some(tags)
+}
+
+/// Looks up the definitions of `name` in the tags file `path`. Files which have been removed
+/// since the tags file was written are skipped.
+fn lookup(name: &str, path: &Path, cache: &Mutex<Cache>) -> Lookup {
+    let mut lookup = Lookup::default();
+    let tags = match load(path, cache) {
+        Some(tags) => tags,
+        None => return lookup,
+    };
+    for tag in tags.get(name).into_iter().flatten() {
+        if !tag.file.is_file() {
+            continue;
+        }
+        let file = tag.file.to_string_lossy().into_owned();
+        let line = match &tag.address {
+            Address::Line(line) => Some(*line),
+            Address::Pattern(pattern) => fs::read_to_string(&tag.file)
+                .ok()
+                .and_then(|text| find_pattern(&text, pattern)),
+        };
+        match line {
+            Some(line) => lookup.definitions.push(((file, line), tag.kind.clone())),
+            None => lookup.outdated.push(file),
+        }
+    }
+    lookup
+}
+
+/// Looks up definitions in the tags files of projects, which are cached until they change
+#[derive(Default)]
+pub struct TagFiles {
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl TagFiles {
+    /// Looks up the definitions of `name` for the document `file` in the background and calls
+    /// `callback` with them. Returns false if there's no tags file for the document.
+    pub fn definitions<F>(&self, name: &str, file: &Path, callback: F) -> bool
+    where
+        F: FnOnce(Lookup) + 'static,
+    {
+        let path = match tags_file(file) {
+            Some(path) => path,
+            None => return false,
+        };
+        let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        let mut callback = Some(callback);
+        rx.attach(Some(&MainContext::default()), move |lookup| {
+            if let Some(callback) = callback.take() {
+                callback(lookup);
+            }
+            source::Continue(false)
+        });
+        let name = name.to_string();
+        let cache = self.cache.clone();
+        thread::spawn(move || {
+            tx.send(lookup(&name, &path, &cache)).ok();
+        });
+        true
+    }
+}
+
+/// A popover to pick one of several definitions of a name
+pub struct DefinitionPicker {
+    popover: Popover,
+    title: Label,
+    list_box: ListBox,
+    /// The definitions in the rows of the list
+    shown: RefCell<Vec<Definition>>,
+    /// Called with the definition the user has picked
+    open_handler: RefCell<Option<Rc<dyn Fn(&str, u64)>>>,
+}
+
+impl DefinitionPicker {
+    pub fn new(relative_to: &HeaderBar) -> Rc<Self> {
+        let popover = Popover::new(Some(relative_to));
+        popover.set_position(PositionType::Bottom);
+
+        let title = Label::new(None);
+        title.get_style_context().add_class("dim-label");
+        let list_box = ListBox::new();
+        list_box.set_selection_mode(SelectionMode::Browse);
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        scrolled_window.set_size_request(500, 250);
+        scrolled_window.add(&list_box);
+
+        let vbox = Box::new(Orientation::Vertical, 6);
+        vbox.set_border_width(6);
+        vbox.pack_start(&title, false, false, 0);
+        vbox.pack_start(&scrolled_window, true, true, 0);
+        vbox.show_all();
+        popover.add(&vbox);
+
+        let picker = Rc::new(Self {
+            popover,
+            title,
+            list_box,
+            shown: RefCell::new(Vec::new()),
+            open_handler: RefCell::new(None),
+        });
+        picker
+            .list_box
+            .connect_row_activated(enclose!((picker) move |_, row| {
+                let definition = picker.shown.borrow().get(row.get_index() as usize).cloned();
+                if let Some((file, line)) = definition {
+                    picker.popover.hide();
+                    if let Some(open) = picker.open_handler.borrow().clone() {
+                        open(&file, line);
+                    }
+                }
+            }));
+
+        picker
+    }
+
+    /// Sets the function which goes to the definition the user has picked
+    pub fn connect_open<F: Fn(&str, u64) + 'static>(&self, f: F) {
+        self.open_handler.replace(Some(Rc::new(f)));
+    }
+
+    /// Lets the user pick one of the `definitions` of `name`, shown relative to `root`
+    pub fn show(&self, name: &str, definitions: Vec<(Definition, Option<String>)>, root: &Path) {
+        self.title
+            .set_text(&format!("{} '{}'", gettext("Definitions of"), name));
+        for row in self.list_box.get_children() {
+            self.list_box.remove(&row);
+        }
+        let mut shown = self.shown.borrow_mut();
+        shown.clear();
+        for ((file, line), kind) in definitions {
+            let rel_path = Path::new(&file)
+                .strip_prefix(root)
+                .map_or_else(|_| file.clone(), |p| p.to_string_lossy().into_owned());
+            let text = match kind {
+                Some(kind) => format!("{}:{} ({})", rel_path, line + 1, kind),
+                None => format!("{}:{}", rel_path, line + 1),
+            };
+            let label = Label::new(Some(text.as_str()));
+            label.set_halign(Align::Start);
+            label.set_ellipsize(pango::EllipsizeMode::Start);
+            label.set_margin_start(6);
+            label.set_margin_end(6);
+            label.set_margin_top(3);
+            label.set_margin_bottom(3);
+            self.list_box.add(&label);
+            shown.push((file, line));
+        }
+        self.list_box.show_all();
+        if let Some(row) = self.list_box.get_row_at_index(0) {
+            self.list_box.select_row(Some(&row));
+            row.grab_focus();
+        }
+        #[cfg(feature = "gtk_v3_22")]
+        self.popover.popup();
+        #[cfg(not(feature = "gtk_v3_22"))]
+        self.popover.show();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_tags() {
+        let content = "!_TAG_FILE_FORMAT\t2\t/extended format/\n\
+                       main\tsrc/main.rs\t/^fn main() {$/;\"\tf\n\
+                       Foo\tsrc/foo.rs\t/^pub struct Foo {$/;\"\tkind:struct\tline:3\n\
+                       Foo\tsrc/bar.rs\t12;\"\ts\n\
+                       path\tsrc/a.rs\t/^const path: &str = \"a\\/b\";$/\n";
+        let tags = parse(content, Path::new("/project"));
+        assert_eq!(tags.len(), 3);
+        assert_eq!(
+            tags["main"],
+            vec![Tag {
+                file: PathBuf::from("/project/src/main.rs"),
+                address: Address::Pattern("^fn main() {$".to_string()),
+                kind: Some("f".to_string()),
+            }]
+        );
+        assert_eq!(tags["Foo"][0].address, Address::Line(2));
+        assert_eq!(tags["Foo"][0].kind, Some("struct".to_string()));
+        assert_eq!(tags["Foo"][1].address, Address::Line(11));
+        assert_eq!(
+            tags["path"][0].address,
+            Address::Pattern("^const path: &str = \"a/b\";$".to_string())
+        );
+    }
+
+    #[test]
+    fn find_patterns() {
+        let text = "use std::io;\n\nfn main() {\n    main_loop();\n}\n";
+        assert_eq!(find_pattern(text, "^fn main() {$"), Some(2));
+        assert_eq!(find_pattern(text, "^    main_loop"), Some(3));
+        assert_eq!(find_pattern(text, "^fn other() {$"), None);
+    }
+
+    #[test]
+    fn lookup_definitions() {
+        let dir = std::env::temp_dir().join(format!("gxi-tags-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rs"), "use std::io;\n\nfn main() {}\n").unwrap();
+        let tags_path = dir.join("tags");
+        fs::write(
+            &tags_path,
+            "main\tmain.rs\t/^fn main() {}$/;\"\tf\n\
+             main\tgone.rs\t/^fn main() {}$/;\"\tf\n\
+             io\tmain.rs\t/^use std::io::Read;$/;\"\tm\n",
+        )
+        .unwrap();
+        let cache = Mutex::new(Cache::new());
+        let file = dir.join("main.rs").to_string_lossy().into_owned();
+
+        assert_eq!(
+            lookup("main", &tags_path, &cache),
+            Lookup {
+                definitions: vec![((file.clone(), 2), Some("f".to_string()))],
+                outdated: Vec::new(),
+            }
+        );
+        assert_eq!(
+            lookup("io", &tags_path, &cache),
+            Lookup {
+                definitions: Vec::new(),
+                outdated: vec![file],
+            }
+        );
+        assert_eq!(lookup("other", &tags_path, &cache), Lookup::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+//! A terminal at the bottom of the window, for a quick shell next to the document. The shell is
+//! started once the panel is shown for the first time, and again if it has exited. Opening the
+//! terminal in a directory while a program runs in the shell (e.g. an editor) starts another shell
+//! in a new tab, so nothing is typed into the program. Needs VTE, so it's only built with the
+//! `terminal` feature.
+
+use crate::panels::{Panels, Slot};
+use gettextrs::gettext;
+use gtk::*;
+use gxi_peer::Sandbox;
+use log::{debug, warn};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use vte::{PtyFlags, TerminalExt};
+
+/// Name the panel is registered under, for the `app.toggle_panel` action
+pub const PANEL_NAME: &str = "terminal";
+
+/// Quotes `arg` for POSIX shells
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// The line typed into a running shell to change to `dir`
+fn cd_command(dir: &Path) -> String {
+    format!("cd {}\n", shell_quote(&dir.to_string_lossy()))
+}
+
+/// Whether the process whose `/proc/<pid>/stat` is `stat` is in the foreground of its terminal,
+/// that is its process group is the one the terminal sends input to
+fn is_foreground(stat: &str) -> Option<bool> {
+    // The name of the command is in parentheses and may contain spaces and parentheses itself
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let (pgrp, tpgid) = (fields.get(2)?, fields.get(5)?);
+    Some(pgrp == tpgid)
+}
+
+/// The command starting the user's shell in `dir`. Inside Flatpak it's started on the host, the
+/// shell of the sandbox isn't of much use.
+fn shell_argv(sandbox: Sandbox, dir: &Path) -> Vec<String> {
+    match sandbox {
+        Sandbox::None => vec![env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())],
+        Sandbox::Flatpak => vec![
+            "flatpak-spawn".to_string(),
+            "--host".to_string(),
+            "--watch-bus".to_string(),
+            format!("--directory={}", dir.to_string_lossy()),
+            "sh".to_string(),
+            "-c".to_string(),
+            "exec \"${SHELL:-/bin/sh}\"".to_string(),
+        ],
+    }
+}
+
+/// A shell in a tab of the terminal panel
+struct Shell {
+    root: ScrolledWindow,
+    terminal: vte::Terminal,
+    /// The process id of the shell while it's running
+    pid: Cell<Option<i32>>,
+}
+
+impl Shell {
+    fn new() -> Self {
+        let terminal = vte::Terminal::new();
+        terminal.set_scrollback_lines(10_000);
+        let root = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        root.set_policy(PolicyType::Never, PolicyType::Automatic);
+        root.set_vexpand(true);
+        root.add(&terminal);
+        root.show_all();
+        Self {
+            root,
+            terminal,
+            pid: Cell::new(None),
+        }
+    }
+
+    fn spawn(&self, dir: &Path) {
+        let argv = shell_argv(Sandbox::detect(), dir);
+        debug!(
+            "{} {:?} {} {:?}",
+            gettext("Starting"),
+            argv,
+            gettext("in"),
+            dir
+        );
+
+        let argv: Vec<&Path> = argv.iter().map(Path::new).collect();
+        let res = self.terminal.spawn_sync(
+            PtyFlags::DEFAULT,
+            dir.to_str(),
+            &argv,
+            &[],
+            glib::SpawnFlags::SEARCH_PATH,
+            None,
+            None::<&gio::Cancellable>,
+        );
+        match res {
+            Ok(pid) => self.pid.set(Some(pid.0)),
+            Err(e) => warn!("{}: {}", gettext("Failed to start the shell"), e),
+        }
+    }
+
+    /// Whether the shell waits for a command, rather than running a program in the foreground.
+    /// Inside Flatpak the shell runs on the host, where we can't look, so it never counts as
+    /// waiting.
+    fn is_waiting(&self) -> bool {
+        self.pid
+            .get()
+            .and_then(|pid| fs::read_to_string(format!("/proc/{}/stat", pid)).ok())
+            .and_then(|stat| is_foreground(&stat))
+            .unwrap_or(false)
+    }
+}
+
+pub struct Terminal {
+    notebook: Notebook,
+    /// The shells in the order of their tabs
+    shells: RefCell<Vec<Rc<Shell>>>,
+    /// The directory the shell is started in next
+    directory: RefCell<Option<PathBuf>>,
+}
+
+impl Terminal {
+    pub fn new(panels: &Panels) -> Rc<Self> {
+        let notebook = Notebook::new();
+        notebook.set_show_tabs(false);
+        notebook.set_scrollable(true);
+        notebook.set_vexpand(true);
+
+        let term = Rc::new(Self {
+            notebook,
+            shells: RefCell::new(Vec::new()),
+            directory: RefCell::new(None),
+        });
+
+        term.notebook.connect_map(enclose!((term) move |_| {
+            Self::start(&term);
+            if let Some(shell) = term.current() {
+                shell.terminal.grab_focus();
+            }
+        }));
+        panels.register(
+            PANEL_NAME,
+            &gettext("Terminal"),
+            Slot::Bottom,
+            &term.notebook,
+        );
+
+        term
+    }
+
+    /// The shell of the current tab
+    fn current(&self) -> Option<Rc<Shell>> {
+        let page = self.notebook.get_current_page()? as usize;
+        self.shells.borrow().get(page).cloned()
+    }
+
+    /// Starts a shell if none is running in the current tab
+    fn start(term: &Rc<Self>) {
+        let dir = term
+            .directory
+            .borrow_mut()
+            .take()
+            .or_else(|| env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("/"));
+        match term.current() {
+            Some(ref shell) if shell.pid.get().is_some() => (),
+            Some(shell) => shell.spawn(&dir),
+            None => Self::add_shell(term, &dir),
+        }
+    }
+
+    /// Starts a shell in `dir` in a new tab
+    fn add_shell(term: &Rc<Self>, dir: &Path) {
+        let shell = Rc::new(Shell::new());
+        let name = dir
+            .file_name()
+            .map_or_else(|| dir.to_string_lossy(), |name| name.to_string_lossy());
+        let label = Label::new(Some(name.as_ref()));
+        label.set_tooltip_text(Some(dir.to_string_lossy().as_ref()));
+        let page = term.notebook.append_page(&shell.root, Some(&label));
+        term.shells.borrow_mut().push(shell.clone());
+        term.notebook.set_show_tabs(term.shells.borrow().len() > 1);
+        term.notebook.set_current_page(Some(page));
+
+        let weak_term = Rc::downgrade(term);
+        let weak_shell = Rc::downgrade(&shell);
+        shell.terminal.connect_child_exited(move |_, status| {
+            debug!("{}: {}", gettext("Shell exited with status"), status);
+            if let (Some(term), Some(shell)) = (weak_term.upgrade(), weak_shell.upgrade()) {
+                term.shell_exited(&shell);
+            }
+        });
+        shell.spawn(dir);
+        shell.terminal.grab_focus();
+    }
+
+    /// Closes the tab of `shell` if there are others, otherwise it's started again once the panel
+    /// is shown again
+    fn shell_exited(&self, shell: &Rc<Shell>) {
+        shell.pid.set(None);
+        let mut shells = self.shells.borrow_mut();
+        if shells.len() > 1 {
+            if let Some(pos) = shells.iter().position(|s| Rc::ptr_eq(s, shell)) {
+                shells.remove(pos);
+                self.notebook.remove_page(Some(pos as u32));
+            }
+            self.notebook.set_show_tabs(shells.len() > 1);
+        } else {
+            shell.terminal.reset(true, true);
+        }
+    }
+
+    /// Changes to `dir`, starting a shell there if none is running, or in a new tab if a program
+    /// runs in the shell. The panel has to be shown by the caller.
+    pub fn open_in(term: &Rc<Self>, dir: &Path) {
+        match term.current() {
+            Some(ref shell) if shell.is_waiting() => {
+                shell.terminal.feed_child(&cd_command(dir));
+            }
+            Some(ref shell) if shell.pid.get().is_some() => Self::add_shell(term, dir),
+            _ => {
+                term.directory.replace(Some(dir.to_path_buf()));
+                if term.notebook.get_mapped() {
+                    Self::start(term);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn foreground() {
+        let stat = "4242 (bash) S 4200 4242 4242 34817 4242 4194560 2391 33016";
+        assert_eq!(is_foreground(stat), Some(true));
+        let stat = "4242 (my (odd) sh) S 4200 4242 4242 34817 4300 4194560 2391 33016";
+        assert_eq!(is_foreground(stat), Some(false));
+        assert_eq!(is_foreground("4242 (bash"), None);
+    }
+
+    #[test]
+    fn cd() {
+        assert_eq!(
+            cd_command(Path::new("/home/me/it's here")),
+            "cd '/home/me/it'\\''s here'\n"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Collects the notifications from xi-core gxi doesn't handle, with a few samples of their
+//! parameters. They can be exported from the menu and attached to bug reports, which tells us
+//! what gxi should support next.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// How many samples of the parameters we keep per method
+const MAX_SAMPLES: usize = 3;
+/// Longer samples (e.g. containing whole documents) are cut off after this many chars
+const MAX_SAMPLE_LEN: usize = 2000;
+
+#[derive(Default)]
+struct Method {
+    count: u64,
+    samples: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct UnhandledMsgs {
+    methods: BTreeMap<String, Method>,
+}
+
+impl UnhandledMsgs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+
+    /// Records that xi-core has sent the notification `method` with `params`, which gxi doesn't
+    /// handle
+    pub fn record(&mut self, method: &str, params: &Value) {
+        let entry = self.methods.entry(method.to_string()).or_default();
+        entry.count += 1;
+        if entry.samples.len() < MAX_SAMPLES {
+            let mut sample = params.to_string();
+            if sample.chars().count() > MAX_SAMPLE_LEN {
+                sample = sample.chars().take(MAX_SAMPLE_LEN).collect();
+                sample.push('…');
+            }
+            entry.samples.push(sample);
+        }
+    }
+
+    /// The report for a bug report, starting with the version of gxi
+    pub fn report(&self, version: &str) -> String {
+        let mut report = format!("gxi {}\n", version);
+        if self.is_empty() {
+            report.push_str("\nNo unhandled notifications\n");
+        }
+        for (method, entry) in &self.methods {
+            report.push_str(&format!("\n{} ({}x)\n", method, entry.count));
+            for sample in &entry.samples {
+                report.push_str(&format!("  {}\n", sample));
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn samples() {
+        let mut msgs = UnhandledMsgs::new();
+        assert!(msgs.is_empty());
+        for i in 0..5 {
+            msgs.record("show_hover", &json!({ "req_id": i }));
+        }
+        msgs.record("big", &json!({ "text": "a".repeat(5000) }));
+        let report = msgs.report("0.7.0");
+        assert!(report.starts_with("gxi 0.7.0\n"));
+        assert!(report
+            .contains("show_hover (5x)\n  {\"req_id\":0}\n  {\"req_id\":1}\n  {\"req_id\":2}\n"));
+        assert!(!report.contains("\"req_id\":3"));
+        assert!(report.len() < 2100 + 200);
+    }
+}
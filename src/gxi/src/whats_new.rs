@@ -0,0 +1,166 @@
+//! Shows what has changed since the last version of gxi the user has run, taken from the
+//! changelog bundled with gxi.
+
+use crate::globals;
+use gettextrs::gettext;
+use gtk::*;
+use gxi_config_storage::{GSchema, GSchemaExt};
+use log::debug;
+
+pub const CHANGELOG: &str = include_str!("../../../Changelog.md");
+
+/// The version of gxi that's running
+pub fn current_version() -> &'static str {
+    globals::VERSION.unwrap_or(env!("CARGO_PKG_VERSION"))
+}
+
+/// Strips the `v` and the git description from a version, e.g. `v0.7.0-12-g1234abc` is `0.7.0`
+pub fn normalize_version(version: &str) -> &str {
+    let version = version.trim().trim_start_matches('v');
+    version.split('-').next().unwrap_or(version)
+}
+
+/// The version a `## Changes in vX.Y.Z` heading is for
+fn heading_version(line: &str) -> Option<&str> {
+    if line.starts_with("## ") {
+        line.rsplit(' ').next().map(normalize_version)
+    } else {
+        None
+    }
+}
+
+/// The sections of `changelog` for the versions newer than `last_version`. If `last_version`
+/// isn't in the changelog, e.g. because it's a development version, only the latest section is
+/// returned.
+fn changes_since(changelog: &str, last_version: &str) -> String {
+    let last_version = normalize_version(last_version);
+    let mut sections: Vec<Vec<&str>> = Vec::new();
+    for line in changelog.lines() {
+        match heading_version(line) {
+            Some(version) if version == last_version => {
+                return sections
+                    .iter()
+                    .map(|section| section.join("\n"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            }
+            Some(_) => sections.push(vec![line]),
+            None => {
+                if let Some(section) = sections.last_mut() {
+                    section.push(line);
+                }
+            }
+        }
+    }
+    sections
+        .first()
+        .map(|section| section.join("\n"))
+        .unwrap_or_default()
+}
+
+/// Turns the (markdown) changelog into Pango markup
+fn markup(changelog: &str) -> String {
+    changelog
+        .lines()
+        .map(|line| {
+            let escaped = glib::markup_escape_text(line.trim()).to_string();
+            if line.starts_with("### ") {
+                format!("<b>{}</b>", &escaped[4..])
+            } else if line.starts_with("## ") {
+                format!("<big><b>{}</b></big>", &escaped[3..])
+            } else if line.trim_start().starts_with("- ") {
+                format!("  • {}", &escaped[2..])
+            } else {
+                escaped
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Shows `changelog` in a dialog with the title `title`
+pub fn show_dialog<P: IsA<Window>>(parent: &P, title: &str, changelog: &str) {
+    let dialog = Dialog::new();
+    dialog.set_title(title);
+    dialog.set_transient_for(Some(parent));
+    dialog.set_modal(true);
+    dialog.set_default_size(500, 450);
+    dialog.add_button(&gettext("Close"), ResponseType::Close);
+
+    let label = Label::new(None);
+    label.set_markup(&markup(changelog));
+    label.set_line_wrap(true);
+    label.set_selectable(true);
+    label.set_halign(Align::Start);
+    label.set_valign(Align::Start);
+    label.set_margin_start(12);
+    label.set_margin_end(12);
+    label.set_margin_top(12);
+    label.set_margin_bottom(12);
+    let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+    scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+    scrolled_window.set_vexpand(true);
+    scrolled_window.add(&label);
+    dialog
+        .get_content_area()
+        .pack_start(&scrolled_window, true, true, 0);
+    scrolled_window.show_all();
+
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.show();
+}
+
+/// Shows what's new if gxi has been updated since it was last run (and the user hasn't disabled
+/// that), then remembers the version that's running
+pub fn check(parent: &ApplicationWindow, gschema: &GSchema) {
+    let version = current_version();
+    let last_version: String = gschema.get_key("last-version");
+    if normalize_version(&last_version) == normalize_version(version) {
+        return;
+    }
+    debug!(
+        "{} {} {} {}",
+        gettext("Updated from"),
+        last_version,
+        gettext("to"),
+        version
+    );
+    // On the first start there's nothing new to the user
+    let show: bool = gschema.get_key("show-whats-new");
+    if show && !last_version.is_empty() {
+        let changes = changes_since(CHANGELOG, &last_version);
+        if !changes.is_empty() {
+            show_dialog(parent, &gettext("What's New"), &changes);
+        }
+    }
+    gschema
+        .set_key("last-version", version.to_string())
+        .unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOG: &str = "## Changes in v0.3.0\n\n - c\n\n## Changes in v0.2.0\n\n - b\n\n## Changes in v0.1.0\n\n - a\n";
+
+    #[test]
+    fn versions() {
+        assert_eq!(normalize_version("v0.7.0-12-g1234abc"), "0.7.0");
+        assert_eq!(heading_version("## Changes in v0.6.2"), Some("0.6.2"));
+        assert_eq!(heading_version("### Bugfixes"), None);
+    }
+
+    #[test]
+    fn changes() {
+        let since = changes_since(LOG, "0.1.0");
+        assert!(since.contains(" - c") && since.contains(" - b"));
+        assert!(!since.contains(" - a"));
+        // Unknown versions only get the latest changes
+        assert_eq!(
+            changes_since(LOG, "0.2.5"),
+            "## Changes in v0.3.0\n\n - c\n"
+        );
+        assert_eq!(changes_since(LOG, "v0.3.0"), "");
+    }
+}
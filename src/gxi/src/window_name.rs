@@ -0,0 +1,58 @@
+//! Windows can be given a name like "work" or "dotfiles", e.g. via `gxi --window work file.txt`.
+//! Each name has a session of its own and is part of the application id, so opening a file with
+//! the name of a window that's open already opens it in that window.
+
+/// Application id of gxi, which is also the id of its GSchema
+pub const APP_ID: &str = "com.github.Cogitri.gxi";
+
+/// Turns `name` into something which may be used as element of the application id (and file
+/// name), which may only contain ASCII letters, digits, `_` and `-` and mustn't start with a digit
+pub fn sanitize(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", sanitized)
+    } else {
+        sanitized
+    }
+}
+
+/// The application id of the window called `name` of the profile `profile`, under which it's
+/// registered on D-Bus
+pub fn application_id(profile: Option<&str>, name: Option<&str>) -> String {
+    let mut id = APP_ID.to_string();
+    if let Some(profile) = profile {
+        id.push_str(&format!(".profile-{}", sanitize(profile)));
+    }
+    if let Some(name) = name {
+        id.push_str(&format!(".{}", sanitize(name)));
+    }
+    id
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        assert_eq!(application_id(None, None), "com.github.Cogitri.gxi");
+        assert_eq!(
+            application_id(None, Some("my notes")),
+            "com.github.Cogitri.gxi.my_notes"
+        );
+        assert_eq!(
+            application_id(Some("writing"), Some("notes")),
+            "com.github.Cogitri.gxi.profile-writing.notes"
+        );
+        assert_eq!(sanitize("2019"), "_2019");
+    }
+}